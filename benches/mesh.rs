@@ -0,0 +1,56 @@
+//! Mesh generation benchmarks.
+//!
+//! Builds meshes of large synthetic grids on one thread and on the whole
+//! rayon pool, so the parallel speedup shows side by side:
+//!
+//! ```bash
+//! cargo bench --bench mesh
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use lrle::terrain::mesh::ShadingMode;
+use lrle::terrain::{MeshOptions, TerrainData, TerrainMesh};
+
+/// Rolling hills on a `size` x `size` grid.
+fn hills(size: usize) -> TerrainData {
+    let points = (0..size)
+        .map(|z| {
+            (0..size)
+                .map(|x| ((x as f64 * 0.05).sin() + (z as f64 * 0.03).cos()) * 20.0)
+                .collect()
+        })
+        .collect();
+    TerrainData::new(points, None)
+}
+
+fn mesh_build(c: &mut Criterion) {
+    // One thread against the whole pool, once on single-core machines
+    let mut pool_sizes = vec![1, rayon::current_num_threads()];
+    pool_sizes.dedup();
+    let mut group = c.benchmark_group("mesh_build");
+    group.sample_size(10);
+    for size in [512, 2048] {
+        let terrain = hills(size);
+        for shading_mode in [ShadingMode::Smooth, ShadingMode::Flat] {
+            let options = MeshOptions {
+                shading_mode,
+                ..MeshOptions::default()
+            };
+            for &pool_size in &pool_sizes {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(pool_size)
+                    .build()
+                    .unwrap();
+                let id = format!("{size}x{size}/{shading_mode:?}/{pool_size} threads");
+                group.bench_function(BenchmarkId::from_parameter(id), |b| {
+                    b.iter(|| pool.install(|| TerrainMesh::build(&terrain, &options)))
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, mesh_build);
+criterion_main!(benches);
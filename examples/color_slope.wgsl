@@ -0,0 +1,14 @@
+// Example color function for `lrle <file> --color-shader examples/color_slope.wgsl`
+//
+// Colors flat ground green and steep slopes grey-brown, with bands at a
+// height interval adjustable from the UI.
+
+// @param band_spacing 10.0 1.0 50.0 Band spacing
+fn user_color(height: f32, normal: vec3f) -> vec3f {
+    let steepness = 1.0 - normal.y;
+    let flat_color = vec3f(0.25, 0.6, 0.2);
+    let steep_color = vec3f(0.5, 0.42, 0.35);
+    let base = mix(flat_color, steep_color, smoothstep(0.1, 0.4, steepness));
+    let band = 0.9 + 0.1 * step(0.5, fract(height / param_band_spacing()));
+    return base * band;
+}
@@ -0,0 +1,264 @@
+//! User settings file.
+//!
+//! `config.toml` in `$XDG_CONFIG_HOME/lrle` (`~/.config/lrle` by default),
+//! or the file given with `--config`, holds the mouse sensitivities,
+//! inverted axes, key bindings and update check, read at startup and
+//! written back by "Save" under Input Settings:
+//!
+//! ```toml
+//! [mouse]
+//! rotate_sensitivity = 0.005
+//! pan_sensitivity = 0.1
+//! zoom_sensitivity = 0.1
+//! invert_x = false
+//! invert_y = true
+//! invert_zoom = false
+//!
+//! [keys]
+//! reset_camera = "R"
+//! toggle_projection = ["P", "I"]
+//! fly_forward = ["W", "Up"]
+//!
+//! [updates]
+//! check = "stable"
+//! ```
+//!
+//! Anything left out keeps its default. Keys are letters, digits, `F1` to
+//! `F12`, arrows (`Left`, `Right`, `Up`, `Down`) and named keys such as
+//! `Space` and `PageDown`; an empty list unbinds an action. The update
+//! check takes a channel, `"stable"` or `"prerelease"`, or `false`, the
+//! default, to not check; `--check-updates` overrides it.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use toml::{Table, Value};
+
+use crate::input::{key_from_name, key_name, Action, InputConfig};
+use crate::update::UpdateChannel;
+
+/// User settings with the file they're saved to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Sensitivities, axes and key bindings
+    pub input: InputConfig,
+    /// Releases to check for at startup, or `None` to not check
+    pub check_updates: Option<UpdateChannel>,
+    /// File the settings are saved to, if any
+    pub file: Option<PathBuf>,
+}
+
+impl Config {
+    /// `lrle/config.toml` in the user's config directory, if it has one.
+    pub fn default_file() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(dir.join("lrle").join("config.toml"))
+    }
+
+    /// Read the settings in `file`, or the defaults if it doesn't exist
+    /// yet. Later changes are saved to it.
+    pub fn load(file: PathBuf) -> Result<Self> {
+        let config = match std::fs::read_to_string(&file) {
+            Ok(text) => Self::from_toml(&text)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            file: Some(file),
+            ..config
+        })
+    }
+
+    /// Write the settings to their file, if they have one, creating its
+    /// directory.
+    pub fn save(&self) -> Result<()> {
+        if let Some(file) = &self.file {
+            if let Some(dir) = file.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(file, self.to_toml())?;
+        }
+        Ok(())
+    }
+
+    /// Serialize the settings the config file holds as TOML.
+    pub fn to_toml(&self) -> String {
+        let input = &self.input;
+        let mut text = String::from("[mouse]\n");
+        text += &format!("rotate_sensitivity = {}\n", input.rotate_sensitivity);
+        text += &format!("pan_sensitivity = {}\n", input.pan_sensitivity);
+        text += &format!("zoom_sensitivity = {}\n", input.zoom_sensitivity);
+        text += &format!("invert_x = {}\n", input.invert_x);
+        text += &format!("invert_y = {}\n", input.invert_y);
+        text += &format!("invert_zoom = {}\n", input.invert_zoom);
+        text += "\n[keys]\n";
+        for action in Action::ALL {
+            let keys: Vec<String> = input
+                .keys
+                .keys(action)
+                .iter()
+                .filter_map(|&key| key_name(key))
+                .map(|name| format!("\"{name}\""))
+                .collect();
+            text += &format!("{} = [{}]\n", action.name(), keys.join(", "));
+        }
+        text += "\n[updates]\n";
+        text += &match self.check_updates.and_then(|c| c.to_possible_value()) {
+            Some(channel) => format!("check = \"{}\"\n", channel.get_name()),
+            None => "check = false\n".to_string(),
+        };
+        text
+    }
+
+    /// Parse settings written by [`to_toml`](Self::to_toml) or by hand,
+    /// over the defaults, without a file.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let table: Table = text.parse()?;
+        let mut config = Self::default();
+        for (section, value) in &table {
+            let set = match section.as_str() {
+                "mouse" => set_mouse,
+                "keys" => set_keys,
+                "updates" => set_updates,
+                _ => bail!("Unknown section [{section}], expected [mouse], [keys] or [updates]"),
+            };
+            let entries = value
+                .as_table()
+                .ok_or_else(|| anyhow!("Expected [{section}] to be a table"))?;
+            for (key, value) in entries {
+                set(&mut config, key, value).with_context(|| format!("In [{section}] {key}"))?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Set mouse setting `key` of `config` to `value`.
+fn set_mouse(config: &mut Config, key: &str, value: &Value) -> Result<()> {
+    let input = &mut config.input;
+    let number = || {
+        value
+            .as_float()
+            .or_else(|| value.as_integer().map(|n| n as f64))
+            .filter(|&n| n > 0.0)
+            .map(|n| n as f32)
+            .ok_or_else(|| anyhow!("Expected a positive number"))
+    };
+    let flag = || {
+        value
+            .as_bool()
+            .ok_or_else(|| anyhow!("Expected true or false"))
+    };
+    match key {
+        "rotate_sensitivity" => input.rotate_sensitivity = number()?,
+        "pan_sensitivity" => input.pan_sensitivity = number()?,
+        "zoom_sensitivity" => input.zoom_sensitivity = number()?,
+        "invert_x" => input.invert_x = flag()?,
+        "invert_y" => input.invert_y = flag()?,
+        "invert_zoom" => input.invert_zoom = flag()?,
+        _ => bail!("Unknown setting"),
+    }
+    Ok(())
+}
+
+/// Bind the key or list of keys `value` to action `name` in `config`.
+fn set_keys(config: &mut Config, name: &str, value: &Value) -> Result<()> {
+    let action = Action::ALL
+        .into_iter()
+        .find(|action| action.name() == name)
+        .ok_or_else(|| anyhow!("Unknown action"))?;
+    let names = match value {
+        Value::String(name) => vec![name.as_str()],
+        Value::Array(names) => names
+            .iter()
+            .map(|name| name.as_str().ok_or_else(|| anyhow!("Expected key names")))
+            .collect::<Result<_>>()?,
+        _ => bail!("Expected a key name or a list of them"),
+    };
+    let keys = names
+        .into_iter()
+        .map(|name| key_from_name(name).ok_or_else(|| anyhow!("Unknown key '{name}'")))
+        .collect::<Result<_>>()?;
+    config.input.keys.set(action, keys);
+    Ok(())
+}
+
+/// Set update check setting `key` of `config` to `value`.
+fn set_updates(config: &mut Config, key: &str, value: &Value) -> Result<()> {
+    if key != "check" {
+        bail!("Unknown setting");
+    }
+    config.check_updates = match value {
+        Value::Boolean(false) => None,
+        Value::String(name) => Some(
+            UpdateChannel::from_str(name, true).map_err(|_| anyhow!("Unknown channel '{name}'"))?,
+        ),
+        _ => bail!("Expected \"stable\", \"prerelease\" or false"),
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::KeyCode;
+
+    #[test]
+    fn test_config_round_trips_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lrle").join("config.toml");
+
+        // No file yet: defaults
+        let mut config = Config::load(file.clone()).unwrap();
+        assert_eq!(config.input, InputConfig::default());
+        assert_eq!(config.check_updates, None);
+
+        config.input.rotate_sensitivity = 0.01;
+        config.input.invert_y = true;
+        config
+            .input
+            .keys
+            .set(Action::FrameTerrain, vec![KeyCode::KeyG, KeyCode::Home]);
+        config.input.keys.set(Action::Screenshot, Vec::new());
+        config.check_updates = Some(UpdateChannel::Prerelease);
+        config.save().unwrap();
+        assert_eq!(Config::load(file).unwrap(), config);
+    }
+
+    #[test]
+    fn test_hand_written_config_over_defaults() {
+        let config = Config::from_toml(
+            "[mouse]\npan_sensitivity = 1\ninvert_zoom = true\n\n[keys]\nfly_forward = \"up\"\n\n\
+             [updates]\ncheck = \"stable\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.check_updates, Some(UpdateChannel::Stable));
+        let input = config.input;
+        assert_eq!(input.pan_sensitivity, 1.0);
+        assert!(input.invert_zoom);
+        assert_eq!(input.keys.keys(Action::FlyForward), [KeyCode::ArrowUp]);
+        assert_eq!(input.keys.keys(Action::FlyBack), [KeyCode::KeyS]);
+
+        let err = |text: &str| format!("{:#}", Config::from_toml(text).unwrap_err());
+        assert_eq!(
+            err("[keys]\nreset_camera = \"Hyper\""),
+            "In [keys] reset_camera: Unknown key 'Hyper'"
+        );
+        assert_eq!(
+            err("[mouse]\nzoom_sensitivity = -1"),
+            "In [mouse] zoom_sensitivity: Expected a positive number"
+        );
+        assert_eq!(
+            err("[view]\nfov = 45"),
+            "Unknown section [view], expected [mouse], [keys] or [updates]"
+        );
+        assert_eq!(
+            err("[updates]\ncheck = \"nightly\""),
+            "In [updates] check: Unknown channel 'nightly'"
+        );
+    }
+}
@@ -0,0 +1,1765 @@
+//! Headless test harness for the application.
+//!
+//! Builds an [`App`] around an offscreen [`Renderer`] and feeds it
+//! synthetic window events the way winit would, so tests can assert on the
+//! camera state and rendered frames that result. Tests using it are skipped
+//! when no GPU adapter, not even a software one, is available.
+
+use clap::Parser;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{
+    DeviceId, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
+};
+use winit::keyboard::KeyCode;
+
+use crate::{App, Args};
+use lrle::renderer::camera::Camera;
+use lrle::renderer::Renderer;
+use lrle::terrain::TerrainData;
+
+/// An application with an offscreen renderer and no window.
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    /// Start the application on `terrain`, rendering `width` x `height`
+    /// frames, with the command-line options in `args` (after the file).
+    ///
+    /// Returns `None` if no GPU adapter is available.
+    pub fn new(terrain: TerrainData, size: (u32, u32), args: &[&str]) -> Option<Self> {
+        let args = Args::parse_from(["lrle", "terrain.fdf"].iter().chain(args));
+        Self::start(App::new(Some(terrain), &args), size)
+    }
+
+    /// Attach a renderer of `width` x `height` frames to `app`, set up
+    /// like at startup.
+    ///
+    /// Returns `None` if no GPU adapter is available.
+    pub fn start(mut app: App, (width, height): (u32, u32)) -> Option<Self> {
+        let renderer = match pollster::block_on(Renderer::new_headless(width, height)) {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                eprintln!("Skipping headless test: {}", e);
+                return None;
+            }
+        };
+        app.attach_renderer(renderer);
+        Some(Self { app })
+    }
+
+    /// Deliver a window event. Returns `false` if the app asked to exit.
+    pub fn send(&mut self, event: WindowEvent) -> bool {
+        self.app.handle_event(event)
+    }
+
+    /// Press and release `key`. Returns `false` if the app asked to exit.
+    pub fn press(&mut self, key: KeyCode) -> bool {
+        self.app.handle_key(key, ElementState::Pressed)
+            && self.app.handle_key(key, ElementState::Released)
+    }
+
+    /// Drag the mouse with `button` held from `from` to `to`, in steps.
+    pub fn drag(&mut self, button: MouseButton, from: (f64, f64), to: (f64, f64)) {
+        const STEPS: usize = 8;
+        self.send(cursor_moved(from));
+        self.send(mouse_input(button, ElementState::Pressed));
+        for i in 1..=STEPS {
+            let t = i as f64 / STEPS as f64;
+            self.send(cursor_moved((
+                from.0 + (to.0 - from.0) * t,
+                from.1 + (to.1 - from.1) * t,
+            )));
+        }
+        self.send(mouse_input(button, ElementState::Released));
+    }
+
+    /// Press and release `button` at `at`.
+    pub fn click(&mut self, button: MouseButton, at: (f64, f64)) {
+        self.send(cursor_moved(at));
+        self.send(mouse_input(button, ElementState::Pressed));
+        self.send(mouse_input(button, ElementState::Released));
+    }
+
+    /// Scroll the mouse wheel by `lines` (positive zooms in).
+    pub fn scroll(&mut self, lines: f32) {
+        self.send(WindowEvent::MouseWheel {
+            device_id: device_id(),
+            delta: MouseScrollDelta::LineDelta(0.0, lines),
+            phase: TouchPhase::Moved,
+        });
+    }
+
+    /// Resize the (virtual) window.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.send(WindowEvent::Resized(PhysicalSize::new(width, height)));
+    }
+
+    /// Render a frame and read it back as RGBA texels, top row first.
+    pub fn frame(&mut self) -> Vec<[u8; 4]> {
+        assert!(self.send(WindowEvent::RedrawRequested));
+        self.renderer().read_pixels().expect("offscreen renderer")
+    }
+
+    /// The application's renderer.
+    pub fn renderer(&mut self) -> &mut Renderer {
+        self.app.renderer.as_mut().expect("renderer attached")
+    }
+
+    /// The main camera.
+    pub fn camera(&self) -> &Camera {
+        &self
+            .app
+            .renderer
+            .as_ref()
+            .expect("renderer attached")
+            .camera
+    }
+}
+
+fn device_id() -> DeviceId {
+    DeviceId::dummy()
+}
+
+fn cursor_moved((x, y): (f64, f64)) -> WindowEvent {
+    WindowEvent::CursorMoved {
+        device_id: device_id(),
+        position: PhysicalPosition::new(x, y),
+    }
+}
+
+fn mouse_input(button: MouseButton, state: ElementState) -> WindowEvent {
+    WindowEvent::MouseInput {
+        device_id: device_id(),
+        state,
+        button,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    use glam::{DVec2, DVec3};
+    use lrle::renderer::bookmarks::Bookmarks;
+    use lrle::renderer::camera::CameraMode;
+    use lrle::renderer::camera_path::Keyframe;
+    use lrle::renderer::load_failure::{LoadFailure, LoadTarget};
+    use lrle::renderer::{
+        perf_log, DepthMode, FogConfig, MotionConfig, Projection, RenderMode, TimelineConfig, Tool,
+    };
+    use lrle::terrain::annotations::{self, Annotation};
+    use lrle::terrain::uncertainty::{UncertaintyConfig, UncertaintyStyle};
+    use lrle::terrain::{ColorMode, ColorScheme, Heightfield};
+
+    /// A small pyramid, so frames show lit slopes in every direction.
+    fn pyramid() -> TerrainData {
+        let points = (0..16)
+            .map(|z: i32| {
+                (0..16)
+                    .map(|x: i32| (8 - (x - 8).abs().max((z - 8).abs())) as f64)
+                    .collect()
+            })
+            .collect();
+        TerrainData::new(points, None)
+    }
+
+    /// Harness with the UI panel hidden, so frames show only the terrain.
+    fn harness() -> Option<Harness> {
+        let mut harness = Harness::new(pyramid(), (96, 64), &[])?;
+        harness.renderer().ui.panel_visible = false;
+        Some(harness)
+    }
+
+    #[test]
+    fn test_drag_rotates_camera() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let azimuth = harness.camera().azimuth;
+        harness.drag(MouseButton::Left, (10.0, 10.0), (60.0, 10.0));
+        assert!(harness.camera().azimuth < azimuth);
+
+        // Moving without a button held leaves the camera alone
+        let azimuth = harness.camera().azimuth;
+        harness.send(cursor_moved((0.0, 0.0)));
+        assert_eq!(harness.camera().azimuth, azimuth);
+    }
+
+    #[test]
+    fn test_shift_drag_pans_camera() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness
+            .app
+            .handle_key(KeyCode::ShiftLeft, ElementState::Pressed);
+        harness.drag(MouseButton::Left, (10.0, 10.0), (60.0, 30.0));
+        harness
+            .app
+            .handle_key(KeyCode::ShiftLeft, ElementState::Released);
+        assert_ne!(harness.camera().target, Camera::new().target);
+    }
+
+    #[test]
+    fn test_scroll_zooms_camera() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let distance = harness.camera().distance;
+        harness.scroll(2.0);
+        assert!(harness.camera().distance < distance);
+    }
+
+    #[test]
+    fn test_keys_toggle_projection_and_reset() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        // The terrain starts framed, far closer than the default camera
+        let (framed, center) = (harness.camera().distance, harness.camera().target);
+        assert!(framed < Camera::new().distance / 2.0);
+        assert!(harness.press(KeyCode::KeyP));
+        assert_eq!(harness.camera().projection, Projection::Orthographic);
+
+        harness.scroll(3.0);
+        assert!(harness.press(KeyCode::KeyR));
+        assert_eq!(harness.camera().distance, framed);
+        assert_eq!(harness.camera().projection, Projection::Perspective);
+
+        // F frames it again from wherever the camera is
+        harness.scroll(-5.0);
+        harness.renderer().camera.target.x += 10.0;
+        assert!(harness.press(KeyCode::KeyF));
+        assert_eq!(harness.camera().distance, framed);
+        assert_eq!(harness.camera().target, center);
+
+        // Escape and the close button quit
+        assert!(!harness.press(KeyCode::Escape));
+        assert!(!harness.send(WindowEvent::CloseRequested));
+    }
+
+    #[test]
+    fn test_frame_shows_terrain() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let frame = harness.frame();
+        assert_eq!(frame.len(), 96 * 64);
+
+        // The camera looks at the pyramid: the center differs from the
+        // background in the corner
+        let background = frame[0];
+        let center = frame[32 * 96 + 48];
+        assert_ne!(center, background);
+
+        // Rotating the camera changes what's drawn
+        harness.drag(MouseButton::Left, (10.0, 10.0), (70.0, 40.0));
+        assert_ne!(harness.frame(), frame);
+    }
+
+    #[test]
+    fn test_turntable_capture_restores_view() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let config = lrle::renderer::TurntableConfig {
+            frames: 4,
+            width: 32,
+            height: 24,
+            ..Default::default()
+        };
+        let frames = harness.renderer().capture_turntable(&config).unwrap();
+        assert_eq!(frames.len(), 4);
+        assert!(frames.iter().all(|frame| frame.len() == 32 * 24));
+        // A quarter turn shows the pyramid's faces under different light
+        assert_ne!(frames[0], frames[1]);
+
+        // The window's size and camera are back
+        let azimuth = harness.camera().azimuth;
+        assert_eq!(harness.frame().len(), 96 * 64);
+        assert_eq!(harness.camera().azimuth, azimuth);
+    }
+
+    #[test]
+    fn test_mode_flag_sets_render_mode() {
+        let Some(mut harness) = Harness::new(pyramid(), (32, 24), &["--mode", "wireframe"]) else {
+            return;
+        };
+        assert_eq!(
+            harness.renderer().settings.render_mode,
+            RenderMode::Wireframe
+        );
+        assert_eq!(harness.frame().len(), 32 * 24);
+    }
+
+    #[test]
+    fn test_depth_buffer_follows_resize() {
+        let Some(mut harness) = Harness::new(pyramid(), (64, 48), &["--mode", "both"]) else {
+            return;
+        };
+        // Every pass, the UI's included, attaches the depth buffer, which
+        // fails validation unless it's resized with the color target
+        harness.resize(80, 40);
+        assert_eq!(harness.frame().len(), 80 * 40);
+        harness.resize(48, 72);
+        assert_eq!(harness.frame().len(), 48 * 72);
+    }
+
+    #[test]
+    fn test_msaa_renders_and_follows_resize() {
+        let Some(mut harness) =
+            Harness::new(pyramid(), (64, 48), &["--mode", "both", "--msaa", "4"])
+        else {
+            return;
+        };
+        let frame = harness.frame();
+        // Falls back to single sampling where 4x is unsupported
+        let samples = harness.renderer().settings.msaa;
+        assert!(harness.renderer().msaa.counts().contains(&samples));
+        assert_eq!(harness.renderer().msaa.samples(), samples);
+        assert!(frame.iter().any(|&pixel| pixel != frame[0]));
+
+        harness.resize(80, 40);
+        assert_eq!(harness.frame().len(), 80 * 40);
+    }
+
+    #[test]
+    fn test_warmup_builds_variants_before_switching() {
+        let Some(mut harness) = Harness::new(pyramid(), (64, 48), &["--mode", "both"]) else {
+            return;
+        };
+        harness.frame();
+        harness.renderer().warm_pipelines();
+        let variants = harness.renderer().msaa.counts().len() * 3 - 1;
+        for _ in 0..variants {
+            assert!(harness.renderer().is_warming_up());
+            harness.frame();
+        }
+        assert!(!harness.renderer().is_warming_up());
+
+        let samples = *harness.renderer().msaa.counts().last().unwrap();
+        harness.renderer().settings.msaa = samples;
+        harness.renderer().settings.depth_mode = DepthMode::Logarithmic;
+        let frame = harness.frame();
+        assert_eq!(harness.renderer().msaa.samples(), samples);
+        assert!(frame.iter().any(|&pixel| pixel != frame[0]));
+    }
+
+    #[test]
+    fn test_ui_scale_resizes_panel() {
+        let Some(mut harness) = Harness::new(pyramid(), (96, 64), &[]) else {
+            return;
+        };
+        let frame = harness.frame();
+        harness.renderer().settings.ui_scale = 2.0;
+        // The new zoom factor takes effect on the next egui pass
+        harness.frame();
+        assert_ne!(harness.frame(), frame);
+
+        harness.renderer().settings.ui_scale = 1.0;
+        harness.frame();
+        assert_eq!(harness.frame(), frame);
+    }
+
+    #[test]
+    fn test_background_lines_and_bloom() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.render_mode = RenderMode::Wireframe;
+        harness.renderer().settings.sky.enabled = false;
+        harness.renderer().settings.background = [0.0; 3];
+        let frame = harness.frame();
+        // The corner shows the background
+        assert_eq!(frame[0], [0, 0, 0, 255]);
+
+        // Dimmed lines are never brighter
+        harness.renderer().settings.line_brightness = 0.3;
+        let dimmed = harness.frame();
+        assert_ne!(dimmed, frame);
+        assert!(dimmed
+            .iter()
+            .zip(&frame)
+            .all(|(a, b)| a[..3].iter().zip(&b[..3]).all(|(a, b)| a <= b)));
+
+        // Bloom spreads light onto the background
+        harness.renderer().settings.bloom.enabled = true;
+        harness.renderer().settings.bloom.threshold = 0.0;
+        let glowing = harness.frame();
+        let lit = |frame: &[[u8; 4]]| frame.iter().filter(|pixel| pixel[..3] != [0, 0, 0]).count();
+        assert!(lit(&glowing) > lit(&dimmed));
+    }
+
+    #[test]
+    fn test_sky_and_fog() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.background = [0.0; 3];
+        let clear = {
+            harness.renderer().settings.sky.enabled = false;
+            harness.frame()
+        };
+        // The sky replaces the background color
+        harness.renderer().settings.sky.enabled = true;
+        let sky = harness.frame();
+        assert_eq!(clear[0], [0, 0, 0, 255]);
+        assert_ne!(sky[0], clear[0]);
+
+        // Thick red fog reddens the terrain but not the sky
+        let red = |frame: &[[u8; 4]]| {
+            frame
+                .iter()
+                .filter(|p| p[0] > p[1].saturating_add(64))
+                .count()
+        };
+        harness.renderer().settings.fog = FogConfig {
+            enabled: true,
+            density: 10.0,
+            color: [1.0, 0.0, 0.0],
+        };
+        let fogged = harness.frame();
+        assert_eq!(fogged[0], sky[0]);
+        assert!(red(&fogged) > red(&sky));
+    }
+
+    #[test]
+    fn test_terrain_layers() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+        let single = harness.frame();
+
+        // A flat plane raised above the pyramid hides part of it
+        let flat = TerrainData::new(vec![vec![0.0; 16]; 16], None);
+        harness.renderer().add_terrain("flat.fdf", flat);
+        harness.renderer().settings.layers[0].offset = [0.0, 12.0, 0.0];
+        let layered = harness.frame();
+        assert_ne!(layered, single);
+
+        // Hidden, it's gone
+        harness.renderer().settings.layers[0].visible = false;
+        assert_eq!(harness.frame(), single);
+
+        // Switching swaps it with the main terrain, which keeps its place
+        harness.renderer().make_main(0);
+        let renderer = harness.renderer();
+        assert_eq!(renderer.terrain_name, "flat.fdf");
+        assert_eq!(renderer.settings.layers[0].name, "terrain.fdf");
+        assert_eq!(renderer.settings.layers[0].offset, [0.0; 3]);
+        renderer.settings.layers[0].visible = true;
+        assert_ne!(harness.frame(), single);
+    }
+
+    #[test]
+    fn test_stitch_seams() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+
+        // A tile east of the pyramid sharing its last column, a little off
+        let mut tile = TerrainData::new(vec![vec![0.5; 16]; 16], None);
+        tile.origin.x = 15.0;
+        harness.renderer().add_terrain("east.fdf", tile);
+        harness.renderer().frame_scene();
+        let cracked = harness.frame();
+
+        harness.renderer().settings.stitch.enabled = true;
+        let stitched = harness.frame();
+        assert_ne!(stitched, cracked);
+
+        // Beyond the tolerance, the seam stays as it is
+        harness.renderer().settings.stitch.tolerance = 0.1;
+        assert_eq!(harness.frame(), cracked);
+    }
+
+    #[test]
+    fn test_ground_grid_and_axes_gizmo() {
+        let Some(mut harness) = Harness::new(pyramid(), (240, 160), &[]) else {
+            return;
+        };
+        harness.renderer().ui.panel_visible = false;
+        harness.renderer().settings.motion.enabled = false;
+        let plain = harness.frame();
+
+        harness.renderer().settings.ground_grid = true;
+        let grid = harness.frame();
+        assert_ne!(grid, plain);
+
+        // The gizmo only covers the bottom-left corner
+        harness.renderer().settings.ground_grid = false;
+        harness.renderer().settings.axes_gizmo = true;
+        let gizmo = harness.frame();
+        let changed = |x: usize, y: usize| gizmo[y * 240 + x] != plain[y * 240 + x];
+        assert!((80..160).any(|y| (0..120).any(|x| changed(x, y))));
+        assert!(!(0..60).any(|y| (0..240).any(|x| changed(x, y))));
+        assert!(!(0..160).any(|y| (140..240).any(|x| changed(x, y))));
+    }
+
+    #[test]
+    fn test_power_saving_waits_for_input() {
+        let Some(mut harness) = Harness::new(pyramid(), (96, 64), &["--max-fps", "30"]) else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+        let start = Instant::now();
+        harness.frame();
+        // Capped, the next frame follows a thirtieth of a second later
+        let due = harness.app.scheduler.due().unwrap();
+        assert!(due >= start + Duration::from_millis(33));
+
+        // Idle with power saving, frames wait for input
+        harness.renderer().settings.pacing.power_saving = true;
+        harness.frame();
+        assert_eq!(harness.app.scheduler.due(), None);
+        harness.scroll(1.0);
+        assert!(harness.app.scheduler.due().is_some());
+
+        // But keep coming while something moves
+        harness.renderer().settings.auto_rotate.enabled = true;
+        harness.frame();
+        harness.frame();
+        assert!(harness.app.scheduler.due().is_some());
+    }
+
+    #[test]
+    fn test_right_click_delineates_catchment() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        // Without a tool, right-clicks do nothing
+        harness.click(MouseButton::Right, (48.0, 32.0));
+        assert!(harness.renderer().catchment.is_none());
+
+        // The screen center looks at the pyramid's face, above the ground
+        let point = harness.renderer().pick((48.0, 32.0)).unwrap().position;
+        assert!(point.y > 0.0);
+
+        harness.renderer().settings.tool = Tool::Catchment;
+        let frame = harness.frame();
+        harness.click(MouseButton::Right, (48.0, 32.0));
+        let catchment = harness.renderer().catchment.clone().unwrap();
+        assert!(catchment.samples.contains(&catchment.outlet));
+        assert_eq!(catchment.area, catchment.samples.len() as f64);
+        // The outline is drawn over the terrain
+        assert_ne!(harness.frame(), frame);
+    }
+
+    #[test]
+    fn test_swipe_splits_snapshot_and_current() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let solid = harness.frame();
+        harness.renderer().take_compare_snapshot();
+        harness.renderer().settings.render_mode = RenderMode::Wireframe;
+        harness.renderer().settings.compare.divider = 0.25;
+        let swiped = harness.frame();
+
+        harness.renderer().settings.compare.enabled = false;
+        let wireframe = harness.frame();
+        assert_ne!(solid, wireframe);
+
+        // The snapshot left of the divider, 24 of 96 columns
+        for (i, pixel) in swiped.iter().enumerate() {
+            let expected = if i % 96 < 24 { solid[i] } else { wireframe[i] };
+            assert_eq!(*pixel, expected, "pixel {i}");
+        }
+    }
+
+    #[test]
+    fn test_timeline_drives_orbit_and_sun() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.frame();
+        let azimuth = harness.camera().azimuth;
+        let renderer = harness.renderer();
+        renderer.settings.sun.enabled = true;
+        renderer.settings.timeline = TimelineConfig {
+            playing: true,
+            sun: true,
+            orbit: true,
+            time: 6.0,
+            ..TimelineConfig::default()
+        };
+
+        // Captures hold the clock still
+        renderer.capture_frame().unwrap();
+        assert_eq!(renderer.settings.timeline.time, 6.0);
+        assert_eq!(renderer.camera.azimuth, azimuth);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        harness.frame();
+        let renderer = harness.renderer();
+        let time = renderer.settings.timeline.time;
+        assert!(time > 6.0);
+        assert!((renderer.settings.sun.hour - time).abs() < 1e-4);
+        let turned = renderer.camera.azimuth - azimuth;
+        assert!((turned - (time - 6.0) / 24.0 * std::f32::consts::TAU).abs() < 1e-4);
+
+        // Paused, the tracks stay put
+        renderer.settings.timeline.playing = false;
+        harness.frame();
+        assert_eq!(harness.renderer().settings.timeline.time, time);
+    }
+
+    #[test]
+    fn test_camera_path_round_trips_and_plays() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let renderer = harness.renderer();
+        renderer.add_camera_keyframe();
+        renderer.settings.timeline.time = 10.0;
+        renderer.camera.azimuth += 1.0;
+        renderer.camera.distance = 30.0;
+        renderer.add_camera_keyframe();
+        let start = renderer.camera_path.keyframes[0];
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("path.json");
+        renderer.export_camera_path(&file);
+        renderer.camera_path = Default::default();
+        renderer.settings.timeline.duration = 5.0;
+        renderer.import_camera_path(&file).unwrap();
+        assert_eq!(renderer.camera_path.keyframes.len(), 2);
+        assert_eq!(renderer.settings.timeline.duration, 10.0);
+
+        // Halfway along the path
+        renderer.settings.timeline.time = 5.0;
+        renderer.settings.timeline.path = true;
+        harness.frame();
+        let camera = harness.camera();
+        assert!((camera.azimuth - (start.azimuth + 0.5)).abs() < 1e-4);
+        assert!((camera.distance - (start.distance + 30.0) / 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_recording_and_auto_rotate() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        // Recording replaces the keyframes after the timeline's time
+        let renderer = harness.renderer();
+        renderer.settings.timeline.time = 2.0;
+        renderer.add_camera_keyframe();
+        renderer.settings.timeline.time = 1.0;
+        renderer.toggle_recording();
+        assert!(renderer.camera_path.keyframes.is_empty());
+        harness.frame();
+        harness.renderer().camera.distance = 30.0;
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        harness.frame();
+        harness.renderer().toggle_recording();
+
+        // Ready to play back from the start of the recording
+        let renderer = harness.renderer();
+        let keyframes = &renderer.camera_path.keyframes;
+        assert_eq!(keyframes.first().unwrap().time, 1.0);
+        assert!(keyframes.last().unwrap().time > 1.0);
+        assert_eq!(keyframes.last().unwrap().distance, 30.0);
+        assert_eq!(renderer.settings.timeline.time, 1.0);
+        assert!(renderer.settings.timeline.path);
+        renderer.settings.timeline.path = false;
+
+        // T turns the view until pressed again
+        harness.press(KeyCode::KeyT);
+        let azimuth = harness.camera().azimuth;
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        harness.frame();
+        assert!(harness.camera().azimuth > azimuth);
+        harness.press(KeyCode::KeyT);
+        harness.frame();
+        let azimuth = harness.camera().azimuth;
+        harness.frame();
+        assert_eq!(harness.camera().azimuth, azimuth);
+    }
+
+    #[test]
+    fn test_export_current_mesh() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.height_scale = 2.0;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("terrain.obj");
+        let job = harness.renderer().export_mesh(path.clone(), None).unwrap();
+        harness.renderer().wait_for_job(job).unwrap();
+
+        // Every sample, at the current height scale
+        let heights = |path: &std::path::Path| -> Vec<f32> {
+            std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("v "))
+                .map(|line| line.split(' ').nth(2).unwrap().parse().unwrap())
+                .collect()
+        };
+        let full = heights(&path);
+        assert_eq!(full.len(), 16 * 16);
+        assert_eq!(full.iter().copied().fold(0.0, f32::max), 16.0);
+
+        // Simplified, the pyramid keeps its peak
+        let job = harness
+            .renderer()
+            .export_mesh(path.clone(), Some(50))
+            .unwrap();
+        harness.renderer().wait_for_job(job).unwrap();
+        let simplified = heights(&path);
+        assert!(simplified.len() < full.len());
+        assert_eq!(simplified.iter().copied().fold(0.0, f32::max), 16.0);
+        let faces = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with("f "))
+            .count();
+        assert!(faces <= 50);
+
+        let job = harness
+            .renderer()
+            .export_mesh(dir.path().join("terrain.ply"), None)
+            .unwrap();
+        assert!(harness.renderer().wait_for_job(job).is_err());
+    }
+
+    #[test]
+    fn test_export_color_map() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("colors.png");
+        let job = harness.renderer().export_color_map(path.clone()).unwrap();
+        harness.renderer().wait_for_job(job).unwrap();
+
+        // One pixel per sample, placed by the world file beside it
+        let decoder =
+            png::Decoder::new(std::io::BufReader::new(std::fs::File::open(&path).unwrap()));
+        let reader = decoder.read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (16, 16));
+        let world = std::fs::read_to_string(dir.path().join("colors.pgw")).unwrap();
+        assert_eq!(world.lines().count(), 6);
+
+        let job = harness
+            .renderer()
+            .export_color_map(dir.path().join("colors.jpg"))
+            .unwrap();
+        assert!(harness.renderer().wait_for_job(job).is_err());
+    }
+
+    #[test]
+    fn test_command_line_colors_match_viewer() {
+        let args = ["lrle", "dem.tif", "--export-colors", "colors.png"];
+        let with = |flag: &'static str| args.into_iter().chain([flag, "other.png"]);
+        assert!(Args::try_parse_from(with("--texture")).is_err());
+        assert!(Args::try_parse_from(with("--uncertainty")).is_err());
+
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        // A tilted plane to measure the pyramid's relief from
+        let points = (0..16)
+            .map(|_| (0..16).map(|x| x as f64 * 0.5).collect())
+            .collect();
+        let reference = TerrainData::new(points, None);
+        let dir = tempfile::tempdir().unwrap();
+        let args = Args::parse_from(["lrle", "terrain.fdf"]);
+        let written = |name: &str, reference: Option<&TerrainData>| {
+            let path = dir.path().join(name);
+            crate::export_colors(&pyramid(), &args, reference, &path).unwrap();
+            std::fs::read(path).unwrap()
+        };
+        let absolute = written("absolute.png", None);
+        let relative = written("relative.png", Some(&reference));
+        assert_ne!(absolute, relative);
+
+        harness.renderer().set_reference(reference.clone());
+        let path = dir.path().join("viewer.png");
+        let job = harness.renderer().export_color_map(path.clone()).unwrap();
+        harness.renderer().wait_for_job(job).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), relative);
+    }
+
+    #[test]
+    fn test_level_of_detail_leaves_no_cracks() {
+        // A slope over four chunks, seen from far enough to coarsen them
+        let points = (0..129)
+            .map(|z| (0..129).map(|x| x as f64 * 0.2 + z as f64 * 0.1).collect())
+            .collect();
+        let Some(mut harness) = Harness::new(TerrainData::new(points, None), (96, 64), &[]) else {
+            return;
+        };
+        harness.renderer().ui.panel_visible = false;
+        harness.renderer().frame_scene();
+        let full = harness.frame();
+        harness.renderer().settings.render_mode = RenderMode::Wireframe;
+        let full_lines = harness.frame();
+
+        let renderer = harness.renderer();
+        renderer.settings.lod.enabled = true;
+        renderer.settings.lod.distance = 0.25;
+        let lines = harness.frame();
+        harness.renderer().settings.render_mode = RenderMode::Solid;
+        let coarse = harness.frame();
+
+        // Fewer lines, but the surface covers the same pixels
+        let background = full[0];
+        let drawn = |frame: &[[u8; 4]]| frame.iter().filter(|&&p| p != background).count();
+        assert!(drawn(&lines) < drawn(&full_lines));
+        for (i, (a, b)) in full.iter().zip(&coarse).enumerate() {
+            assert_eq!(*a == background, *b == background, "pixel {i}");
+        }
+    }
+
+    #[test]
+    fn test_presentation_hides_ui_and_steps_keyframes() {
+        assert!(Args::try_parse_from(["lrle", "terrain.fdf", "--present", "1920"]).is_err());
+        let Some(mut harness) = Harness::new(pyramid(), (96, 64), &["--present", "1280x720"])
+        else {
+            return;
+        };
+
+        // No panel drawn, though it's still switched on
+        let renderer = harness.renderer();
+        assert!(renderer.presenting && renderer.ui.panel_visible);
+        let clean = renderer.capture_frame().unwrap();
+        assert_eq!(harness.frame(), clean);
+        harness.press(KeyCode::F5);
+        assert_ne!(harness.frame(), clean);
+        harness.press(KeyCode::F5);
+        assert_eq!(harness.frame(), clean);
+
+        // Keyframes 0, 5 and 10 seconds in, each turned a little further
+        let renderer = harness.renderer();
+        let azimuth = renderer.camera.azimuth;
+        for i in 0..3 {
+            renderer.settings.timeline.time = i as f32 * 5.0;
+            renderer.camera.azimuth = azimuth + i as f32;
+            renderer.add_camera_keyframe();
+        }
+        harness.press(KeyCode::Digit2);
+        assert_eq!(harness.camera().azimuth, azimuth + 1.0);
+        assert_eq!(harness.renderer().settings.timeline.time, 5.0);
+
+        // Stepping stops at the ends
+        harness.press(KeyCode::PageDown);
+        harness.press(KeyCode::PageDown);
+        assert_eq!(harness.camera().azimuth, azimuth + 2.0);
+        harness.press(KeyCode::PageUp);
+        harness.press(KeyCode::ArrowLeft);
+        harness.press(KeyCode::ArrowLeft);
+        assert_eq!(harness.camera().azimuth, azimuth);
+        harness.press(KeyCode::Digit9);
+        assert_eq!(harness.camera().azimuth, azimuth);
+
+        harness.press(KeyCode::Space);
+        assert!(harness.renderer().settings.timeline.playing);
+    }
+
+    #[test]
+    fn test_bookmarks_recall_views_by_number() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("pyramid.fdf.views.json");
+        let renderer = harness.renderer();
+        renderer.load_bookmarks(file.clone()).unwrap();
+        renderer.add_camera_keyframe();
+        renderer.camera.azimuth = 1.0;
+        renderer.save_bookmark("East");
+        renderer.camera.azimuth = 2.0;
+        renderer.save_bookmark("South");
+        assert!(file.exists());
+
+        // With bookmarks, number keys recall them instead of keyframes
+        harness.press(KeyCode::Digit1);
+        assert_eq!(harness.camera().azimuth, 1.0);
+        harness.press(KeyCode::Digit2);
+        assert_eq!(harness.camera().azimuth, 2.0);
+
+        // Deleting saves the rest, which the next session reads back
+        harness.renderer().delete_bookmark(0);
+        harness.renderer().load_bookmarks(file).unwrap();
+        let views = &harness.renderer().bookmarks.views;
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].name, "South");
+    }
+
+    #[test]
+    fn test_bookmark_frustum_shows_its_view() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("pyramid.fdf.views.json");
+        harness.renderer().load_bookmarks(file).unwrap();
+        let aspect = 96.0 / 64.0;
+        harness.renderer().camera.azimuth = 1.0;
+        harness.renderer().save_bookmark("East");
+        let seen = harness.camera().frustum_corners(aspect);
+
+        // Drawn from elsewhere, where the bookmarked camera was, through
+        // the lens it had then
+        let renderer = harness.renderer();
+        renderer.camera.azimuth = 2.0;
+        renderer.camera.far = renderer.camera.near * 2.0;
+        assert!(renderer.show_bookmark_frustum(0, 1.0));
+        let corners = harness.renderer().captured_frustum.unwrap();
+        for (corner, seen) in corners.iter().zip(seen) {
+            assert!(corner.distance(seen) < 1e-3);
+        }
+        assert_eq!(harness.camera().azimuth, 2.0);
+        harness.frame();
+        assert!(!harness.renderer().show_bookmark_frustum(1, aspect));
+    }
+
+    #[test]
+    fn test_session_restores_view_unless_overridden() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let renderer = harness.renderer();
+        renderer.camera.azimuth = 1.0;
+        renderer.settings.render_mode = RenderMode::Wireframe;
+        renderer.settings.color_scheme = ColorScheme::Neon;
+        let session = harness.app.session().unwrap();
+        assert_eq!(session.window, Some((96, 64)));
+
+        // Restored on the next launch, except for the mode given there
+        let args = Args::parse_from(["lrle", "terrain.fdf", "--mode", "both"]);
+        let mut app = App::new(Some(pyramid()), &args);
+        app.restore_session(&session, |id| id == "mode");
+        assert_eq!(app.window_size, Some((96, 64)));
+        let mut restored = Harness::start(app, (96, 64)).unwrap();
+        assert_eq!(restored.camera().azimuth, 1.0);
+        let settings = &restored.renderer().settings;
+        assert_eq!(settings.render_mode, RenderMode::Both);
+        assert_eq!(settings.color_scheme, ColorScheme::Neon);
+    }
+
+    #[test]
+    fn test_raster_calculator_colors_by_result() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let renderer = harness.renderer();
+        renderer.run_algebra("double = height * 2").unwrap();
+        renderer
+            .run_algebra("diff = (double - height).abs()")
+            .unwrap();
+        assert_eq!(renderer.settings.color_mode, ColorMode::Channel(1));
+        harness.frame();
+
+        let err = harness.renderer().run_algebra("x = rain").unwrap_err();
+        assert!(err.to_string().contains("layers: height, double, diff"));
+    }
+
+    #[test]
+    fn test_jobs_run_past_failures() {
+        if pollster::block_on(Renderer::new_headless(1, 1)).is_err() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hill.fdf"), "0 0 0\n0 5 0\n0 0 0\n").unwrap();
+        let file = dir.path().join("jobs.yaml");
+        std::fs::write(
+            &file,
+            r#"
+defaults: {input: hill.fdf, width: 48, height: 32}
+jobs:
+  - output: out/framed.png
+  - {input: missing.fdf, output: out/missing.png}
+  - output: out/close.png
+    mode: both
+    camera: {target: [1, 5, 1], distance: 4, azimuth: 0, elevation: 45}
+"#,
+        )
+        .unwrap();
+
+        let summary = crate::jobs::run(&file).unwrap();
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.failed.len(), 1);
+        assert!(summary.failed[0].0.ends_with("missing.png"));
+        for name in ["framed.png", "close.png"] {
+            let png = std::fs::read(dir.path().join("out").join(name)).unwrap();
+            assert!(png.starts_with(b"\x89PNG"));
+        }
+        assert!(!dir.path().join("out/missing.png").exists());
+    }
+
+    #[test]
+    fn test_smooth_motion_glides_to_reset_view() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.drag(MouseButton::Left, (10.0, 10.0), (80.0, 40.0));
+        let turned = harness.frame();
+
+        // Slow enough that the view stays near the turned one for a while
+        harness.renderer().settings.motion = MotionConfig {
+            enabled: true,
+            response: 1000.0,
+        };
+        harness.press(KeyCode::KeyR);
+        assert_eq!(harness.camera().azimuth, Camera::new().azimuth);
+        let reset = harness.renderer().capture_frame().unwrap();
+        assert_ne!(turned, reset);
+        assert_ne!(harness.frame(), reset);
+        assert_ne!(harness.frame(), reset);
+
+        harness.renderer().settings.motion.enabled = false;
+        assert_eq!(harness.frame(), reset);
+    }
+
+    #[test]
+    fn test_fly_mode_moves_while_keys_are_held() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.press(KeyCode::KeyV);
+        assert_eq!(harness.camera().mode, CameraMode::Fly);
+        let eye = harness.camera().position();
+        let forward = harness.camera().forward();
+
+        harness.app.handle_key(KeyCode::KeyW, ElementState::Pressed);
+        harness.frame();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        harness.frame();
+        let moved = harness.camera().position() - eye;
+        assert!(moved.dot(forward) > 0.0);
+        assert!(moved.cross(forward).length() < 1e-3);
+
+        // Released, it stays put
+        harness
+            .app
+            .handle_key(KeyCode::KeyW, ElementState::Released);
+        let eye = harness.camera().position();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        harness.frame();
+        assert_eq!(harness.camera().position(), eye);
+    }
+
+    #[test]
+    fn test_flying_camera_stays_above_terrain() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.press(KeyCode::KeyV);
+        // Level with the ground, inside the pyramid
+        let camera = &mut harness.renderer().camera;
+        camera.target = glam::Vec3::ZERO;
+        camera.distance = 1.0;
+        camera.elevation = 0.0;
+        harness.frame();
+
+        let terrain = pyramid();
+        let eye = harness.camera().position().as_dvec3() + terrain.center();
+        let ground = Heightfield::new(&terrain, 1.0)
+            .sample_height(eye.x, eye.z)
+            .unwrap();
+        assert!(eye.y > ground);
+    }
+
+    #[test]
+    fn test_input_latency_counts_to_next_frame() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.frame();
+        assert!(harness.renderer().latency().input_to_present().is_none());
+        // Offscreen frames have no surface to wait for
+        assert!(harness.renderer().latency().acquire_wait().is_none());
+
+        harness.scroll(1.0);
+        harness.frame();
+        let latency = harness.renderer().latency().input_to_present().unwrap();
+        assert!(latency.max >= latency.mean);
+    }
+
+    #[test]
+    fn test_perf_log_writes_a_row_per_frame() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("perf.csv");
+        harness.renderer().log_performance(&path).unwrap();
+        harness.frame();
+        harness.frame();
+        harness.renderer().finish_perf_log();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert!(lines[0].starts_with("# lrle "));
+        assert_eq!(lines[1], perf_log::HEADER);
+        assert_eq!(lines.len(), 4);
+        let row: Vec<_> = lines[3].split(',').collect();
+        assert_eq!(row[0], "1");
+        assert!(row[4].parse::<usize>().unwrap() >= 1);
+    }
+
+    #[test]
+    fn test_framed_capture_shows_whole_terrain() {
+        // Far too close to see the whole pyramid
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().camera.distance = 2.0;
+        let background = harness.renderer().capture_frame().unwrap()[0];
+        harness.renderer().frame_scene();
+        let frame = harness.renderer().capture_frame().unwrap();
+        assert_eq!(frame.len(), 96 * 64);
+
+        // Background all around the terrain
+        let rows: Vec<_> = frame.chunks(96).collect();
+        for edge in [rows[0], rows[63]] {
+            assert!(edge.iter().all(|&pixel| pixel == background));
+        }
+        assert!(rows
+            .iter()
+            .all(|row| row[0] == background && row[95] == background));
+        assert_ne!(frame[32 * 96 + 48], background);
+    }
+
+    #[test]
+    fn test_f12_saves_screenshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_arg = dir.path().to_str().unwrap();
+        let Some(mut harness) = Harness::new(pyramid(), (96, 64), &["--screenshot-dir", dir_arg])
+        else {
+            return;
+        };
+        let frame = harness.frame();
+        assert!(harness.press(KeyCode::F12));
+        harness.frame();
+
+        let entries = std::fs::read_dir(dir.path()).unwrap();
+        let paths: Vec<_> = entries.map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(paths.len(), 1);
+        let name = paths[0].file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("lrle-") && name.ends_with(".png"));
+
+        // The frame at the window's size, without the panel
+        let decoder = png::Decoder::new(std::io::BufReader::new(
+            std::fs::File::open(&paths[0]).unwrap(),
+        ));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (96, 64));
+        harness.renderer().ui.panel_visible = false;
+        assert_eq!(pixels, harness.frame().as_flattened());
+        assert_ne!(frame, harness.frame());
+    }
+
+    #[test]
+    fn test_right_click_inspects_point() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.tool = Tool::Inspect;
+        let frame = harness.frame();
+        harness.click(MouseButton::Right, (48.0, 32.0));
+        let pick = harness.renderer().inspected.unwrap();
+        assert_eq!(Some(pick), harness.renderer().pick((48.0, 32.0)));
+
+        // The stored height of the nearest sample, beside the picked point
+        let (column, row) = pick.sample;
+        assert_eq!(pick.height, pyramid().at(column, row));
+        assert!((pick.grid.x - column as f64).abs() <= 0.5);
+        assert!((pick.grid.y - row as f64).abs() <= 0.5);
+
+        // Drawn as a marker
+        assert_ne!(harness.frame(), frame);
+    }
+
+    #[test]
+    fn test_right_clicks_draw_annotations() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.tool = Tool::Polyline;
+        let frame = harness.frame();
+
+        // A click, then a freehand stroke across the pyramid
+        harness.click(MouseButton::Right, (36.0, 32.0));
+        harness.drag(MouseButton::Right, (40.0, 32.0), (60.0, 32.0));
+        let sketch = harness.renderer().sketch.clone().unwrap();
+        assert!(sketch.outline().len() > 3);
+        assert_ne!(harness.frame(), frame);
+
+        // Switching shape tools finishes the line
+        harness.renderer().settings.tool = Tool::Polygon;
+        harness.click(MouseButton::Right, (44.0, 32.0));
+        assert_eq!(harness.renderer().annotations, vec![sketch]);
+
+        // Notes need text
+        harness.renderer().settings.tool = Tool::Note;
+        harness.click(MouseButton::Right, (48.0, 32.0));
+        assert_eq!(harness.renderer().annotations.len(), 1);
+        harness.renderer().ui.note_text = "Summit".to_string();
+        harness.click(MouseButton::Right, (48.0, 32.0));
+        assert_eq!(harness.renderer().annotations.len(), 2);
+    }
+
+    #[test]
+    fn test_paint_brush_colors_and_saves_fdf() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.tool = Tool::Paint;
+        harness.renderer().settings.file_colors = false;
+        harness.renderer().settings.brush.color = [0.0, 0.0, 1.0];
+        harness.renderer().settings.brush.radius = 1.0;
+        let frame = harness.frame();
+
+        // A stroke across the pyramid shows in blue
+        harness.drag(MouseButton::Right, (40.0, 32.0), (56.0, 32.0));
+        assert!(harness.renderer().settings.file_colors);
+        assert_ne!(harness.frame(), frame);
+
+        // Saved with the heights; the rest keeps the color mode's colors
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("painted.fdf");
+        let job = harness.renderer().save_fdf(path.clone()).unwrap();
+        harness.renderer().wait_for_job(job).unwrap();
+        let saved = lrle::terrain::loader::load_fdf(&path).unwrap();
+        assert_eq!(saved.heights(), pyramid().heights());
+        let colors = saved.colors.unwrap();
+        let painted = colors.iter().filter(|&&c| c == 0x0000FF).count();
+        assert!(painted >= 5, "{painted}");
+        assert!(colors.iter().any(|&c| c != 0x0000FF && c != 0xFFFFFF));
+    }
+
+    #[test]
+    fn test_main_terrain_loads_in_background() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+        let frame = harness.frame();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pyramid.fdf");
+        std::fs::write(&path, lrle::terrain::loader::to_fdf(&pyramid())).unwrap();
+
+        // Listed as a job while loading, then shown and framed like at startup
+        harness.renderer().camera.distance *= 2.0;
+        let options = lrle::terrain::LoadOptions::default();
+        let job = harness.renderer().load_main_terrain(path, options);
+        harness.renderer().wait_for_job(job).unwrap();
+        assert_eq!(harness.renderer().terrain_name, "pyramid.fdf");
+        assert_eq!(harness.frame(), frame);
+    }
+
+    #[test]
+    fn test_mesh_built_while_loading_draws_like_uploaded() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+        harness.renderer().settings.render_mode = RenderMode::Both;
+        // More chunks than are written in one frame
+        let points = (0..8 * 64 + 1)
+            .map(|z: usize| {
+                (0..9 * 64 + 1)
+                    .map(|x| ((x / 7 + z / 5) % 9) as f64)
+                    .collect()
+            })
+            .collect();
+        let terrain = TerrainData::new(points, None);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ridges.fdf");
+        std::fs::write(&path, lrle::terrain::loader::to_fdf(&terrain)).unwrap();
+
+        let options = lrle::terrain::LoadOptions::default();
+        let job = harness.renderer().load_main_terrain(path, options);
+        harness.renderer().wait_for_job(job).unwrap();
+        let loaded = harness.frame();
+        harness.renderer().upload_terrain(&terrain, 1.0);
+        assert_eq!(harness.frame(), loaded);
+    }
+
+    #[test]
+    fn test_float_grid_detail_follows_camera() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ripples.flt");
+        let header = "ncols 256\nnrows 256\nxllcorner 0\nyllcorner 0\ncellsize 1\n";
+        std::fs::write(path.with_extension("hdr"), header).unwrap();
+        let bytes: Vec<u8> = (0..256 * 256)
+            .flat_map(|i| ((i % 7) as f32).to_le_bytes())
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+
+        // Shown as an overview of every fourth sample
+        let options = lrle::terrain::LoadOptions {
+            max_size: 64,
+            ..Default::default()
+        };
+        let job = harness.renderer().load_main_terrain(path, options);
+        harness.renderer().wait_for_job(job).unwrap();
+        harness.frame();
+        harness.renderer().finish_jobs();
+        assert_eq!(harness.renderer().detail_subdivisions(), None);
+
+        // Close up, the grid is read around the camera at full resolution
+        harness.renderer().camera.distance = 20.0;
+        harness.frame();
+        harness.renderer().finish_jobs();
+        assert_eq!(harness.renderer().detail_subdivisions(), Some(4));
+        harness.renderer().settings.render_mode = RenderMode::Both;
+        harness.frame();
+
+        // Colored by ruggedness, the overview is drawn alone
+        harness.renderer().settings.color_mode = ColorMode::Ruggedness;
+        harness.frame();
+        assert_eq!(harness.renderer().detail_subdivisions(), None);
+    }
+
+    #[test]
+    fn test_failed_load_is_kept_until_retried() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.fdf");
+        std::fs::write(&path, "0 1 2\n0 1\n0 1 2\n").unwrap();
+
+        // The window keeps the old terrain and shows the offending line
+        let options = lrle::terrain::LoadOptions::default();
+        let job = harness.renderer().load_main_terrain(path.clone(), options);
+        harness.renderer().wait_for_job(job).unwrap();
+        let failure = harness.renderer().load_failure.clone().unwrap();
+        assert_eq!(failure.path, path);
+        assert_eq!(failure.line, Some((2, "0 1".to_string())));
+        assert_ne!(harness.renderer().terrain_name, "broken.fdf");
+        harness.frame();
+
+        // Fixed and retried, it loads like any other
+        std::fs::write(&path, lrle::terrain::loader::to_fdf(&pyramid())).unwrap();
+        let job = harness.renderer().retry_load().unwrap();
+        harness.renderer().wait_for_job(job).unwrap();
+        assert!(harness.renderer().load_failure.is_none());
+        assert_eq!(harness.renderer().terrain_name, "broken.fdf");
+        assert!(harness.renderer().retry_load().is_none());
+    }
+
+    #[test]
+    fn test_failed_grids_are_kept_for_their_role() {
+        let dir = tempfile::tempdir().unwrap();
+        let broken = dir.path().join("broken.fdf");
+        std::fs::write(&broken, "0 1 2\n0 1\n").unwrap();
+        let grid = dir.path().join("grid.fdf");
+        std::fs::write(&grid, lrle::terrain::loader::to_fdf(&pyramid())).unwrap();
+        let options = lrle::terrain::LoadOptions::default();
+
+        // At startup the first failure is kept, later ones only logged
+        let mut failure = None;
+        let path = broken.to_str().unwrap();
+        for target in [LoadTarget::Reference, LoadTarget::Layer] {
+            assert!(crate::load_or_keep_failure(path, &options, target, &mut failure).is_none());
+        }
+        assert_eq!(failure.unwrap().target, LoadTarget::Reference);
+
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let job = harness
+            .renderer()
+            .load_channel("depth".into(), broken.clone(), options);
+        harness.renderer().wait_for_job(job).unwrap();
+        let failure = harness.renderer().load_failure.clone().unwrap();
+        assert_eq!(failure.target, LoadTarget::Channel("depth".into()));
+
+        // Retried, the grid is loaded for what it was for
+        harness.renderer().load_failure = Some(LoadFailure {
+            path: grid,
+            ..failure
+        });
+        let job = harness.renderer().retry_load().unwrap();
+        harness.renderer().wait_for_job(job).unwrap();
+        harness.renderer().run_algebra("twice = depth * 2").unwrap();
+    }
+
+    #[test]
+    fn test_drag_selects_region_with_stats() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.tool = Tool::SelectRectangle;
+        let frame = harness.frame();
+        harness.drag(MouseButton::Right, (48.0, 32.0), (48.0, 52.0));
+        let selection = harness.renderer().selection.clone().unwrap();
+        let stats = selection.stats.unwrap();
+        assert!(selection.cells.len() > 4);
+        assert!(stats.min <= stats.mean && stats.mean <= stats.max);
+        assert_eq!(
+            selection.to_csv().lines().count(),
+            selection.cells.len() + 1
+        );
+        assert_ne!(harness.frame(), frame);
+
+        // A lasso along the same stroke encloses less
+        harness.renderer().settings.tool = Tool::SelectLasso;
+        harness.drag(MouseButton::Right, (48.0, 32.0), (48.0, 52.0));
+        let lasso = harness.renderer().selection.clone().unwrap();
+        assert!(lasso.cells.len() < selection.cells.len());
+
+        // A click clears it
+        harness.click(MouseButton::Right, (48.0, 40.0));
+        assert!(harness.renderer().selection.is_none());
+    }
+
+    #[test]
+    fn test_denoise_with_raw_preview() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+        let raw = harness.frame();
+
+        let denoise = &mut harness.renderer().settings.denoise;
+        denoise.enabled = true;
+        denoise.range = 4.0;
+        let filtered = harness.frame();
+        assert_ne!(filtered, raw);
+
+        // Holding the preview shows the raw heights, and letting go the
+        // filtered ones again
+        harness.renderer().settings.denoise.show_raw = true;
+        assert_eq!(harness.frame(), raw);
+        harness.renderer().settings.denoise.show_raw = false;
+        assert_eq!(harness.frame(), filtered);
+    }
+
+    #[test]
+    fn test_crop_to_selection() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+        let full = harness.frame();
+        let distance = harness.renderer().camera.distance;
+
+        harness.renderer().settings.tool = Tool::SelectRectangle;
+        harness.drag(MouseButton::Right, (48.0, 32.0), (48.0, 52.0));
+        let (columns, rows) = harness
+            .renderer()
+            .selection
+            .as_ref()
+            .unwrap()
+            .extent()
+            .unwrap();
+        assert!(columns.len() < 16 && rows.len() < 16);
+
+        // The window is framed closer, and the selection goes with the old grid
+        harness.renderer().crop(columns, rows);
+        assert!(harness.renderer().camera.distance < distance);
+        assert!(harness.renderer().selection.is_none());
+        assert_ne!(harness.frame(), full);
+
+        harness.renderer().restore_original();
+        assert_eq!(harness.renderer().camera.distance, distance);
+        assert_eq!(harness.frame(), full);
+    }
+
+    #[test]
+    fn test_downsample_and_restore_original() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.motion.enabled = false;
+        let full = harness.frame();
+
+        harness
+            .renderer()
+            .process(lrle::terrain::processing::Processing::Downsample(4));
+        assert_ne!(harness.frame(), full);
+
+        // Undone along with the view
+        harness.renderer().restore_original();
+        assert_eq!(harness.frame(), full);
+    }
+
+    #[test]
+    fn test_uncertainty_styles_change_frame() {
+        // Least certain at the summit
+        let mut terrain = pyramid();
+        terrain.add_channel("sigma", &pyramid());
+        let Some(mut harness) = Harness::new(terrain, (96, 64), &[]) else {
+            return;
+        };
+        harness.renderer().ui.panel_visible = false;
+        let plain = harness.frame();
+
+        let frames: Vec<_> = UncertaintyStyle::ALL
+            .into_iter()
+            .map(|style| {
+                harness.renderer().settings.uncertainty = UncertaintyConfig {
+                    channel: Some(0),
+                    style,
+                    ..UncertaintyConfig::default()
+                };
+                harness.frame()
+            })
+            .collect();
+        for (i, frame) in frames.iter().enumerate() {
+            assert_ne!(*frame, plain);
+            assert!(frames[i + 1..].iter().all(|other| other != frame));
+        }
+    }
+
+    #[test]
+    fn test_draped_image_blends_into_surface() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().ui.panel_visible = false;
+        harness.renderer().settings.render_mode = RenderMode::Solid;
+        let plain = harness.frame();
+
+        let image = lrle::terrain::drape::DrapeImage {
+            width: 2,
+            height: 2,
+            rgba: vec![
+                [255, 0, 0, 255],
+                [0, 0, 255, 255],
+                [0, 255, 0, 255],
+                [255; 4],
+            ],
+        };
+        harness.renderer().set_drape(&image);
+        let draped = harness.frame();
+        assert_ne!(draped, plain);
+
+        harness.renderer().settings.texture_blend = 0.0;
+        assert_eq!(harness.frame(), plain);
+    }
+
+    #[test]
+    fn test_alignment_tool_moves_draped_image() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.render_mode = RenderMode::Solid;
+        let image = lrle::terrain::drape::DrapeImage {
+            width: 2,
+            height: 1,
+            rgba: vec![[255, 0, 0, 255], [0, 0, 255, 255]],
+        };
+        harness.renderer().set_drape(&image);
+        harness.renderer().settings.tool = Tool::AlignImage;
+
+        // A new control point starts where the image shows already
+        harness.click(MouseButton::Right, (48.0, 32.0));
+        // Without the alignment window covering the small frame
+        harness.renderer().settings.tool = Tool::None;
+        let placed = harness.frame();
+        let control = harness.renderer().drape_alignment.points[0];
+        let unaligned = lrle::terrain::drape::image_position(&pyramid(), control.terrain);
+        assert!(control.image.abs_diff_eq(unaligned, 1e-9));
+
+        // Moving its image end shifts the image across the surface
+        harness.renderer().drape_alignment.points[0].image.x += 0.25;
+        assert_ne!(harness.frame(), placed);
+        harness.renderer().drape_alignment.points[0] = control;
+        assert_eq!(harness.frame(), placed);
+
+        // Clicking beside it grabs it, and dragging moves its terrain end
+        harness.renderer().settings.tool = Tool::AlignImage;
+        harness.drag(MouseButton::Right, (48.0, 32.0), (56.0, 32.0));
+        harness.renderer().settings.tool = Tool::None;
+        let points = &harness.renderer().drape_alignment.points;
+        assert_eq!(points.len(), 1);
+        assert_ne!(points[0].terrain, control.terrain);
+        assert_ne!(harness.frame(), placed);
+
+        // Another image starts over
+        harness.renderer().set_drape(&image);
+        assert!(harness.renderer().drape_alignment.points.is_empty());
+    }
+
+    #[test]
+    fn test_environment_lights_reflects_and_fills_background() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().ui.panel_visible = false;
+        harness.renderer().settings.render_mode = RenderMode::Solid;
+        let plain = harness.frame();
+
+        // Warm to one side, cool to the other, dark below the horizon
+        let (width, height) = (16, 8);
+        let rgb = (0..width * height)
+            .map(|i| match (i / width < height / 2, i % width < width / 2) {
+                (true, true) => [4.0, 1.5, 0.2],
+                (true, false) => [0.2, 0.6, 2.0],
+                (false, _) => [0.05; 3],
+            })
+            .collect();
+        let map = lrle::terrain::environment::EnvironmentMap { width, height, rgb };
+        harness.renderer().set_environment(&map);
+        let lit = harness.frame();
+        assert_ne!(lit, plain);
+        assert_ne!(lit[0], plain[0], "background");
+
+        harness.renderer().settings.environment.rotation = 180.0;
+        assert_ne!(harness.frame(), lit);
+
+        harness.renderer().settings.environment.enabled = false;
+        assert_eq!(harness.frame(), plain);
+    }
+
+    #[test]
+    fn test_water_level_floods_low_ground() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dry = harness.frame();
+        let Some(mut flooded) = Harness::new(pyramid(), (96, 64), &["--water-level", "4"]) else {
+            return;
+        };
+        flooded.renderer().ui.panel_visible = false;
+        assert!(flooded.renderer().settings.water.enabled);
+
+        // The lower half of the pyramid turns blue; the summit stays dry
+        let blue = |frame: &[[u8; 4]]| {
+            frame
+                .iter()
+                .filter(|[r, _, b, _]| *b as i32 > *r as i32 + 40)
+                .count()
+        };
+        let wet = flooded.frame();
+        assert!(blue(&wet) > blue(&dry) + 100);
+        assert_ne!(wet, dry);
+    }
+
+    #[test]
+    fn test_right_clicks_check_line_of_sight() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.tool = Tool::LineOfSight;
+
+        // The first click picks the observer, the second the target
+        harness.click(MouseButton::Right, (40.0, 32.0));
+        assert!(harness.renderer().sight_start.is_some());
+        harness.click(MouseButton::Right, (56.0, 32.0));
+        assert!(harness.renderer().sight_start.is_none());
+        // The pyramid's upper flank rises between the two points
+        let los = harness.renderer().line_of_sight.clone().unwrap();
+        assert!(!los.is_visible());
+
+        // Raising the observer to the required height clears the pyramid
+        let height = los.required_height as f32 + 0.01;
+        harness.renderer().settings.sight.observer_height = height;
+        harness.frame();
+        assert!(harness
+            .renderer()
+            .line_of_sight
+            .as_ref()
+            .unwrap()
+            .is_visible());
+    }
+
+    #[test]
+    fn test_right_clicks_cut_profile() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.renderer().settings.tool = Tool::Profile;
+
+        // The first click starts the cut, the second ends it
+        harness.click(MouseButton::Right, (40.0, 32.0));
+        assert!(harness.renderer().profile_start.is_some());
+        harness.click(MouseButton::Right, (56.0, 32.0));
+        assert!(harness.renderer().profile_start.is_none());
+        // The cut climbs the pyramid's flank
+        let profile = harness.renderer().profile.clone().unwrap();
+        let (low, high) = profile.height_range();
+        assert!(high > low);
+        assert!(profile.climb().0 > 0.0);
+    }
+
+    #[test]
+    fn test_resize_changes_frame_size() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        harness.resize(40, 30);
+        assert_eq!(harness.frame().len(), 40 * 30);
+
+        // Zero-sized windows (minimized) keep the previous size
+        harness.resize(0, 0);
+        assert_eq!(harness.frame().len(), 40 * 30);
+    }
+
+    #[test]
+    fn test_dropped_file_replaces_main_terrain() {
+        let Some(mut harness) = harness() else {
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let note = Annotation::Note {
+            position: DVec2::ZERO,
+            text: "summit".to_string(),
+        };
+        harness.renderer().annotations.push(note);
+        let pose = Keyframe::from_camera(harness.camera(), DVec3::ZERO, 0.0);
+        harness.renderer().camera_path.insert(pose);
+
+        // A broken file leaves the pyramid and its edits in place and says
+        // why
+        let broken = dir.path().join("broken.fdf");
+        std::fs::write(&broken, "0 1 2\n0 1\n").unwrap();
+        harness.send(WindowEvent::DroppedFile(broken.clone()));
+        harness.renderer().finish_jobs();
+        let failure = harness.renderer().load_failure.clone().unwrap();
+        assert_eq!(failure.path, broken);
+        assert_eq!(failure.line, Some((2, "0 1".to_string())));
+        assert_eq!(harness.renderer().terrain_name, "terrain.fdf");
+        assert_eq!(harness.renderer().annotations.len(), 1);
+        harness.frame();
+
+        let dropped = dir.path().join("dropped.fdf");
+        std::fs::write(
+            &dropped,
+            "0 1 2 1 0\n1 2 3 2 1\n2 3 4 3 2\n1 2 3 2 1\n0 1 2 1 0\n",
+        )
+        .unwrap();
+        harness.send(WindowEvent::DroppedFile(dropped.clone()));
+        harness.renderer().finish_jobs();
+        harness.frame();
+        assert!(harness.renderer().load_failure.is_none());
+        assert_eq!(harness.renderer().terrain_name, "dropped.fdf");
+        assert!(harness.renderer().annotations.is_empty());
+        assert!(harness.renderer().camera_path.keyframes.is_empty());
+        assert_eq!(harness.app.bookmarks, Some(Bookmarks::file_for(&dropped)));
+        assert_eq!(harness.app.terrain_file, Some(dropped.clone()));
+
+        // Annotations are saved next to the file and come back with it
+        harness.renderer().settings.tool = Tool::Note;
+        harness.renderer().ui.note_text = "Cairn".to_string();
+        harness.click(MouseButton::Right, (48.0, 32.0));
+        let saved = harness.renderer().annotations.clone();
+        assert_eq!(saved.len(), 1);
+        assert!(annotations::file_for(&dropped).exists());
+        assert!(annotations::file_for(&dropped).exists());
+        let other = dir.path().join("other.fdf");
+        std::fs::write(&other, "0 1\n2 3\n").unwrap();
+        for path in [other, dropped] {
+            harness.send(WindowEvent::DroppedFile(path));
+            harness.renderer().finish_jobs();
+            harness.frame();
+        }
+        assert_eq!(harness.renderer().annotations, saved);
+    }
+}
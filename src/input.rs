@@ -1,15 +1,21 @@
 //! Input handling for camera control.
 //!
-//! Processes mouse and keyboard events to update camera state.
+//! Processes mouse and keyboard events to update camera state. Keys are
+//! looked up in [`KeyBindings`], which the config file can remap.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
 use glam::Vec3;
 use winit::event::{ElementState, MouseButton, MouseScrollDelta};
 use winit::keyboard::KeyCode;
 
-use crate::renderer::camera::Camera;
+use crate::renderer::camera::{Camera, CameraMode};
+use crate::renderer::pacing::{FixedStep, UPDATE_STEP};
 use crate::renderer::Projection;
 
-/// Sensitivity constants for input handling.
+/// Sensitivity constants and key bindings for input handling.
+#[derive(Debug, Clone, PartialEq)]
 pub struct InputConfig {
     /// Mouse rotation sensitivity (radians per pixel)
     pub rotate_sensitivity: f32,
@@ -17,6 +23,14 @@ pub struct InputConfig {
     pub pan_sensitivity: f32,
     /// Scroll zoom sensitivity (multiplier per scroll unit)
     pub zoom_sensitivity: f32,
+    /// Turn the other way when dragging sideways to rotate or look
+    pub invert_x: bool,
+    /// Tilt the other way when dragging up or down to rotate or look
+    pub invert_y: bool,
+    /// Zoom out when scrolling up
+    pub invert_zoom: bool,
+    /// Keys bound to each action
+    pub keys: KeyBindings,
     /// Minimum camera distance
     pub min_distance: f32,
     /// Maximum camera distance
@@ -25,6 +39,8 @@ pub struct InputConfig {
     pub min_elevation: f32,
     /// Maximum elevation angle (radians, avoid looking straight up)
     pub max_elevation: f32,
+    /// Fly mode speed in camera distances per second
+    pub fly_speed: f32,
 }
 
 impl Default for InputConfig {
@@ -33,14 +49,275 @@ impl Default for InputConfig {
             rotate_sensitivity: 0.005,
             pan_sensitivity: 0.1,
             zoom_sensitivity: 0.1,
+            invert_x: false,
+            invert_y: false,
+            invert_zoom: false,
+            keys: KeyBindings::default(),
             min_distance: 1.0,
             max_distance: 500.0,
             min_elevation: -std::f32::consts::FRAC_PI_2 + 0.1,
             max_elevation: std::f32::consts::FRAC_PI_2 - 0.1,
+            fly_speed: 0.5,
+        }
+    }
+}
+
+/// Actions that can be bound to keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    ResetCamera,
+    FrameTerrain,
+    ToggleFly,
+    ToggleProjection,
+    ToggleAutoRotate,
+    PlayTimeline,
+    NextKeyframe,
+    PreviousKeyframe,
+    Screenshot,
+    Presentation,
+    FlyForward,
+    FlyBack,
+    FlyLeft,
+    FlyRight,
+    FlyDown,
+    FlyUp,
+}
+
+impl Action {
+    /// All actions, in the order the settings list them.
+    pub const ALL: [Action; 16] = [
+        Action::ResetCamera,
+        Action::FrameTerrain,
+        Action::ToggleFly,
+        Action::ToggleProjection,
+        Action::ToggleAutoRotate,
+        Action::PlayTimeline,
+        Action::NextKeyframe,
+        Action::PreviousKeyframe,
+        Action::Screenshot,
+        Action::Presentation,
+        Action::FlyForward,
+        Action::FlyBack,
+        Action::FlyLeft,
+        Action::FlyRight,
+        Action::FlyDown,
+        Action::FlyUp,
+    ];
+
+    /// Key of the action in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ResetCamera => "reset_camera",
+            Self::FrameTerrain => "frame_terrain",
+            Self::ToggleFly => "toggle_fly",
+            Self::ToggleProjection => "toggle_projection",
+            Self::ToggleAutoRotate => "toggle_auto_rotate",
+            Self::PlayTimeline => "play_timeline",
+            Self::NextKeyframe => "next_keyframe",
+            Self::PreviousKeyframe => "previous_keyframe",
+            Self::Screenshot => "screenshot",
+            Self::Presentation => "presentation",
+            Self::FlyForward => "fly_forward",
+            Self::FlyBack => "fly_back",
+            Self::FlyLeft => "fly_left",
+            Self::FlyRight => "fly_right",
+            Self::FlyDown => "fly_down",
+            Self::FlyUp => "fly_up",
+        }
+    }
+
+    /// Name shown in the UI.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ResetCamera => "Reset Camera",
+            Self::FrameTerrain => "Frame Terrain",
+            Self::ToggleFly => "Toggle Fly Mode",
+            Self::ToggleProjection => "Toggle Projection",
+            Self::ToggleAutoRotate => "Toggle Auto-Rotate",
+            Self::PlayTimeline => "Play/Pause Timeline",
+            Self::NextKeyframe => "Next Keyframe",
+            Self::PreviousKeyframe => "Previous Keyframe",
+            Self::Screenshot => "Save Screenshot",
+            Self::Presentation => "Presentation Mode",
+            Self::FlyForward => "Fly Forward",
+            Self::FlyBack => "Fly Back",
+            Self::FlyLeft => "Fly Left",
+            Self::FlyRight => "Fly Right",
+            Self::FlyDown => "Fly Down",
+            Self::FlyUp => "Fly Up",
+        }
+    }
+
+    /// Keys bound to the action unless the config file says otherwise.
+    fn default_keys(self) -> &'static [KeyCode] {
+        match self {
+            Self::ResetCamera => &[KeyCode::KeyR],
+            Self::FrameTerrain => &[KeyCode::KeyF],
+            Self::ToggleFly => &[KeyCode::KeyV],
+            Self::ToggleProjection => &[KeyCode::KeyP, KeyCode::KeyI],
+            Self::ToggleAutoRotate => &[KeyCode::KeyT],
+            Self::PlayTimeline => &[KeyCode::Space],
+            Self::NextKeyframe => &[KeyCode::PageDown, KeyCode::ArrowRight],
+            Self::PreviousKeyframe => &[KeyCode::PageUp, KeyCode::ArrowLeft],
+            Self::Screenshot => &[KeyCode::F12],
+            Self::Presentation => &[KeyCode::F5],
+            Self::FlyForward => &[KeyCode::KeyW],
+            Self::FlyBack => &[KeyCode::KeyS],
+            Self::FlyLeft => &[KeyCode::KeyA],
+            Self::FlyRight => &[KeyCode::KeyD],
+            Self::FlyDown => &[KeyCode::KeyQ],
+            Self::FlyUp => &[KeyCode::KeyE],
+        }
+    }
+
+    /// Direction the action flies in, for the fly mode movement actions.
+    fn fly_direction(self, forward: Vec3, right: Vec3) -> Option<Vec3> {
+        match self {
+            Self::FlyForward => Some(forward),
+            Self::FlyBack => Some(-forward),
+            Self::FlyRight => Some(right),
+            Self::FlyLeft => Some(-right),
+            Self::FlyUp => Some(Vec3::Y),
+            Self::FlyDown => Some(-Vec3::Y),
+            _ => None,
         }
     }
 }
 
+/// Keys bound to each action; an action can have several keys, or none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    keys: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: Action::ALL
+                .into_iter()
+                .map(|action| (action, action.default_keys().to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Keys bound to `action`.
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Bind `keys` to `action`, unbinding them from other actions.
+    pub fn set(&mut self, action: Action, keys: Vec<KeyCode>) {
+        for bound in self.keys.values_mut() {
+            bound.retain(|key| !keys.contains(key));
+        }
+        self.keys.insert(action, keys);
+    }
+
+    /// Action bound to `key`, if any.
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|&action| self.keys(action).contains(&key))
+    }
+
+    /// Names of the keys bound to `action`, e.g. "P / I".
+    pub fn label(&self, action: Action) -> String {
+        let names: Vec<&str> = self
+            .keys(action)
+            .iter()
+            .filter_map(|&key| key_name(key))
+            .collect();
+        match names.is_empty() {
+            true => "Unbound".to_string(),
+            false => names.join(" / "),
+        }
+    }
+}
+
+/// Names of the keys that can be bound, as written in the config file.
+/// Letters, digits and named keys match egui's key names.
+const KEY_NAMES: [(&str, KeyCode); 61] = [
+    ("A", KeyCode::KeyA),
+    ("B", KeyCode::KeyB),
+    ("C", KeyCode::KeyC),
+    ("D", KeyCode::KeyD),
+    ("E", KeyCode::KeyE),
+    ("F", KeyCode::KeyF),
+    ("G", KeyCode::KeyG),
+    ("H", KeyCode::KeyH),
+    ("I", KeyCode::KeyI),
+    ("J", KeyCode::KeyJ),
+    ("K", KeyCode::KeyK),
+    ("L", KeyCode::KeyL),
+    ("M", KeyCode::KeyM),
+    ("N", KeyCode::KeyN),
+    ("O", KeyCode::KeyO),
+    ("P", KeyCode::KeyP),
+    ("Q", KeyCode::KeyQ),
+    ("R", KeyCode::KeyR),
+    ("S", KeyCode::KeyS),
+    ("T", KeyCode::KeyT),
+    ("U", KeyCode::KeyU),
+    ("V", KeyCode::KeyV),
+    ("W", KeyCode::KeyW),
+    ("X", KeyCode::KeyX),
+    ("Y", KeyCode::KeyY),
+    ("Z", KeyCode::KeyZ),
+    ("0", KeyCode::Digit0),
+    ("1", KeyCode::Digit1),
+    ("2", KeyCode::Digit2),
+    ("3", KeyCode::Digit3),
+    ("4", KeyCode::Digit4),
+    ("5", KeyCode::Digit5),
+    ("6", KeyCode::Digit6),
+    ("7", KeyCode::Digit7),
+    ("8", KeyCode::Digit8),
+    ("9", KeyCode::Digit9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Space", KeyCode::Space),
+    ("Enter", KeyCode::Enter),
+    ("Backspace", KeyCode::Backspace),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Left", KeyCode::ArrowLeft),
+    ("Right", KeyCode::ArrowRight),
+    ("Up", KeyCode::ArrowUp),
+    ("Down", KeyCode::ArrowDown),
+];
+
+/// Name of a key that can be bound.
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, code)| code == key)
+        .map(|&(name, _)| name)
+}
+
+/// Key of a name from [`key_name`], ignoring case.
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name.trim()))
+        .map(|&(_, key)| key)
+}
+
 /// Tracks mouse state for drag operations.
 #[derive(Default)]
 pub struct InputState {
@@ -54,6 +331,8 @@ pub struct InputState {
     pub shift_pressed: bool,
     /// Last mouse position (for computing delta)
     pub last_mouse_pos: Option<(f32, f32)>,
+    /// Fly mode movement actions whose keys are held
+    pub move_keys: HashSet<Action>,
 }
 
 impl InputState {
@@ -76,13 +355,19 @@ impl InputState {
 pub struct InputController {
     pub config: InputConfig,
     pub state: InputState,
+    /// Time since the last [`update`](Self::update), in fixed steps
+    clock: FixedStep,
 }
 
+/// Fly mode speed multiplier while Shift is held.
+const FAST_FLY: f32 = 4.0;
+
 impl InputController {
     pub fn new() -> Self {
         Self {
             config: InputConfig::default(),
             state: InputState::new(),
+            clock: FixedStep::default(),
         }
     }
 
@@ -101,24 +386,42 @@ impl InputController {
     pub fn handle_keyboard(&mut self, key: KeyCode, state: ElementState, camera: &mut Camera) {
         let pressed = state == ElementState::Pressed;
 
-        match key {
-            KeyCode::ShiftLeft | KeyCode::ShiftRight => {
-                self.state.shift_pressed = pressed;
+        if matches!(key, KeyCode::ShiftLeft | KeyCode::ShiftRight) {
+            self.state.shift_pressed = pressed;
+        }
+
+        match self.config.keys.action(key) {
+            Some(Action::ResetCamera) if pressed => {
+                // Reset camera to default, in the same mode
+                *camera = Camera {
+                    mode: camera.mode,
+                    ..Camera::new()
+                };
             }
-            KeyCode::KeyR if pressed => {
-                // Reset camera to default
-                *camera = Camera::new();
+            Some(Action::ToggleFly) if pressed => {
+                camera.mode = match camera.mode {
+                    CameraMode::Orbit => CameraMode::Fly,
+                    CameraMode::Fly => CameraMode::Orbit,
+                }
             }
-            KeyCode::KeyP if pressed => {
+            Some(Action::ToggleProjection) if pressed => {
                 camera.projection = match camera.projection {
                     Projection::Perspective => Projection::Orthographic,
                     Projection::Orthographic => Projection::Perspective,
                 }
             }
-            KeyCode::KeyI if pressed => {
-                camera.projection = match camera.projection {
-                    Projection::Perspective => Projection::Orthographic,
-                    Projection::Orthographic => Projection::Perspective,
+            Some(
+                action @ (Action::FlyForward
+                | Action::FlyBack
+                | Action::FlyLeft
+                | Action::FlyRight
+                | Action::FlyDown
+                | Action::FlyUp),
+            ) => {
+                if pressed {
+                    self.state.move_keys.insert(action);
+                } else {
+                    self.state.move_keys.remove(&action);
                 }
             }
             _ => {}
@@ -133,7 +436,13 @@ impl InputController {
             let dx = x - last_x;
             let dy = y - last_y;
 
-            if self.state.is_rotating() {
+            if self.state.is_rotating() && camera.mode == CameraMode::Fly {
+                let (dx, dy) = self.drag_axes(dx, dy);
+                let sensitivity = self.config.rotate_sensitivity;
+                let limits = (self.config.min_elevation, self.config.max_elevation);
+                camera.look(-dx * sensitivity, dy * sensitivity, limits);
+                updated = true;
+            } else if self.state.is_rotating() {
                 self.rotate_camera(camera, dx, dy);
                 updated = true;
             } else if self.state.is_panning() {
@@ -146,18 +455,79 @@ impl InputController {
         updated
     }
 
-    /// Handle mouse scroll for zooming.
+    /// Handle mouse scroll for zooming, or in fly mode for changing speed.
     pub fn handle_scroll(&mut self, delta: MouseScrollDelta, camera: &mut Camera) {
         let scroll_amount = match delta {
             MouseScrollDelta::LineDelta(_, y) => y,
             MouseScrollDelta::PixelDelta(pos) => pos.y as f32 * 0.1,
         };
+        let scroll_amount = match self.config.invert_zoom {
+            true => -scroll_amount,
+            false => scroll_amount,
+        };
+
+        match camera.mode {
+            CameraMode::Orbit => self.zoom_camera(camera, scroll_amount),
+            CameraMode::Fly => {
+                let factor = (1.0 + self.config.zoom_sensitivity).powf(scroll_amount);
+                self.config.fly_speed = (self.config.fly_speed * factor).clamp(0.01, 10.0);
+            }
+        }
+    }
+
+    /// Forget held keys, e.g. when the window loses focus and won't see
+    /// them released.
+    pub fn release_keys(&mut self) {
+        self.state.move_keys.clear();
+        self.state.shift_pressed = false;
+    }
+
+    /// Move the camera by the held fly keys for the time since the last
+    /// call, in fixed steps. Called once per frame.
+    pub fn update(&mut self, camera: &mut Camera) {
+        for _ in 0..self.clock.tick(Instant::now()) {
+            self.fly(camera, UPDATE_STEP);
+        }
+    }
+
+    /// Move eye and target along the view for `dt` seconds of held fly
+    /// keys: by default W and S forward and back, A and D sideways, Q and E
+    /// down and up.
+    fn fly(&self, camera: &mut Camera, dt: f32) {
+        if camera.mode != CameraMode::Fly || self.state.move_keys.is_empty() {
+            return;
+        }
+        let forward = camera.forward();
+        let right = forward.cross(Vec3::Y).normalize_or_zero();
+        let direction: Vec3 = self
+            .state
+            .move_keys
+            .iter()
+            .filter_map(|action| action.fly_direction(forward, right))
+            .sum();
+
+        let fast = if self.state.shift_pressed {
+            FAST_FLY
+        } else {
+            1.0
+        };
+        let speed = camera.distance * self.config.fly_speed * fast;
+        camera.target += direction.normalize_or_zero() * speed * dt;
+    }
 
-        self.zoom_camera(camera, scroll_amount);
+    /// Mouse delta with the inverted axes flipped.
+    fn drag_axes(&self, dx: f32, dy: f32) -> (f32, f32) {
+        let flip = |delta: f32, invert: bool| if invert { -delta } else { delta };
+        (
+            flip(dx, self.config.invert_x),
+            flip(dy, self.config.invert_y),
+        )
     }
 
     /// Rotate camera based on mouse delta.
     fn rotate_camera(&self, camera: &mut Camera, dx: f32, dy: f32) {
+        let (dx, dy) = self.drag_axes(dx, dy);
+
         // Horizontal movement rotates azimuth
         camera.azimuth -= dx * self.config.rotate_sensitivity;
 
@@ -270,6 +640,96 @@ mod tests {
         assert_eq!(camera.distance, 50.0);
     }
 
+    #[test]
+    fn test_fly_mode() {
+        let mut controller = InputController::new();
+        let mut camera = Camera::new();
+        controller.handle_keyboard(KeyCode::KeyV, ElementState::Pressed, &mut camera);
+        assert_eq!(camera.mode, CameraMode::Fly);
+
+        // Dragging looks around from the eye
+        let eye = camera.position();
+        controller.state.left_pressed = true;
+        controller.state.last_mouse_pos = Some((0.0, 0.0));
+        controller.handle_mouse_move(40.0, 10.0, &mut camera);
+        assert!((camera.position() - eye).length() < 1e-3);
+        assert_ne!(camera.target, Vec3::ZERO);
+
+        // W flies along the view, half a distance per second
+        let eye = camera.position();
+        let forward = camera.forward();
+        controller.handle_keyboard(KeyCode::KeyW, ElementState::Pressed, &mut camera);
+        controller.fly(&mut camera, 1.0);
+        assert!((camera.position() - (eye + forward * 25.0)).length() < 1e-3);
+
+        // Opposite keys cancel; released keys stop
+        controller.handle_keyboard(KeyCode::KeyS, ElementState::Pressed, &mut camera);
+        let eye = camera.position();
+        controller.fly(&mut camera, 1.0);
+        assert!((camera.position() - eye).length() < 1e-3);
+        controller.release_keys();
+        controller.handle_keyboard(KeyCode::KeyE, ElementState::Pressed, &mut camera);
+        controller.handle_keyboard(KeyCode::KeyE, ElementState::Released, &mut camera);
+        controller.fly(&mut camera, 1.0);
+        assert!((camera.position() - eye).length() < 1e-3);
+
+        // Scrolling changes the speed instead of zooming
+        controller.handle_scroll(MouseScrollDelta::LineDelta(0.0, 1.0), &mut camera);
+        assert_eq!(camera.distance, 50.0);
+        assert!(controller.config.fly_speed > 0.5);
+
+        // Reset keeps the mode
+        controller.handle_keyboard(KeyCode::KeyR, ElementState::Pressed, &mut camera);
+        assert_eq!(camera.mode, CameraMode::Fly);
+    }
+
+    #[test]
+    fn test_remapped_keys_and_inverted_axes() {
+        let mut controller = InputController::new();
+        let mut camera = Camera::new();
+        controller
+            .config
+            .keys
+            .set(Action::ResetCamera, vec![KeyCode::KeyX]);
+        controller
+            .config
+            .keys
+            .set(Action::FlyForward, vec![KeyCode::ArrowUp, KeyCode::KeyI]);
+
+        // Binding a key takes it from the action it had
+        assert_eq!(
+            controller.config.keys.keys(Action::ToggleProjection),
+            [KeyCode::KeyP]
+        );
+        assert_eq!(controller.config.keys.label(Action::FlyForward), "Up / I");
+
+        camera.distance = 100.0;
+        controller.handle_keyboard(KeyCode::KeyR, ElementState::Pressed, &mut camera);
+        assert_eq!(camera.distance, 100.0);
+        controller.handle_keyboard(KeyCode::KeyX, ElementState::Pressed, &mut camera);
+        assert_eq!(camera.distance, 50.0);
+
+        controller.handle_keyboard(KeyCode::ArrowUp, ElementState::Pressed, &mut camera);
+        assert!(controller.state.move_keys.contains(&Action::FlyForward));
+        controller.handle_keyboard(KeyCode::KeyW, ElementState::Pressed, &mut camera);
+        assert_eq!(controller.state.move_keys.len(), 1);
+
+        // Inverted axes turn and zoom the other way
+        let azimuth = camera.azimuth;
+        controller.config.invert_x = true;
+        controller.config.invert_zoom = true;
+        controller.state.left_pressed = true;
+        controller.state.last_mouse_pos = Some((0.0, 0.0));
+        controller.handle_mouse_move(10.0, 0.0, &mut camera);
+        assert!(camera.azimuth > azimuth);
+        controller.handle_scroll(MouseScrollDelta::LineDelta(0.0, 1.0), &mut camera);
+        assert!(camera.distance > 50.0);
+
+        assert_eq!(key_from_name("pagedown"), Some(KeyCode::PageDown));
+        assert_eq!(key_name(KeyCode::Digit3), Some("3"));
+        assert_eq!(key_from_name("Hyper"), None);
+    }
+
     #[test]
     fn test_zoom_limits() {
         let mut controller = InputController::new();
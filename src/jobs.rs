@@ -0,0 +1,511 @@
+//! Batch render jobs for `lrle jobs`.
+//!
+//! A job file lists renders to run headlessly, e.g. to produce a set of
+//! figures overnight. It's YAML, so JSON works as well:
+//!
+//! ```yaml
+//! defaults:
+//!   input: dem.tif
+//!   width: 1920
+//!   height: 1080
+//! jobs:
+//!   - output: figures/overview.png
+//!   - output: figures/north.png
+//!     camera: {azimuth: 0, elevation: 20}
+//!     mode: both
+//!   - input: survey-2023.tif
+//!     output: figures/detail.png
+//!     camera: {target: [512, 80, 512], distance: 150, azimuth: 135, elevation: 20, fov: 45}
+//!     color_scheme: heatmap
+//!     contour_interval: 10
+//! ```
+//!
+//! Each job takes the `defaults`, then its own keys. Paths are relative to
+//! the job file. Without a `target`, the camera frames the whole terrain
+//! from its azimuth and elevation. A job that fails, because its entry is
+//! invalid, its input doesn't load or the render fails, is reported and the
+//! others still run.
+
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use glam::DVec3;
+use serde_json::{Map, Value};
+use winit::dpi::PhysicalSize;
+
+use lrle::renderer::camera::Camera;
+use lrle::renderer::camera_path::Keyframe;
+use lrle::renderer::{screenshot, RenderMode, Renderer};
+use lrle::terrain::{load_terrain, ColorScheme, LoadOptions, TerrainData};
+
+/// Keys a job may set.
+const KEYS: [&str; 11] = [
+    "input",
+    "output",
+    "width",
+    "height",
+    "camera",
+    "mode",
+    "color_scheme",
+    "height_scale",
+    "shadows",
+    "ambient_occlusion",
+    "contour_interval",
+];
+
+/// Camera of a render job.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JobCamera {
+    /// Horizontal rotation in radians
+    pub azimuth: f32,
+    /// Vertical rotation in radians
+    pub elevation: f32,
+    /// Vertical field of view in degrees, if not the default
+    pub fov: Option<f32>,
+    /// Point looked at, in terrain coordinates, and the distance from it;
+    /// `None` frames the whole terrain
+    pub view: Option<(DVec3, f32)>,
+}
+
+impl Default for JobCamera {
+    fn default() -> Self {
+        Self {
+            azimuth: 45f32.to_radians(),
+            elevation: 30f32.to_radians(),
+            fov: None,
+            view: None,
+        }
+    }
+}
+
+/// One render: a terrain, how to draw it and the PNG to write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderJob {
+    /// Terrain file
+    pub input: PathBuf,
+    /// PNG written
+    pub output: PathBuf,
+    /// Image width and height in pixels
+    pub size: (u32, u32),
+    /// Camera pose the image is rendered from
+    pub camera: JobCamera,
+    /// Solid surface, wireframe or both
+    pub render_mode: RenderMode,
+    /// Colors of the heights
+    pub color_scheme: ColorScheme,
+    /// Height scale multiplier
+    pub height_scale: f32,
+    /// Cast terrain shadows from the light
+    pub shadows: bool,
+    /// Bake ambient occlusion into the surface
+    pub ambient_occlusion: bool,
+    /// Height difference between contour lines, if drawn
+    pub contour_interval: Option<f32>,
+}
+
+impl RenderJob {
+    /// Render of `input` to `output` at 1920x1080 with default settings.
+    pub fn new(input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        Self {
+            input: input.into(),
+            output: output.into(),
+            size: (1920, 1080),
+            camera: JobCamera::default(),
+            render_mode: RenderMode::default(),
+            color_scheme: ColorScheme::default(),
+            height_scale: 1.0,
+            shadows: false,
+            ambient_occlusion: false,
+            contour_interval: None,
+        }
+    }
+
+    /// Parse a job entry, with its paths relative to `base`.
+    fn from_json(value: &Value, base: &Path) -> Result<Self> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| anyhow!("Expected a mapping of job settings"))?;
+        if let Some(key) = object.keys().find(|key| !KEYS.contains(&key.as_str())) {
+            bail!("Unknown key \"{key}\"");
+        }
+        let path = |key: &str| -> Result<PathBuf> {
+            let path = object
+                .get(key)
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("missing path \"{key}\""))?;
+            Ok(base.join(path))
+        };
+        let mut job = Self::new(path("input")?, path("output")?);
+
+        let side = |key: &str, default: u32| -> Result<u32> {
+            match object.get(key) {
+                None => Ok(default),
+                Some(value) => value
+                    .as_u64()
+                    .and_then(|side| u32::try_from(side).ok())
+                    .filter(|&side| side > 0)
+                    .ok_or_else(|| anyhow!("\"{key}\" must be a positive whole number")),
+            }
+        };
+        job.size = (side("width", job.size.0)?, side("height", job.size.1)?);
+        if let Some(camera) = object.get("camera") {
+            job.camera = parse_camera(camera).context("camera")?;
+        }
+        if let Some(mode) = enum_value(object, "mode")? {
+            job.render_mode = mode;
+        }
+        if let Some(scheme) = enum_value(object, "color_scheme")? {
+            job.color_scheme = scheme;
+        }
+        if let Some(scale) = number(object, "height_scale")? {
+            job.height_scale = scale as f32;
+        }
+        job.shadows = flag(object, "shadows")?;
+        job.ambient_occlusion = flag(object, "ambient_occlusion")?;
+        job.contour_interval = match number(object, "contour_interval")? {
+            Some(interval) if interval <= 0.0 => bail!("\"contour_interval\" must be positive"),
+            interval => interval.map(|interval| interval as f32),
+        };
+        Ok(job)
+    }
+
+    /// Draw `terrain` as this job describes and write the PNG, creating
+    /// its directory if needed.
+    pub fn render(&self, renderer: &mut Renderer, terrain: &TerrainData) -> Result<()> {
+        let (width, height) = self.size;
+        renderer.resize(PhysicalSize::new(width, height));
+        // Everything a job sets is set every time, so jobs run in any order
+        let settings = &mut renderer.settings;
+        settings.render_mode = self.render_mode;
+        settings.color_scheme = self.color_scheme;
+        settings.shadows = self.shadows;
+        settings.ambient_occlusion = self.ambient_occlusion;
+        settings.contour.enabled = self.contour_interval.is_some();
+        if let Some(interval) = self.contour_interval {
+            settings.contour.interval = interval;
+        }
+        renderer.camera = Camera::new();
+        renderer.upload_terrain(terrain, self.height_scale);
+
+        let camera = self.camera;
+        let fov = camera.fov.unwrap_or(renderer.camera.fov);
+        match camera.view {
+            Some((target, distance)) => renderer.set_camera_pose(&Keyframe {
+                time: 0.0,
+                target,
+                distance,
+                azimuth: camera.azimuth,
+                elevation: camera.elevation,
+                fov,
+            }),
+            None => {
+                renderer.camera.azimuth = camera.azimuth;
+                renderer.camera.elevation = camera.elevation;
+                renderer.camera.fov = fov;
+                renderer.frame_scene();
+            }
+        }
+
+        let pixels = renderer
+            .capture_frame()
+            .context("Failed to render the frame")?;
+        if let Some(dir) = self.output.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.output, screenshot::to_png(&pixels, self.size)?)
+            .with_context(|| format!("Failed to write {}", self.output.display()))?;
+        Ok(())
+    }
+}
+
+/// Parse the jobs of a job file, with paths relative to `base`. Errors
+/// for the file as a whole, such as invalid YAML, fail the parse; an
+/// invalid job fails only its own entry.
+pub fn parse(text: &str, base: &Path) -> Result<Vec<Result<RenderJob>>> {
+    let file: Value = serde_yaml::from_str(text).context("Invalid YAML")?;
+    let jobs = file
+        .get("jobs")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("Expected a mapping with a \"jobs\" list"))?;
+    let defaults = match file.get("defaults") {
+        None => Map::new(),
+        Some(Value::Object(defaults)) => defaults.clone(),
+        Some(_) => bail!("\"defaults\" must be a mapping"),
+    };
+
+    Ok(jobs
+        .iter()
+        .map(|job| {
+            let mut merged = defaults.clone();
+            if let Value::Object(job) = job {
+                merged.extend(job.clone());
+            } else {
+                bail!("Expected a mapping of job settings");
+            }
+            RenderJob::from_json(&Value::Object(merged), base)
+        })
+        .collect())
+}
+
+/// Outcome of a job file.
+#[derive(Debug)]
+pub struct Summary {
+    /// Jobs in the file
+    pub total: usize,
+    /// Output (or position, if it has none) and error of each failed job
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+/// Run every job in the file at `path`, printing each one's progress and
+/// outcome. Fails only if the file itself can't be read or parsed.
+pub fn run(path: &Path) -> Result<Summary> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let jobs =
+        parse(&text, base).with_context(|| format!("Invalid job file {}", path.display()))?;
+    let total = jobs.len();
+
+    let mut runner = Runner::default();
+    let mut failed = Vec::new();
+    let start = Instant::now();
+    for (i, job) in jobs.into_iter().enumerate() {
+        let job_start = Instant::now();
+        let label = job.as_ref().map_or_else(
+            |_| format!("job {}", i + 1),
+            |job| job.output.display().to_string(),
+        );
+        match job.and_then(|job| runner.run(&job)) {
+            Ok(()) => println!(
+                "[{}/{}] Wrote {} in {:.1} s",
+                i + 1,
+                total,
+                label,
+                job_start.elapsed().as_secs_f32()
+            ),
+            Err(err) => {
+                eprintln!("[{}/{}] {} failed: {:#}", i + 1, total, label, err);
+                failed.push((label, err));
+            }
+        }
+    }
+    println!(
+        "Rendered {} of {} jobs in {:.1} s",
+        total - failed.len(),
+        total,
+        start.elapsed().as_secs_f32()
+    );
+    Ok(Summary { total, failed })
+}
+
+/// Renderer and last terrain shared by consecutive jobs.
+#[derive(Default)]
+struct Runner {
+    renderer: Option<Renderer>,
+    terrain: Option<(PathBuf, TerrainData)>,
+}
+
+impl Runner {
+    fn run(&mut self, job: &RenderJob) -> Result<()> {
+        // Jobs on the same terrain usually come together; keeping just the
+        // last one bounds memory on long lists of large DEMs
+        if self
+            .terrain
+            .as_ref()
+            .is_none_or(|(path, _)| *path != job.input)
+        {
+            self.terrain = None;
+            let terrain = load_terrain(&job.input, &LoadOptions::default())
+                .with_context(|| format!("Failed to load {}", job.input.display()))?;
+            self.terrain = Some((job.input.clone(), terrain));
+        }
+        let Some((_, terrain)) = &self.terrain else {
+            unreachable!("terrain loaded above");
+        };
+
+        let renderer = match &mut self.renderer {
+            Some(renderer) => renderer,
+            None => {
+                let (width, height) = job.size;
+                self.renderer
+                    .insert(pollster::block_on(Renderer::new_headless(width, height))?)
+            }
+        };
+        // A panic, e.g. a GPU validation error, fails only this job; the
+        // next one starts over with a new renderer
+        std::panic::catch_unwind(AssertUnwindSafe(|| job.render(renderer, terrain))).unwrap_or_else(
+            |_| {
+                self.renderer = None;
+                Err(anyhow!("Renderer panicked"))
+            },
+        )
+    }
+}
+
+/// Parse a job's camera; angles are in degrees.
+fn parse_camera(value: &Value) -> Result<JobCamera> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow!("Expected a mapping"))?;
+    if let Some(key) = object
+        .keys()
+        .find(|key| !["azimuth", "elevation", "fov", "target", "distance"].contains(&key.as_str()))
+    {
+        bail!("Unknown key \"{key}\"");
+    }
+    let mut camera = JobCamera::default();
+    if let Some(azimuth) = number(object, "azimuth")? {
+        camera.azimuth = (azimuth as f32).to_radians();
+    }
+    if let Some(elevation) = number(object, "elevation")? {
+        camera.elevation = (elevation as f32).to_radians();
+    }
+    camera.fov = number(object, "fov")?.map(|fov| fov as f32);
+
+    let distance = number(object, "distance")?;
+    camera.view = match object.get("target") {
+        None if distance.is_some() => bail!("\"distance\" needs a \"target\""),
+        None => None,
+        Some(target) => {
+            let xyz: Vec<f64> = target
+                .as_array()
+                .map(|xyz| xyz.iter().filter_map(Value::as_f64).collect())
+                .unwrap_or_default();
+            let [x, y, z] = xyz[..] else {
+                bail!("\"target\" must be a list of 3 numbers");
+            };
+            let distance = distance.ok_or_else(|| anyhow!("\"target\" needs a \"distance\""))?;
+            if distance <= 0.0 {
+                bail!("\"distance\" must be positive");
+            }
+            Some((DVec3::new(x, y, z), distance as f32))
+        }
+    };
+    Ok(camera)
+}
+
+fn number(object: &Map<String, Value>, key: &str) -> Result<Option<f64>> {
+    object
+        .get(key)
+        .map(|value| {
+            value
+                .as_f64()
+                .ok_or_else(|| anyhow!("\"{key}\" must be a number"))
+        })
+        .transpose()
+}
+
+fn flag(object: &Map<String, Value>, key: &str) -> Result<bool> {
+    object.get(key).map_or(Ok(false), |value| {
+        value
+            .as_bool()
+            .ok_or_else(|| anyhow!("\"{key}\" must be true or false"))
+    })
+}
+
+/// A value of a command-line enum, by the name the command line uses.
+fn enum_value<T: ValueEnum>(object: &Map<String, Value>, key: &str) -> Result<Option<T>> {
+    let Some(value) = object.get(key) else {
+        return Ok(None);
+    };
+    let name = value
+        .as_str()
+        .ok_or_else(|| anyhow!("\"{key}\" must be a name"))?;
+    T::from_str(name, true).map(Some).map_err(|_| {
+        let names: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|variant| Some(variant.to_possible_value()?.get_name().to_string()))
+            .collect();
+        anyhow!(
+            "Unknown {key} \"{name}\", expected one of {}",
+            names.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_jobs_with_defaults() {
+        let jobs = parse(
+            r#"
+defaults:
+  input: dem.tif
+  width: 640
+  height: 480
+jobs:
+  - output: figures/overview.png
+  - output: figures/detail.png
+    width: 320
+    mode: both
+    color_scheme: heatmap
+    contour_interval: 10
+    camera: {target: [512, 80, 512], distance: 150, azimuth: 90, fov: 45}
+"#,
+            Path::new("talk"),
+        )
+        .unwrap();
+        let jobs: Vec<RenderJob> = jobs.into_iter().map(Result::unwrap).collect();
+
+        assert_eq!(jobs[0].input, Path::new("talk/dem.tif"));
+        assert_eq!(jobs[0].output, Path::new("talk/figures/overview.png"));
+        assert_eq!(jobs[0].size, (640, 480));
+        assert_eq!(jobs[0].camera, JobCamera::default());
+
+        let detail = &jobs[1];
+        assert_eq!(detail.size, (320, 480));
+        assert_eq!(detail.render_mode, RenderMode::Both);
+        assert_eq!(detail.color_scheme, ColorScheme::Heatmap);
+        assert_eq!(detail.contour_interval, Some(10.0));
+        assert_eq!(detail.camera.azimuth, std::f32::consts::FRAC_PI_2);
+        assert_eq!(detail.camera.elevation, 30f32.to_radians());
+        assert_eq!(detail.camera.fov, Some(45.0));
+        assert_eq!(
+            detail.camera.view,
+            Some((DVec3::new(512.0, 80.0, 512.0), 150.0))
+        );
+    }
+
+    #[test]
+    fn test_invalid_jobs_fail_alone() {
+        let jobs = parse(
+            r#"
+jobs:
+  - {input: a.fdf, output: a.png}
+  - {input: a.fdf}
+  - {input: a.fdf, output: b.png, mode: shaded}
+  - {input: a.fdf, output: c.png, widht: 100}
+  - {input: a.fdf, output: d.png, camera: {distance: 10}}
+  - output.png
+"#,
+            Path::new(""),
+        )
+        .unwrap();
+        let errors: Vec<String> = jobs
+            .iter()
+            .map(|job| {
+                job.as_ref()
+                    .map_or_else(|err| format!("{err:#}"), |_| "ok".into())
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "ok",
+                "missing path \"output\"",
+                "Unknown mode \"shaded\", expected one of wireframe, solid, both",
+                "Unknown key \"widht\"",
+                "camera: \"distance\" needs a \"target\"",
+                "Expected a mapping of job settings",
+            ]
+        );
+
+        assert!(parse("jobs: [", Path::new("")).is_err());
+        assert!(parse("- {input: a.fdf}", Path::new("")).is_err());
+    }
+}
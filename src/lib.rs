@@ -0,0 +1,33 @@
+//! # lrle - A Terrain Visualization Library
+//!
+//! The terrain loading, meshing and rendering behind the `lrle` viewer, for
+//! use in other applications:
+//!
+//! - [`terrain`] - [`TerrainData`](terrain::TerrainData) height grids, the
+//!   file loaders and [`TerrainMesh`](terrain::TerrainMesh) generation,
+//!   plus analysis and export, and ray and height queries against the
+//!   surface through [`Heightfield`](terrain::Heightfield)
+//! - [`renderer`] - The wgpu [`Renderer`](renderer::Renderer) and its
+//!   camera, settings and passes
+//! - [`TerrainView`] - A renderer drawing into textures of the host's
+//!   device, see [`view`]
+//! - [`render_tile`] - Hillshade and color relief web map tiles, drawn on
+//!   the CPU, see [`terrain::tiles`]
+//! - [`input`] - Mouse and keyboard camera control
+//!
+//! The `lrle` binary is a thin command line and window wrapper around
+//! these. [`ui`], [`config`] and [`update`] are the viewer's panel, settings
+//! file and release check, public as the renderer uses them, and
+//! [`session`] keeps how each terrain was last shown.
+
+pub mod config;
+pub mod input;
+pub mod renderer;
+pub mod session;
+pub mod terrain;
+pub mod ui;
+pub mod update;
+pub mod view;
+
+pub use terrain::tiles::render_tile;
+pub use view::TerrainView;
@@ -7,50 +7,670 @@
 //! ```bash
 //! lrle terrain.fdf                    # Load file with defaults
 //! lrle terrain.fdf --height-scale 2.0 # Load with height multiplier
+//! lrle terrain.fdf --mode both        # Solid surface with wireframe
+//! lrle heightmap.png                  # 8- or 16-bit grayscale heightmap
+//! lrle dem.tif --max-raster-size 2048 # GeoTIFF DEM, averaged down
+//! lrle 2019.tif 2024.tif              # Two surveys, overlaid
+//! lrle tile_a.tif tile_b.tif --stitch 0.5  # Close cracks between tiles
+//! lrle dem.tif --lod                  # Coarser distant chunks
+//! lrle lidar.tif --smooth 1.5 --downsample 4  # Tame a noisy, dense grid
+//! lrle terrain.fdf --msaa 4           # Antialiased edges and lines
+//! lrle terrain.fdf --vsync off --max-fps 144
+//! lrle terrain.fdf --adapter nvidia   # Pick the GPU on hybrid laptops
+//! lrle dem.tif --camera-path tour.json --perf-log perf.csv
+//! lrle dem.asc --nodata-fill 0        # ESRI ASCII grid, holes at 0
+//! lrle terrain.fdf --color-shader examples/color_slope.wgsl
+//! lrle terrain.fdf --export-ridgelines ridges.geojson
+//! lrle terrain.fdf --ambient-occlusion --export-gltf terrain.glb
+//! lrle terrain.fdf --lightmap --export-obj terrain.obj
+//! lrle terrain.fdf --export-usdz terrain.usdz
+//! lrle terrain.fdf --export terrain.obj   # Format by extension
+//! lrle dem.tif --export terrain.glb --max-triangles 100000
+//! lrle dem.tif --export terrain.stl --max-triangles 500000  # For printing
+//! lrle dem.tif --tin 0.5 --export terrain.glb  # Adaptive triangles
+//! lrle dem.tif --tin 0.5 --breaklines roads.geojson
+//! lrle dem.tif --export-colors colors.tif  # Georeferenced color image
+//! lrle terrain.fdf --transparent      # Overlay window without background
+//! lrle terrain.fdf --screenshot-dir shots  # F12 saves PNGs here
+//! lrle dem.tif --present 1920x1080 --camera-path talk.json
+//! lrle terrain.fdf --reference before.fdf  # Height change since a survey
+//! lrle dem.tif --channel temperature=temp.asc
+//! lrle dem.tif --vector-field wind_u.asc wind_v.asc
+//! lrle dem.tif --points wells.csv     # Markers with attributes on hover
+//! lrle dem.tif --texture ortho.jpg --mode solid  # Draped imagery
+//! lrle dem.tif --environment sky.hdr --mode solid  # Image-based lighting
+//! lrle dem.tif --uncertainty sigma.asc
+//! lrle terrain.fdf --units feet        # Label measurements in feet
+//! lrle dem.tif --water-level 12        # Flood up to a height
+//! lrle terrain.fdf --check-updates    # Notice when a new release is out
+//! lrle terrain.fdf --config trackpad.toml  # Other sensitivities and keys
+//! lrle terrain.fdf --fresh            # Not as it was last shown
+//! lrle contours terrain.fdf --interval 10 -o contours.geojson
+//! lrle render terrain.fdf -o preview.png --azimuth 45 --elevation 30
+//! lrle jobs figures.yaml              # Batch renders from a job file
+//! lrle dump-scene terrain.fdf --json
+//! lrle gpu-bench                      # Frame rate of every GPU adapter
 //! ```
 //!
 //! ## Controls
 //!
+//! Keys below are the defaults; the config file can remap them.
+//!
 //! - `ESC` - Quit application
 //! - Left Drag: Rotate camera
 //! - Scroll: Zoom in/out
 //! - Shift+Drag / Middle Drag: Pan
-//! - R: Reset camera
+//! - Right Click: Use the selected tool, e.g. delineate a catchment
+//! - Right Drag: Draw freehand with the polyline and polygon tools, paint,
+//!   or select a region
+//! - R: Reset camera, framing the whole terrain
+//! - F: Frame the whole terrain, keeping the view direction
+//! - V: Toggle fly mode (drag to look, WASD to move, Q/E down/up)
+//! - T: Toggle turntable auto-rotation
 //! - Tab: Toggle UI panel
+//! - F12: Save a screenshot
+//! - F5: Toggle presentation mode (no UI)
+//! - 1-9: Jump to a bookmarked view, or without bookmarks a camera path
+//!   keyframe
+//! - Page Down / Page Up, Right / Left: Next / previous keyframe
+//! - Space: Play or pause the timeline
 //! - ESC: Quit
 
-mod input;
-mod renderer;
-mod terrain;
-mod ui;
+#[cfg(test)]
+mod harness;
+mod jobs;
+mod scene;
 
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use winit::{
     application::ApplicationHandler,
-    event::{ElementState, KeyEvent, WindowEvent},
+    dpi::PhysicalSize,
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
-    window::{Window, WindowId},
+    window::{Window, WindowId, WindowLevel},
 };
 
-use input::InputController;
-use renderer::Renderer;
-use terrain::{load_fdf, TerrainData};
+use jobs::{JobCamera, RenderJob};
+use lrle::config::Config;
+use lrle::input::{Action, InputConfig, InputController};
+use lrle::renderer::adapter::{self, AdapterChoice};
+use lrle::renderer::bench::{self, BenchOptions};
+use lrle::renderer::bookmarks::Bookmarks;
+use lrle::renderer::camera_path::Keyframe;
+use lrle::renderer::layers;
+use lrle::renderer::load_failure::{LoadFailure, LoadTarget};
+use lrle::renderer::recovery::Autosave;
+use lrle::renderer::{
+    FrameScheduler, LightingConfig, PacingConfig, RenderMode, RenderStrategy, Renderer, Vsync,
+};
+use lrle::session::Session;
+use lrle::terrain::annotations;
+use lrle::terrain::breaklines;
+use lrle::terrain::color_map::{self, ColorMap};
+use lrle::terrain::contours::{self, ContourFormat};
+use lrle::terrain::export::{self, MeshFormat};
+use lrle::terrain::lightmap::{self, LightmapOptions};
+use lrle::terrain::processing::Processing;
+use lrle::terrain::relief::{self, ReferenceSurface, ReliefConfig};
+use lrle::terrain::simplify;
+use lrle::terrain::tin;
+use lrle::terrain::units::Units;
+use lrle::terrain::vectors::VectorField;
+use lrle::terrain::{
+    analysis, ao, load_terrain, raster, ColorScheme, LoadOptions, MeshOptions, TerrainData,
+    TerrainFormat, TerrainMesh,
+};
+use lrle::update::{UpdateChannel, UpdateCheck};
+use scene::SceneSummary;
 
 /// Command-line arguments for lrle
 #[derive(Parser, Debug)]
 #[command(name = "lrle")]
 #[command(version, about = "Modern terrain visualization tool", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// Path to .fdf file to load
-    file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the terrain file to load (.fdf, a .png or .jpg heightmap, or a
+    /// .tif or .asc DEM)
+    #[arg(required = true)]
+    file: Option<String>,
+
+    /// More terrain files, drawn next to the first and listed under
+    /// "Terrains" to move, hide or switch to
+    #[arg(value_name = "MORE")]
+    more: Vec<String>,
+
+    /// Average the border samples the terrains share where they differ by
+    /// at most this height, closing cracks between mosaicked tiles
+    #[arg(long, value_name = "TOLERANCE")]
+    stitch: Option<f32>,
+
+    /// Terrain file format, instead of picking it from the extension
+    #[arg(long, value_enum)]
+    format: Option<TerrainFormat>,
+
+    /// Average GeoTIFF and ASCII grid DEMs down until neither side exceeds
+    /// this many samples
+    #[arg(long, value_name = "SAMPLES", default_value_t = raster::DEFAULT_MAX_SIZE)]
+    max_raster_size: usize,
+
+    /// Height for nodata cells of GeoTIFF and ASCII grid DEMs, instead of
+    /// filling them from their neighbors
+    #[arg(long, value_name = "HEIGHT", allow_negative_numbers = true)]
+    nodata_fill: Option<f64>,
+
+    /// Blur the heights with a Gaussian of this standard deviation in
+    /// samples after loading, e.g. to tame LiDAR noise
+    #[arg(long, value_name = "SIGMA")]
+    smooth: Option<f64>,
+
+    /// Keep every this many samples along each side after loading, e.g. 4
+    /// for grids too dense to view raw
+    #[arg(long, value_name = "FACTOR", value_parser = clap::value_parser!(u64).range(2..))]
+    downsample: Option<u64>,
 
     /// Height scale multiplier (default: 1.0)
     #[arg(long, default_value = "1.0")]
     height_scale: f32,
+
+    /// Unit of the terrain's heights and cell size, instead of the one its
+    /// file gives, for labeling and converting measurements
+    #[arg(long, value_enum)]
+    units: Option<Units>,
+
+    /// Draw the terrain as a lit solid surface, a wireframe, or both
+    #[arg(long, value_enum, default_value_t)]
+    mode: RenderMode,
+
+    /// WGSL file defining `fn user_color(height: f32, normal: vec3f) -> vec3f`
+    /// used to color the solid surface (reloaded on change)
+    #[arg(long, value_name = "PATH")]
+    color_shader: Option<PathBuf>,
+
+    /// Write extracted ridge and valley lines to a GeoJSON file
+    #[arg(long, value_name = "PATH")]
+    export_ridgelines: Option<PathBuf>,
+
+    /// Color scheme mapping heights to colors
+    #[arg(long, value_enum, default_value_t)]
+    color_scheme: ColorScheme,
+
+    /// Color by height even when the terrain file gives per-point colors
+    #[arg(long)]
+    height_colors: bool,
+
+    /// Bake ambient occlusion into the mesh colors, in the viewer and in
+    /// glTF exports
+    #[arg(long)]
+    ambient_occlusion: bool,
+
+    /// Draw distant chunks of large terrains with coarser meshes
+    #[arg(long)]
+    lod: bool,
+
+    /// Samples per pixel for antialiasing (1, 4 or 8), lowered to what the
+    /// GPU supports
+    #[arg(long, value_name = "SAMPLES", default_value_t = 1, value_parser = parse_msaa)]
+    msaa: u32,
+
+    /// GPU to render on, by its index in the `gpu-bench` list or by part
+    /// of its name (e.g. "nvidia"); wgpu picks one by default
+    #[arg(long, value_name = "INDEX|NAME")]
+    adapter: Option<AdapterChoice>,
+
+    /// Wait for the display's vertical blank before showing frames (on),
+    /// or show them right away (off)
+    #[arg(long, value_enum)]
+    vsync: Option<Vsync>,
+
+    /// Draw at most this many frames per second
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u32).range(1..))]
+    max_fps: Option<u32>,
+
+    /// Write each frame's timings, draw calls, culled chunks and memory use
+    /// to a CSV file, to compare performance across versions
+    #[arg(long, value_name = "PATH")]
+    perf_log: Option<PathBuf>,
+
+    /// Write the terrain mesh to a file in the format its extension names:
+    /// .glb, .obj, .stl or .usdz
+    #[arg(long, value_name = "PATH")]
+    export: Option<PathBuf>,
+
+    /// Write the terrain mesh to a binary glTF (.glb) file
+    #[arg(long, value_name = "PATH")]
+    export_gltf: Option<PathBuf>,
+
+    /// Write the terrain mesh to a Wavefront OBJ file
+    #[arg(long, value_name = "PATH")]
+    export_obj: Option<PathBuf>,
+
+    /// Write the terrain mesh with its colors to a USDZ file for AR Quick
+    /// Look
+    #[arg(long, value_name = "PATH")]
+    export_usdz: Option<PathBuf>,
+
+    /// Bake lighting and shadows into a texture included with mesh exports
+    #[arg(long)]
+    lightmap: bool,
+
+    /// Simplify mesh exports to at most this many triangles, keeping ridges
+    /// and the outline, e.g. 100000 for game engines
+    #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u64).range(2..))]
+    max_triangles: Option<u64>,
+
+    /// Triangulate adaptively, within this height error of every sample,
+    /// in the viewer and in mesh exports
+    #[arg(long, value_name = "TOLERANCE")]
+    tin: Option<f32>,
+
+    /// Keep TIN edges along the roads, ridges and walls of a GeoJSON or DXF
+    /// file of polylines
+    #[arg(long, value_name = "PATH", requires = "tin")]
+    breaklines: Option<PathBuf>,
+
+    /// Write each sample's color to a georeferenced image: a .tif GeoTIFF,
+    /// or a .png with a world file beside it. The viewer draws a --texture
+    /// or --uncertainty over the colors, so neither can be exported
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["texture", "uncertainty"])]
+    export_colors: Option<PathBuf>,
+
+    /// Load a second grid and color the terrain by its height above it
+    #[arg(long, value_name = "PATH")]
+    reference: Option<String>,
+
+    /// Load a grid of other values, e.g. temperature or land cover classes,
+    /// as a named channel the terrain can be colored by (repeatable)
+    #[arg(long = "channel", value_name = "NAME=PATH")]
+    channels: Vec<String>,
+
+    /// Load a grid of per-sample height uncertainty (σ) as the
+    /// "uncertainty" channel and show it over the surface
+    #[arg(long, value_name = "PATH")]
+    uncertainty: Option<String>,
+
+    /// Load east and north components of a vector field, e.g. wind or
+    /// surface flow, and draw it as arrows draped over the terrain
+    #[arg(long, num_args = 2, value_names = ["EAST", "NORTH"])]
+    vector_field: Option<Vec<String>>,
+
+    /// Drape a PNG or JPEG image, e.g. a satellite or orthophoto, across
+    /// the terrain's extent in the solid modes
+    #[arg(long, value_name = "PATH")]
+    texture: Option<PathBuf>,
+
+    /// Light the solid surface from a Radiance HDR panorama, reflect it
+    /// and show it behind the terrain
+    #[arg(long, value_name = "PATH")]
+    environment: Option<PathBuf>,
+
+    /// Load point features such as wells from a CSV or GeoJSON file and
+    /// draw them as markers on the surface
+    #[arg(long, value_name = "PATH")]
+    points: Option<PathBuf>,
+
+    /// Start with the water plane shown at this height, e.g. 0 for sea
+    /// level or a flood stage
+    #[arg(long, value_name = "HEIGHT", allow_negative_numbers = true)]
+    water_level: Option<f32>,
+
+    /// Open a transparent, always-on-top window showing only the terrain
+    /// over the desktop, e.g. as a streaming overlay
+    #[arg(long)]
+    transparent: bool,
+
+    /// Directory screenshots taken with F12 are saved to (default: the
+    /// current directory)
+    #[arg(long, value_name = "DIR")]
+    screenshot_dir: Option<PathBuf>,
+
+    /// Present at a fixed resolution, e.g. 1920x1080: a borderless window
+    /// of that size that can't be resized, with all UI hidden
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_resolution)]
+    present: Option<(u32, u32)>,
+
+    /// Load camera path keyframes from a JSON file, e.g. to step through
+    /// them with the number keys and Page Up/Down
+    #[arg(long, value_name = "PATH")]
+    camera_path: Option<PathBuf>,
+
+    /// Check GitHub once at startup for a newer release, offering only full
+    /// releases or prereleases too, and show a notice when there is one
+    /// (default: the config file's `[updates] check`)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CHANNEL",
+        num_args = 0..=1,
+        default_missing_value = "stable"
+    )]
+    check_updates: Option<UpdateChannel>,
+
+    /// Settings file with mouse sensitivities, key bindings and the update
+    /// check (default: lrle/config.toml in the user's config directory)
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Start from the defaults instead of the window size, camera and
+    /// settings the terrain was last shown with
+    #[arg(long)]
+    fresh: bool,
+}
+
+impl Args {
+    /// Whether the main terrain's heights are needed before the window
+    /// opens, to process them, resample other grids onto them or export
+    /// them, so it can't load in the background.
+    fn needs_heights(&self) -> bool {
+        self.smooth.is_some()
+            || self.downsample.is_some()
+            || !self.channels.is_empty()
+            || self.uncertainty.is_some()
+            || self.vector_field.is_some()
+            || self.units.is_some()
+            || self.export_ridgelines.is_some()
+            || self.export.is_some()
+            || self.export_gltf.is_some()
+            || self.export_obj.is_some()
+            || self.export_usdz.is_some()
+            || self.export_colors.is_some()
+    }
+}
+
+/// Parse a `WIDTHxHEIGHT` resolution.
+fn parse_resolution(text: &str) -> Result<(u32, u32), String> {
+    let parse = |side: &str| side.trim().parse::<u32>().ok().filter(|&side| side > 0);
+    text.split_once(['x', 'X'])
+        .and_then(|(width, height)| Some((parse(width)?, parse(height)?)))
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, e.g. 1920x1080, got \"{text}\""))
+}
+
+/// Parse a `--msaa` sample count, one of the counts offered in the UI.
+fn parse_msaa(text: &str) -> Result<u32, String> {
+    text.trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|count| lrle::renderer::msaa::SAMPLE_COUNTS.contains(count))
+        .ok_or_else(|| format!("expected 1, 4 or 8 samples, got \"{text}\""))
+}
+
+/// Batch commands that run without opening a window.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Trace contour lines and write them as GeoJSON, DXF, or SVG
+    Contours {
+        /// Path to the terrain file to load
+        file: String,
+
+        /// Height difference between contour levels
+        #[arg(long)]
+        interval: f64,
+
+        /// Output file; the format is picked from the extension
+        /// (.geojson, .dxf, or .svg)
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+    },
+    /// Render one frame of a terrain offscreen, without opening a window,
+    /// and write it as a PNG
+    Render {
+        /// Path to the terrain file to load
+        file: String,
+
+        /// Output PNG file
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+
+        /// Image width in pixels
+        #[arg(long, default_value_t = 1920)]
+        width: u32,
+
+        /// Image height in pixels
+        #[arg(long, default_value_t = 1080)]
+        height: u32,
+
+        /// Camera azimuth in degrees around the terrain
+        #[arg(long, default_value_t = 45.0, allow_negative_numbers = true)]
+        azimuth: f32,
+
+        /// Camera elevation in degrees above the horizon
+        #[arg(long, default_value_t = 30.0, allow_negative_numbers = true)]
+        elevation: f32,
+
+        /// Height scale multiplier
+        #[arg(long, default_value = "1.0")]
+        height_scale: f32,
+
+        /// Draw the terrain as a lit solid surface, a wireframe, or both
+        #[arg(long, value_enum, default_value_t)]
+        mode: RenderMode,
+
+        /// Color scheme mapping heights to colors
+        #[arg(long, value_enum, default_value_t)]
+        color_scheme: ColorScheme,
+    },
+    /// Run the render jobs listed in a YAML file, e.g. a set of figures,
+    /// reporting each one and carrying on past failures
+    Jobs {
+        /// Job file listing inputs, cameras, settings and outputs
+        file: PathBuf,
+    },
+    /// Render a standard scene on every GPU adapter and report its frame
+    /// rate and upload throughput, to pick the one for `--adapter`
+    GpuBench {
+        /// Frames to time on each adapter
+        #[arg(long, default_value_t = 200)]
+        frames: u32,
+
+        /// Frame width in pixels
+        #[arg(long, default_value_t = 1920)]
+        width: u32,
+
+        /// Frame height in pixels
+        #[arg(long, default_value_t = 1080)]
+        height: u32,
+    },
+    /// Print the mesh statistics, bounds, normalization and default camera
+    /// the viewer would use for a terrain
+    DumpScene {
+        /// Path to the terrain file to load
+        file: String,
+
+        /// Height scale multiplier
+        #[arg(long, default_value = "1.0")]
+        height_scale: f32,
+
+        /// Print a JSON object instead of text
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Trace contours of `file` and write them to `output`.
+fn export_contours(file: &str, interval: f64, output: &Path) -> Result<()> {
+    let format = ContourFormat::from_path(output).ok_or_else(|| {
+        anyhow!(
+            "Unknown contour format for {}, expected .geojson, .dxf, or .svg",
+            output.display()
+        )
+    })?;
+    if interval.is_nan() || interval <= 0.0 {
+        bail!("Contour interval must be positive, got {}", interval);
+    }
+
+    let terrain = load_terrain(file, &LoadOptions::default())?;
+    let contours = contours::trace(&terrain, interval);
+    std::fs::write(output, format.write(&contours))?;
+    println!("Wrote {} contours to {}", contours.len(), output.display());
+    Ok(())
+}
+
+/// Run the GPU benchmark on every adapter and print how each did.
+fn gpu_bench(options: &BenchOptions) -> Result<()> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapters = adapter::list(&instance);
+    if adapters.is_empty() {
+        bail!("No GPU adapters were found");
+    }
+    let (width, height) = options.size;
+    println!(
+        "Rendering a {0}x{0} terrain at {width}x{height}, {1} frames on each adapter\n",
+        bench::SCENE_SIDE,
+        options.frames
+    );
+    for (index, gpu) in adapters.iter().enumerate() {
+        println!("{index}: {}", adapter::describe(&gpu.get_info()));
+        match bench::run(gpu, options) {
+            Ok(result) => println!(
+                "   {:.1} fps ({:.2} ms a frame), uploads at {:.0} MiB/s",
+                result.fps,
+                result.frame_time.as_secs_f64() * 1000.0,
+                result.upload_rate / (1024.0 * 1024.0)
+            ),
+            Err(err) => println!("   failed: {err:#}"),
+        }
+    }
+    println!("\nRun the viewer on one with --adapter <index or name>");
+    Ok(())
+}
+
+/// The mesh of `terrain` the exports write, built with `options` and
+/// colored like the viewer colors it at startup: by height above
+/// `reference` if given, and with ambient occlusion if `args` asks for it.
+fn export_surface(
+    terrain: &TerrainData,
+    args: &Args,
+    reference: Option<&TerrainData>,
+    options: &MeshOptions,
+) -> TerrainMesh {
+    let mut mesh = TerrainMesh::build(terrain, options);
+    let relief = ReliefConfig {
+        enabled: true,
+        reference: ReferenceSurface::Grid,
+        ..ReliefConfig::default()
+    };
+    if let Some(relative) = relief::relative_terrain(terrain, &relief, reference) {
+        mesh.recolor(&relative, options);
+    }
+    if args.ambient_occlusion {
+        mesh.apply_occlusion(&ao::bake(terrain, args.height_scale as f64));
+    }
+    mesh
+}
+
+/// Write the mesh exports requested in `args`, colored as by
+/// [`export_surface`], with a baked lightmap if enabled.
+fn export_mesh(terrain: &TerrainData, args: &Args, reference: Option<&TerrainData>) -> Result<()> {
+    let height_scale = args.height_scale as f64;
+    let options = MeshOptions {
+        height_scale: args.height_scale,
+        color_scheme: args.color_scheme,
+        file_colors: !args.height_colors,
+        ..MeshOptions::default()
+    };
+    let mesh = export_surface(terrain, args, reference, &options);
+
+    // Lit like the viewer's default view
+    let texture = if args.lightmap {
+        let lighting = LightingConfig::default();
+        let map = lightmap::bake(
+            terrain,
+            &mesh,
+            &LightmapOptions {
+                direction: lighting.direction.as_dvec3(),
+                ambient: lighting.ambient as f64,
+                height_scale,
+            },
+        );
+        Some((lightmap::grid_uvs(terrain), map.to_png()?))
+    } else {
+        None
+    };
+
+    // Vertices kept by triangulation and simplification carry their
+    // texture coordinates
+    let (mesh, texture) = match args.tin {
+        Some(tolerance) => {
+            let kept = match &args.breaklines {
+                Some(path) => breaklines::samples(terrain, &breaklines::load(path)?),
+                None => Vec::new(),
+            };
+            let adapted =
+                tin::triangulate(&mesh, terrain.width, tolerance * args.height_scale, &kept);
+            let (mesh, sources) = tin::compact(&adapted);
+            let texture = texture.map(|(uvs, png)| {
+                let uvs = sources.iter().map(|&i| uvs[i as usize]);
+                (uvs.collect(), png)
+            });
+            (mesh, texture)
+        }
+        None => (mesh, texture),
+    };
+    let (mesh, texture) = match args.max_triangles {
+        Some(max) => {
+            let simplified = simplify::simplify(&mesh, max as usize);
+            let texture = texture.map(|(uvs, png)| {
+                let uvs = simplified.sources.iter().map(|&i| uvs[i as usize]);
+                (uvs.collect(), png)
+            });
+            (simplified.mesh, texture)
+        }
+        None => (mesh, texture),
+    };
+    let texture = texture
+        .as_ref()
+        .map(|(uvs, png)| (uvs.as_slice(), png.as_slice()));
+    let exports = [
+        (&args.export, None),
+        (&args.export_gltf, Some(MeshFormat::Gltf)),
+        (&args.export_obj, Some(MeshFormat::Obj)),
+        (&args.export_usdz, Some(MeshFormat::Usdz)),
+    ];
+    for (path, format) in exports {
+        let Some(path) = path else {
+            continue;
+        };
+        match format {
+            Some(format) => export::write_as(format, &mesh, path, texture)?,
+            None => export::write(&mesh, path, texture)?,
+        }
+        log::info!("Wrote mesh to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Write the terrain's colors, as by [`export_surface`], to a
+/// georeferenced image at `path`.
+fn export_colors(
+    terrain: &TerrainData,
+    args: &Args,
+    reference: Option<&TerrainData>,
+    path: &Path,
+) -> Result<()> {
+    let options = MeshOptions {
+        color_scheme: args.color_scheme,
+        file_colors: !args.height_colors,
+        // sRGB colors, as the image stores them
+        linear_colors: false,
+        ..MeshOptions::default()
+    };
+    let mesh = export_surface(terrain, args, reference, &options);
+    let colors: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.color).collect();
+    color_map::write(&ColorMap::new(terrain, &colors), path)?;
+    log::info!("Wrote color map to {}", path.display());
+    Ok(())
 }
 
 /// Main application state managing window, renderer, and terrain mesh.
@@ -59,73 +679,409 @@ struct App {
     window: Option<Arc<Window>>,
     /// GPU renderer instance
     renderer: Option<Renderer>,
-    /// Terrain data for mesh generation
-    terrain: TerrainData,
+    /// Terrain data for mesh generation, or `None` while it's loaded in
+    /// the background
+    terrain: Option<TerrainData>,
+    /// File to load the terrain from in the background, and how
+    background_load: Option<(PathBuf, LoadOptions)>,
+    /// Float grid the terrain is an overview of, kept mapped to draw its
+    /// detail near the camera, and how it was loaded
+    mapped_grid: Option<(PathBuf, LoadOptions)>,
+    /// Why the terrain failed to load at startup, shown once the window
+    /// opens
+    load_failure: Option<LoadFailure>,
+    /// Name the terrain is listed under
+    name: String,
+    /// Other terrains to draw next to it, with their names
+    layers: Vec<(String, TerrainData)>,
+    /// Stitch the terrains' shared borders within this tolerance
+    stitch: Option<f32>,
     /// Height scale multiplier
     height_scale: f32,
+    /// Initial render mode
+    render_mode: RenderMode,
+    /// Optional user color function snippet
+    color_shader: Option<PathBuf>,
+    /// Initial color scheme
+    color_scheme: ColorScheme,
+    /// Start with the file's per-point colors, when it has them
+    file_colors: bool,
+    /// Start with baked ambient occlusion enabled
+    ambient_occlusion: bool,
+    /// Start with levels of detail enabled
+    lod: bool,
+    /// Start with the TIN strategy at this tolerance
+    tin: Option<f32>,
+    /// Breaklines file constraining the TIN
+    breaklines: Option<PathBuf>,
+    /// Point features file to show
+    points: Option<PathBuf>,
+    /// Image to drape over the terrain
+    texture: Option<PathBuf>,
+    /// HDR environment map lighting the scene
+    environment: Option<PathBuf>,
+    /// Samples per pixel to start with
+    msaa: u32,
+    /// GPU to render on, unless wgpu picks one
+    adapter: Option<AdapterChoice>,
+    /// Present mode and frame rate cap to start with
+    pacing: PacingConfig,
+    /// Start with the water plane at this height
+    water_level: Option<f32>,
+    /// Transparent, always-on-top overlay window
+    transparent: bool,
+    /// Directory screenshots are saved to
+    screenshot_dir: Option<PathBuf>,
+    /// CSV file frame timings are logged to
+    perf_log: Option<PathBuf>,
+    /// Fixed window size of presentation mode
+    present: Option<(u32, u32)>,
+    /// Camera path loaded at startup
+    camera_path: Option<PathBuf>,
+    /// File the terrain's camera bookmarks are kept in
+    bookmarks: Option<PathBuf>,
+    /// File the terrain's annotations are kept in
+    annotations: Option<PathBuf>,
+    /// Reference grid for relative relief
+    reference: Option<TerrainData>,
+    /// How files dropped on the window are loaded
+    drop_options: LoadOptions,
+    /// Vector field drawn over the terrain
+    vector_field: Option<VectorField>,
+    /// Terrain channel shown as height uncertainty
+    uncertainty: Option<usize>,
+    /// Release channel to check for updates on, if checking
+    check_updates: Option<UpdateChannel>,
+    /// Settings file read at startup, which input settings are saved to
+    config_file: Option<PathBuf>,
+    /// Directory unsaved edits are autosaved to for crash recovery
+    recovery_dir: Option<PathBuf>,
+    /// File the terrain was loaded from, which its bookmarks, annotations,
+    /// crash recovery and session follow
+    terrain_file: Option<PathBuf>,
+    /// File the terrain's session is saved to on exit
+    session_file: Option<PathBuf>,
+    /// Window size to open with, from the last session
+    window_size: Option<(u32, u32)>,
+    /// Camera pose to start with, from the last session
+    camera: Option<Keyframe>,
     /// Input controller for camera
     input: InputController,
+    /// When the next frame is drawn
+    scheduler: FrameScheduler,
 }
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        // Only create window once
-        if self.window.is_some() {
-            return;
+impl App {
+    /// Create the application for `terrain`, configured from the command
+    /// line. The window and renderer are created on resume.
+    fn new(terrain: Option<TerrainData>, args: &Args) -> Self {
+        Self {
+            window: None,
+            renderer: None,
+            terrain,
+            background_load: None,
+            mapped_grid: None,
+            load_failure: None,
+            name: args.file.as_deref().map_or_else(
+                || "Terrain".to_string(),
+                |file| layers::name_of(Path::new(file)),
+            ),
+            layers: Vec::new(),
+            stitch: args.stitch,
+            height_scale: args.height_scale,
+            render_mode: args.mode,
+            color_shader: args.color_shader.clone(),
+            color_scheme: args.color_scheme,
+            file_colors: !args.height_colors,
+            ambient_occlusion: args.ambient_occlusion,
+            lod: args.lod,
+            tin: args.tin,
+            breaklines: args.breaklines.clone(),
+            points: args.points.clone(),
+            texture: args.texture.clone(),
+            environment: args.environment.clone(),
+            msaa: args.msaa,
+            adapter: args.adapter.clone(),
+            pacing: PacingConfig {
+                present_mode: args.vsync.map(Vsync::present_mode).unwrap_or_default(),
+                max_fps: args.max_fps,
+                power_saving: false,
+            },
+            water_level: args.water_level,
+            transparent: args.transparent,
+            screenshot_dir: args.screenshot_dir.clone(),
+            perf_log: args.perf_log.clone(),
+            present: args.present,
+            camera_path: args.camera_path.clone(),
+            bookmarks: args
+                .file
+                .as_deref()
+                .map(|file| Bookmarks::file_for(Path::new(file))),
+            annotations: None,
+            reference: None,
+            drop_options: LoadOptions {
+                format: None,
+                max_size: args.max_raster_size,
+                nodata_fill: args.nodata_fill,
+            },
+            vector_field: None,
+            uncertainty: None,
+            check_updates: args.check_updates,
+            config_file: None,
+            recovery_dir: None,
+            terrain_file: args.file.as_deref().map(PathBuf::from),
+            session_file: None,
+            window_size: None,
+            camera: None,
+            input: InputController::new(),
+            scheduler: FrameScheduler::default(),
         }
+    }
 
-        let window_attrs = Window::default_attributes().with_title("lrle - Terrain Viewer");
+    /// Start the way the terrain was shown in `session`, except for the
+    /// settings `given` on the command line.
+    fn restore_session(&mut self, session: &Session, given: impl Fn(&str) -> bool) {
+        if !given("mode") {
+            self.render_mode = session.render_mode;
+        }
+        if !given("color_scheme") {
+            self.color_scheme = session.color_scheme;
+        }
+        if !given("height_scale") {
+            self.height_scale = session.height_scale;
+        }
+        self.window_size = session.window;
+        self.camera = session.camera;
+    }
 
-        let window = match event_loop.create_window(window_attrs) {
-            Ok(w) => Arc::new(w),
-            Err(e) => {
-                log::error!("Failed to create window: {}", e);
-                event_loop.exit();
-                return;
-            }
+    /// How the terrain is shown now, if it loaded.
+    fn session(&self) -> Option<Session> {
+        let renderer = self.renderer.as_ref().filter(|r| r.has_terrain())?;
+        let size = renderer.size;
+        Some(Session {
+            window: self.present.is_none().then_some((size.width, size.height)),
+            camera: Some(renderer.camera_pose()),
+            color_scheme: renderer.settings.color_scheme,
+            render_mode: renderer.settings.render_mode,
+            height_scale: renderer.settings.height_scale,
+        })
+    }
+
+    /// Save how the terrain is shown now to the session file, for its next
+    /// launch.
+    fn save_session(&self) {
+        let (Some(file), Some(terrain), Some(session)) =
+            (&self.session_file, &self.terrain_file, self.session())
+        else {
+            return;
         };
+        if let Err(err) = session.save(file, terrain) {
+            log::warn!("Failed to write {}: {:#}", file.display(), err);
+        }
+    }
 
-        match pollster::block_on(Renderer::new(window.clone())) {
-            Ok(mut renderer) => {
-                renderer.upload_terrain(&self.terrain, self.height_scale);
-                self.renderer = Some(renderer);
-                self.window = Some(window);
+    /// Upload the terrain and startup settings to a new renderer and start
+    /// drawing with it.
+    fn attach_renderer(&mut self, mut renderer: Renderer) {
+        renderer.settings.render_mode = self.render_mode;
+        renderer.settings.color_scheme = self.color_scheme;
+        renderer.settings.file_colors = self.file_colors;
+        renderer.settings.ambient_occlusion = self.ambient_occlusion;
+        renderer.settings.lod.enabled = self.lod;
+        if let Some(tolerance) = self.tin {
+            renderer.settings.strategy = RenderStrategy::Tin;
+            renderer.settings.tin_tolerance = tolerance;
+        }
+        if let Some(path) = &self.breaklines {
+            renderer.load_breaklines(path.clone());
+        }
+        if let Some(path) = &self.points {
+            renderer.load_points(path.clone());
+        }
+        if let Some(path) = &self.texture {
+            renderer.load_drape(path.clone());
+        }
+        if let Some(path) = &self.environment {
+            renderer.load_environment(path.clone());
+        }
+        renderer.settings.msaa = self.msaa;
+        renderer.settings.pacing = self.pacing;
+        if let Some(level) = self.water_level {
+            renderer.settings.water.enabled = true;
+            renderer.settings.water.level = level;
+        }
+        if let Some(tolerance) = self.stitch {
+            renderer.settings.stitch.enabled = true;
+            renderer.settings.stitch.tolerance = tolerance;
+        }
+        match (&self.terrain, &self.background_load) {
+            (Some(terrain), _) => {
+                renderer.upload_terrain(terrain, self.height_scale);
+                if let Some((file, options)) = &self.mapped_grid {
+                    if let Err(err) = renderer.map_main_terrain(file, *options) {
+                        log::warn!("Failed to map {}: {}", file.display(), err);
+                    }
+                }
             }
-            Err(e) => {
-                log::error!("Failed to create renderer: {}", e);
-                event_loop.exit();
+            (None, Some((file, options))) => {
+                renderer.settings.height_scale = self.height_scale;
+                renderer.load_main_terrain(file.clone(), *options);
+            }
+            (None, None) => {}
+        }
+        renderer.terrain_name = self.name.clone();
+        renderer.load_failure = self.load_failure.take();
+        for (name, terrain) in &self.layers {
+            renderer.add_terrain(name.clone(), terrain.clone());
+        }
+        renderer.frame_scene();
+        if let Some(pose) = self.camera {
+            renderer.restore_camera(pose);
+        }
+        if let Some(reference) = &self.reference {
+            renderer.set_reference(reference.clone());
+        }
+        if let Some(field) = &self.vector_field {
+            renderer.set_vector_field(field.clone());
+        }
+        if let Some(index) = self.uncertainty {
+            renderer.set_uncertainty(index);
+        }
+        if self.color_shader.is_some() {
+            renderer.set_color_snippet(self.color_shader.clone());
+        }
+        if let Some(dir) = &self.screenshot_dir {
+            renderer.screenshot_dir = dir.clone();
+        }
+        if let Some(path) = &self.camera_path {
+            if let Err(err) = renderer.import_camera_path(path) {
+                log::warn!("Failed to read {}: {:#}", path.display(), err);
+            }
+        }
+        if let Some(path) = &self.perf_log {
+            if let Err(err) = renderer.log_performance(path) {
+                log::warn!("{:#}", err);
+            }
+        }
+        if let Some(file) = &self.bookmarks {
+            if let Err(err) = renderer.load_bookmarks(file.clone()) {
+                log::warn!("Failed to read {}: {:#}", file.display(), err);
+            }
+        }
+        if let Some(file) = &self.annotations {
+            if let Err(err) = renderer.load_annotations(file.clone()) {
+                log::warn!("Failed to read {}: {:#}", file.display(), err);
+            }
+        }
+        if let Some(file) = &self.config_file {
+            match Config::load(file.clone()) {
+                Ok(config) => {
+                    self.input.config = config.input.clone();
+                    renderer.user_config = config;
+                }
+                Err(err) => log::warn!("Failed to read {}: {:#}", file.display(), err),
             }
         }
+        if let Some(channel) = self.check_updates.or(renderer.user_config.check_updates) {
+            renderer.update_check = Some(UpdateCheck::spawn(channel));
+        }
+        if let Some(dir) = &self.recovery_dir {
+            renderer.enable_autosave(dir.clone());
+        }
+        renderer.presenting = self.present.is_some();
+        self.renderer = Some(renderer);
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+    /// Replace the terrain with the file at `path`, dropped on the window,
+    /// loading it in the background. The terrain shown so far keeps its
+    /// session for its next launch. If it fails to load, the current
+    /// terrain stays and the renderer reports why.
+    fn open_dropped(&mut self, path: &Path) {
+        if self.renderer.is_none() {
+            return;
+        }
+        self.save_session();
+        if let Some(renderer) = &mut self.renderer {
+            renderer.load_failure = None;
+            renderer.load_main_terrain(path.to_path_buf(), self.drop_options);
+        }
+    }
+
+    /// Once another file has loaded as the main terrain, drop the old
+    /// one's edits and move bookmarks, annotations and crash recovery to
+    /// the new file.
+    fn follow_opened_file(&mut self) {
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        let Some(path) = renderer.opened_file.take() else {
+            return;
+        };
+        if self.terrain_file.as_ref() == Some(&path) {
+            return;
+        }
+        log::info!("Opened {}", path.display());
+        renderer.clear_edits();
+        let bookmarks = Bookmarks::file_for(&path);
+        if let Err(err) = renderer.load_bookmarks(bookmarks.clone()) {
+            log::warn!("Failed to read {}: {:#}", bookmarks.display(), err);
+        }
+        self.bookmarks = Some(bookmarks);
+        let annotations = annotations::file_for(&path);
+        if let Err(err) = renderer.load_annotations(annotations.clone()) {
+            log::warn!("Failed to read {}: {:#}", annotations.display(), err);
+        }
+        self.annotations = Some(annotations);
+        if self.recovery_dir.is_some() {
+            renderer.finish_autosave();
+            let dir = Autosave::dir_for(&path);
+            renderer.enable_autosave(dir.clone());
+            self.recovery_dir = Some(dir);
+        }
+        self.terrain_file = Some(path);
+    }
+
+    /// Handle a window event. Returns `false` when the application should
+    /// exit.
+    fn handle_event(&mut self, event: WindowEvent) -> bool {
+        // Any input may change the picture: draw a frame for it, as soon
+        // as the frame rate cap allows
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            if let Some(renderer) = &self.renderer {
+                self.scheduler.request(&renderer.settings.pacing);
+            }
+        }
+        // Time from input to the frame showing it
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseWheel { .. }
+        ) {
+            if let Some(renderer) = &mut self.renderer {
+                renderer.note_input(Instant::now());
+            }
+        }
+
         // Let egui handle the event first
-        if let Some(ref mut renderer) = self.renderer {
-            if let Some(ref window) = self.window {
-                if renderer.handle_window_event(window, &event) {
-                    return; // egui consumed the event
-                }
+        if let (Some(renderer), Some(window)) = (&mut self.renderer, &self.window) {
+            if renderer.handle_window_event(window, &event) {
+                return true; // egui consumed the event
             }
         }
 
         match event {
-            // Close on window close button or ESC key
-            WindowEvent::CloseRequested => {
-                event_loop.exit();
-            }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(KeyCode::Escape),
-                        state: ElementState::Pressed,
-                        ..
-                    },
-                ..
-            } => {
-                event_loop.exit();
-            }
+            // Close on window close button
+            WindowEvent::CloseRequested => return false,
+
+            // Open terrain files dropped on the window
+            WindowEvent::DroppedFile(path) => self.open_dropped(&path),
+
+            // Keys released elsewhere never reach the window
+            WindowEvent::Focused(false) => self.input.release_keys(),
 
-            // Handle other keyboard input for camera control
+            // Keyboard input for camera control and quitting
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
@@ -134,15 +1090,19 @@ impl ApplicationHandler for App {
                         ..
                     },
                 ..
-            } => {
-                if let Some(ref mut renderer) = self.renderer {
-                    self.input.handle_keyboard(key, state, &mut renderer.camera);
-                }
-            }
+            } => return self.handle_key(key, state),
 
             // Mouse button events
             WindowEvent::MouseInput { button, state, .. } => {
                 self.input.handle_mouse_button(button, state);
+                // Right-click runs the selected tool on the terrain
+                if button == MouseButton::Right && state == ElementState::Pressed {
+                    if let (Some(renderer), Some(cursor)) =
+                        (&mut self.renderer, self.input.state.last_mouse_pos)
+                    {
+                        renderer.use_tool(cursor);
+                    }
+                }
             }
 
             // Mouse movement
@@ -153,6 +1113,11 @@ impl ApplicationHandler for App {
                         position.y as f32,
                         &mut renderer.camera,
                     );
+                    // Right-dragging draws freehand with the drawing tools
+                    // and paints or selects with the paint and selection tools
+                    if self.input.state.right_pressed {
+                        renderer.drag_tool((position.x as f32, position.y as f32));
+                    }
                 }
             }
 
@@ -170,34 +1135,198 @@ impl ApplicationHandler for App {
                 }
             }
 
+            // Moved to a monitor with another scale factor. egui picks the
+            // new factor up itself; the surface follows the window's new
+            // physical size, in case no resize event comes with it
+            WindowEvent::ScaleFactorChanged { .. } => {
+                if let (Some(renderer), Some(window)) = (&mut self.renderer, &self.window) {
+                    renderer.resize(window.inner_size());
+                }
+            }
+
             // Render frame
             WindowEvent::RedrawRequested => {
-                if let (Some(ref mut renderer), Some(ref window)) =
-                    (&mut self.renderer, &self.window)
-                {
-                    match renderer.render(window) {
+                if let Some(ref mut renderer) = self.renderer {
+                    let start = Instant::now();
+                    self.input.update(&mut renderer.camera);
+                    match renderer.render(self.window.as_deref()) {
                         Ok(_) => {}
                         Err(wgpu::SurfaceError::Lost) => {
                             renderer.resize(renderer.size);
                         }
                         Err(wgpu::SurfaceError::OutOfMemory) => {
                             log::error!("Out of GPU memory");
-                            event_loop.exit();
+                            return false;
                         }
                         Err(e) => {
                             log::warn!("Render error: {:?}", e);
                         }
                     }
-                }
+                    // Input settings edited in the UI, keeping the fly speed
+                    // picked with the scroll wheel
+                    if let Some(config) = renderer.take_input_change() {
+                        self.input.config = InputConfig {
+                            fly_speed: self.input.config.fly_speed,
+                            ..config.clone()
+                        };
+                    }
 
-                // Request next frame
-                if let Some(ref window) = self.window {
-                    window.request_redraw();
+                    // Schedule the next frame; held fly keys keep moving
+                    let busy = renderer.is_busy() || !self.input.state.move_keys.is_empty();
+                    self.scheduler
+                        .frame_started(start, &renderer.settings.pacing, busy);
                 }
+                self.follow_opened_file();
             }
 
             _ => {}
         }
+        true
+    }
+
+    /// Handle a key press or release. Returns `false` when the application
+    /// should exit.
+    ///
+    /// Split out of [`handle_event`](Self::handle_event) because winit's
+    /// `KeyEvent` can't be constructed outside winit, so tests call this
+    /// directly.
+    fn handle_key(&mut self, key: KeyCode, state: ElementState) -> bool {
+        if key == KeyCode::Escape && state == ElementState::Pressed {
+            return false;
+        }
+        if let Some(ref mut renderer) = self.renderer {
+            let action = self.input.config.keys.action(key);
+            if state == ElementState::Pressed {
+                match action {
+                    Some(Action::Screenshot) => renderer.request_screenshot(),
+                    Some(Action::Presentation) => renderer.presenting = !renderer.presenting,
+                    Some(Action::FrameTerrain) => renderer.frame_scene(),
+                    Some(Action::ToggleAutoRotate) => {
+                        let spin = &mut renderer.settings.auto_rotate;
+                        spin.enabled = !spin.enabled;
+                    }
+                    Some(Action::PlayTimeline) => {
+                        let timeline = &mut renderer.settings.timeline;
+                        timeline.playing = !timeline.playing;
+                    }
+                    Some(Action::NextKeyframe) => renderer.step_keyframe(true),
+                    Some(Action::PreviousKeyframe) => renderer.step_keyframe(false),
+                    Some(_) => {}
+                    None => {
+                        // Bookmarks when the terrain has any, else keyframes
+                        if let Some(index) = number_key(key) {
+                            if renderer.bookmarks.views.is_empty() {
+                                renderer.show_keyframe(index);
+                            } else {
+                                renderer.show_bookmark(index);
+                            }
+                        }
+                    }
+                }
+            }
+            self.input.handle_keyboard(key, state, &mut renderer.camera);
+            // Reset cameras are framed like at startup
+            if action == Some(Action::ResetCamera) && state == ElementState::Pressed {
+                renderer.frame_scene();
+            }
+        }
+        true
+    }
+}
+
+/// Bookmark or camera path keyframe a number key jumps to: 1 to 9 for the
+/// first nine.
+fn number_key(key: KeyCode) -> Option<usize> {
+    const DIGITS: [KeyCode; 9] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    DIGITS.iter().position(|&digit| digit == key)
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // Only create window once
+        if self.window.is_some() {
+            return;
+        }
+
+        let mut window_attrs = Window::default_attributes().with_title("lrle - Terrain Viewer");
+        if self.transparent {
+            window_attrs = window_attrs
+                .with_transparent(true)
+                .with_window_level(WindowLevel::AlwaysOnTop);
+        }
+        if let Some((width, height)) = self.present {
+            // Exactly the broadcast size for window captures, in physical
+            // pixels whatever the display scaling
+            window_attrs = window_attrs
+                .with_decorations(false)
+                .with_resizable(false)
+                .with_inner_size(PhysicalSize::new(width, height));
+        } else if let Some((width, height)) = self.window_size {
+            window_attrs = window_attrs.with_inner_size(PhysicalSize::new(width, height));
+        }
+
+        let window = match event_loop.create_window(window_attrs) {
+            Ok(w) => Arc::new(w),
+            Err(e) => {
+                log::error!("Failed to create window: {}", e);
+                event_loop.exit();
+                return;
+            }
+        };
+
+        match pollster::block_on(Renderer::new(
+            window.clone(),
+            self.transparent,
+            self.adapter.as_ref(),
+        )) {
+            Ok(renderer) => {
+                self.attach_renderer(renderer);
+                self.window = Some(window);
+            }
+            Err(e) => {
+                log::error!("Failed to create renderer: {}", e);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        // Quitting normally leaves nothing to recover
+        if let Some(renderer) = &mut self.renderer {
+            renderer.finish_jobs();
+            renderer.finish_autosave();
+            renderer.save_pipeline_cache();
+            renderer.finish_perf_log();
+        }
+        self.save_session();
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        if !self.handle_event(event) {
+            event_loop.exit();
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        // Sleep until the next frame is due, or until input without one
+        match (&self.window, self.scheduler.due()) {
+            (Some(window), Some(due)) if Instant::now() >= due => {
+                window.request_redraw();
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+            (Some(_), Some(due)) => event_loop.set_control_flow(ControlFlow::WaitUntil(due)),
+            _ => event_loop.set_control_flow(ControlFlow::Wait),
+        }
     }
 }
 
@@ -205,30 +1334,284 @@ fn main() -> Result<()> {
     // Initialize logging
     env_logger::init();
 
-    let args = Args::parse();
+    // Matches are kept to tell settings given on the command line from
+    // defaults, which a saved session overrides
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    match &args.command {
+        Some(Command::Contours {
+            file,
+            interval,
+            output,
+        }) => return export_contours(file, *interval, output),
+        Some(Command::Render {
+            file,
+            output,
+            width,
+            height,
+            azimuth,
+            elevation,
+            height_scale,
+            mode,
+            color_scheme,
+        }) => {
+            let terrain = load_terrain(file, &LoadOptions::default())?;
+            let job = RenderJob {
+                size: (*width, *height),
+                camera: JobCamera {
+                    azimuth: azimuth.to_radians(),
+                    elevation: elevation.to_radians(),
+                    ..JobCamera::default()
+                },
+                render_mode: *mode,
+                color_scheme: *color_scheme,
+                height_scale: *height_scale,
+                ..RenderJob::new(file, output)
+            };
+            let mut renderer = pollster::block_on(Renderer::new_headless(*width, *height))?;
+            job.render(&mut renderer, &terrain)?;
+            println!("Wrote {}x{} render to {}", width, height, output.display());
+            return Ok(());
+        }
+        Some(Command::Jobs { file }) => {
+            let summary = jobs::run(file)?;
+            if !summary.failed.is_empty() {
+                bail!(
+                    "{} of {} jobs failed: {}",
+                    summary.failed.len(),
+                    summary.total,
+                    summary
+                        .failed
+                        .iter()
+                        .map(|(label, _)| label.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            return Ok(());
+        }
+        Some(Command::GpuBench {
+            frames,
+            width,
+            height,
+        }) => {
+            gpu_bench(&BenchOptions {
+                size: (*width, *height),
+                frames: *frames,
+            })?;
+            return Ok(());
+        }
+        Some(Command::DumpScene {
+            file,
+            height_scale,
+            json,
+        }) => {
+            let scene =
+                SceneSummary::compute(&load_terrain(file, &LoadOptions::default())?, *height_scale);
+            print!(
+                "{}",
+                if *json {
+                    scene.to_json()
+                } else {
+                    scene.to_text()
+                }
+            );
+            return Ok(());
+        }
+        None => {}
+    }
 
     // Load terrain data from file
-    let terrain = load_fdf(&args.file)?;
-    log::info!(
-        "Loaded terrain: {}x{}, height range: {:?}",
-        terrain.width,
-        terrain.height,
-        terrain.height_bounds()
-    );
+    let file = args.file.as_deref().context("No terrain file given")?;
+    let options = LoadOptions {
+        format: args.format,
+        max_size: args.max_raster_size,
+        nodata_fill: args.nodata_fill,
+    };
+    // Text grids can take long to parse: they load in the background while
+    // the window lists their progress, unless the command line needs the
+    // heights before the window opens
+    let format = options
+        .format
+        .unwrap_or_else(|| TerrainFormat::from_path(Path::new(file)));
+    // A file that fails to load opens the window anyway, showing why, so it
+    // can be fixed and loaded again
+    let mut load_failure = None;
+    let terrain = if format == TerrainFormat::Fdf && !args.needs_heights() {
+        None
+    } else {
+        load_or_keep_failure(file, &options, LoadTarget::Main, &mut load_failure)
+    };
+    // Other grids pick their format from their extension
+    let grid_options = LoadOptions {
+        format: None,
+        ..options
+    };
+    // Loaded before the exports, which color by it
+    let reference = args.reference.as_deref().and_then(|path| {
+        load_or_keep_failure(
+            path,
+            &grid_options,
+            LoadTarget::Reference,
+            &mut load_failure,
+        )
+    });
+    let (terrain, uncertainty) = match terrain {
+        Some(terrain) => {
+            let (terrain, uncertainty) = prepare_main_terrain(
+                terrain,
+                &options,
+                &args,
+                reference.as_ref(),
+                &mut load_failure,
+            )?;
+            (Some(terrain), uncertainty)
+        }
+        None => (None, None),
+    };
 
     // Create event loop and run application
     let event_loop = EventLoop::new()?;
-    event_loop.set_control_flow(ControlFlow::Poll);
+    event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut app = App {
-        window: None,
-        renderer: None,
-        terrain,
-        height_scale: args.height_scale,
-        input: InputController::new(),
-    };
+    let mut app = App::new(terrain, &args);
+    if app.terrain.is_none() && load_failure.is_none() {
+        app.background_load = Some((PathBuf::from(file), options));
+    }
+    // Processed heights no longer match the grid's
+    if format == TerrainFormat::FloatGrid && args.smooth.is_none() && args.downsample.is_none() {
+        app.mapped_grid = Some((PathBuf::from(file), options));
+    }
+    app.uncertainty = uncertainty;
+    app.layers = args
+        .more
+        .iter()
+        .filter_map(|file| {
+            let terrain =
+                load_or_keep_failure(file, &options, LoadTarget::Layer, &mut load_failure)?;
+            Some((layers::name_of(Path::new(file)), terrain))
+        })
+        .collect();
+    app.config_file = args.config.clone().or_else(Config::default_file);
+    app.annotations = args
+        .file
+        .as_deref()
+        .map(|file| annotations::file_for(Path::new(file)));
+    app.recovery_dir = args
+        .file
+        .as_deref()
+        .map(|file| Autosave::dir_for(Path::new(file)));
+    app.session_file = Session::default_file();
+    if let (Some(session_file), false) = (&app.session_file, args.fresh) {
+        match Session::load(session_file, Path::new(file)) {
+            Ok(Some(session)) => app.restore_session(&session, |id| {
+                matches.value_source(id) == Some(ValueSource::CommandLine)
+            }),
+            Ok(None) => {}
+            Err(err) => log::warn!("Failed to read {}: {:#}", session_file.display(), err),
+        }
+    }
+    app.reference = reference;
+    if let (Some(paths), Some(terrain)) = (&args.vector_field, &app.terrain) {
+        let target = LoadTarget::VectorField([&paths[0], &paths[1]].map(PathBuf::from));
+        let [east, north] = [&paths[0], &paths[1]].map(|path| {
+            load_or_keep_failure(path, &grid_options, target.clone(), &mut load_failure)
+        });
+        if let (Some(east), Some(north)) = (east, north) {
+            app.vector_field = Some(VectorField::resampled(&east, &north, terrain));
+        }
+    }
+    app.load_failure = load_failure;
 
     event_loop.run_app(&mut app)?;
 
     Ok(())
 }
+
+/// Load `path` with `options`. If it fails to load, keep why in `failure`,
+/// to show once the window opens, unless an earlier failure is kept.
+fn load_or_keep_failure(
+    path: &str,
+    options: &LoadOptions,
+    target: LoadTarget,
+    failure: &mut Option<LoadFailure>,
+) -> Option<TerrainData> {
+    match load_terrain(path, options) {
+        Ok(terrain) => Some(terrain),
+        Err(err) => {
+            log::warn!("Failed to load {path}: {err}");
+            failure.get_or_insert_with(|| LoadFailure::new(path.into(), *options, target, &err));
+            None
+        }
+    }
+}
+
+/// Apply the processing, channels and units `args` ask for to the loaded
+/// main terrain, and write the exports they ask for, colored by height
+/// above `reference` if loaded. Returns it with the index of its
+/// uncertainty channel, if loaded. Channels that fail to load are left
+/// out, with the first failure kept in `failure`.
+fn prepare_main_terrain(
+    mut terrain: TerrainData,
+    options: &LoadOptions,
+    args: &Args,
+    reference: Option<&TerrainData>,
+    failure: &mut Option<LoadFailure>,
+) -> Result<(TerrainData, Option<usize>)> {
+    let grid_options = LoadOptions {
+        format: None,
+        ..*options
+    };
+    // Smoothed before downsampling, so the coarser grid doesn't alias noise
+    if let Some(sigma) = args.smooth {
+        terrain = Processing::Smooth(sigma).apply(&terrain);
+    }
+    if let Some(factor) = args.downsample {
+        terrain = Processing::Downsample(factor as usize).apply(&terrain);
+    }
+    for channel in &args.channels {
+        let (name, path) = channel
+            .split_once('=')
+            .with_context(|| format!("Expected --channel NAME=PATH, got {channel}"))?;
+        let target = LoadTarget::Channel(name.to_string());
+        if let Some(grid) = load_or_keep_failure(path, &grid_options, target, failure) {
+            terrain.add_channel(name, &grid);
+        }
+    }
+    let mut uncertainty = None;
+    if let Some(path) = &args.uncertainty {
+        let target = LoadTarget::Uncertainty;
+        if let Some(grid) = load_or_keep_failure(path, &grid_options, target, failure) {
+            terrain.add_channel("uncertainty", &grid);
+            uncertainty = Some(terrain.channels().len() - 1);
+        }
+    }
+    if let Some(units) = args.units {
+        terrain.units = units;
+    }
+    log::info!(
+        "Loaded terrain: {}x{}, height range: {:?}",
+        terrain.width,
+        terrain.height,
+        terrain.height_bounds()
+    );
+
+    if let Some(path) = &args.export_ridgelines {
+        let lines = analysis::ridgelines(&terrain);
+        std::fs::write(path, analysis::to_geojson(&lines, &terrain))?;
+        log::info!("Wrote {} ridgelines to {}", lines.len(), path.display());
+    }
+
+    if args.export.is_some()
+        || args.export_gltf.is_some()
+        || args.export_obj.is_some()
+        || args.export_usdz.is_some()
+    {
+        export_mesh(&terrain, args, reference)?;
+    }
+    if let Some(path) = &args.export_colors {
+        export_colors(&terrain, args, reference, path)?;
+    }
+    Ok((terrain, uncertainty))
+}
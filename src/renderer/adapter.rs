@@ -0,0 +1,131 @@
+//! Picking the GPU to render on.
+//!
+//! By default wgpu picks an adapter itself, which on laptops with both an
+//! integrated and a discrete GPU isn't always the faster one. `--adapter`
+//! picks one explicitly, by its index in the list `lrle gpu-bench` prints
+//! or by part of its name, such as `nvidia` or `intel`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+/// Every adapter on every backend, in the order indices refer to.
+pub fn list(instance: &wgpu::Instance) -> Vec<wgpu::Adapter> {
+    instance.enumerate_adapters(wgpu::Backends::all())
+}
+
+/// An adapter's name, backend and kind, e.g.
+/// `NVIDIA GeForce RTX 3060 (Vulkan, discrete GPU)`.
+pub fn describe(info: &wgpu::AdapterInfo) -> String {
+    let kind = match info.device_type {
+        wgpu::DeviceType::DiscreteGpu => "discrete GPU",
+        wgpu::DeviceType::IntegratedGpu => "integrated GPU",
+        wgpu::DeviceType::VirtualGpu => "virtual GPU",
+        wgpu::DeviceType::Cpu => "software",
+        wgpu::DeviceType::Other => "other",
+    };
+    format!("{} ({:?}, {})", info.name, info.backend, kind)
+}
+
+/// The adapter asked for with `--adapter`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdapterChoice {
+    /// Position in the adapter list
+    Index(usize),
+    /// Part of the name, matched regardless of case
+    Name(String),
+}
+
+impl FromStr for AdapterChoice {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("expected an adapter index or name".to_string());
+        }
+        Ok(match text.parse() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Name(text.to_lowercase()),
+        })
+    }
+}
+
+impl fmt::Display for AdapterChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "adapter {index}"),
+            Self::Name(name) => write!(f, "adapter \"{name}\""),
+        }
+    }
+}
+
+impl AdapterChoice {
+    /// Position of the chosen adapter among `infos`: the one at the index,
+    /// or the first whose name matches.
+    pub fn position(&self, infos: &[wgpu::AdapterInfo]) -> Result<usize> {
+        let found = match self {
+            Self::Index(index) => Some(*index).filter(|&index| index < infos.len()),
+            Self::Name(name) => infos
+                .iter()
+                .position(|info| info.name.to_lowercase().contains(name.as_str())),
+        };
+        let Some(position) = found else {
+            let available: Vec<_> = infos
+                .iter()
+                .enumerate()
+                .map(|(index, info)| format!("{index}: {}", describe(info)))
+                .collect();
+            if available.is_empty() {
+                bail!("No {self}, no GPU adapters were found");
+            }
+            bail!("No {self}, expected one of\n{}", available.join("\n"));
+        };
+        Ok(position)
+    }
+
+    /// The chosen adapter out of `adapters`.
+    pub fn pick(&self, mut adapters: Vec<wgpu::Adapter>) -> Result<wgpu::Adapter> {
+        let infos: Vec<_> = adapters.iter().map(wgpu::Adapter::get_info).collect();
+        Ok(adapters.swap_remove(self.position(&infos)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str, device_type: wgpu::DeviceType) -> wgpu::AdapterInfo {
+        wgpu::AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend: wgpu::Backend::Vulkan,
+        }
+    }
+
+    #[test]
+    fn test_choice_by_index_or_name() {
+        let infos = [
+            info("Intel(R) UHD Graphics 630", wgpu::DeviceType::IntegratedGpu),
+            info("NVIDIA GeForce RTX 3060", wgpu::DeviceType::DiscreteGpu),
+        ];
+        let choose = |text: &str| text.parse::<AdapterChoice>().unwrap().position(&infos);
+        assert_eq!(choose("1").unwrap(), 1);
+        assert_eq!(choose("nvidia").unwrap(), 1);
+        assert_eq!(choose(" Intel ").unwrap(), 0);
+        assert_eq!(
+            describe(&infos[1]),
+            "NVIDIA GeForce RTX 3060 (Vulkan, discrete GPU)"
+        );
+
+        let err = choose("2").unwrap_err().to_string();
+        assert!(err.starts_with("No adapter 2, expected one of\n0: Intel"));
+        assert!(choose("radeon").is_err());
+        assert!("".parse::<AdapterChoice>().is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! Sky gradient and distance fog.
+//!
+//! Both read one scene uniform block, bound at [`SCENE_BINDING`] next to
+//! each terrain pipeline's own uniforms and declared by the `scene.wgsl`
+//! prelude that [`load`](super::shaders::load) prepends to their shaders.
+//!
+//! The sky is a fullscreen pass drawn first in the main pass, behind
+//! everything, blending from the horizon color to the zenith color by the
+//! elevation of each pixel's view ray. Fog mixes surface colors toward the
+//! fog color by `1 - exp(-density * distance)`, with the density given
+//! relative to the scene radius so the same setting suits any terrain.
+//! The overview inset binds a block of zeros: no fog, and no environment
+//! map (see [`environment`](super::environment)), whose settings and
+//! irradiance ride in the same block.
+
+use glam::{Mat4, Vec3};
+
+use super::environment::{self, EnvironmentConfig, EnvironmentTexture};
+use super::DEPTH_FORMAT;
+use crate::terrain::colors::srgb_to_linear;
+use crate::terrain::environment::Irradiance;
+
+/// Bind group slot of the scene uniforms in the terrain pipelines.
+pub const SCENE_BINDING: u32 = 6;
+
+/// Background gradient settings. Colors are sRGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyConfig {
+    /// Whether the gradient replaces the flat background color
+    pub enabled: bool,
+    /// Color straight up
+    pub zenith: [f32; 3],
+    /// Color at and below the horizon
+    pub horizon: [f32; 3],
+}
+
+impl Default for SkyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            zenith: [0.22, 0.3, 0.48],
+            horizon: [0.62, 0.66, 0.74],
+        }
+    }
+}
+
+/// Distance fog settings. The color is sRGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogConfig {
+    /// Whether surfaces fade with distance
+    pub enabled: bool,
+    /// Extinction per scene radius of distance
+    pub density: f32,
+    /// Color distant surfaces fade to
+    pub color: [f32; 3],
+}
+
+impl Default for FogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            density: 1.0,
+            color: [0.62, 0.66, 0.74],
+        }
+    }
+}
+
+/// Scene uniforms shared by the sky and the terrain shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SceneUniforms {
+    /// Clip space to world space, for the sky's view rays
+    inv_view_proj: [[f32; 4]; 4],
+    camera_position: [f32; 3],
+    /// Extinction per world unit, 0.0 without fog
+    fog_density: f32,
+    camera_forward: [f32; 3],
+    _pad0: f32,
+    fog_color: [f32; 3],
+    _pad1: f32,
+    sky_zenith: [f32; 3],
+    _pad2: f32,
+    sky_horizon: [f32; 3],
+    /// Environment map brightness, 0.0 without one
+    env_intensity: f32,
+    /// Environment map turn about the vertical axis, in radians
+    env_rotation: f32,
+    /// 1.0 to show the environment map as the background
+    env_background: f32,
+    /// Power encoding linear environment colors for the target
+    env_gamma: f32,
+    _pad3: f32,
+    env_irradiance: [[f32; 4]; 9],
+}
+
+impl SceneUniforms {
+    /// Uniforms for a camera at `position` looking along `forward` with
+    /// `view_proj`, over a scene of `radius`, lit by the `environment` map
+    /// of that irradiance if any. Colors are converted to linear when the
+    /// target is `srgb`, as the surface encodes them, and environment
+    /// colors, linear already, encoded when it isn't.
+    pub fn new(
+        view_proj: Mat4,
+        (position, forward): (Vec3, Vec3),
+        radius: f32,
+        (sky, fog): (&SkyConfig, &FogConfig),
+        environment: Option<(&EnvironmentConfig, &Irradiance)>,
+        srgb: bool,
+    ) -> Self {
+        let color = |c: [f32; 3]| if srgb { c.map(srgb_to_linear) } else { c };
+        let fog_density = if fog.enabled {
+            fog.density.max(0.0) / radius.max(f32::EPSILON)
+        } else {
+            0.0
+        };
+        Self {
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            camera_position: position.to_array(),
+            fog_density,
+            camera_forward: forward.to_array(),
+            fog_color: color(fog.color),
+            sky_zenith: color(sky.zenith),
+            sky_horizon: color(sky.horizon),
+            ..Default::default()
+        }
+        .with_environment(environment.filter(|(config, _)| config.enabled), srgb)
+    }
+
+    fn with_environment(
+        self,
+        environment: Option<(&EnvironmentConfig, &Irradiance)>,
+        srgb: bool,
+    ) -> Self {
+        let Some((config, irradiance)) = environment else {
+            return self;
+        };
+        Self {
+            env_intensity: config.intensity.max(0.0),
+            env_rotation: config.rotation.to_radians(),
+            env_background: if config.background { 1.0 } else { 0.0 },
+            env_gamma: if srgb { 1.0 } else { 1.0 / 2.2 },
+            env_irradiance: irradiance.map(|[r, g, b]| [r, g, b, 0.0]),
+            ..self
+        }
+    }
+
+    /// Whether the environment map is shown as the background.
+    pub fn shows_environment(&self) -> bool {
+        self.env_background > 0.5 && self.env_intensity > 0.0
+    }
+}
+
+/// Layout entry of the scene uniforms, for the terrain bind group layouts.
+pub fn layout_entry() -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: SCENE_BINDING,
+        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// Create a scene uniform buffer, initially without fog.
+pub fn create_buffer(device: &wgpu::Device, label: &str) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: std::mem::size_of::<SceneUniforms>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// The sky gradient pipeline.
+pub struct Sky {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Sky {
+    /// Create the pipeline drawing onto a surface of `format` from
+    /// `sky.wgsl` source, reading the scene uniforms from `scene_buffer`
+    /// and looking rays up in `environment`.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        (scene_buffer, environment): (&wgpu::Buffer, &EnvironmentTexture),
+        source: &str,
+    ) -> Self {
+        let [texture_entry, sampler_entry] = environment::layout_entries();
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[layout_entry(), texture_entry, sampler_entry],
+            label: Some("Sky Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, cache, &pipeline_layout, format, 1, source);
+        let bind_group = create_bind_group(device, &bind_group_layout, scene_buffer, environment);
+
+        Self {
+            bind_group_layout,
+            pipeline_layout,
+            pipeline,
+            bind_group,
+        }
+    }
+
+    /// Look rays up in `environment` from now on.
+    pub fn set_environment(
+        &mut self,
+        device: &wgpu::Device,
+        scene_buffer: &wgpu::Buffer,
+        environment: &EnvironmentTexture,
+    ) {
+        self.bind_group =
+            create_bind_group(device, &self.bind_group_layout, scene_buffer, environment);
+    }
+
+    /// Build a pipeline from new shader source drawing `samples` per pixel,
+    /// to be installed with [`set_pipeline`](Self::set_pipeline) once it
+    /// validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        samples: u32,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_pipeline(
+            device,
+            cache,
+            &self.pipeline_layout,
+            format,
+            samples,
+            source,
+        )
+    }
+
+    /// Replace the pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Fill the pass's target with the gradient, leaving depth untouched.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    scene_buffer: &wgpu::Buffer,
+    environment: &EnvironmentTexture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: SCENE_BINDING,
+                resource: scene_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: environment::ENVIRONMENT_BINDING,
+                resource: wgpu::BindingResource::TextureView(environment.view()),
+            },
+            wgpu::BindGroupEntry {
+                binding: environment::ENVIRONMENT_BINDING + 1,
+                resource: wgpu::BindingResource::Sampler(environment.sampler()),
+            },
+        ],
+        label: Some("Sky Bind Group"),
+    })
+}
+
+/// Build the fullscreen pipeline drawn first in the main pass, which has
+/// a depth attachment but must not write or test it.
+fn create_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    samples: u32,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Sky Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Sky Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: samples,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniforms(fog: &FogConfig, srgb: bool) -> SceneUniforms {
+        SceneUniforms::new(
+            Mat4::IDENTITY,
+            (Vec3::ZERO, Vec3::NEG_Z),
+            100.0,
+            (&SkyConfig::default(), fog),
+            None,
+            srgb,
+        )
+    }
+
+    #[test]
+    fn test_fog_density_is_relative_to_radius() {
+        let fog = FogConfig {
+            enabled: true,
+            density: 2.0,
+            ..Default::default()
+        };
+        // One scene radius away, e^-2 of the color remains
+        assert_eq!(uniforms(&fog, false).fog_density, 0.02);
+    }
+
+    #[test]
+    fn test_disabled_fog_has_no_density() {
+        assert_eq!(uniforms(&FogConfig::default(), false).fog_density, 0.0);
+    }
+
+    #[test]
+    fn test_colors_are_linear_on_srgb_targets() {
+        let fog = FogConfig::default();
+        assert_eq!(uniforms(&fog, false).fog_color, fog.color);
+        assert_eq!(
+            uniforms(&fog, true).fog_color,
+            fog.color.map(srgb_to_linear)
+        );
+    }
+
+    #[test]
+    fn test_environment_only_when_enabled() {
+        let fog = FogConfig::default();
+        let irradiance = [[0.5; 3]; 9];
+        let environment = |config: &EnvironmentConfig| {
+            SceneUniforms::new(
+                Mat4::IDENTITY,
+                (Vec3::ZERO, Vec3::NEG_Z),
+                100.0,
+                (&SkyConfig::default(), &fog),
+                Some((config, &irradiance)),
+                false,
+            )
+        };
+        let config = EnvironmentConfig {
+            rotation: 90.0,
+            ..Default::default()
+        };
+        let shown = environment(&config);
+        assert!(shown.shows_environment());
+        assert_eq!(shown.env_rotation, 90f32.to_radians());
+        assert_eq!(shown.env_irradiance[4], [0.5, 0.5, 0.5, 0.0]);
+        assert_eq!(shown.env_gamma, 1.0 / 2.2);
+
+        let off = environment(&EnvironmentConfig {
+            enabled: false,
+            ..config
+        });
+        assert_eq!(off, uniforms(&fog, false));
+        assert!(!off.shows_environment());
+    }
+}
@@ -0,0 +1,156 @@
+//! A quick GPU benchmark for `lrle gpu-bench`.
+//!
+//! Every adapter renders the same synthetic terrain, so frame rates can be
+//! compared across GPUs and machines to pick the `--adapter` to run the
+//! viewer on. Each frame is waited for before the next is drawn, so the
+//! rate counts the GPU's work rather than how many frames it can queue.
+//! Upload throughput is timed writing a large buffer, as loading a big
+//! terrain's mesh does.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use super::{RenderMode, Renderer};
+use crate::terrain::TerrainData;
+
+/// Side of the benchmark terrain, in samples.
+pub const SCENE_SIDE: usize = 512;
+
+/// Bytes written for each upload timing.
+const UPLOAD_BYTES: u64 = 64 << 20;
+
+/// Uploads timed, after one untimed to warm up.
+const UPLOADS: u32 = 4;
+
+/// Frames drawn before timing, while caches and clocks settle.
+const WARMUP_FRAMES: u32 = 5;
+
+/// How a benchmark runs.
+#[derive(Debug, Clone, Copy)]
+pub struct BenchOptions {
+    /// Frame size in pixels
+    pub size: (u32, u32),
+    /// Frames timed
+    pub frames: u32,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            size: (1920, 1080),
+            frames: 200,
+        }
+    }
+}
+
+/// What one adapter managed.
+#[derive(Debug, Clone, Copy)]
+pub struct BenchResult {
+    /// Frames drawn per second
+    pub fps: f64,
+    /// Mean time to draw a frame
+    pub frame_time: Duration,
+    /// Bytes uploaded to the GPU per second
+    pub upload_rate: f64,
+}
+
+/// The benchmark terrain: rolling ridges with finer ripples, the same on
+/// every run.
+pub fn scene() -> TerrainData {
+    let heights = (0..SCENE_SIDE * SCENE_SIDE)
+        .map(|i| {
+            let (x, z) = ((i % SCENE_SIDE) as f64, (i / SCENE_SIDE) as f64);
+            40.0 * (x * 0.05).sin() * (z * 0.04).cos()
+                + 15.0 * (x * 0.13 + z * 0.07).sin()
+                + 5.0 * (x * 0.31).sin() * (z * 0.29).sin()
+        })
+        .collect();
+    TerrainData::from_heights(SCENE_SIDE, heights, None)
+}
+
+/// Render the benchmark terrain on `adapter` and time its frames and
+/// uploads.
+///
+/// # Errors
+///
+/// Returns an error if the adapter can't create a device or a frame fails.
+pub fn run(adapter: &wgpu::Adapter, options: &BenchOptions) -> Result<BenchResult> {
+    let (width, height) = options.size;
+    let mut renderer = pollster::block_on(Renderer::new_headless_on(adapter, width, height))?;
+    renderer.settings.render_mode = RenderMode::Both;
+    renderer.upload_terrain(&scene(), 1.0);
+    renderer.frame_scene();
+
+    for _ in 0..WARMUP_FRAMES {
+        draw(&mut renderer)?;
+    }
+    let start = Instant::now();
+    for _ in 0..options.frames {
+        // A slow turn, so culling and overdraw vary as they would in use
+        renderer.camera.azimuth += 0.01;
+        draw(&mut renderer)?;
+    }
+    let elapsed = start.elapsed();
+    let frames = options.frames.max(1);
+
+    Ok(BenchResult {
+        fps: frames as f64 / elapsed.as_secs_f64(),
+        frame_time: elapsed / frames,
+        upload_rate: upload_rate(&renderer),
+    })
+}
+
+/// Draw a frame and wait for the GPU to finish it.
+fn draw(renderer: &mut Renderer) -> Result<()> {
+    renderer.render(None).context("Failed to render a frame")?;
+    renderer
+        .device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .context("Failed to wait for the GPU")?;
+    Ok(())
+}
+
+/// Bytes per second written to a GPU buffer through the queue.
+fn upload_rate(renderer: &Renderer) -> f64 {
+    let buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Benchmark Upload Buffer"),
+        size: UPLOAD_BYTES,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let data = vec![0x5au8; UPLOAD_BYTES as usize];
+    let upload = || {
+        renderer.queue.write_buffer(&buffer, 0, &data);
+        renderer.queue.submit([]);
+        let _ = renderer.device.poll(wgpu::PollType::wait_indefinitely());
+    };
+    upload();
+    let start = Instant::now();
+    for _ in 0..UPLOADS {
+        upload();
+    }
+    (UPLOAD_BYTES * UPLOADS as u64) as f64 / start.elapsed().as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::adapter;
+
+    #[test]
+    fn test_bench_times_frames_and_uploads() {
+        let instance = wgpu::Instance::default();
+        let Some(adapter) = adapter::list(&instance).into_iter().next() else {
+            return;
+        };
+        let options = BenchOptions {
+            size: (64, 48),
+            frames: 3,
+        };
+        let result = run(&adapter, &options).unwrap();
+        assert!(result.fps > 0.0 && result.fps.is_finite());
+        assert!(result.frame_time > Duration::ZERO);
+        assert!(result.upload_rate > 0.0);
+    }
+}
@@ -0,0 +1,295 @@
+//! Per-layer blend modes.
+//!
+//! A translucent layer with a blend mode other than
+//! [`BlendMode::Normal`] is accumulated into its own set of
+//! [`LayerTargets`] instead of the shared OIT targets. A fullscreen pass
+//! then reads those targets along with a copy of the scene drawn so far and
+//! combines the two with the layer's mode in the shader, so e.g. a
+//! difference layer shows where it departs from what lies beneath it.
+//!
+//! Copying the scene needs `COPY_SRC` on the surface; without it every
+//! layer falls back to normal blending.
+
+use super::oit::{LayerTargets, Oit};
+use super::BlendMode;
+
+/// Blend mode shader uniforms.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlendUniforms {
+    mode: u32,
+    _pad: [u32; 3],
+}
+
+/// Layer targets, scene copy and composite pipeline for blended layers.
+pub struct Blend {
+    layers: Vec<Layer>,
+    scene_texture: wgpu::Texture,
+    scene_view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+/// One blended layer's targets and mode uniforms.
+struct Layer {
+    targets: LayerTargets,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Blend {
+    /// Create targets for `count` layers of the given size and the
+    /// composite pipeline drawing onto a surface of `format`, from
+    /// `blend.wgsl` source. Layer targets share `oit`'s layout.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        oit: &Oit,
+        count: usize,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Blend Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blend Composite Pipeline Layout"),
+            bind_group_layouts: &[oit.bind_group_layout(), &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_composite_pipeline(device, cache, &pipeline_layout, format, source);
+
+        let (scene_texture, scene_view) = create_scene_texture(device, width, height, format);
+        let layers = (0..count)
+            .map(|_| {
+                let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Blend Uniform Buffer"),
+                    size: std::mem::size_of::<BlendUniforms>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group =
+                    create_bind_group(device, &bind_group_layout, &scene_view, &uniform_buffer);
+                Layer {
+                    targets: oit.create_targets(device, width, height),
+                    uniform_buffer,
+                    bind_group,
+                }
+            })
+            .collect();
+
+        Self {
+            layers,
+            scene_texture,
+            scene_view,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Recreate the targets and scene copy for a new surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, oit: &Oit, width: u32, height: u32) {
+        let (scene_texture, scene_view) =
+            create_scene_texture(device, width, height, self.scene_texture.format());
+        for layer in &mut self.layers {
+            layer.targets = oit.create_targets(device, width, height);
+            layer.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &scene_view,
+                &layer.uniform_buffer,
+            );
+        }
+        self.scene_texture = scene_texture;
+        self.scene_view = scene_view;
+    }
+
+    /// Build a composite pipeline from new shader source, to be installed
+    /// with [`set_pipeline`](Self::set_pipeline) once it validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_composite_pipeline(device, cache, &self.pipeline_layout, format, source)
+    }
+
+    /// Replace the composite pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Begin the pass accumulating layer `index`, testing against the opaque
+    /// scene's depth. Draw with the OIT layer pipeline.
+    pub fn begin_layer<'a>(
+        &'a self,
+        index: usize,
+        encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        self.layers[index].targets.begin(encoder, depth_view)
+    }
+
+    /// Blend layer `index` into `target` with `mode`. `target` is a view of
+    /// `surface`, which is copied first so the shader can read it.
+    pub fn composite(
+        &self,
+        index: usize,
+        mode: BlendMode,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &wgpu::Texture,
+        target: &wgpu::TextureView,
+    ) {
+        let layer = &self.layers[index];
+        queue.write_buffer(
+            &layer.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&BlendUniforms {
+                mode: mode.shader_index(),
+                _pad: [0; 3],
+            }),
+        );
+        encoder.copy_texture_to_texture(
+            surface.as_image_copy(),
+            self.scene_texture.as_image_copy(),
+            self.scene_texture.size(),
+        );
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blend Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, layer.targets.bind_group(), &[]);
+        pass.set_bind_group(1, &layer.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Create the texture receiving a copy of the scene.
+fn create_scene_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Blend Scene Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// Bind the scene copy and a layer's mode uniforms.
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    scene_view: &wgpu::TextureView,
+    uniform_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(scene_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("Blend Bind Group"),
+    })
+}
+
+/// Build the fullscreen composite pipeline. It replaces the target's
+/// pixels, having blended with the scene copy itself.
+fn create_composite_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Blend Composite Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Blend Composite Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
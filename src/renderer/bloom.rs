@@ -0,0 +1,421 @@
+//! Bloom over the finished scene.
+//!
+//! Pixels brighter than a threshold bleed a soft glow into their
+//! surroundings, so bright lines on a dark background and sunlit slopes
+//! look emissive. The glow is built in stages at half resolution: a bright
+//! pass keeps the part of each pixel's linear color above the threshold,
+//! a separable Gaussian blur spreads it horizontally and then vertically,
+//! and a composite pass adds the blurred light back onto the scene,
+//! upsampled with bilinear filtering.
+//!
+//! Like the color vision simulation, the pass reads a copy of the scene,
+//! which needs `COPY_SRC` on the surface; without it bloom is skipped.
+
+/// Format of the half-resolution glow targets, with headroom above 1.0.
+const GLOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Glow settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomConfig {
+    /// Whether the glow is drawn
+    pub enabled: bool,
+    /// Linear luminance above which pixels glow
+    pub threshold: f32,
+    /// Strength of the added glow
+    pub intensity: f32,
+    /// Reach of the glow in pixels
+    pub radius: f32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 0.3,
+            intensity: 1.5,
+            radius: 12.0,
+        }
+    }
+}
+
+/// Size of the glow targets for a surface of `width` by `height`.
+fn glow_size(width: u32, height: u32) -> (u32, u32) {
+    (width.div_ceil(2).max(1), height.div_ceil(2).max(1))
+}
+
+/// Bloom shader uniforms.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniforms {
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+    /// Whether the scene copy holds sRGB-encoded values to linearize
+    encoded: u32,
+}
+
+/// Pipelines of the bloom stages.
+pub struct BloomPipelines {
+    bright: wgpu::RenderPipeline,
+    blur_horizontal: wgpu::RenderPipeline,
+    blur_vertical: wgpu::RenderPipeline,
+    composite: wgpu::RenderPipeline,
+}
+
+/// Scene copy and the two glow targets the blur ping-pongs between.
+struct Targets {
+    scene_texture: wgpu::Texture,
+    /// Bright pass output, and the blur's final result
+    glow_view: wgpu::TextureView,
+    /// Horizontally blurred glow
+    blurred_view: wgpu::TextureView,
+    /// Reads the glow target: horizontal blur and composite
+    glow_bind_group: wgpu::BindGroup,
+    /// Reads the horizontally blurred target: bright pass and vertical blur
+    blurred_bind_group: wgpu::BindGroup,
+}
+
+/// Targets and pipelines of the bloom stages.
+pub struct Bloom {
+    targets: Targets,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: BloomPipelines,
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+}
+
+impl Bloom {
+    /// Create the targets for a surface of the given size and the
+    /// pipelines drawing onto a surface of `format`, from `bloom.wgsl`
+    /// source.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Self {
+        let texture_entry = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(2, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("Bloom Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = create_pipelines(device, cache, &pipeline_layout, format, source);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bloom Uniform Buffer"),
+            size: std::mem::size_of::<BloomUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Bloom Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let targets = create_targets(
+            device,
+            &bind_group_layout,
+            (width, height),
+            format,
+            (&uniform_buffer, &sampler),
+        );
+
+        Self {
+            targets,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines,
+            uniform_buffer,
+            sampler,
+        }
+    }
+
+    /// Recreate the targets for a new surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = create_targets(
+            device,
+            &self.bind_group_layout,
+            (width, height),
+            self.targets.scene_texture.format(),
+            (&self.uniform_buffer, &self.sampler),
+        );
+    }
+
+    /// Build the pipelines from new shader source, to be installed with
+    /// [`set_pipelines`](Self::set_pipelines) once they validate.
+    pub fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> BloomPipelines {
+        create_pipelines(device, cache, &self.pipeline_layout, format, source)
+    }
+
+    /// Replace the pipelines.
+    pub fn set_pipelines(&mut self, pipelines: BloomPipelines) {
+        self.pipelines = pipelines;
+    }
+
+    /// Redraw `target`, a view of `surface`, with the glow of `config`.
+    pub fn apply(
+        &self,
+        config: &BloomConfig,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &wgpu::Texture,
+        target: &wgpu::TextureView,
+    ) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&BloomUniforms {
+                threshold: config.threshold,
+                intensity: config.intensity,
+                radius: config.radius,
+                encoded: u32::from(!surface.format().is_srgb()),
+            }),
+        );
+        let targets = &self.targets;
+        encoder.copy_texture_to_texture(
+            surface.as_image_copy(),
+            targets.scene_texture.as_image_copy(),
+            targets.scene_texture.size(),
+        );
+
+        let stages = [
+            (
+                "Bloom Bright Pass",
+                &self.pipelines.bright,
+                &targets.blurred_bind_group,
+                &targets.glow_view,
+            ),
+            (
+                "Bloom Horizontal Blur Pass",
+                &self.pipelines.blur_horizontal,
+                &targets.glow_bind_group,
+                &targets.blurred_view,
+            ),
+            (
+                "Bloom Vertical Blur Pass",
+                &self.pipelines.blur_vertical,
+                &targets.blurred_bind_group,
+                &targets.glow_view,
+            ),
+            (
+                "Bloom Composite Pass",
+                &self.pipelines.composite,
+                &targets.glow_bind_group,
+                target,
+            ),
+        ];
+        for (label, pipeline, bind_group, view) in stages {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+/// Create the scene copy, the glow targets, and the bind groups reading
+/// each glow target.
+fn create_targets(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    (width, height): (u32, u32),
+    format: wgpu::TextureFormat,
+    (uniform_buffer, sampler): (&wgpu::Buffer, &wgpu::Sampler),
+) -> Targets {
+    let texture = |label, (width, height), format, usage| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        })
+    };
+    let scene_texture = texture(
+        "Bloom Scene Texture",
+        (width, height),
+        format,
+        wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+    );
+    let glow_usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+    let size = glow_size(width, height);
+    let view =
+        |texture: wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let glow_view = view(texture("Bloom Glow Texture", size, GLOW_FORMAT, glow_usage));
+    let blurred_view = view(texture(
+        "Bloom Blurred Texture",
+        size,
+        GLOW_FORMAT,
+        glow_usage,
+    ));
+
+    let scene_view = scene_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = |label, source: &wgpu::TextureView| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some(label),
+        })
+    };
+    let glow_bind_group = bind_group("Bloom Glow Bind Group", &glow_view);
+    let blurred_bind_group = bind_group("Bloom Blurred Bind Group", &blurred_view);
+
+    Targets {
+        scene_texture,
+        glow_view,
+        blurred_view,
+        glow_bind_group,
+        blurred_bind_group,
+    }
+}
+
+/// Build the fullscreen pipelines of each stage: the bright pass and blurs
+/// replace a glow target's pixels, the composite the surface's.
+fn create_pipelines(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> BloomPipelines {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Bloom Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let pipeline = |label, entry_point, format| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(entry_point),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache,
+        })
+    };
+
+    BloomPipelines {
+        bright: pipeline("Bloom Bright Pipeline", "fs_bright", GLOW_FORMAT),
+        blur_horizontal: pipeline(
+            "Bloom Horizontal Blur Pipeline",
+            "fs_blur_horizontal",
+            GLOW_FORMAT,
+        ),
+        blur_vertical: pipeline(
+            "Bloom Vertical Blur Pipeline",
+            "fs_blur_vertical",
+            GLOW_FORMAT,
+        ),
+        composite: pipeline("Bloom Composite Pipeline", "fs_composite", format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glow_size_rounds_up() {
+        assert_eq!(glow_size(96, 64), (48, 32));
+        assert_eq!(glow_size(97, 1), (49, 1));
+        assert_eq!(glow_size(0, 0), (1, 1));
+    }
+}
@@ -0,0 +1,252 @@
+//! Named camera views.
+//!
+//! Bookmarks keep camera poses worth coming back to. Each terrain has its
+//! own, saved as JSON next to the terrain file (`alps.tif` keeps them in
+//! `alps.tif.views.json`) whenever they change, so they last across
+//! sessions:
+//!
+//! ```json
+//! {"bookmarks":[
+//!   {"name":"Summit","target":[512,80,512],"distance":400,"azimuth":45,"elevation":30,"fov":60,
+//!    "aspect":1.78,"near":0.5,"far":2400,"projection":"perspective"}
+//! ]}
+//! ```
+//!
+//! Poses are stored like camera path keyframes, without the time, followed
+//! by the [`Lens`] the view was saved with, which bookmarks written by hand
+//! may leave out.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+
+use super::camera::{Camera, Projection};
+use super::camera_path::Keyframe;
+
+/// Viewport shape and clip planes of a bookmarked view, which with its
+/// pose give the frustum it saw.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lens {
+    /// Width/height aspect ratio of the viewport
+    pub aspect: f32,
+    /// Near clipping plane distance
+    pub near: f32,
+    /// Far clipping plane distance
+    pub far: f32,
+    /// Perspective or orthographic
+    pub projection: Projection,
+}
+
+impl Lens {
+    /// Lens of `camera` drawing a viewport of `aspect`.
+    pub fn from_camera(camera: &Camera, aspect: f32) -> Self {
+        Self {
+            aspect,
+            near: camera.near,
+            far: camera.far,
+            projection: camera.projection,
+        }
+    }
+
+    /// Give `camera` these clip planes and projection.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.near = self.near;
+        camera.far = self.far;
+        camera.projection = self.projection;
+    }
+}
+
+/// A named camera pose.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    /// Name shown in the list
+    pub name: String,
+    /// Camera pose; its time is unused
+    pub pose: Keyframe,
+    /// Lens the view was saved with, if known
+    pub lens: Option<Lens>,
+}
+
+/// The bookmarks of a terrain, with the file they're saved to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bookmarks {
+    /// Bookmarks in the order they were added
+    pub views: Vec<Bookmark>,
+    /// File the bookmarks are saved to, if any
+    pub file: Option<PathBuf>,
+}
+
+impl Bookmarks {
+    /// File keeping the bookmarks of the terrain at `terrain`.
+    pub fn file_for(terrain: &Path) -> PathBuf {
+        let mut name = terrain.as_os_str().to_owned();
+        name.push(".views.json");
+        PathBuf::from(name)
+    }
+
+    /// Read the bookmarks saved in `file`, or none if it doesn't exist
+    /// yet. Later changes are saved to it.
+    pub fn load(file: PathBuf) -> Result<Self> {
+        let views = match std::fs::read_to_string(&file) {
+            Ok(text) => Self::from_json(&text)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            views,
+            file: Some(file),
+        })
+    }
+
+    /// Write the bookmarks to their file, if they have one.
+    pub fn save(&self) -> Result<()> {
+        if let Some(file) = &self.file {
+            std::fs::write(file, self.to_json())?;
+        }
+        Ok(())
+    }
+
+    /// Keep `pose` seen through `lens` as `name`, replacing a bookmark of
+    /// the same name.
+    pub fn insert(&mut self, name: &str, pose: Keyframe, lens: Option<Lens>) {
+        match self.views.iter_mut().find(|view| view.name == name) {
+            Some(view) => (view.pose, view.lens) = (pose, lens),
+            None => self.views.push(Bookmark {
+                name: name.to_string(),
+                pose,
+                lens,
+            }),
+        }
+    }
+
+    /// Serialize as JSON, one bookmark per line.
+    pub fn to_json(&self) -> String {
+        let views: Vec<String> = self
+            .views
+            .iter()
+            .map(|view| {
+                let mut value = view.pose.pose_json();
+                value["name"] = json!(view.name);
+                if let Some(lens) = view.lens {
+                    value["aspect"] = json!(lens.aspect);
+                    value["near"] = json!(lens.near);
+                    value["far"] = json!(lens.far);
+                    value["projection"] = json!(match lens.projection {
+                        Projection::Perspective => "perspective",
+                        Projection::Orthographic => "orthographic",
+                    });
+                }
+                format!("  {value}")
+            })
+            .collect();
+        format!("{{\"bookmarks\":[\n{}\n]}}\n", views.join(",\n"))
+    }
+
+    /// Parse bookmarks written by [`to_json`](Self::to_json) or by hand.
+    pub fn from_json(text: &str) -> Result<Vec<Bookmark>> {
+        let value: Value = serde_json::from_str(text).context("Invalid JSON")?;
+        let views = value
+            .get("bookmarks")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("Expected an object with a \"bookmarks\" array"))?;
+        views
+            .iter()
+            .enumerate()
+            .map(|(i, view)| {
+                let name = view
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow!("missing string \"name\""))
+                    .with_context(|| format!("Bookmark {i}"))?;
+                let pose =
+                    Keyframe::pose_from_json(view, 0.0).with_context(|| format!("Bookmark {i}"))?;
+                let lens = lens_from_json(view).with_context(|| format!("Bookmark {i}"))?;
+                Ok(Bookmark {
+                    name: name.to_string(),
+                    pose,
+                    lens,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Lens of a bookmark written by [`Bookmarks::to_json`], or `None` if it
+/// has no aspect and clip planes.
+fn lens_from_json(view: &Value) -> Result<Option<Lens>> {
+    let number = |key: &str| view.get(key).and_then(Value::as_f64).map(|n| n as f32);
+    let (Some(aspect), Some(near), Some(far)) = (number("aspect"), number("near"), number("far"))
+    else {
+        return Ok(None);
+    };
+    if aspect <= 0.0 || far <= near {
+        bail!("\"aspect\" must be positive and \"far\" beyond \"near\"");
+    }
+    let projection = match view.get("projection").and_then(Value::as_str) {
+        None | Some("perspective") => Projection::Perspective,
+        Some("orthographic") => Projection::Orthographic,
+        Some(other) => bail!("Unknown projection {other:?}"),
+    };
+    Ok(Some(Lens {
+        aspect,
+        near,
+        far,
+        projection,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::DVec3;
+
+    fn pose(x: f64) -> Keyframe {
+        Keyframe {
+            time: 0.0,
+            target: DVec3::new(x, 1.0, 2.0),
+            distance: 50.0,
+            azimuth: 0.5,
+            elevation: 0.25,
+            fov: 45.0,
+        }
+    }
+
+    #[test]
+    fn test_bookmarks_round_trip_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = Bookmarks::file_for(&dir.path().join("alps.tif"));
+        assert!(file.ends_with("alps.tif.views.json"));
+
+        // No file yet: no bookmarks
+        let mut bookmarks = Bookmarks::load(file.clone()).unwrap();
+        assert!(bookmarks.views.is_empty());
+
+        let lens = Lens {
+            aspect: 1.5,
+            near: 0.5,
+            far: 800.0,
+            projection: Projection::Orthographic,
+        };
+        bookmarks.insert("Summit", pose(1.0), None);
+        bookmarks.insert("Valley", pose(2.0), None);
+        // Same name again moves the bookmark
+        bookmarks.insert("Summit", pose(3.0), Some(lens));
+        bookmarks.save().unwrap();
+
+        let read = Bookmarks::load(file).unwrap();
+        assert_eq!(read.views.len(), 2);
+        assert_eq!(read.views[0].name, "Summit");
+        assert_eq!(read.views[0].pose.target.x, 3.0);
+        assert_eq!(read.views[0].lens, Some(lens));
+        assert!((read.views[1].pose.azimuth - 0.5).abs() < 1e-6);
+        assert_eq!(read.views[1].lens, None);
+
+        let err = Bookmarks::from_json(r#"{"bookmarks": [{"name": "A"}]}"#).unwrap_err();
+        assert!(format!("{err:#}").contains("Bookmark 0"));
+        let view = r#"{"name":"A","target":[0,0,0],"distance":1,"azimuth":0,"elevation":0,
+            "aspect":1,"near":10,"far":1}"#;
+        let err = Bookmarks::from_json(&format!(r#"{{"bookmarks":[{view}]}}"#)).unwrap_err();
+        assert!(format!("{err:#}").contains("beyond"));
+    }
+}
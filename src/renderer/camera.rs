@@ -2,8 +2,10 @@
 //!
 //! Provides an orbital (arcball-style) camera that rotates around a target point.
 //! Supports perspective projection with configurable field of view.
+//! In [`CameraMode::Fly`] input turns the camera about its eye instead and
+//! moves eye and target together, for flying through the terrain.
 
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Vec2, Vec3};
 
 /// Projection mode for camera
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -13,6 +15,16 @@ pub enum Projection {
     Orthographic,
 }
 
+/// How input moves the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    /// Dragging orbits the target, scrolling zooms toward it
+    #[default]
+    Orbit,
+    /// Dragging looks around from the eye; keys move eye and target
+    Fly,
+}
+
 /// Orbital camera that rotates around a target point.
 ///
 /// Uses spherical coordinates (distance, azimuth, elevation) to position
@@ -23,6 +35,7 @@ pub enum Projection {
 /// - Azimuth: Horizontal rotation around Y axis (0 = +Z direction)
 /// - Elevation: Vertical angle from XZ plane (clamped to avoid gimbal lock)
 /// - Distance: Distance from target point
+#[derive(Debug, Clone)]
 pub struct Camera {
     /// Distance from target point
     pub distance: f32,
@@ -47,8 +60,24 @@ pub struct Camera {
 
     /// Projection mode (perspective or orthographic)
     pub projection: Projection,
+
+    /// Fit near/far to the scene each frame instead of using fixed values
+    pub auto_clip: bool,
+
+    /// How input moves the camera
+    pub mode: CameraMode,
 }
 
+/// Smallest allowed near/far ratio when fitting perspective clip planes.
+///
+/// Keeps the near plane from collapsing to zero when the camera is inside
+/// the scene bounds, which would waste all depth precision.
+const MIN_NEAR_FAR_RATIO: f32 = 1e-4;
+
+/// Extra room around a box framed by [`Camera::fit_to_bounds`], as a
+/// factor of its projected size.
+const FIT_MARGIN: f32 = 1.1;
+
 impl Camera {
     /// Create a new camera with default settings.
     ///
@@ -64,9 +93,84 @@ impl Camera {
             near: 0.1,
             far: 1000.0,
             projection: Projection::Perspective,
+            auto_clip: true,
+            mode: CameraMode::Orbit,
         }
     }
 
+    /// Fit the near and far planes tightly around a bounding sphere.
+    ///
+    /// Tighter planes give better depth precision, avoiding z-fighting on
+    /// large terrains and clipping when the terrain extends past a fixed
+    /// far plane.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - Bounding sphere center in world space
+    /// * `radius` - Bounding sphere radius
+    pub fn fit_clip_planes(&mut self, center: Vec3, radius: f32) {
+        let radius = radius.max(0.01);
+        let dist = (self.position() - center).length();
+        // Small margin so geometry exactly on the sphere isn't clipped
+        let far = (dist + radius) * 1.01;
+
+        self.far = far;
+        self.near = match self.projection {
+            Projection::Perspective => (dist - radius).max(far * MIN_NEAR_FAR_RATIO),
+            // Orthographic depth is linear and may extend behind the eye
+            Projection::Orthographic => dist - radius * 1.01,
+        };
+    }
+
+    /// Aim at the middle of an axis-aligned box from the distance where all
+    /// of it just fits the view, with a [`FIT_MARGIN`] border, keeping the
+    /// azimuth and elevation.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - Box corner with the smallest coordinates
+    /// * `max` - Box corner with the largest coordinates
+    /// * `aspect` - Width/height aspect ratio of the viewport
+    pub fn fit_to_bounds(&mut self, min: Vec3, max: Vec3, aspect: f32) {
+        let center = (min + max) / 2.0;
+        let half = ((max - min) / 2.0).max(Vec3::splat(0.01));
+        self.target = center;
+        self.distance = 1.0;
+        let forward = self.forward();
+        let right = forward.cross(Vec3::Y).normalize();
+        let up = right.cross(forward);
+
+        // Offsets of the corners across and along the view
+        let corners = (0..8).map(|i| {
+            let sign = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            let p = half * sign;
+            (
+                p.dot(right) * FIT_MARGIN,
+                p.dot(up) * FIT_MARGIN,
+                p.dot(forward),
+            )
+        });
+        self.distance = match self.projection {
+            Projection::Perspective => {
+                let tan_y = (self.fov.to_radians() / 2.0).tan();
+                let tan_x = tan_y * aspect;
+                // Each corner needs enough depth to fall inside the frustum
+                corners
+                    .map(|(x, y, depth)| (x.abs() / tan_x).max(y.abs() / tan_y) - depth)
+                    .fold(0.0, f32::max)
+            }
+            // The view is half the distance high
+            Projection::Orthographic => corners
+                .map(|(x, y, _)| (x.abs() * 2.0 / aspect).max(y.abs() * 2.0))
+                .fold(0.0, f32::max),
+        }
+        .max(0.01);
+    }
+
     /// Calculate camera position in world space from orbital parameters.
     ///
     /// Converts spherical coordinates (distance, azimuth, elevation) to
@@ -78,6 +182,23 @@ impl Camera {
         self.target + Vec3::new(x, y, z)
     }
 
+    /// Unit vector from the eye toward the target.
+    pub fn forward(&self) -> Vec3 {
+        (self.target - self.position()) / self.distance
+    }
+
+    /// Turn the view about the eye, which stays put: `yaw` radians to the
+    /// left and `pitch` radians down, with the elevation kept within
+    /// `min_elevation..=max_elevation`.
+    pub fn look(&mut self, yaw: f32, pitch: f32, (min_elevation, max_elevation): (f32, f32)) {
+        let eye = self.position();
+        self.azimuth += yaw;
+        self.elevation = (self.elevation + pitch).clamp(min_elevation, max_elevation);
+        // The target swings around the eye at the same distance
+        let offset = self.position() - self.target;
+        self.target = eye - offset;
+    }
+
     /// Build the view matrix (world to camera transform).
     ///
     /// Uses right-handed look-at with Y-up convention.
@@ -103,6 +224,39 @@ impl Camera {
         }
     }
 
+    /// World-space corners of the view frustum.
+    ///
+    /// Returns the four near-plane corners followed by the four far-plane
+    /// corners, each ordered bottom-left, bottom-right, top-right, top-left.
+    ///
+    /// # Arguments
+    ///
+    /// * `aspect` - Width/height aspect ratio of the viewport
+    pub fn frustum_corners(&self, aspect: f32) -> [Vec3; 8] {
+        let inverse = self.build_view_projection_matrix(aspect).inverse();
+        let ndc_xy = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+        let mut corners = [Vec3::ZERO; 8];
+        // wgpu clip space uses a 0..1 depth range
+        for (plane, z) in [0.0, 1.0].into_iter().enumerate() {
+            for (i, (x, y)) in ndc_xy.into_iter().enumerate() {
+                corners[plane * 4 + i] = inverse.project_point3(Vec3::new(x, y, z));
+            }
+        }
+        corners
+    }
+
+    /// World-space ray through a point on screen, given in normalized device
+    /// coordinates (x right, y up, -1 to 1).
+    ///
+    /// Returns the ray's origin on the near plane and its unit direction.
+    pub fn ray(&self, ndc: Vec2, aspect: f32) -> (Vec3, Vec3) {
+        let inverse = self.build_view_projection_matrix(aspect).inverse();
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        (near, (far - near).normalize())
+    }
+
     /// Set camera to isometric view preset.
     ///
     /// Sets orthographic projection with standard isometric angles:
@@ -231,8 +385,137 @@ mod tests {
         assert_ne!(persp, ortho);
     }
 
+    // ==================== Frustum Tests ====================
+
+    #[test]
+    fn test_frustum_plane_centers() {
+        let camera = Camera::new();
+        let corners = camera.frustum_corners(16.0 / 9.0);
+        let forward = (camera.target - camera.position()).normalize();
+
+        let near_center = corners[..4].iter().copied().sum::<Vec3>() / 4.0;
+        let far_center = corners[4..].iter().copied().sum::<Vec3>() / 4.0;
+
+        let expected_near = camera.position() + forward * camera.near;
+        let expected_far = camera.position() + forward * camera.far;
+        assert!((near_center - expected_near).length() < 0.01);
+        assert!((far_center - expected_far).length() / camera.far < 0.001);
+    }
+
+    #[test]
+    fn test_frustum_aspect() {
+        let mut camera = Camera::new();
+        camera.projection = Projection::Orthographic;
+        let corners = camera.frustum_corners(2.0);
+
+        // Orthographic: near and far planes have equal size, width = 2 * height
+        let width = (corners[1] - corners[0]).length();
+        let height = (corners[3] - corners[0]).length();
+        assert!((width / height - 2.0).abs() < 0.001);
+        assert!(((corners[5] - corners[4]).length() - width).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_ray_through_screen_center_and_corner() {
+        let camera = Camera::new();
+        let forward = (camera.target - camera.position()).normalize();
+
+        let (origin, direction) = camera.ray(Vec2::ZERO, 1.5);
+        assert!((origin - (camera.position() + forward * camera.near)).length() < 0.01);
+        assert!((direction - forward).length() < 1e-4);
+
+        // The bottom-left ray runs along the frustum's bottom-left edge
+        let corners = camera.frustum_corners(1.5);
+        let (origin, direction) = camera.ray(Vec2::new(-1.0, -1.0), 1.5);
+        assert!((origin - corners[0]).length() < 0.01);
+        assert!((direction - (corners[4] - corners[0]).normalize()).length() < 1e-4);
+    }
+
+    // ==================== Clip Plane Tests ====================
+
+    #[test]
+    fn test_fit_clip_planes_outside_sphere() {
+        let mut camera = Camera::new();
+        camera.distance = 100.0;
+        camera.fit_clip_planes(Vec3::ZERO, 10.0);
+
+        assert!(camera.near > 85.0 && camera.near <= 90.0);
+        assert!(camera.far >= 110.0 && camera.far < 115.0);
+    }
+
+    #[test]
+    fn test_fit_clip_planes_inside_sphere() {
+        let mut camera = Camera::new();
+        camera.distance = 5.0;
+        camera.fit_clip_planes(Vec3::ZERO, 1000.0);
+
+        // Near stays positive for perspective
+        assert!(camera.near > 0.0);
+        assert!(camera.far > 1005.0);
+        assert!(camera.build_projection_matrix(1.0).determinant().abs() > 0.0);
+    }
+
+    #[test]
+    fn test_fit_clip_planes_orthographic_behind_eye() {
+        let mut camera = Camera::new();
+        camera.projection = Projection::Orthographic;
+        camera.distance = 5.0;
+        camera.fit_clip_planes(Vec3::ZERO, 100.0);
+
+        assert!(camera.near < 0.0);
+    }
+
+    #[test]
+    fn test_fit_to_bounds_frames_box() {
+        let (min, max) = (Vec3::new(-10.0, 0.0, -30.0), Vec3::new(30.0, 5.0, 10.0));
+        let mut camera = Camera::new();
+        for (projection, aspect) in [
+            (Projection::Perspective, 2.0),
+            (Projection::Perspective, 0.5),
+            (Projection::Orthographic, 0.5),
+        ] {
+            camera.projection = projection;
+            camera.fit_to_bounds(min, max, aspect);
+            assert_eq!(camera.target, Vec3::new(10.0, 2.5, -10.0));
+            camera.fit_clip_planes(camera.target, (max - min).length() / 2.0);
+
+            // Every corner in view, the outermost just inside the margin
+            let view_proj = camera.build_view_projection_matrix(aspect);
+            let extent = (0..8)
+                .map(|i| {
+                    let corner = Vec3::select(
+                        glam::BVec3::new(i & 1 == 0, i & 2 == 0, i & 4 == 0),
+                        min,
+                        max,
+                    );
+                    let ndc = view_proj.project_point3(corner);
+                    assert!((0.0..=1.0).contains(&ndc.z));
+                    ndc.x.abs().max(ndc.y.abs())
+                })
+                .fold(0.0, f32::max);
+            assert!((extent - 1.0 / FIT_MARGIN).abs() < 1e-4, "{extent}");
+        }
+    }
+
     // ==================== Isometric Preset Tests ====================
 
+    #[test]
+    fn test_look_keeps_eye() {
+        let mut camera = Camera::new();
+        camera.target = Vec3::new(3.0, 1.0, -2.0);
+        let eye = camera.position();
+
+        camera.look(0.5, -0.2, (-1.4, 1.4));
+        assert!((camera.position() - eye).length() < 1e-4);
+        assert!((camera.forward().length() - 1.0).abs() < 1e-5);
+        assert_eq!(camera.elevation, std::f32::consts::FRAC_PI_6 - 0.2);
+
+        // Pitch stops at the limits
+        camera.look(0.0, 10.0, (-1.4, 1.4));
+        assert_eq!(camera.elevation, 1.4);
+        assert!((camera.position() - eye).length() < 1e-4);
+    }
+
     #[test]
     fn test_isometric_preset_sets_orthographic() {
         let mut camera = Camera::new();
@@ -0,0 +1,341 @@
+//! Camera paths for fly-throughs.
+//!
+//! A path is a list of camera keyframes at timeline times; between two
+//! keyframes every value is interpolated linearly. Paths are saved as JSON
+//! so they can be versioned, shared or written by scripts:
+//!
+//! ```json
+//! {"keyframes":[
+//!   {"time":0,"target":[512,80,512],"distance":400,"azimuth":45,"elevation":30,"fov":60},
+//!   {"time":10,"target":[600,60,480],"distance":150,"azimuth":135,"elevation":20,"fov":60}
+//! ]}
+//! ```
+//!
+//! `target` is the point looked at in terrain coordinates (x and z as in
+//! the grid's georeference, y the height as drawn), so a path stays put
+//! when the renderer moves its origin. Angles are in degrees; azimuths
+//! aren't wrapped, so going from 350 to 370 turns 20 degrees.
+//!
+//! Paths can also be recorded from the live camera while flying or
+//! orbiting by hand, a keyframe every [`RECORD_INTERVAL`] seconds.
+
+use anyhow::{anyhow, bail, Context, Result};
+use glam::DVec3;
+use serde_json::{json, Value};
+
+use super::camera::Camera;
+
+/// Seconds between keyframes recorded from the live camera.
+pub const RECORD_INTERVAL: f32 = 0.5;
+
+/// Camera pose at a timeline time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    /// Timeline time in seconds
+    pub time: f32,
+    /// Point looked at, in terrain coordinates
+    pub target: DVec3,
+    /// Distance from the target
+    pub distance: f32,
+    /// Horizontal rotation in radians
+    pub azimuth: f32,
+    /// Vertical rotation in radians
+    pub elevation: f32,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+}
+
+impl Keyframe {
+    /// Pose of `camera` at `time`, for a renderer whose world origin is at
+    /// `origin` in terrain coordinates.
+    pub fn from_camera(camera: &Camera, origin: DVec3, time: f32) -> Self {
+        Self {
+            time,
+            target: camera.target.as_dvec3() + origin,
+            distance: camera.distance,
+            azimuth: camera.azimuth,
+            elevation: camera.elevation,
+            fov: camera.fov,
+        }
+    }
+
+    /// Move `camera` to this pose.
+    pub fn apply(&self, camera: &mut Camera, origin: DVec3) {
+        camera.target = (self.target - origin).as_vec3();
+        camera.distance = self.distance;
+        camera.azimuth = self.azimuth;
+        camera.elevation = self.elevation;
+        camera.fov = self.fov;
+    }
+
+    /// Pose a fraction `t` of the way to `other`.
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            time: mix(self.time, other.time),
+            target: self.target.lerp(other.target, t as f64),
+            distance: mix(self.distance, other.distance),
+            azimuth: mix(self.azimuth, other.azimuth),
+            elevation: mix(self.elevation, other.elevation),
+            fov: mix(self.fov, other.fov),
+        }
+    }
+
+    fn to_json(self) -> Value {
+        let mut value = self.pose_json();
+        value["time"] = json!(self.time);
+        value
+    }
+
+    fn from_json(value: &Value) -> Result<Self> {
+        let time = value
+            .get("time")
+            .and_then(Value::as_f64)
+            .ok_or_else(|| anyhow!("missing number \"time\""))?;
+        Self::pose_from_json(value, time as f32)
+    }
+
+    /// The pose as a JSON object, without the time, as in keyframes.
+    pub(super) fn pose_json(self) -> Value {
+        json!({
+            "target": self.target.to_array(),
+            "distance": self.distance,
+            "azimuth": self.azimuth.to_degrees(),
+            "elevation": self.elevation.to_degrees(),
+            "fov": self.fov,
+        })
+    }
+
+    /// Read a pose written by [`pose_json`](Self::pose_json) or by hand,
+    /// placing it at `time`.
+    pub(super) fn pose_from_json(value: &Value, time: f32) -> Result<Self> {
+        let number = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_f64)
+                .ok_or_else(|| anyhow!("missing number \"{key}\""))
+        };
+        let target: Vec<f64> = value
+            .get("target")
+            .and_then(Value::as_array)
+            .map(|xyz| xyz.iter().filter_map(Value::as_f64).collect())
+            .unwrap_or_default();
+        let [x, y, z] = target[..] else {
+            bail!("\"target\" must be an array of 3 numbers");
+        };
+        let distance = number("distance")? as f32;
+        if distance <= 0.0 {
+            bail!("\"distance\" must be positive");
+        }
+        Ok(Self {
+            time,
+            target: DVec3::new(x, y, z),
+            distance,
+            azimuth: (number("azimuth")? as f32).to_radians(),
+            elevation: (number("elevation")? as f32).to_radians(),
+            // Optional, for scripts that don't care about the lens
+            fov: number("fov").map_or(Camera::new().fov, |fov| fov as f32),
+        })
+    }
+}
+
+/// Keyframes of a fly-through, in time order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    /// Add `keyframe` in time order, replacing one at the same time.
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        let i = self
+            .keyframes
+            .partition_point(|other| other.time < keyframe.time);
+        match self.keyframes.get_mut(i) {
+            Some(other) if other.time == keyframe.time => *other = keyframe,
+            _ => self.keyframes.insert(i, keyframe),
+        }
+    }
+
+    /// Time of the last keyframe, or zero for an empty path.
+    pub fn end(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |last| last.time)
+    }
+
+    /// Pose at `time`, holding the first and last keyframes before and
+    /// after the path. `None` for an empty path.
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let first = self.keyframes.first()?;
+        let i = self.keyframes.partition_point(|key| key.time <= time);
+        let pose = match (i.checked_sub(1), self.keyframes.get(i)) {
+            (Some(prev), Some(next)) => {
+                let prev = &self.keyframes[prev];
+                prev.lerp(next, (time - prev.time) / (next.time - prev.time))
+            }
+            (Some(prev), None) => self.keyframes[prev],
+            (None, _) => *first,
+        };
+        Some(Keyframe { time, ..pose })
+    }
+
+    /// Serialize as JSON, one keyframe per line.
+    pub fn to_json(&self) -> String {
+        let keyframes: Vec<String> = self
+            .keyframes
+            .iter()
+            .map(|key| format!("  {}", key.to_json()))
+            .collect();
+        format!("{{\"keyframes\":[\n{}\n]}}\n", keyframes.join(",\n"))
+    }
+
+    /// Parse a path written by [`to_json`](Self::to_json) or by hand.
+    /// Keyframes may come in any order.
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(text).context("Invalid JSON")?;
+        let keyframes = value
+            .get("keyframes")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("Expected an object with a \"keyframes\" array"))?;
+        let mut path = Self::default();
+        for (i, keyframe) in keyframes.iter().enumerate() {
+            path.insert(Keyframe::from_json(keyframe).with_context(|| format!("Keyframe {i}"))?);
+        }
+        Ok(path)
+    }
+}
+
+/// Recording of the live camera into a camera path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recording {
+    /// Timeline time of the first keyframe
+    pub start: f32,
+    /// Seconds recorded so far
+    elapsed: f32,
+    /// Seconds into the recording the next keyframe is due
+    next: f32,
+}
+
+impl Recording {
+    /// Start recording keyframes from timeline time `start`.
+    pub fn new(start: f32) -> Self {
+        Self {
+            start,
+            elapsed: 0.0,
+            next: 0.0,
+        }
+    }
+
+    /// Timeline time the recording has reached.
+    pub fn time(&self) -> f32 {
+        self.start + self.elapsed
+    }
+
+    /// Add a keyframe of `camera` to `path` if one is due, then run the
+    /// recording on by `dt` seconds.
+    pub fn record(&mut self, path: &mut CameraPath, camera: &Camera, origin: DVec3, dt: f32) {
+        if self.elapsed >= self.next {
+            path.insert(Keyframe::from_camera(camera, origin, self.time()));
+            self.next = self.elapsed + RECORD_INTERVAL;
+        }
+        self.elapsed += dt.max(0.0);
+    }
+
+    /// End the recording with a keyframe of `camera` where it stopped.
+    pub fn finish(self, path: &mut CameraPath, camera: &Camera, origin: DVec3) {
+        path.insert(Keyframe::from_camera(camera, origin, self.time()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, x: f64, azimuth: f32) -> Keyframe {
+        Keyframe {
+            time,
+            target: DVec3::new(x, 0.0, 0.0),
+            distance: 10.0,
+            azimuth,
+            elevation: 0.5,
+            fov: 60.0,
+        }
+    }
+
+    #[test]
+    fn test_sample_between_keyframes() {
+        let mut path = CameraPath::default();
+        path.insert(keyframe(4.0, 100.0, 2.0));
+        path.insert(keyframe(0.0, 0.0, 0.0));
+        // Same time again replaces the keyframe
+        path.insert(keyframe(4.0, 200.0, 4.0));
+        assert_eq!(path.keyframes.len(), 2);
+        assert_eq!(path.end(), 4.0);
+
+        let middle = path.sample(1.0).unwrap();
+        assert_eq!(middle.target.x, 50.0);
+        assert_eq!(middle.azimuth, 1.0);
+        assert_eq!(middle.time, 1.0);
+
+        // Held before and after the path
+        assert_eq!(path.sample(-1.0).unwrap().target.x, 0.0);
+        assert_eq!(path.sample(9.0).unwrap().target.x, 200.0);
+        assert_eq!(CameraPath::default().sample(0.0), None);
+    }
+
+    #[test]
+    fn test_recording_keeps_a_keyframe_per_interval() {
+        let mut path = CameraPath::default();
+        let mut camera = Camera::new();
+        let mut recording = Recording::new(10.0);
+        // Two seconds at 8 frames a second, moving steadily
+        for _ in 0..16 {
+            recording.record(&mut path, &camera, DVec3::ZERO, 0.125);
+            camera.target.x += 1.0;
+        }
+        recording.finish(&mut path, &camera, DVec3::ZERO);
+
+        let times: Vec<f32> = path.keyframes.iter().map(|key| key.time).collect();
+        assert_eq!(times, [10.0, 10.5, 11.0, 11.5, 12.0]);
+        assert_eq!(path.keyframes[1].target.x, 4.0);
+        assert_eq!(path.keyframes[4].target.x, 16.0);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut path = CameraPath::default();
+        path.insert(keyframe(0.0, 512.5, std::f32::consts::FRAC_PI_2));
+        path.insert(keyframe(2.5, -3.0, 0.25));
+        let json = path.to_json();
+        assert!(json.contains("\"azimuth\":90.0"));
+
+        let parsed = CameraPath::from_json(&json).unwrap();
+        assert_eq!(parsed.keyframes.len(), 2);
+        for (a, b) in parsed.keyframes.iter().zip(&path.keyframes) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.target, b.target);
+            assert!((a.azimuth - b.azimuth).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_parse_scripted_path() {
+        // Out of order, without a field of view
+        let path = CameraPath::from_json(
+            r#"{"keyframes": [
+                {"time": 5, "target": [1, 2, 3], "distance": 20, "azimuth": 0, "elevation": 45},
+                {"time": 0, "target": [0, 0, 0], "distance": 10, "azimuth": 0, "elevation": 45}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(path.keyframes[1].target, DVec3::new(1.0, 2.0, 3.0));
+        assert_eq!(path.keyframes[1].fov, 60.0);
+
+        let err = CameraPath::from_json(r#"{"keyframes": [{"time": 0, "target": [0, 0, 0]}]}"#)
+            .unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "Keyframe 0: missing number \"distance\""
+        );
+        assert!(CameraPath::from_json("[]").is_err());
+    }
+}
@@ -0,0 +1,562 @@
+//! Geometry clipmap rendering for large terrains.
+//!
+//! Instead of building a vertex per height sample, the terrain heights are
+//! uploaded once as a texture and drawn with a fixed grid that follows the
+//! camera. The grid is instanced once per level, each level covering twice
+//! the area of the previous one at half the resolution, so the vertex count
+//! stays constant no matter how large the terrain is. See `clipmap.wgsl`
+//! for how the levels are nested and stitched together.
+
+use bytemuck::{Pod, Zeroable};
+use glam::{DVec3, Mat4, Vec2, Vec3, Vec3Swizzles};
+use wgpu::util::DeviceExt;
+
+use super::{DepthMode, LightingConfig, DEPTH_FORMAT};
+use crate::terrain::colors::{height_to_color, height_to_color_custom, linear_to_srgb};
+use crate::terrain::{ColorScheme, GradientConfig, TerrainData};
+
+/// Quads along one side of a clipmap level. Must be divisible by 4.
+pub const GRID_SIZE: u32 = 64;
+
+/// Upper bound on the number of levels, enough for a 2M-sample wide grid.
+pub const MAX_LEVELS: u32 = 16;
+
+/// Entries in the color scheme lookup texture.
+const COLOR_RAMP_SIZE: usize = 256;
+
+/// Vertex of the shared level grid, in level quads relative to its center.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct GridVertex {
+    pub grid: [f32; 2],
+}
+
+impl GridVertex {
+    /// Returns the vertex buffer layout descriptor for wgpu.
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GridVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x2,
+            }],
+        }
+    }
+}
+
+/// A `size` x `size` quad grid centered on the origin, as a triangle list
+/// with the same winding as [`TerrainMesh`](crate::terrain::TerrainMesh).
+pub fn grid_geometry(size: u32) -> (Vec<GridVertex>, Vec<u32>) {
+    let half = size as f32 / 2.0;
+    let side = size + 1;
+
+    let mut vertices = Vec::with_capacity((side * side) as usize);
+    for z in 0..side {
+        for x in 0..side {
+            vertices.push(GridVertex {
+                grid: [x as f32 - half, z as f32 - half],
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity((size * size * 6) as usize);
+    for z in 0..size {
+        for x in 0..size {
+            let top_left = z * side + x;
+            let top_right = top_left + 1;
+            let bottom_left = top_left + side;
+            let bottom_right = bottom_left + 1;
+            indices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                top_right,
+                top_right,
+                bottom_left,
+                bottom_right,
+            ]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Number of levels needed for the coarsest one to cover the whole terrain
+/// from anywhere on it.
+pub fn level_count(width: usize, height: usize) -> u32 {
+    let extent = width.max(height) as f64;
+    let half_size = GRID_SIZE as f64 / 2.0;
+    let mut levels = 1;
+    while half_size * 2f64.powi(levels as i32 - 1) < extent && levels < MAX_LEVELS {
+        levels += 1;
+    }
+    levels
+}
+
+/// Sample a color scheme into an sRGB RGBA8 lookup table.
+///
+/// The ramp texture is sRGB, so the shader reads linear colors. Without
+/// `linear_colors` the scheme colors are encoded once more so they read back
+/// unconverted, matching [`MeshOptions::linear_colors`](crate::terrain::MeshOptions::linear_colors).
+pub fn color_ramp(
+    scheme: ColorScheme,
+    gradient: &GradientConfig,
+    linear_colors: bool,
+) -> Vec<[u8; 4]> {
+    (0..COLOR_RAMP_SIZE)
+        .map(|i| {
+            let t = i as f32 / (COLOR_RAMP_SIZE - 1) as f32;
+            let color = match scheme {
+                ColorScheme::Custom => height_to_color_custom(t, gradient),
+                scheme => height_to_color(t, scheme),
+            };
+            let color = if linear_colors {
+                color
+            } else {
+                color.map(linear_to_srgb)
+            };
+            let [r, g, b] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+            [r, g, b, 255]
+        })
+        .collect()
+}
+
+/// Bounding box of the terrain relative to `origin` as `(min, max)`, as
+/// used for fitting clip planes and framing when no mesh is built.
+pub fn terrain_bounds(terrain: &TerrainData, origin: DVec3, height_scale: f32) -> (Vec3, Vec3) {
+    let (min_h, max_h) = terrain.height_bounds();
+    let scale = height_scale as f64;
+    let mut center = terrain.center();
+    center.y = (min_h + max_h) / 2.0 * scale;
+
+    let size = DVec3::new(
+        terrain.width.saturating_sub(1) as f64 * terrain.cell_size,
+        (max_h - min_h) * scale,
+        terrain.height.saturating_sub(1) as f64 * terrain.cell_size,
+    );
+    let center = center - origin;
+    (
+        (center - size / 2.0).as_vec3(),
+        (center + size / 2.0).as_vec3(),
+    )
+}
+
+/// Uniform data for the clipmap shader.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct ClipmapUniforms {
+    view_proj: [[f32; 4]; 4],
+    light_dir: [f32; 3],
+    ambient: f32,
+    light_color: [f32; 3],
+    log_depth_coef: f32,
+    grid_origin: [f32; 2],
+    cell_size: f32,
+    height_scale: f32,
+    camera_grid: [f32; 2],
+    grid_extent: [f32; 2],
+    height_base: f32,
+    height_range: f32,
+    curvature_coef: f32,
+    grid_size: f32,
+    curvature_center: [f32; 2],
+    _pad: [f32; 2],
+}
+
+impl ClipmapUniforms {
+    /// Uniforms placing `clipmap` relative to the render origin.
+    pub fn new(clipmap: &Clipmap, render_origin: DVec3, height_scale: f32) -> Self {
+        let scale = height_scale as f64;
+        Self {
+            view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            light_dir: [0.5, 0.8, 0.3],
+            ambient: 0.3,
+            light_color: [1.0, 1.0, 1.0],
+            log_depth_coef: 0.0,
+            grid_origin: (clipmap.origin - render_origin.xz()).as_vec2().to_array(),
+            cell_size: clipmap.cell_size as f32,
+            height_scale,
+            camera_grid: [0.0; 2],
+            grid_extent: clipmap.extent.to_array(),
+            height_base: (clipmap.min_height * scale - render_origin.y) as f32,
+            height_range: clipmap.height_range,
+            curvature_coef: 0.0,
+            grid_size: GRID_SIZE as f32,
+            curvature_center: [0.0; 2],
+            _pad: [0.0; 2],
+        }
+    }
+
+    pub fn update(&mut self, view_proj: Mat4, log_depth_coef: f32, lighting: &LightingConfig) {
+        self.view_proj = view_proj.to_cols_array_2d();
+        self.log_depth_coef = log_depth_coef;
+        self.light_dir = lighting.direction.to_array();
+        self.light_color = lighting.color.to_array();
+        self.ambient = lighting.ambient;
+    }
+
+    pub fn set_curvature(&mut self, coef: f32, center: Vec2) {
+        self.curvature_coef = coef;
+        self.curvature_center = center.to_array();
+    }
+
+    /// Center the levels on the camera's horizontal position.
+    pub fn set_camera(&mut self, position: Vec3) {
+        let grid = (position.xz() - Vec2::from_array(self.grid_origin)) / self.cell_size;
+        self.camera_grid = grid.to_array();
+    }
+}
+
+/// Bind group layout: uniforms, shader params, scene uniforms, height map,
+/// color ramp.
+pub fn bind_group_layout(
+    device: &wgpu::Device,
+    params_entry: wgpu::BindGroupLayoutEntry,
+    scene_entry: wgpu::BindGroupLayoutEntry,
+) -> wgpu::BindGroupLayout {
+    let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            params_entry,
+            scene_entry,
+            texture_entry(2),
+            texture_entry(3),
+        ],
+        label: Some("Clipmap Bind Group Layout"),
+    })
+}
+
+/// Build the clipmap pipeline from WGSL source, drawing `samples` per
+/// pixel.
+pub fn create_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    depth_mode: DepthMode,
+    samples: u32,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Clipmap Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Clipmap Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[GridVertex::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(depth_mode.fragment_entry_point()),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: samples,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache,
+    })
+}
+
+/// GPU resources for drawing one terrain as a clipmap.
+pub struct Clipmap {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    color_texture: wgpu::Texture,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    levels: u32,
+    /// World-space (x, z) of sample [0][0]
+    origin: glam::DVec2,
+    cell_size: f64,
+    /// Largest valid texel coordinate
+    extent: Vec2,
+    min_height: f64,
+    height_range: f32,
+}
+
+impl Clipmap {
+    /// Upload the terrain heights and build the level grid.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the terrain is empty or larger than the
+    /// device's maximum texture size.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        (params_buffer, scene_buffer): (&wgpu::Buffer, &wgpu::Buffer),
+        terrain: &TerrainData,
+    ) -> Result<Self, String> {
+        let max_size = device.limits().max_texture_dimension_2d as usize;
+        if terrain.width == 0 || terrain.height == 0 {
+            return Err("terrain is empty".to_string());
+        }
+        if terrain.width > max_size || terrain.height > max_size {
+            return Err(format!(
+                "{}x{} terrain exceeds the maximum texture size {}",
+                terrain.width, terrain.height, max_size
+            ));
+        }
+
+        // Store heights relative to the minimum to keep f32 precision
+        let (min_h, max_h) = terrain.height_bounds();
+        let heights: Vec<f32> = terrain
+            .heights()
+            .iter()
+            .map(|&h| (h - min_h) as f32)
+            .collect();
+
+        let height_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Clipmap Height Texture"),
+                size: wgpu::Extent3d {
+                    width: terrain.width as u32,
+                    height: terrain.height as u32,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&heights),
+        );
+        let color_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Clipmap Color Ramp"),
+            size: wgpu::Extent3d {
+                width: COLOR_RAMP_SIZE as u32,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Clipmap Uniform Buffer"),
+            size: std::mem::size_of::<ClipmapUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: super::params::PARAMS_BINDING,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: super::atmosphere::SCENE_BINDING,
+                    resource: scene_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &height_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(
+                        &color_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+            label: Some("Clipmap Bind Group"),
+        });
+
+        let (vertices, indices) = grid_geometry(GRID_SIZE);
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Clipmap Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Clipmap Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let levels = level_count(terrain.width, terrain.height);
+        log::info!(
+            "Clipmap: {} levels of {}x{} quads",
+            levels,
+            GRID_SIZE,
+            GRID_SIZE
+        );
+
+        Ok(Self {
+            uniform_buffer,
+            bind_group,
+            color_texture,
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            levels,
+            origin: terrain.origin,
+            cell_size: terrain.cell_size,
+            extent: Vec2::new((terrain.width - 1) as f32, (terrain.height - 1) as f32),
+            min_height: min_h,
+            height_range: (max_h - min_h) as f32,
+        })
+    }
+
+    /// Upload the color ramp for a color scheme; see [`color_ramp`].
+    pub fn set_colors(
+        &self,
+        queue: &wgpu::Queue,
+        scheme: ColorScheme,
+        gradient: &GradientConfig,
+        linear_colors: bool,
+    ) {
+        let ramp = color_ramp(scheme, gradient, linear_colors);
+        queue.write_texture(
+            self.color_texture.as_image_copy(),
+            bytemuck::cast_slice(&ramp),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(COLOR_RAMP_SIZE as u32 * 4),
+                rows_per_image: None,
+            },
+            self.color_texture.size(),
+        );
+    }
+
+    /// Write this frame's uniforms.
+    pub fn write_uniforms(&self, queue: &wgpu::Queue, uniforms: &ClipmapUniforms) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniforms));
+    }
+
+    /// Draw all levels. The clipmap pipeline must already be set.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..self.levels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_geometry() {
+        let (vertices, indices) = grid_geometry(4);
+
+        assert_eq!(vertices.len(), 25);
+        assert_eq!(indices.len(), 4 * 4 * 6);
+        assert_eq!(vertices[0].grid, [-2.0, -2.0]);
+        assert_eq!(vertices[24].grid, [2.0, 2.0]);
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+    }
+
+    #[test]
+    fn test_level_count_covers_terrain() {
+        assert_eq!(level_count(10, 10), 1);
+        assert_eq!(level_count(32, 32), 1);
+        assert_eq!(level_count(33, 10), 2);
+
+        let levels = level_count(5000, 100);
+        let coverage = GRID_SIZE as f64 / 2.0 * 2f64.powi(levels as i32 - 1);
+        assert!(coverage >= 5000.0);
+        assert!(coverage / 2.0 < 5000.0);
+
+        assert_eq!(level_count(usize::MAX, 1), MAX_LEVELS);
+    }
+
+    #[test]
+    fn test_color_ramp_matches_scheme() {
+        let ramp = color_ramp(ColorScheme::Monochrome, &GradientConfig::default(), true);
+
+        assert_eq!(ramp.len(), COLOR_RAMP_SIZE);
+        assert!(ramp[0][0] < ramp[COLOR_RAMP_SIZE - 1][0]);
+        assert!(ramp.iter().all(|c| c[3] == 255));
+
+        // Monochrome starts at 0.1; unconverted colors are encoded again
+        assert_eq!(ramp[0][0], 26);
+        let unconverted = color_ramp(ColorScheme::Monochrome, &GradientConfig::default(), false);
+        assert_eq!(unconverted[0][0], 89);
+    }
+
+    #[test]
+    fn test_terrain_bounds() {
+        let terrain = TerrainData::new(vec![vec![0.0, 0.0, 0.0], vec![0.0, 0.0, 4.0]], None);
+        let (min, max) = terrain_bounds(&terrain, terrain.center(), 1.0);
+
+        assert!((min - Vec3::new(-1.0, 0.0, -0.5)).length() < 1e-5);
+        assert!((max - Vec3::new(1.0, 4.0, 0.5)).length() < 1e-5);
+    }
+
+    #[test]
+    fn test_uniform_size() {
+        let uniforms_size = std::mem::size_of::<ClipmapUniforms>();
+        assert_eq!(uniforms_size % 16, 0);
+        assert_eq!(uniforms_size, 160);
+    }
+}
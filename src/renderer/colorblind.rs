@@ -0,0 +1,350 @@
+//! Color vision deficiency simulation.
+//!
+//! A last pass over the finished scene, before the UI, shows it as seen
+//! with protanopia, deuteranopia or tritanopia, so palette authors can check
+//! that a custom gradient's colors stay apart. Colors are mapped in linear
+//! RGB with the full-severity matrices of Machado, Oliveira and Fernandes
+//! (2009).
+//!
+//! Like the layer blend modes, the pass reads a copy of the scene, which
+//! needs `COPY_SRC` on the surface; without it the simulation is skipped.
+
+/// Simulated color vision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorVision {
+    /// No simulation
+    #[default]
+    Normal,
+    /// Missing long-wavelength (red) cones
+    Protanopia,
+    /// Missing medium-wavelength (green) cones
+    Deuteranopia,
+    /// Missing short-wavelength (blue) cones
+    Tritanopia,
+}
+
+impl ColorVision {
+    /// All options, in UI order.
+    pub const ALL: [ColorVision; 4] = [
+        ColorVision::Normal,
+        ColorVision::Protanopia,
+        ColorVision::Deuteranopia,
+        ColorVision::Tritanopia,
+    ];
+
+    /// Display name.
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorVision::Normal => "Normal",
+            ColorVision::Protanopia => "Protanopia",
+            ColorVision::Deuteranopia => "Deuteranopia",
+            ColorVision::Tritanopia => "Tritanopia",
+        }
+    }
+
+    /// Row-major matrix taking a linear RGB color to the simulated one.
+    pub fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            ColorVision::Normal => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            ColorVision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorVision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    /// Simulated color of a linear RGB color, clamped to the displayable
+    /// range.
+    pub fn simulate(self, color: [f32; 3]) -> [f32; 3] {
+        self.matrix()
+            .map(|row| (row[0] * color[0] + row[1] * color[1] + row[2] * color[2]).clamp(0.0, 1.0))
+    }
+}
+
+/// Simulation shader uniforms.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ColorblindUniforms {
+    /// Matrix rows, padded to vec4
+    rows: [[f32; 4]; 3],
+    /// Whether the scene copy holds sRGB-encoded values to linearize
+    encoded: u32,
+    _pad: [u32; 3],
+}
+
+/// Scene copy and pipeline of the simulation pass.
+pub struct Colorblind {
+    scene_texture: wgpu::Texture,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Colorblind {
+    /// Create the scene copy of the given size and the pipeline drawing
+    /// onto a surface of `format`, from `colorblind.wgsl` source.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Colorblind Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Colorblind Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, cache, &pipeline_layout, format, source);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Colorblind Uniform Buffer"),
+            size: std::mem::size_of::<ColorblindUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let scene_texture = create_scene_texture(device, width, height, format);
+        let bind_group =
+            create_bind_group(device, &bind_group_layout, &scene_texture, &uniform_buffer);
+
+        Self {
+            scene_texture,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    /// Recreate the scene copy for a new surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.scene_texture =
+            create_scene_texture(device, width, height, self.scene_texture.format());
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.scene_texture,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Build a pipeline from new shader source, to be installed with
+    /// [`set_pipeline`](Self::set_pipeline) once it validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_pipeline(device, cache, &self.pipeline_layout, format, source)
+    }
+
+    /// Replace the pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Redraw `target`, a view of `surface`, as seen with `vision`.
+    pub fn apply(
+        &self,
+        vision: ColorVision,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &wgpu::Texture,
+        target: &wgpu::TextureView,
+    ) {
+        let matrix = vision.matrix();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&ColorblindUniforms {
+                rows: matrix.map(|[r, g, b]| [r, g, b, 0.0]),
+                encoded: u32::from(!surface.format().is_srgb()),
+                _pad: [0; 3],
+            }),
+        );
+        encoder.copy_texture_to_texture(
+            surface.as_image_copy(),
+            self.scene_texture.as_image_copy(),
+            self.scene_texture.size(),
+        );
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Colorblind Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Create the texture receiving a copy of the scene.
+fn create_scene_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Colorblind Scene Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+/// Bind the scene copy and the simulation uniforms.
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    scene_texture: &wgpu::Texture,
+    uniform_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    let scene_view = scene_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&scene_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("Colorblind Bind Group"),
+    })
+}
+
+/// Build the fullscreen pipeline replacing the target's pixels.
+fn create_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Colorblind Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Colorblind Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grays_are_kept() {
+        for vision in ColorVision::ALL {
+            for gray in [0.0, 0.5, 1.0] {
+                let [r, g, b] = vision.simulate([gray; 3]);
+                for channel in [r, g, b] {
+                    assert!((channel - gray).abs() < 1e-3, "{:?}", vision);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_red_green_confusion() {
+        let red = [0.8, 0.1, 0.1];
+        let green = [0.1, 0.45, 0.1];
+        let distance =
+            |a: [f32; 3], b: [f32; 3]| (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt();
+
+        let normal = distance(red, green);
+        for vision in [ColorVision::Protanopia, ColorVision::Deuteranopia] {
+            let simulated = distance(vision.simulate(red), vision.simulate(green));
+            assert!(simulated < normal * 0.5, "{:?}: {}", vision, simulated);
+        }
+        assert_eq!(ColorVision::Normal.simulate(red), red);
+    }
+}
@@ -0,0 +1,147 @@
+//! A/B swipe comparison of render settings.
+//!
+//! A snapshot keeps the settings applied when drawing: render mode,
+//! lighting, contours and height scale. While the swipe is on, the terrain
+//! is drawn twice in the main pass, with the snapshot's settings left of a
+//! vertical divider and the current ones right of it, each clipped by a
+//! scissor rect. Both sides share the mesh, so settings baked into it, such
+//! as the colors, can't differ between them. Translucent layers and line
+//! overlays are drawn once with the current settings.
+
+use super::{ContourConfig, LightingConfig, RenderMode, SolidUniforms, WireframeUniforms};
+
+/// Swipe comparison configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompareConfig {
+    /// Whether the snapshot is shown left of the divider
+    pub enabled: bool,
+    /// Divider position as a fraction of the window width from the left
+    pub divider: f32,
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            divider: 0.5,
+        }
+    }
+}
+
+/// Settings kept for the left side of the swipe.
+#[derive(Debug, Clone, Copy)]
+pub struct CompareSnapshot {
+    pub render_mode: RenderMode,
+    pub lighting: LightingConfig,
+    pub contour: ContourConfig,
+    pub height_scale: f32,
+}
+
+/// Scissor rects `(x, y, width, height)` left and right of the divider in
+/// a window of the given size. A side with no width is `None`.
+pub fn split(divider: f32, width: u32, height: u32) -> [Option<(u32, u32, u32, u32)>; 2] {
+    let x = (width as f32 * divider.clamp(0.0, 1.0)).round() as u32;
+    let left = (x > 0).then_some((0, 0, x, height));
+    let right = (x < width).then_some((x, 0, width - x, height));
+    [left, right]
+}
+
+/// Uniform buffers and bind groups the snapshot side is drawn with.
+pub struct Compare {
+    /// Settings of the left side, once taken
+    pub snapshot: Option<CompareSnapshot>,
+    wireframe_uniform_buffer: wgpu::Buffer,
+    wireframe_bind_group: wgpu::BindGroup,
+    solid_uniform_buffer: wgpu::Buffer,
+    solid_bind_group: wgpu::BindGroup,
+}
+
+impl Compare {
+    /// Create the snapshot side's buffers. The bind groups come from the
+    /// renderer, which owns their layouts.
+    pub fn new(
+        device: &wgpu::Device,
+        create_wireframe_bind_group: impl FnOnce(&wgpu::Buffer) -> wgpu::BindGroup,
+        create_solid_bind_group: impl FnOnce(&wgpu::Buffer) -> wgpu::BindGroup,
+    ) -> Self {
+        let uniform_buffer = |label, size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let wireframe_uniform_buffer = uniform_buffer(
+            "Compare Wireframe Uniform Buffer",
+            std::mem::size_of::<WireframeUniforms>(),
+        );
+        let wireframe_bind_group = create_wireframe_bind_group(&wireframe_uniform_buffer);
+        let solid_uniform_buffer = uniform_buffer(
+            "Compare Solid Uniform Buffer",
+            std::mem::size_of::<SolidUniforms>(),
+        );
+        let solid_bind_group = create_solid_bind_group(&solid_uniform_buffer);
+
+        Self {
+            snapshot: None,
+            wireframe_uniform_buffer,
+            wireframe_bind_group,
+            solid_uniform_buffer,
+            solid_bind_group,
+        }
+    }
+
+    /// Upload this frame's uniforms for the snapshot side.
+    pub(super) fn update(
+        &self,
+        queue: &wgpu::Queue,
+        wireframe: &WireframeUniforms,
+        solid: &SolidUniforms,
+    ) {
+        queue.write_buffer(
+            &self.wireframe_uniform_buffer,
+            0,
+            bytemuck::bytes_of(wireframe),
+        );
+        queue.write_buffer(&self.solid_uniform_buffer, 0, bytemuck::bytes_of(solid));
+    }
+
+    /// Bind group for drawing with the wireframe pipeline on the snapshot
+    /// side.
+    pub fn wireframe_bind_group(&self) -> &wgpu::BindGroup {
+        &self.wireframe_bind_group
+    }
+
+    /// Bind group for drawing with the solid pipeline on the snapshot side.
+    pub fn solid_bind_group(&self) -> &wgpu::BindGroup {
+        &self.solid_bind_group
+    }
+
+    /// Uniform buffer the solid bind group reads, for rebuilding it.
+    pub fn solid_uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.solid_uniform_buffer
+    }
+
+    /// Replace the solid bind group, when the resources it binds change.
+    pub fn set_solid_bind_group(&mut self, bind_group: wgpu::BindGroup) {
+        self.solid_bind_group = bind_group;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_at_divider() {
+        assert_eq!(
+            split(0.25, 800, 600),
+            [Some((0, 0, 200, 600)), Some((200, 0, 600, 600))]
+        );
+
+        // Dragged to either edge, one side fills the window
+        assert_eq!(split(0.0, 800, 600), [None, Some((0, 0, 800, 600))]);
+        assert_eq!(split(1.5, 800, 600), [Some((0, 0, 800, 600)), None]);
+    }
+}
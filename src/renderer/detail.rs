@@ -0,0 +1,260 @@
+//! Full-resolution detail of memory-mapped float grids near the camera.
+//!
+//! A float grid with a side over `--max-raster-size` is shown as an
+//! overview of one sample per block (see [`float_grid`]). The renderer
+//! keeps the grid mapped, and while the camera is close to the terrain it
+//! reads the [`Window`] of mesh chunks around the point looked at from the
+//! grid, at up to [`DETAIL_SIZE`] samples a side, in the background, and
+//! draws it in place of those chunks. Only the pages of the samples read
+//! are loaded, so the detail follows the camera over grids of any size.
+//! The map is held until another terrain is loaded, so for the whole
+//! session as a rule; truncating the grid's file in the meantime crashes
+//! lrle with SIGBUS on the next window read past the new end.
+//!
+//! A window's outline falls on the overview's samples, so it meets the
+//! chunks around it there. It's drawn with the main terrain's pipelines
+//! and uniforms but meshed on its own, so it's only streamed while the
+//! surface shows plain height colors on a flat mesh; color modes, relief
+//! and the like are computed over the overview alone.
+//!
+//! [`float_grid`]: crate::terrain::float_grid
+
+use glam::DVec2;
+
+use crate::terrain::float_grid::MappedGrid;
+use crate::terrain::loader::{LoadError, LoadOptions};
+use crate::terrain::mesh::CHUNK_SIZE;
+use crate::terrain::units::Units;
+use crate::terrain::TerrainData;
+
+/// Longest side of a detail window, in samples.
+pub const DETAIL_SIZE: usize = 1024;
+
+/// Chunks on each side of the one looked at that a window spans.
+const WINDOW_RADIUS: usize = 1;
+
+/// Mesh chunks along the width and height of a terrain of `width` x
+/// `height` samples, as [`TerrainMesh::build`] tiles it.
+///
+/// [`TerrainMesh::build`]: crate::terrain::TerrainMesh::build
+pub fn chunk_counts(width: usize, height: usize) -> (usize, usize) {
+    let chunks = |samples: usize| samples.saturating_sub(1).max(1).div_ceil(CHUNK_SIZE);
+    (chunks(width), chunks(height))
+}
+
+/// Chunks of the overview drawn at a higher resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    /// First and last chunk column
+    pub columns: (usize, usize),
+    /// First and last chunk row
+    pub rows: (usize, usize),
+    /// Window samples per overview cell along each side
+    pub subdivisions: usize,
+}
+
+impl Window {
+    /// First and last overview sample the window spans, along the columns
+    /// and the rows of an overview of `width` x `height` samples.
+    pub fn samples(&self, (width, height): (usize, usize)) -> [(usize, usize); 2] {
+        let span = |(first, last): (usize, usize), samples: usize| {
+            let end = samples.saturating_sub(1);
+            (
+                (first * CHUNK_SIZE).min(end),
+                ((last + 1) * CHUNK_SIZE).min(end),
+            )
+        };
+        [span(self.columns, width), span(self.rows, height)]
+    }
+
+    /// Whether the window covers chunk `index` of a mesh `chunks_x` chunks
+    /// wide.
+    pub fn covers(&self, index: usize, chunks_x: usize) -> bool {
+        let (x, z) = (index % chunks_x, index / chunks_x);
+        (self.columns.0..=self.columns.1).contains(&x) && (self.rows.0..=self.rows.1).contains(&z)
+    }
+}
+
+/// A float grid shown as its overview, kept mapped to read detail from.
+pub struct MappedTerrain {
+    grid: MappedGrid,
+    /// Grid samples per overview sample
+    step: usize,
+    /// How the grid was loaded, for its nodata
+    options: LoadOptions,
+    /// Overview samples along each side
+    size: (usize, usize),
+    /// Placement of the overview
+    origin: DVec2,
+    cell_size: f64,
+    units: Units,
+    /// Height range of the overview, which windows are colored by
+    height_bounds: (f64, f64),
+}
+
+impl MappedTerrain {
+    /// Keep `grid`, loaded with `options` as `terrain`, to read windows of
+    /// it. `None` if `terrain` is the whole grid already, or isn't its
+    /// overview.
+    pub fn new(grid: MappedGrid, options: LoadOptions, terrain: &TerrainData) -> Option<Self> {
+        let step = grid.overview_step(options.max_size);
+        let picks = |samples: usize| samples.saturating_sub((step - 1) / 2).div_ceil(step);
+        let size = (picks(grid.width), picks(grid.height));
+        (step > 1 && size == (terrain.width, terrain.height)).then(|| Self {
+            grid,
+            step,
+            options,
+            size,
+            origin: terrain.origin,
+            cell_size: terrain.cell_size,
+            units: terrain.units,
+            height_bounds: terrain.height_bounds(),
+        })
+    }
+
+    /// Window of the chunks around world point `target` (x, z), or `None`
+    /// if it's off the terrain or the grid holds no more detail there than
+    /// the overview.
+    pub fn window_at(&self, target: DVec2) -> Option<Window> {
+        let (width, height) = self.size;
+        let cell = (target - self.origin) / self.cell_size;
+        let last = DVec2::new(width as f64 - 1.0, height as f64 - 1.0);
+        if !(cell.cmpge(DVec2::ZERO).all() && cell.cmple(last).all()) {
+            return None;
+        }
+        let (chunks_x, chunks_z) = chunk_counts(width, height);
+        let around = |cell: f64, chunks: usize| {
+            let center = (cell as usize / CHUNK_SIZE).min(chunks - 1);
+            (
+                center.saturating_sub(WINDOW_RADIUS),
+                (center + WINDOW_RADIUS).min(chunks - 1),
+            )
+        };
+        let mut window = Window {
+            columns: around(cell.x, chunks_x),
+            rows: around(cell.y, chunks_z),
+            subdivisions: 1,
+        };
+        let [(c0, c1), (r0, r1)] = window.samples(self.size);
+        let cells = (c1 - c0).max(r1 - r0).max(1);
+        // Window samples must fall on grid samples
+        window.subdivisions = (1..=self.step)
+            .rev()
+            .find(|&d| self.step.is_multiple_of(d) && cells * d <= DETAIL_SIZE)?;
+        (window.subdivisions > 1).then_some(window)
+    }
+
+    /// World-space size of `window`'s longer side.
+    pub fn extent(&self, window: &Window) -> f64 {
+        let [(c0, c1), (r0, r1)] = window.samples(self.size);
+        (c1 - c0).max(r1 - r0) as f64 * self.cell_size
+    }
+
+    /// Read `window` from the grid, placed over the overview, with nodata
+    /// filled as the grid was loaded and colored by the overview's height
+    /// range.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LoadError::Image`] if the window holds no valid samples.
+    pub fn read(&self, window: &Window) -> Result<TerrainData, LoadError> {
+        let [(c0, c1), (r0, r1)] = window.samples(self.size);
+        let subdivisions = window.subdivisions;
+        // Overview rows count from the south, grid rows from the north
+        let offset = (self.step - 1) / 2;
+        let raster = self.grid.region_every(
+            offset + c0 * self.step,
+            offset + (self.size.1 - 1 - r1) * self.step,
+            (c1 - c0) * subdivisions + 1,
+            (r1 - r0) * subdivisions + 1,
+            self.step / subdivisions,
+        );
+        let options = LoadOptions {
+            max_size: usize::MAX,
+            ..self.options
+        };
+        let mut terrain = raster.into_terrain(self.grid.georef, &options)?;
+        terrain.origin = self.origin + DVec2::new(c0 as f64, r0 as f64) * self.cell_size;
+        terrain.cell_size = self.cell_size / subdivisions as f64;
+        terrain.units = self.units;
+        terrain.set_height_bounds(self.height_bounds);
+        Ok(terrain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::float_grid::load_float_grid;
+    use glam::Vec3Swizzles;
+
+    /// A `side` x `side` float grid whose samples count up row by row from
+    /// the north, loaded as an overview of `max_size` samples a side.
+    fn mapped(dir: &std::path::Path, side: usize, max_size: usize) -> (MappedGrid, TerrainData) {
+        let path = dir.join("grid.flt");
+        let header = format!("ncols {side}\nnrows {side}\nxllcorner 0\nyllcorner 0\ncellsize 2\n");
+        std::fs::write(path.with_extension("hdr"), header).unwrap();
+        let bytes: Vec<u8> = (0..side * side)
+            .flat_map(|i| (i as f32).to_le_bytes())
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+        let options = LoadOptions {
+            max_size,
+            ..LoadOptions::default()
+        };
+        let terrain = load_float_grid(&path, &options).unwrap();
+        (MappedGrid::open(&path).unwrap(), terrain)
+    }
+
+    #[test]
+    fn test_window_meets_overview() {
+        let dir = tempfile::tempdir().unwrap();
+        let (grid, terrain) = mapped(dir.path(), 40, 10);
+        let options = LoadOptions {
+            max_size: 10,
+            ..LoadOptions::default()
+        };
+        let source = MappedTerrain::new(grid, options, &terrain).unwrap();
+
+        let window = source.window_at(terrain.center().xz()).unwrap();
+        assert_eq!((window.columns, window.rows), ((0, 0), (0, 0)));
+        // Every grid sample between the overview's, four per cell
+        assert_eq!(window.subdivisions, 4);
+        assert!(window.covers(0, 1));
+        assert!(source.window_at(DVec2::new(-10.0, 0.0)).is_none());
+
+        let patch = source.read(&window).unwrap();
+        assert_eq!((patch.width, patch.height), (37, 37));
+        assert_eq!(patch.height_bounds(), terrain.height_bounds());
+        for (x, z) in [(0, 0), (3, 5), (9, 9)] {
+            assert_eq!(
+                patch.world_position(x * 4, z * 4),
+                terrain.world_position(x, z)
+            );
+        }
+        // In between lie the grid's own samples: one further east
+        let between = patch.at(1, 0) - patch.at(0, 0);
+        assert_eq!(between, 1.0);
+    }
+
+    #[test]
+    fn test_whole_grid_has_no_detail() {
+        let dir = tempfile::tempdir().unwrap();
+        let (grid, terrain) = mapped(dir.path(), 8, 10);
+        assert!(MappedTerrain::new(grid, LoadOptions::default(), &terrain).is_none());
+    }
+
+    #[test]
+    fn test_windows_span_chunks_around_target() {
+        assert_eq!(chunk_counts(65, 200), (1, 4));
+        let window = Window {
+            columns: (1, 3),
+            rows: (0, 1),
+            subdivisions: 2,
+        };
+        assert_eq!(window.samples((200, 100)), [(64, 199), (0, 99)]);
+        assert!(window.covers(5, 4));
+        assert!(!window.covers(4, 4));
+        assert!(!window.covers(9, 4));
+    }
+}
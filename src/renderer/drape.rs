@@ -0,0 +1,98 @@
+//! Draped image texture sampled by the solid shader.
+//!
+//! The image is uploaded with its mip chain and looked up by each
+//! fragment's mesh-space (x, z) position, placed over the terrain like the
+//! uncertainty map (see [`uncertainty::placement`](super::uncertainty::placement)),
+//! and blended with the vertex colors by the texture blend setting.
+
+use crate::terrain::drape::DrapeImage;
+
+/// Draped image texture with trilinear filtering.
+pub struct DrapeTexture {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl DrapeTexture {
+    /// A blank texture, bound until an image is draped.
+    pub fn empty(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let blank = DrapeImage {
+            width: 1,
+            height: 1,
+            rgba: vec![[255; 4]],
+        };
+        Self::new(device, queue, &blank, true)
+    }
+
+    /// Texture of `image`, shrunk to the device's largest texture size.
+    /// `srgb` decodes it to linear colors when sampled, for sRGB targets,
+    /// which get linear vertex colors too.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, image: &DrapeImage, srgb: bool) -> Self {
+        let max_size = device.limits().max_texture_dimension_2d;
+        let levels = image.clone().fit(max_size).mip_chain();
+        let base = &levels[0];
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Drape Texture"),
+            size: wgpu::Extent3d {
+                width: base.width,
+                height: base.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: if srgb {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (level, image) in levels.iter().enumerate() {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&image.rgba),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(image.width * 4),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: image.width,
+                    height: image.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Drape Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 16,
+            ..Default::default()
+        });
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler,
+        }
+    }
+
+    /// Texture view for the solid bind group.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Trilinear sampler for the solid bind group.
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+}
@@ -0,0 +1,193 @@
+//! Image-based lighting from an HDR environment map.
+//!
+//! The panorama is uploaded as a half-float texture with its mip chain,
+//! bound next to the scene uniforms of the sky and the solid shader. The
+//! sky samples it along each pixel's view ray as the background, the solid
+//! shader along the reflected view ray from a blurred mip, weighted by
+//! Fresnel, and its diffuse [`Irradiance`] replaces the flat ambient term.
+//! Both turn it about the vertical axis by the configured rotation; the
+//! nine irradiance coefficients ride in [`SceneUniforms`].
+//!
+//! [`SceneUniforms`]: super::atmosphere::SceneUniforms
+
+use crate::terrain::environment::{EnvironmentMap, Irradiance};
+
+/// Bind group slot of the environment texture, in the sky and solid
+/// bind groups; its sampler follows.
+pub const ENVIRONMENT_BINDING: u32 = 9;
+
+/// Environment lighting settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvironmentConfig {
+    /// Whether a loaded environment map lights the scene
+    pub enabled: bool,
+    /// Turn about the vertical axis, in degrees
+    pub rotation: f32,
+    /// Brightness of its light, reflections and background
+    pub intensity: f32,
+    /// Show it behind the terrain instead of the sky gradient
+    pub background: bool,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rotation: 0.0,
+            intensity: 1.0,
+            background: true,
+        }
+    }
+}
+
+/// Environment map texture with trilinear filtering, and its irradiance.
+pub struct EnvironmentTexture {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    irradiance: Irradiance,
+}
+
+impl EnvironmentTexture {
+    /// A black texture, bound until an environment map is loaded.
+    pub fn empty(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let black = EnvironmentMap {
+            width: 1,
+            height: 1,
+            rgb: vec![[0.0; 3]],
+        };
+        Self::new(device, queue, &black)
+    }
+
+    /// Texture of `map`, shrunk to the device's largest texture size.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, map: &EnvironmentMap) -> Self {
+        let max_size = device.limits().max_texture_dimension_2d;
+        let levels = map.clone().fit(max_size).mip_chain();
+        let base = &levels[0];
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Environment Texture"),
+            size: wgpu::Extent3d {
+                width: base.width,
+                height: base.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (level, map) in levels.iter().enumerate() {
+            let texels: Vec<[u16; 4]> = map
+                .rgb
+                .iter()
+                .map(|&[r, g, b]| [to_f16(r), to_f16(g), to_f16(b), to_f16(1.0)])
+                .collect();
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&texels),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(map.width * 8),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: map.width,
+                    height: map.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        // Wraps around the horizon, clamps at the poles
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Environment Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler,
+            irradiance: map.irradiance(),
+        }
+    }
+
+    /// Texture view for the sky and solid bind groups.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Trilinear sampler for the sky and solid bind groups.
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Diffuse irradiance of the map.
+    pub fn irradiance(&self) -> &Irradiance {
+        &self.irradiance
+    }
+}
+
+/// Layout entries of the environment texture and its sampler, for the sky
+/// and solid bind group layouts.
+pub fn layout_entries() -> [wgpu::BindGroupLayoutEntry; 2] {
+    [
+        wgpu::BindGroupLayoutEntry {
+            binding: ENVIRONMENT_BINDING,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: ENVIRONMENT_BINDING + 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ]
+}
+
+/// Half-float bits of `value`, saturating to the largest finite half and
+/// flushing values too small for a normal half to zero.
+fn to_f16(value: f32) -> u16 {
+    if value.is_nan() {
+        return 0x7e00;
+    }
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = ((bits >> 13) & 0x3ff) as u16;
+    match exponent {
+        31.. => sign | 0x7bff,
+        ..=0 => sign,
+        _ => sign | (exponent as u16) << 10 | mantissa,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_float_conversion() {
+        assert_eq!(to_f16(0.0), 0);
+        assert_eq!(to_f16(1.0), 0x3c00);
+        assert_eq!(to_f16(-2.0), 0xc000);
+        assert_eq!(to_f16(0.5), 0x3800);
+        assert_eq!(to_f16(65504.0), 0x7bff);
+        assert_eq!(to_f16(1e9), 0x7bff);
+        assert_eq!(to_f16(1e-9), 0);
+    }
+}
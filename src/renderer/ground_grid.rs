@@ -0,0 +1,242 @@
+//! Reference grid on the zero-height plane.
+//!
+//! A square around the scene at height zero is drawn with its own pipeline
+//! after the opaque scene, and the shader draws antialiased lines on it at
+//! a round spacing, with every fifth line stronger. The grid is blended
+//! over what's behind it without writing depth, and fades out toward its
+//! edges, so it reads as an endless floor that terrain above it hides.
+
+use glam::{Mat4, Vec3};
+
+use super::{DepthMode, DEPTH_FORMAT};
+
+/// Half-width of the grid square, in scene radii.
+const EXTENT: f32 = 3.0;
+
+/// Minor lines across a scene radius, before rounding the spacing.
+const LINES_PER_RADIUS: f32 = 5.0;
+
+/// Round spacing (1, 2 or 5 times a power of ten) giving about
+/// [`LINES_PER_RADIUS`] minor lines across a scene of `radius`.
+pub fn spacing(radius: f32) -> f32 {
+    let rough = (radius / LINES_PER_RADIUS).max(f32::MIN_POSITIVE);
+    let magnitude = 10f32.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .find(|step| step * magnitude >= rough)
+        .unwrap_or(10.0);
+    step * magnitude
+}
+
+/// Ground grid shader uniforms.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GroundGridUniforms {
+    view_proj: [[f32; 4]; 4],
+    /// Horizontal (x, z) center of the grid square
+    center: [f32; 2],
+    /// Half-width of the grid square
+    extent: f32,
+    /// Distance between minor lines
+    spacing: f32,
+    /// Height of the plane
+    y: f32,
+    /// 1 / log2(far + 1) for logarithmic depth, 0.0 to use linear depth
+    log_depth_coef: f32,
+    _pad: [f32; 2],
+}
+
+/// Pipeline and uniforms of the ground grid.
+pub struct GroundGrid {
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GroundGrid {
+    /// Create the pipeline drawing onto a surface of `format`, from
+    /// `ground_grid.wgsl` source.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        depth_mode: DepthMode,
+        source: &str,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Ground Grid Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ground Grid Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(
+            device,
+            cache,
+            &pipeline_layout,
+            format,
+            depth_mode,
+            1,
+            source,
+        );
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ground Grid Uniform Buffer"),
+            size: std::mem::size_of::<GroundGridUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("Ground Grid Bind Group"),
+        });
+
+        Self {
+            pipeline_layout,
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    /// Build a pipeline from new shader source drawing `samples` per pixel,
+    /// to be installed with [`set_pipeline`](Self::set_pipeline) once it
+    /// validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        depth_mode: DepthMode,
+        samples: u32,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_pipeline(
+            device,
+            cache,
+            &self.pipeline_layout,
+            format,
+            depth_mode,
+            samples,
+            source,
+        )
+    }
+
+    /// Replace the pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Place the grid at height `y` around a scene of `bounds` (center and
+    /// radius), seen with `view_proj`.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        view_proj: Mat4,
+        log_depth_coef: f32,
+        (center, radius): (Vec3, f32),
+        y: f32,
+    ) {
+        let uniforms = GroundGridUniforms {
+            view_proj: view_proj.to_cols_array_2d(),
+            center: [center.x, center.z],
+            extent: radius * EXTENT,
+            spacing: spacing(radius),
+            y,
+            log_depth_coef,
+            _pad: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draw the grid over the opaque scene.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..6, 0..1);
+    }
+}
+
+/// Build the grid pipeline, blending over the scene and testing but not
+/// writing depth.
+fn create_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    depth_mode: DepthMode,
+    samples: u32,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Ground Grid Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Ground Grid Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(depth_mode.fragment_entry_point()),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: samples,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spacing_is_round() {
+        assert_eq!(spacing(50.0), 10.0);
+        assert_eq!(spacing(40.0), 10.0);
+        assert_eq!(spacing(30.0), 10.0);
+        assert_eq!(spacing(8.0), 2.0);
+        assert_eq!(spacing(2000.0), 500.0);
+        assert!((spacing(0.1) - 0.02).abs() < 1e-6);
+    }
+}
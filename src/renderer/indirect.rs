@@ -0,0 +1,291 @@
+//! Indirect multi-draw of mesh chunks.
+//!
+//! A compute pass (`cull.wgsl`) writes one indexed draw per chunk into
+//! indirect buffers, zeroing the instance count of chunks outside the view
+//! frustum or hidden by the last occlusion query results. All chunks are
+//! then drawn with a single `multi_draw_indexed_indirect` call per index
+//! buffer, so the CPU cost no longer grows with the number of chunks.
+//!
+//! Requires [`wgpu::DownlevelFlags::INDIRECT_EXECUTION`] and compute
+//! shaders; see [`is_supported`].
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2};
+use wgpu::util::{DeviceExt, DrawIndexedIndirectArgs};
+
+use super::HeightTransform;
+use crate::terrain::mesh::MeshChunk;
+
+/// Invocations per compute workgroup, matching `cull.wgsl`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Whether the adapter can build and execute indirect draws.
+pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION | wgpu::DownlevelFlags::COMPUTE_SHADERS)
+}
+
+/// Chunk bounds and index ranges as laid out in `cull.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct GpuChunk {
+    min: [f32; 3],
+    first_triangle: u32,
+    max: [f32; 3],
+    triangle_count: u32,
+    first_line: u32,
+    line_count: u32,
+    _pad: [u32; 2],
+}
+
+impl From<&MeshChunk> for GpuChunk {
+    fn from(chunk: &MeshChunk) -> Self {
+        Self {
+            min: chunk.min.to_array(),
+            first_triangle: chunk.triangles.start,
+            max: chunk.max.to_array(),
+            triangle_count: chunk.triangles.len() as u32,
+            first_line: chunk.lines.start,
+            line_count: chunk.lines.len() as u32,
+            _pad: [0; 2],
+        }
+    }
+}
+
+/// Uniform data for the culling pass.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct CullUniforms {
+    view_proj: [[f32; 4]; 4],
+    chunk_count: u32,
+    curvature_coef: f32,
+    curvature_center: [f32; 2],
+    height_scale: f32,
+    height_offset: f32,
+    _pad: [f32; 2],
+}
+
+/// Bind group layout of the culling pass.
+pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            storage_entry(1, true),
+            storage_entry(2, true),
+            storage_entry(3, false),
+            storage_entry(4, false),
+        ],
+        label: Some("Cull Bind Group Layout"),
+    })
+}
+
+/// Build the culling compute pipeline from WGSL source.
+pub fn create_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    source: &str,
+) -> wgpu::ComputePipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Cull Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Cull Pipeline"),
+        layout: Some(layout),
+        module: &shader,
+        entry_point: Some("cs_main"),
+        compilation_options: Default::default(),
+        cache,
+    })
+}
+
+/// GPU draw list for the chunks of one mesh.
+pub struct IndirectChunks {
+    chunk_count: u32,
+    uniform_buffer: wgpu::Buffer,
+    visibility_buffer: wgpu::Buffer,
+    triangle_args: wgpu::Buffer,
+    line_args: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl IndirectChunks {
+    /// Upload chunk bounds and create the draw list buffers.
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        chunks: &[MeshChunk],
+    ) -> Self {
+        let gpu_chunks: Vec<GpuChunk> = chunks.iter().map(GpuChunk::from).collect();
+        let chunk_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cull Chunk Buffer"),
+            contents: bytemuck::cast_slice(&gpu_chunks),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cull Uniform Buffer"),
+            size: std::mem::size_of::<CullUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let visibility_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cull Visibility Buffer"),
+            contents: bytemuck::cast_slice(&vec![1u32; chunks.len()]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let args_buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: (chunks.len() * std::mem::size_of::<DrawIndexedIndirectArgs>())
+                    as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+                mapped_at_creation: false,
+            })
+        };
+        let triangle_args = args_buffer("Triangle Indirect Buffer");
+        let line_args = args_buffer("Line Indirect Buffer");
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: chunk_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: visibility_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: triangle_args.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: line_args.as_entire_binding(),
+                },
+            ],
+            label: Some("Cull Bind Group"),
+        });
+
+        Self {
+            chunk_count: chunks.len() as u32,
+            uniform_buffer,
+            visibility_buffer,
+            triangle_args,
+            line_args,
+            bind_group,
+        }
+    }
+
+    /// Write this frame's culling inputs. `visible` holds the occlusion
+    /// results per chunk; `None` treats every chunk as visible.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        view_proj: Mat4,
+        curvature: (f32, Vec2),
+        height: HeightTransform,
+        visible: Option<&[bool]>,
+    ) {
+        let uniforms = CullUniforms {
+            view_proj: view_proj.to_cols_array_2d(),
+            chunk_count: self.chunk_count,
+            curvature_coef: curvature.0,
+            curvature_center: curvature.1.to_array(),
+            height_scale: height.scale,
+            height_offset: height.offset,
+            _pad: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+
+        let mask: Vec<u32> = match visible {
+            Some(visible) => visible.iter().map(|&v| v as u32).collect(),
+            None => vec![1; self.chunk_count as usize],
+        };
+        queue.write_buffer(&self.visibility_buffer, 0, bytemuck::cast_slice(&mask));
+    }
+
+    /// Record the compute pass building the draw lists.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Cull Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(self.chunk_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Draw all chunk triangles. The solid pipeline and triangle index
+    /// buffer must already be set.
+    pub fn draw_triangles(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.multi_draw_indexed_indirect(&self.triangle_args, 0, self.chunk_count);
+    }
+
+    /// Draw all chunk lines. The wireframe pipeline and line index buffer
+    /// must already be set.
+    pub fn draw_lines(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.multi_draw_indexed_indirect(&self.line_args, 0, self.chunk_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_gpu_chunk_layout() {
+        // Must match the WGSL struct: vec3 + u32, vec3 + u32, 4 x u32
+        assert_eq!(std::mem::size_of::<GpuChunk>(), 48);
+        assert_eq!(std::mem::size_of::<DrawIndexedIndirectArgs>(), 20);
+        assert_eq!(std::mem::size_of::<CullUniforms>(), 96);
+    }
+
+    #[test]
+    fn test_gpu_chunk_from_mesh_chunk() {
+        let chunk = MeshChunk {
+            triangles: 12..48,
+            lines: 100..160,
+            min: Vec3::ZERO,
+            max: Vec3::ONE,
+            lods: Vec::new(),
+        };
+        let gpu = GpuChunk::from(&chunk);
+
+        assert_eq!(gpu.first_triangle, 12);
+        assert_eq!(gpu.triangle_count, 36);
+        assert_eq!(gpu.first_line, 100);
+        assert_eq!(gpu.line_count, 60);
+        assert_eq!(gpu.max, [1.0; 3]);
+    }
+}
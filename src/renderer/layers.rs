@@ -0,0 +1,310 @@
+//! Other terrains loaded alongside the main one.
+//!
+//! Each layer keeps its terrain and its own vertex and index buffers, and
+//! is drawn after the main terrain with the same pipelines and colors, to
+//! overlay two versions of a terrain or set them side by side. A layer's
+//! offset and scale are baked into its mesh, rebuilt when they change, so
+//! lighting, contours and fog see it where it's drawn. Layers are built at
+//! unit height scale, flat whatever the earth model, and share one set of
+//! uniforms: the main terrain's, without its translucency or uncertainty
+//! overlay.
+//!
+//! Analysis, tools and exports work on the main terrain; making a layer
+//! the main terrain swaps the two. With seam stitching on, layers are
+//! meshed from a copy whose borders are stitched to the other terrains'.
+
+use std::path::Path;
+
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use super::{SolidUniforms, WireframeUniforms};
+use crate::terrain::{MeshOptions, TerrainData, TerrainMesh};
+
+/// Name, visibility and placement of a layer, edited in the UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerConfig {
+    /// File name the terrain was loaded from
+    pub name: String,
+    /// Whether the layer is drawn
+    pub visible: bool,
+    /// Shift east, up and north, in terrain units; the height shift is
+    /// scaled with the heights
+    pub offset: [f32; 3],
+    /// Size relative to the terrain's own, around its center at height zero
+    pub scale: f32,
+}
+
+impl LayerConfig {
+    /// A visible layer in its file's place.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            offset: [0.0; 3],
+            scale: 1.0,
+        }
+    }
+
+    /// Whether the layer is placed the same as `other`.
+    fn same_placement(&self, other: &Self) -> bool {
+        self.offset == other.offset && self.scale == other.scale
+    }
+}
+
+/// Name a terrain is listed under: the file name of `path`.
+pub fn name_of(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Move mesh-space point `p` of a layer around `pivot` by its placement.
+fn place(p: Vec3, pivot: Vec3, config: &LayerConfig) -> Vec3 {
+    pivot + (p - pivot) * config.scale + Vec3::from(config.offset)
+}
+
+/// GPU buffers of a layer's mesh, or of another mesh drawn like one.
+pub(super) struct MeshBuffers {
+    vertex_buffer: wgpu::Buffer,
+    triangle_index_buffer: wgpu::Buffer,
+    wireframe_index_buffer: wgpu::Buffer,
+    num_triangle_indices: u32,
+    num_wireframe_indices: u32,
+    /// Mesh-space bounding box
+    pub bounds: (Vec3, Vec3),
+}
+
+impl MeshBuffers {
+    /// Upload `mesh`, with its lines as segment pairs, or `None` if it's
+    /// empty.
+    pub fn new(device: &wgpu::Device, mesh: &TerrainMesh) -> Option<Self> {
+        let bounds = mesh.bounds?;
+        let buffer = |label, contents: &[u8], usage| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage,
+            })
+        };
+        Some(Self {
+            vertex_buffer: buffer(
+                "Layer Vertex Buffer",
+                bytemuck::cast_slice(&mesh.vertices),
+                wgpu::BufferUsages::VERTEX,
+            ),
+            triangle_index_buffer: buffer(
+                "Layer Triangle Index Buffer",
+                bytemuck::cast_slice(&mesh.triangle_indices),
+                wgpu::BufferUsages::INDEX,
+            ),
+            wireframe_index_buffer: buffer(
+                "Layer Wireframe Index Buffer",
+                bytemuck::cast_slice(&mesh.indices),
+                wgpu::BufferUsages::INDEX,
+            ),
+            num_triangle_indices: mesh.triangle_indices.len() as u32,
+            num_wireframe_indices: mesh.indices.len() as u32,
+            bounds,
+        })
+    }
+
+    /// Draw the surface with the bound solid pipeline and bind group.
+    pub fn draw_triangles(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(
+            self.triangle_index_buffer.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+        pass.draw_indexed(0..self.num_triangle_indices, 0, 0..1);
+    }
+
+    /// Draw the grid lines with the bound wireframe pipeline and bind group.
+    pub fn draw_lines(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(
+            self.wireframe_index_buffer.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+        pass.draw_indexed(0..self.num_wireframe_indices, 0, 0..1);
+    }
+}
+
+/// A terrain drawn next to the main one.
+pub struct Layer {
+    pub terrain: TerrainData,
+    /// Copy with stitched borders, meshed instead when set
+    stitched: Option<TerrainData>,
+    /// Placement the buffers were built with
+    built: Option<LayerConfig>,
+    buffers: Option<MeshBuffers>,
+}
+
+impl Layer {
+    /// A layer of `terrain`, uploaded on the next [`Self::upload`].
+    pub fn new(terrain: TerrainData) -> Self {
+        Self {
+            terrain,
+            stitched: None,
+            built: None,
+            buffers: None,
+        }
+    }
+
+    /// Mesh `terrain`, the layer's with stitched borders, from the next
+    /// [`Self::upload`] on, or the layer's own again with `None`.
+    pub fn set_stitched(&mut self, terrain: Option<TerrainData>) {
+        self.stitched = terrain;
+    }
+
+    /// Whether the mesh must be rebuilt for `config`'s placement.
+    pub fn moved(&self, config: &LayerConfig) -> bool {
+        self.built
+            .as_ref()
+            .is_none_or(|built| !built.same_placement(config))
+    }
+
+    /// Build the mesh with `options`, which place it relative to the render
+    /// origin at unit height scale, move it by `config` and upload it.
+    pub fn upload(&mut self, device: &wgpu::Device, options: &MeshOptions, config: &LayerConfig) {
+        self.built = Some(config.clone());
+        let terrain = self.stitched.as_ref().unwrap_or(&self.terrain);
+        let mut mesh = TerrainMesh::build(terrain, options);
+
+        // Scaled around the footprint's center at height zero
+        let origin = options.origin.unwrap_or(self.terrain.center());
+        let center = self.terrain.center() - origin;
+        let pivot = Vec3::new(center.x as f32, -origin.y as f32, center.z as f32);
+        for vertex in &mut mesh.vertices {
+            vertex.position = place(vertex.position.into(), pivot, config).to_array();
+        }
+        mesh.bounds = mesh
+            .bounds
+            .map(|(min, max)| (place(min, pivot, config), place(max, pivot, config)));
+        self.buffers = MeshBuffers::new(device, &mesh);
+    }
+
+    /// Mesh-space bounding box at unit height scale, once uploaded.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        self.buffers.as_ref().map(|buffers| buffers.bounds)
+    }
+
+    /// Draw the surface with the bound solid pipeline and bind group.
+    pub fn draw_triangles(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if let Some(buffers) = &self.buffers {
+            buffers.draw_triangles(pass);
+        }
+    }
+
+    /// Draw the grid lines with the bound wireframe pipeline and bind group.
+    pub fn draw_lines(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if let Some(buffers) = &self.buffers {
+            buffers.draw_lines(pass);
+        }
+    }
+}
+
+/// Uniform buffers and bind groups all layers are drawn with.
+pub struct LayerUniforms {
+    wireframe_uniform_buffer: wgpu::Buffer,
+    wireframe_bind_group: wgpu::BindGroup,
+    solid_uniform_buffer: wgpu::Buffer,
+    solid_bind_group: wgpu::BindGroup,
+}
+
+impl LayerUniforms {
+    /// Create the layers' buffers. The bind groups come from the renderer,
+    /// which owns their layouts.
+    pub fn new(
+        device: &wgpu::Device,
+        create_wireframe_bind_group: impl FnOnce(&wgpu::Buffer) -> wgpu::BindGroup,
+        create_solid_bind_group: impl FnOnce(&wgpu::Buffer) -> wgpu::BindGroup,
+    ) -> Self {
+        let uniform_buffer = |label, size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let wireframe_uniform_buffer = uniform_buffer(
+            "Layer Wireframe Uniform Buffer",
+            std::mem::size_of::<WireframeUniforms>(),
+        );
+        let wireframe_bind_group = create_wireframe_bind_group(&wireframe_uniform_buffer);
+        let solid_uniform_buffer = uniform_buffer(
+            "Layer Solid Uniform Buffer",
+            std::mem::size_of::<SolidUniforms>(),
+        );
+        let solid_bind_group = create_solid_bind_group(&solid_uniform_buffer);
+
+        Self {
+            wireframe_uniform_buffer,
+            wireframe_bind_group,
+            solid_uniform_buffer,
+            solid_bind_group,
+        }
+    }
+
+    /// Upload this frame's uniforms for the layers.
+    pub(super) fn update(
+        &self,
+        queue: &wgpu::Queue,
+        wireframe: &WireframeUniforms,
+        solid: &SolidUniforms,
+    ) {
+        queue.write_buffer(
+            &self.wireframe_uniform_buffer,
+            0,
+            bytemuck::bytes_of(wireframe),
+        );
+        queue.write_buffer(&self.solid_uniform_buffer, 0, bytemuck::bytes_of(solid));
+    }
+
+    /// Bind group for drawing layers with the wireframe pipeline.
+    pub fn wireframe_bind_group(&self) -> &wgpu::BindGroup {
+        &self.wireframe_bind_group
+    }
+
+    /// Bind group for drawing layers with the solid pipeline.
+    pub fn solid_bind_group(&self) -> &wgpu::BindGroup {
+        &self.solid_bind_group
+    }
+
+    /// Uniform buffer the solid bind group reads, for rebuilding it.
+    pub fn solid_uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.solid_uniform_buffer
+    }
+
+    /// Replace the solid bind group, when the resources it binds change.
+    pub fn set_solid_bind_group(&mut self, bind_group: wgpu::BindGroup) {
+        self.solid_bind_group = bind_group;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_scales_around_pivot() {
+        let config = LayerConfig {
+            offset: [10.0, 1.0, 0.0],
+            scale: 2.0,
+            ..LayerConfig::new("b.fdf")
+        };
+        let pivot = Vec3::new(5.0, 0.0, 5.0);
+        assert_eq!(place(pivot, pivot, &config), Vec3::new(15.0, 1.0, 5.0));
+        assert_eq!(
+            place(Vec3::new(6.0, 3.0, 4.0), pivot, &config),
+            Vec3::new(17.0, 7.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn test_name_is_file_name() {
+        assert_eq!(name_of(Path::new("surveys/2024.tif")), "2024.tif");
+    }
+}
@@ -0,0 +1,104 @@
+//! Terrain files that failed to load, kept to show and retry.
+//!
+//! A load that fails, in the background or at startup before the window
+//! opened, is kept as a [`LoadFailure`] the panel shows in a modal with the
+//! line the parser stopped at, so the file can be fixed and loaded again
+//! without relaunching.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::terrain::loader::LoadError;
+use crate::terrain::LoadOptions;
+
+/// Characters of the offending line shown before it's cut short.
+const MAX_LINE_CHARS: usize = 80;
+
+/// What a file that failed to load was for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadTarget {
+    /// The main terrain
+    Main,
+    /// A layer drawn next to the main terrain
+    Layer,
+    /// The reference surface for relative relief
+    Reference,
+    /// A named channel of the main terrain
+    Channel(String),
+    /// The main terrain's channel shown as height uncertainty
+    Uncertainty,
+    /// A component of a vector field, with the files of both, east first
+    VectorField([PathBuf; 2]),
+}
+
+/// A terrain file that failed to load, and how to load it again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadFailure {
+    /// The file that failed to load
+    pub path: PathBuf,
+    /// How it was loaded, to load it the same way again
+    pub options: LoadOptions,
+    /// What it was loaded for
+    pub target: LoadTarget,
+    /// Why it failed
+    pub message: String,
+    /// The line the error is on, counting from 1, with its text
+    pub line: Option<(usize, String)>,
+}
+
+impl LoadFailure {
+    /// The failure of loading `path` with `options` with `err`, reading the
+    /// offending line back from the file.
+    pub fn new(path: PathBuf, options: LoadOptions, target: LoadTarget, err: &LoadError) -> Self {
+        let line = err
+            .line()
+            .map(|number| (number, line_text(&path, number).unwrap_or_default()));
+        Self {
+            path,
+            options,
+            target,
+            message: err.to_string(),
+            line,
+        }
+    }
+}
+
+/// Line `number` of the text file at `path`, cut short to fit the modal.
+fn line_text(path: &Path, number: usize) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let line = BufReader::new(file)
+        .lines()
+        .nth(number.checked_sub(1)?)?
+        .ok()?;
+    let line = line.trim_end();
+    Some(match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::loader;
+
+    #[test]
+    fn test_failure_quotes_offending_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.fdf");
+        let long = "1 ".repeat(60);
+        std::fs::write(&path, format!("0 1 2\n0 1 x\n{long}\n")).unwrap();
+
+        let err = loader::load_fdf(&path).unwrap_err();
+        let failure =
+            LoadFailure::new(path.clone(), LoadOptions::default(), LoadTarget::Main, &err);
+        assert_eq!(failure.line, Some((2, "0 1 x".to_string())));
+        assert!(failure.message.contains("line 2"), "{}", failure.message);
+
+        let text = line_text(&path, 3).unwrap();
+        assert_eq!(text.chars().count(), MAX_LINE_CHARS + 1);
+        assert!(text.ends_with('…'));
+        assert_eq!(line_text(&path, 0), None);
+    }
+}
@@ -5,23 +5,141 @@
 //! - Shader compilation and pipeline setup
 //! - Mesh upload and rendering
 //! - Camera uniform updates
+//! - Shader hot-reload in debug builds
 
+pub mod adapter;
+pub mod atmosphere;
+pub mod bench;
+pub mod blend;
+pub mod bloom;
+pub mod bookmarks;
 pub mod camera;
+pub mod camera_path;
+pub mod clipmap;
+pub mod colorblind;
+pub mod compare;
+pub mod detail;
+pub mod drape;
+pub mod environment;
+pub mod ground_grid;
+pub mod indirect;
+pub mod layers;
+pub mod load_failure;
+pub mod motion;
+pub mod msaa;
+pub mod occlusion;
+pub mod oit;
+pub mod overview;
+pub mod pacing;
+pub mod params;
+pub mod perf_log;
+pub mod pipeline_cache;
+pub mod recovery;
+pub mod screenshot;
+pub mod shaders;
+pub mod shadow;
+pub mod timeline;
+pub mod tools;
+pub mod turntable;
+pub mod uncertainty;
+pub mod workers;
 
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
 
-use glam::{Mat4, Vec3};
+use anyhow::Context;
+use glam::{DAffine2, DVec2, DVec3, Mat2, Mat4, Vec2, Vec3, Vec3Swizzles, Vec4Swizzles};
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 
-use crate::terrain::{ColorScheme, GradientConfig, TerrainMesh, Vertex};
-use crate::ui::Ui;
+use crate::config::Config;
+use crate::input::InputConfig;
+use crate::terrain::algebra::Statement;
+use crate::terrain::analysis::{self, LineKind, Polyline, TerrainStats};
+use crate::terrain::annotations::{self, Annotation};
+use crate::terrain::ao;
+use crate::terrain::breaklines::{self, Breakline};
+use crate::terrain::color_map::{self, ColorMap};
+use crate::terrain::colors::{rgb_to_hex, srgb_to_linear};
+use crate::terrain::drape::{image_position, ControlPoint, DrapeAlignment, DrapeImage};
+use crate::terrain::environment::EnvironmentMap;
+use crate::terrain::export;
+use crate::terrain::filters::{self, DenoiseConfig};
+use crate::terrain::float_grid::MappedGrid;
+use crate::terrain::grid::Channel;
+use crate::terrain::hydrology::{Catchment, FlowGrid};
+use crate::terrain::loader;
+use crate::terrain::lod::{LodConfig, LOD_LEVELS};
+use crate::terrain::mesh::{self, LineTopology, MeshChunk};
+use crate::terrain::picking::Pick;
+use crate::terrain::points::{self, PointConfig, PointSet};
+use crate::terrain::processing::Processing;
+use crate::terrain::profile::Profile;
+use crate::terrain::relief::{self, ReliefConfig};
+use crate::terrain::selection::Selection;
+use crate::terrain::simplify;
+use crate::terrain::solar;
+use crate::terrain::stitch::{self, StitchConfig};
+use crate::terrain::sun;
+use crate::terrain::tin;
+use crate::terrain::uncertainty::{self as sigma, UncertaintyConfig, UncertaintyStyle};
+use crate::terrain::units::{UnitFormat, Units};
+use crate::terrain::vectors::{VectorConfig, VectorField};
+use crate::terrain::visibility::{LineOfSight, SightConfig};
+use crate::terrain::{
+    ColorMode, ColorScheme, GradientConfig, Heightfield, MeshOptions, SlopeBands, TerrainMesh,
+    Vertex, EARTH_RADIUS,
+};
+use crate::ui::{FrameInfo, Ui, UiResponse};
+use crate::update::{UpdateCheck, CURRENT_VERSION};
+use adapter::AdapterChoice;
+pub use atmosphere::{FogConfig, SkyConfig};
+use atmosphere::{SceneUniforms, Sky};
+use blend::Blend;
+pub use bloom::BloomConfig;
+use bloom::{Bloom, BloomPipelines};
+use bookmarks::{Bookmarks, Lens};
 use camera::Camera;
 pub use camera::Projection;
+use camera_path::{CameraPath, Keyframe, Recording};
+use clipmap::{Clipmap, ClipmapUniforms};
+pub use colorblind::ColorVision;
+use colorblind::Colorblind;
+use compare::Compare;
+pub use compare::{CompareConfig, CompareSnapshot};
+use detail::{MappedTerrain, Window as DetailWindow};
+use drape::DrapeTexture;
+use environment::{EnvironmentConfig, EnvironmentTexture};
+use ground_grid::GroundGrid;
+use indirect::IndirectChunks;
+use layers::{Layer, LayerConfig, LayerUniforms, MeshBuffers};
+use load_failure::{LoadFailure, LoadTarget};
+pub use motion::MotionConfig;
+use msaa::Msaa;
+use occlusion::OcclusionCuller;
+use oit::Oit;
+use overview::Overview;
+pub use overview::{OverviewConfig, OverviewView};
+use pacing::{FixedStep, LatencyStats, UPDATE_STEP};
+pub use pacing::{FrameScheduler, PacingConfig, PresentMode, Vsync};
+use params::ShaderParams;
+use perf_log::{FrameSample, Memory, PerfLog};
+use pipeline_cache::{PipelineCache, Warmup};
+use recovery::{Autosave, Edits};
+use shaders::{ShaderSources, ShaderWatcher};
+use shadow::ShadowMap;
+pub use timeline::TimelineConfig;
+use tools::Overlay;
+pub use tools::{Brush, Tool};
+use turntable::TurntableFormat;
+pub use turntable::{AutoRotate, TurntableConfig};
+use uncertainty::UncertaintyMap;
+use workers::{Finished, JobId, Workers};
 
 /// Rendering mode for the terrain.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
 pub enum RenderMode {
     /// Wireframe rendering (lines only)
     Wireframe,
@@ -32,6 +150,219 @@ pub enum RenderMode {
     Both,
 }
 
+/// Depth buffer convention.
+///
+/// Reversed-Z maps the near plane to depth 1 and the far plane to 0, which
+/// pairs the float depth format's precision with the perspective divide
+/// and removes z-fighting on large terrains. Standard-Z is kept for the GL
+/// backend, where the clip-space remapping cancels out the benefit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthMode {
+    /// Near = 0, far = 1, `Less` comparison
+    Standard,
+    /// Near = 1, far = 0, `Greater` comparison
+    #[default]
+    Reversed,
+    /// Depth written per fragment as log2(1 + w) / log2(1 + far), for
+    /// planetary-scale extents. Falls back to linear depth for orthographic
+    /// projection, where w is constant.
+    Logarithmic,
+}
+
+impl DepthMode {
+    /// Depth comparison function for pipelines.
+    pub fn compare(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard | DepthMode::Logarithmic => wgpu::CompareFunction::Less,
+            DepthMode::Reversed => wgpu::CompareFunction::Greater,
+        }
+    }
+
+    /// Value the depth buffer is cleared to (the far plane).
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthMode::Standard | DepthMode::Logarithmic => 1.0,
+            DepthMode::Reversed => 0.0,
+        }
+    }
+
+    /// Fragment shader entry point writing depth in this mode.
+    pub fn fragment_entry_point(self) -> &'static str {
+        match self {
+            DepthMode::Standard | DepthMode::Reversed => "fs_main",
+            DepthMode::Logarithmic => "fs_main_log_depth",
+        }
+    }
+
+    /// Solid shader entry point for translucent layers in this mode.
+    pub fn oit_entry_point(self) -> &'static str {
+        match self {
+            DepthMode::Standard | DepthMode::Reversed => "fs_oit",
+            DepthMode::Logarithmic => "fs_oit_log_depth",
+        }
+    }
+
+    /// Shader coefficient `1 / log2(far + 1)` for logarithmic depth, or 0.0
+    /// when the shader should keep the rasterized linear depth.
+    pub fn log_depth_coef(self, camera: &Camera) -> f32 {
+        if self == DepthMode::Logarithmic && camera.projection == Projection::Perspective {
+            1.0 / (camera.far + 1.0).log2()
+        } else {
+            0.0
+        }
+    }
+
+    /// Matrix applied after the projection to remap clip-space depth.
+    pub fn clip_remap(self) -> Mat4 {
+        match self {
+            DepthMode::Standard | DepthMode::Logarithmic => Mat4::IDENTITY,
+            // z' = w - z, so depth z/w becomes 1 - z/w
+            DepthMode::Reversed => Mat4::from_cols(
+                glam::Vec4::X,
+                glam::Vec4::Y,
+                glam::Vec4::new(0.0, 0.0, -1.0, 0.0),
+                glam::Vec4::new(0.0, 0.0, 1.0, 1.0),
+            ),
+        }
+    }
+}
+
+/// How a translucent layer combines with the scene beneath it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Weighted average with other normal layers, regardless of order
+    #[default]
+    Normal,
+    /// Layer color added to the scene
+    Additive,
+    /// Absolute difference between the layer and the scene
+    Difference,
+    /// Darker of the layer and the scene, per channel
+    Min,
+    /// Lighter of the layer and the scene, per channel
+    Max,
+}
+
+impl BlendMode {
+    /// All modes, in UI order.
+    pub const ALL: [BlendMode; 5] = [
+        BlendMode::Normal,
+        BlendMode::Additive,
+        BlendMode::Difference,
+        BlendMode::Min,
+        BlendMode::Max,
+    ];
+
+    /// Display name.
+    pub fn label(self) -> &'static str {
+        match self {
+            BlendMode::Normal => "Normal",
+            BlendMode::Additive => "Additive",
+            BlendMode::Difference => "Difference",
+            BlendMode::Min => "Min",
+            BlendMode::Max => "Max",
+        }
+    }
+
+    /// Mode number understood by `blend.wgsl`.
+    pub fn shader_index(self) -> u32 {
+        match self {
+            BlendMode::Normal => 0,
+            BlendMode::Additive => 1,
+            BlendMode::Difference => 2,
+            BlendMode::Min => 3,
+            BlendMode::Max => 4,
+        }
+    }
+}
+
+/// How the terrain surface is turned into geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderStrategy {
+    /// One vertex per height sample, built on the CPU
+    #[default]
+    Mesh,
+    /// Camera-centered geometry clipmap sampling a height texture, for
+    /// free-flying over terrains too large to mesh. Always drawn solid,
+    /// and ignores globe mode.
+    Clipmap,
+    /// The mesh triangulated adaptively, within
+    /// [`RenderSettings::tin_tolerance`] of every sample, with far fewer
+    /// triangles on flat ground. Exports are triangulated too.
+    Tin,
+}
+
+/// How the terrain accounts for the curvature of the earth.
+///
+/// Only meaningful for wide-area DEMs (tens of kilometers or more) in meter
+/// units, where distant terrain sinks below the horizon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EarthModel {
+    /// Flat grid, no correction
+    #[default]
+    Flat,
+    /// Drop terrain by d^2 / 2R with horizontal distance d from the camera
+    Curved,
+    /// Map a longitude/latitude grid onto a sphere
+    Globe,
+}
+
+impl EarthModel {
+    /// Coefficient k of the vertex drop k * d^2, or 0.0 for no drop.
+    pub fn curvature_coef(self) -> f32 {
+        match self {
+            EarthModel::Curved => (0.5 / EARTH_RADIUS) as f32,
+            EarthModel::Flat | EarthModel::Globe => 0.0,
+        }
+    }
+}
+
+/// Vertical transform from mesh positions to world positions, applied in
+/// the vertex shaders before the curvature drop.
+///
+/// Meshes store their heights at the scale they were built with, so the
+/// height scale can change without rebuilding them: `y * scale + offset`
+/// rescales heights about the world's zero level rather than the render
+/// origin's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeightTransform {
+    pub scale: f32,
+    pub offset: f32,
+}
+
+impl HeightTransform {
+    /// Leaves positions unchanged, for world-space geometry.
+    pub const IDENTITY: Self = Self {
+        scale: 1.0,
+        offset: 0.0,
+    };
+
+    /// Transform showing a mesh built at `baked` height scale, relative to
+    /// a render origin at height `origin_y`, at `height_scale`.
+    pub fn between(baked: f32, height_scale: f32, origin_y: f64) -> Self {
+        let scale = height_scale / baked;
+        Self {
+            scale,
+            offset: (origin_y * (scale as f64 - 1.0)) as f32,
+        }
+    }
+
+    /// World height of mesh height `y`.
+    pub fn apply(self, y: f32) -> f32 {
+        y * self.scale + self.offset
+    }
+
+    /// Mesh height drawn at world height `y`. A zero scale flattens every
+    /// height, so `y` is only shifted back.
+    pub fn invert(self, y: f32) -> f32 {
+        if self.scale == 0.0 {
+            y - self.offset
+        } else {
+            (y - self.offset) / self.scale
+        }
+    }
+}
+
 /// Lighting configuration for solid rendering.
 #[derive(Debug, Clone, Copy)]
 pub struct LightingConfig {
@@ -54,6 +385,76 @@ impl Default for LightingConfig {
     }
 }
 
+/// Sun-path shadow study: drives the light from the sun's position for a
+/// latitude, date and time of day.
+#[derive(Debug, Clone, Copy)]
+pub struct SunStudy {
+    /// Whether the sun position overrides the light direction
+    pub enabled: bool,
+    /// Latitude in degrees, north positive
+    pub latitude: f32,
+    /// Day of the year (1-365)
+    pub day_of_year: u32,
+    /// Local solar time in hours (12.0 = solar noon)
+    pub hour: f32,
+    /// Color the terrain by the day's clear-sky irradiation
+    pub irradiance: bool,
+}
+
+impl SunStudy {
+    /// Direction toward the sun (east = +X, up = +Y, north = +Z).
+    pub fn direction(&self) -> Vec3 {
+        sun::sun_direction(
+            self.latitude as f64,
+            self.day_of_year as f64,
+            self.hour as f64,
+        )
+        .as_vec3()
+    }
+
+    /// Latitude and day the irradiance overlay is computed for, or `None`
+    /// when it's off.
+    pub fn irradiance_key(&self) -> Option<(f32, u32)> {
+        self.irradiance.then_some((self.latitude, self.day_of_year))
+    }
+}
+
+impl Default for SunStudy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latitude: 45.0,
+            day_of_year: sun::day_of_year(6, 21),
+            hour: 9.0,
+            irradiance: false,
+        }
+    }
+}
+
+/// Translucent water plane configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaterConfig {
+    /// Whether the water plane is drawn
+    pub enabled: bool,
+    /// Water surface height, in terrain height units before scaling
+    pub level: f32,
+    /// Opacity (0.0 - 1.0)
+    pub opacity: f32,
+    /// How the water combines with the scene beneath it
+    pub blend: BlendMode,
+}
+
+impl Default for WaterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            level: 0.0,
+            opacity: 0.5,
+            blend: BlendMode::default(),
+        }
+    }
+}
+
 /// Contour line configuration.
 #[derive(Debug, Clone, Copy)]
 pub struct ContourConfig {
@@ -78,24 +479,237 @@ impl Default for ContourConfig {
     }
 }
 
+/// User-adjustable view settings edited through the UI.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    /// Current render mode
+    pub render_mode: RenderMode,
+    /// Mesh or clipmap geometry
+    pub strategy: RenderStrategy,
+    /// Height error allowed by the TIN strategy, in terrain height units
+    pub tin_tolerance: f32,
+    /// Skip mesh chunks hidden behind other terrain
+    pub occlusion_culling: bool,
+    /// Cull chunks on the GPU and draw them with indirect multi-draws,
+    /// where supported
+    pub indirect_draw: bool,
+    /// Depth buffer convention
+    pub depth_mode: DepthMode,
+    /// Samples per pixel of the main pass; see [`msaa`]
+    pub msaa: u32,
+    /// Earth curvature correction
+    pub earth_model: EarthModel,
+    /// Lighting configuration
+    pub lighting: LightingConfig,
+    /// Cast terrain shadows from the light in solid modes
+    pub shadows: bool,
+    /// Sun position study
+    pub sun: SunStudy,
+    /// Share of the draped image in the solid surface's color, against
+    /// the height colors
+    pub texture_blend: f32,
+    /// Solid surface opacity; below 1.0 the surface is drawn as a
+    /// translucent layer
+    pub surface_opacity: f32,
+    /// Blend mode of the surface layer; other than normal, the surface is
+    /// drawn as a translucent layer even when opaque
+    pub surface_blend: BlendMode,
+    /// Translucent water plane
+    pub water: WaterConfig,
+    /// Picture-in-picture overview camera
+    pub overview: OverviewConfig,
+    /// Smoothing of camera moves
+    pub motion: MotionConfig,
+    /// A/B swipe between a settings snapshot and the current settings
+    pub compare: CompareConfig,
+    /// Shared clock of the animated views
+    pub timeline: TimelineConfig,
+    /// Frame count, size and length of turntable exports
+    pub turntable: TurntableConfig,
+    /// Continuous turntable rotation of the live view
+    pub auto_rotate: AutoRotate,
+    /// Contour line configuration
+    pub contour: ContourConfig,
+    /// Quantity the mesh is colored by
+    pub color_mode: ColorMode,
+    /// Window half-width of the terrain index color modes; see
+    /// [`MeshOptions::index_radius`]
+    pub index_radius: usize,
+    /// Color scheme for terrain
+    pub color_scheme: ColorScheme,
+    /// Custom gradient for terrain coloring
+    pub gradient: GradientConfig,
+    /// Slope-angle bands of the slope bands color mode
+    pub slope_bands: SlopeBands,
+    /// Convert scheme colors to linear space; see [`MeshOptions::linear_colors`]
+    pub gamma_correct: bool,
+    /// Color vision deficiency the finished image is shown with
+    pub color_vision: ColorVision,
+    /// Glow around bright parts of the finished image
+    pub bloom: BloomConfig,
+    /// Color behind the terrain, in sRGB, when the sky is off
+    pub background: [f32; 3],
+    /// Gradient drawn behind the terrain
+    pub sky: SkyConfig,
+    /// Fading of distant surfaces
+    pub fog: FogConfig,
+    /// Lighting, reflections and background from a loaded environment map
+    pub environment: EnvironmentConfig,
+    /// Reference grid on the zero-height plane
+    pub ground_grid: bool,
+    /// XYZ axes in a corner of the window, turning with the camera
+    pub axes_gizmo: bool,
+    /// Brightness of the terrain's wireframe lines, from 0 to 1; overlay
+    /// lines are always drawn at full brightness
+    pub line_brightness: f32,
+    /// Use the terrain file's per-point colors; see [`MeshOptions::file_colors`]
+    pub file_colors: bool,
+    /// Height scale multiplier
+    pub height_scale: f32,
+    /// Fraction of wireframe lines to keep; see [`MeshOptions::edge_density`]
+    pub edge_density: f32,
+    /// Coarser meshes for distant chunks
+    pub lod: LodConfig,
+    /// Overlay extracted ridge and valley lines
+    pub ridgelines: bool,
+    /// Arrows or streamlines of the loaded vector field
+    pub vectors: VectorConfig,
+    /// Markers of the loaded point features
+    pub points: PointConfig,
+    /// Channel shown as height uncertainty, and how
+    pub uncertainty: UncertaintyConfig,
+    /// Darken mesh colors by baked ambient occlusion
+    pub ambient_occlusion: bool,
+    /// Color, and optionally shape, the terrain by height above a
+    /// reference surface
+    pub relief: ReliefConfig,
+    /// Smooth noise out of the heights the mesh is built from
+    pub denoise: DenoiseConfig,
+    /// Tool run by right-clicking the terrain
+    pub tool: Tool,
+    /// Antenna heights of the line-of-sight tool
+    pub sight: SightConfig,
+    /// Color and size of the paint tool's brush
+    pub brush: Brush,
+    /// Units measurements are shown in, converted from the terrain's
+    pub units: Units,
+    /// Named parameters declared by the active shaders
+    pub shader_params: ShaderParams,
+    /// Size of the UI relative to the monitor's scale factor
+    pub ui_scale: f32,
+    /// Present mode, frame rate cap and power saving
+    pub pacing: PacingConfig,
+    /// Other loaded terrains, in load order; see [`layers`]
+    pub layers: Vec<LayerConfig>,
+    /// Average the shared borders of the main terrain and the layers
+    pub stitch: StitchConfig,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            render_mode: RenderMode::default(),
+            strategy: RenderStrategy::default(),
+            tin_tolerance: tin::DEFAULT_TOLERANCE,
+            occlusion_culling: true,
+            indirect_draw: true,
+            depth_mode: DepthMode::default(),
+            msaa: 1,
+            earth_model: EarthModel::default(),
+            lighting: LightingConfig::default(),
+            shadows: false,
+            sun: SunStudy::default(),
+            texture_blend: 1.0,
+            surface_opacity: 1.0,
+            surface_blend: BlendMode::default(),
+            water: WaterConfig::default(),
+            overview: OverviewConfig::default(),
+            motion: MotionConfig::default(),
+            compare: CompareConfig::default(),
+            timeline: TimelineConfig::default(),
+            turntable: TurntableConfig::default(),
+            auto_rotate: AutoRotate::default(),
+            contour: ContourConfig::default(),
+            color_mode: ColorMode::default(),
+            index_radius: 1,
+            color_scheme: ColorScheme::default(),
+            gradient: GradientConfig::default(),
+            slope_bands: SlopeBands::default(),
+            gamma_correct: true,
+            color_vision: ColorVision::default(),
+            bloom: BloomConfig::default(),
+            background: DEFAULT_BACKGROUND,
+            sky: SkyConfig::default(),
+            fog: FogConfig::default(),
+            environment: EnvironmentConfig::default(),
+            ground_grid: false,
+            axes_gizmo: false,
+            line_brightness: 1.0,
+            file_colors: true,
+            height_scale: 1.0,
+            edge_density: 1.0,
+            lod: LodConfig::default(),
+            ridgelines: false,
+            vectors: VectorConfig::default(),
+            points: PointConfig::default(),
+            uncertainty: UncertaintyConfig::default(),
+            ambient_occlusion: false,
+            relief: ReliefConfig::default(),
+            denoise: DenoiseConfig::default(),
+            tool: Tool::default(),
+            sight: SightConfig::default(),
+            brush: Brush::default(),
+            units: Units::default(),
+            shader_params: ShaderParams::default(),
+            ui_scale: 1.0,
+            pacing: PacingConfig::default(),
+            layers: Vec::new(),
+            stitch: StitchConfig::default(),
+        }
+    }
+}
+
 /// Uniform data sent to shaders (wireframe - simple).
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct WireframeUniforms {
     view_proj: [[f32; 4]; 4],
+    log_depth_coef: f32,
+    curvature_coef: f32,
+    curvature_center: [f32; 2],
+    height_scale: f32,
+    height_offset: f32,
+    line_brightness: f32,
+    _pad: f32,
 }
 
 impl WireframeUniforms {
     fn new() -> Self {
         Self {
             view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            log_depth_coef: 0.0,
+            curvature_coef: 0.0,
+            curvature_center: [0.0; 2],
+            height_scale: 1.0,
+            height_offset: 0.0,
+            line_brightness: 1.0,
+            _pad: 0.0,
         }
     }
 
-    fn update(&mut self, camera: &Camera, aspect: f32) {
-        self.view_proj = camera
-            .build_view_projection_matrix(aspect)
-            .to_cols_array_2d();
+    fn update(&mut self, view_proj: Mat4, log_depth_coef: f32) {
+        self.view_proj = view_proj.to_cols_array_2d();
+        self.log_depth_coef = log_depth_coef;
+    }
+
+    fn set_curvature(&mut self, coef: f32, center: Vec2) {
+        self.curvature_coef = coef;
+        self.curvature_center = center.to_array();
+    }
+
+    fn set_height(&mut self, height: HeightTransform) {
+        self.height_scale = height.scale;
+        self.height_offset = height.offset;
     }
 }
 
@@ -105,7 +719,7 @@ impl WireframeUniforms {
 struct SolidUniforms {
     view_proj: [[f32; 4]; 4],
     light_dir: [f32; 3],
-    _pad0: f32,
+    shadows_enabled: f32, // 1.0 = enabled, 0.0 = disabled
     light_color: [f32; 3],
     ambient: f32,
     // Contour parameters
@@ -113,7 +727,24 @@ struct SolidUniforms {
     contour_interval: f32,
     contour_width: f32,
     contour_enabled: f32, // 1.0 = enabled, 0.0 = disabled
-    _pad1: [f32; 2],
+    log_depth_coef: f32,  // 0.0 = linear depth
+    curvature_coef: f32,  // 0.0 = flat
+    curvature_center: [f32; 2],
+    opacity: f32,
+    oit_range: f32,
+    light_view_proj: [[f32; 4]; 4],
+    height_scale: f32,
+    height_offset: f32,
+    uncertainty_style: f32, // 0.0 = off, see UncertaintyStyle::shader_index
+    uncertainty_strength: f32,
+    uncertainty_origin: [f32; 2],
+    uncertainty_scale: [f32; 2],
+    drape_origin: [f32; 2],
+    drape_scale: [f32; 2],
+    drape_correction: [f32; 4],
+    drape_shift: [f32; 2],
+    drape_blend: f32, // 0.0 = no draped image
+    _padding: f32,
 }
 
 impl SolidUniforms {
@@ -121,27 +752,43 @@ impl SolidUniforms {
         Self {
             view_proj: Mat4::IDENTITY.to_cols_array_2d(),
             light_dir: [0.5, 0.8, 0.3],
-            _pad0: 0.0,
+            shadows_enabled: 0.0,
             light_color: [1.0, 1.0, 1.0],
             ambient: 0.3,
             contour_color: [0.0, 0.0, 0.0],
             contour_interval: 5.0,
             contour_width: 0.15,
             contour_enabled: 0.0,
-            _pad1: [0.0, 0.0],
+            log_depth_coef: 0.0,
+            curvature_coef: 0.0,
+            curvature_center: [0.0; 2],
+            opacity: 1.0,
+            oit_range: 1.0,
+            light_view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            height_scale: 1.0,
+            height_offset: 0.0,
+            uncertainty_style: 0.0,
+            uncertainty_strength: 0.0,
+            uncertainty_origin: [0.0; 2],
+            uncertainty_scale: [0.0; 2],
+            drape_origin: [0.0; 2],
+            drape_scale: [0.0; 2],
+            drape_correction: Mat2::IDENTITY.to_cols_array(),
+            drape_shift: [0.0; 2],
+            drape_blend: 0.0,
+            _padding: 0.0,
         }
     }
 
     fn update(
         &mut self,
-        camera: &Camera,
-        aspect: f32,
+        view_proj: Mat4,
+        log_depth_coef: f32,
         lighting: &LightingConfig,
         contour: &ContourConfig,
     ) {
-        self.view_proj = camera
-            .build_view_projection_matrix(aspect)
-            .to_cols_array_2d();
+        self.view_proj = view_proj.to_cols_array_2d();
+        self.log_depth_coef = log_depth_coef;
         self.light_dir = lighting.direction.to_array();
         self.light_color = lighting.color.to_array();
         self.ambient = lighting.ambient;
@@ -150,6 +797,125 @@ impl SolidUniforms {
         self.contour_width = contour.width;
         self.contour_enabled = if contour.enabled { 1.0 } else { 0.0 };
     }
+
+    fn set_curvature(&mut self, coef: f32, center: Vec2) {
+        self.curvature_coef = coef;
+        self.curvature_center = center.to_array();
+    }
+
+    fn set_height(&mut self, height: HeightTransform) {
+        self.height_scale = height.scale;
+        self.height_offset = height.offset;
+    }
+
+    /// Set the opacity used when drawn as a translucent layer, and the view
+    /// distance over which layer weights fall off.
+    fn set_translucency(&mut self, opacity: f32, range: f32) {
+        self.opacity = opacity;
+        self.oit_range = range;
+    }
+
+    /// Enable shadows with the shadow map's light transform, or disable
+    /// them with `None`.
+    fn set_shadows(&mut self, light_view_proj: Option<Mat4>) {
+        self.shadows_enabled = if light_view_proj.is_some() { 1.0 } else { 0.0 };
+        self.light_view_proj = light_view_proj.unwrap_or(Mat4::IDENTITY).to_cols_array_2d();
+    }
+
+    /// Fade or hatch the surface by the uncertainty map as set by
+    /// `config`, with the map's placement (see [`uncertainty::placement`]),
+    /// or show no uncertainty with `None`.
+    fn set_uncertainty(&mut self, shown: Option<(&UncertaintyConfig, (Vec2, Vec2))>) {
+        let Some((config, (origin, scale))) = shown else {
+            self.uncertainty_style = 0.0;
+            return;
+        };
+        self.uncertainty_style = config.style.shader_index() as f32;
+        self.uncertainty_strength = config.strength;
+        self.uncertainty_origin = origin.to_array();
+        self.uncertainty_scale = scale.to_array();
+    }
+
+    /// Blend the draped image into the surface color by `blend`, with the
+    /// image's placement (see [`uncertainty::placement`]) and alignment
+    /// correction, or show the vertex colors only with `None`.
+    fn set_drape(&mut self, shown: Option<(f32, (Vec2, Vec2), DAffine2)>) {
+        let Some((blend, (origin, scale), correction)) = shown else {
+            self.drape_blend = 0.0;
+            return;
+        };
+        self.drape_blend = blend;
+        self.drape_origin = origin.to_array();
+        self.drape_scale = scale.to_array();
+        self.drape_correction = correction.matrix2.as_mat2().to_cols_array();
+        self.drape_shift = correction.translation.as_vec2().to_array();
+    }
+}
+
+/// Where frames are drawn.
+enum FrameTarget {
+    /// Window surface, presented after each frame
+    Surface(wgpu::Surface<'static>),
+    /// Offscreen texture for headless rendering, see [`Renderer::read_pixels`]
+    Offscreen(wgpu::Texture),
+}
+
+/// Format of the offscreen target, matching the sRGB surfaces preferred
+/// for windows.
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Create the offscreen target texture for `config`.
+fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
+/// An alpha mode of `supported` that blends the surface with what's behind
+/// the window, preferring premultiplied alpha as the renderer writes it.
+fn transparent_alpha_mode(
+    supported: &[wgpu::CompositeAlphaMode],
+) -> Option<wgpu::CompositeAlphaMode> {
+    [
+        wgpu::CompositeAlphaMode::PreMultiplied,
+        wgpu::CompositeAlphaMode::PostMultiplied,
+        wgpu::CompositeAlphaMode::Inherit,
+    ]
+    .into_iter()
+    .find(|mode| supported.contains(mode))
+}
+
+/// Request the device and queue the renderer needs from `adapter`.
+async fn request_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let device = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            // Sample counts beyond 4x and a pipeline cache, where the
+            // adapter has them
+            required_features: adapter.features()
+                & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                    | wgpu::Features::PIPELINE_CACHE),
+            required_limits: wgpu::Limits::default(),
+            label: None,
+            memory_hints: Default::default(),
+            trace: Default::default(),
+            experimental_features: Default::default(),
+        })
+        .await?;
+    Ok(device)
 }
 
 /// GPU renderer managing wgpu state and rendering.
@@ -157,10 +923,12 @@ impl SolidUniforms {
 /// Handles the complete rendering pipeline from mesh upload to frame presentation.
 pub struct Renderer {
     // Core wgpu objects
-    surface: wgpu::Surface<'static>,
+    target: FrameTarget,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
+    /// Clear to transparent, for a window composited over the desktop
+    transparent: bool,
 
     /// Current window size (for aspect ratio and resize handling)
     pub size: winit::dpi::PhysicalSize<u32>,
@@ -168,16 +936,181 @@ pub struct Renderer {
     // Depth buffer
     depth_texture: wgpu::Texture,
     depth_view: wgpu::TextureView,
+    /// Multisampled targets of the main pass
+    pub msaa: Msaa,
+    /// Single-sampled solid, wireframe and line strip pipelines for the
+    /// overview inset, drawn after the main pass is resolved; `None`
+    /// without multisampling
+    overview_pipelines: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline, StripPipelines)>,
 
     // Wireframe pipeline
     wireframe_pipeline: wgpu::RenderPipeline,
+    wireframe_pipeline_layout: wgpu::PipelineLayout,
+    /// Wireframe pipelines for the terrain's lines when drawn as strips
+    strip_pipelines: StripPipelines,
     wireframe_uniform_buffer: wgpu::Buffer,
     wireframe_bind_group: wgpu::BindGroup,
+    /// Wireframe uniforms of the terrain's own lines, dimmed by the line
+    /// brightness; overlays use the full-brightness ones
+    grid_uniform_buffer: wgpu::Buffer,
+    grid_bind_group: wgpu::BindGroup,
+    /// Camera, sky and fog uniforms shared by the terrain pipelines
+    scene_uniform_buffer: wgpu::Buffer,
+    /// Gradient background drawn first in the main pass
+    sky: Sky,
+    /// Reference grid drawn over the opaque scene
+    ground_grid: GroundGrid,
 
     // Solid pipeline
     solid_pipeline: wgpu::RenderPipeline,
+    solid_pipeline_layout: wgpu::PipelineLayout,
+    clipmap_pipeline: wgpu::RenderPipeline,
+    clipmap_pipeline_layout: wgpu::PipelineLayout,
+    clipmap_bind_group_layout: wgpu::BindGroupLayout,
+    /// Height texture and grid for the clipmap strategy
+    clipmap: Option<Clipmap>,
     solid_uniform_buffer: wgpu::Buffer,
+    solid_bind_group_layout: wgpu::BindGroupLayout,
     solid_bind_group: wgpu::BindGroup,
+    /// Sun depth map sampled by the solid shader
+    shadow_map: ShadowMap,
+    /// Shown σ channel, sampled by the solid shader
+    uncertainty_map: UncertaintyMap,
+    /// Image draped over the terrain, sampled by the solid shader
+    drape_texture: DrapeTexture,
+    /// Whether an image is draped, rather than the blank texture bound
+    has_drape: bool,
+    /// The draped image shrunk for the alignment window
+    drape_preview: Option<DrapeImage>,
+    /// Environment map sampled by the sky and solid shaders
+    environment_texture: EnvironmentTexture,
+    /// Whether an environment map is loaded, rather than the black texture
+    /// bound
+    has_environment: bool,
+    /// Order-independent transparency targets and composite
+    oit: Oit,
+    /// Solid shader drawing translucent layers into the OIT targets
+    oit_layer_pipeline: wgpu::RenderPipeline,
+    /// Targets and composite for layers with a blend mode
+    blend: Blend,
+    /// Glow over the finished scene
+    bloom: Bloom,
+    /// Color vision simulation over the finished scene
+    colorblind: Colorblind,
+    /// Uniforms, marker and background for the overview inset
+    overview: Overview,
+    /// Snapshot and uniforms for the left side of the swipe comparison
+    compare: Compare,
+    /// Other loaded terrains, in the order of `settings.layers`
+    layers: Vec<Layer>,
+    /// Uniforms the layers are drawn with
+    layer_uniforms: LayerUniforms,
+    /// Solid shader uniforms for the water plane
+    water_uniform_buffer: wgpu::Buffer,
+    water_bind_group: wgpu::BindGroup,
+    /// Water plane quad, rewritten each frame it's drawn
+    water_vertex_buffer: wgpu::Buffer,
+    water_index_buffer: wgpu::Buffer,
+
+    // Captured camera frustum, drawn as lines for debugging
+    frustum_vertex_buffer: Option<wgpu::Buffer>,
+    frustum_index_buffer: Option<wgpu::Buffer>,
+    /// World-space corners of the captured frustum
+    pub captured_frustum: Option<[Vec3; 8]>,
+
+    // Ridge and valley line overlay, extracted once per terrain
+    ridgelines: Option<Vec<Polyline>>,
+    /// Elevation and slope distributions, computed on request
+    terrain_stats: Option<TerrainStats>,
+    /// Sample counts over the height range, for the color scheme previews
+    height_histogram: Vec<u32>,
+    /// Baked ambient occlusion and the height scale it was baked for
+    ao_factors: Option<(f32, Vec<f32>)>,
+    /// Irradiance overlay, computed for the sun study's latitude and day
+    irradiation: Option<Irradiation>,
+    ridgeline_vertex_buffer: Option<wgpu::Buffer>,
+    ridgeline_index_buffer: Option<wgpu::Buffer>,
+    num_ridgeline_indices: u32,
+
+    /// Vector field drawn over the terrain, e.g. wind
+    vector_field: Option<VectorField>,
+    vector_vertex_buffer: Option<wgpu::Buffer>,
+    vector_index_buffer: Option<wgpu::Buffer>,
+    num_vector_indices: u32,
+    /// Vector overlay settings the lines were built with
+    prev_vectors: VectorConfig,
+
+    /// Point features drawn over the terrain, e.g. wells
+    pub points: Option<PointSet>,
+    point_vertex_buffer: Option<wgpu::Buffer>,
+    point_index_buffer: Option<wgpu::Buffer>,
+    num_point_indices: u32,
+    /// Point marker settings the pins were built with
+    prev_points: PointConfig,
+
+    // Uncertainty whiskers, drawn as a line overlay
+    whisker_vertex_buffer: Option<wgpu::Buffer>,
+    whisker_index_buffer: Option<wgpu::Buffer>,
+    num_whisker_indices: u32,
+    /// Uncertainty settings the map and whiskers were built with
+    prev_uncertainty: UncertaintyConfig,
+
+    // Tool results, drawn as line overlays
+    /// Flow routing for the catchment tool, computed once per terrain
+    flow: Option<FlowGrid>,
+    /// Last delineated catchment
+    pub catchment: Option<Catchment>,
+    /// Observer picked for the line-of-sight tool, waiting for a target
+    pub sight_start: Option<DVec2>,
+    /// Last line-of-sight check
+    pub line_of_sight: Option<LineOfSight>,
+    /// Start of a profile cut, waiting for its end
+    pub profile_start: Option<DVec2>,
+    /// Heights along the last profile cut
+    pub profile: Option<Profile>,
+    /// Last point picked with the inspect tool
+    pub inspected: Option<Pick>,
+    /// Shapes and notes drawn with the annotation tools
+    pub annotations: Vec<Annotation>,
+    /// Lines the TIN strategy's triangulation keeps edges along
+    pub breaklines: Vec<Breakline>,
+    /// File the annotations are saved to whenever they change, if any
+    annotations_file: Option<PathBuf>,
+    /// Shape being drawn, added to the annotations once finished
+    pub sketch: Option<Annotation>,
+    /// Region selected with the selection tools
+    pub selection: Option<Selection>,
+    /// Points dragged so far for the selection: the first corner of a
+    /// rectangle or the lasso's outline
+    selecting: Vec<DVec2>,
+    /// Control points aligning the draped image
+    pub drape_alignment: DrapeAlignment,
+    /// Control point whose terrain end the alignment tool is dragging
+    grabbed_control_point: Option<usize>,
+    tool_vertex_buffer: Option<wgpu::Buffer>,
+    tool_index_buffer: Option<wgpu::Buffer>,
+    num_tool_indices: u32,
+
+    // Named shader parameters, shared by both pipelines
+    params_buffer: wgpu::Buffer,
+
+    /// Watches shader files for changes (debug builds, or a color snippet)
+    shader_watcher: Option<ShaderWatcher>,
+    /// User-supplied WGSL color function patched into the solid shader
+    color_snippet: Option<PathBuf>,
+    /// Last shader compilation error, shown in the UI overlay
+    pub shader_error: Option<String>,
+    /// Main terrain file last loaded in the background, until the app
+    /// takes it to move its per-file state over
+    pub opened_file: Option<PathBuf>,
+    /// Compiled pipelines, kept across runs where supported
+    pipeline_cache: PipelineCache,
+    /// The GPU rendered on, as `adapter::describe` puts it
+    adapter_name: String,
+    /// Pipeline variants still to build ahead of use
+    warmup: Warmup,
+    /// Error of the last raster calculator statement, shown under it
+    pub algebra_error: Option<String>,
 
     // Mesh buffers
     vertex_buffer: Option<wgpu::Buffer>,
@@ -185,27 +1118,50 @@ pub struct Renderer {
     triangle_index_buffer: Option<wgpu::Buffer>,
     num_wireframe_indices: u32,
     num_triangle_indices: u32,
+    /// Format of both index buffers, 16-bit for small meshes
+    index_format: wgpu::IndexFormat,
+    /// Whether the wireframe index buffer holds line pairs or strips
+    line_topology: LineTopology,
+    /// Index ranges and bounds of the uploaded mesh tiles
+    mesh_chunks: Vec<MeshChunk>,
+    /// Index buffer contents still to write, while a loaded terrain's mesh
+    /// is uploaded; only the chunks written are drawn meanwhile
+    pending_indices: Option<PendingIndices>,
+    /// Mesh built for the main terrain by the job that loaded it, with the
+    /// options it was built with, used unless the settings changed since
+    loaded_mesh: Option<(MeshOptions, TerrainMesh)>,
+    /// Occlusion queries for `mesh_chunks`
+    occlusion: Option<OcclusionCuller>,
+    occlusion_pipeline: wgpu::RenderPipeline,
+    /// GPU-built draw list for `mesh_chunks`, if indirect draws are supported
+    indirect_chunks: Option<IndirectChunks>,
+    cull_pipeline: Option<wgpu::ComputePipeline>,
+    cull_pipeline_layout: wgpu::PipelineLayout,
+    cull_bind_group_layout: wgpu::BindGroupLayout,
+    /// Chunks drawn in the last frame, when culling
+    chunks_drawn: Option<usize>,
+    /// Bounding box of the drawn terrain, for fitting clip planes and
+    /// framing the camera
+    scene_box: Option<(Vec3, Vec3)>,
+    /// Bounding box of the uploaded mesh, before the height transform
+    mesh_box: Option<(Vec3, Vec3)>,
+    /// Height scale baked into the uploaded mesh's positions
+    mesh_height_scale: f32,
+    /// World-space point the f32 mesh positions are relative to
+    render_origin: DVec3,
 
-    /// Current render mode
-    pub render_mode: RenderMode,
-
-    /// Lighting configuration
-    pub lighting: LightingConfig,
-
-    /// Contour line configuration
-    pub contour: ContourConfig,
-
-    /// Color scheme for terrain
-    pub color_scheme: ColorScheme,
-
-    /// Custom gradient for terrain coloring
-    pub gradient: GradientConfig,
+    /// View settings edited through the UI
+    pub settings: RenderSettings,
 
     /// Orbital camera for viewing the terrain
     pub camera: Camera,
+    /// Camera frames are drawn from, following `camera` as
+    /// [`MotionConfig`] smooths it
+    view_camera: Camera,
 
-    // egui
-    egui_state: egui_winit::State,
+    // egui; the winit state is absent when rendering offscreen
+    egui_ctx: egui::Context,
+    egui_state: Option<egui_winit::State>,
     egui_renderer: egui_wgpu::Renderer,
 
     /// UI state
@@ -215,21 +1171,183 @@ pub struct Renderer {
     last_frame: Instant,
     frame_count: u32,
     fps: f32,
+    /// Frame time run as fixed animation steps
+    clock: FixedStep,
+    /// Input-to-present latency and surface acquire wait
+    latency: LatencyStats,
+    /// Per-frame timings written for `--perf-log`
+    perf_log: Option<PerfLog>,
 
     /// Terrain data for mesh regeneration
     terrain_data: Option<crate::terrain::TerrainData>,
-    /// Height scale for mesh regeneration
-    height_scale: f32,
+    /// Copy of the terrain with its borders stitched to the layers', which
+    /// the mesh is built from instead when set
+    stitched_terrain: Option<crate::terrain::TerrainData>,
+    /// Samples of all terrains the last stitch changed
+    stitched_samples: usize,
+    /// Name the main terrain is listed under among the layers
+    pub terrain_name: String,
+    /// Main terrain as loaded, while it's cropped, smoothed or downsampled
+    original: Option<crate::terrain::TerrainData>,
+    /// Second grid for relative relief against a loaded reference
+    reference_data: Option<crate::terrain::TerrainData>,
+    /// Previous color mode to detect changes
+    prev_color_mode: ColorMode,
+    /// Previous terrain index window to detect changes
+    prev_index_radius: usize,
     /// Previous color scheme to detect changes
     prev_color_scheme: ColorScheme,
     /// Previous gradient to detect changes
     prev_gradient: GradientConfig,
+    /// Previous slope bands to detect changes
+    prev_slope_bands: SlopeBands,
+    /// Previous gamma correction toggle to detect changes
+    prev_gamma_correct: bool,
+    /// Previous file colors toggle to detect changes
+    prev_file_colors: bool,
+    /// Whether the paint tool changed the terrain's colors since the mesh
+    /// was last built
+    colors_painted: bool,
+    /// Paint strokes that changed the terrain's colors, for autosaving
+    paint_strokes: u64,
     /// Previous height scale to detect changes
     prev_height_scale: f32,
+    /// Previous edge density to detect changes
+    prev_edge_density: f32,
+    /// Previous TIN tolerance to detect changes
+    prev_tin_tolerance: f32,
+    /// Previous level of detail toggle to detect changes
+    prev_lod: bool,
+    /// Previous ridgeline toggle to detect changes
+    prev_ridgelines: bool,
+    /// Previous ambient occlusion toggle to detect changes
+    prev_ambient_occlusion: bool,
+    /// Previous relief configuration to detect changes
+    prev_relief: ReliefConfig,
+    /// Previous seam stitching configuration to detect changes
+    prev_stitch: StitchConfig,
+    /// Previous denoise configuration to detect changes
+    prev_denoise: DenoiseConfig,
+    /// Previous irradiance overlay latitude and day to detect changes
+    prev_irradiance: Option<(f32, u32)>,
+    /// Previous earth model to detect switching to or from globe mode
+    prev_earth_model: EarthModel,
+    /// Previous render strategy to detect changes
+    prev_strategy: RenderStrategy,
+    /// Depth mode the pipelines were built with
+    prev_depth_mode: DepthMode,
+    /// Sample count the pipelines were last built for
+    prev_msaa: u32,
+    /// Present mode the surface was last configured with
+    prev_present_mode: PresentMode,
+    /// Present modes the surface supports; none offscreen
+    present_modes: Vec<PresentMode>,
+    /// Whether something moved in the last frame and the next should
+    /// follow without waiting for input
+    busy: bool,
+
+    /// Turntable export requested from the UI, run after the current frame
+    pending_turntable: Option<PathBuf>,
+    /// Screenshot requested with F12 or from the UI, taken after the
+    /// current frame
+    pending_screenshot: bool,
+    /// Directory screenshots are saved to
+    pub screenshot_dir: PathBuf,
+    /// Keyframes the camera follows on the timeline's path track
+    pub camera_path: CameraPath,
+    /// Camera path being recorded from the live camera
+    recording: Option<Recording>,
+    /// Named camera views of the terrain
+    pub bookmarks: Bookmarks,
+    /// Presentation mode: no UI is drawn and window events go straight to
+    /// the camera
+    pub presenting: bool,
+    /// Drawing turntable frames or a screenshot: the UI is left out
+    capturing: bool,
+    /// Background check for a newer release, until its notice is dismissed
+    pub update_check: Option<UpdateCheck>,
+    /// Input settings edited under Input Settings, with their file
+    pub user_config: Config,
+    /// Whether the input settings were edited since last taken
+    input_changed: bool,
+    /// Periodic writer of unsaved edits for crash recovery
+    autosave: Option<Autosave>,
+    /// Edits left by a session that crashed, until restored or discarded
+    pub recovered: Option<Edits>,
+    /// Terrain file that failed to load, until loaded again or dismissed
+    pub load_failure: Option<LoadFailure>,
+    /// Camera pose to show once the main terrain loads in the background,
+    /// instead of framing it
+    restored_camera: Option<Keyframe>,
+    /// Water level the flooded share and area were last measured at
+    flooded: Option<(f32, (f64, f64))>,
+    /// Analysis, bakes and exports running in the background
+    jobs: Workers<JobDone>,
+    /// Job computing the terrain analysis
+    stats_job: Option<JobId>,
+    /// Job baking the irradiance overlay, with its latitude and day of year
+    irradiance_job: Option<(JobId, (f32, u32))>,
+    /// Float grid the main terrain is the overview of, mapped to draw it
+    /// near the camera at full resolution
+    detail_source: Option<Arc<MappedTerrain>>,
+    /// Full-resolution window drawn in place of its chunks
+    detail: Option<(DetailWindow, MeshBuffers)>,
+    /// Job reading a detail window, with the window
+    detail_job: Option<(JobId, DetailWindow)>,
+}
+
+/// Result of a background job, applied to the renderer once it finishes.
+type JobDone = Box<dyn FnOnce(&mut Renderer) + Send>;
+
+/// Result of a terrain load that failed: the failure, kept to be shown.
+fn load_failed(failure: LoadFailure) -> JobDone {
+    log::warn!(
+        "Failed to load {}: {}",
+        failure.path.display(),
+        failure.message
+    );
+    Box::new(move |renderer: &mut Renderer| renderer.load_failure = Some(failure))
+}
+
+/// Index buffer contents of `parts` one after the other, in `format`,
+/// padded to whole words.
+fn index_bytes(format: wgpu::IndexFormat, parts: [&[u32]; 2]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = match format {
+        // Restarts truncate to 0xFFFF, the 16-bit restart value
+        wgpu::IndexFormat::Uint16 => parts
+            .concat()
+            .into_iter()
+            .flat_map(|i| (i as u16).to_ne_bytes())
+            .collect(),
+        wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(&parts.concat()).to_vec(),
+    };
+    bytes.resize(
+        bytes
+            .len()
+            .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize),
+        0,
+    );
+    bytes
+}
+
+/// Jobs list label of a job writing `path`.
+fn writing_label(path: &std::path::Path) -> String {
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    format!("Writing {}", name.to_string_lossy())
 }
 
 const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+/// Default color behind the terrain, in sRGB.
+const DEFAULT_BACKGROUND: [f32; 3] = [0.35, 0.35, 0.42];
+
+/// Height ranges the color scheme previews count samples in.
+const HEIGHT_HISTOGRAM_BINS: usize = 64;
+
+/// Distance the camera target may drift from the render origin before the
+/// mesh is rebuilt around a new origin, keeping f32 positions precise.
+const REBASE_DISTANCE: f32 = 4096.0;
+
 fn create_depth_texture(
     device: &wgpu::Device,
     width: u32,
@@ -253,104 +1371,753 @@ fn create_depth_texture(
     (texture, view)
 }
 
-impl Renderer {
-    /// Create a new renderer for the given window.
-    ///
-    /// # Arguments
-    ///
-    /// * `window` - The window to render to
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if GPU initialization fails.
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
-        let size = window.inner_size();
+/// Edges of a frustum as index pairs into [`Camera::frustum_corners`].
+const FRUSTUM_LINE_INDICES: [u32; 24] = [
+    0, 1, 1, 2, 2, 3, 3, 0, // near plane
+    4, 5, 5, 6, 6, 7, 7, 4, // far plane
+    0, 4, 1, 5, 2, 6, 3, 7, // connecting edges
+];
 
-        // Create wgpu instance
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
+/// Line-list geometry for a frustum given its eight corners.
+fn frustum_lines(corners: &[Vec3; 8]) -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = corners
+        .iter()
+        .map(|corner| Vertex {
+            position: corner.to_array(),
+            color: [1.0, 0.85, 0.0],
+            normal: [0.0, 1.0, 0.0],
+        })
+        .collect();
+    (vertices, FRUSTUM_LINE_INDICES.to_vec())
+}
 
-        // Create surface for the window
-        let surface = instance.create_surface(window.clone())?;
+/// Offset of ridgelines along the surface normal, in cells, so they draw
+/// over the solid surface.
+const RIDGELINE_LIFT: f32 = 0.05;
 
-        // Request GPU adapter
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await?;
+/// Translucent layers that can take a blend mode: the surface and the
+/// water plane.
+const BLEND_LAYERS: usize = 2;
 
-        // Create device and queue
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-                label: None,
-                memory_hints: Default::default(),
-                trace: Default::default(),
-                experimental_features: Default::default(),
-            })
-            .await?;
+/// Two triangles over the water plane's four corners.
+const WATER_INDICES: [u32; 6] = [0, 1, 2, 2, 1, 3];
 
-        // Configure surface
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
+/// Water plane color.
+const WATER_COLOR: [f32; 3] = [0.05, 0.3, 0.55];
 
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        surface.configure(&device, &config);
+/// Vector field arrow and streamline color.
+const VECTOR_COLOR: [f32; 3] = [0.95, 0.95, 1.0];
 
-        // Init egui
-        let egui_ctx = egui::Context::default();
-        let egui_state = egui_winit::State::new(
-            egui_ctx,
-            egui::ViewportId::ROOT,
-            &window,
-            Some(window.scale_factor() as f32),
-            None,
-            Some(2048),
-        );
-        let egui_renderer = egui_wgpu::Renderer::new(
-            &device,
-            surface_format,
-            egui_wgpu::RendererOptions {
-                depth_stencil_format: Some(DEPTH_FORMAT),
-                ..Default::default()
-            },
-        );
-        let ui = Ui::new();
+/// Uncertainty whisker color.
+const WHISKER_COLOR: [f32; 3] = [1.0, 0.55, 0.1];
 
-        // Create depth texture
-        let (depth_texture, depth_view) = create_depth_texture(&device, size.width, size.height);
+/// Width of whisker caps, as a fraction of the whisker spacing.
+const WHISKER_CAP: f64 = 0.4;
 
-        // Load wireframe shader
-        let wireframe_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Wireframe Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/terrain.wgsl").into()),
-        });
+/// Quad at height `y` covering a bounding sphere, for the water plane.
+fn water_quad((center, radius): (Vec3, f32), y: f32) -> [Vertex; 4] {
+    let corner = |dx: f32, dz: f32| Vertex {
+        position: [center.x + dx * radius, y, center.z + dz * radius],
+        color: WATER_COLOR,
+        normal: [0.0, 1.0, 0.0],
+    };
+    [
+        corner(-1.0, -1.0),
+        corner(1.0, -1.0),
+        corner(-1.0, 1.0),
+        corner(1.0, 1.0),
+    ]
+}
+
+/// Sampling interval of the sun over the day for shadow-hours exports.
+const SHADOW_HOURS_STEP: f64 = 0.25;
+
+/// Sampling interval of the sun over the day for the irradiance overlay,
+/// coarser than exports since it's recomputed as the study changes.
+const IRRADIANCE_STEP: f64 = 0.5;
+
+/// Mesh chunks whose indices are written per frame while the mesh of a
+/// loaded terrain is uploaded.
+const UPLOAD_CHUNKS: usize = 64;
+
+/// Index buffers of a loaded terrain's mesh still being written, a few
+/// chunks per frame, so a large mesh doesn't hold up a single frame.
+struct PendingIndices {
+    /// Contents of the triangle and wireframe index buffers, padded to
+    /// whole words
+    contents: [Vec<u8>; 2],
+    /// Bytes of each written so far
+    written: [usize; 2],
+    /// Chunks whose full-detail indices are written
+    chunks: usize,
+    /// Full index counts, drawn once all are written
+    counts: [u32; 2],
+}
+
+/// Daily clear-sky irradiation shown by the irradiance overlay.
+struct Irradiation {
+    /// Latitude and day of year it was computed for
+    key: (f32, u32),
+    /// Energy per grid vertex in kWh/m²
+    energy: Vec<f64>,
+    /// Lowest and highest energy, the ends of the legend
+    range: (f64, f64),
+}
+
+/// Line-list geometry for ridge and valley lines on `mesh`.
+fn ridgeline_geometry(
+    lines: &[Polyline],
+    mesh: &TerrainMesh,
+    lift: f32,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for line in lines {
+        let color = match line.kind {
+            LineKind::Ridge => [1.0, 0.35, 0.1],
+            LineKind::Valley => [0.1, 0.5, 1.0],
+        };
+        let start = vertices.len() as u32;
+        for &i in &line.vertices {
+            let v = &mesh.vertices[i];
+            let position = Vec3::from(v.position) + Vec3::from(v.normal) * lift;
+            vertices.push(Vertex {
+                position: position.to_array(),
+                color,
+                normal: v.normal,
+            });
+        }
+        for n in 1..line.vertices.len() as u32 {
+            indices.push(start + n - 1);
+            indices.push(start + n);
+        }
+    }
+    (vertices, indices)
+}
+
+/// Bind group layout of the wireframe pipeline: its uniforms, read by both
+/// stages since logarithmic depth needs them in the fragment stage, the
+/// shader parameters and the scene uniforms.
+fn create_wireframe_bind_group_layout(
+    device: &wgpu::Device,
+    params_layout_entry: wgpu::BindGroupLayoutEntry,
+    scene_layout_entry: wgpu::BindGroupLayoutEntry,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            params_layout_entry,
+            scene_layout_entry,
+        ],
+        label: Some("Wireframe Bind Group Layout"),
+    })
+}
+
+/// Bind group layout of the solid pipeline: its uniforms, the shader
+/// parameters, the scene uniforms, the shadow map with its comparison
+/// sampler, the uncertainty map, the draped image and the environment map.
+fn create_solid_bind_group_layout(
+    device: &wgpu::Device,
+    params_layout_entry: wgpu::BindGroupLayoutEntry,
+    scene_layout_entry: wgpu::BindGroupLayoutEntry,
+) -> wgpu::BindGroupLayout {
+    let environment_entries = environment::layout_entries();
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            params_layout_entry,
+            scene_layout_entry,
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 8,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            environment_entries[0],
+            environment_entries[1],
+        ],
+        label: Some("Solid Bind Group Layout"),
+    })
+}
+
+/// Build the wireframe pipeline from WGSL source, drawing `samples` per
+/// pixel: line lists, or line strips with indices of `strip_format`.
+#[allow(clippy::too_many_arguments)]
+fn create_wireframe_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    depth_mode: DepthMode,
+    samples: u32,
+    source: &str,
+    strip_format: Option<wgpu::IndexFormat>,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Wireframe Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Wireframe Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(depth_mode.fragment_entry_point()),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: match strip_format {
+                Some(_) => wgpu::PrimitiveTopology::LineStrip,
+                None => wgpu::PrimitiveTopology::LineList,
+            },
+            strip_index_format: strip_format,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: samples,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache,
+    })
+}
+
+/// Wireframe pipelines drawing the terrain's line strips, one per index
+/// format since the restart value depends on it.
+struct StripPipelines {
+    uint16: wgpu::RenderPipeline,
+    uint32: wgpu::RenderPipeline,
+}
+
+impl StripPipelines {
+    /// Build both from the wireframe WGSL source, as
+    /// [`create_wireframe_pipeline`] does.
+    fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        depth_mode: DepthMode,
+        samples: u32,
+        source: &str,
+    ) -> Self {
+        let create = |strip_format| {
+            create_wireframe_pipeline(
+                device,
+                cache,
+                layout,
+                format,
+                depth_mode,
+                samples,
+                source,
+                Some(strip_format),
+            )
+        };
+        Self {
+            uint16: create(wgpu::IndexFormat::Uint16),
+            uint32: create(wgpu::IndexFormat::Uint32),
+        }
+    }
+
+    /// Pipeline for strips indexed with `format`.
+    fn get(&self, format: wgpu::IndexFormat) -> &wgpu::RenderPipeline {
+        match format {
+            wgpu::IndexFormat::Uint16 => &self.uint16,
+            wgpu::IndexFormat::Uint32 => &self.uint32,
+        }
+    }
+}
+
+/// Resources the solid shader reads besides its own uniforms.
+struct SolidMaps<'a> {
+    params: &'a wgpu::Buffer,
+    shadow: &'a ShadowMap,
+    uncertainty: &'a UncertaintyMap,
+    drape: &'a DrapeTexture,
+    environment: &'a EnvironmentTexture,
+}
+
+impl SolidMaps<'_> {
+    /// Bind group for drawing with the solid pipeline from `uniform_buffer`
+    /// and `scene_buffer`.
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        label: &str,
+        (uniform_buffer, scene_buffer): (&wgpu::Buffer, &wgpu::Buffer),
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: params::PARAMS_BINDING,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: atmosphere::SCENE_BINDING,
+                    resource: scene_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(self.shadow.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(self.shadow.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(self.uncertainty.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(self.uncertainty.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(self.drape.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::Sampler(self.drape.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: environment::ENVIRONMENT_BINDING,
+                    resource: wgpu::BindingResource::TextureView(self.environment.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: environment::ENVIRONMENT_BINDING + 1,
+                    resource: wgpu::BindingResource::Sampler(self.environment.sampler()),
+                },
+            ],
+            label: Some(label),
+        })
+    }
+}
+
+/// Every pipeline built from the shader sources, for one sample count and
+/// depth convention.
+struct Pipelines {
+    wireframe: wgpu::RenderPipeline,
+    strips: StripPipelines,
+    solid: wgpu::RenderPipeline,
+    occlusion: wgpu::RenderPipeline,
+    cull: Option<wgpu::ComputePipeline>,
+    shadow: wgpu::RenderPipeline,
+    oit_layer: wgpu::RenderPipeline,
+    oit_composite: wgpu::RenderPipeline,
+    blend_composite: wgpu::RenderPipeline,
+    bloom: BloomPipelines,
+    colorblind: wgpu::RenderPipeline,
+    overview: wgpu::RenderPipeline,
+    clipmap: wgpu::RenderPipeline,
+    resolve: wgpu::RenderPipeline,
+    sky: wgpu::RenderPipeline,
+    ground_grid: wgpu::RenderPipeline,
+    overview_sets: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline, StripPipelines)>,
+}
+
+/// Build the solid shaded (triangle list) pipeline from WGSL source,
+/// drawing `samples` per pixel.
+fn create_solid_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    depth_mode: DepthMode,
+    samples: u32,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Solid Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Solid Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(depth_mode.fragment_entry_point()),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: samples,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache,
+    })
+}
+
+impl Renderer {
+    /// Create a new renderer for the given window.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The window to render to
+    /// * `transparent` - Clear to transparent so the window shows the desktop
+    ///   behind the terrain, if the surface can composite with alpha
+    /// * `choice` - The GPU to render on, or `None` to let wgpu pick one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if GPU initialization fails, or the chosen adapter
+    /// doesn't exist or can't present to the window.
+    pub async fn new(
+        window: Arc<Window>,
+        transparent: bool,
+        choice: Option<&AdapterChoice>,
+    ) -> anyhow::Result<Self> {
+        let size = window.inner_size();
+
+        // Create wgpu instance
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        // Create surface for the window
+        let surface = instance.create_surface(window.clone())?;
+
+        // Request GPU adapter
+        let adapter = match choice {
+            Some(choice) => {
+                let adapter = choice.pick(adapter::list(&instance))?;
+                if !adapter.is_surface_supported(&surface) {
+                    anyhow::bail!(
+                        "{} can't draw to this window",
+                        adapter::describe(&adapter.get_info())
+                    );
+                }
+                adapter
+            }
+            None => {
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::default(),
+                        compatible_surface: Some(&surface),
+                        force_fallback_adapter: false,
+                    })
+                    .await?
+            }
+        };
+        log::info!("Rendering on {}", adapter::describe(&adapter.get_info()));
+        let (device, queue) = request_device(&adapter).await?;
+
+        // Configure surface
+        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(surface_caps.formats[0]);
+        let (alpha_mode, transparent) = if transparent {
+            match transparent_alpha_mode(&surface_caps.alpha_modes) {
+                Some(mode) => (mode, true),
+                None => {
+                    log::warn!("Surface doesn't support transparency, drawing opaque");
+                    (surface_caps.alpha_modes[0], false)
+                }
+            }
+        } else {
+            (surface_caps.alpha_modes[0], false)
+        };
+
+        let config = wgpu::SurfaceConfiguration {
+            // Copies of the scene feed layer blend modes, where supported
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode: PresentMode::default().fit(&surface_caps.present_modes),
+            alpha_mode,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &config);
+
+        // Init egui
+        let egui_ctx = egui::Context::default();
+        let egui_state = egui_winit::State::new(
+            egui_ctx,
+            egui::ViewportId::ROOT,
+            &window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(2048),
+        );
+
+        // Pipelines compiled in earlier runs load from disk
+        let pipeline_cache = PipelineCache::new(
+            &device,
+            &adapter.get_info(),
+            PipelineCache::default_dir().as_deref(),
+        );
+        let mut renderer = Self::with_target(
+            &adapter,
+            device,
+            queue,
+            config,
+            FrameTarget::Surface(surface),
+            Some(egui_state),
+            pipeline_cache,
+        )?;
+        renderer.transparent = transparent;
+        renderer.present_modes = PresentMode::ALL
+            .into_iter()
+            .filter(|mode| surface_caps.present_modes.contains(&mode.to_wgpu()))
+            .collect();
+        renderer.warm_pipelines();
+        Ok(renderer)
+    }
+
+    /// Create a renderer drawing `width` x `height` frames into an offscreen
+    /// texture instead of a window, for tests and batch rendering. Frames
+    /// are read back with [`read_pixels`](Self::read_pixels).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no GPU adapter is available.
+    pub async fn new_headless(width: u32, height: u32) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await?;
+        Self::new_headless_on(&adapter, width, height).await
+    }
+
+    /// Create a headless renderer like [`new_headless`](Self::new_headless)
+    /// on a given adapter, such as one picked from [`adapter::list`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the adapter can't create a device.
+    pub async fn new_headless_on(
+        adapter: &wgpu::Adapter,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let (device, queue) = request_device(adapter).await?;
+        Self::with_device(adapter, device, queue, OFFSCREEN_FORMAT, width, height)
+    }
+
+    /// Create a renderer on a device the caller already has, drawing
+    /// `format` frames of `width` x `height` into an offscreen texture
+    /// until [`render_to`](Self::render_to) hands it another one.
+    ///
+    /// `adapter` is the one `device` came from; its capabilities decide
+    /// the MSAA sample counts and GPU culling on offer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shaders fail to build.
+    pub fn with_device(
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let texture = create_offscreen_texture(&device, &config);
+        let pipeline_cache = PipelineCache::new(&device, &adapter.get_info(), None);
+
+        let mut renderer = Self::with_target(
+            adapter,
+            device,
+            queue,
+            config,
+            FrameTarget::Offscreen(texture),
+            None,
+            pipeline_cache,
+        )?;
+        // Offscreen frames are stills to read back
+        renderer.settings.motion.enabled = false;
+        Ok(renderer)
+    }
+
+    /// Set up pipelines and resources for a configured frame target.
+    fn with_target(
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        target: FrameTarget,
+        egui_state: Option<egui_winit::State>,
+        pipeline_cache: PipelineCache,
+    ) -> anyhow::Result<Self> {
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+        let egui_ctx = match &egui_state {
+            Some(state) => state.egui_ctx().clone(),
+            None => egui::Context::default(),
+        };
+        // The UI scale setting is the only thing zooming the UI
+        egui_ctx.options_mut(|options| options.zoom_with_keyboard = false);
+        let egui_renderer = egui_wgpu::Renderer::new(
+            &device,
+            config.format,
+            egui_wgpu::RendererOptions {
+                depth_stencil_format: Some(DEPTH_FORMAT),
+                ..Default::default()
+            },
+        );
+        let ui = Ui::new();
+
+        // Create depth texture
+        let (depth_texture, depth_view) = create_depth_texture(&device, size.width, size.height);
 
-        // Load solid shader
-        let solid_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Solid Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/solid.wgsl").into()),
+        // Load shaders and create the shared parameter buffer
+        let sources = shaders::load(None).map_err(anyhow::Error::msg)?;
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shader Params Buffer"),
+            contents: bytemuck::bytes_of(&sources.params.to_uniforms()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let params_layout_entry = params::layout_entry();
+        let params_entry = wgpu::BindGroupEntry {
+            binding: params::PARAMS_BINDING,
+            resource: params_buffer.as_entire_binding(),
+        };
+        // Camera, sky and fog settings shared by the terrain and sky shaders
+        let scene_uniform_buffer = atmosphere::create_buffer(&device, "Scene Uniform Buffer");
+        let scene_layout_entry = atmosphere::layout_entry();
 
         // Create wireframe uniform buffer and bind group
         let wireframe_uniforms = WireframeUniforms::new();
@@ -362,26 +2129,21 @@ impl Renderer {
             });
 
         let wireframe_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("Wireframe Bind Group Layout"),
-            });
+            create_wireframe_bind_group_layout(&device, params_layout_entry, scene_layout_entry);
 
         let wireframe_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &wireframe_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wireframe_uniform_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wireframe_uniform_buffer.as_entire_binding(),
+                },
+                params_entry.clone(),
+                wgpu::BindGroupEntry {
+                    binding: atmosphere::SCENE_BINDING,
+                    resource: scene_uniform_buffer.as_entire_binding(),
+                },
+            ],
             label: Some("Wireframe Bind Group"),
         });
 
@@ -394,171 +2156,491 @@ impl Renderer {
         });
 
         let solid_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("Solid Bind Group Layout"),
-            });
+            create_solid_bind_group_layout(&device, params_layout_entry, scene_layout_entry);
 
-        let solid_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &solid_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: solid_uniform_buffer.as_entire_binding(),
-            }],
-            label: Some("Solid Bind Group"),
+        let cache = pipeline_cache.get();
+        let shadow_map = ShadowMap::new(&device, cache, &sources.shadow);
+        let uncertainty_map = UncertaintyMap::empty(&device, &queue);
+        let drape_texture = DrapeTexture::empty(&device, &queue);
+        let environment_texture = EnvironmentTexture::empty(&device, &queue);
+        let solid_maps = SolidMaps {
+            params: &params_buffer,
+            shadow: &shadow_map,
+            uncertainty: &uncertainty_map,
+            drape: &drape_texture,
+            environment: &environment_texture,
+        };
+        let create_solid_bind_group =
+            |label, uniform_buffer: &wgpu::Buffer, scene_buffer: &wgpu::Buffer| {
+                solid_maps.bind_group(
+                    &device,
+                    &solid_bind_group_layout,
+                    label,
+                    (uniform_buffer, scene_buffer),
+                )
+            };
+        let solid_bind_group = create_solid_bind_group(
+            "Solid Bind Group",
+            &solid_uniform_buffer,
+            &scene_uniform_buffer,
+        );
+
+        // The water plane is drawn by the solid shader with its own uniforms
+        let water_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Water Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[solid_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let water_bind_group = create_solid_bind_group(
+            "Water Bind Group",
+            &water_uniform_buffer,
+            &scene_uniform_buffer,
+        );
+        let water_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Water Vertex Buffer"),
+            size: (std::mem::size_of::<Vertex>() * 4) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let water_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Water Index Buffer"),
+            contents: bytemuck::cast_slice(&WATER_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let create_wireframe_bind_group =
+            |label, uniform_buffer: &wgpu::Buffer, scene_buffer: &wgpu::Buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &wireframe_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: uniform_buffer.as_entire_binding(),
+                        },
+                        params_entry.clone(),
+                        wgpu::BindGroupEntry {
+                            binding: atmosphere::SCENE_BINDING,
+                            resource: scene_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some(label),
+                })
+            };
+
+        // The overview inset draws with the same pipelines from another
+        // camera, without fog
+        let overview = Overview::new(
+            &device,
+            cache,
+            config.format,
+            &sources.overview,
+            |uniform_buffer, scene_buffer| {
+                create_wireframe_bind_group(
+                    "Overview Wireframe Bind Group",
+                    uniform_buffer,
+                    scene_buffer,
+                )
+            },
+            |uniform_buffer, scene_buffer| {
+                create_solid_bind_group("Overview Solid Bind Group", uniform_buffer, scene_buffer)
+            },
+        );
+        // So does the snapshot side of the swipe comparison, in the same fog
+        let compare = Compare::new(
+            &device,
+            |uniform_buffer| {
+                create_wireframe_bind_group(
+                    "Compare Wireframe Bind Group",
+                    uniform_buffer,
+                    &scene_uniform_buffer,
+                )
+            },
+            |uniform_buffer| {
+                create_solid_bind_group(
+                    "Compare Solid Bind Group",
+                    uniform_buffer,
+                    &scene_uniform_buffer,
+                )
+            },
+        );
+        // And the other loaded terrains
+        let layer_uniforms = LayerUniforms::new(
+            &device,
+            |uniform_buffer| {
+                create_wireframe_bind_group(
+                    "Layer Wireframe Bind Group",
+                    uniform_buffer,
+                    &scene_uniform_buffer,
+                )
+            },
+            |uniform_buffer| {
+                create_solid_bind_group(
+                    "Layer Solid Bind Group",
+                    uniform_buffer,
+                    &scene_uniform_buffer,
+                )
+            },
+        );
+        let grid_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[wireframe_uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let grid_bind_group = create_wireframe_bind_group(
+            "Grid Bind Group",
+            &grid_uniform_buffer,
+            &scene_uniform_buffer,
+        );
+
+        // Reversed-Z brings no precision benefit on GL, see DepthMode
+        let depth_mode = if adapter.get_info().backend == wgpu::Backend::Gl {
+            DepthMode::Standard
+        } else {
+            DepthMode::Reversed
+        };
+        let settings = RenderSettings {
+            depth_mode,
+            shader_params: sources.params.clone(),
+            ..Default::default()
+        };
 
-        // Create wireframe pipeline
+        // Create pipelines
         let wireframe_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Wireframe Pipeline Layout"),
                 bind_group_layouts: &[&wireframe_bind_group_layout],
                 push_constant_ranges: &[],
             });
+        let wireframe_pipeline = create_wireframe_pipeline(
+            &device,
+            cache,
+            &wireframe_pipeline_layout,
+            config.format,
+            settings.depth_mode,
+            1,
+            &sources.wireframe,
+            None,
+        );
+        let strip_pipelines = StripPipelines::new(
+            &device,
+            cache,
+            &wireframe_pipeline_layout,
+            config.format,
+            settings.depth_mode,
+            1,
+            &sources.wireframe,
+        );
 
-        let wireframe_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Wireframe Pipeline"),
-            layout: Some(&wireframe_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &wireframe_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &wireframe_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::LineList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
-
-        // Create solid pipeline
         let solid_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Solid Pipeline Layout"),
                 bind_group_layouts: &[&solid_bind_group_layout],
                 push_constant_ranges: &[],
             });
+        let solid_pipeline = create_solid_pipeline(
+            &device,
+            cache,
+            &solid_pipeline_layout,
+            config.format,
+            settings.depth_mode,
+            1,
+            &sources.solid,
+        );
+        let oit = Oit::new(
+            &device,
+            cache,
+            (config.width, config.height),
+            config.format,
+            &sources.oit,
+        );
+        let oit_layer_pipeline = oit::create_layer_pipeline(
+            &device,
+            cache,
+            &solid_pipeline_layout,
+            settings.depth_mode,
+            &sources.solid,
+        );
+        let blend = Blend::new(
+            &device,
+            cache,
+            &oit,
+            BLEND_LAYERS,
+            (config.width, config.height),
+            config.format,
+            &sources.blend,
+        );
+        let bloom = Bloom::new(
+            &device,
+            cache,
+            (config.width, config.height),
+            config.format,
+            &sources.bloom,
+        );
+        let colorblind = Colorblind::new(
+            &device,
+            cache,
+            (config.width, config.height),
+            config.format,
+            &sources.colorblind,
+        );
+        let sky = Sky::new(
+            &device,
+            cache,
+            config.format,
+            (&scene_uniform_buffer, &environment_texture),
+            &sources.sky,
+        );
+        let ground_grid = GroundGrid::new(
+            &device,
+            cache,
+            config.format,
+            settings.depth_mode,
+            &sources.ground_grid,
+        );
+        let msaa = Msaa::new(
+            &device,
+            cache,
+            msaa::supported_counts(adapter, config.format),
+            config.format,
+            &sources.depth_resolve,
+        );
 
-        let solid_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Solid Pipeline"),
-            layout: Some(&solid_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &solid_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &solid_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
+        let occlusion_pipeline = occlusion::create_box_pipeline(
+            &device,
+            cache,
+            &wireframe_pipeline_layout,
+            config.format,
+            settings.depth_mode,
+            1,
+            &sources.wireframe,
+        );
+
+        let cull_bind_group_layout = indirect::bind_group_layout(&device);
+        let cull_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cull Pipeline Layout"),
+            bind_group_layouts: &[&cull_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let cull_pipeline = indirect::is_supported(adapter).then(|| {
+            indirect::create_pipeline(&device, cache, &cull_pipeline_layout, &sources.cull)
         });
+        if cull_pipeline.is_none() {
+            log::info!("Indirect draws unsupported, drawing chunks directly");
+        }
+
+        let clipmap_bind_group_layout =
+            clipmap::bind_group_layout(&device, params_layout_entry, scene_layout_entry);
+        let clipmap_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Clipmap Pipeline Layout"),
+                bind_group_layouts: &[&clipmap_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let clipmap_pipeline = clipmap::create_pipeline(
+            &device,
+            cache,
+            &clipmap_pipeline_layout,
+            config.format,
+            settings.depth_mode,
+            1,
+            &sources.clipmap,
+        );
+
+        let shader_watcher =
+            cfg!(debug_assertions).then(|| ShaderWatcher::new(shaders::shader_dir()));
 
         let camera = Camera::new();
 
         Ok(Self {
-            surface,
+            target,
             device,
             queue,
             config,
+            transparent: false,
             size,
             depth_texture,
             depth_view,
+            msaa,
+            overview_pipelines: None,
             wireframe_pipeline,
+            wireframe_pipeline_layout,
+            strip_pipelines,
             wireframe_uniform_buffer,
             wireframe_bind_group,
+            grid_uniform_buffer,
+            grid_bind_group,
+            scene_uniform_buffer,
+            sky,
+            ground_grid,
             solid_pipeline,
+            solid_pipeline_layout,
+            clipmap_pipeline,
+            clipmap_pipeline_layout,
+            clipmap_bind_group_layout,
+            clipmap: None,
             solid_uniform_buffer,
+            solid_bind_group_layout,
             solid_bind_group,
+            shadow_map,
+            uncertainty_map,
+            drape_texture,
+            has_drape: false,
+            drape_preview: None,
+            environment_texture,
+            has_environment: false,
+            oit,
+            oit_layer_pipeline,
+            blend,
+            bloom,
+            colorblind,
+            overview,
+            compare,
+            layers: Vec::new(),
+            layer_uniforms,
+            water_uniform_buffer,
+            water_bind_group,
+            water_vertex_buffer,
+            water_index_buffer,
+            frustum_vertex_buffer: None,
+            frustum_index_buffer: None,
+            captured_frustum: None,
+            ridgelines: None,
+            terrain_stats: None,
+            height_histogram: Vec::new(),
+            ao_factors: None,
+            irradiation: None,
+            ridgeline_vertex_buffer: None,
+            ridgeline_index_buffer: None,
+            num_ridgeline_indices: 0,
+            vector_field: None,
+            vector_vertex_buffer: None,
+            vector_index_buffer: None,
+            num_vector_indices: 0,
+            prev_vectors: VectorConfig::default(),
+            points: None,
+            point_vertex_buffer: None,
+            point_index_buffer: None,
+            num_point_indices: 0,
+            prev_points: PointConfig::default(),
+            whisker_vertex_buffer: None,
+            whisker_index_buffer: None,
+            num_whisker_indices: 0,
+            prev_uncertainty: UncertaintyConfig::default(),
+            flow: None,
+            catchment: None,
+            sight_start: None,
+            line_of_sight: None,
+            profile_start: None,
+            profile: None,
+            inspected: None,
+            annotations: Vec::new(),
+            annotations_file: None,
+            breaklines: Vec::new(),
+            sketch: None,
+            selection: None,
+            selecting: Vec::new(),
+            drape_alignment: DrapeAlignment::default(),
+            grabbed_control_point: None,
+            tool_vertex_buffer: None,
+            tool_index_buffer: None,
+            num_tool_indices: 0,
+            params_buffer,
+            shader_watcher,
+            color_snippet: None,
+            shader_error: None,
+            opened_file: None,
+            pipeline_cache,
+            adapter_name: adapter::describe(&adapter.get_info()),
+            warmup: Warmup::default(),
+            algebra_error: None,
             vertex_buffer: None,
             wireframe_index_buffer: None,
             triangle_index_buffer: None,
             num_wireframe_indices: 0,
             num_triangle_indices: 0,
-            render_mode: RenderMode::default(),
-            lighting: LightingConfig::default(),
-            contour: ContourConfig::default(),
-            color_scheme: ColorScheme::default(),
-            gradient: GradientConfig::default(),
+            index_format: wgpu::IndexFormat::Uint32,
+            line_topology: LineTopology::List,
+            mesh_chunks: Vec::new(),
+            pending_indices: None,
+            loaded_mesh: None,
+            occlusion: None,
+            occlusion_pipeline,
+            indirect_chunks: None,
+            cull_pipeline,
+            cull_pipeline_layout,
+            cull_bind_group_layout,
+            chunks_drawn: None,
+            scene_box: None,
+            mesh_box: None,
+            mesh_height_scale: 1.0,
+            render_origin: DVec3::ZERO,
+            settings,
+            view_camera: camera.clone(),
             camera,
+            egui_ctx,
             egui_state,
             egui_renderer,
             ui,
             last_frame: Instant::now(),
             frame_count: 0,
             fps: 0.0,
+            clock: FixedStep::default(),
+            latency: LatencyStats::default(),
+            perf_log: None,
             terrain_data: None,
-            height_scale: 1.0,
+            stitched_terrain: None,
+            stitched_samples: 0,
+            terrain_name: "Terrain".to_string(),
+            original: None,
+            reference_data: None,
+            prev_color_mode: ColorMode::default(),
+            prev_index_radius: 1,
             prev_color_scheme: ColorScheme::default(),
             prev_gradient: GradientConfig::default(),
+            prev_slope_bands: SlopeBands::default(),
+            prev_gamma_correct: true,
+            prev_file_colors: true,
+            colors_painted: false,
+            paint_strokes: 0,
             prev_height_scale: 1.0,
+            prev_edge_density: 1.0,
+            prev_tin_tolerance: tin::DEFAULT_TOLERANCE,
+            prev_lod: false,
+            prev_ridgelines: false,
+            prev_ambient_occlusion: false,
+            prev_relief: ReliefConfig::default(),
+            prev_stitch: StitchConfig::default(),
+            prev_denoise: DenoiseConfig::default(),
+            prev_irradiance: None,
+            prev_earth_model: EarthModel::default(),
+            prev_strategy: RenderStrategy::default(),
+            prev_depth_mode: depth_mode,
+            prev_msaa: 1,
+            prev_present_mode: PresentMode::default(),
+            present_modes: Vec::new(),
+            busy: false,
+            pending_turntable: None,
+            recording: None,
+            bookmarks: Bookmarks::default(),
+            pending_screenshot: false,
+            screenshot_dir: PathBuf::from("."),
+            camera_path: CameraPath::default(),
+            presenting: false,
+            capturing: false,
+            update_check: None,
+            user_config: Config::default(),
+            input_changed: false,
+            autosave: None,
+            recovered: None,
+            load_failure: None,
+            restored_camera: None,
+            flooded: None,
+            jobs: Workers::default(),
+            stats_job: None,
+            irradiance_job: None,
+            detail_source: None,
+            detail: None,
+            detail_job: None,
         })
     }
 
@@ -568,7 +2650,12 @@ impl Renderer {
         window: &Window,
         event: &winit::event::WindowEvent,
     ) -> bool {
-        self.egui_state.on_window_event(window, event).consumed
+        if self.presenting {
+            return false;
+        }
+        self.egui_state
+            .as_mut()
+            .is_some_and(|state| state.on_window_event(window, event).consumed)
     }
 
     /// Handle window resize.
@@ -579,167 +2666,3603 @@ impl Renderer {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            match &mut self.target {
+                FrameTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+                FrameTarget::Offscreen(texture) => {
+                    *texture = create_offscreen_texture(&self.device, &self.config)
+                }
+            }
 
             // Recreate depth texture for new size
             let (depth_texture, depth_view) =
                 create_depth_texture(&self.device, new_size.width, new_size.height);
             self.depth_texture = depth_texture;
             self.depth_view = depth_view;
+            self.oit
+                .resize(&self.device, new_size.width, new_size.height);
+            self.blend
+                .resize(&self.device, &self.oit, new_size.width, new_size.height);
+            self.bloom
+                .resize(&self.device, new_size.width, new_size.height);
+            self.colorblind
+                .resize(&self.device, new_size.width, new_size.height);
+            self.msaa
+                .resize(&self.device, new_size.width, new_size.height);
         }
     }
 
+    /// Read back the last offscreen frame as RGBA texels, row by row from
+    /// the top. Returns `None` when rendering to a window.
+    pub fn read_pixels(&self) -> Option<Vec<[u8; 4]>> {
+        let FrameTarget::Offscreen(texture) = &self.target else {
+            return None;
+        };
+        // Surface formats other than 8-bit RGBA or BGRA can't be read as texels
+        let bgra = match self.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => return None,
+        };
+        let row_bytes = self.config.width * 4;
+        // Buffer rows must be aligned; the padding is dropped below
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row_bytes * self.config.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.device.poll(wgpu::PollType::wait_indefinitely()).ok()?;
+        receiver.recv().ok()?.ok()?;
+
+        let data = buffer.slice(..).get_mapped_range();
+        let pixels = data
+            .chunks_exact(padded_row_bytes as usize)
+            .flat_map(|row| {
+                row[..row_bytes as usize]
+                    .chunks_exact(4)
+                    .map(move |texel| match bgra {
+                        false => [texel[0], texel[1], texel[2], texel[3]],
+                        true => [texel[2], texel[1], texel[0], texel[3]],
+                    })
+            })
+            .collect();
+        Some(pixels)
+    }
+
     /// Upload terrain data to GPU.
     ///
     /// Stores the terrain data and generates a mesh with the current color scheme.
     /// The terrain data is retained so the mesh can be regenerated when the color scheme changes.
     pub fn upload_terrain(&mut self, terrain: &crate::terrain::TerrainData, height_scale: f32) {
         self.terrain_data = Some(terrain.clone());
-        self.height_scale = height_scale;
+        self.ridgelines = None;
+        self.terrain_stats = None;
+        self.flooded = None;
+        self.height_histogram = analysis::height_histogram(terrain, HEIGHT_HISTOGRAM_BINS);
+        self.ao_factors = None;
+        self.irradiation = None;
+        self.cancel_terrain_jobs();
+        self.detail_source = None;
+        self.flow = None;
+        self.clear_tools();
+        self.settings.height_scale = height_scale;
+        self.settings.units = terrain.units;
+        self.render_origin = terrain.center();
+        self.regenerate_mesh();
+    }
+
+    /// Use `grid` as the reference surface for relative relief and show
+    /// the terrain's height above it.
+    pub fn set_reference(&mut self, grid: crate::terrain::TerrainData) {
+        self.reference_data = Some(grid);
+        self.settings.relief.enabled = true;
+        self.settings.relief.reference = relief::ReferenceSurface::Grid;
+        self.regenerate_mesh();
+    }
+
+    /// Add `terrain` as a layer listed under `name`, drawn in its file's
+    /// place next to the main terrain.
+    pub fn add_terrain(&mut self, name: impl Into<String>, terrain: crate::terrain::TerrainData) {
+        self.layers.push(Layer::new(terrain));
+        self.settings.layers.push(LayerConfig::new(name));
+        if self.settings.stitch.enabled {
+            self.regenerate_mesh();
+        } else {
+            self.upload_layers(false);
+        }
+    }
+
+    /// Load the grid at `path` with `options` in the background and hand
+    /// it to `apply` once it's ready. If it fails to load, the failure is
+    /// kept in [`load_failure`](Self::load_failure), to retry as `target`.
+    fn spawn_load(
+        &mut self,
+        path: PathBuf,
+        options: loader::LoadOptions,
+        target: LoadTarget,
+        apply: impl FnOnce(&mut Renderer, crate::terrain::TerrainData) + Send + 'static,
+    ) -> JobId {
+        let name = layers::name_of(&path);
+        self.jobs.spawn(format!("Loading {name}"), move |progress| {
+            let terrain = loader::load_terrain_with_progress(&path, &options, |done| {
+                progress.set(done);
+                !progress.is_cancelled()
+            });
+            progress.check()?;
+            Ok(match terrain {
+                Ok(terrain) => Box::new(move |renderer: &mut Renderer| apply(renderer, terrain)),
+                Err(err) => load_failed(LoadFailure::new(path, options, target, &err)),
+            })
+        })
+    }
+
+    /// Load the terrain at `path` in the background and add it as a layer.
+    /// If it fails to load, the failure is kept in
+    /// [`load_failure`](Self::load_failure).
+    pub fn open_terrain(&mut self, path: PathBuf, options: loader::LoadOptions) -> JobId {
+        let name = layers::name_of(&path);
+        self.spawn_load(
+            path,
+            options,
+            LoadTarget::Layer,
+            move |renderer, terrain| {
+                renderer.add_terrain(name, terrain);
+            },
+        )
+    }
+
+    /// Load the terrain at `path` in the background as the main terrain,
+    /// framing it once it's ready. The window keeps drawing, and lists the
+    /// load with its progress, meanwhile. If it fails to load, the failure
+    /// is kept in [`load_failure`](Self::load_failure).
+    pub fn load_main_terrain(&mut self, path: PathBuf, options: loader::LoadOptions) -> JobId {
+        let name = layers::name_of(&path);
+        let file = path.clone();
+        // Meshes shaped by other grids or bakes are built once loaded
+        let mesh_options = (self.settings.strategy != RenderStrategy::Clipmap
+            && self.mesh_from_heights())
+        .then(|| self.main_mesh_options(DVec3::ZERO));
+        self.jobs.spawn(format!("Loading {name}"), move |progress| {
+            // Loading and meshing each fill half the bar
+            let share = if mesh_options.is_some() { 0.5 } else { 1.0 };
+            let terrain = loader::load_terrain_with_progress(&path, &options, |done| {
+                progress.set(done * share);
+                !progress.is_cancelled()
+            });
+            progress.check()?;
+            let terrain = match terrain {
+                Ok(terrain) => terrain,
+                Err(err) => {
+                    return Ok(load_failed(LoadFailure::new(
+                        path,
+                        options,
+                        LoadTarget::Main,
+                        &err,
+                    )))
+                }
+            };
+            let mesh = match mesh_options {
+                Some(mesh_options) => {
+                    let mesh_options = MeshOptions {
+                        origin: Some(terrain.center()),
+                        ..mesh_options
+                    };
+                    let mesh = TerrainMesh::build_with_progress(&terrain, &mesh_options, |done| {
+                        progress.set(share + done * (1.0 - share));
+                        !progress.is_cancelled()
+                    })
+                    .ok_or(workers::Cancelled)?;
+                    Some((mesh_options, mesh))
+                }
+                None => None,
+            };
+            Ok(Box::new(move |renderer: &mut Renderer| {
+                renderer.apply_main_terrain(name, file, options, terrain, mesh);
+            }) as JobDone)
+        })
+    }
+
+    /// Show `terrain`, loaded from `file` with `options` by
+    /// [`load_main_terrain`](Self::load_main_terrain), as the main terrain,
+    /// uploading the `mesh` built with it if the settings still match.
+    fn apply_main_terrain(
+        &mut self,
+        name: String,
+        file: PathBuf,
+        options: loader::LoadOptions,
+        terrain: crate::terrain::TerrainData,
+        mesh: Option<(MeshOptions, TerrainMesh)>,
+    ) {
+        self.terrain_name = name;
+        self.original = None;
+        self.loaded_mesh = mesh;
+        self.upload_terrain(&terrain, self.settings.height_scale);
+        self.loaded_mesh = None;
+        let format = options
+            .format
+            .unwrap_or_else(|| loader::TerrainFormat::from_path(&file));
+        if format == loader::TerrainFormat::FloatGrid {
+            if let Err(err) = self.map_main_terrain(&file, options) {
+                log::warn!("Failed to map {}: {}", file.display(), err);
+            }
+        }
+        match self.restored_camera.take() {
+            Some(pose) => self.set_camera_pose(&pose),
+            None => self.frame_scene(),
+        }
+        self.opened_file = Some(file);
+    }
+
+    /// Load the grid at `path` in the background as the reference surface
+    /// for relative relief.
+    pub fn load_reference(&mut self, path: PathBuf, options: loader::LoadOptions) -> JobId {
+        self.spawn_load(path, options, LoadTarget::Reference, |renderer, grid| {
+            renderer.set_reference(grid);
+        })
+    }
+
+    /// Load the grid at `path` in the background as the terrain's channel
+    /// `name`, resampled onto it.
+    pub fn load_channel(
+        &mut self,
+        name: String,
+        path: PathBuf,
+        options: loader::LoadOptions,
+    ) -> JobId {
+        let target = LoadTarget::Channel(name.clone());
+        self.spawn_load(path, options, target, move |renderer, grid| {
+            renderer.add_channel(&name, &grid);
+        })
+    }
+
+    /// Load the grid at `path` in the background as the terrain's
+    /// uncertainty channel, and show it.
+    pub fn load_uncertainty(&mut self, path: PathBuf, options: loader::LoadOptions) -> JobId {
+        self.spawn_load(path, options, LoadTarget::Uncertainty, |renderer, grid| {
+            if let Some(index) = renderer.add_channel("uncertainty", &grid) {
+                renderer.set_uncertainty(index);
+            }
+        })
+    }
+
+    /// Load the east and north components of a vector field from `paths`
+    /// in the background, one after the other, and draw the field.
+    pub fn load_vector_field(
+        &mut self,
+        paths: [PathBuf; 2],
+        options: loader::LoadOptions,
+    ) -> JobId {
+        let target = LoadTarget::VectorField(paths.clone());
+        let [east, north] = paths;
+        self.spawn_load(east, options, target.clone(), move |renderer, east| {
+            renderer.spawn_load(north, options, target, move |renderer, north| {
+                if let Some(terrain) = &renderer.terrain_data {
+                    let field = VectorField::resampled(&east, &north, terrain);
+                    renderer.set_vector_field(field);
+                }
+            });
+        })
+    }
+
+    /// Add `grid` as the terrain's channel `name`, resampled onto it.
+    /// Returns the channel's index, or `None` without a terrain.
+    fn add_channel(&mut self, name: &str, grid: &crate::terrain::TerrainData) -> Option<usize> {
+        let terrain = self.terrain_data.as_mut()?;
+        terrain.add_channel(name, grid);
+        let index = terrain.channels().len() - 1;
+        self.regenerate_mesh();
+        Some(index)
+    }
+
+    /// Load breaklines from the GeoJSON or DXF file at `path` in the
+    /// background, replacing any loaded before.
+    pub fn load_breaklines(&mut self, path: PathBuf) -> JobId {
+        let name = layers::name_of(&path);
+        self.jobs.spawn(format!("Loading {name}"), move |_| {
+            let lines = breaklines::load(&path)?;
+            Ok(Box::new(move |renderer: &mut Renderer| {
+                renderer.set_breaklines(lines);
+            }) as JobDone)
+        })
+    }
+
+    /// Keep edges along `lines` in the TIN strategy's triangulation.
+    pub fn set_breaklines(&mut self, lines: Vec<Breakline>) {
+        log::info!("Constraining the TIN by {} breaklines", lines.len());
+        self.breaklines = lines;
+        if self.settings.strategy == RenderStrategy::Tin {
+            self.regenerate_mesh();
+        }
+    }
+
+    /// Load the file that last failed to load again, the way it was loaded
+    /// before, e.g. once it's fixed. Returns the load's job, if a load had
+    /// failed.
+    pub fn retry_load(&mut self) -> Option<JobId> {
+        let failure = self.load_failure.take()?;
+        let (path, options) = (failure.path, failure.options);
+        Some(match failure.target {
+            LoadTarget::Main => self.load_main_terrain(path, options),
+            LoadTarget::Layer => self.open_terrain(path, options),
+            LoadTarget::Reference => self.load_reference(path, options),
+            LoadTarget::Channel(name) => self.load_channel(name, path, options),
+            LoadTarget::Uncertainty => self.load_uncertainty(path, options),
+            LoadTarget::VectorField(paths) => self.load_vector_field(paths, options),
+        })
+    }
+
+    /// Remove layer `index`.
+    fn remove_terrain(&mut self, index: usize) {
+        if index < self.layers.len() {
+            self.layers.remove(index);
+            self.settings.layers.remove(index);
+            if self.settings.stitch.enabled {
+                self.regenerate_mesh();
+            }
+        }
+    }
+
+    /// Make layer `index` the main terrain, and the main terrain a layer in
+    /// its place. The view stays put.
+    pub fn make_main(&mut self, index: usize) {
+        if index >= self.layers.len() {
+            return;
+        }
+        let layer = self.layers.remove(index);
+        let config = self.settings.layers.remove(index);
+        self.original = None;
+        if let Some(main) = self.terrain_data.take() {
+            let name = std::mem::replace(&mut self.terrain_name, config.name);
+            self.layers.insert(index, Layer::new(main));
+            self.settings.layers.insert(index, LayerConfig::new(name));
+        } else {
+            self.terrain_name = config.name;
+        }
+
+        // Render space moves with the main terrain's center
+        let previous_origin = self.render_origin;
+        self.upload_terrain(&layer.terrain, self.settings.height_scale);
+        let shift = (previous_origin - self.render_origin).as_vec3();
+        self.camera.target += shift;
+        self.view_camera.target += shift;
+        self.upload_uncertainty_map();
+        self.upload_whiskers();
+    }
+
+    /// Crop the main terrain to `columns` and `rows` of its grid, clipped
+    /// to it, and frame what's left. The full grid is kept for
+    /// [`Self::restore_original`].
+    pub fn crop(&mut self, columns: Range<usize>, rows: Range<usize>) {
+        let Some(terrain) = &self.terrain_data else {
+            return;
+        };
+        let cropped = terrain.crop(columns, rows);
+        if cropped.width < 2 || cropped.height < 2 {
+            log::warn!("Crop needs at least 2x2 samples");
+            return;
+        }
+        if self.original.is_none() {
+            self.original = self.terrain_data.clone();
+        }
+        self.replace_terrain(&cropped);
+        self.frame_scene();
+    }
+
+    /// Smooth or downsample the main terrain with `processing`, keeping
+    /// the view. The terrain as loaded is kept for
+    /// [`Self::restore_original`].
+    pub fn process(&mut self, processing: Processing) {
+        let Some(terrain) = &self.terrain_data else {
+            return;
+        };
+        let processed = processing.apply(terrain);
+        if processed.width < 2 || processed.height < 2 {
+            log::warn!("Downsampling needs at least 2x2 samples left");
+            return;
+        }
+        if self.original.is_none() {
+            self.original = self.terrain_data.clone();
+        }
+        self.replace_terrain(&processed);
+    }
+
+    /// Go back to the main terrain as loaded, undoing crops, smoothing and
+    /// downsampling, and frame it.
+    pub fn restore_original(&mut self) {
+        if let Some(terrain) = self.original.take() {
+            self.replace_terrain(&terrain);
+            self.frame_scene();
+        }
+    }
+
+    /// Replace the main terrain with another grid of it, e.g. a window
+    /// of it or a smoothed copy.
+    fn replace_terrain(&mut self, terrain: &crate::terrain::TerrainData) {
+        self.upload_terrain(terrain, self.settings.height_scale);
+        self.upload_uncertainty_map();
+        self.upload_whiskers();
+    }
+
+    /// Draw `field` over the terrain, with the overlay enabled.
+    pub fn set_vector_field(&mut self, field: VectorField) {
+        self.vector_field = Some(field);
+        self.settings.vectors.enabled = true;
+        self.upload_vectors();
+    }
+
+    /// Load point features from the CSV or GeoJSON file at `path` in the
+    /// background, replacing any loaded before.
+    pub fn load_points(&mut self, path: PathBuf) -> JobId {
+        let name = layers::name_of(&path);
+        self.jobs.spawn(format!("Loading {name}"), move |_| {
+            let set = points::load(&path)?;
+            Ok(Box::new(move |renderer: &mut Renderer| {
+                renderer.set_points(Some(set));
+            }) as JobDone)
+        })
+    }
+
+    /// Draw `set` over the terrain, with the markers enabled, or remove
+    /// the points with `None`.
+    pub fn set_points(&mut self, set: Option<PointSet>) {
+        if let Some(set) = &set {
+            log::info!("Showing {} points", set.points.len());
+        }
+        let config = &mut self.settings.points;
+        config.enabled = set.is_some();
+        config.color_by = None;
+        config.size_by = None;
+        self.points = set;
+        self.upload_points();
+    }
+
+    /// Drape `image` over the terrain, shown in the solid modes, dropping
+    /// the control points aligning the last one.
+    pub fn set_drape(&mut self, image: &DrapeImage) {
+        let srgb = self.config.format.is_srgb();
+        self.drape_texture = DrapeTexture::new(&self.device, &self.queue, image, srgb);
+        self.has_drape = true;
+        self.drape_preview = Some(image.clone().fit(tools::DRAPE_PREVIEW_SIZE));
+        self.ui.drape_texture = None;
+        self.drape_alignment = DrapeAlignment::default();
+        self.grabbed_control_point = None;
+        self.rebuild_solid_bind_groups();
+        self.upload_tool_overlay();
+    }
+
+    /// Load the PNG or JPEG image at `path` in the background and drape
+    /// it over the terrain.
+    pub fn load_drape(&mut self, path: PathBuf) -> JobId {
+        let name = layers::name_of(&path);
+        self.jobs.spawn(format!("Loading {name}"), move |_| {
+            let image = DrapeImage::load(&path)?;
+            Ok(Box::new(move |renderer: &mut Renderer| {
+                renderer.set_drape(&image);
+            }) as JobDone)
+        })
+    }
+
+    /// Light the scene with the environment `map`, reflect it off the solid
+    /// surface and show it behind the terrain.
+    pub fn set_environment(&mut self, map: &EnvironmentMap) {
+        self.environment_texture = EnvironmentTexture::new(&self.device, &self.queue, map);
+        self.has_environment = true;
+        self.sky.set_environment(
+            &self.device,
+            &self.scene_uniform_buffer,
+            &self.environment_texture,
+        );
+        self.rebuild_solid_bind_groups();
+    }
+
+    /// Load the Radiance HDR panorama at `path` in the background and
+    /// light the scene with it.
+    pub fn load_environment(&mut self, path: PathBuf) -> JobId {
+        let name = layers::name_of(&path);
+        self.jobs.spawn(format!("Loading {name}"), move |_| {
+            let map = EnvironmentMap::load(&path)?;
+            Ok(Box::new(move |renderer: &mut Renderer| {
+                renderer.set_environment(&map);
+            }) as JobDone)
+        })
+    }
+
+    /// Show channel `index` of the terrain as height uncertainty.
+    pub fn set_uncertainty(&mut self, index: usize) {
+        self.settings.uncertainty.channel = Some(index);
+        self.upload_uncertainty_map();
+        self.upload_whiskers();
+    }
+
+    /// Evaluate the map algebra `statement` (see [`algebra`]), store its
+    /// result as a channel of the terrain and color by it.
+    ///
+    /// [`algebra`]: crate::terrain::algebra
+    pub fn run_algebra(&mut self, statement: &str) -> anyhow::Result<()> {
+        let statement = Statement::parse(statement)?;
+        let Some(terrain) = &mut self.terrain_data else {
+            anyhow::bail!("No terrain loaded");
+        };
+        let index = statement.run(terrain)?;
+        self.settings.color_mode = ColorMode::Channel(index);
+        // A replaced channel keeps its index, so rebuild even when it was shown
         self.regenerate_mesh();
+        if self.settings.uncertainty.channel == Some(index) {
+            self.upload_uncertainty_map();
+            self.upload_whiskers();
+        }
+        Ok(())
     }
 
     /// Regenerate mesh from stored terrain data with current settings.
     fn regenerate_mesh(&mut self) {
-        if let Some(ref terrain) = self.terrain_data {
-            let mesh = if self.color_scheme == ColorScheme::Custom {
-                TerrainMesh::from_terrain_with_gradient(
+        self.stitch_seams();
+        self.drop_detail();
+        let Some(ref terrain) = self.terrain_data else {
+            return;
+        };
+
+        if self.settings.strategy == RenderStrategy::Clipmap && self.clipmap.is_none() {
+            match Clipmap::new(
+                &self.device,
+                &self.queue,
+                &self.clipmap_bind_group_layout,
+                (&self.params_buffer, &self.scene_uniform_buffer),
+                terrain,
+            ) {
+                Ok(clipmap) => self.clipmap = Some(clipmap),
+                Err(err) => {
+                    log::warn!("Clipmap unavailable: {}", err);
+                    self.settings.strategy = RenderStrategy::Mesh;
+                }
+            }
+        }
+
+        // Globe positions and the clipmap's bounds can't be rescaled
+        // afterwards; other meshes keep raw heights
+        self.mesh_height_scale = if self.bakes_height_scale() {
+            self.settings.height_scale
+        } else {
+            1.0
+        };
+        match self.settings.strategy {
+            RenderStrategy::Mesh | RenderStrategy::Tin => {
+                self.clipmap = None;
+                let options = self.main_mesh_options(self.render_origin);
+                let loaded = (self.loaded_mesh.take()).filter(|(built, _)| {
+                    *built == options && self.stitched_terrain.is_none() && self.mesh_from_heights()
+                });
+                let staged = loaded.is_some();
+                let mesh = match loaded {
+                    Some((_, mesh)) => mesh,
+                    None => self.build_mesh(&options),
+                };
+                let mesh = self.adapt_mesh(mesh, &options);
+                self.upload_mesh_buffers(&mesh, staged);
+                self.upload_ridgelines(&mesh);
+            }
+            RenderStrategy::Clipmap => {
+                let bounds = clipmap::terrain_bounds(
                     terrain,
-                    self.height_scale,
-                    crate::terrain::mesh::ShadingMode::Smooth,
-                    &self.gradient,
-                )
+                    self.render_origin,
+                    self.settings.height_scale,
+                );
+                if let Some(clipmap) = &self.clipmap {
+                    clipmap.set_colors(
+                        &self.queue,
+                        self.settings.color_scheme,
+                        &self.settings.gradient,
+                        self.settings.gamma_correct,
+                    );
+                }
+                // The clipmap replaces the mesh, which may be too large to build
+                self.upload_mesh_buffers(&TerrainMesh::default(), false);
+                self.upload_ridgelines(&TerrainMesh::default());
+                self.scene_box = Some(bounds);
+            }
+        }
+
+        self.prev_color_mode = self.settings.color_mode;
+        self.prev_index_radius = self.settings.index_radius;
+        self.prev_color_scheme = self.settings.color_scheme;
+        self.prev_gradient = self.settings.gradient;
+        self.prev_slope_bands = self.settings.slope_bands;
+        self.prev_gamma_correct = self.settings.gamma_correct;
+        self.prev_file_colors = self.settings.file_colors;
+        self.colors_painted = false;
+        self.prev_height_scale = self.settings.height_scale;
+        self.prev_edge_density = self.settings.edge_density;
+        self.prev_tin_tolerance = self.settings.tin_tolerance;
+        self.prev_lod = self.settings.lod.enabled;
+        self.prev_ridgelines = self.settings.ridgelines;
+        self.prev_ambient_occlusion = self.settings.ambient_occlusion;
+        self.prev_relief = self.settings.relief;
+        self.prev_stitch = self.settings.stitch;
+        self.prev_denoise = self.settings.denoise;
+        self.prev_irradiance = self.settings.sun.irradiance_key();
+        self.prev_earth_model = self.settings.earth_model;
+        self.prev_strategy = self.settings.strategy;
+        self.upload_tool_overlay();
+        self.upload_vectors();
+        self.upload_points();
+        self.upload_whiskers();
+        self.upload_layers(true);
+        if let Some(corners) = self.captured_frustum {
+            self.set_frustum(corners);
+        }
+    }
+
+    /// Stitch the shared borders of the main terrain and the layers, into
+    /// copies their meshes are built from, when enabled and any samples
+    /// change.
+    fn stitch_seams(&mut self) {
+        self.stitched_terrain = None;
+        self.stitched_samples = 0;
+        for layer in &mut self.layers {
+            layer.set_stitched(None);
+        }
+        let config = self.settings.stitch;
+        let Some(main) = self.terrain_data.as_ref() else {
+            return;
+        };
+        if !config.enabled || self.layers.is_empty() {
+            return;
+        }
+        let mut tiles: Vec<_> = std::iter::once(main)
+            .chain(self.layers.iter().map(|layer| &layer.terrain))
+            .cloned()
+            .collect();
+        self.stitched_samples = stitch::stitch(&mut tiles, config.tolerance as f64);
+        if self.stitched_samples > 0 {
+            let mut tiles = tiles.into_iter();
+            self.stitched_terrain = tiles.next();
+            for (layer, tile) in self.layers.iter_mut().zip(tiles) {
+                layer.set_stitched(Some(tile));
+            }
+        }
+    }
+
+    /// Rebuild the meshes of all layers, or of those moved since they were
+    /// built, colored like the main terrain.
+    fn upload_layers(&mut self, all: bool) {
+        let options = MeshOptions {
+            origin: Some(self.render_origin),
+            globe: false,
+            lod_levels: 0,
+            ..self.mesh_options()
+        };
+        for (layer, config) in self.layers.iter_mut().zip(&self.settings.layers) {
+            if all || layer.moved(config) {
+                layer.upload(&self.device, &options, config);
+            }
+        }
+    }
+
+    /// Options the main terrain's mesh is built with, placed around
+    /// `origin`.
+    fn main_mesh_options(&self, origin: DVec3) -> MeshOptions {
+        MeshOptions {
+            // As for `mesh_height_scale`
+            height_scale: if self.bakes_height_scale() {
+                self.settings.height_scale
             } else {
-                TerrainMesh::from_terrain_with_options(
-                    terrain,
-                    self.height_scale,
-                    crate::terrain::mesh::ShadingMode::Smooth,
-                    self.color_scheme,
-                )
-            };
-            self.upload_mesh_buffers(&mesh);
-            self.prev_color_scheme = self.color_scheme;
-            self.prev_gradient = self.gradient;
-            self.prev_height_scale = self.height_scale;
+                1.0
+            },
+            origin: Some(origin),
+            line_topology: LineTopology::Strip,
+            ..self.mesh_options()
         }
     }
 
-    /// Upload terrain mesh to GPU buffers.
-    ///
-    /// Creates vertex and index buffers for both wireframe and solid rendering.
-    fn upload_mesh_buffers(&mut self, mesh: &TerrainMesh) {
-        if mesh.vertices.is_empty() {
-            self.vertex_buffer = None;
-            self.wireframe_index_buffer = None;
-            self.triangle_index_buffer = None;
-            self.num_wireframe_indices = 0;
-            self.num_triangle_indices = 0;
+    /// Whether the main terrain's mesh is built from its heights alone,
+    /// not denoised, measured against a reference or shaded by bakes.
+    fn mesh_from_heights(&self) -> bool {
+        let settings = &self.settings;
+        !settings.relief.enabled
+            && !settings.denoise.active()
+            && !settings.ambient_occlusion
+            && settings.sun.irradiance_key().is_none()
+    }
+
+    /// Mesh options for the current color, globe and edge settings.
+    fn mesh_options(&self) -> MeshOptions {
+        MeshOptions {
+            color_mode: self.settings.color_mode,
+            index_radius: self.settings.index_radius,
+            color_scheme: self.settings.color_scheme,
+            gradient: self.settings.gradient,
+            slope_bands: self.settings.slope_bands,
+            globe: self.settings.earth_model == EarthModel::Globe,
+            edge_density: self.settings.edge_density,
+            linear_colors: self.settings.gamma_correct,
+            file_colors: self.settings.file_colors,
+            lod_levels: if self.settings.lod.enabled
+                && self.settings.strategy == RenderStrategy::Mesh
+            {
+                LOD_LEVELS
+            } else {
+                0
+            },
+            ..MeshOptions::default()
+        }
+    }
+
+    /// Build the triangle mesh of the terrain with `options`, denoised and
+    /// recolored by the relief, irradiance and ambient occlusion settings.
+    /// Empty without a terrain.
+    fn build_mesh(&mut self, options: &MeshOptions) -> TerrainMesh {
+        let irradiance = self.settings.sun.irradiance_key();
+        if let Some(key) = irradiance {
+            if self.irradiation.as_ref().is_none_or(|i| i.key != key) {
+                self.bake_irradiation(key);
+            }
+        }
+        let Some(terrain) = self
+            .stitched_terrain
+            .as_ref()
+            .or(self.terrain_data.as_ref())
+        else {
+            return TerrainMesh::default();
+        };
+        let denoise = self.settings.denoise;
+        let denoised = denoise
+            .active()
+            .then(|| filters::bilateral(terrain, denoise.radius as usize, denoise.range as f64));
+        let terrain = denoised.as_ref().unwrap_or(terrain);
+        let relief = self.settings.relief;
+        let relative = relief
+            .enabled
+            .then(|| relief::relative_terrain(terrain, &relief, self.reference_data.as_ref()))
+            .flatten();
+        // Flattened terrain takes both shape and colors from the
+        // relative heights
+        let terrain = match &relative {
+            Some(relative) if relief.flatten => relative,
+            _ => terrain,
+        };
+        let mut mesh = TerrainMesh::build(terrain, options);
+        if let Some(relative) = relative.as_ref().filter(|_| !relief.flatten) {
+            mesh.recolor(relative, options);
+        }
+        if let Some(irradiation) = self.irradiation.as_ref().filter(|_| irradiance.is_some()) {
+            let mut colors = solar::colors(&irradiation.energy, irradiation.range);
+            if options.linear_colors {
+                for color in &mut colors {
+                    *color = color.map(srgb_to_linear);
+                }
+            }
+            mesh.paint(&colors);
+        }
+        if relief != self.prev_relief
+            || self.settings.stitch != self.prev_stitch
+            || denoise != self.prev_denoise
+        {
+            // Occlusion follows the shape, which flattening, stitching and
+            // denoising change
+            self.ao_factors = None;
+        }
+        if self.settings.ambient_occlusion {
+            // Bake once per height scale
+            let height_scale = self.settings.height_scale;
+            if self
+                .ao_factors
+                .as_ref()
+                .is_none_or(|(scale, _)| *scale != height_scale)
+            {
+                self.ao_factors = Some((height_scale, ao::bake(terrain, height_scale as f64)));
+            }
+            if let Some((_, factors)) = &self.ao_factors {
+                mesh.apply_occlusion(factors);
+            }
+        }
+        mesh
+    }
+
+    /// `mesh`, built with `options`, triangulated adaptively with the TIN
+    /// strategy, within the tolerance at the mesh's height scale.
+    fn adapt_mesh(&self, mesh: TerrainMesh, options: &MeshOptions) -> TerrainMesh {
+        let Some(terrain) = &self.terrain_data else {
+            return mesh;
+        };
+        match self.settings.strategy {
+            RenderStrategy::Tin => tin::triangulate(
+                &mesh,
+                terrain.width,
+                self.settings.tin_tolerance * options.height_scale,
+                &breaklines::samples(terrain, &self.breaklines),
+            ),
+            _ => mesh,
+        }
+    }
+
+    /// Compute the irradiance overlay for `key`'s latitude and day of year
+    /// in the background, unless already underway. The mesh is rebuilt
+    /// with it once done, and shows the last overlay until then.
+    fn bake_irradiation(&mut self, key @ (latitude, day_of_year): (f32, u32)) {
+        if self.irradiance_job.is_some_and(|(_, baking)| baking == key) {
+            return;
+        }
+        if let Some((id, _)) = self.irradiance_job.take() {
+            self.jobs.cancel(id);
+        }
+        // Computed on the terrain's own shape, even when flattened
+        let Some(terrain) = self.terrain_data.clone() else {
+            return;
+        };
+        let id = self.jobs.spawn("Baking irradiance", move |progress| {
+            let energy = solar::daily_irradiation(
+                &terrain,
+                latitude as f64,
+                day_of_year as f64,
+                IRRADIANCE_STEP,
+                |done| {
+                    progress.set(done as f32);
+                    !progress.is_cancelled()
+                },
+            )
+            .ok_or(workers::Cancelled)?;
+            let range = solar::range(&energy);
+            Ok(Box::new(move |renderer: &mut Renderer| {
+                renderer.irradiance_job = None;
+                renderer.irradiation = Some(Irradiation { key, energy, range });
+                if renderer.settings.sun.irradiance_key() == Some(key) {
+                    renderer.regenerate_mesh();
+                }
+            }) as JobDone)
+        });
+        self.irradiance_job = Some((id, key));
+    }
+
+    /// Compute the terrain's elevation and slope distributions in the
+    /// background, unless already underway.
+    fn spawn_analysis(&mut self) {
+        if self.stats_job.is_some() {
+            return;
+        }
+        let Some(terrain) = self.terrain_data.clone() else {
             return;
+        };
+        let id = self
+            .jobs
+            .spawn("Computing terrain analysis", move |progress| {
+                let stats = TerrainStats::compute(&terrain);
+                progress.check()?;
+                Ok(Box::new(move |renderer: &mut Renderer| {
+                    renderer.stats_job = None;
+                    renderer.terrain_stats = Some(stats);
+                }) as JobDone)
+            });
+        self.stats_job = Some(id);
+    }
+
+    /// Write `contents` to `path` in the background, once `build` made
+    /// them, logging `what` was written.
+    fn spawn_write(
+        &mut self,
+        what: &'static str,
+        path: PathBuf,
+        build: impl FnOnce(&workers::Progress) -> anyhow::Result<Vec<u8>> + Send + 'static,
+    ) -> JobId {
+        self.jobs.spawn(writing_label(&path), move |progress| {
+            let contents = build(progress)?;
+            progress.check()?;
+            std::fs::write(&path, contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            log::info!("Wrote {what} to {}", path.display());
+            Ok(Box::new(|_: &mut Renderer| {}) as JobDone)
+        })
+    }
+
+    /// Wait for job `id` and apply its result, or return why it failed.
+    pub fn wait_for_job(&mut self, id: JobId) -> anyhow::Result<()> {
+        match self.jobs.wait_for(id) {
+            Some(job) => job.result.map(|done| done(self)),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply the results of finished jobs, logging failures.
+    fn apply_jobs(&mut self, finished: Vec<Finished<JobDone>>) {
+        for job in finished {
+            match job.result {
+                Ok(done) => done(self),
+                Err(err) if workers::is_cancelled(&err) => log::info!("{}: cancelled", job.label),
+                Err(err) => log::warn!("{}: {:#}", job.label, err),
+            }
+            // Failed or cancelled jobs may be retried
+            if self.stats_job == Some(job.id) {
+                self.stats_job = None;
+            }
+            if self.irradiance_job.is_some_and(|(id, _)| id == job.id) {
+                self.irradiance_job = None;
+            }
+            if self.detail_job.is_some_and(|(id, _)| id == job.id) {
+                self.detail_job = None;
+            }
+        }
+    }
+
+    /// Cancel the analysis and irradiance bake, whose results are only
+    /// wanted for the current terrain.
+    fn cancel_terrain_jobs(&mut self) {
+        if let Some(id) = self.stats_job.take() {
+            self.jobs.cancel(id);
+        }
+        if let Some((id, _)) = self.irradiance_job.take() {
+            self.jobs.cancel(id);
+        }
+    }
+
+    /// Keep the float grid at `path`, which the main terrain was just
+    /// loaded from with `options`, mapped to draw the terrain near the
+    /// camera at full resolution (see [`detail`]). Nothing changes if the
+    /// terrain shows the whole grid already.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`loader::LoadError`] if the grid can no longer be mapped.
+    pub fn map_main_terrain(
+        &mut self,
+        path: &std::path::Path,
+        options: loader::LoadOptions,
+    ) -> Result<(), loader::LoadError> {
+        let grid = MappedGrid::open(path)?;
+        self.detail_source = (self.terrain_data.as_ref())
+            .and_then(|terrain| MappedTerrain::new(grid, options, terrain))
+            .map(Arc::new);
+        Ok(())
+    }
+
+    /// Window of the main terrain to draw at full resolution, around the
+    /// point looked at, if the camera is close enough to see its detail.
+    fn detail_window(&self) -> Option<DetailWindow> {
+        let source = self.detail_source.as_ref()?;
+        let terrain = self.terrain_data.as_ref()?;
+        // The window only matches the overview's chunks on a flat mesh
+        // colored by height alone
+        let settings = &self.settings;
+        let plain = settings.strategy == RenderStrategy::Mesh
+            && settings.earth_model != EarthModel::Globe
+            && settings.color_mode == ColorMode::Height
+            && !(settings.file_colors && terrain.colors.is_some())
+            && self.mesh_from_heights()
+            && self.stitched_terrain.is_none();
+        if !plain {
+            return None;
+        }
+        let target = self.camera.target.as_dvec3() + self.render_origin;
+        let window = source.window_at(target.xz())?;
+        // Seen from farther than the window is wide, its detail is too
+        // small to tell from the overview
+        let distance = self.camera.position().distance(self.camera.target) as f64;
+        (distance < source.extent(&window)).then_some(window)
+    }
+
+    /// Read the window of the main terrain near the camera at full
+    /// resolution in the background when it moves, and stop drawing it
+    /// when none is wanted. The last window is drawn until the next is
+    /// read.
+    fn stream_detail(&mut self) {
+        let Some(window) = self.detail_window() else {
+            self.drop_detail();
+            return;
+        };
+        if self
+            .detail
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == window)
+            || self
+                .detail_job
+                .is_some_and(|(_, reading)| reading == window)
+        {
+            return;
+        }
+        if let Some((id, _)) = self.detail_job.take() {
+            self.jobs.cancel(id);
+        }
+        let Some(source) = self.detail_source.clone() else {
+            return;
+        };
+        let options = MeshOptions {
+            origin: Some(self.render_origin),
+            globe: false,
+            lod_levels: 0,
+            ..self.mesh_options()
+        };
+        let id = self.jobs.spawn("Reading detail", move |progress| {
+            let terrain = source.read(&window)?;
+            progress.check()?;
+            let mesh = TerrainMesh::build(&terrain, &options);
+            Ok(Box::new(move |renderer: &mut Renderer| {
+                renderer.detail_job = None;
+                renderer.detail =
+                    MeshBuffers::new(&renderer.device, &mesh).map(|buffers| (window, buffers));
+            }) as JobDone)
+        });
+        self.detail_job = Some((id, window));
+    }
+
+    /// Samples per grid cell of the full-resolution window drawn near the
+    /// camera, or `None` while none is.
+    pub fn detail_subdivisions(&self) -> Option<usize> {
+        (self.detail.as_ref()).map(|(window, _)| window.subdivisions)
+    }
+
+    /// Stop drawing and reading the full-resolution window, e.g. when the
+    /// mesh it matches is rebuilt.
+    fn drop_detail(&mut self) {
+        self.detail = None;
+        if let Some((id, _)) = self.detail_job.take() {
+            self.jobs.cancel(id);
+        }
+    }
+
+    /// Cancel the analysis and bakes still running and wait for exports to
+    /// finish writing, e.g. before quitting.
+    pub fn finish_jobs(&mut self) {
+        self.cancel_terrain_jobs();
+        for job in self.jobs.status() {
+            match self.wait_for_job(job.id) {
+                Err(err) if !workers::is_cancelled(&err) => log::warn!("{}: {:#}", job.label, err),
+                _ => {}
+            }
+        }
+    }
+
+    /// Write the terrain as currently colored, with the current height
+    /// scale, to a mesh file in the format its extension names, simplified
+    /// to at most `max_triangles`. The mesh is simplified, encoded and
+    /// written by a background job.
+    pub fn export_mesh(
+        &mut self,
+        path: PathBuf,
+        max_triangles: Option<usize>,
+    ) -> anyhow::Result<JobId> {
+        let options = MeshOptions {
+            height_scale: self.settings.height_scale,
+            // Linear colors, which the exporters convert as their formats need
+            linear_colors: true,
+            lod_levels: 0,
+            ..self.mesh_options()
+        };
+        let mesh = self.build_mesh(&options);
+        if mesh.vertices.is_empty() {
+            anyhow::bail!("No terrain to export");
+        }
+        let mesh = self.adapt_mesh(mesh, &options);
+        let adaptive = self.settings.strategy == RenderStrategy::Tin;
+        Ok(self.jobs.spawn(writing_label(&path), move |_| {
+            // Without the grid vertices the triangulation left out
+            let mesh = match adaptive {
+                true => tin::compact(&mesh).0,
+                false => mesh,
+            };
+            let mesh = match max_triangles {
+                Some(max) => simplify::simplify(&mesh, max).mesh,
+                None => mesh,
+            };
+            export::write(&mesh, &path, None)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            log::info!("Wrote mesh to {}", path.display());
+            Ok(Box::new(|_: &mut Renderer| {}) as JobDone)
+        }))
+    }
+
+    /// Write each sample's color as currently shown, with relief,
+    /// irradiance and ambient occlusion but not the draped image or
+    /// uncertainty drawn over it, as a georeferenced image in the format
+    /// its extension names. The file is encoded and written by a
+    /// background job.
+    pub fn export_color_map(&mut self, path: PathBuf) -> anyhow::Result<JobId> {
+        let options = MeshOptions {
+            // sRGB colors, as the image stores them
+            linear_colors: false,
+            lod_levels: 0,
+            ..self.mesh_options()
+        };
+        let mesh = self.build_mesh(&options);
+        let Some(terrain) = self
+            .terrain_data
+            .clone()
+            .filter(|_| !mesh.vertices.is_empty())
+        else {
+            anyhow::bail!("No terrain to export");
+        };
+        Ok(self.jobs.spawn(writing_label(&path), move |_| {
+            let colors: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.color).collect();
+            color_map::write(&ColorMap::new(&terrain, &colors), &path)?;
+            log::info!("Wrote color map to {}", path.display());
+            Ok(Box::new(|_: &mut Renderer| {}) as JobDone)
+        }))
+    }
+
+    /// Whether height scale changes need a rebuild, because the current
+    /// geometry bakes the scale in.
+    fn bakes_height_scale(&self) -> bool {
+        self.settings.earth_model == EarthModel::Globe
+            || self.settings.strategy == RenderStrategy::Clipmap
+    }
+
+    /// Transform from the uploaded mesh's heights to the current height
+    /// scale.
+    fn height_transform(&self) -> HeightTransform {
+        HeightTransform::between(
+            self.mesh_height_scale,
+            self.settings.height_scale,
+            self.render_origin.y,
+        )
+    }
+
+    /// Refit the scene bounds to the mesh at the current height scale.
+    fn fit_scene_bounds(&mut self) {
+        let Some((min, max)) = self.mesh_box else {
+            return;
+        };
+        let height = self.height_transform();
+        let (low, high) = (height.apply(min.y), height.apply(max.y));
+        let min = Vec3::new(min.x, low.min(high), min.z);
+        let max = Vec3::new(max.x, low.max(high), max.z);
+        self.scene_box = Some((min, max));
+    }
+
+    /// Bounding sphere of the drawn terrain as `(center, radius)`.
+    fn scene_bounds(&self) -> Option<(Vec3, f32)> {
+        self.scene_box
+            .map(|(min, max)| ((min + max) / 2.0, (max - min).length() / 2.0))
+    }
+
+    /// Bounding sphere of the drawn terrain and the visible layers as
+    /// `(center, radius)`.
+    fn view_bounds(&self) -> Option<(Vec3, f32)> {
+        let height =
+            HeightTransform::between(1.0, self.settings.height_scale, self.render_origin.y);
+        let layers = self
+            .layers
+            .iter()
+            .zip(&self.settings.layers)
+            .filter(|(_, config)| config.visible)
+            .filter_map(|(layer, _)| layer.bounds())
+            .map(|(min, max)| {
+                let (low, high) = (height.apply(min.y), height.apply(max.y));
+                (
+                    Vec3::new(min.x, low.min(high), min.z),
+                    Vec3::new(max.x, low.max(high), max.z),
+                )
+            });
+        self.scene_box
+            .into_iter()
+            .chain(layers)
+            .reduce(|(min, max), (low, high)| (min.min(low), max.max(high)))
+            .map(|(min, max)| ((min + max) / 2.0, (max - min).length() / 2.0))
+    }
+
+    /// Follow a height scale change on the GPU, without rebuilding the mesh.
+    fn rescale_heights(&mut self) {
+        self.fit_scene_bounds();
+        if let Some(corners) = self.captured_frustum {
+            self.set_frustum(corners);
+        }
+        self.prev_height_scale = self.settings.height_scale;
+    }
+
+    /// Use a WGSL `user_color` function from `path` to color the solid surface.
+    ///
+    /// The snippet is watched and reloaded whenever the file changes.
+    /// Passing `None` restores the built-in color schemes.
+    pub fn set_color_snippet(&mut self, path: Option<PathBuf>) {
+        if let Some(path) = &path {
+            match &mut self.shader_watcher {
+                Some(watcher) => watcher.watch(path),
+                None => self.shader_watcher = Some(ShaderWatcher::new(path)),
+            }
+        }
+        self.color_snippet = path;
+        self.reload_shaders();
+    }
+
+    /// Rebuild both pipelines from the current shader sources.
+    ///
+    /// Compilation and validation errors are captured with an error scope
+    /// instead of panicking. On failure the previous pipelines are kept and
+    /// the error is stored in [`Self::shader_error`] for the UI to display.
+    pub fn reload_shaders(&mut self) {
+        let sources = match shaders::load(self.color_snippet.as_deref()) {
+            Ok(sources) => sources,
+            Err(err) => {
+                log::warn!("Shader reload failed: {}", err);
+                self.shader_error = Some(err);
+                return;
+            }
+        };
+
+        let samples = msaa::fit_count(self.settings.msaa, self.msaa.counts());
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipelines = self.build_pipelines(&sources, samples, self.settings.depth_mode);
+        let previous_params = self.settings.shader_params.clone();
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(err) => {
+                log::warn!("Shader reload failed: {}", err);
+                self.shader_error = Some(err.to_string());
+            }
+            None => {
+                log::info!("Shaders reloaded");
+                self.set_pipelines(pipelines);
+                if samples != self.msaa.samples() {
+                    let (width, height) = (self.config.width, self.config.height);
+                    self.msaa.set_samples(&self.device, samples, width, height);
+                }
+                if samples != self.settings.msaa {
+                    log::warn!(
+                        "{}x MSAA is unsupported, using {}x",
+                        self.settings.msaa,
+                        samples
+                    );
+                    self.settings.msaa = samples;
+                    self.prev_msaa = samples;
+                }
+                self.settings.shader_params = sources.params;
+                self.settings
+                    .shader_params
+                    .keep_values_from(&previous_params);
+                self.shader_error = None;
+            }
+        }
+    }
+
+    /// Build the pipelines for every other sample count and depth
+    /// convention ahead of use, one variant a frame behind a progress
+    /// splash, so switching to them later doesn't stall.
+    pub fn warm_pipelines(&mut self) {
+        let current = (self.msaa.samples(), self.settings.depth_mode);
+        self.warmup = Warmup::new(self.msaa.counts(), current);
+    }
+
+    /// Whether pipeline variants are still being built ahead of use.
+    pub fn is_warming_up(&self) -> bool {
+        !self.warmup.is_done()
+    }
+
+    /// Build the warm-up's next variant, if any are left, and save the
+    /// pipeline cache once all are. A failure ends the warm-up; switching
+    /// then builds the pipelines as needed, reporting errors as usual.
+    fn step_warmup(&mut self) {
+        let Some((samples, depth_mode)) = self.warmup.next_variant() else {
+            return;
+        };
+        let Ok(sources) = shaders::load(self.color_snippet.as_deref()) else {
+            self.warmup = Warmup::default();
+            return;
+        };
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        drop(self.build_pipelines(&sources, samples, depth_mode));
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            log::warn!("Building pipelines ahead failed: {}", err);
+            self.warmup = Warmup::default();
+        }
+        if self.warmup.is_done() {
+            log::info!("Pipelines built ahead of use");
+            self.save_pipeline_cache();
+        }
+    }
+
+    /// Write the pipeline cache to disk, if it's kept there.
+    pub fn save_pipeline_cache(&self) {
+        if let Err(err) = self.pipeline_cache.save() {
+            log::warn!("Failed to write the pipeline cache: {:#}", err);
+        }
+    }
+
+    /// Build every pipeline from `sources` for `samples` per pixel and
+    /// `depth_mode`, through the pipeline cache. Errors are reported to the
+    /// device's error scopes.
+    fn build_pipelines(
+        &self,
+        sources: &ShaderSources,
+        samples: u32,
+        depth_mode: DepthMode,
+    ) -> Pipelines {
+        let cache = self.pipeline_cache.get();
+        let wireframe_pipeline = create_wireframe_pipeline(
+            &self.device,
+            cache,
+            &self.wireframe_pipeline_layout,
+            self.config.format,
+            depth_mode,
+            samples,
+            &sources.wireframe,
+            None,
+        );
+        let strip_pipelines = StripPipelines::new(
+            &self.device,
+            cache,
+            &self.wireframe_pipeline_layout,
+            self.config.format,
+            depth_mode,
+            samples,
+            &sources.wireframe,
+        );
+        let solid_pipeline = create_solid_pipeline(
+            &self.device,
+            cache,
+            &self.solid_pipeline_layout,
+            self.config.format,
+            depth_mode,
+            samples,
+            &sources.solid,
+        );
+        let occlusion_pipeline = occlusion::create_box_pipeline(
+            &self.device,
+            cache,
+            &self.wireframe_pipeline_layout,
+            self.config.format,
+            depth_mode,
+            samples,
+            &sources.wireframe,
+        );
+        let cull_pipeline = self.cull_pipeline.as_ref().map(|_| {
+            indirect::create_pipeline(
+                &self.device,
+                cache,
+                &self.cull_pipeline_layout,
+                &sources.cull,
+            )
+        });
+        let shadow_pipeline = self
+            .shadow_map
+            .build_pipeline(&self.device, cache, &sources.shadow);
+        let oit_layer_pipeline = oit::create_layer_pipeline(
+            &self.device,
+            cache,
+            &self.solid_pipeline_layout,
+            depth_mode,
+            &sources.solid,
+        );
+        let oit_composite_pipeline =
+            self.oit
+                .build_pipeline(&self.device, cache, self.config.format, &sources.oit);
+        let blend_composite_pipeline =
+            self.blend
+                .build_pipeline(&self.device, cache, self.config.format, &sources.blend);
+        let bloom_pipelines =
+            self.bloom
+                .build_pipelines(&self.device, cache, self.config.format, &sources.bloom);
+        let colorblind_pipeline = self.colorblind.build_pipeline(
+            &self.device,
+            cache,
+            self.config.format,
+            &sources.colorblind,
+        );
+        let overview_pipeline = self.overview.build_pipeline(
+            &self.device,
+            cache,
+            self.config.format,
+            &sources.overview,
+        );
+        let clipmap_pipeline = clipmap::create_pipeline(
+            &self.device,
+            cache,
+            &self.clipmap_pipeline_layout,
+            self.config.format,
+            depth_mode,
+            samples,
+            &sources.clipmap,
+        );
+        let resolve_pipeline =
+            self.msaa
+                .build_pipeline(&self.device, cache, &sources.depth_resolve);
+        let sky_pipeline = self.sky.build_pipeline(
+            &self.device,
+            cache,
+            self.config.format,
+            samples,
+            &sources.sky,
+        );
+        let ground_grid_pipeline = self.ground_grid.build_pipeline(
+            &self.device,
+            cache,
+            self.config.format,
+            depth_mode,
+            samples,
+            &sources.ground_grid,
+        );
+        let overview_pipelines = (samples > 1).then(|| {
+            let solid = create_solid_pipeline(
+                &self.device,
+                cache,
+                &self.solid_pipeline_layout,
+                self.config.format,
+                depth_mode,
+                1,
+                &sources.solid,
+            );
+            let wireframe = create_wireframe_pipeline(
+                &self.device,
+                cache,
+                &self.wireframe_pipeline_layout,
+                self.config.format,
+                depth_mode,
+                1,
+                &sources.wireframe,
+                None,
+            );
+            let strips = StripPipelines::new(
+                &self.device,
+                cache,
+                &self.wireframe_pipeline_layout,
+                self.config.format,
+                depth_mode,
+                1,
+                &sources.wireframe,
+            );
+            (solid, wireframe, strips)
+        });
+
+        Pipelines {
+            wireframe: wireframe_pipeline,
+            strips: strip_pipelines,
+            solid: solid_pipeline,
+            occlusion: occlusion_pipeline,
+            cull: cull_pipeline,
+            shadow: shadow_pipeline,
+            oit_layer: oit_layer_pipeline,
+            oit_composite: oit_composite_pipeline,
+            blend_composite: blend_composite_pipeline,
+            bloom: bloom_pipelines,
+            colorblind: colorblind_pipeline,
+            overview: overview_pipeline,
+            clipmap: clipmap_pipeline,
+            resolve: resolve_pipeline,
+            sky: sky_pipeline,
+            ground_grid: ground_grid_pipeline,
+            overview_sets: overview_pipelines,
+        }
+    }
+
+    /// Draw with `pipelines` from now on.
+    fn set_pipelines(&mut self, pipelines: Pipelines) {
+        self.wireframe_pipeline = pipelines.wireframe;
+        self.strip_pipelines = pipelines.strips;
+        self.solid_pipeline = pipelines.solid;
+        self.clipmap_pipeline = pipelines.clipmap;
+        self.occlusion_pipeline = pipelines.occlusion;
+        self.cull_pipeline = pipelines.cull;
+        self.shadow_map.set_pipeline(pipelines.shadow);
+        self.oit_layer_pipeline = pipelines.oit_layer;
+        self.oit.set_pipeline(pipelines.oit_composite);
+        self.blend.set_pipeline(pipelines.blend_composite);
+        self.bloom.set_pipelines(pipelines.bloom);
+        self.colorblind.set_pipeline(pipelines.colorblind);
+        self.overview.set_pipeline(pipelines.overview);
+        self.msaa.set_pipeline(pipelines.resolve);
+        self.sky.set_pipeline(pipelines.sky);
+        self.ground_grid.set_pipeline(pipelines.ground_grid);
+        self.overview_pipelines = pipelines.overview_sets;
+    }
+
+    /// Write the hours each grid vertex spends in terrain shadow over the
+    /// sun study's day as an ESRI ASCII grid.
+    fn export_shadow_hours(&mut self, path: PathBuf) {
+        let Some(terrain) = self.terrain_data.clone() else {
+            return;
+        };
+        let (latitude, day_of_year) = (self.settings.sun.latitude, self.settings.sun.day_of_year);
+        self.spawn_write("shadow hours", path, move |progress| {
+            let hours = sun::shadow_hours(
+                &terrain,
+                latitude as f64,
+                day_of_year as f64,
+                SHADOW_HOURS_STEP,
+                |done| {
+                    progress.set(done as f32);
+                    !progress.is_cancelled()
+                },
+            )
+            .ok_or(workers::Cancelled)?;
+            Ok(sun::to_ascii_grid(&terrain, &hours).into_bytes())
+        });
+    }
+
+    /// Render a turntable of the current view and write it as an animated
+    /// GIF or WebP, picked by the extension of `path`.
+    fn export_turntable(&mut self, path: &std::path::Path) {
+        let Some(format) = TurntableFormat::from_path(path) else {
+            log::warn!(
+                "Unknown turntable format for {}, expected .gif or .webp",
+                path.display()
+            );
+            return;
+        };
+        let config = self.settings.turntable;
+        let Some(frames) = self.capture_turntable(&config) else {
+            log::warn!("Turntable capture failed");
+            return;
+        };
+        let result = format
+            .encode(
+                &frames,
+                (config.width, config.height),
+                config.frame_delay_ms(),
+            )
+            .and_then(|data| Ok(std::fs::write(path, data)?));
+        match result {
+            Ok(()) => log::info!("Wrote turntable to {}", path.display()),
+            Err(err) => log::warn!("Failed to write {}: {}", path.display(), err),
+        }
+    }
+
+    /// Draw `config.frames` frames of one camera turn around its target into
+    /// an offscreen texture of the configured size, without the UI, and read
+    /// them back. The camera is restored afterwards.
+    ///
+    /// Returns `None` if a frame fails to render or read back.
+    pub fn capture_turntable(&mut self, config: &TurntableConfig) -> Option<Vec<Vec<[u8; 4]>>> {
+        let camera = self.camera.clone();
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+        let frames = self.capture(size, |renderer| {
+            (0..config.frames)
+                .map(|i| {
+                    renderer.camera.azimuth = config.azimuth(camera.azimuth, i);
+                    renderer.render(None).ok()?;
+                    renderer.read_pixels()
+                })
+                .collect()
+        });
+        self.camera = camera;
+        frames
+    }
+
+    /// Take a screenshot after the current frame.
+    pub fn request_screenshot(&mut self) {
+        self.pending_screenshot = true;
+    }
+
+    /// Draw the current view into an offscreen texture of the window's
+    /// size, without the UI, and write it as a timestamped PNG in
+    /// [`screenshot_dir`](Self::screenshot_dir).
+    fn save_screenshot(&mut self) {
+        let size = self.size;
+        let Some(pixels) = self.capture_frame() else {
+            log::warn!("Screenshot capture failed");
+            return;
+        };
+        let path = screenshot::path_in(&self.screenshot_dir, std::time::SystemTime::now());
+        let result = screenshot::to_png(&pixels, (size.width, size.height))
+            .and_then(|data| Ok(std::fs::write(&path, data)?));
+        match result {
+            Ok(()) => log::info!("Wrote screenshot to {}", path.display()),
+            Err(err) => log::warn!("Failed to write {}: {}", path.display(), err),
+        }
+    }
+
+    /// Draw the current view into an offscreen texture of the window's
+    /// size, without the UI, and read it back.
+    ///
+    /// Returns `None` if the frame fails to render or read back.
+    pub fn capture_frame(&mut self) -> Option<Vec<[u8; 4]>> {
+        self.capture(self.size, |renderer| {
+            renderer.render(None).ok()?;
+            renderer.read_pixels()
+        })
+    }
+
+    /// Lift a flying camera back above the surface when it comes within
+    /// twice its near plane of it, so it can't fly into hills.
+    fn keep_camera_above_terrain(&mut self) {
+        if self.camera.mode != camera::CameraMode::Fly {
+            return;
+        }
+        let Some(terrain) = &self.terrain_data else {
+            return;
+        };
+        let eye = self.camera.position().as_dvec3() + self.render_origin;
+        let surface = Heightfield::new(terrain, self.settings.height_scale as f64);
+        let Some(ground) = surface.sample_height(eye.x, eye.z) else {
+            return;
+        };
+        let lift = ground + 2.0 * self.camera.near as f64 - eye.y;
+        if lift > 0.0 {
+            self.camera.target.y += lift as f32;
+        }
+    }
+
+    /// Aim the camera at the middle of the terrain from where all of it is
+    /// in view, keeping its direction.
+    pub fn frame_scene(&mut self) {
+        if let Some((min, max)) = self.scene_box {
+            let aspect = self.size.width as f32 / self.size.height as f32;
+            self.camera.fit_to_bounds(min, max, aspect);
+        }
+    }
+
+    /// Run `draw` with frames going to an offscreen texture of `size`
+    /// without the UI, so they can be read back. The frame target and size
+    /// are restored afterwards.
+    fn capture<T>(
+        &mut self,
+        size: winit::dpi::PhysicalSize<u32>,
+        draw: impl FnOnce(&mut Self) -> Option<T>,
+    ) -> Option<T> {
+        let window_size = self.size;
+        let usage = self.config.usage;
+        let view_camera = self.view_camera.clone();
+
+        self.config.usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+        let offscreen =
+            FrameTarget::Offscreen(create_offscreen_texture(&self.device, &self.config));
+        let target = std::mem::replace(&mut self.target, offscreen);
+        self.resize(size);
+        self.capturing = true;
+
+        let result = draw(self);
+
+        self.capturing = false;
+        self.view_camera = view_camera;
+        self.target = target;
+        self.config.usage = usage;
+        self.resize(window_size);
+        result
+    }
+
+    /// Keep the current render mode, lighting, contours and height scale
+    /// for the left side of the swipe comparison, and start swiping.
+    pub fn take_compare_snapshot(&mut self) {
+        let settings = &self.settings;
+        self.compare.snapshot = Some(CompareSnapshot {
+            render_mode: settings.render_mode,
+            lighting: settings.lighting,
+            contour: settings.contour,
+            height_scale: settings.height_scale,
+        });
+        self.settings.compare.enabled = true;
+    }
+
+    /// Freeze the current camera frustum so it stays visible as a line box
+    /// while the camera moves away from it.
+    pub fn capture_frustum(&mut self, aspect: f32) {
+        self.set_frustum(self.camera.frustum_corners(aspect));
+    }
+
+    /// Upload line buffers for a captured frustum.
+    ///
+    /// The lines are drawn with the mesh's height transform, so their
+    /// heights are mapped back into mesh space; call again when the
+    /// transform changes.
+    fn set_frustum(&mut self, corners: [Vec3; 8]) {
+        let height = self.height_transform();
+        let (vertices, indices) = frustum_lines(&corners.map(|c| c.with_y(height.invert(c.y))));
+
+        self.frustum_vertex_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Frustum Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+        self.frustum_index_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Frustum Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        ));
+        self.captured_frustum = Some(corners);
+    }
+
+    /// Upload the ridgeline overlay for `mesh`, extracting the lines on
+    /// first use. Clears the overlay when disabled or the mesh is empty.
+    fn upload_ridgelines(&mut self, mesh: &TerrainMesh) {
+        self.ridgeline_vertex_buffer = None;
+        self.ridgeline_index_buffer = None;
+        self.num_ridgeline_indices = 0;
+        let Some(terrain) = &self.terrain_data else {
+            return;
+        };
+        if !self.settings.ridgelines || mesh.vertices.is_empty() {
+            return;
+        }
+
+        let lines = self
+            .ridgelines
+            .get_or_insert_with(|| analysis::ridgelines(terrain));
+        let lift = terrain.cell_size as f32 * RIDGELINE_LIFT;
+        let (vertices, indices) = ridgeline_geometry(lines, mesh, lift);
+        if indices.is_empty() {
+            return;
+        }
+
+        self.ridgeline_vertex_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Ridgeline Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+        self.ridgeline_index_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Ridgeline Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        ));
+        self.num_ridgeline_indices = indices.len() as u32;
+    }
+
+    /// Terrain point under `cursor`, in physical pixels, with its
+    /// world-space position on the unscaled surface.
+    ///
+    /// Picks against the flat terrain, so points found in globe mode lie
+    /// slightly off the curved surface.
+    pub fn pick(&self, (x, y): (f32, f32)) -> Option<Pick> {
+        let terrain = self.terrain_data.as_ref()?;
+        let (width, height) = (self.size.width as f32, self.size.height as f32);
+        let ndc = Vec2::new(2.0 * x / width - 1.0, 1.0 - 2.0 * y / height);
+        let (origin, direction) = self.camera.ray(ndc, width / height);
+        Pick::cast(
+            terrain,
+            origin.as_dvec3() + self.render_origin,
+            direction.as_dvec3(),
+            self.settings.height_scale as f64,
+        )
+    }
+
+    /// Run the selected tool on the terrain under `cursor`.
+    pub fn use_tool(&mut self, cursor: (f32, f32)) {
+        if self.settings.tool == Tool::None {
+            return;
+        }
+        let Some(pick) = self.pick(cursor) else {
+            return;
+        };
+        let Some(terrain) = &self.terrain_data else {
+            return;
+        };
+        let point = pick.position;
+        let index = pick.sample.1 * terrain.width + pick.sample.0;
+        match self.settings.tool {
+            Tool::None => {}
+            Tool::Inspect => {
+                log::info!(
+                    "Sample ({}, {}) height {} at {}",
+                    pick.sample.0,
+                    pick.sample.1,
+                    pick.height,
+                    point
+                );
+                self.inspected = Some(pick);
+            }
+            Tool::Catchment => {
+                let flow = self.flow.get_or_insert_with(|| FlowGrid::compute(terrain));
+                let outlet = flow.snap(terrain, index, tools::CATCHMENT_SNAP_RADIUS);
+                let catchment = Catchment::delineate(terrain, flow, outlet);
+                log::info!(
+                    "Catchment of {} samples, area {}",
+                    catchment.samples.len(),
+                    catchment.area
+                );
+                self.catchment = Some(catchment);
+            }
+            Tool::LineOfSight => match self.sight_start.take() {
+                Some(start) => {
+                    self.line_of_sight =
+                        LineOfSight::compute(terrain, start, point.xz(), &self.settings.sight);
+                }
+                None => {
+                    self.sight_start = Some(point.xz());
+                    self.line_of_sight = None;
+                }
+            },
+            Tool::Profile => match self.profile_start.take() {
+                Some(start) => {
+                    self.profile = Profile::compute(terrain, start, point.xz());
+                }
+                None => {
+                    self.profile_start = Some(point.xz());
+                    self.profile = None;
+                }
+            },
+            Tool::Polyline | Tool::Polygon => {
+                let shape = match self.settings.tool {
+                    Tool::Polygon => Annotation::Polygon(Vec::new()),
+                    _ => Annotation::Polyline(Vec::new()),
+                };
+                // Switching between the tools starts a new shape
+                if self.sketch.as_ref().is_some_and(|sketch| {
+                    std::mem::discriminant(sketch) != std::mem::discriminant(&shape)
+                }) {
+                    self.finish_sketch();
+                }
+                self.sketch.get_or_insert(shape).extend(point.xz(), None);
+            }
+            Tool::Paint => self.paint(pick.sample),
+            Tool::SelectRectangle | Tool::SelectLasso => {
+                // A click without dragging clears the selection
+                self.selecting = vec![point.xz()];
+                self.selection = None;
+            }
+            Tool::AlignImage => {
+                let radius = terrain.cell_size * tools::CONTROL_POINT_GRAB_RADIUS;
+                let grabbed = self.drape_alignment.nearest(point.xz(), radius);
+                self.grabbed_control_point = grabbed.or_else(|| {
+                    // Starts where the image shows now, so adding it
+                    // doesn't move the image
+                    let image = self
+                        .drape_alignment
+                        .solve(terrain)
+                        .transform_point2(image_position(terrain, point.xz()));
+                    self.drape_alignment.points.push(ControlPoint {
+                        terrain: point.xz(),
+                        image,
+                    });
+                    Some(self.drape_alignment.points.len() - 1)
+                });
+            }
+            Tool::Note => {
+                let text = self.ui.note_text.trim();
+                if !text.is_empty() {
+                    self.annotations.push(Annotation::Note {
+                        position: point.xz(),
+                        text: text.to_string(),
+                    });
+                    self.write_annotations();
+                }
+            }
+        }
+        self.upload_tool_overlay();
+    }
+
+    /// Continue a freehand stroke of the shape being drawn or of the paint
+    /// brush to the terrain under `cursor`, while the right button is held.
+    pub fn drag_tool(&mut self, cursor: (f32, f32)) {
+        if self.settings.tool == Tool::Paint {
+            if let Some(pick) = self.pick(cursor) {
+                self.paint(pick.sample);
+            }
+            return;
+        }
+        if matches!(
+            self.settings.tool,
+            Tool::SelectRectangle | Tool::SelectLasso
+        ) {
+            self.drag_selection(cursor);
+            return;
+        }
+        if self.settings.tool == Tool::AlignImage {
+            let point = self.pick(cursor).map(|pick| pick.position.xz());
+            let grabbed = self.grabbed_control_point;
+            let control = grabbed.and_then(|i| self.drape_alignment.points.get_mut(i));
+            if let (Some(point), Some(control)) = (point, control) {
+                control.terrain = point;
+                self.upload_tool_overlay();
+            }
+            return;
+        }
+        let (Some(sketch), Some(terrain)) = (&self.sketch, &self.terrain_data) else {
+            return;
+        };
+        if !matches!(self.settings.tool, Tool::Polyline | Tool::Polygon) {
+            return;
+        }
+        let spacing = terrain.cell_size * annotations::FREEHAND_SPACING;
+        let Some(point) = self.pick(cursor).map(|pick| pick.position) else {
+            return;
+        };
+        let mut sketch = sketch.clone();
+        sketch.extend(point.xz(), Some(spacing));
+        if Some(&sketch) != self.sketch.as_ref() {
+            self.sketch = Some(sketch);
+            self.upload_tool_overlay();
+        }
+    }
+
+    /// Paint the terrain's colors with the brush around sample `(x, z)`
+    /// and show them. A terrain without colors starts from those of the
+    /// color mode. The mesh is rebuilt at most once a frame.
+    fn paint(&mut self, sample: (usize, usize)) {
+        let options = self.mesh_options();
+        let Some(terrain) = &mut self.terrain_data else {
+            return;
+        };
+        if terrain.colors.is_none() {
+            terrain.colors = Some(mesh::mode_hex_colors(terrain, &options));
+        }
+        let brush = self.settings.brush;
+        if terrain.paint(sample, brush.radius as f64, rgb_to_hex(brush.color)) {
+            self.colors_painted = true;
+            self.paint_strokes += 1;
+        }
+        self.settings.file_colors = true;
+    }
+
+    /// Write the terrain, with its painted colors, as an .fdf file from a
+    /// background job.
+    pub fn save_fdf(&mut self, path: PathBuf) -> anyhow::Result<JobId> {
+        let Some(terrain) = self.terrain_data.clone() else {
+            anyhow::bail!("No terrain to save");
+        };
+        Ok(self.spawn_write("terrain", path, move |_| {
+            Ok(loader::to_fdf(&terrain).into_bytes())
+        }))
+    }
+
+    /// Stretch the selection's rectangle to, or extend its lasso through,
+    /// the terrain under `cursor`.
+    fn drag_selection(&mut self, cursor: (f32, f32)) {
+        let Some(point) = self.pick(cursor).map(|pick| pick.position.xz()) else {
+            return;
+        };
+        let (Some(terrain), Some(&start)) = (&self.terrain_data, self.selecting.first()) else {
+            return;
+        };
+        let selection = if self.settings.tool == Tool::SelectRectangle {
+            Selection::rectangle(terrain, start, point)
+        } else {
+            let spacing = terrain.cell_size * annotations::FREEHAND_SPACING;
+            if self.selecting.last().unwrap().distance(point) < spacing {
+                return;
+            }
+            self.selecting.push(point);
+            Selection::polygon(terrain, &self.selecting)
+        };
+        self.selection = Some(selection);
+        self.upload_tool_overlay();
+    }
+
+    /// Add the shape being drawn to the annotations, dropping it if it has
+    /// too few points.
+    fn finish_sketch(&mut self) {
+        if let Some(sketch) = self.sketch.take().filter(Annotation::is_complete) {
+            self.annotations.push(sketch);
+            self.write_annotations();
+        }
+        self.upload_tool_overlay();
+    }
+
+    /// Redo the line-of-sight check once its antenna heights change.
+    fn update_line_of_sight(&mut self) {
+        let (Some(terrain), Some(los)) = (&self.terrain_data, &self.line_of_sight) else {
+            return;
+        };
+        if los.config == self.settings.sight {
+            return;
+        }
+        self.line_of_sight = LineOfSight::compute(
+            terrain,
+            los.observer.xz(),
+            los.target.xz(),
+            &self.settings.sight,
+        );
+        self.upload_tool_overlay();
+    }
+
+    /// Clear the results of all tools.
+    fn clear_tools(&mut self) {
+        self.catchment = None;
+        self.sight_start = None;
+        self.line_of_sight = None;
+        self.profile_start = None;
+        self.profile = None;
+        self.inspected = None;
+        self.selection = None;
+        self.upload_tool_overlay();
+    }
+
+    /// Drop the edits made to the terrain shown so far, its annotations
+    /// and camera path, when another file replaces it.
+    pub fn clear_edits(&mut self) {
+        self.annotations.clear();
+        self.sketch = None;
+        self.camera_path = CameraPath::default();
+        self.recording = None;
+        self.paint_strokes = 0;
+        self.upload_tool_overlay();
+    }
+
+    /// Clear the profile cut, when its window is closed.
+    fn clear_profile(&mut self) {
+        self.profile_start = None;
+        self.profile = None;
+        self.upload_tool_overlay();
+    }
+
+    /// Upload the tool result overlay, or clear it when there are no results.
+    fn upload_tool_overlay(&mut self) {
+        self.tool_vertex_buffer = None;
+        self.tool_index_buffer = None;
+        self.num_tool_indices = 0;
+        let Some(terrain) = &self.terrain_data else {
+            return;
+        };
+
+        // In mesh space, like the ridgelines
+        let mut overlay = Overlay::new(self.render_origin, self.mesh_height_scale as f64);
+        if let Some(catchment) = &self.catchment {
+            let lift = terrain.cell_size * RIDGELINE_LIFT as f64;
+            overlay.catchment(catchment, terrain, lift);
+        }
+        if let Some(start) = self.sight_start {
+            if let Some(ground) = terrain.height_at(start.x, start.y) {
+                let height = self.settings.sight.observer_height as f64;
+                overlay.mast(DVec3::new(start.x, ground + height, start.y), height);
+            }
+        }
+        if let Some(los) = &self.line_of_sight {
+            overlay.sight_line(los);
+        }
+        if let Some(pick) = &self.inspected {
+            overlay.marker(pick.position, terrain);
+        }
+        let lift = terrain.cell_size * RIDGELINE_LIFT as f64;
+        if let Some(start) = self.profile_start {
+            overlay.profile_end(start, terrain);
+        }
+        if let Some(profile) = &self.profile {
+            overlay.profile(profile, terrain, lift);
+        }
+        if let Some(selection) = &self.selection {
+            overlay.selection(selection, terrain, lift);
+        }
+        for annotation in self.annotations.iter().chain(&self.sketch) {
+            overlay.annotation(annotation, terrain, lift);
+        }
+        if self.has_drape {
+            for control in &self.drape_alignment.points {
+                overlay.control_point(control.terrain, terrain);
+            }
+        }
+        let Overlay {
+            vertices, indices, ..
+        } = overlay;
+        if indices.is_empty() {
+            return;
+        }
+        self.tool_vertex_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Tool Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+        self.tool_index_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Tool Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        ));
+        self.num_tool_indices = indices.len() as u32;
+    }
+
+    /// Upload the vector field overlay, or clear it when it's hidden.
+    fn upload_vectors(&mut self) {
+        self.vector_vertex_buffer = None;
+        self.vector_index_buffer = None;
+        self.num_vector_indices = 0;
+        self.prev_vectors = self.settings.vectors;
+        let (Some(terrain), Some(field)) = (&self.terrain_data, &self.vector_field) else {
+            return;
+        };
+        if !self.settings.vectors.enabled {
+            return;
+        }
+
+        // In mesh space, like the ridgelines
+        let mut overlay = Overlay::new(self.render_origin, self.mesh_height_scale as f64);
+        let lift = terrain.cell_size * RIDGELINE_LIFT as f64;
+        for line in field.lines(&self.settings.vectors) {
+            overlay.draped(&line, terrain, lift, VECTOR_COLOR);
+        }
+        let Overlay {
+            vertices, indices, ..
+        } = overlay;
+        if indices.is_empty() {
+            return;
+        }
+        self.vector_vertex_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Vector Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+        self.vector_index_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Vector Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        ));
+        self.num_vector_indices = indices.len() as u32;
+    }
+
+    /// Upload the point markers, or clear them when they're hidden.
+    fn upload_points(&mut self) {
+        self.point_vertex_buffer = None;
+        self.point_index_buffer = None;
+        self.num_point_indices = 0;
+        self.prev_points = self.settings.points;
+        let (Some(terrain), Some(set)) = (&self.terrain_data, &self.points) else {
+            return;
+        };
+        if !self.settings.points.enabled {
+            return;
+        }
+
+        // In mesh space, like the ridgelines
+        let mut overlay = Overlay::new(self.render_origin, self.mesh_height_scale as f64);
+        let colors = set.colors(&self.settings.points);
+        let sizes = set.sizes(&self.settings.points);
+        for ((point, color), size) in set.points.iter().zip(colors).zip(sizes) {
+            overlay.point(point.position, terrain, size * terrain.cell_size, color);
+        }
+        let Overlay {
+            vertices, indices, ..
+        } = overlay;
+        if indices.is_empty() {
+            return;
+        }
+        self.point_vertex_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Point Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+        self.point_index_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Point Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        ));
+        self.num_point_indices = indices.len() as u32;
+    }
+
+    /// Screen positions of the tops of the point markers in front of the
+    /// camera, in physical pixels, with the points' indices.
+    fn point_markers(&self, view_proj: Mat4, height: HeightTransform) -> Vec<(Vec2, usize)> {
+        let (Some(terrain), Some(set)) = (&self.terrain_data, &self.points) else {
+            return Vec::new();
+        };
+        if !self.settings.points.enabled {
+            return Vec::new();
+        }
+        let overlay = Overlay::new(self.render_origin, self.mesh_height_scale as f64);
+        let heights: Vec<f64> = set
+            .sizes(&self.settings.points)
+            .iter()
+            .map(|size| size * terrain.cell_size)
+            .collect();
+        let size = Vec2::new(self.size.width as f32, self.size.height as f32);
+        overlay
+            .point_anchors(set.points.iter().map(|p| p.position), &heights, terrain)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, top)| {
+                let top = Vec3::new(top.x, height.apply(top.y), top.z);
+                let clip = view_proj * top.extend(1.0);
+                let ndc = (clip.w > 0.0).then(|| clip.xy() / clip.w)?;
+                let screen = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) / 2.0 * size;
+                Some((screen, i))
+            })
+            .collect()
+    }
+
+    /// The terrain's σ channel shown as uncertainty, if any.
+    fn uncertainty_channel(&self) -> Option<&Channel> {
+        let index = self.settings.uncertainty.channel?;
+        self.terrain_data.as_ref()?.channels().get(index)
+    }
+
+    /// Rebuild the uncertainty map from the shown σ channel, and the solid
+    /// bind groups sampling it.
+    fn upload_uncertainty_map(&mut self) {
+        self.uncertainty_map = match self.uncertainty_channel() {
+            Some(channel) => UncertaintyMap::new(&self.device, &self.queue, channel),
+            None => UncertaintyMap::empty(&self.device, &self.queue),
+        };
+        self.rebuild_solid_bind_groups();
+    }
+
+    /// Rebuild the bind groups of the solid shader, after one of the maps
+    /// it samples changed.
+    fn rebuild_solid_bind_groups(&mut self) {
+        let maps = SolidMaps {
+            params: &self.params_buffer,
+            shadow: &self.shadow_map,
+            uncertainty: &self.uncertainty_map,
+            drape: &self.drape_texture,
+            environment: &self.environment_texture,
+        };
+        let bind_group = |label, buffers| {
+            maps.bind_group(&self.device, &self.solid_bind_group_layout, label, buffers)
+        };
+        let scene = &self.scene_uniform_buffer;
+        let solid = bind_group("Solid Bind Group", (&self.solid_uniform_buffer, scene));
+        let water = bind_group("Water Bind Group", (&self.water_uniform_buffer, scene));
+        let overview = bind_group(
+            "Overview Solid Bind Group",
+            (
+                self.overview.solid_uniform_buffer(),
+                self.overview.scene_uniform_buffer(),
+            ),
+        );
+        let compare = bind_group(
+            "Compare Solid Bind Group",
+            (self.compare.solid_uniform_buffer(), scene),
+        );
+        let layer = bind_group(
+            "Layer Solid Bind Group",
+            (self.layer_uniforms.solid_uniform_buffer(), scene),
+        );
+        self.solid_bind_group = solid;
+        self.water_bind_group = water;
+        self.overview.set_solid_bind_group(overview);
+        self.compare.set_solid_bind_group(compare);
+        self.layer_uniforms.set_solid_bind_group(layer);
+    }
+
+    /// Rebuild the whiskers over the shown σ channel, when uncertainty is
+    /// drawn as whiskers.
+    fn upload_whiskers(&mut self) {
+        self.whisker_vertex_buffer = None;
+        self.whisker_index_buffer = None;
+        self.num_whisker_indices = 0;
+        self.prev_uncertainty = self.settings.uncertainty;
+        let config = self.settings.uncertainty;
+        if config.style != UncertaintyStyle::Whiskers {
+            return;
+        }
+        let (Some(terrain), Some(channel)) = (&self.terrain_data, self.uncertainty_channel())
+        else {
+            return;
+        };
+
+        // In mesh space, like the ridgelines
+        let mut overlay = Overlay::new(self.render_origin, self.mesh_height_scale as f64);
+        let cap = sigma::whisker_spacing(terrain) as f64 * terrain.cell_size * WHISKER_CAP;
+        for (center, half) in sigma::whiskers(terrain, channel, config.sigmas) {
+            overlay.whisker(center, half, cap, WHISKER_COLOR);
+        }
+        let Overlay {
+            vertices, indices, ..
+        } = overlay;
+        if indices.is_empty() {
+            return;
+        }
+        self.whisker_vertex_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Whisker Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+        self.whisker_index_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Whisker Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        ));
+        self.num_whisker_indices = indices.len() as u32;
+    }
+
+    /// Write the last catchment's outline as GeoJSON.
+    fn export_catchment(&self, path: &std::path::Path) {
+        let (Some(terrain), Some(catchment)) = (&self.terrain_data, &self.catchment) else {
+            return;
+        };
+        match std::fs::write(path, catchment.to_geojson(terrain)) {
+            Ok(()) => log::info!("Wrote catchment to {}", path.display()),
+            Err(err) => log::warn!("Failed to write {}: {}", path.display(), err),
+        }
+    }
+
+    /// Write the annotations as GeoJSON.
+    fn export_annotations(&self, path: &std::path::Path) {
+        let Some(terrain) = &self.terrain_data else {
+            return;
+        };
+        match std::fs::write(path, annotations::to_geojson(&self.annotations, terrain)) {
+            Ok(()) => log::info!("Wrote annotations to {}", path.display()),
+            Err(err) => log::warn!("Failed to write {}: {}", path.display(), err),
+        }
+    }
+
+    /// Keep the current camera as a keyframe of the camera path at the
+    /// timeline's time.
+    pub fn add_camera_keyframe(&mut self) {
+        let time = self.settings.timeline.time;
+        self.camera_path.insert(Keyframe::from_camera(
+            &self.camera,
+            self.render_origin,
+            time,
+        ));
+    }
+
+    /// Start recording the live camera into the camera path from the
+    /// timeline's time, replacing the keyframes after it, or stop and rewind
+    /// the timeline to play the recording back.
+    pub fn toggle_recording(&mut self) {
+        let timeline = &mut self.settings.timeline;
+        match self.recording.take() {
+            Some(recording) => {
+                recording.finish(&mut self.camera_path, &self.camera, self.render_origin);
+                timeline.duration = timeline.duration.max(self.camera_path.end());
+                timeline.time = recording.start;
+                timeline.path = true;
+            }
+            None => {
+                let start = timeline.time;
+                self.camera_path.keyframes.retain(|key| key.time < start);
+                // The camera is the user's while recording
+                timeline.path = false;
+                timeline.orbit = false;
+                self.recording = Some(Recording::new(start));
+            }
+        }
+    }
+
+    /// Write the camera path as JSON.
+    pub fn export_camera_path(&self, path: &std::path::Path) {
+        match std::fs::write(path, self.camera_path.to_json()) {
+            Ok(()) => log::info!("Wrote camera path to {}", path.display()),
+            Err(err) => log::warn!("Failed to write {}: {}", path.display(), err),
+        }
+    }
+
+    /// Replace the camera path with one read from a JSON file, lengthening
+    /// the timeline to fit it.
+    pub fn import_camera_path(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        self.camera_path = CameraPath::from_json(&text)?;
+        let timeline = &mut self.settings.timeline;
+        timeline.duration = timeline.duration.max(self.camera_path.end());
+        Ok(())
+    }
+
+    /// Jump to keyframe `index` of the camera path, moving the timeline to
+    /// its time. Returns `false` if there is no such keyframe.
+    pub fn show_keyframe(&mut self, index: usize) -> bool {
+        let Some(keyframe) = self.camera_path.keyframes.get(index).copied() else {
+            return false;
+        };
+        self.set_camera_pose(&keyframe);
+        self.settings.timeline.time = keyframe.time;
+        true
+    }
+
+    /// Read the terrain's bookmarks from `file`, which later changes are
+    /// saved to.
+    pub fn load_bookmarks(&mut self, file: PathBuf) -> anyhow::Result<()> {
+        self.bookmarks = Bookmarks::load(file)?;
+        Ok(())
+    }
+
+    /// Input settings edited in the UI since last taken, for the input
+    /// controller to pick up.
+    pub fn take_input_change(&mut self) -> Option<&InputConfig> {
+        std::mem::take(&mut self.input_changed).then_some(&self.user_config.input)
+    }
+
+    /// Write the input settings to their file.
+    fn save_config(&self) {
+        let Some(file) = &self.user_config.file else {
+            return;
+        };
+        match self.user_config.save() {
+            Ok(()) => log::info!("Wrote settings to {}", file.display()),
+            Err(err) => log::warn!("Failed to write {}: {:#}", file.display(), err),
+        }
+    }
+
+    /// Autosave edits to `dir` for crash recovery, first offering to
+    /// restore edits a crashed session left there.
+    pub fn enable_autosave(&mut self, dir: PathBuf) {
+        match Edits::load(&dir) {
+            Ok(edits) => self.recovered = edits,
+            Err(err) => log::warn!("Failed to read {}: {:#}", dir.display(), err),
+        }
+        self.autosave = Some(Autosave::new(dir, &self.annotations, &self.camera_path));
+    }
+
+    /// Write edits that changed to the recovery directory, if due.
+    fn autosave_edits(&mut self) {
+        let (Some(autosave), Some(terrain)) = (&mut self.autosave, &self.terrain_data) else {
+            return;
+        };
+        if !autosave.due() {
+            return;
+        }
+        let written = autosave.write(
+            self.paint_strokes,
+            terrain,
+            &self.annotations,
+            &self.camera_path,
+        );
+        match written {
+            Ok(true) => log::info!("Autosaved edits to {}", autosave.dir.display()),
+            Ok(false) => {}
+            Err(err) => log::warn!("Failed to write {}: {:#}", autosave.dir.display(), err),
+        }
+    }
+
+    /// Bring back the edits of a crashed session over the current ones.
+    pub fn restore_edits(&mut self) {
+        let Some(edits) = self.recovered.take() else {
+            return;
+        };
+        if let (Some(terrain), Some(colors)) = (&mut self.terrain_data, edits.colors) {
+            if colors.len() == terrain.width * terrain.height {
+                terrain.colors = Some(colors);
+                self.colors_painted = true;
+                self.paint_strokes += 1;
+                self.settings.file_colors = true;
+            }
+        }
+        // Annotations saved before the crash are already back
+        let lost: Vec<_> = (edits.annotations.into_iter())
+            .filter(|annotation| !self.annotations.contains(annotation))
+            .collect();
+        if !lost.is_empty() {
+            self.annotations.extend(lost);
+            self.write_annotations();
+        }
+        if !edits.camera_path.keyframes.is_empty() {
+            self.camera_path = edits.camera_path;
+        }
+        self.upload_tool_overlay();
+    }
+
+    /// Drop the edits of a crashed session and their recovery directory.
+    fn discard_edits(&mut self) {
+        self.recovered = None;
+        self.finish_autosave();
+    }
+
+    /// Remove the recovery directory, on a clean exit. Edits of a crashed
+    /// session that were neither restored nor discarded are kept.
+    pub fn finish_autosave(&self) {
+        let Some(autosave) = self.autosave.as_ref().filter(|_| self.recovered.is_none()) else {
+            return;
+        };
+        if let Err(err) = autosave.discard() {
+            log::warn!("Failed to remove {}: {:#}", autosave.dir.display(), err);
+        }
+    }
+
+    /// Keep the current camera, with its lens, as the bookmark `name`,
+    /// replacing one of the same name, and save the bookmarks.
+    pub fn save_bookmark(&mut self, name: &str) {
+        let pose = Keyframe::from_camera(&self.camera, self.render_origin, 0.0);
+        let aspect = self.size.width as f32 / self.size.height as f32;
+        let lens = Lens::from_camera(&self.camera, aspect);
+        self.bookmarks.insert(name, pose, Some(lens));
+        self.write_bookmarks();
+    }
+
+    /// Move the camera to bookmark `index`. Returns `false` if there is no
+    /// such bookmark.
+    pub fn show_bookmark(&mut self, index: usize) -> bool {
+        let Some(view) = self.bookmarks.views.get(index) else {
+            return false;
+        };
+        let pose = view.pose;
+        self.set_camera_pose(&pose);
+        true
+    }
+
+    /// Show the frustum seen from bookmark `index` as a line box, like a
+    /// captured one, through the lens it was saved with, or the current
+    /// camera's for a viewport of `aspect` if it has none. Returns `false`
+    /// if there is no such bookmark.
+    pub fn show_bookmark_frustum(&mut self, index: usize, aspect: f32) -> bool {
+        let Some(view) = self.bookmarks.views.get(index) else {
+            return false;
+        };
+        let mut camera = self.camera.clone();
+        view.pose.apply(&mut camera, self.render_origin);
+        let aspect = match view.lens {
+            Some(lens) => {
+                lens.apply(&mut camera);
+                lens.aspect
+            }
+            None => aspect,
+        };
+        self.set_frustum(camera.frustum_corners(aspect));
+        true
+    }
+
+    /// Remove bookmark `index` and save the rest.
+    pub fn delete_bookmark(&mut self, index: usize) {
+        if index < self.bookmarks.views.len() {
+            self.bookmarks.views.remove(index);
+            self.write_bookmarks();
+        }
+    }
+
+    /// Read the annotations saved in `file` in place of the current ones.
+    /// Later changes are saved to it.
+    pub fn load_annotations(&mut self, file: PathBuf) -> anyhow::Result<()> {
+        self.annotations = annotations::load(&file)?;
+        self.annotations_file = Some(file);
+        self.upload_tool_overlay();
+        Ok(())
+    }
+
+    /// Save the annotations to the terrain's annotation file.
+    fn write_annotations(&self) {
+        let (Some(file), Some(terrain)) = (&self.annotations_file, &self.terrain_data) else {
+            return;
+        };
+        match annotations::save(file, &self.annotations, terrain) {
+            Ok(()) => log::info!("Wrote annotations to {}", file.display()),
+            Err(err) => log::warn!("Failed to write {}: {:#}", file.display(), err),
+        }
+    }
+
+    /// Save the bookmarks to the terrain's bookmark file.
+    fn write_bookmarks(&self) {
+        let Some(file) = &self.bookmarks.file else {
+            return;
+        };
+        match self.bookmarks.save() {
+            Ok(()) => log::info!("Wrote bookmarks to {}", file.display()),
+            Err(err) => log::warn!("Failed to write {}: {}", file.display(), err),
+        }
+    }
+
+    /// Move the camera to the pose of `keyframe`, whatever its time.
+    pub fn set_camera_pose(&mut self, keyframe: &Keyframe) {
+        keyframe.apply(&mut self.camera, self.render_origin);
+    }
+
+    /// The camera's pose, in terrain coordinates.
+    pub fn camera_pose(&self) -> Keyframe {
+        Keyframe::from_camera(&self.camera, self.render_origin, 0.0)
+    }
+
+    /// Move the camera to `pose` from a previous session, right away or,
+    /// while the main terrain loads in the background, once it's loaded.
+    pub fn restore_camera(&mut self, pose: Keyframe) {
+        match self.terrain_data {
+            Some(_) => self.set_camera_pose(&pose),
+            None => self.restored_camera = Some(pose),
+        }
+    }
+
+    /// Whether a main terrain is loaded.
+    pub fn has_terrain(&self) -> bool {
+        self.terrain_data.is_some()
+    }
+
+    /// Jump to the camera path's next keyframe after the timeline's time,
+    /// or with `forward` false the last one before it, staying put at
+    /// either end.
+    pub fn step_keyframe(&mut self, forward: bool) {
+        let time = self.settings.timeline.time;
+        let keyframes = &self.camera_path.keyframes;
+        let index = if forward {
+            Some(keyframes.partition_point(|key| key.time <= time))
+        } else {
+            keyframes
+                .partition_point(|key| key.time < time)
+                .checked_sub(1)
+        };
+        if let Some(index) = index {
+            self.show_keyframe(index);
+        }
+    }
+
+    /// Remove the captured frustum.
+    pub fn clear_frustum(&mut self) {
+        self.frustum_vertex_buffer = None;
+        self.frustum_index_buffer = None;
+        self.captured_frustum = None;
+    }
+
+    /// Move the render origin to the camera target once it drifts more than
+    /// [`REBASE_DISTANCE`] away, so vertex positions stay small in f32.
+    ///
+    /// The camera and captured frustum are shifted by the same amount, so
+    /// the view doesn't change.
+    fn rebase_origin(&mut self) {
+        if self.terrain_data.is_none() || self.camera.target.length() < REBASE_DISTANCE {
+            return;
+        }
+
+        let shift = self.camera.target;
+        self.render_origin += shift.as_dvec3();
+        self.camera.target = Vec3::ZERO;
+        self.view_camera.target -= shift;
+        if let Some(corners) = self.captured_frustum {
+            self.set_frustum(corners.map(|c| c - shift));
+        }
+        log::debug!("Rebased render origin to {:?}", self.render_origin);
+        self.regenerate_mesh();
+    }
+
+    /// Upload terrain mesh to GPU buffers.
+    ///
+    /// Creates vertex and index buffers for both wireframe and solid rendering.
+    /// `staged` index buffers are written a few chunks per frame instead,
+    /// starting now.
+    fn upload_mesh_buffers(&mut self, mesh: &TerrainMesh, staged: bool) {
+        self.pending_indices = None;
+        if mesh.vertices.is_empty() {
+            self.vertex_buffer = None;
+            self.wireframe_index_buffer = None;
+            self.triangle_index_buffer = None;
+            self.num_wireframe_indices = 0;
+            self.num_triangle_indices = 0;
+            self.mesh_chunks.clear();
+            self.occlusion = None;
+            self.indirect_chunks = None;
+            self.scene_box = None;
+            self.mesh_box = None;
+            return;
+        }
+
+        self.mesh_box = mesh.bounds;
+        self.fit_scene_bounds();
+        self.mesh_chunks = mesh.chunks.clone();
+        self.occlusion = OcclusionCuller::new(&self.device, &mesh.chunks);
+        self.indirect_chunks = self
+            .cull_pipeline
+            .as_ref()
+            .map(|_| IndirectChunks::new(&self.device, &self.cull_bind_group_layout, &mesh.chunks));
+
+        self.vertex_buffer = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Vertex Buffer"),
+                contents: bytemuck::cast_slice(&mesh.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        ));
+
+        // Coarser levels of detail follow the full-detail indices
+        let index_format = mesh.index_format();
+        self.index_format = index_format;
+        self.line_topology = mesh.line_topology;
+        let counts = [mesh.triangle_indices.len(), mesh.indices.len()].map(|n| n as u32);
+        if staged {
+            let contents = [
+                index_bytes(
+                    index_format,
+                    [&mesh.triangle_indices, &mesh.lod_triangle_indices],
+                ),
+                index_bytes(index_format, [&mesh.indices, &mesh.lod_indices]),
+            ];
+            let index_buffer = |label, contents: &[u8]| {
+                self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size: contents.len() as u64,
+                    usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            };
+            self.triangle_index_buffer = Some(index_buffer("Triangle Index Buffer", &contents[0]));
+            self.wireframe_index_buffer =
+                Some(index_buffer("Wireframe Index Buffer", &contents[1]));
+            [self.num_triangle_indices, self.num_wireframe_indices] = [0, 0];
+            self.pending_indices = Some(PendingIndices {
+                contents,
+                written: [0, 0],
+                chunks: 0,
+                counts,
+            });
+            self.write_pending_indices();
+            return;
+        }
+        let index_buffer = |label, parts: [&[u32]; 2]| {
+            if index_format == wgpu::IndexFormat::Uint16 {
+                // Restarts truncate to 0xFFFF, the 16-bit restart value
+                let indices: Vec<u16> = parts.concat().into_iter().map(|i| i as u16).collect();
+                return self
+                    .device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some(label),
+                        contents: bytemuck::cast_slice(&indices),
+                        usage: wgpu::BufferUsages::INDEX,
+                    });
+            }
+            if parts[1].is_empty() {
+                return self
+                    .device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some(label),
+                        contents: bytemuck::cast_slice(parts[0]),
+                        usage: wgpu::BufferUsages::INDEX,
+                    });
+            }
+            let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: ((parts[0].len() + parts[1].len()) * std::mem::size_of::<u32>()) as u64,
+                usage: wgpu::BufferUsages::INDEX,
+                mapped_at_creation: true,
+            });
+            {
+                let mut view = buffer.slice(..).get_mapped_range_mut();
+                let (full, lod) = view.split_at_mut(std::mem::size_of_val(parts[0]));
+                full.copy_from_slice(bytemuck::cast_slice(parts[0]));
+                lod.copy_from_slice(bytemuck::cast_slice(parts[1]));
+            }
+            buffer.unmap();
+            buffer
+        };
+        self.wireframe_index_buffer = Some(index_buffer(
+            "Wireframe Index Buffer",
+            [&mesh.indices, &mesh.lod_indices],
+        ));
+        self.triangle_index_buffer = Some(index_buffer(
+            "Triangle Index Buffer",
+            [&mesh.triangle_indices, &mesh.lod_triangle_indices],
+        ));
+
+        [self.num_triangle_indices, self.num_wireframe_indices] = counts;
+    }
+
+    /// Write the next chunks of the staged index buffers, which are drawn
+    /// from then on, and all that's left, levels of detail included, with
+    /// the last.
+    fn write_pending_indices(&mut self) {
+        let Some(pending) = &mut self.pending_indices else {
+            return;
+        };
+        let (Some(triangles), Some(lines)) =
+            (&self.triangle_index_buffer, &self.wireframe_index_buffer)
+        else {
+            return;
+        };
+        let total = self.mesh_chunks.len();
+        pending.chunks = (pending.chunks + UPLOAD_CHUNKS).min(total);
+        let done = pending.chunks == total;
+        let ends = match &self.mesh_chunks[..pending.chunks] {
+            [.., last] if !done => [last.triangles.end, last.lines.end],
+            _ => pending.counts,
+        };
+        let index_size = match self.index_format {
+            wgpu::IndexFormat::Uint16 => 2,
+            wgpu::IndexFormat::Uint32 => 4,
+        };
+        for (((buffer, contents), written), end) in [triangles, lines]
+            .into_iter()
+            .zip(&pending.contents)
+            .zip(&mut pending.written)
+            .zip(ends)
+        {
+            // Writes cover whole words, which may start the next chunk
+            let end = match done {
+                true => contents.len(),
+                false => (end as usize * index_size)
+                    .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize)
+                    .min(contents.len()),
+            };
+            if end > *written {
+                self.queue
+                    .write_buffer(buffer, *written as u64, &contents[*written..end]);
+                *written = end;
+            }
+        }
+        [self.num_triangle_indices, self.num_wireframe_indices] = ends;
+        if done {
+            self.pending_indices = None;
+        }
+    }
+
+    /// Run the animations on by `dt` seconds: the timeline clock with its
+    /// orbit track, auto-rotate, the camera path recording and the view's
+    /// glide after the camera.
+    fn step_animations(&mut self, dt: f32) {
+        let elapsed = self.settings.timeline.advance(dt);
+        if self.settings.timeline.orbit {
+            self.camera.azimuth += self.settings.timeline.orbit_angle(elapsed);
+        }
+        self.camera.azimuth += self.settings.auto_rotate.angle(dt);
+        if let Some(recording) = &mut self.recording {
+            recording.record(&mut self.camera_path, &self.camera, self.render_origin, dt);
+        }
+        self.settings
+            .motion
+            .follow(&mut self.view_camera, &self.camera, dt);
+    }
+
+    /// Render a frame into `texture`, e.g. one the host application
+    /// composites into its own frame, without the UI. The texture must come
+    /// from this renderer's device, have its format and be usable as a
+    /// render attachment; the view is resized to match it. Later frames go
+    /// to the same texture until another one is passed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the renderer draws to a window or `texture` has
+    /// another format.
+    pub fn render_to(&mut self, texture: &wgpu::Texture) -> anyhow::Result<()> {
+        if let FrameTarget::Surface(_) = self.target {
+            anyhow::bail!("Renderer draws to a window");
+        }
+        if texture.format() != self.config.format {
+            anyhow::bail!(
+                "Expected a {:?} texture, got {:?}",
+                self.config.format,
+                texture.format()
+            );
+        }
+        // Layer blend modes copy the scene where the texture allows it
+        self.config.usage = texture.usage();
+        let size = winit::dpi::PhysicalSize::new(texture.width(), texture.height());
+        if size != self.size {
+            self.resize(size);
+        }
+        self.target = FrameTarget::Offscreen(texture.clone());
+        self.render(None)?;
+        Ok(())
+    }
+
+    /// Render a frame.
+    ///
+    /// Updates camera uniforms and draws the terrain based on current render mode.
+    /// `window` feeds input to the UI; offscreen renderers pass `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`wgpu::SurfaceError`] if surface acquisition fails.
+    pub fn render(&mut self, window: Option<&Window>) -> Result<(), wgpu::SurfaceError> {
+        // Update FPS counter
+        self.frame_count += 1;
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame).as_secs_f32();
+        if elapsed >= 1.0 {
+            self.fps = self.frame_count as f32 / elapsed;
+            self.frame_count = 0;
+            self.last_frame = now;
+        }
+        let steps = self.clock.tick(now);
+        self.latency.frame_started();
+
+        let finished = self.jobs.poll();
+        self.apply_jobs(finished);
+        self.stream_detail();
+        self.write_pending_indices();
+
+        // Animations are held still while capturing, and captures show
+        // where the camera is headed
+        if self.capturing {
+            self.view_camera = self.camera.clone();
+        } else {
+            for _ in 0..steps {
+                self.step_animations(UPDATE_STEP);
+            }
+            if self.settings.timeline.path {
+                let time = self.settings.timeline.time;
+                if let Some(keyframe) = self.camera_path.sample(time) {
+                    keyframe.apply(&mut self.camera, self.render_origin);
+                }
+            }
+            if !self.settings.motion.enabled {
+                self.view_camera = self.camera.clone();
+            }
+        }
+
+        // Follow the sun through the day
+        let timeline = &self.settings.timeline;
+        let sun = &mut self.settings.sun;
+        if sun.enabled {
+            if timeline.sun {
+                sun.hour = timeline.sun_hour();
+            }
+            self.settings.lighting.direction = sun.direction();
+        }
+
+        // Pick up edited shaders (debug builds only)
+        if self
+            .shader_watcher
+            .as_mut()
+            .is_some_and(|watcher| watcher.poll())
+        {
+            self.reload_shaders();
+        }
+
+        if let Some(culler) = &mut self.occlusion {
+            culler.update(&self.device);
+        }
+
+        let (output, frame_texture, acquire_wait) = match &self.target {
+            FrameTarget::Surface(surface) => {
+                let start = Instant::now();
+                let output = surface.get_current_texture()?;
+                let texture = output.texture.clone();
+                (Some(output), texture, Some(start.elapsed()))
+            }
+            FrameTarget::Offscreen(texture) => (None, texture.clone(), None),
+        };
+        let view = frame_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        self.keep_camera_above_terrain();
+
+        // Update uniforms
+        let aspect = self.size.width as f32 / self.size.height as f32;
+
+        if let Some((center, radius)) = self.view_bounds() {
+            for camera in [&mut self.camera, &mut self.view_camera] {
+                if camera.auto_clip {
+                    camera.fit_clip_planes(center, radius);
+                }
+            }
+        }
+
+        // Update wireframe uniforms
+        let mut wireframe_uniforms = WireframeUniforms::new();
+        let view_proj = self.settings.depth_mode.clip_remap()
+            * self.view_camera.build_view_projection_matrix(aspect);
+        let log_depth_coef = self.settings.depth_mode.log_depth_coef(&self.view_camera);
+        wireframe_uniforms.update(view_proj, log_depth_coef);
+        // Curvature drop is measured from the point below the camera
+        let curvature_coef = self.settings.earth_model.curvature_coef();
+        let curvature_center = self.view_camera.position().xz();
+        wireframe_uniforms.set_curvature(curvature_coef, curvature_center);
+        let height = self.height_transform();
+        wireframe_uniforms.set_height(height);
+        self.queue.write_buffer(
+            &self.wireframe_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[wireframe_uniforms]),
+        );
+        let mut grid_uniforms = wireframe_uniforms;
+        grid_uniforms.line_brightness = self.settings.line_brightness.clamp(0.0, 1.0);
+        self.queue.write_buffer(
+            &self.grid_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[grid_uniforms]),
+        );
+        let radius = self.scene_bounds().map_or(1.0, |(_, radius)| radius);
+        let ground_grid = self.scene_bounds().filter(|_| self.settings.ground_grid);
+        if let Some(bounds) = ground_grid {
+            // Height zero, like the water level
+            let y = -self.render_origin.y as f32;
+            self.ground_grid
+                .update(&self.queue, view_proj, log_depth_coef, bounds, y);
+        }
+        let scene_uniforms = SceneUniforms::new(
+            view_proj,
+            (self.view_camera.position(), self.view_camera.forward()),
+            radius,
+            (&self.settings.sky, &self.settings.fog),
+            Some((
+                &self.settings.environment,
+                self.environment_texture.irradiance(),
+            ))
+            .filter(|_| self.has_environment),
+            self.config.format.is_srgb(),
+        );
+        self.queue.write_buffer(
+            &self.scene_uniform_buffer,
+            0,
+            bytemuck::bytes_of(&scene_uniforms),
+        );
+
+        // Fit the shadow map to the mesh when shadows will be sampled
+        let solid = matches!(
+            self.settings.render_mode,
+            RenderMode::Solid | RenderMode::Both
+        );
+        let shadow_view_proj = self
+            .scene_bounds()
+            .filter(|_| self.settings.shadows && solid && self.vertex_buffer.is_some())
+            .map(|bounds| shadow::light_view_proj(self.settings.lighting.direction, bounds));
+        if let Some(light_view_proj) = shadow_view_proj {
+            self.shadow_map.update(
+                &self.queue,
+                light_view_proj,
+                (curvature_coef, curvature_center),
+                height,
+            );
+        }
+
+        // Update solid uniforms
+        let mut solid_uniforms = SolidUniforms::new();
+        solid_uniforms.update(
+            view_proj,
+            log_depth_coef,
+            &self.settings.lighting,
+            &self.settings.contour,
+        );
+        solid_uniforms.set_curvature(curvature_coef, curvature_center);
+        solid_uniforms.set_height(height);
+        solid_uniforms.set_shadows(shadow_view_proj);
+        // Mesh-space lookups don't match the bent globe positions
+        let uncertainty = self
+            .terrain_data
+            .as_ref()
+            .filter(|_| self.uncertainty_channel().is_some())
+            .filter(|_| self.settings.earth_model != EarthModel::Globe)
+            .map(|terrain| {
+                (
+                    &self.settings.uncertainty,
+                    uncertainty::placement(terrain, self.render_origin),
+                )
+            });
+        let fades =
+            uncertainty.is_some_and(|(config, _)| config.style == UncertaintyStyle::Transparency);
+        // A see-through surface is drawn with the translucent layers
+        let translucent_surface = solid
+            && (self.settings.surface_opacity < 1.0
+                || self.settings.surface_blend != BlendMode::Normal
+                || fades);
+        solid_uniforms.set_translucency(self.settings.surface_opacity, self.view_camera.far);
+        solid_uniforms.set_uncertainty(uncertainty);
+        let drape = self
+            .terrain_data
+            .as_ref()
+            .filter(|_| self.has_drape)
+            .filter(|_| self.settings.earth_model != EarthModel::Globe)
+            .map(|terrain| {
+                (
+                    self.settings.texture_blend,
+                    uncertainty::placement(terrain, self.render_origin),
+                    self.drape_alignment.solve(terrain),
+                )
+            });
+        solid_uniforms.set_drape(drape);
+        self.queue.write_buffer(
+            &self.solid_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[solid_uniforms]),
+        );
+
+        // Layers: opaque, without the overlay, from unit height scale
+        if !self.layers.is_empty() {
+            let layer_height =
+                HeightTransform::between(1.0, self.settings.height_scale, self.render_origin.y);
+            let mut layer_wireframe = grid_uniforms;
+            layer_wireframe.set_height(layer_height);
+            let mut layer_solid = solid_uniforms;
+            layer_solid.set_height(layer_height);
+            layer_solid.set_translucency(1.0, self.view_camera.far);
+            layer_solid.set_uncertainty(None);
+            layer_solid.set_drape(None);
+            self.layer_uniforms
+                .update(&self.queue, &layer_wireframe, &layer_solid);
+        }
+
+        // Water plane: lit like the surface, without contours
+        let water = self.settings.water;
+        let water_bounds = self.scene_bounds().filter(|_| water.enabled);
+        if let Some(bounds) = water_bounds {
+            let mut water_uniforms = solid_uniforms;
+            water_uniforms.contour_enabled = 0.0;
+            water_uniforms.set_uncertainty(None);
+            water_uniforms.set_drape(None);
+            // The quad is placed in world space
+            water_uniforms.set_height(HeightTransform::IDENTITY);
+            water_uniforms.set_translucency(water.opacity, self.view_camera.far);
+            self.queue.write_buffer(
+                &self.water_uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[water_uniforms]),
+            );
+            let level = water.level as f64 * self.settings.height_scale as f64;
+            let quad = water_quad(bounds, (level - self.render_origin.y) as f32);
+            self.queue
+                .write_buffer(&self.water_vertex_buffer, 0, bytemuck::cast_slice(&quad));
+        }
+
+        // Overview inset: the same uniforms seen from the fixed camera
+        let overview_viewport = self
+            .scene_bounds()
+            .filter(|_| self.settings.overview.enabled)
+            .and_then(|bounds| {
+                overview::viewport(&self.settings.overview, self.size.width, self.size.height)
+                    .map(|viewport| (bounds, viewport))
+            })
+            .map(|(bounds, viewport)| {
+                let overview_view_proj = self.settings.depth_mode.clip_remap()
+                    * overview::view_proj(self.settings.overview.view, bounds);
+                let mut wireframe_uniforms = wireframe_uniforms;
+                wireframe_uniforms.update(overview_view_proj, 0.0);
+                let mut solid_uniforms = solid_uniforms;
+                solid_uniforms.update(
+                    overview_view_proj,
+                    0.0,
+                    &self.settings.lighting,
+                    &self.settings.contour,
+                );
+                let mut marker = overview::marker_lines(
+                    self.view_camera.position(),
+                    self.view_camera.target,
+                    bounds.1 * overview::MARKER_SCALE,
+                );
+                // Drawn with the mesh's height transform
+                for vertex in &mut marker {
+                    vertex.position[1] = height.invert(vertex.position[1]);
+                }
+                self.overview
+                    .update(&self.queue, &wireframe_uniforms, &solid_uniforms, &marker);
+                viewport
+            });
+
+        // Swipe comparison: the snapshot side's uniforms and the scissor
+        // rects of both sides
+        let compare_sides = self
+            .compare
+            .snapshot
+            .filter(|_| self.settings.compare.enabled)
+            .map(|snapshot| {
+                let snapshot_height = HeightTransform::between(
+                    self.mesh_height_scale,
+                    snapshot.height_scale,
+                    self.render_origin.y,
+                );
+                let mut wireframe_uniforms = grid_uniforms;
+                wireframe_uniforms.set_height(snapshot_height);
+                let mut solid_uniforms = solid_uniforms;
+                solid_uniforms.update(
+                    view_proj,
+                    log_depth_coef,
+                    &snapshot.lighting,
+                    &snapshot.contour,
+                );
+                solid_uniforms.set_height(snapshot_height);
+                // The shadow map is rendered for the current light and heights
+                if snapshot.lighting.direction != self.settings.lighting.direction
+                    || snapshot.height_scale != self.settings.height_scale
+                {
+                    solid_uniforms.set_shadows(None);
+                }
+                self.compare
+                    .update(&self.queue, &wireframe_uniforms, &solid_uniforms);
+                let rects = compare::split(
+                    self.settings.compare.divider,
+                    self.size.width,
+                    self.size.height,
+                );
+                (snapshot.render_mode, rects)
+            });
+
+        if let Some(clipmap) = &self.clipmap {
+            let mut clipmap_uniforms =
+                ClipmapUniforms::new(clipmap, self.render_origin, self.settings.height_scale);
+            clipmap_uniforms.update(view_proj, log_depth_coef, &self.settings.lighting);
+            clipmap_uniforms.set_curvature(curvature_coef, curvature_center);
+            clipmap_uniforms.set_camera(self.view_camera.position());
+            clipmap.write_uniforms(&self.queue, &clipmap_uniforms);
+        }
+
+        self.queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::bytes_of(&self.settings.shader_params.to_uniforms()),
+        );
+
+        // Flooded share of the terrain, measured again when the water moves
+        let water = self.settings.water;
+        if water.enabled && self.flooded.is_none_or(|(level, _)| level != water.level) {
+            self.flooded = self
+                .terrain_data
+                .as_ref()
+                .map(|terrain| (water.level, analysis::flooded(terrain, water.level as f64)));
+        }
+
+        // Begin egui frame, at the monitor's scale factor (tracked by
+        // egui-winit) times the UI scale
+        self.egui_ctx.set_zoom_factor(self.settings.ui_scale);
+        let raw_input = match (&mut self.egui_state, window) {
+            (Some(state), Some(window)) => state.take_egui_input(window),
+            _ => egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(
+                    egui::Pos2::ZERO,
+                    egui::vec2(self.size.width as f32, self.size.height as f32)
+                        / self.egui_ctx.zoom_factor(),
+                )),
+                ..Default::default()
+            },
+        };
+        let egui_ctx = self.egui_ctx.clone();
+        // Note pins on screen, in physical pixels, if in front of the camera
+        let notes = self.terrain_data.as_ref().map_or(Vec::new(), |terrain| {
+            let overlay = Overlay::new(self.render_origin, self.mesh_height_scale as f64);
+            let size = Vec2::new(self.size.width as f32, self.size.height as f32);
+            overlay
+                .note_anchors(&self.annotations, terrain)
+                .into_iter()
+                .filter_map(|(top, text)| {
+                    let top = Vec3::new(top.x, height.apply(top.y), top.z);
+                    let clip = view_proj * top.extend(1.0);
+                    let ndc = (clip.w > 0.0).then(|| clip.xy() / clip.w)?;
+                    let screen = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) / 2.0 * size;
+                    Some((screen, text))
+                })
+                .collect()
+        });
+        let point_markers = self.point_markers(view_proj, height);
+        if let Some(check) = &mut self.update_check {
+            check.poll();
+        }
+        let jobs = self.jobs.status();
+        let frame_info = FrameInfo {
+            fps: self.fps,
+            input_latency: self.latency.input_to_present(),
+            acquire_wait: self.latency.acquire_wait(),
+            aspect,
+            shader_error: self.shader_error.as_deref(),
+            algebra_error: self.algebra_error.as_deref(),
+            captured_frustum: self.captured_frustum.as_ref(),
+            chunks: self
+                .chunks_drawn
+                .map(|drawn| (drawn, self.mesh_chunks.len())),
+            terrain_stats: self.terrain_stats.as_ref(),
+            computing_analysis: self.stats_job.is_some(),
+            jobs: &jobs,
+            height_histogram: &self.height_histogram,
+            has_reference: self.reference_data.is_some(),
+            terrain_name: &self.terrain_name,
+            stitched_samples: self.stitched_samples,
+            grid_size: self
+                .terrain_data
+                .as_ref()
+                .map_or((0, 0), |terrain| (terrain.width, terrain.height)),
+            detail: self.detail_subdivisions(),
+            breaklines: self.breaklines.len(),
+            points: self.points.as_ref(),
+            point_markers,
+            original_size: self
+                .original
+                .as_ref()
+                .map(|terrain| (terrain.width, terrain.height)),
+            has_drape: self.has_drape,
+            drape_preview: self.drape_preview.as_ref(),
+            drape_alignment: &self.drape_alignment,
+            has_environment: self.has_environment,
+            has_file_colors: self
+                .terrain_data
+                .as_ref()
+                .is_some_and(|terrain| terrain.colors.is_some()),
+            msaa_counts: self.msaa.counts(),
+            present_modes: &self.present_modes,
+            can_copy_scene: self.config.usage.contains(wgpu::TextureUsages::COPY_SRC),
+            channels: self
+                .terrain_data
+                .as_ref()
+                .map_or(&[], |terrain| terrain.channels()),
+            height_range: self.terrain_data.as_ref().map(|t| t.height_bounds()),
+            units: UnitFormat {
+                source: self
+                    .terrain_data
+                    .as_ref()
+                    .map_or_else(Units::default, |t| t.units),
+                display: self.settings.units,
+            },
+            has_vector_field: self.vector_field.is_some(),
+            has_compare_snapshot: self.compare.snapshot.is_some(),
+            camera_keyframes: self.camera_path.keyframes.len(),
+            bookmarks: &self.bookmarks.views,
+            recording: self.recording.map(|recording| recording.time()),
+            catchment: self.catchment.as_ref(),
+            sight_start: self.sight_start,
+            line_of_sight: self.line_of_sight.as_ref(),
+            profile_start: self.profile_start,
+            profile: self.profile.as_ref(),
+            inspected: self.inspected.as_ref(),
+            selection: self.selection.as_ref(),
+            annotations: &self.annotations,
+            sketch: self.sketch.as_ref(),
+            notes,
+            irradiance_range: self
+                .irradiation
+                .as_ref()
+                .filter(|_| self.settings.sun.irradiance)
+                .map(|irradiation| irradiation.range),
+            update: self.update_check.as_ref().and_then(UpdateCheck::available),
+            flooded: self
+                .flooded
+                .filter(|_| water.enabled)
+                .map(|(_, flooded)| flooded),
+            config_file: self.user_config.file.as_deref(),
+            recovered: self.recovered.as_ref(),
+            load_failure: self.load_failure.as_ref(),
+            warmup: (!self.warmup.is_done()).then(|| self.warmup.fraction()),
+        };
+        let mut response = UiResponse::default();
+        let full_output = egui_ctx.run(raw_input, |ctx| {
+            if !self.presenting {
+                response = self.ui.render(
+                    ctx,
+                    &mut self.camera,
+                    &mut self.settings,
+                    &mut self.user_config.input,
+                    &frame_info,
+                );
+            }
+        });
+
+        if response.reset_camera {
+            self.camera = Camera {
+                mode: self.camera.mode,
+                ..Camera::new()
+            };
+            self.frame_scene();
+        }
+        if response.frame_scene {
+            self.frame_scene();
+        }
+        if response.dismiss_update {
+            self.update_check = None;
+        }
+        if response.input_changed {
+            self.input_changed = true;
+        }
+        if response.save_config {
+            self.save_config();
+        }
+        if response.restore_edits {
+            self.restore_edits();
+        }
+        if response.discard_edits {
+            self.discard_edits();
+        }
+        if response.retry_load {
+            self.retry_load();
+        }
+        if response.dismiss_load_failure {
+            self.load_failure = None;
+        }
+        self.autosave_edits();
+        if response.capture_frustum {
+            self.capture_frustum(aspect);
+        }
+        if response.clear_frustum {
+            self.clear_frustum();
+        }
+        if let Some(id) = response.cancel_job {
+            self.jobs.cancel(id);
+        }
+        if response.compute_analysis {
+            self.spawn_analysis();
+        }
+        if let Some(path) = response.export_shadow_hours {
+            self.export_shadow_hours(path);
+        }
+        if response.export_turntable.is_some() {
+            self.pending_turntable = response.export_turntable;
+        }
+        if response.save_screenshot {
+            self.pending_screenshot = true;
+        }
+        if let Some((path, max_triangles)) = response.export_mesh {
+            if let Err(err) = self.export_mesh(path.clone(), max_triangles) {
+                log::warn!("Failed to write {}: {:#}", path.display(), err);
+            }
+        }
+        if let Some(path) = response.export_color_map {
+            if let Err(err) = self.export_color_map(path.clone()) {
+                log::warn!("Failed to write {}: {:#}", path.display(), err);
+            }
+        }
+        if let Some(path) = response.open_terrain {
+            self.open_terrain(path, loader::LoadOptions::default());
+        }
+        if let Some(path) = response.load_breaklines {
+            self.load_breaklines(path);
+        }
+        if response.clear_breaklines {
+            self.set_breaklines(Vec::new());
+        }
+        if let Some(path) = response.load_points {
+            self.load_points(path);
+        }
+        if response.clear_points {
+            self.set_points(None);
+        }
+        if let Some(index) = response.make_main_terrain {
+            self.make_main(index);
+        }
+        if let Some(index) = response.remove_terrain {
+            self.remove_terrain(index);
+        }
+        if let Some((columns, rows)) = response.crop {
+            self.crop(columns, rows);
+        }
+        if let Some(processing) = response.process {
+            self.process(processing);
+        }
+        if response.restore_original {
+            self.restore_original();
+        }
+        if response.take_compare_snapshot {
+            self.take_compare_snapshot();
+        }
+        if response.add_camera_keyframe {
+            self.add_camera_keyframe();
+        }
+        if response.clear_camera_path {
+            self.camera_path = CameraPath::default();
+        }
+        if response.toggle_recording {
+            self.toggle_recording();
+        }
+        if let Some(name) = response.save_bookmark {
+            self.save_bookmark(&name);
+        }
+        if let Some(index) = response.show_bookmark {
+            self.show_bookmark(index);
+        }
+        if let Some(index) = response.show_bookmark_frustum {
+            self.show_bookmark_frustum(index, aspect);
+        }
+        if let Some(index) = response.delete_bookmark {
+            self.delete_bookmark(index);
+        }
+        if let Some(path) = response.export_camera_path {
+            self.export_camera_path(&path);
+        }
+        if let Some(path) = response.import_camera_path {
+            match self.import_camera_path(&path) {
+                Ok(()) => log::info!("Read camera path from {}", path.display()),
+                Err(err) => log::warn!("Failed to read {}: {:#}", path.display(), err),
+            }
+        }
+        if response.clear_tools {
+            self.clear_tools();
+        }
+        if response.clear_profile {
+            self.clear_profile();
+        }
+        if let Some((index, image)) = response.move_control_point {
+            if let Some(control) = self.drape_alignment.points.get_mut(index) {
+                control.image = image;
+            }
+        }
+        if let Some(index) = response.remove_control_point {
+            self.drape_alignment.points.remove(index);
+            self.grabbed_control_point = None;
+            self.upload_tool_overlay();
+        }
+        if response.clear_control_points {
+            self.drape_alignment.points.clear();
+            self.grabbed_control_point = None;
+            self.upload_tool_overlay();
+        }
+        if let Some(path) = response.export_catchment {
+            self.export_catchment(&path);
+        }
+        if response.finish_annotation {
+            self.finish_sketch();
+        }
+        if response.clear_annotations {
+            self.annotations.clear();
+            self.write_annotations();
+            self.sketch = None;
+            self.upload_tool_overlay();
+        }
+        if let Some(path) = response.export_annotations {
+            self.export_annotations(&path);
+        }
+        if let Some(statement) = response.run_algebra {
+            self.algebra_error = self
+                .run_algebra(&statement)
+                .err()
+                .map(|err| format!("{err:#}"));
+        }
+        if let Some(path) = response.save_fdf {
+            if let Err(err) = self.save_fdf(path.clone()) {
+                log::warn!("Failed to write {}: {:#}", path.display(), err);
+            }
         }
-
-        self.vertex_buffer = Some(self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&mesh.vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            },
-        ));
-
-        self.wireframe_index_buffer = Some(self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Wireframe Index Buffer"),
-                contents: bytemuck::cast_slice(&mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            },
-        ));
-
-        self.triangle_index_buffer = Some(self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Triangle Index Buffer"),
-                contents: bytemuck::cast_slice(&mesh.triangle_indices),
-                usage: wgpu::BufferUsages::INDEX,
-            },
-        ));
-
-        self.num_wireframe_indices = mesh.indices.len() as u32;
-        self.num_triangle_indices = mesh.triangle_indices.len() as u32;
-    }
-
-    /// Render a frame.
-    ///
-    /// Updates camera uniforms and draws the terrain based on current render mode.
-    ///
-    /// # Errors
-    ///
-    /// Returns [`wgpu::SurfaceError`] if surface acquisition fails.
-    pub fn render(&mut self, window: &Window) -> Result<(), wgpu::SurfaceError> {
-        // Update FPS counter
-        self.frame_count += 1;
-        let now = Instant::now();
-        let elapsed = now.duration_since(self.last_frame).as_secs_f32();
-        if elapsed >= 1.0 {
-            self.fps = self.frame_count as f32 / elapsed;
-            self.frame_count = 0;
-            self.last_frame = now;
+        self.update_line_of_sight();
+        if self.settings.points != self.prev_points {
+            self.upload_points();
+        }
+        if self.settings.vectors != self.prev_vectors {
+            self.upload_vectors();
+        }
+        if self.settings.uncertainty != self.prev_uncertainty {
+            if self.settings.uncertainty.channel != self.prev_uncertainty.channel {
+                self.upload_uncertainty_map();
+            }
+            self.upload_whiskers();
         }
 
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        // Update uniforms
-        let aspect = self.size.width as f32 / self.size.height as f32;
-
-        // Update wireframe uniforms
-        let mut wireframe_uniforms = WireframeUniforms::new();
-        wireframe_uniforms.update(&self.camera, aspect);
-        self.queue.write_buffer(
-            &self.wireframe_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[wireframe_uniforms]),
-        );
+        self.rebase_origin();
 
-        // Update solid uniforms
-        let mut solid_uniforms = SolidUniforms::new();
-        solid_uniforms.update(&self.camera, aspect, &self.lighting, &self.contour);
-        self.queue.write_buffer(
-            &self.solid_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[solid_uniforms]),
-        );
+        // Variants the settings can switch to compile ahead, a step a frame
+        self.step_warmup();
 
-        // Begin egui frame
-        let raw_input = self.egui_state.take_egui_input(window);
-        let egui_ctx = self.egui_state.egui_ctx().clone();
-        let full_output = egui_ctx.run(raw_input, |ctx| {
-            let response = self.ui.render(
-                ctx,
-                &mut self.camera,
-                &mut self.render_mode,
-                &mut self.color_scheme,
-                &mut self.gradient,
-                &mut self.lighting,
-                &mut self.contour,
-                &mut self.height_scale,
-                self.fps,
-            );
-            if response.reset_camera {
-                self.camera = Camera::new();
-            }
-        });
+        // Depth compare is baked into the pipelines
+        if self.settings.depth_mode != self.prev_depth_mode {
+            self.prev_depth_mode = self.settings.depth_mode;
+            self.reload_shaders();
+        }
+        // So is the sample count
+        if self.settings.msaa != self.prev_msaa {
+            self.prev_msaa = self.settings.msaa;
+            self.reload_shaders();
+        }
+        // The present mode is part of the surface configuration
+        if self.settings.pacing.present_mode != self.prev_present_mode {
+            self.prev_present_mode = self.settings.pacing.present_mode;
+            self.configure_present_mode();
+        }
 
-        // Regenerate mesh if color scheme, gradient, or height scale changed
-        if self.color_scheme != self.prev_color_scheme
-            || self.gradient != self.prev_gradient
-            || (self.height_scale - self.prev_height_scale).abs() > f32::EPSILON
+        // Regenerate mesh if color mode, index window, scheme, gradient, slope bands,
+        // gamma correction, file colors or their paint, edge density, ridgelines,
+        // ambient occlusion, relief, irradiance, earth model, or strategy changed, or
+        // the height scale changed and is baked into the geometry or occlusion.
+        // Other height scale changes only rescale the mesh in the shaders
+        let height_scale_changed =
+            (self.settings.height_scale - self.prev_height_scale).abs() > f32::EPSILON;
+        if self.settings.color_mode != self.prev_color_mode
+            || self.settings.index_radius != self.prev_index_radius
+            || self.settings.color_scheme != self.prev_color_scheme
+            || self.settings.gradient != self.prev_gradient
+            || self.settings.slope_bands != self.prev_slope_bands
+            || self.settings.gamma_correct != self.prev_gamma_correct
+            || self.settings.file_colors != self.prev_file_colors
+            || self.colors_painted
+            || (height_scale_changed
+                && (self.bakes_height_scale() || self.settings.ambient_occlusion))
+            || (self.settings.edge_density - self.prev_edge_density).abs() > f32::EPSILON
+            || self.settings.lod.enabled != self.prev_lod
+            || self.settings.ridgelines != self.prev_ridgelines
+            || self.settings.ambient_occlusion != self.prev_ambient_occlusion
+            || self.settings.relief != self.prev_relief
+            || self.settings.stitch != self.prev_stitch
+            || self.settings.denoise != self.prev_denoise
+            || self.settings.sun.irradiance_key() != self.prev_irradiance
+            || self.settings.earth_model != self.prev_earth_model
+            || self.settings.strategy != self.prev_strategy
+            || (self.settings.strategy == RenderStrategy::Tin
+                && self.settings.tin_tolerance != self.prev_tin_tolerance)
         {
             self.regenerate_mesh();
+        } else if height_scale_changed {
+            self.rescale_heights();
         }
+        // Layers moved in the UI
+        self.upload_layers(false);
+
+        // Keep drawing while anything moves, egui's animations included
+        let ui_animating = full_output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .is_some_and(|viewport| viewport.repaint_delay.is_zero());
+        self.busy = ui_animating
+            || self.settings.timeline.playing
+            || self.settings.auto_rotate.enabled
+            || self.recording.is_some()
+            || self.jobs.is_busy()
+            || !self.warmup.is_done()
+            || !motion::arrived(&self.view_camera, &self.camera);
 
         // Handle egui platform output (cursor changes, etc.)
-        self.egui_state
-            .handle_platform_output(window, full_output.platform_output);
+        if let (Some(state), Some(window)) = (&mut self.egui_state, window) {
+            state.handle_platform_output(window, full_output.platform_output);
+        }
 
         // Prepare egui for rendering
         let paint_jobs = egui_ctx.tessellate(full_output.shapes, full_output.pixels_per_point);
@@ -770,83 +6293,861 @@ impl Renderer {
             &screen_descriptor,
         );
 
-        // Begin render pass
+        // Full-resolution window drawn in place of the chunks it covers
+        let (chunks_x, chunks_z) = (self.terrain_data.as_ref()).map_or((1, 1), |terrain| {
+            detail::chunk_counts(terrain.width, terrain.height)
+        });
+        // While a loaded mesh is uploaded, only the indices written are
+        // drawn, as a whole, at full detail
+        let uploading = self.pending_indices.is_some();
+        let detail = self.detail.as_ref().filter(|_| {
+            !translucent_surface
+                && compare_sides.is_none()
+                && !uploading
+                && self.mesh_chunks.len() == chunks_x * chunks_z
+        });
+        let covered =
+            |chunk: u32| detail.is_some_and(|(window, _)| window.covers(chunk as usize, chunks_x));
+
+        // Chunks to draw and chunks to test, when culling
+        // A translucent surface hides nothing, so it can't cull, and each
+        // chunk can only be queried once per pass, so neither can a swipe
+        // or the chunks a window covers
+        let culler = self.occlusion.as_ref().filter(|_| {
+            self.settings.occlusion_culling
+                && !translucent_surface
+                && compare_sides.is_none()
+                && detail.is_none()
+                && !uploading
+        });
+        // Level of detail per chunk, from the eye's distance to its box
+        let lod = self.settings.lod;
+        let lod_levels: Option<Vec<usize>> = (lod.enabled
+            && !uploading
+            && self.mesh_chunks.iter().any(|chunk| !chunk.lods.is_empty()))
+        .then(|| {
+            let eye = self.view_camera.position();
+            let eye = eye.with_y(height.invert(eye.y));
+            self.mesh_chunks
+                .iter()
+                .map(|chunk| {
+                    let size = (chunk.max - chunk.min).xz().max_element();
+                    let distance = eye.distance(eye.clamp(chunk.min, chunk.max));
+                    lod.level(distance, size, chunk.lods.len())
+                })
+                .collect()
+        });
+        let level = |chunk: u32| {
+            lod_levels
+                .as_ref()
+                .map_or(0, |levels| levels[chunk as usize])
+        };
+        let all_chunks = 0..self.mesh_chunks.len() as u32;
+        // The GPU draw list only knows full-detail ranges
+        let indirect = match (&self.indirect_chunks, &self.cull_pipeline) {
+            (Some(chunks), Some(pipeline))
+                if self.settings.indirect_draw && lod_levels.is_none() && !uploading =>
+            {
+                Some((chunks, pipeline))
+            }
+            _ => None,
+        };
+        let (drawn_chunks, hidden_chunks) = match culler {
+            Some(culler) => {
+                let eye = self.view_camera.position();
+                let (drawn, hidden) = culler.partition(eye.with_y(height.invert(eye.y)));
+                (Some(drawn), hidden)
+            }
+            None => (None, Vec::new()),
+        };
+        self.chunks_drawn = drawn_chunks.as_ref().map(|drawn| drawn.len());
+
+        // The GPU draw list has no per-chunk draws to query, so test every
+        // chunk's box instead
+        let hidden_chunks = match (culler, indirect) {
+            (Some(culler), Some(_)) => (0..culler.chunk_count()).collect(),
+            _ => hidden_chunks,
+        };
+        if let Some((chunks, pipeline)) = indirect {
+            let visible = match &drawn_chunks {
+                Some(drawn) => {
+                    let mut visible = vec![false; self.mesh_chunks.len()];
+                    for &chunk in drawn {
+                        visible[chunk as usize] = true;
+                    }
+                    Some(visible)
+                }
+                None => detail.map(|_| all_chunks.clone().map(|chunk| !covered(chunk)).collect()),
+            };
+            chunks.update(
+                &self.queue,
+                view_proj,
+                (curvature_coef, curvature_center),
+                height,
+                visible.as_deref(),
+            );
+            chunks.dispatch(&mut encoder, pipeline);
+        }
+
+        // Depth from the sun, sampled by the solid pass
+        if shadow_view_proj.is_some() {
+            if let (Some(vertex_buffer), Some(triangle_index_buffer)) =
+                (&self.vertex_buffer, &self.triangle_index_buffer)
+            {
+                self.shadow_map.render(
+                    &mut encoder,
+                    vertex_buffer,
+                    (triangle_index_buffer, self.index_format),
+                    self.num_triangle_indices,
+                );
+            }
+        }
+
+        // Begin render pass, multisampled and resolved into the frame when
+        // antialiasing
+        let (scene_view, resolve_target) = self.msaa.color_attachment(&view);
+        let mut terrain_draws = 0;
         {
-            let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: scene_view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.1,
-                            b: 0.15,
-                            a: 1.0,
+                        load: wgpu::LoadOp::Clear(if self.transparent {
+                            wgpu::Color::TRANSPARENT
+                        } else {
+                            // sRGB surfaces take linear clear colors
+                            let [r, g, b] = self.settings.background.map(|c| {
+                                if self.config.format.is_srgb() {
+                                    srgb_to_linear(c) as f64
+                                } else {
+                                    c as f64
+                                }
+                            });
+                            wgpu::Color { r, g, b, a: 1.0 }
                         }),
                         store: wgpu::StoreOp::Store,
                     },
                     depth_slice: None,
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_view,
+                    view: self.msaa.depth_attachment(&self.depth_view),
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(self.settings.depth_mode.clear_value()),
                         store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
                 }),
-                occlusion_query_set: None,
+                occlusion_query_set: culler.map(|culler| culler.query_set()),
                 timestamp_writes: None,
             });
 
-            // Convert to 'static lifetime for egui compatibility
-            let mut render_pass = render_pass.forget_lifetime();
+            // Sky or environment behind everything, unless the desktop
+            // shows through
+            let background = self.settings.sky.enabled || scene_uniforms.shows_environment();
+            if background && !self.transparent {
+                self.sky.draw(&mut render_pass);
+            }
 
-            // Draw terrain based on render mode
+            // Draw terrain based on render mode, on each side of the divider
+            // when comparing
+            let current = (
+                self.settings.render_mode,
+                &self.solid_bind_group,
+                &self.grid_bind_group,
+            );
+            let sides = match compare_sides {
+                Some((snapshot_mode, [left, right])) => {
+                    let snapshot = (
+                        snapshot_mode,
+                        self.compare.solid_bind_group(),
+                        self.compare.wireframe_bind_group(),
+                    );
+                    [
+                        left.map(|rect| (Some(rect), snapshot)),
+                        right.map(|rect| (Some(rect), current)),
+                    ]
+                }
+                None => [Some((None, current)), None],
+            };
             if let Some(vertex_buffer) = &self.vertex_buffer {
-                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                for (rect, (render_mode, solid_bind_group, wireframe_bind_group)) in
+                    sides.into_iter().flatten()
+                {
+                    if let Some((x, y, width, height)) = rect {
+                        render_pass.set_scissor_rect(x, y, width, height);
+                    }
+                    let wireframe = matches!(render_mode, RenderMode::Wireframe | RenderMode::Both);
+
+                    // Draw solid first (if applicable)
+                    let solid = matches!(render_mode, RenderMode::Solid | RenderMode::Both)
+                        && !translucent_surface;
+                    if solid {
+                        if let Some(triangle_index_buffer) = &self.triangle_index_buffer {
+                            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                            render_pass.set_pipeline(&self.solid_pipeline);
+                            render_pass.set_bind_group(0, solid_bind_group, &[]);
+                            render_pass.set_index_buffer(
+                                triangle_index_buffer.slice(..),
+                                self.index_format,
+                            );
+                            match (&drawn_chunks, indirect) {
+                                (_, Some((chunks, _))) => {
+                                    chunks.draw_triangles(&mut render_pass);
+                                    terrain_draws += 1;
+                                }
+                                (Some(drawn), None) => {
+                                    for &chunk in drawn {
+                                        let range = self.mesh_chunks[chunk as usize]
+                                            .triangles_at(level(chunk));
+                                        render_pass.begin_occlusion_query(chunk);
+                                        render_pass.draw_indexed(range, 0, 0..1);
+                                        render_pass.end_occlusion_query();
+                                    }
+                                    terrain_draws += drawn.len();
+                                }
+                                (None, None) if lod_levels.is_some() || detail.is_some() => {
+                                    for chunk in all_chunks.clone().filter(|&c| !covered(c)) {
+                                        let range = self.mesh_chunks[chunk as usize]
+                                            .triangles_at(level(chunk));
+                                        render_pass.draw_indexed(range, 0, 0..1);
+                                        terrain_draws += 1;
+                                    }
+                                }
+                                (None, None) => {
+                                    render_pass.draw_indexed(0..self.num_triangle_indices, 0, 0..1);
+                                    terrain_draws += 1;
+                                }
+                            }
+                            if let Some((_, patch)) = detail {
+                                patch.draw_triangles(&mut render_pass);
+                                terrain_draws += 1;
+                            }
+                        }
+                    }
 
-                // Draw solid first (if applicable)
-                if matches!(self.render_mode, RenderMode::Solid | RenderMode::Both) {
-                    if let Some(triangle_index_buffer) = &self.triangle_index_buffer {
-                        render_pass.set_pipeline(&self.solid_pipeline);
-                        render_pass.set_bind_group(0, &self.solid_bind_group, &[]);
-                        render_pass.set_index_buffer(
-                            triangle_index_buffer.slice(..),
-                            wgpu::IndexFormat::Uint32,
-                        );
-                        render_pass.draw_indexed(0..self.num_triangle_indices, 0, 0..1);
+                    // Draw wireframe on top (if applicable)
+                    if wireframe {
+                        if let Some(wireframe_index_buffer) = &self.wireframe_index_buffer {
+                            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                            render_pass.set_pipeline(match self.line_topology {
+                                LineTopology::List => &self.wireframe_pipeline,
+                                LineTopology::Strip => self.strip_pipelines.get(self.index_format),
+                            });
+                            render_pass.set_bind_group(0, wireframe_bind_group, &[]);
+                            render_pass.set_index_buffer(
+                                wireframe_index_buffer.slice(..),
+                                self.index_format,
+                            );
+                            match (&drawn_chunks, indirect) {
+                                (_, Some((chunks, _))) => {
+                                    chunks.draw_lines(&mut render_pass);
+                                    terrain_draws += 1;
+                                }
+                                (Some(drawn), None) => {
+                                    // Lines carry the queries when there's no solid surface
+                                    for &chunk in drawn {
+                                        let range =
+                                            self.mesh_chunks[chunk as usize].lines_at(level(chunk));
+                                        if !solid {
+                                            render_pass.begin_occlusion_query(chunk);
+                                        }
+                                        render_pass.draw_indexed(range, 0, 0..1);
+                                        if !solid {
+                                            render_pass.end_occlusion_query();
+                                        }
+                                    }
+                                    terrain_draws += drawn.len();
+                                }
+                                (None, None) if lod_levels.is_some() || detail.is_some() => {
+                                    for chunk in all_chunks.clone().filter(|&c| !covered(c)) {
+                                        let range =
+                                            self.mesh_chunks[chunk as usize].lines_at(level(chunk));
+                                        render_pass.draw_indexed(range, 0, 0..1);
+                                        terrain_draws += 1;
+                                    }
+                                }
+                                (None, None) => {
+                                    render_pass.draw_indexed(
+                                        0..self.num_wireframe_indices,
+                                        0,
+                                        0..1,
+                                    );
+                                    terrain_draws += 1;
+                                }
+                            }
+                            // The window's lines are segment pairs
+                            if let Some((_, patch)) = detail {
+                                render_pass.set_pipeline(&self.wireframe_pipeline);
+                                patch.draw_lines(&mut render_pass);
+                                terrain_draws += 1;
+                            }
+                        }
                     }
                 }
+                render_pass.set_scissor_rect(0, 0, self.size.width, self.size.height);
+
+                // Test the bounding boxes of chunks hidden last time
+                if let Some(culler) = culler {
+                    render_pass.set_pipeline(&self.occlusion_pipeline);
+                    render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+                    culler.draw_boxes(&mut render_pass, &hidden_chunks);
+                }
+            }
+
+            if let Some(clipmap) = &self.clipmap {
+                render_pass.set_pipeline(&self.clipmap_pipeline);
+                clipmap.draw(&mut render_pass);
+            }
+
+            // Other loaded terrains, whole and opaque
+            let visible_layers = || {
+                self.layers
+                    .iter()
+                    .zip(&self.settings.layers)
+                    .filter(|(_, config)| config.visible)
+                    .map(|(layer, _)| layer)
+            };
+            let render_mode = self.settings.render_mode;
+            if matches!(render_mode, RenderMode::Solid | RenderMode::Both) {
+                render_pass.set_pipeline(&self.solid_pipeline);
+                render_pass.set_bind_group(0, self.layer_uniforms.solid_bind_group(), &[]);
+                for layer in visible_layers() {
+                    layer.draw_triangles(&mut render_pass);
+                }
+            }
+            if matches!(render_mode, RenderMode::Wireframe | RenderMode::Both) {
+                render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(0, self.layer_uniforms.wireframe_bind_group(), &[]);
+                for layer in visible_layers() {
+                    layer.draw_lines(&mut render_pass);
+                }
+            }
+
+            // Draw the ridgeline overlay, if any
+            if let (Some(vertex_buffer), Some(index_buffer)) =
+                (&self.ridgeline_vertex_buffer, &self.ridgeline_index_buffer)
+            {
+                render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..self.num_ridgeline_indices, 0, 0..1);
+            }
+
+            // Draw the vector field overlay, if any
+            if let (Some(vertex_buffer), Some(index_buffer)) =
+                (&self.vector_vertex_buffer, &self.vector_index_buffer)
+            {
+                render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..self.num_vector_indices, 0, 0..1);
+            }
+
+            // Draw the point markers, if any
+            if let (Some(vertex_buffer), Some(index_buffer)) =
+                (&self.point_vertex_buffer, &self.point_index_buffer)
+            {
+                render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..self.num_point_indices, 0, 0..1);
+            }
+
+            // Draw the uncertainty whiskers, if any
+            if let (Some(vertex_buffer), Some(index_buffer)) =
+                (&self.whisker_vertex_buffer, &self.whisker_index_buffer)
+            {
+                render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..self.num_whisker_indices, 0, 0..1);
+            }
+
+            // Draw the tool result overlay, if any
+            if let (Some(vertex_buffer), Some(index_buffer)) =
+                (&self.tool_vertex_buffer, &self.tool_index_buffer)
+            {
+                render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..self.num_tool_indices, 0, 0..1);
+            }
+
+            // Draw the captured frustum, if any
+            if let (Some(vertex_buffer), Some(index_buffer)) =
+                (&self.frustum_vertex_buffer, &self.frustum_index_buffer)
+            {
+                render_pass.set_pipeline(&self.wireframe_pipeline);
+                render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..FRUSTUM_LINE_INDICES.len() as u32, 0, 0..1);
+            }
+
+            // Draw the ground grid over the opaque scene, if enabled
+            if ground_grid.is_some() {
+                self.ground_grid.draw(&mut render_pass);
+            }
+        }
+        self.msaa.resolve_depth(&mut encoder, &self.depth_view);
 
-                // Draw wireframe on top (if applicable)
-                if matches!(self.render_mode, RenderMode::Wireframe | RenderMode::Both) {
-                    if let Some(wireframe_index_buffer) = &self.wireframe_index_buffer {
-                        render_pass.set_pipeline(&self.wireframe_pipeline);
-                        render_pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
-                        render_pass.set_index_buffer(
-                            wireframe_index_buffer.slice(..),
-                            wgpu::IndexFormat::Uint32,
-                        );
-                        render_pass.draw_indexed(0..self.num_wireframe_indices, 0, 0..1);
+        // Translucent layers. Normal layers are blended over the opaque
+        // scene regardless of the order they're drawn in; layers with other
+        // blend modes follow in order, each combined with the scene so far
+        let surface_layer = match (&self.vertex_buffer, &self.triangle_index_buffer) {
+            (Some(vertex_buffer), Some(index_buffer)) if translucent_surface => {
+                Some((vertex_buffer, index_buffer))
+            }
+            _ => None,
+        };
+        let can_blend = self.config.usage.contains(wgpu::TextureUsages::COPY_SRC);
+        let layer_mode = |mode| if can_blend { mode } else { BlendMode::Normal };
+        let layers: [(bool, BlendMode); BLEND_LAYERS] = [
+            (
+                surface_layer.is_some(),
+                layer_mode(self.settings.surface_blend),
+            ),
+            (water_bounds.is_some(), layer_mode(water.blend)),
+        ];
+        let draw_layer = |pass: &mut wgpu::RenderPass, index: usize| {
+            pass.set_pipeline(&self.oit_layer_pipeline);
+            match (index, surface_layer) {
+                (0, Some((vertex_buffer, index_buffer))) => {
+                    pass.set_bind_group(0, &self.solid_bind_group, &[]);
+                    pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                    pass.set_index_buffer(index_buffer.slice(..), self.index_format);
+                    match indirect {
+                        Some((chunks, _)) => chunks.draw_triangles(pass),
+                        None if lod_levels.is_some() => {
+                            for chunk in all_chunks.clone() {
+                                let range =
+                                    self.mesh_chunks[chunk as usize].triangles_at(level(chunk));
+                                pass.draw_indexed(range, 0, 0..1);
+                            }
+                        }
+                        None => pass.draw_indexed(0..self.num_triangle_indices, 0, 0..1),
                     }
                 }
+                (0, None) => {}
+                _ => {
+                    pass.set_bind_group(0, &self.water_bind_group, &[]);
+                    pass.set_vertex_buffer(0, self.water_vertex_buffer.slice(..));
+                    pass.set_index_buffer(
+                        self.water_index_buffer.slice(..),
+                        wgpu::IndexFormat::Uint32,
+                    );
+                    pass.draw_indexed(0..WATER_INDICES.len() as u32, 0, 0..1);
+                }
+            }
+        };
+
+        let oit_layers: Vec<usize> = (0..BLEND_LAYERS)
+            .filter(|&index| layers[index] == (true, BlendMode::Normal))
+            .collect();
+        if !oit_layers.is_empty() {
+            {
+                let mut layer_pass = self.oit.begin_layers(&mut encoder, &self.depth_view);
+                for &index in &oit_layers {
+                    draw_layer(&mut layer_pass, index);
+                }
+            }
+            self.oit.composite(&mut encoder, &view);
+        }
+        for (index, &(shown, mode)) in layers.iter().enumerate() {
+            if !shown || mode == BlendMode::Normal {
+                continue;
+            }
+            {
+                let mut layer_pass = self
+                    .blend
+                    .begin_layer(index, &mut encoder, &self.depth_view);
+                draw_layer(&mut layer_pass, index);
+            }
+            self.blend.composite(
+                index,
+                mode,
+                &self.queue,
+                &mut encoder,
+                &frame_texture,
+                &view,
+            );
+        }
+
+        // Overview inset over the finished scene, in its own viewport
+        if let Some(viewport) = overview_viewport {
+            let (solid_pipeline, wireframe_pipeline, strip_pipelines) =
+                match &self.overview_pipelines {
+                    Some((solid, wireframe, strips)) => (solid, wireframe, strips),
+                    None => (
+                        &self.solid_pipeline,
+                        &self.wireframe_pipeline,
+                        &self.strip_pipelines,
+                    ),
+                };
+            let mut overview_pass = self.overview.begin(
+                &mut encoder,
+                &view,
+                &self.depth_view,
+                self.settings.depth_mode,
+                viewport,
+            );
+            if let Some(vertex_buffer) = &self.vertex_buffer {
+                overview_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                if let (true, Some(index_buffer)) = (solid, &self.triangle_index_buffer) {
+                    overview_pass.set_pipeline(solid_pipeline);
+                    overview_pass.set_bind_group(0, self.overview.solid_bind_group(), &[]);
+                    overview_pass.set_index_buffer(index_buffer.slice(..), self.index_format);
+                    overview_pass.draw_indexed(0..self.num_triangle_indices, 0, 0..1);
+                }
+                let wireframe = matches!(
+                    self.settings.render_mode,
+                    RenderMode::Wireframe | RenderMode::Both
+                );
+                if let (true, Some(index_buffer)) = (wireframe, &self.wireframe_index_buffer) {
+                    overview_pass.set_pipeline(match self.line_topology {
+                        LineTopology::List => wireframe_pipeline,
+                        LineTopology::Strip => strip_pipelines.get(self.index_format),
+                    });
+                    overview_pass.set_bind_group(0, self.overview.wireframe_bind_group(), &[]);
+                    overview_pass.set_index_buffer(index_buffer.slice(..), self.index_format);
+                    overview_pass.draw_indexed(0..self.num_wireframe_indices, 0, 0..1);
+                }
             }
+            overview_pass.set_pipeline(wireframe_pipeline);
+            self.overview.draw_marker(&mut overview_pass);
+        }
 
-            // Render egui UI
+        // Bloom and simulated color vision cover the scene but not the UI
+        if can_blend && self.settings.bloom.enabled {
+            self.bloom.apply(
+                &self.settings.bloom,
+                &self.queue,
+                &mut encoder,
+                &frame_texture,
+                &view,
+            );
+        }
+        if can_blend && self.settings.color_vision != ColorVision::Normal {
+            self.colorblind.apply(
+                self.settings.color_vision,
+                &self.queue,
+                &mut encoder,
+                &frame_texture,
+                &view,
+            );
+        }
+
+        // Render egui UI on top of everything
+        if !self.capturing {
+            let ui_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("UI Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                // egui's pipeline is built for the depth format, though it
+                // neither tests nor writes depth
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            // Convert to 'static lifetime for egui compatibility
+            let mut ui_pass = ui_pass.forget_lifetime();
             self.egui_renderer
-                .render(&mut render_pass, &paint_jobs, &screen_descriptor);
+                .render(&mut ui_pass, &paint_jobs, &screen_descriptor);
         }
 
         for id in &full_output.textures_delta.free {
             self.egui_renderer.free_texture(id);
         }
 
+        if let Some(culler) = self
+            .occlusion
+            .as_mut()
+            .filter(|_| self.settings.occlusion_culling)
+        {
+            culler.resolve(&mut encoder);
+        }
+
         // Submit commands and present
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
+        self.latency.frame_presented(acquire_wait, Instant::now());
+        self.log_frame(FrameSample {
+            started: now,
+            finished: Instant::now(),
+            draws: terrain_draws,
+            chunks: self.mesh_chunks.len(),
+            culled: self
+                .chunks_drawn
+                .map_or(0, |drawn| self.mesh_chunks.len().saturating_sub(drawn)),
+        });
+
+        if let Some(culler) = &mut self.occlusion {
+            culler.after_submit();
+        }
+
+        if let Some(path) = self.pending_turntable.take() {
+            self.export_turntable(&path);
+        }
+        if std::mem::take(&mut self.pending_screenshot) {
+            self.save_screenshot();
+        }
+
+        Ok(())
+    }
+
+    /// Whether something moved in the last frame, like a playing timeline,
+    /// a gliding camera or a running job, so the next frame shouldn't wait
+    /// for input when power saving.
+    pub fn is_busy(&self) -> bool {
+        self.busy
+    }
+
+    /// Note an input event arriving at `at`, for the input-to-present
+    /// latency.
+    pub fn note_input(&mut self, at: Instant) {
+        self.latency.input(at);
+    }
 
+    /// Recent input-to-present latency and surface acquire waits.
+    pub fn latency(&self) -> &LatencyStats {
+        &self.latency
+    }
+
+    /// Write each frame's timings, draw calls, culled chunks and memory to
+    /// a CSV file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created.
+    pub fn log_performance(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        let about = format!("lrle {CURRENT_VERSION} on {}", self.adapter_name);
+        self.perf_log = Some(PerfLog::create(path, &about)?);
+        log::info!("Logging frame timings to {}", path.display());
         Ok(())
     }
+
+    /// Write out the performance log's buffered rows.
+    pub fn finish_perf_log(&mut self) {
+        if let Some(Err(err)) = self.perf_log.as_mut().map(PerfLog::flush) {
+            log::warn!("Failed to write the performance log: {}", err);
+        }
+    }
+
+    /// Add `frame` to the performance log, if one is kept. A log that
+    /// fails to write is dropped rather than retried every frame.
+    fn log_frame(&mut self, frame: FrameSample) {
+        let Some(log) = &mut self.perf_log else {
+            return;
+        };
+        let device = &self.device;
+        if let Err(err) = log.record(&frame, || Memory::sample(device)) {
+            log::warn!("Stopped the performance log: {}", err);
+            self.perf_log = None;
+        }
+    }
+
+    /// Reconfigure the window surface with the chosen present mode, or the
+    /// closest one it supports.
+    fn configure_present_mode(&mut self) {
+        let FrameTarget::Surface(surface) = &self.target else {
+            return;
+        };
+        let supported: Vec<_> = self
+            .present_modes
+            .iter()
+            .map(|mode| mode.to_wgpu())
+            .collect();
+        let mode = self.settings.pacing.present_mode.fit(&supported);
+        if mode != self.settings.pacing.present_mode.to_wgpu() {
+            log::warn!(
+                "{} presentation is unsupported, using {:?}",
+                self.settings.pacing.present_mode.label(),
+                mode
+            );
+        }
+        self.config.present_mode = mode;
+        surface.configure(&self.device, &self.config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec4;
+
+    #[test]
+    fn test_reversed_depth_remap() {
+        let remap = DepthMode::Reversed.clip_remap();
+        let near = remap * Vec4::new(0.0, 0.0, 0.0, 2.0);
+        let far = remap * Vec4::new(0.0, 0.0, 2.0, 2.0);
+        let mid = remap * Vec4::new(1.0, -1.0, 0.5, 2.0);
+
+        assert_eq!(near.z / near.w, 1.0);
+        assert_eq!(far.z / far.w, 0.0);
+        assert_eq!(mid, Vec4::new(1.0, -1.0, 1.5, 2.0));
+    }
+
+    #[test]
+    fn test_depth_mode_clear_is_far_plane() {
+        for mode in [DepthMode::Standard, DepthMode::Reversed] {
+            let remap = mode.clip_remap();
+            let far = remap * Vec4::new(0.0, 0.0, 1.0, 1.0);
+            assert_eq!(far.z, mode.clear_value());
+        }
+        assert_eq!(DepthMode::Standard.clip_remap(), Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn test_pipelines_build_for_every_depth_mode() {
+        // Skipped without an adapter
+        let instance = wgpu::Instance::default();
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&Default::default())) else {
+            return;
+        };
+        let (device, _queue) = pollster::block_on(adapter.request_device(&Default::default()))
+            .expect("Failed to create device");
+        let sources = shaders::load(None).unwrap();
+        let wireframe_bind_group_layout = create_wireframe_bind_group_layout(
+            &device,
+            params::layout_entry(),
+            atmosphere::layout_entry(),
+        );
+        let solid_bind_group_layout = create_solid_bind_group_layout(
+            &device,
+            params::layout_entry(),
+            atmosphere::layout_entry(),
+        );
+        let pipeline_layout = |bind_group_layout| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[bind_group_layout],
+                push_constant_ranges: &[],
+            })
+        };
+        let wireframe_layout = pipeline_layout(&wireframe_bind_group_layout);
+        let solid_layout = pipeline_layout(&solid_bind_group_layout);
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+        for mode in [
+            DepthMode::Standard,
+            DepthMode::Reversed,
+            DepthMode::Logarithmic,
+        ] {
+            // Logarithmic depth reads the uniforms in the fragment stage
+            // too, which the wireframe layout has to expose
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            create_wireframe_pipeline(
+                &device,
+                None,
+                &wireframe_layout,
+                format,
+                mode,
+                1,
+                &sources.wireframe,
+                None,
+            );
+            StripPipelines::new(
+                &device,
+                None,
+                &wireframe_layout,
+                format,
+                mode,
+                1,
+                &sources.wireframe,
+            );
+            create_solid_pipeline(
+                &device,
+                None,
+                &solid_layout,
+                format,
+                mode,
+                1,
+                &sources.solid,
+            );
+            let error = pollster::block_on(device.pop_error_scope());
+            assert!(error.is_none(), "{mode:?}: {}", error.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_blend_mode_shader_indices() {
+        // blend.wgsl treats index 0 as normal and the rest by position
+        for (i, mode) in BlendMode::ALL.into_iter().enumerate() {
+            assert_eq!(mode.shader_index(), i as u32);
+        }
+        assert_eq!(BlendMode::default(), BlendMode::Normal);
+    }
+
+    #[test]
+    fn test_transparent_alpha_mode() {
+        use wgpu::CompositeAlphaMode::*;
+
+        assert_eq!(transparent_alpha_mode(&[Opaque]), None);
+        assert_eq!(
+            transparent_alpha_mode(&[Opaque, PostMultiplied, PreMultiplied]),
+            Some(PreMultiplied)
+        );
+        assert_eq!(transparent_alpha_mode(&[Opaque, Inherit]), Some(Inherit));
+    }
+
+    #[test]
+    fn test_log_depth_coef() {
+        let mut camera = Camera::new();
+        camera.far = 1023.0;
+
+        assert_eq!(DepthMode::Logarithmic.log_depth_coef(&camera), 0.1);
+        assert_eq!(DepthMode::Reversed.log_depth_coef(&camera), 0.0);
+
+        // Orthographic keeps linear depth
+        camera.projection = Projection::Orthographic;
+        assert_eq!(DepthMode::Logarithmic.log_depth_coef(&camera), 0.0);
+    }
+
+    #[test]
+    fn test_curvature_drop() {
+        // Terrain 10 km away sits ~7.85 m below the horizon plane
+        let k = EarthModel::Curved.curvature_coef();
+        assert!((k * 10_000.0f32.powi(2) - 7.85).abs() < 0.01);
+
+        assert_eq!(EarthModel::Flat.curvature_coef(), 0.0);
+        assert_eq!(EarthModel::Globe.curvature_coef(), 0.0);
+    }
+
+    #[test]
+    fn test_height_transform_matches_rebuilt_mesh() {
+        // A mesh built at scale 1 around an origin 40 units up, shown at 3
+        let (height, origin_y) = (25.0, 40.0);
+        let transform = HeightTransform::between(1.0, 3.0, origin_y);
+        let world = transform.apply((height - origin_y) as f32);
+        assert!((world - (height * 3.0 - origin_y) as f32).abs() < 1e-4);
+        assert!((transform.invert(world) - (height - origin_y) as f32).abs() < 1e-4);
+
+        assert_eq!(
+            HeightTransform::between(2.0, 2.0, origin_y),
+            HeightTransform::IDENTITY
+        );
+    }
 }
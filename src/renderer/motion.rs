@@ -0,0 +1,139 @@
+//! Smoothed camera motion.
+//!
+//! Input, the UI and view changes such as "Reset Camera" move the camera to
+//! its new pose at once. Frames are drawn from a second camera that follows
+//! it with exponential smoothing, so orbiting, zooming and panning ease out
+//! after the mouse stops and jumps between views become short flights.
+
+use super::camera::Camera;
+
+/// Camera smoothing settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionConfig {
+    /// Whether the drawn camera glides after the camera instead of
+    /// matching it every frame
+    pub enabled: bool,
+    /// Seconds to cover about two thirds of the remaining way
+    pub response: f32,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            response: 0.12,
+        }
+    }
+}
+
+/// Change below which the view counts as arrived, relative to the distance
+/// for positions and in radians or degrees for angles.
+const SETTLED: f32 = 1e-4;
+
+impl MotionConfig {
+    /// Move `view` toward `goal` for a frame `dt` seconds long. Settings
+    /// that don't animate, such as the projection, are taken from `goal`.
+    pub fn follow(&self, view: &mut Camera, goal: &Camera, dt: f32) {
+        if !self.enabled || self.response <= 0.0 {
+            *view = goal.clone();
+            return;
+        }
+
+        // Azimuths wrap around: turn the short way
+        let turn = (goal.azimuth - view.azimuth + std::f32::consts::PI)
+            .rem_euclid(std::f32::consts::TAU)
+            - std::f32::consts::PI;
+        let zoom = (goal.distance / view.distance).ln();
+        let shift = goal.target - view.target;
+        let settled = turn.abs() < SETTLED
+            && zoom.abs() < SETTLED
+            && shift.length() < goal.distance * SETTLED
+            && (goal.elevation - view.elevation).abs() < SETTLED
+            && (goal.fov - view.fov).abs() < SETTLED;
+        if settled || !zoom.is_finite() {
+            *view = goal.clone();
+            return;
+        }
+
+        // Same share of the way each second, whatever the frame rate
+        let t = 1.0 - (-dt.max(0.0) / self.response).exp();
+        *view = Camera {
+            target: view.target + shift * t,
+            // Zoom in equal ratios, so it eases alike near and far
+            distance: view.distance * (zoom * t).exp(),
+            azimuth: view.azimuth + turn * t,
+            elevation: view.elevation + (goal.elevation - view.elevation) * t,
+            fov: view.fov + (goal.fov - view.fov) * t,
+            ..goal.clone()
+        };
+    }
+}
+
+/// Whether `view` has arrived at `goal`, which [`MotionConfig::follow`]
+/// ends by copying it exactly.
+pub fn arrived(view: &Camera, goal: &Camera) -> bool {
+    view.target == goal.target
+        && view.distance == goal.distance
+        && view.azimuth == goal.azimuth
+        && view.elevation == goal.elevation
+        && view.fov == goal.fov
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_view_glides_to_goal() {
+        let motion = MotionConfig::default();
+        let mut view = Camera::new();
+        let goal = Camera {
+            target: Vec3::new(10.0, 0.0, 0.0),
+            distance: 200.0,
+            ..Camera::new()
+        };
+
+        // One response time covers about two thirds of the way
+        motion.follow(&mut view, &goal, motion.response);
+        assert!((view.target.x - 10.0 * (1.0 - (-1f32).exp())).abs() < 1e-4);
+        assert!(view.distance > 50.0 && view.distance < 200.0);
+
+        for _ in 0..200 {
+            motion.follow(&mut view, &goal, 1.0 / 60.0);
+        }
+        assert_eq!(view.target, goal.target);
+        assert_eq!(view.distance, goal.distance);
+        assert!(arrived(&view, &goal));
+
+        // Disabled, the view matches at once
+        let mut view = Camera::new();
+        let still = MotionConfig {
+            enabled: false,
+            ..motion
+        };
+        still.follow(&mut view, &goal, 0.0);
+        assert_eq!(view.distance, goal.distance);
+    }
+
+    #[test]
+    fn test_azimuth_turns_the_short_way() {
+        let motion = MotionConfig::default();
+        let mut view = Camera {
+            azimuth: 0.1,
+            ..Camera::new()
+        };
+        // Several turns on, but just 0.2 radians the other way round
+        let goal = Camera {
+            azimuth: 3.0 * std::f32::consts::TAU - 0.1,
+            ..Camera::new()
+        };
+        motion.follow(&mut view, &goal, 0.01);
+        assert!(view.azimuth < 0.1 && view.azimuth > -0.1);
+
+        for _ in 0..200 {
+            motion.follow(&mut view, &goal, 1.0 / 60.0);
+        }
+        assert_eq!(view.azimuth, goal.azimuth);
+    }
+}
@@ -0,0 +1,293 @@
+//! Multisample antialiasing of the main scene pass.
+//!
+//! With more than one sample, the opaque scene (surface, wireframe lines
+//! and line overlays) is drawn into multisampled color and depth targets,
+//! and the color is resolved into the frame as the pass ends. Translucent
+//! layers, the overview inset and the UI are drawn single-sampled after it,
+//! so a fullscreen pass then copies the first depth sample of each pixel
+//! into the ordinary depth buffer for them to test against.
+//!
+//! The sample count is baked into the main pass's pipelines, which are
+//! rebuilt when it changes.
+
+use super::DEPTH_FORMAT;
+
+/// Sample counts offered, where the adapter supports them.
+pub const SAMPLE_COUNTS: [u32; 3] = [1, 4, 8];
+
+/// Sample counts of [`SAMPLE_COUNTS`] that `adapter` can render `format`
+/// color and the depth buffer with. Counts other than 4 need adapter
+/// specific format features, which the device must have been created with.
+pub fn supported_counts(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> Vec<u32> {
+    let specific = adapter
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    let color = adapter.get_texture_format_features(format).flags;
+    let depth = adapter.get_texture_format_features(DEPTH_FORMAT).flags;
+    SAMPLE_COUNTS
+        .into_iter()
+        .filter(|&count| {
+            count == 1
+                || ((specific || count == 4)
+                    && color.sample_count_supported(count)
+                    && depth.sample_count_supported(count))
+        })
+        .collect()
+}
+
+/// The largest of the `supported` counts that doesn't exceed `requested`.
+pub fn fit_count(requested: u32, supported: &[u32]) -> u32 {
+    supported
+        .iter()
+        .copied()
+        .filter(|&count| count <= requested)
+        .max()
+        .unwrap_or(1)
+}
+
+/// Multisampled targets of the main pass.
+struct Targets {
+    color_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    /// Binds the multisampled depth for the resolve pass
+    bind_group: wgpu::BindGroup,
+}
+
+/// Multisampled targets and the depth resolve pipeline.
+pub struct Msaa {
+    samples: u32,
+    counts: Vec<u32>,
+    format: wgpu::TextureFormat,
+    targets: Option<Targets>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Msaa {
+    /// Start single-sampled, with the sample `counts` the adapter supports
+    /// for a surface of `format`, and the resolve pipeline from
+    /// `depth_resolve.wgsl` source.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        counts: Vec<u32>,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: true,
+                },
+                count: None,
+            }],
+            label: Some("Depth Resolve Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Resolve Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_resolve_pipeline(device, cache, &pipeline_layout, source);
+
+        Self {
+            samples: 1,
+            counts,
+            format,
+            targets: None,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Current sample count.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Sample counts the adapter supports, in increasing order.
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// Switch to `samples` per pixel, creating or dropping the targets.
+    /// The main pass's pipelines must be rebuilt to match.
+    pub fn set_samples(&mut self, device: &wgpu::Device, samples: u32, width: u32, height: u32) {
+        self.samples = samples;
+        self.resize(device, width, height);
+    }
+
+    /// Recreate the targets for a new surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = (self.samples > 1).then(|| {
+            let texture = |label, format, usage| {
+                device
+                    .create_texture(&wgpu::TextureDescriptor {
+                        label: Some(label),
+                        size: wgpu::Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: self.samples,
+                        dimension: wgpu::TextureDimension::D2,
+                        format,
+                        usage,
+                        view_formats: &[],
+                    })
+                    .create_view(&wgpu::TextureViewDescriptor::default())
+            };
+            let color_view = texture(
+                "Multisampled Color Texture",
+                self.format,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            );
+            let depth_view = texture(
+                "Multisampled Depth Texture",
+                DEPTH_FORMAT,
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            );
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                }],
+                label: Some("Depth Resolve Bind Group"),
+            });
+            Targets {
+                color_view,
+                depth_view,
+                bind_group,
+            }
+        });
+    }
+
+    /// Build a resolve pipeline from new shader source, to be installed
+    /// with [`set_pipeline`](Self::set_pipeline) once it validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_resolve_pipeline(device, cache, &self.pipeline_layout, source)
+    }
+
+    /// Replace the resolve pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Color attachment view and resolve target of the main pass drawing
+    /// into `frame`.
+    pub fn color_attachment<'a>(
+        &'a self,
+        frame: &'a wgpu::TextureView,
+    ) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+        match &self.targets {
+            Some(targets) => (&targets.color_view, Some(frame)),
+            None => (frame, None),
+        }
+    }
+
+    /// Depth attachment of the main pass, `depth_view` when single-sampled.
+    pub fn depth_attachment<'a>(
+        &'a self,
+        depth_view: &'a wgpu::TextureView,
+    ) -> &'a wgpu::TextureView {
+        self.targets
+            .as_ref()
+            .map_or(depth_view, |targets| &targets.depth_view)
+    }
+
+    /// Copy the main pass's depth into `depth_view`, when multisampled.
+    pub fn resolve_depth(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Resolve Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &targets.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Build the fullscreen pipeline writing every pixel's depth.
+fn create_resolve_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Depth Resolve Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Depth Resolve Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_count() {
+        assert_eq!(fit_count(8, &[1, 4, 8]), 8);
+        // Unsupported counts fall back to the next lower one
+        assert_eq!(fit_count(8, &[1, 4]), 4);
+        assert_eq!(fit_count(2, &[1, 4, 8]), 1);
+        assert_eq!(fit_count(4, &[]), 1);
+    }
+}
@@ -0,0 +1,387 @@
+//! Occlusion culling of mesh chunks with hardware occlusion queries.
+//!
+//! Each frame the chunks that were visible in the last read-back frame are
+//! drawn front to back, each inside an occlusion query. The bounding boxes
+//! of the remaining chunks are then drawn (depth-tested, writing nothing)
+//! inside their own queries. Query results are read back asynchronously and
+//! decide which chunks are drawn in the following frames, so terrain hidden
+//! behind mountains costs only a box, and a chunk coming into view appears
+//! a frame or two late at most. If a read-back fails, every chunk is drawn
+//! until the next one succeeds.
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use super::{DepthMode, DEPTH_FORMAT};
+use crate::terrain::mesh::MeshChunk;
+use crate::terrain::Vertex;
+
+/// Triangles of a box over the corners produced by [`box_corners`].
+const BOX_INDICES: [u32; 36] = [
+    0, 1, 2, 0, 2, 3, // -z
+    4, 6, 5, 4, 7, 6, // +z
+    0, 4, 5, 0, 5, 1, // -y
+    3, 2, 6, 3, 6, 7, // +y
+    0, 3, 7, 0, 7, 4, // -x
+    1, 5, 6, 1, 6, 2, // +x
+];
+
+/// The eight corners of an axis-aligned box.
+fn box_corners(min: Vec3, max: Vec3) -> [Vec3; 8] {
+    [
+        Vec3::new(min.x, min.y, min.z),
+        Vec3::new(max.x, min.y, min.z),
+        Vec3::new(max.x, max.y, min.z),
+        Vec3::new(min.x, max.y, min.z),
+        Vec3::new(min.x, min.y, max.z),
+        Vec3::new(max.x, min.y, max.z),
+        Vec3::new(max.x, max.y, max.z),
+        Vec3::new(min.x, max.y, max.z),
+    ]
+}
+
+/// Build the pipeline drawing chunk bounding boxes for occlusion queries.
+///
+/// Uses the wireframe shader and bind group for the transform, with color
+/// and depth writes disabled, at the main pass's `samples` per pixel.
+pub fn create_box_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    depth_mode: DepthMode,
+    samples: u32,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Occlusion Box Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Occlusion Box Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(depth_mode.fragment_entry_point()),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::empty(),
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: samples,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache,
+    })
+}
+
+/// Progress of the asynchronous query read-back.
+enum Readback {
+    /// The read-back buffer is free
+    Idle,
+    /// Results were resolved this frame and will be mapped after submit
+    Resolved,
+    /// Waiting for the mapping to finish, or fail
+    Mapping(Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+/// Occlusion query state for the chunks of one mesh.
+pub struct OcclusionCuller {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback: Readback,
+    box_vertex_buffer: wgpu::Buffer,
+    box_index_buffer: wgpu::Buffer,
+    bounds: Vec<(Vec3, Vec3)>,
+    visible: Vec<bool>,
+}
+
+impl OcclusionCuller {
+    /// Create queries and bounding boxes for `chunks`.
+    ///
+    /// Returns `None` if there are too few chunks to be worth culling or
+    /// more than fit in one query set.
+    pub fn new(device: &wgpu::Device, chunks: &[MeshChunk]) -> Option<Self> {
+        let count = chunks.len() as u32;
+        if !(2..=wgpu::QUERY_SET_MAX_QUERIES).contains(&count) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Chunk Occlusion Queries"),
+            ty: wgpu::QueryType::Occlusion,
+            count,
+        });
+        let size = (count * wgpu::QUERY_SIZE) as wgpu::BufferAddress;
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Resolve Buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Occlusion Readback Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bounds: Vec<_> = chunks.iter().map(|c| (c.min, c.max)).collect();
+        let vertices: Vec<Vertex> = bounds
+            .iter()
+            .flat_map(|&(min, max)| box_corners(min, max))
+            .map(|corner| Vertex {
+                position: corner.to_array(),
+                color: [0.0; 3],
+                normal: [0.0, 1.0, 0.0],
+            })
+            .collect();
+        let box_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Occlusion Box Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let box_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Occlusion Box Index Buffer"),
+            contents: bytemuck::cast_slice(&BOX_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            readback: Readback::Idle,
+            box_vertex_buffer,
+            box_index_buffer,
+            visible: vec![true; bounds.len()],
+            bounds,
+        })
+    }
+
+    /// Number of chunks, one query each.
+    pub fn chunk_count(&self) -> u32 {
+        self.bounds.len() as u32
+    }
+
+    /// Query set to attach to the render pass.
+    pub fn query_set(&self) -> &wgpu::QuerySet {
+        &self.query_set
+    }
+
+    /// Pick up finished query results. Call once per frame before drawing.
+    pub fn update(&mut self, device: &wgpu::Device) {
+        let Readback::Mapping(mapped) = &self.readback else {
+            return;
+        };
+        let _ = device.poll(wgpu::PollType::Poll);
+        match mapped.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            Ok(Ok(())) => {
+                {
+                    let data = self.readback_buffer.slice(..).get_mapped_range();
+                    let samples: &[u64] = bytemuck::cast_slice(&data);
+                    apply_results(&mut self.visible, samples);
+                }
+                self.readback_buffer.unmap();
+            }
+            // No results to cull by: draw everything, and query again next
+            // frame instead of waiting for a mapping that never finishes
+            Ok(Err(_)) | Err(TryRecvError::Disconnected) => {
+                log::warn!("Occlusion query read-back failed, drawing every chunk");
+                self.visible.fill(true);
+            }
+        }
+        self.readback = Readback::Idle;
+    }
+
+    /// Split the chunks into those to draw (front to back from `eye`) and
+    /// those to test with their bounding box.
+    pub fn partition(&self, eye: Vec3) -> (Vec<u32>, Vec<u32>) {
+        partition(&self.bounds, &self.visible, eye)
+    }
+
+    /// Draw the bounding boxes of `hidden` chunks inside their queries.
+    /// The box pipeline and wireframe bind group must already be set.
+    pub fn draw_boxes(&self, render_pass: &mut wgpu::RenderPass<'_>, hidden: &[u32]) {
+        render_pass.set_vertex_buffer(0, self.box_vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.box_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for &chunk in hidden {
+            render_pass.begin_occlusion_query(chunk);
+            render_pass.draw_indexed(0..BOX_INDICES.len() as u32, (chunk * 8) as i32, 0..1);
+            render_pass.end_occlusion_query();
+        }
+    }
+
+    /// Copy this frame's results for read-back, unless a read-back is
+    /// still in flight. Call after the render pass.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !matches!(self.readback, Readback::Idle) {
+            return;
+        }
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..self.bounds.len() as u32,
+            &self.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            self.resolve_buffer.size(),
+        );
+        self.readback = Readback::Resolved;
+    }
+
+    /// Start mapping resolved results. Call after the frame is submitted.
+    pub fn after_submit(&mut self) {
+        if !matches!(self.readback, Readback::Resolved) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.readback = Readback::Mapping(receiver);
+    }
+}
+
+/// Update visibility from per-chunk passed sample counts.
+fn apply_results(visible: &mut [bool], samples: &[u64]) {
+    for (visible, &count) in visible.iter_mut().zip(samples) {
+        *visible = count > 0;
+    }
+}
+
+/// Visible chunks sorted front to back, and hidden chunks. A chunk whose
+/// box contains the eye is always drawn, since its box faces may be clipped
+/// by the near plane.
+fn partition(bounds: &[(Vec3, Vec3)], visible: &[bool], eye: Vec3) -> (Vec<u32>, Vec<u32>) {
+    let mut drawn = Vec::new();
+    let mut hidden = Vec::new();
+    for (i, (&(min, max), &is_visible)) in bounds.iter().zip(visible).enumerate() {
+        let contains_eye = eye.cmpge(min).all() && eye.cmple(max).all();
+        if is_visible || contains_eye {
+            drawn.push(i as u32);
+        } else {
+            hidden.push(i as u32);
+        }
+    }
+
+    let distance = |i: &u32| {
+        let (min, max) = bounds[*i as usize];
+        eye.distance_squared((min + max) / 2.0)
+    };
+    drawn.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+    (drawn, hidden)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_covers_all_faces() {
+        let corners = box_corners(Vec3::ZERO, Vec3::ONE);
+
+        // Each of the 6 faces is two triangles on a shared axis plane
+        for face in BOX_INDICES.chunks(6) {
+            let points: Vec<Vec3> = face.iter().map(|&i| corners[i as usize]).collect();
+            let on_plane = (0..3).any(|axis| points.iter().all(|p| p[axis] == points[0][axis]));
+            assert!(on_plane);
+        }
+    }
+
+    #[test]
+    fn test_apply_results() {
+        let mut visible = vec![true, false, true];
+        apply_results(&mut visible, &[0, 12, 3]);
+        assert_eq!(visible, vec![false, true, true]);
+    }
+
+    #[test]
+    fn test_failed_readback_draws_every_chunk() {
+        // Skipped without an adapter
+        let instance = wgpu::Instance::default();
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&Default::default())) else {
+            return;
+        };
+        let (device, _queue) = pollster::block_on(adapter.request_device(&Default::default()))
+            .expect("Failed to create device");
+        let chunk = |x: f32| MeshChunk {
+            triangles: 0..0,
+            lines: 0..0,
+            min: Vec3::new(x, 0.0, 0.0),
+            max: Vec3::new(x + 1.0, 1.0, 1.0),
+            lods: Vec::new(),
+        };
+        let mut culler = OcclusionCuller::new(&device, &[chunk(0.0), chunk(1.0)]).unwrap();
+        culler.visible = vec![false, true];
+
+        // The buffer is gone before the mapping finishes
+        culler.readback = Readback::Resolved;
+        culler.after_submit();
+        culler.readback_buffer.destroy();
+        culler.update(&device);
+        assert!(matches!(culler.readback, Readback::Idle));
+        assert_eq!(culler.visible, [true, true]);
+    }
+
+    #[test]
+    fn test_partition_sorts_front_to_back() {
+        let bounds = vec![
+            (Vec3::new(20.0, 0.0, 0.0), Vec3::new(30.0, 1.0, 10.0)),
+            (Vec3::new(10.0, 0.0, 0.0), Vec3::new(20.0, 1.0, 10.0)),
+            (Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 1.0, 10.0)),
+        ];
+        let eye = Vec3::new(-5.0, 5.0, 5.0);
+
+        let (drawn, hidden) = partition(&bounds, &[true, true, false], eye);
+        assert_eq!(drawn, vec![1, 0]);
+        assert_eq!(hidden, vec![2]);
+    }
+
+    #[test]
+    fn test_partition_draws_chunk_containing_eye() {
+        let bounds = vec![(Vec3::ZERO, Vec3::splat(10.0))];
+        let (drawn, hidden) = partition(&bounds, &[false], Vec3::splat(5.0));
+
+        assert_eq!(drawn, vec![0]);
+        assert!(hidden.is_empty());
+    }
+}
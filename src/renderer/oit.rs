@@ -0,0 +1,348 @@
+//! Weighted blended order-independent transparency.
+//!
+//! Translucent layers (the water plane and a see-through terrain surface)
+//! are drawn after the opaque scene into two offscreen targets: an
+//! accumulation target summing depth-weighted premultiplied colors, and a
+//! revealage target multiplying `1 - alpha`. A fullscreen composite pass
+//! then blends their weighted average over the opaque scene, so overlaps
+//! look the same whatever order the layers are drawn in.
+//!
+//! Layers test against the opaque depth buffer but don't write it.
+
+use super::{DepthMode, Vertex, DEPTH_FORMAT};
+
+/// Accumulation target format; needs range above 1.0 and blending.
+const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Revealage target format.
+const REVEAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+/// Accumulation and revealage targets for one group of translucent layers.
+pub struct LayerTargets {
+    accum_view: wgpu::TextureView,
+    reveal_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl LayerTargets {
+    /// Bind group exposing both targets to a composite shader.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Begin the pass accumulating translucent layers, testing against the
+    /// opaque scene's depth.
+    pub fn begin<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        let target = |view, clear| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })
+        };
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("OIT Layer Pass"),
+            color_attachments: &[
+                target(&self.accum_view, wgpu::Color::TRANSPARENT),
+                // Nothing covers the scene yet: fully revealed
+                target(&self.reveal_view, wgpu::Color::WHITE),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+}
+
+/// Offscreen targets and composite pipeline for translucent layers.
+pub struct Oit {
+    targets: LayerTargets,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Oit {
+    /// Create targets of the given size and the composite pipeline drawing
+    /// onto a surface of `format`, from `oit.wgsl` source.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0), texture_entry(1)],
+            label: Some("OIT Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("OIT Composite Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_composite_pipeline(device, cache, &pipeline_layout, format, source);
+
+        let targets = create_targets(device, &bind_group_layout, width, height);
+        Self {
+            targets,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Recreate the targets for a new surface size.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = self.create_targets(device, width, height);
+    }
+
+    /// Layout of a [`LayerTargets`] bind group.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Create another set of targets, for layers composited separately.
+    pub fn create_targets(&self, device: &wgpu::Device, width: u32, height: u32) -> LayerTargets {
+        create_targets(device, &self.bind_group_layout, width, height)
+    }
+
+    /// Build a composite pipeline from new shader source, to be installed
+    /// with [`set_pipeline`](Self::set_pipeline) once it validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_composite_pipeline(device, cache, &self.pipeline_layout, format, source)
+    }
+
+    /// Replace the composite pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Begin the pass accumulating translucent layers, testing against the
+    /// opaque scene's depth.
+    pub fn begin_layers<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        self.targets.begin(encoder, depth_view)
+    }
+
+    /// Blend the accumulated layers over `target`.
+    pub fn composite(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("OIT Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.targets.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Create the accumulation and revealage targets and their bind group.
+fn create_targets(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    width: u32,
+    height: u32,
+) -> LayerTargets {
+    let create_view = |label, format| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    };
+    let accum_view = create_view("OIT Accumulation Texture", ACCUM_FORMAT);
+    let reveal_view = create_view("OIT Revealage Texture", REVEAL_FORMAT);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&accum_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&reveal_view),
+            },
+        ],
+        label: Some("OIT Bind Group"),
+    });
+    LayerTargets {
+        accum_view,
+        reveal_view,
+        bind_group,
+    }
+}
+
+/// Build the fullscreen composite pipeline.
+fn create_composite_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("OIT Composite Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("OIT Composite Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
+
+/// Build the pipeline drawing translucent layers with the solid shader's
+/// `fs_oit` entry points.
+pub fn create_layer_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    depth_mode: DepthMode,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("OIT Layer Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    // Sum into the accumulation target, multiply (1 - alpha) into revealage
+    let additive = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+    let reveal = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("OIT Layer Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(depth_mode.oit_entry_point()),
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format: ACCUM_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+                Some(wgpu::ColorTargetState {
+                    format: REVEAL_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: reveal,
+                        alpha: reveal,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+            ],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            // Back faces of a see-through surface are visible
+            cull_mode: None,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
@@ -0,0 +1,398 @@
+//! Picture-in-picture overview camera.
+//!
+//! After the main view, the scene is drawn a second time from a fixed
+//! orthographic camera (straight down or isometric) fitted to the scene
+//! bounds, into a small square viewport in the bottom-right corner of the
+//! window. A marker shows where the main camera is and which way it faces.
+//!
+//! The inset reuses the wireframe and solid pipelines with its own uniform
+//! buffers, and a scene uniform buffer left zeroed so the inset has no
+//! fog. Clipmap terrain and translucent layers aren't drawn in it.
+
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use super::{DepthMode, SolidUniforms, WireframeUniforms, DEPTH_FORMAT};
+use crate::terrain::Vertex;
+
+/// Gap between the inset and the window edges, in pixels.
+const MARGIN: u32 = 12;
+/// Smallest inset worth drawing, in pixels.
+const MIN_SIZE: u32 = 32;
+
+/// Vertices in the camera marker: three axis lines and a heading line.
+pub const MARKER_VERTICES: usize = 8;
+/// Size of the camera marker relative to the scene radius.
+pub const MARKER_SCALE: f32 = 0.04;
+const MARKER_COLOR: [f32; 3] = [1.0, 0.25, 0.2];
+const MARKER_INDICES: [u32; MARKER_VERTICES] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+/// Fixed viewpoint of the overview camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverviewView {
+    /// Straight down, with the first grid row at the top
+    #[default]
+    TopDown,
+    /// Standard isometric angles, as [`Camera::set_isometric`](super::camera::Camera::set_isometric)
+    Isometric,
+}
+
+impl OverviewView {
+    /// Direction from the scene toward the overview camera, and the
+    /// camera's up vector.
+    fn orientation(self) -> (Vec3, Vec3) {
+        match self {
+            OverviewView::TopDown => (Vec3::Y, Vec3::NEG_Z),
+            OverviewView::Isometric => (Vec3::ONE.normalize(), Vec3::Y),
+        }
+    }
+}
+
+/// Overview inset configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverviewConfig {
+    /// Whether the inset is drawn
+    pub enabled: bool,
+    /// Overview camera viewpoint
+    pub view: OverviewView,
+    /// Inset side length as a fraction of the window's smaller dimension
+    pub size: f32,
+}
+
+impl Default for OverviewConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            view: OverviewView::default(),
+            size: 0.3,
+        }
+    }
+}
+
+/// Orthographic view-projection of the overview camera, framing a
+/// bounding sphere.
+pub fn view_proj(view: OverviewView, (center, radius): (Vec3, f32)) -> Mat4 {
+    let (direction, up) = view.orientation();
+    let radius = radius.max(1e-3);
+    let eye = center + direction * radius * 2.0;
+    let view = Mat4::look_at_rh(eye, center, up);
+    let proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, radius * 4.0);
+    proj * view
+}
+
+/// Inset position and side length `(x, y, side)` in a window of the given
+/// size, or `None` if it would be too small to show anything.
+pub fn viewport(config: &OverviewConfig, width: u32, height: u32) -> Option<(u32, u32, u32)> {
+    let side = (width.min(height) as f32 * config.size.clamp(0.0, 1.0)) as u32;
+    let side = side
+        .min(width.saturating_sub(2 * MARGIN))
+        .min(height.saturating_sub(2 * MARGIN));
+    if side < MIN_SIZE {
+        return None;
+    }
+    Some((width - MARGIN - side, height - MARGIN - side, side))
+}
+
+/// Line-list marker at the main camera's `position`, with a heading line
+/// toward its `target` projected onto the ground plane.
+pub fn marker_lines(position: Vec3, target: Vec3, size: f32) -> [Vertex; MARKER_VERTICES] {
+    let heading = (target - position).with_y(0.0).normalize_or_zero();
+    let vertex = |offset: Vec3| Vertex {
+        position: (position + offset * size).to_array(),
+        color: MARKER_COLOR,
+        normal: [0.0, 1.0, 0.0],
+    };
+    [
+        vertex(-Vec3::X),
+        vertex(Vec3::X),
+        vertex(-Vec3::Y),
+        vertex(Vec3::Y),
+        vertex(-Vec3::Z),
+        vertex(Vec3::Z),
+        vertex(Vec3::ZERO),
+        vertex(heading * 3.0),
+    ]
+}
+
+/// Uniform buffers, marker geometry and background pipeline for the inset.
+pub struct Overview {
+    wireframe_uniform_buffer: wgpu::Buffer,
+    wireframe_bind_group: wgpu::BindGroup,
+    solid_uniform_buffer: wgpu::Buffer,
+    solid_bind_group: wgpu::BindGroup,
+    /// Scene uniforms without fog, never written
+    scene_uniform_buffer: wgpu::Buffer,
+    marker_vertex_buffer: wgpu::Buffer,
+    marker_index_buffer: wgpu::Buffer,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Overview {
+    /// Create the inset's buffers and its background pipeline drawing onto
+    /// a surface of `format`, from `overview.wgsl` source. The bind groups
+    /// come from the renderer, which owns their layouts, given a uniform
+    /// buffer and the inset's scene buffer.
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        source: &str,
+        create_wireframe_bind_group: impl FnOnce(&wgpu::Buffer, &wgpu::Buffer) -> wgpu::BindGroup,
+        create_solid_bind_group: impl FnOnce(&wgpu::Buffer, &wgpu::Buffer) -> wgpu::BindGroup,
+    ) -> Self {
+        let uniform_buffer = |label, size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let wireframe_uniform_buffer = uniform_buffer(
+            "Overview Wireframe Uniform Buffer",
+            std::mem::size_of::<WireframeUniforms>(),
+        );
+        let scene_uniform_buffer =
+            super::atmosphere::create_buffer(device, "Overview Scene Buffer");
+        let wireframe_bind_group =
+            create_wireframe_bind_group(&wireframe_uniform_buffer, &scene_uniform_buffer);
+        let solid_uniform_buffer = uniform_buffer(
+            "Overview Solid Uniform Buffer",
+            std::mem::size_of::<SolidUniforms>(),
+        );
+        let solid_bind_group =
+            create_solid_bind_group(&solid_uniform_buffer, &scene_uniform_buffer);
+
+        let marker_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Overview Marker Vertex Buffer"),
+            size: (std::mem::size_of::<Vertex>() * MARKER_VERTICES) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let marker_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overview Marker Index Buffer"),
+            contents: bytemuck::cast_slice(&MARKER_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overview Background Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, cache, &pipeline_layout, format, source);
+
+        Self {
+            wireframe_uniform_buffer,
+            wireframe_bind_group,
+            solid_uniform_buffer,
+            scene_uniform_buffer,
+            solid_bind_group,
+            marker_vertex_buffer,
+            marker_index_buffer,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Build a background pipeline from new shader source, to be installed
+    /// with [`set_pipeline`](Self::set_pipeline) once it validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_pipeline(device, cache, &self.pipeline_layout, format, source)
+    }
+
+    /// Replace the background pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Upload this frame's uniforms, already carrying the overview
+    /// view-projection, and the camera marker.
+    pub(super) fn update(
+        &self,
+        queue: &wgpu::Queue,
+        wireframe: &WireframeUniforms,
+        solid: &SolidUniforms,
+        marker: &[Vertex; MARKER_VERTICES],
+    ) {
+        queue.write_buffer(
+            &self.wireframe_uniform_buffer,
+            0,
+            bytemuck::bytes_of(wireframe),
+        );
+        queue.write_buffer(&self.solid_uniform_buffer, 0, bytemuck::bytes_of(solid));
+        queue.write_buffer(&self.marker_vertex_buffer, 0, bytemuck::cast_slice(marker));
+    }
+
+    /// Bind group for drawing with the wireframe pipeline in the inset.
+    pub fn wireframe_bind_group(&self) -> &wgpu::BindGroup {
+        &self.wireframe_bind_group
+    }
+
+    /// Bind group for drawing with the solid pipeline in the inset.
+    pub fn solid_bind_group(&self) -> &wgpu::BindGroup {
+        &self.solid_bind_group
+    }
+
+    /// Uniform buffer the solid bind group reads, for rebuilding it.
+    pub fn solid_uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.solid_uniform_buffer
+    }
+
+    /// Scene uniform buffer the bind groups read, for rebuilding them.
+    pub fn scene_uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.scene_uniform_buffer
+    }
+
+    /// Replace the solid bind group, when the resources it binds change.
+    pub fn set_solid_bind_group(&mut self, bind_group: wgpu::BindGroup) {
+        self.solid_bind_group = bind_group;
+    }
+
+    /// Begin the inset pass over the finished main view: clear the depth
+    /// buffer, restrict drawing to the inset and fill its background.
+    pub fn begin<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        target: &'a wgpu::TextureView,
+        depth_view: &'a wgpu::TextureView,
+        depth_mode: DepthMode,
+        (x, y, side): (u32, u32, u32),
+    ) -> wgpu::RenderPass<'a> {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overview Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(depth_mode.clear_value()),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_viewport(x as f32, y as f32, side as f32, side as f32, 0.0, 1.0);
+        pass.set_scissor_rect(x, y, side, side);
+        pass.set_pipeline(&self.pipeline);
+        pass.draw(0..3, 0..1);
+        pass
+    }
+
+    /// Draw the camera marker with the wireframe pipeline, which must be
+    /// set on `pass`.
+    pub fn draw_marker(&self, pass: &mut wgpu::RenderPass) {
+        pass.set_bind_group(0, &self.wireframe_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.marker_vertex_buffer.slice(..));
+        pass.set_index_buffer(
+            self.marker_index_buffer.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+        pass.draw_indexed(0..MARKER_VERTICES as u32, 0, 0..1);
+    }
+}
+
+/// Build the background fill pipeline. It ignores depth but must match the
+/// pass's depth attachment.
+fn create_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Overview Background Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Overview Background Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec4;
+
+    #[test]
+    fn test_view_proj_frames_bounds() {
+        let bounds = (Vec3::new(10.0, 2.0, -5.0), 20.0);
+        for view in [OverviewView::TopDown, OverviewView::Isometric] {
+            let m = view_proj(view, bounds);
+            let center = m * bounds.0.extend(1.0);
+            assert!(center.x.abs() < 1e-4 && center.y.abs() < 1e-4);
+            assert!((0.0..=1.0).contains(&center.z));
+        }
+
+        // Top-down: east to the right, the first grid row (low z) at the top
+        let m = view_proj(OverviewView::TopDown, bounds);
+        let east = m * Vec4::new(30.0, 2.0, -5.0, 1.0);
+        let row0 = m * Vec4::new(10.0, 2.0, -25.0, 1.0);
+        assert!((east.x - 1.0).abs() < 1e-4);
+        assert!((row0.y - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_viewport_in_corner() {
+        let config = OverviewConfig::default();
+        let (x, y, side) = viewport(&config, 1000, 600).unwrap();
+        assert_eq!(side, 180);
+        assert_eq!((x + side, y + side), (1000 - MARGIN, 600 - MARGIN));
+
+        // Too small a window to fit an inset
+        assert!(viewport(&config, 40, 40).is_none());
+    }
+
+    #[test]
+    fn test_marker_heading_is_horizontal() {
+        let marker = marker_lines(Vec3::new(0.0, 10.0, 0.0), Vec3::new(5.0, 0.0, 0.0), 1.0);
+        assert_eq!(marker[6].position, [0.0, 10.0, 0.0]);
+        assert_eq!(marker[7].position, [3.0, 10.0, 0.0]);
+    }
+}
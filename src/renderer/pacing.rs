@@ -0,0 +1,387 @@
+//! Fixed-timestep updates and frame pacing.
+//!
+//! Animations, meaning the timeline clock, the orbit track and auto-rotate,
+//! camera path recording, camera motion smoothing and fly mode, advance in
+//! steps of [`UPDATE_STEP`] rather than by each frame's length. A frame runs
+//! as many whole steps as the time since the last one covers and carries
+//! the rest over, so animations play the same at any refresh rate and catch
+//! up after a dropped frame. A stall longer than [`MAX_CATCH_UP`], like
+//! dragging the window, is dropped rather than replayed in one burst.
+//!
+//! How often frames are drawn is up to [`PacingConfig`]: the surface's
+//! present mode, an optional frame rate cap, and a power saving mode that
+//! draws only after input or while something moves. [`FrameScheduler`]
+//! turns it into the time the event loop should wake for the next frame.
+//!
+//! [`LatencyStats`] measures how long frames take to respond: from an
+//! input event to presenting the first frame drawn after it, and how long
+//! each frame waits for the surface to hand out a texture. Presenting
+//! isn't the photon reaching the eye, as the compositor and display add
+//! their own delay, but long or growing times here point at the present
+//! mode or the compositor queueing frames.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Length of an update step in seconds, short enough that the remainder
+/// carried between frames doesn't show.
+pub const UPDATE_STEP: f32 = 1.0 / 240.0;
+
+/// Longest time between frames that is caught up on, in seconds.
+pub const MAX_CATCH_UP: f32 = 0.1;
+
+/// Accumulator of frame time into whole update steps.
+#[derive(Debug, Clone)]
+pub struct FixedStep {
+    /// Time of the last call to [`tick`](Self::tick)
+    last: Instant,
+    /// Time not yet run as a step, in seconds
+    carried: f64,
+}
+
+impl Default for FixedStep {
+    fn default() -> Self {
+        Self {
+            last: Instant::now(),
+            carried: 0.0,
+        }
+    }
+}
+
+impl FixedStep {
+    /// Number of steps due for the time since the last tick, or since
+    /// creation.
+    pub fn tick(&mut self, now: Instant) -> u32 {
+        let dt = now.saturating_duration_since(self.last).as_secs_f32();
+        self.last = now;
+        self.advance(dt)
+    }
+
+    /// Number of steps due after `dt` more seconds.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        let step = UPDATE_STEP as f64;
+        self.carried += dt.clamp(0.0, MAX_CATCH_UP) as f64;
+        // Whole steps, forgiving rounding error
+        let steps = (self.carried / step + 1e-6).floor();
+        self.carried = (self.carried - steps * step).max(0.0);
+        steps as u32
+    }
+}
+
+/// How frames are handed to the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentMode {
+    /// Wait for vertical blank: no tearing, at most the refresh rate
+    #[default]
+    Fifo,
+    /// Replace the queued frame: no tearing, low latency, frames dropped
+    Mailbox,
+    /// Present right away: lowest latency, may tear
+    Immediate,
+}
+
+impl PresentMode {
+    /// All modes, in UI order.
+    pub const ALL: [PresentMode; 3] = [
+        PresentMode::Fifo,
+        PresentMode::Mailbox,
+        PresentMode::Immediate,
+    ];
+
+    /// Display name.
+    pub fn label(self) -> &'static str {
+        match self {
+            PresentMode::Fifo => "VSync (Fifo)",
+            PresentMode::Mailbox => "Mailbox",
+            PresentMode::Immediate => "Immediate",
+        }
+    }
+
+    /// The wgpu present mode.
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+
+    /// The wgpu present mode to use given the `supported` ones: this one,
+    /// else the other mode without vsync, else Fifo, which every surface
+    /// supports.
+    pub fn fit(self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let fallback = match self {
+            PresentMode::Fifo => PresentMode::Fifo,
+            PresentMode::Mailbox => PresentMode::Immediate,
+            PresentMode::Immediate => PresentMode::Mailbox,
+        };
+        [self, fallback]
+            .into_iter()
+            .map(PresentMode::to_wgpu)
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+}
+
+/// `--vsync` setting, picking the present mode to start with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Vsync {
+    /// Wait for vertical blank (Fifo)
+    On,
+    /// Present right away (Immediate, else Mailbox)
+    Off,
+}
+
+impl Vsync {
+    /// The present mode this stands for.
+    pub fn present_mode(self) -> PresentMode {
+        match self {
+            Vsync::On => PresentMode::Fifo,
+            Vsync::Off => PresentMode::Immediate,
+        }
+    }
+}
+
+/// When frames are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PacingConfig {
+    /// How frames are handed to the display
+    pub present_mode: PresentMode,
+    /// Most frames per second, if capped
+    pub max_fps: Option<u32>,
+    /// Draw only after input or while something moves
+    pub power_saving: bool,
+}
+
+/// Schedules frames for the frame rate cap and power saving.
+#[derive(Debug, Clone)]
+pub struct FrameScheduler {
+    /// Start of the last frame
+    last_frame: Instant,
+    /// When the next frame should be drawn, if one is wanted
+    due: Option<Instant>,
+}
+
+impl Default for FrameScheduler {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            last_frame: now,
+            due: Some(now),
+        }
+    }
+}
+
+impl FrameScheduler {
+    /// Note a frame started at `now` and schedule the next, unless power
+    /// saving and nothing is `busy` moving.
+    pub fn frame_started(&mut self, now: Instant, config: &PacingConfig, busy: bool) {
+        self.last_frame = now;
+        self.due = (busy || !config.power_saving).then(|| self.earliest(config));
+    }
+
+    /// Schedule a frame for input, as soon as the cap allows.
+    pub fn request(&mut self, config: &PacingConfig) {
+        if self.due.is_none() {
+            self.due = Some(self.earliest(config));
+        }
+    }
+
+    /// When the next frame should be drawn, if one is wanted.
+    pub fn due(&self) -> Option<Instant> {
+        self.due
+    }
+
+    /// Earliest start of the next frame under the cap.
+    fn earliest(&self, config: &PacingConfig) -> Instant {
+        match config.max_fps.filter(|&fps| fps > 0) {
+            Some(fps) => self.last_frame + Duration::from_secs_f64(1.0 / fps as f64),
+            None => self.last_frame,
+        }
+    }
+}
+
+/// Frames kept for the latency statistics.
+pub const LATENCY_WINDOW: usize = 120;
+
+/// Mean and worst of recent times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spread {
+    /// Mean time
+    pub mean: Duration,
+    /// Longest time
+    pub max: Duration,
+}
+
+impl Spread {
+    /// Spread of `times`, if there are any.
+    fn of(times: &VecDeque<Duration>) -> Option<Self> {
+        let max = times.iter().max().copied()?;
+        let mean = times.iter().sum::<Duration>() / times.len() as u32;
+        Some(Self { mean, max })
+    }
+}
+
+/// Input-to-present latency and surface acquire waits over the last
+/// [`LATENCY_WINDOW`] frames.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    /// Oldest input not yet taken up by a frame
+    pending_input: Option<Instant>,
+    /// Oldest input the frame being drawn shows
+    frame_input: Option<Instant>,
+    input_to_present: VecDeque<Duration>,
+    acquire_wait: VecDeque<Duration>,
+}
+
+impl LatencyStats {
+    /// Note an input event arriving at `at`.
+    pub fn input(&mut self, at: Instant) {
+        self.pending_input.get_or_insert(at);
+    }
+
+    /// Note a frame starting: the input so far is what it shows.
+    pub fn frame_started(&mut self) {
+        self.frame_input = self.pending_input.take();
+    }
+
+    /// Note the frame presented at `at`, after waiting `acquire` for its
+    /// surface texture, if it was drawn to a window.
+    pub fn frame_presented(&mut self, acquire: Option<Duration>, at: Instant) {
+        if let Some(acquire) = acquire {
+            push_recent(&mut self.acquire_wait, acquire);
+        }
+        if let Some(input) = self.frame_input.take() {
+            push_recent(
+                &mut self.input_to_present,
+                at.saturating_duration_since(input),
+            );
+        }
+    }
+
+    /// Time from input to presenting the frame showing it.
+    pub fn input_to_present(&self) -> Option<Spread> {
+        Spread::of(&self.input_to_present)
+    }
+
+    /// Time spent waiting for the surface's next texture.
+    pub fn acquire_wait(&self) -> Option<Spread> {
+        Spread::of(&self.acquire_wait)
+    }
+}
+
+/// Add `time` to `times`, dropping the oldest beyond the window.
+fn push_recent(times: &mut VecDeque<Duration>, time: Duration) {
+    if times.len() == LATENCY_WINDOW {
+        times.pop_front();
+    }
+    times.push_back(time);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_follow_time_not_frames() {
+        // One second at 60 and at 144 frames per second
+        let steps = |fps: u32| {
+            let mut clock = FixedStep::default();
+            (0..fps)
+                .map(|_| clock.advance(1.0 / fps as f32))
+                .sum::<u32>()
+        };
+        let second = (1.0 / UPDATE_STEP).round() as u32;
+        assert!(steps(60).abs_diff(second) <= 1);
+        assert!(steps(144).abs_diff(second) <= 1);
+
+        // A stall catches up only so far
+        let mut clock = FixedStep::default();
+        assert_eq!(
+            clock.advance(5.0),
+            (MAX_CATCH_UP / UPDATE_STEP).round() as u32
+        );
+        assert_eq!(clock.advance(-1.0), 0);
+    }
+
+    #[test]
+    fn test_present_mode_fallback() {
+        use wgpu::PresentMode as Wgpu;
+        let all = [Wgpu::Fifo, Wgpu::Mailbox, Wgpu::Immediate];
+        for mode in PresentMode::ALL {
+            assert_eq!(mode.fit(&all), mode.to_wgpu());
+        }
+        assert_eq!(
+            PresentMode::Immediate.fit(&[Wgpu::Fifo, Wgpu::Mailbox]),
+            Wgpu::Mailbox
+        );
+        assert_eq!(PresentMode::Mailbox.fit(&[Wgpu::Fifo]), Wgpu::Fifo);
+        assert_eq!(PresentMode::Immediate.fit(&[]), Wgpu::Fifo);
+    }
+
+    #[test]
+    fn test_frame_scheduling() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::default();
+
+        // Uncapped, the next frame is due right away
+        let free = PacingConfig::default();
+        scheduler.frame_started(start, &free, false);
+        assert_eq!(scheduler.due(), Some(start));
+
+        // Capped at 50 fps, 20 ms after the last one started, input
+        // included
+        let capped = PacingConfig {
+            max_fps: Some(50),
+            ..free
+        };
+        scheduler.frame_started(start, &capped, false);
+        assert_eq!(scheduler.due(), Some(start + Duration::from_millis(20)));
+
+        // Power saving waits for input while nothing moves
+        let saving = PacingConfig {
+            power_saving: true,
+            ..capped
+        };
+        scheduler.frame_started(start, &saving, false);
+        assert_eq!(scheduler.due(), None);
+        scheduler.request(&saving);
+        assert_eq!(scheduler.due(), Some(start + Duration::from_millis(20)));
+        scheduler.frame_started(start, &saving, true);
+        assert!(scheduler.due().is_some());
+    }
+
+    #[test]
+    fn test_latency_counts_from_oldest_input_shown() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.input_to_present(), None);
+
+        // Two events before a frame count from the first
+        stats.input(ms(0));
+        stats.input(ms(5));
+        stats.frame_started();
+        // One during the frame waits for the next
+        stats.input(ms(12));
+        stats.frame_presented(Some(Duration::from_millis(4)), ms(20));
+        stats.frame_started();
+        stats.frame_presented(Some(Duration::from_millis(2)), ms(30));
+        // A frame without input adds only its acquire wait
+        stats.frame_started();
+        stats.frame_presented(Some(Duration::from_millis(6)), ms(40));
+
+        let latency = stats.input_to_present().unwrap();
+        assert_eq!(latency.max, Duration::from_millis(20));
+        assert_eq!(latency.mean, Duration::from_millis(19));
+        let wait = stats.acquire_wait().unwrap();
+        assert_eq!(wait.mean, Duration::from_millis(4));
+        assert_eq!(wait.max, Duration::from_millis(6));
+
+        for _ in 0..LATENCY_WINDOW {
+            stats.frame_started();
+            stats.frame_presented(Some(Duration::ZERO), ms(50));
+        }
+        assert_eq!(stats.acquire_wait().unwrap().max, Duration::ZERO);
+    }
+}
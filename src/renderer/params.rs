@@ -0,0 +1,319 @@
+//! Named shader parameters.
+//!
+//! Shaders declare tweakable scalar parameters with annotation comments:
+//!
+//! ```text
+//! // @param exposure 1.0 0.25 4.0 Exposure
+//! ```
+//!
+//! The fields are the name, default value, slider minimum, slider maximum,
+//! and an optional label. Each parameter is assigned a slot in one generic
+//! uniform block, and a WGSL prelude is generated with an accessor per
+//! parameter (`param_exposure()`), so a new shader feature only needs the
+//! annotation to get a uniform and a UI slider.
+
+use bytemuck::{Pod, Zeroable};
+
+/// Maximum number of parameters in the uniform block.
+pub const MAX_PARAMS: usize = 16;
+
+/// Bind group slot of the parameter block in every pipeline.
+pub const PARAMS_BINDING: u32 = 1;
+
+const ANNOTATION: &str = "@param";
+
+/// Layout entry of the parameter block, visible to both shader stages.
+pub fn layout_entry() -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: PARAMS_BINDING,
+        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// A single named scalar parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderParam {
+    /// Identifier used in the WGSL accessor (`param_<name>()`)
+    pub name: String,
+    /// Label shown in the UI
+    pub label: String,
+    /// Current value
+    pub value: f32,
+    /// Value declared in the shader
+    pub default: f32,
+    /// Slider minimum
+    pub min: f32,
+    /// Slider maximum
+    pub max: f32,
+}
+
+/// Uniform block holding all parameter values, packed four per vec4.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ParamUniforms {
+    values: [[f32; 4]; MAX_PARAMS / 4],
+}
+
+/// The set of parameters declared across the active shaders.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShaderParams {
+    params: Vec<ShaderParam>,
+}
+
+impl ShaderParams {
+    /// Collect the `@param` annotations from several shader sources.
+    ///
+    /// A parameter declared in more than one source shares a single slot;
+    /// the first declaration wins.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message for malformed annotations or too many parameters.
+    pub fn parse(sources: &[&str]) -> Result<Self, String> {
+        let mut params: Vec<ShaderParam> = Vec::new();
+
+        for source in sources {
+            for (line_idx, line) in source.lines().enumerate() {
+                let Some(rest) = line.trim().strip_prefix("//") else {
+                    continue;
+                };
+                let Some(rest) = rest.trim().strip_prefix(ANNOTATION) else {
+                    continue;
+                };
+                let param = parse_annotation(rest)
+                    .map_err(|e| format!("line {}: invalid @param: {}", line_idx + 1, e))?;
+                if !params.iter().any(|p| p.name == param.name) {
+                    params.push(param);
+                }
+            }
+        }
+
+        if params.len() > MAX_PARAMS {
+            return Err(format!(
+                "{} shader parameters declared, at most {} are supported",
+                params.len(),
+                MAX_PARAMS
+            ));
+        }
+
+        Ok(Self { params })
+    }
+
+    /// Carry over values from a previous parameter set, matched by name.
+    ///
+    /// Used when shaders are reloaded so tweaked sliders keep their values.
+    pub fn keep_values_from(&mut self, previous: &ShaderParams) {
+        for param in &mut self.params {
+            if let Some(old) = previous.get(&param.name) {
+                param.value = old.clamp(param.min, param.max);
+            }
+        }
+    }
+
+    /// All parameters in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = &ShaderParam> {
+        self.params.iter()
+    }
+
+    /// Mutable access to all parameters, for UI sliders.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut ShaderParam> {
+        self.params.iter_mut()
+    }
+
+    /// Whether no parameters are declared.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Current value of a parameter.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.params.iter().find(|p| p.name == name).map(|p| p.value)
+    }
+
+    /// Set a parameter's value. Returns false if no such parameter exists.
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        match self.params.iter_mut().find(|p| p.name == name) {
+            Some(param) => {
+                param.value = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reset every parameter to its declared default.
+    pub fn reset(&mut self) {
+        for param in &mut self.params {
+            param.value = param.default;
+        }
+    }
+
+    /// Pack the current values for upload.
+    pub fn to_uniforms(&self) -> ParamUniforms {
+        let mut uniforms = ParamUniforms::zeroed();
+        for (i, param) in self.params.iter().enumerate() {
+            uniforms.values[i / 4][i % 4] = param.value;
+        }
+        uniforms
+    }
+
+    /// WGSL declaring the parameter block and one accessor per parameter.
+    pub fn wgsl_prelude(&self) -> String {
+        let mut wgsl = format!(
+            "struct ShaderParams {{\n    values: array<vec4<f32>, {}>,\n}}\n\n\
+             @group(0) @binding({})\nvar<uniform> shader_params: ShaderParams;\n",
+            MAX_PARAMS / 4,
+            PARAMS_BINDING
+        );
+        for (i, param) in self.params.iter().enumerate() {
+            wgsl.push_str(&format!(
+                "\nfn param_{}() -> f32 {{\n    return shader_params.values[{}][{}];\n}}\n",
+                param.name,
+                i / 4,
+                i % 4
+            ));
+        }
+        wgsl
+    }
+}
+
+/// Parse `<name> <default> <min> <max> [label...]`.
+fn parse_annotation(s: &str) -> Result<ShaderParam, String> {
+    let mut fields = s.split_whitespace();
+    let name = fields.next().ok_or("missing name")?;
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        || name.starts_with(|c: char| c.is_ascii_digit())
+    {
+        return Err(format!("'{}' is not a valid identifier", name));
+    }
+
+    let mut number = |what: &str| -> Result<f32, String> {
+        let field = fields.next().ok_or(format!("missing {}", what))?;
+        field
+            .parse()
+            .map_err(|_| format!("expected number for {}, got '{}'", what, field))
+    };
+    let default = number("default")?;
+    let min = number("min")?;
+    let max = number("max")?;
+    if min > max {
+        return Err(format!("min {} is greater than max {}", min, max));
+    }
+
+    let label = fields.collect::<Vec<_>>().join(" ");
+    let label = if label.is_empty() {
+        name.to_string()
+    } else {
+        label
+    };
+
+    Ok(ShaderParam {
+        name: name.to_string(),
+        label,
+        value: default,
+        default,
+        min,
+        max,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_annotations() {
+        let source = "// @param exposure 1.0 0.25 4.0 Exposure\n\
+                      fn main() {}\n\
+                      //@param band_spacing 10 1 50";
+        let params = ShaderParams::parse(&[source]).unwrap();
+        let params: Vec<_> = params.iter().collect();
+
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].name, "exposure");
+        assert_eq!(params[0].label, "Exposure");
+        assert_eq!(params[0].value, 1.0);
+        assert_eq!(params[0].min, 0.25);
+        assert_eq!(params[0].max, 4.0);
+        assert_eq!(params[1].label, "band_spacing");
+    }
+
+    #[test]
+    fn test_parse_ignores_code_mentions() {
+        let source = "let x = 1.0; // not @param here";
+        let params = ShaderParams::parse(&[source]).unwrap();
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_params_share_slot() {
+        let a = "// @param fog 0.1 0.0 1.0";
+        let b = "// @param fog 0.5 0.0 1.0\n// @param other 1 0 2";
+        let params = ShaderParams::parse(&[a, b]).unwrap();
+
+        assert_eq!(params.iter().count(), 2);
+        assert_eq!(params.get("fog"), Some(0.1));
+    }
+
+    #[test]
+    fn test_malformed_annotations() {
+        assert!(ShaderParams::parse(&["// @param"]).is_err());
+        assert!(ShaderParams::parse(&["// @param 1bad 0 0 1"]).is_err());
+        assert!(ShaderParams::parse(&["// @param x abc 0 1"]).is_err());
+        assert!(ShaderParams::parse(&["// @param x 0 2 1"]).is_err());
+    }
+
+    #[test]
+    fn test_too_many_params() {
+        let source: String = (0..=MAX_PARAMS)
+            .map(|i| format!("// @param p{} 0 0 1\n", i))
+            .collect();
+        assert!(ShaderParams::parse(&[&source]).is_err());
+    }
+
+    #[test]
+    fn test_uniform_packing() {
+        let source: String = (0..6)
+            .map(|i| format!("// @param p{} {} 0 10\n", i, i))
+            .collect();
+        let params = ShaderParams::parse(&[&source]).unwrap();
+        let uniforms = params.to_uniforms();
+
+        assert_eq!(uniforms.values[0], [0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(uniforms.values[1], [4.0, 5.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_prelude_accessors() {
+        let source = "// @param a 0 0 1\n// @param b 0 0 1\n// @param c 0 0 1\n\
+                      // @param d 0 0 1\n// @param e 0 0 1";
+        let prelude = ShaderParams::parse(&[source]).unwrap().wgsl_prelude();
+
+        assert!(prelude.contains("fn param_a() -> f32"));
+        assert!(prelude.contains("return shader_params.values[1][0];"));
+    }
+
+    #[test]
+    fn test_keep_values_and_reset() {
+        let source = "// @param a 0.5 0 1\n// @param b 0.5 0 1";
+        let mut old = ShaderParams::parse(&[source]).unwrap();
+        assert!(old.set("a", 0.9));
+        assert!(old.set("b", 5.0));
+        assert!(!old.set("missing", 1.0));
+
+        let mut new = ShaderParams::parse(&[source]).unwrap();
+        new.keep_values_from(&old);
+        assert_eq!(new.get("a"), Some(0.9));
+        // Carried values are clamped to the declared range
+        assert_eq!(new.get("b"), Some(1.0));
+
+        new.reset();
+        assert_eq!(new.get("a"), Some(0.5));
+    }
+}
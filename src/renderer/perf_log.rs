@@ -0,0 +1,216 @@
+//! Per-frame performance log for `--perf-log`.
+//!
+//! Every frame drawn adds a row to a CSV file: when it started, the time
+//! since the previous frame, the CPU time spent building it, its terrain
+//! draw calls, and the terrain chunks drawn and hidden by occlusion
+//! culling. Logs taken with two versions on the same terrain and camera
+//! path can be diffed or plotted to spot performance regressions with real
+//! workloads. A first comment line names the version and GPU.
+//!
+//! Memory is sampled once a second, as reading it isn't free: the
+//! process's resident memory where the system reports it (Linux), and GPU
+//! memory allocated where the backend reports it (DirectX 12). Columns
+//! without a value are left empty.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+/// Column names, the second line of a log.
+pub const HEADER: &str =
+    "frame,time_s,frame_ms,cpu_ms,draws,chunks,chunks_culled,resident_bytes,gpu_bytes";
+
+/// Time between memory samples.
+const MEMORY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One frame's measurements.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameSample {
+    /// When the frame started
+    pub started: Instant,
+    /// When its commands were submitted and presented
+    pub finished: Instant,
+    /// Terrain draw calls
+    pub draws: usize,
+    /// Terrain chunks
+    pub chunks: usize,
+    /// Chunks hidden by occlusion culling
+    pub culled: usize,
+}
+
+/// Memory in use, where it's reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Memory {
+    /// Resident memory of the process, in bytes
+    pub resident: Option<u64>,
+    /// GPU memory allocated by the device, in bytes
+    pub gpu: Option<u64>,
+}
+
+impl Memory {
+    /// Memory in use by this process and `device`.
+    pub fn sample(device: &wgpu::Device) -> Self {
+        Self {
+            resident: resident_bytes(),
+            gpu: device
+                .generate_allocator_report()
+                .map(|report| report.total_allocated_bytes),
+        }
+    }
+}
+
+/// Resident memory of the process, from `/proc/self/status`.
+#[cfg(target_os = "linux")]
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_bytes() -> Option<u64> {
+    None
+}
+
+/// A CSV log of frame timings, written as frames are drawn.
+#[derive(Debug)]
+pub struct PerfLog<W: Write = BufWriter<File>> {
+    out: W,
+    /// Frames logged so far
+    frames: u64,
+    /// Start of the first frame logged
+    start: Option<Instant>,
+    /// Start of the last frame logged
+    last: Option<Instant>,
+    /// Last memory sample and when it was taken
+    memory: Option<(Memory, Instant)>,
+}
+
+impl PerfLog {
+    /// Create a log at `path`, noting `about` (the version and GPU) first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn create(path: &Path, about: &str) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        Self::new(BufWriter::new(file), about)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+impl<W: Write> PerfLog<W> {
+    /// Start a log written to `out`.
+    pub fn new(mut out: W, about: &str) -> io::Result<Self> {
+        writeln!(out, "# {about}")?;
+        writeln!(out, "{HEADER}")?;
+        Ok(Self {
+            out,
+            frames: 0,
+            start: None,
+            last: None,
+            memory: None,
+        })
+    }
+
+    /// Add a row for `frame`, sampling memory with `memory` if the last
+    /// sample is a second old.
+    pub fn record(
+        &mut self,
+        frame: &FrameSample,
+        memory: impl FnOnce() -> Memory,
+    ) -> io::Result<()> {
+        let start = *self.start.get_or_insert(frame.started);
+        let frame_ms = self
+            .last
+            .replace(frame.started)
+            .map(|last| format!("{:.3}", millis(frame.started - last)))
+            .unwrap_or_default();
+        let memory = match self.memory {
+            Some((memory, at)) if frame.started - at < MEMORY_INTERVAL => memory,
+            _ => {
+                let memory = memory();
+                self.memory = Some((memory, frame.started));
+                memory
+            }
+        };
+        let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+        writeln!(
+            self.out,
+            "{},{:.3},{},{:.3},{},{},{},{},{}",
+            self.frames,
+            (frame.started - start).as_secs_f64(),
+            frame_ms,
+            millis(frame.finished.saturating_duration_since(frame.started)),
+            frame.draws,
+            frame.chunks,
+            frame.culled,
+            optional(memory.resident),
+            optional(memory.gpu),
+        )?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Write out rows still buffered.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// The writer the log went to.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// A duration in milliseconds.
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_time_frames_and_sample_memory_each_second() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut log = PerfLog::new(Vec::new(), "lrle 0.1.0 on Test GPU").unwrap();
+        let mut samples = 0;
+        for (i, started) in [0, 16, 40, 1010].into_iter().enumerate() {
+            let frame = FrameSample {
+                started: ms(started),
+                finished: ms(started + 5),
+                draws: 2,
+                chunks: 16,
+                culled: i,
+            };
+            log.record(&frame, || {
+                samples += 1;
+                Memory {
+                    resident: Some(1024 * samples),
+                    gpu: None,
+                }
+            })
+            .unwrap();
+        }
+
+        let text = String::from_utf8(log.into_inner()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "# lrle 0.1.0 on Test GPU");
+        assert_eq!(lines[1], HEADER);
+        assert_eq!(lines[2], "0,0.000,,5.000,2,16,0,1024,");
+        assert_eq!(lines[3], "1,0.016,16.000,5.000,2,16,1,1024,");
+        assert_eq!(lines[4], "2,0.040,24.000,5.000,2,16,2,1024,");
+        // A second on, memory is sampled again
+        assert_eq!(lines[5], "3,1.010,970.000,5.000,2,16,3,2048,");
+        assert_eq!(samples, 2);
+    }
+}
@@ -0,0 +1,170 @@
+//! Pipelines compiled ahead of use and kept across runs.
+//!
+//! Building a render pipeline compiles its shaders for the GPU, long enough
+//! to stall a frame. Two things keep that out of the frames after startup:
+//!
+//! - Where the device supports it (Vulkan), pipelines are built through a
+//!   [`PipelineCache`] whose data is written to `$XDG_CACHE_HOME/lrle`
+//!   (`~/.cache/lrle` by default) on exit, one file per GPU and driver, and
+//!   read back on the next launch, so pipelines compiled before load from
+//!   it instead.
+//! - The window's renderer builds its pipelines once for every sample count
+//!   and depth convention the settings can switch to, one [`Warmup`] step a
+//!   frame behind a progress splash, so switching later finds them compiled,
+//!   in the pipeline cache or else in the driver's own shader cache.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use super::DepthMode;
+
+/// A wgpu pipeline cache, where the device has one, and the file it's kept
+/// in between runs.
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    cache: Option<wgpu::PipelineCache>,
+    file: Option<PathBuf>,
+}
+
+impl PipelineCache {
+    /// `lrle` in the user's cache directory, if they have one.
+    pub fn default_dir() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(dir.join("lrle"))
+    }
+
+    /// A cache on `device` of adapter `info`, started from and saved to its
+    /// file in `dir` if given, or none if the device doesn't support one.
+    pub fn new(device: &wgpu::Device, info: &wgpu::AdapterInfo, dir: Option<&Path>) -> Self {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return Self::default();
+        }
+        let file = dir
+            .zip(wgpu::util::pipeline_cache_key(info))
+            .map(|(dir, key)| dir.join(key));
+        let data = file.as_deref().and_then(|file| std::fs::read(file).ok());
+        // SAFETY: the data was written by `save` from the cache of a device
+        // of the same adapter and driver, which the file name encodes; wgpu
+        // checks its header and, with `fallback`, starts empty on a mismatch
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline Cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Self {
+            cache: Some(cache),
+            file,
+        }
+    }
+
+    /// The cache to build pipelines through, if the device has one.
+    pub fn get(&self) -> Option<&wgpu::PipelineCache> {
+        self.cache.as_ref()
+    }
+
+    /// Write the cache's data to its file, if it has both. The file is
+    /// replaced in one rename, so a crash mid-write keeps the last copy.
+    pub fn save(&self) -> Result<()> {
+        let (Some(cache), Some(file)) = (&self.cache, &self.file) else {
+            return Ok(());
+        };
+        let Some(data) = cache.get_data() else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temporary = file.with_extension("tmp");
+        std::fs::write(&temporary, data)?;
+        std::fs::rename(&temporary, file)?;
+        Ok(())
+    }
+}
+
+/// The pipeline variants still to build ahead of use: pairs of a sample
+/// count and a depth convention.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Warmup {
+    pending: Vec<(u32, DepthMode)>,
+    total: usize,
+}
+
+impl Warmup {
+    /// Every pairing of `sample_counts` with each depth convention, except
+    /// `current`, which is built already. Variants of the current depth
+    /// convention come first, as MSAA is switched more often.
+    pub fn new(sample_counts: &[u32], current: (u32, DepthMode)) -> Self {
+        let mut modes = vec![current.1];
+        modes.extend(
+            [
+                DepthMode::Reversed,
+                DepthMode::Standard,
+                DepthMode::Logarithmic,
+            ]
+            .into_iter()
+            .filter(|&mode| mode != current.1),
+        );
+        let pending: Vec<_> = modes
+            .into_iter()
+            .flat_map(|mode| sample_counts.iter().map(move |&samples| (samples, mode)))
+            .filter(|&variant| variant != current)
+            .rev()
+            .collect();
+        Self {
+            total: pending.len(),
+            pending,
+        }
+    }
+
+    /// The next variant to build, if any are left.
+    pub fn next_variant(&mut self) -> Option<(u32, DepthMode)> {
+        self.pending.pop()
+    }
+
+    /// Whether every variant is built.
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Share of the variants built, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        match self.total {
+            0 => 1.0,
+            total => (total - self.pending.len()) as f32 / total as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_skips_current_variant_and_counts_progress() {
+        let mut warmup = Warmup::new(&[1, 4], (1, DepthMode::Reversed));
+        assert_eq!(warmup.fraction(), 0.0);
+        let mut built = Vec::new();
+        while let Some(variant) = warmup.next_variant() {
+            built.push(variant);
+            assert_eq!(warmup.fraction(), built.len() as f32 / 5.0);
+        }
+        assert_eq!(
+            built,
+            vec![
+                (4, DepthMode::Reversed),
+                (1, DepthMode::Standard),
+                (4, DepthMode::Standard),
+                (1, DepthMode::Logarithmic),
+                (4, DepthMode::Logarithmic),
+            ]
+        );
+        assert!(warmup.is_done());
+        assert_eq!(Warmup::default().fraction(), 1.0);
+    }
+}
@@ -0,0 +1,238 @@
+//! Crash recovery of unsaved edits.
+//!
+//! While the viewer runs, edits that changed are written every
+//! [`AUTOSAVE_INTERVAL`] to a recovery directory next to the terrain file
+//! (`alps.tif` keeps them in `alps.tif.recovery/`):
+//!
+//! - `terrain.fdf`: the terrain with its painted colors, once painted
+//! - `annotations.geojson`: the drawn shapes and notes
+//! - `camera_path.json`: the camera path's keyframes
+//!
+//! Each file is written under a temporary name and renamed over the last
+//! copy, so a crash mid-write leaves the previous one. Quitting normally
+//! removes the directory; one found at startup was left by a crash, and the
+//! viewer offers to restore it.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use super::camera_path::CameraPath;
+use crate::terrain::annotations::{self, Annotation};
+use crate::terrain::{loader, TerrainData};
+
+/// Time between checks for edits to write.
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+const TERRAIN_FILE: &str = "terrain.fdf";
+const ANNOTATIONS_FILE: &str = "annotations.geojson";
+const CAMERA_PATH_FILE: &str = "camera_path.json";
+
+/// Edits read back from a recovery directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Edits {
+    /// Painted per-sample colors, if the terrain was painted
+    pub colors: Option<Vec<u32>>,
+    /// Drawn shapes and notes
+    pub annotations: Vec<Annotation>,
+    /// Camera path keyframes
+    pub camera_path: CameraPath,
+}
+
+impl Edits {
+    /// Read the edits left in `dir`, or `None` if there is no such
+    /// directory.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        let read = |name: &str| -> Result<Option<String>> {
+            let path = dir.join(name);
+            match std::fs::read_to_string(&path) {
+                Ok(text) => Ok(Some(text)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+            }
+        };
+        let colors = match read(TERRAIN_FILE)? {
+            Some(text) => loader::parse_fdf_content(&text)?.colors,
+            None => None,
+        };
+        let annotations = match read(ANNOTATIONS_FILE)? {
+            Some(text) => annotations::from_geojson(&text)?,
+            None => Vec::new(),
+        };
+        let camera_path = match read(CAMERA_PATH_FILE)? {
+            Some(text) => CameraPath::from_json(&text)?,
+            None => CameraPath::default(),
+        };
+        Ok(Some(Self {
+            colors,
+            annotations,
+            camera_path,
+        }))
+    }
+
+    /// Short list of what was recovered, e.g. "painted colors, 2
+    /// annotations".
+    pub fn summary(&self) -> String {
+        let count = |n: usize, what: &str| match n {
+            1 => format!("1 {what}"),
+            n => format!("{n} {what}s"),
+        };
+        let mut parts = Vec::new();
+        if self.colors.is_some() {
+            parts.push("painted colors".to_string());
+        }
+        if !self.annotations.is_empty() {
+            parts.push(count(self.annotations.len(), "annotation"));
+        }
+        if !self.camera_path.keyframes.is_empty() {
+            parts.push(count(self.camera_path.keyframes.len(), "camera keyframe"));
+        }
+        match parts.is_empty() {
+            true => "nothing".to_string(),
+            false => parts.join(", "),
+        }
+    }
+}
+
+/// Periodic writer of edits to a recovery directory.
+#[derive(Debug)]
+pub struct Autosave {
+    /// Directory edits are written to
+    pub dir: PathBuf,
+    /// Time of the last check
+    last_check: Instant,
+    /// Paint strokes, annotations and camera path as last written
+    written: (u64, Vec<Annotation>, CameraPath),
+}
+
+impl Autosave {
+    /// Recovery directory of the terrain at `terrain`.
+    pub fn dir_for(terrain: &Path) -> PathBuf {
+        let mut name = terrain.as_os_str().to_owned();
+        name.push(".recovery");
+        PathBuf::from(name)
+    }
+
+    /// Write edits to `dir` once they differ from `annotations` and
+    /// `camera_path`, the state the session starts with.
+    pub fn new(dir: PathBuf, annotations: &[Annotation], camera_path: &CameraPath) -> Self {
+        Self {
+            dir,
+            last_check: Instant::now(),
+            written: (0, annotations.to_vec(), camera_path.clone()),
+        }
+    }
+
+    /// Whether the interval since the last check has passed.
+    pub fn due(&self) -> bool {
+        self.last_check.elapsed() >= AUTOSAVE_INTERVAL
+    }
+
+    /// Write the edits that changed since last written: `terrain` once
+    /// `strokes` paint strokes changed it, `annotations` and
+    /// `camera_path`. Returns whether anything was written.
+    pub fn write(
+        &mut self,
+        strokes: u64,
+        terrain: &TerrainData,
+        annotations: &[Annotation],
+        camera_path: &CameraPath,
+    ) -> Result<bool> {
+        self.last_check = Instant::now();
+        let (written_strokes, written_annotations, written_path) = &self.written;
+        let painted = strokes != *written_strokes;
+        let annotated = annotations != written_annotations.as_slice();
+        let moved = camera_path != written_path;
+        if !(painted || annotated || moved) {
+            return Ok(false);
+        }
+
+        std::fs::create_dir_all(&self.dir)?;
+        if painted {
+            self.replace(TERRAIN_FILE, &loader::to_fdf(terrain))?;
+        }
+        if annotated {
+            self.replace(
+                ANNOTATIONS_FILE,
+                &annotations::to_geojson(annotations, terrain),
+            )?;
+        }
+        if moved {
+            self.replace(CAMERA_PATH_FILE, &camera_path.to_json())?;
+        }
+        self.written = (strokes, annotations.to_vec(), camera_path.clone());
+        Ok(true)
+    }
+
+    /// Write `contents` to file `name`, replacing it only once complete.
+    fn replace(&self, name: &str, contents: &str) -> Result<()> {
+        let path = self.dir.join(name);
+        let partial = self.dir.join(format!("{name}.tmp"));
+        std::fs::write(&partial, contents)?;
+        std::fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    /// Remove the recovery directory, e.g. on a clean exit.
+    pub fn discard(&self) -> Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::camera_path::Keyframe;
+    use glam::{DVec2, DVec3};
+
+    #[test]
+    fn test_edits_recovered_from_autosave() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = Autosave::dir_for(&tmp.path().join("alps.fdf"));
+        let mut terrain = TerrainData::new(vec![vec![0.0, 1.0], vec![2.0, 3.0]], None);
+        let mut autosave = Autosave::new(dir.clone(), &[], &CameraPath::default());
+
+        // Nothing changed, nothing written
+        let path = CameraPath::default();
+        assert!(!autosave.write(0, &terrain, &[], &path).unwrap());
+        assert_eq!(Edits::load(&dir).unwrap(), None);
+
+        let annotations = vec![Annotation::Note {
+            position: DVec2::new(0.5, 0.5),
+            text: "Camp".to_string(),
+        }];
+        assert!(autosave.write(0, &terrain, &annotations, &path).unwrap());
+        terrain.colors = Some(vec![0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF]);
+        let mut path = CameraPath::default();
+        path.insert(Keyframe {
+            time: 2.0,
+            target: DVec3::new(1.0, 0.0, 1.0),
+            distance: 10.0,
+            azimuth: 0.5,
+            elevation: 0.25,
+            fov: 45.0,
+        });
+        assert!(autosave.write(1, &terrain, &annotations, &path).unwrap());
+        assert!(!autosave.due());
+
+        let edits = Edits::load(&dir).unwrap().unwrap();
+        assert_eq!(edits.colors, terrain.colors);
+        assert_eq!(edits.annotations, annotations);
+        assert_eq!(edits.camera_path.keyframes.len(), 1);
+        assert_eq!(
+            edits.summary(),
+            "painted colors, 1 annotation, 1 camera keyframe"
+        );
+
+        autosave.discard().unwrap();
+        assert!(!dir.exists());
+        autosave.discard().unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+//! Screenshot export.
+//!
+//! The renderer draws the current view again into an offscreen texture of
+//! the window's size, without the UI, and reads it back; the frame is
+//! written here as a PNG named after the time it was taken.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+/// Encode RGBA texels, row by row from the top, as a PNG file.
+pub fn to_png(pixels: &[[u8; 4]], (width, height): (u32, u32)) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels.as_flattened())?;
+    writer.finish()?;
+    Ok(out)
+}
+
+/// Path in `dir` for a screenshot taken at `time`, e.g.
+/// `lrle-20240315-093000.png` in UTC. A number is appended if a file of
+/// that name already exists, as for several shots within a second.
+pub fn path_in(dir: &Path, time: SystemTime) -> PathBuf {
+    let name = format!("lrle-{}", timestamp(time));
+    let mut path = dir.join(format!("{name}.png"));
+    for i in 2.. {
+        if !path.exists() {
+            break;
+        }
+        path = dir.join(format!("{name}-{i}.png"));
+    }
+    path
+}
+
+/// `time` as `YYYYMMDD-HHMMSS` in UTC.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Gregorian (year, month, day) of the day `days` after 1970-01-01, after
+/// Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamped_names() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_112_345);
+        assert_eq!(timestamp(time), "20240228-092545");
+        assert_eq!(
+            timestamp(time + Duration::from_secs(86_400)),
+            "20240229-092545"
+        );
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+
+        // Taken twice in a second
+        let dir = tempfile::tempdir().unwrap();
+        let first = path_in(dir.path(), time);
+        assert_eq!(first, dir.path().join("lrle-20240228-092545.png"));
+        std::fs::write(&first, []).unwrap();
+        let second = path_in(dir.path(), time);
+        assert_eq!(second, dir.path().join("lrle-20240228-092545-2.png"));
+    }
+}
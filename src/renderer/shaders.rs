@@ -0,0 +1,525 @@
+//! WGSL shader sources and hot-reload support.
+//!
+//! Release builds always use the shaders embedded with `include_str!`.
+//! Debug builds read them from `src/shaders/` on disk instead, so a
+//! contributor can edit a shader and see the result without recompiling.
+//! The embedded copy is used as a fallback when the file can't be read.
+//!
+//! The solid shader also accepts a user-supplied WGSL color function (see
+//! [`patch_user_color`]), which is patched in at runtime. [`load`] assembles
+//! the final sources, including the generated [`ShaderParams`] prelude.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::params::ShaderParams;
+
+/// A WGSL shader file with its compile-time embedded contents.
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderFile {
+    /// File name relative to the shaders directory
+    pub name: &'static str,
+    /// Source embedded at compile time
+    pub embedded: &'static str,
+}
+
+/// Wireframe line shader.
+pub const WIREFRAME: ShaderFile = ShaderFile {
+    name: "terrain.wgsl",
+    embedded: include_str!("../shaders/terrain.wgsl"),
+};
+
+/// Solid shaded surface shader.
+pub const SOLID: ShaderFile = ShaderFile {
+    name: "solid.wgsl",
+    embedded: include_str!("../shaders/solid.wgsl"),
+};
+
+/// Geometry clipmap surface shader.
+pub const CLIPMAP: ShaderFile = ShaderFile {
+    name: "clipmap.wgsl",
+    embedded: include_str!("../shaders/clipmap.wgsl"),
+};
+
+/// Chunk culling compute shader building indirect draws.
+pub const CULL: ShaderFile = ShaderFile {
+    name: "cull.wgsl",
+    embedded: include_str!("../shaders/cull.wgsl"),
+};
+
+/// Depth-only shader rendering the shadow map.
+pub const SHADOW: ShaderFile = ShaderFile {
+    name: "shadow.wgsl",
+    embedded: include_str!("../shaders/shadow.wgsl"),
+};
+
+/// Composite shader resolving order-independent transparency.
+pub const OIT: ShaderFile = ShaderFile {
+    name: "oit.wgsl",
+    embedded: include_str!("../shaders/oit.wgsl"),
+};
+
+/// Composite shader applying per-layer blend modes.
+pub const BLEND: ShaderFile = ShaderFile {
+    name: "blend.wgsl",
+    embedded: include_str!("../shaders/blend.wgsl"),
+};
+
+/// Color vision deficiency simulation over the finished scene.
+pub const COLORBLIND: ShaderFile = ShaderFile {
+    name: "colorblind.wgsl",
+    embedded: include_str!("../shaders/colorblind.wgsl"),
+};
+
+/// Glow around bright pixels of the finished scene.
+pub const BLOOM: ShaderFile = ShaderFile {
+    name: "bloom.wgsl",
+    embedded: include_str!("../shaders/bloom.wgsl"),
+};
+
+/// Copies multisampled scene depth into the single-sampled depth buffer.
+pub const DEPTH_RESOLVE: ShaderFile = ShaderFile {
+    name: "depth_resolve.wgsl",
+    embedded: include_str!("../shaders/depth_resolve.wgsl"),
+};
+
+/// Scene uniforms and fog, prepended to the terrain and sky shaders.
+pub const SCENE: ShaderFile = ShaderFile {
+    name: "scene.wgsl",
+    embedded: include_str!("../shaders/scene.wgsl"),
+};
+
+/// Gradient sky behind the scene.
+pub const SKY: ShaderFile = ShaderFile {
+    name: "sky.wgsl",
+    embedded: include_str!("../shaders/sky.wgsl"),
+};
+
+/// Reference grid on the zero-height plane.
+pub const GROUND_GRID: ShaderFile = ShaderFile {
+    name: "ground_grid.wgsl",
+    embedded: include_str!("../shaders/ground_grid.wgsl"),
+};
+
+/// Background fill for the overview inset.
+pub const OVERVIEW: ShaderFile = ShaderFile {
+    name: "overview.wgsl",
+    embedded: include_str!("../shaders/overview.wgsl"),
+};
+
+impl ShaderFile {
+    /// Current shader source.
+    ///
+    /// In debug builds this reads the file from [`shader_dir`], falling back
+    /// to the embedded source. Release builds return the embedded source.
+    pub fn source(&self) -> Cow<'static, str> {
+        if cfg!(debug_assertions) {
+            if let Ok(source) = fs::read_to_string(shader_dir().join(self.name)) {
+                return Cow::Owned(source);
+            }
+        }
+        Cow::Borrowed(self.embedded)
+    }
+}
+
+/// Final shader sources ready for pipeline creation.
+#[derive(Debug, Clone)]
+pub struct ShaderSources {
+    /// Wireframe shader with the parameter and scene preludes
+    pub wireframe: String,
+    /// Solid shader with the user color snippet and the parameter and
+    /// scene preludes
+    pub solid: String,
+    /// Clipmap shader with the parameter and scene preludes
+    pub clipmap: String,
+    /// Sky shader with the scene prelude
+    pub sky: String,
+    /// Chunk culling compute shader, used as is
+    pub cull: String,
+    /// Shadow map depth shader, used as is
+    pub shadow: String,
+    /// Transparency composite shader, used as is
+    pub oit: String,
+    /// Blend mode composite shader, used as is
+    pub blend: String,
+    /// Color vision simulation shader, used as is
+    pub colorblind: String,
+    /// Bloom shader, used as is
+    pub bloom: String,
+    /// Multisampled depth resolve shader, used as is
+    pub depth_resolve: String,
+    /// Ground grid shader, used as is
+    pub ground_grid: String,
+    /// Overview inset background shader, used as is
+    pub overview: String,
+    /// Parameters declared across all shaders
+    pub params: ShaderParams,
+}
+
+/// Load and assemble all shaders.
+///
+/// Patches in the user color snippet at `color_snippet` (if any), collects
+/// the `@param` annotations, and prepends the generated parameter prelude
+/// and the scene uniforms prelude.
+///
+/// # Errors
+///
+/// Returns a message if the snippet can't be read or patched, or if a
+/// parameter annotation is malformed.
+pub fn load(color_snippet: Option<&Path>) -> Result<ShaderSources, String> {
+    let wireframe = WIREFRAME.source();
+    let clipmap = CLIPMAP.source();
+    let mut solid = SOLID.source().into_owned();
+
+    if let Some(path) = color_snippet {
+        let snippet = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        solid =
+            patch_user_color(&solid, &snippet).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    let params = ShaderParams::parse(&[&wireframe, &solid, &clipmap])?;
+    let prelude = format!("{}\n{}", params.wgsl_prelude(), SCENE.source());
+
+    Ok(ShaderSources {
+        wireframe: format!("{}\n{}", prelude, wireframe),
+        solid: format!("{}\n{}", prelude, solid),
+        clipmap: format!("{}\n{}", prelude, clipmap),
+        sky: format!("{}\n{}", SCENE.source(), SKY.source()),
+        cull: CULL.source().into_owned(),
+        shadow: SHADOW.source().into_owned(),
+        oit: OIT.source().into_owned(),
+        blend: BLEND.source().into_owned(),
+        colorblind: COLORBLIND.source().into_owned(),
+        bloom: BLOOM.source().into_owned(),
+        depth_resolve: DEPTH_RESOLVE.source().into_owned(),
+        ground_grid: GROUND_GRID.source().into_owned(),
+        overview: OVERVIEW.source().into_owned(),
+        params,
+    })
+}
+
+/// Marker lines delimiting the replaceable `user_color` block in the solid shader.
+const USER_COLOR_BEGIN: &str = "// @user_color begin";
+const USER_COLOR_END: &str = "// @user_color end";
+
+/// Replace the default `user_color` block in `source` with a user snippet.
+///
+/// The snippet must define
+/// `fn user_color(height: f32, normal: vec3f) -> vec3f`, where `height` is
+/// the world-space height (after height scaling) and `normal` is the
+/// normalized surface normal. The returned color is lit like the built-in
+/// gradient colors.
+///
+/// # Errors
+///
+/// Returns a message if the snippet doesn't define `user_color` or the
+/// shader has no `user_color` block to replace.
+pub fn patch_user_color(source: &str, snippet: &str) -> Result<String, String> {
+    if !snippet.contains("fn user_color") {
+        return Err(
+            "color snippet must define `fn user_color(height: f32, normal: vec3f) -> vec3f`"
+                .to_string(),
+        );
+    }
+
+    let begin = source
+        .find(USER_COLOR_BEGIN)
+        .ok_or("shader has no user_color block")?;
+    let end = source[begin..]
+        .find(USER_COLOR_END)
+        .map(|i| begin + i + USER_COLOR_END.len())
+        .ok_or("shader has no end of user_color block")?;
+
+    Ok(format!(
+        "{}const USE_USER_COLOR: bool = true;\n\n{}\n{}",
+        &source[..begin],
+        snippet.trim_end(),
+        &source[end..]
+    ))
+}
+
+/// Directory the shaders are loaded from in debug builds.
+pub fn shader_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src")
+        .join("shaders")
+}
+
+/// Polls directories and files for modified `.wgsl` shaders.
+///
+/// Uses file modification times rather than OS notifications, which is
+/// plenty for a handful of shader files and keeps the watcher portable.
+pub struct ShaderWatcher {
+    paths: Vec<PathBuf>,
+    mtimes: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+    /// Minimum time between directory scans
+    pub interval: Duration,
+}
+
+impl ShaderWatcher {
+    /// Start watching `path` (a shader directory or a single file),
+    /// recording the current state of its shaders.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let paths = vec![path.into()];
+        let mtimes = scan(&paths);
+        Self {
+            paths,
+            mtimes,
+            last_poll: Instant::now(),
+            interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Also watch `path`, a shader directory or a single file.
+    pub fn watch(&mut self, path: impl Into<PathBuf>) {
+        self.paths.push(path.into());
+        self.mtimes = scan(&self.paths);
+    }
+
+    /// Rate-limited [`check`](Self::check), cheap enough to call every frame.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < self.interval {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        self.check()
+    }
+
+    /// Rescan the directory and return true if any shader was added,
+    /// removed, or modified since the last scan.
+    pub fn check(&mut self) -> bool {
+        let mtimes = scan(&self.paths);
+        let changed = mtimes != self.mtimes;
+        self.mtimes = mtimes;
+        changed
+    }
+}
+
+/// Collect modification times of the watched files and of all `.wgsl`
+/// files in the watched directories.
+fn scan(paths: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    let mut files = Vec::new();
+    for path in paths {
+        match fs::read_dir(path) {
+            Ok(entries) => files.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "wgsl")),
+            ),
+            Err(_) => files.push(path.clone()),
+        }
+    }
+
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let mtime = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((path, mtime))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse and validate WGSL, panicking with the error message on failure.
+    fn validate(source: &str) {
+        let module = naga::front::wgsl::parse_str(source)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(source)));
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_loaded_shaders_are_valid() {
+        let sources = load(None).unwrap();
+        for source in [&sources.wireframe, &sources.solid, &sources.clipmap] {
+            assert!(source.contains("fn vs_main"));
+            assert!(source.contains("fn fs_main"));
+            validate(source);
+        }
+    }
+
+    #[test]
+    fn test_cull_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.cull.contains("fn cs_main"));
+        validate(&sources.cull);
+    }
+
+    #[test]
+    fn test_shadow_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.shadow.contains("fn vs_main"));
+        validate(&sources.shadow);
+    }
+
+    #[test]
+    fn test_oit_shaders_are_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.solid.contains("fn fs_oit_log_depth"));
+        assert!(sources.oit.contains("fn fs_main"));
+        validate(&sources.oit);
+    }
+
+    #[test]
+    fn test_blend_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.blend.contains("fn blend_colors"));
+        validate(&sources.blend);
+    }
+
+    #[test]
+    fn test_colorblind_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.colorblind.contains("fn fs_main"));
+        validate(&sources.colorblind);
+    }
+
+    #[test]
+    fn test_bloom_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.bloom.contains("fn fs_composite"));
+        validate(&sources.bloom);
+    }
+
+    #[test]
+    fn test_depth_resolve_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.depth_resolve.contains("texture_multisampled_2d"));
+        validate(&sources.depth_resolve);
+    }
+
+    #[test]
+    fn test_sky_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.sky.contains("var<uniform> scene"));
+        validate(&sources.sky);
+    }
+
+    #[test]
+    fn test_scene_prelude_binding() {
+        let binding = format!("@binding({})", crate::renderer::atmosphere::SCENE_BINDING);
+        assert!(SCENE.source().contains(&binding));
+    }
+
+    #[test]
+    fn test_ground_grid_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.ground_grid.contains("fn fs_main_log_depth"));
+        validate(&sources.ground_grid);
+    }
+
+    #[test]
+    fn test_overview_shader_is_valid() {
+        let sources = load(None).unwrap();
+        assert!(sources.overview.contains("fn fs_main"));
+        validate(&sources.overview);
+    }
+
+    #[test]
+    fn test_example_snippet_is_valid() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/color_slope.wgsl");
+        let sources = load(Some(&path)).unwrap();
+
+        assert!(sources.params.get("band_spacing").is_some());
+        validate(&sources.solid);
+    }
+
+    #[test]
+    fn test_load_missing_snippet() {
+        assert!(load(Some(Path::new("/nonexistent/snippet.wgsl"))).is_err());
+    }
+
+    #[test]
+    fn test_watcher_detects_new_and_modified_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.wgsl");
+        let mut watcher = ShaderWatcher::new(dir.path());
+        assert!(!watcher.check());
+
+        fs::write(&path, "// v1").unwrap();
+        assert!(watcher.check());
+        assert!(!watcher.check());
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(watcher.check());
+    }
+
+    #[test]
+    fn test_watcher_ignores_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = ShaderWatcher::new(dir.path());
+
+        fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+        assert!(!watcher.check());
+    }
+
+    #[test]
+    fn test_watcher_watches_single_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snippet.wgsl");
+        fs::write(&path, "// v1").unwrap();
+        let mut watcher = ShaderWatcher::new(shader_dir());
+        watcher.watch(&path);
+        assert!(!watcher.check());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.check());
+    }
+
+    // ==================== User Color Tests ====================
+
+    const SNIPPET: &str =
+        "fn user_color(height: f32, normal: vec3f) -> vec3f {\n    return normal;\n}\n";
+
+    #[test]
+    fn test_patch_user_color_replaces_default() {
+        let patched = patch_user_color(SOLID.embedded, SNIPPET).unwrap();
+
+        assert!(patched.contains("const USE_USER_COLOR: bool = true;"));
+        assert!(!patched.contains("const USE_USER_COLOR: bool = false;"));
+        assert!(patched.contains("return normal;"));
+        assert!(!patched.contains("return vec3<f32>(0.0);"));
+        assert!(patched.contains("fn fs_main"));
+    }
+
+    #[test]
+    fn test_patch_user_color_requires_function() {
+        let result = patch_user_color(SOLID.embedded, "fn other() {}");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_patch_user_color_requires_markers() {
+        let result = patch_user_color(WIREFRAME.embedded, SNIPPET);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_poll_is_rate_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = ShaderWatcher::new(dir.path());
+        watcher.interval = Duration::from_secs(3600);
+
+        fs::write(dir.path().join("test.wgsl"), "// v1").unwrap();
+        assert!(!watcher.poll());
+    }
+}
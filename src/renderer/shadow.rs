@@ -0,0 +1,281 @@
+//! Directional shadow mapping.
+//!
+//! The terrain is rendered from the light with an orthographic projection
+//! fitted to the scene bounds, and the solid shader compares each
+//! fragment's light-space depth against this map. The depth pass always
+//! uses a standard (not reversed) depth range, independent of
+//! [`DepthMode`](super::DepthMode).
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2, Vec3};
+
+use super::HeightTransform;
+use crate::terrain::Vertex;
+
+/// Width and height of the shadow map in texels.
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Uniform data for the shadow depth pass, matching `shadow.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ShadowUniforms {
+    light_view_proj: [[f32; 4]; 4],
+    curvature_coef: f32,
+    _pad: f32,
+    curvature_center: [f32; 2],
+    height_scale: f32,
+    height_offset: f32,
+    _pad1: [f32; 2],
+}
+
+/// Orthographic light view-projection covering a bounding sphere, looking
+/// along `-direction` (the direction toward the light).
+pub fn light_view_proj(direction: Vec3, (center, radius): (Vec3, f32)) -> Mat4 {
+    let direction = direction.normalize_or(Vec3::Y);
+    let radius = radius.max(1e-3);
+    // Avoid a degenerate up vector when the light is straight overhead
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    let eye = center + direction * radius * 2.0;
+    let view = Mat4::look_at_rh(eye, center, up);
+    let proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, radius, radius * 3.0);
+    proj * view
+}
+
+/// Shadow map texture, sampler and depth-only pipeline.
+pub struct ShadowMap {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    /// Create the shadow map and its pipeline from `shadow.wgsl` source.
+    pub fn new(device: &wgpu::Device, cache: Option<&wgpu::PipelineCache>, source: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Uniform Buffer"),
+            size: std::mem::size_of::<ShadowUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("Shadow Bind Group Layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("Shadow Bind Group"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, cache, &pipeline_layout, source);
+
+        Self {
+            view,
+            sampler,
+            uniform_buffer,
+            bind_group,
+            pipeline_layout,
+            pipeline,
+        }
+    }
+
+    /// Build a depth pipeline from new shader source, to be installed with
+    /// [`set_pipeline`](Self::set_pipeline) once it validates.
+    pub fn build_pipeline(
+        &self,
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        source: &str,
+    ) -> wgpu::RenderPipeline {
+        create_pipeline(device, cache, &self.pipeline_layout, source)
+    }
+
+    /// Replace the depth pipeline.
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// Depth texture view, for binding in the solid shader.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Comparison sampler for the shadow map.
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    /// Write this frame's light transform and curvature.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        light_view_proj: Mat4,
+        curvature: (f32, Vec2),
+        height: HeightTransform,
+    ) {
+        let uniforms = ShadowUniforms {
+            light_view_proj: light_view_proj.to_cols_array_2d(),
+            curvature_coef: curvature.0,
+            _pad: 0.0,
+            curvature_center: curvature.1.to_array(),
+            height_scale: height.scale,
+            height_offset: height.offset,
+            _pad1: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Record the depth pass drawing `index_count` triangle indices.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        vertex_buffer: &wgpu::Buffer,
+        (index_buffer, index_format): (&wgpu::Buffer, wgpu::IndexFormat),
+        index_count: u32,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        pass.set_index_buffer(index_buffer.slice(..), index_format);
+        pass.draw_indexed(0..index_count, 0, 0..1);
+    }
+}
+
+/// Build the depth-only pipeline.
+fn create_pipeline(
+    device: &wgpu::Device,
+    cache: Option<&wgpu::PipelineCache>,
+    layout: &wgpu::PipelineLayout,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shadow Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Shadow Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: None,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: SHADOW_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            // Slope-scaled bias against shadow acne on steep terrain
+            bias: wgpu::DepthBiasState {
+                constant: 2,
+                slope_scale: 2.0,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadow_uniforms_layout() {
+        // mat4 + f32 + f32 + vec2, as in shadow.wgsl
+        assert_eq!(std::mem::size_of::<ShadowUniforms>(), 96);
+    }
+
+    #[test]
+    fn test_light_view_proj_covers_bounds() {
+        let bounds = (Vec3::new(10.0, 0.0, -5.0), 20.0);
+        for direction in [Vec3::new(0.5, 0.8, 0.3), Vec3::Y, Vec3::new(-1.0, 0.1, 0.0)] {
+            let m = light_view_proj(direction, bounds);
+            for offset in [
+                Vec3::ZERO,
+                Vec3::X,
+                -Vec3::Y,
+                Vec3::Z,
+                Vec3::new(0.5, 0.5, -0.5),
+            ] {
+                let p = m.project_point3(bounds.0 + offset * bounds.1 * 0.99);
+                assert!(p.x.abs() <= 1.0 && p.y.abs() <= 1.0, "{:?}", p);
+                assert!((0.0..=1.0).contains(&p.z), "{:?}", p);
+            }
+        }
+    }
+}
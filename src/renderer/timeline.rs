@@ -0,0 +1,120 @@
+//! Shared clock for animated views.
+//!
+//! One timeline drives every animation, so they play, pause, speed up and
+//! loop together. Its time runs from zero to the loop length; each track
+//! maps that span to what it animates: the sun study to a full day, the
+//! camera orbit to one turn around its target. The camera path track reads
+//! its keyframes at the timeline's time as is.
+
+/// Timeline clock and the tracks it drives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimelineConfig {
+    /// Whether the clock is running
+    pub playing: bool,
+    /// Start over at the end instead of stopping
+    pub looping: bool,
+    /// Timeline seconds per real second
+    pub speed: f32,
+    /// Loop length in timeline seconds
+    pub duration: f32,
+    /// Current time, from 0 to `duration`
+    pub time: f32,
+    /// Drive the sun study's time of day, a day per loop
+    pub sun: bool,
+    /// Orbit the camera around its target, a turn per loop
+    pub orbit: bool,
+    /// Move the camera along the camera path's keyframes
+    pub path: bool,
+}
+
+impl Default for TimelineConfig {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            looping: true,
+            speed: 1.0,
+            duration: 24.0,
+            time: 0.0,
+            sun: false,
+            orbit: false,
+            path: false,
+        }
+    }
+}
+
+impl TimelineConfig {
+    /// Run the clock for `dt` real seconds while playing, wrapping or
+    /// stopping at the end. Returns the timeline seconds that passed,
+    /// counting across a wrap.
+    pub fn advance(&mut self, dt: f32) -> f32 {
+        if !self.playing || self.duration <= 0.0 {
+            return 0.0;
+        }
+        let start = self.time;
+        let end = start + dt * self.speed;
+        if self.looping {
+            self.time = end.rem_euclid(self.duration);
+            end - start
+        } else {
+            self.time = end.clamp(0.0, self.duration);
+            if self.time != end {
+                self.playing = false;
+            }
+            self.time - start
+        }
+    }
+
+    /// Part of the loop played so far (0.0 - 1.0).
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0.0 {
+            (self.time / self.duration).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Time of day the sun track sets, in hours.
+    pub fn sun_hour(&self) -> f32 {
+        self.progress() * 24.0
+    }
+
+    /// Camera azimuth change, in radians, for `elapsed` timeline seconds
+    /// of the orbit track.
+    pub fn orbit_angle(&self, elapsed: f32) -> f32 {
+        if self.duration > 0.0 {
+            elapsed / self.duration * std::f32::consts::TAU
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_loops_or_stops() {
+        let mut timeline = TimelineConfig {
+            playing: true,
+            speed: 2.0,
+            duration: 10.0,
+            time: 9.0,
+            ..TimelineConfig::default()
+        };
+        // Across the end of the loop, the elapsed time keeps counting
+        assert_eq!(timeline.advance(1.0), 2.0);
+        assert_eq!(timeline.time, 1.0);
+        assert_eq!(timeline.orbit_angle(5.0), std::f32::consts::PI);
+
+        timeline.looping = false;
+        timeline.time = 9.0;
+        assert_eq!(timeline.advance(1.0), 1.0);
+        assert_eq!(timeline.time, 10.0);
+        assert!(!timeline.playing);
+        assert_eq!(timeline.sun_hour(), 24.0);
+
+        // Paused, nothing moves
+        assert_eq!(timeline.advance(1.0), 0.0);
+    }
+}
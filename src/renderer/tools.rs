@@ -0,0 +1,447 @@
+//! Terrain tools run by right-clicking the surface.
+//!
+//! The renderer picks the terrain point under the cursor and hands it to
+//! the selected tool. Results are kept in world space and drawn over the
+//! flat surface as one line overlay, rebuilt whenever the mesh is. The
+//! drawing tools add to the user's annotations instead; note text is
+//! drawn by the UI above each note's pin. The paint brush writes the
+//! terrain's own per-point colors, which can be saved as an .fdf file, and
+//! the selection tools outline a region whose statistics the UI shows,
+//! and the profile tool a cut whose heights it plots. The alignment tool
+//! pins control points matched with features of the draped image.
+
+use glam::{DVec2, DVec3, Vec3, Vec3Swizzles};
+
+use crate::terrain::annotations::Annotation;
+use crate::terrain::hydrology::Catchment;
+use crate::terrain::profile::Profile;
+use crate::terrain::selection::Selection;
+use crate::terrain::visibility::LineOfSight;
+use crate::terrain::{TerrainData, Vertex};
+
+/// Tool run on the picked point when right-clicking the terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tool {
+    /// Right-clicks do nothing
+    #[default]
+    None,
+    /// Delineate the catchment draining to the clicked point
+    Catchment,
+    /// Check the sight line between two clicked points
+    LineOfSight,
+    /// Plot the heights along the cut between two clicked points
+    Profile,
+    /// Draw an open line through clicked or dragged points
+    Polyline,
+    /// Draw a closed shape through clicked or dragged points
+    Polygon,
+    /// Pin a text note to the clicked point
+    Note,
+    /// Show the grid coordinates and height of the clicked point
+    Inspect,
+    /// Paint the terrain's per-point colors under the clicked or dragged
+    /// points with the [`Brush`]
+    Paint,
+    /// Select the samples in a rectangle dragged between two corners
+    SelectRectangle,
+    /// Select the samples inside a freehand outline
+    SelectLasso,
+    /// Place or drag control points matching terrain features with the
+    /// draped image, see [`DrapeAlignment`](crate::terrain::drape::DrapeAlignment)
+    AlignImage,
+}
+
+/// Color brush of the paint tool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+    /// Paint color in sRGB
+    pub color: [f32; 3],
+    /// Samples within this many cells of the picked one are painted
+    pub radius: f32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 0.2, 0.2],
+            radius: 3.0,
+        }
+    }
+}
+
+/// Samples searched around a clicked pour point for the strongest flow.
+pub const CATCHMENT_SNAP_RADIUS: usize = 2;
+
+/// Distance from a control point within which clicks grab it, in cells.
+pub const CONTROL_POINT_GRAB_RADIUS: f64 = 3.0;
+
+/// Largest side of the draped image shown in the alignment window.
+pub const DRAPE_PREVIEW_SIZE: u32 = 256;
+
+/// Height of the outlet marker, in cells.
+const OUTLET_MARKER_HEIGHT: f64 = 4.0;
+
+/// Height of note pins, in cells.
+const NOTE_PIN_HEIGHT: f64 = 4.0;
+
+/// Size of the inspected point marker, in cells.
+const PICK_MARKER_SIZE: f64 = 1.5;
+
+/// Inspected point marker color.
+const PICK_MARKER_COLOR: [f32; 3] = [1.0, 0.3, 1.0];
+
+/// Selection outline color.
+const SELECTION_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// Annotation line color.
+const ANNOTATION_COLOR: [f32; 3] = [1.0, 0.85, 0.2];
+
+/// Image alignment control point color.
+const CONTROL_POINT_COLOR: [f32; 3] = [0.3, 0.6, 1.0];
+
+/// Profile cut line color.
+const PROFILE_COLOR: [f32; 3] = [1.0, 0.55, 0.1];
+
+/// Catchment outline color.
+const CATCHMENT_COLOR: [f32; 3] = [0.0, 0.9, 0.8];
+
+/// Sight line color up to the first obstruction.
+const SIGHT_CLEAR_COLOR: [f32; 3] = [0.2, 1.0, 0.3];
+
+/// Sight line color beyond the first obstruction.
+const SIGHT_BLOCKED_COLOR: [f32; 3] = [1.0, 0.2, 0.2];
+
+/// Line-list geometry of tool results and other lines over the surface.
+///
+/// Points are given in world space with unscaled heights; heights are
+/// multiplied by the height scale and positions made relative to the
+/// render origin as they're added.
+pub struct Overlay {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    origin: DVec3,
+    height_scale: f64,
+}
+
+impl Overlay {
+    pub fn new(origin: DVec3, height_scale: f64) -> Self {
+        Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            origin,
+            height_scale,
+        }
+    }
+
+    /// Trace `catchment`'s outline over the surface, raised by `lift`,
+    /// with a vertical marker at its outlet.
+    ///
+    /// Outline edges are split at every cell so they follow the surface.
+    pub fn catchment(&mut self, catchment: &Catchment, terrain: &TerrainData, lift: f64) {
+        for ring in catchment.polygons.iter().flatten() {
+            self.draped(ring, terrain, lift, CATCHMENT_COLOR);
+        }
+
+        let outlet = terrain.world_position(
+            catchment.outlet % terrain.width,
+            catchment.outlet / terrain.width,
+        );
+        self.push(outlet, 0.0, CATCHMENT_COLOR);
+        self.push(
+            outlet,
+            terrain.cell_size * OUTLET_MARKER_HEIGHT,
+            CATCHMENT_COLOR,
+        );
+    }
+
+    /// Trace a world-space (x, z) polyline over the surface, raised by
+    /// `lift`, splitting its edges at every cell so they follow the surface.
+    pub fn draped(&mut self, line: &[DVec2], terrain: &TerrainData, lift: f64, color: [f32; 3]) {
+        for edge in line.windows(2) {
+            let steps = ((edge[1] - edge[0]).length() / terrain.cell_size)
+                .ceil()
+                .max(1.0);
+            let mut prev = drape(terrain, edge[0]);
+            for i in 1..=steps as usize {
+                let next = drape(terrain, edge[0].lerp(edge[1], i as f64 / steps));
+                self.segment(prev, next, lift, color);
+                prev = next;
+            }
+        }
+    }
+
+    /// Trace an annotation's outline over the surface, raised by `lift`,
+    /// or the pin of a note.
+    pub fn annotation(&mut self, annotation: &Annotation, terrain: &TerrainData, lift: f64) {
+        if let Annotation::Note { position, .. } = annotation {
+            let ground = drape(terrain, *position);
+            self.push(ground, 0.0, ANNOTATION_COLOR);
+            self.push(ground, note_pin_height(terrain), ANNOTATION_COLOR);
+            return;
+        }
+        self.draped(&annotation.outline(), terrain, lift, ANNOTATION_COLOR);
+    }
+
+    /// Trace a selection's outline over the surface, raised by `lift`.
+    pub fn selection(&mut self, selection: &Selection, terrain: &TerrainData, lift: f64) {
+        self.draped(&selection.outline, terrain, lift, SELECTION_COLOR);
+    }
+
+    /// Mesh-space tops of the pins [`annotation`](Self::annotation) draws
+    /// for the notes among `annotations`, with their text.
+    pub fn note_anchors<'a>(
+        &self,
+        annotations: &'a [Annotation],
+        terrain: &TerrainData,
+    ) -> Vec<(Vec3, &'a str)> {
+        annotations
+            .iter()
+            .filter_map(|annotation| match annotation {
+                Annotation::Note { position, text } => Some((*position, text.as_str())),
+                _ => None,
+            })
+            .map(|(position, text)| {
+                let top = self.place(drape(terrain, position), note_pin_height(terrain));
+                (top, text)
+            })
+            .collect()
+    }
+
+    /// Trace a profile's cut over the surface, raised by `lift`, with a
+    /// vertical tick at each end.
+    pub fn profile(&mut self, profile: &Profile, terrain: &TerrainData, lift: f64) {
+        self.draped(&[profile.start, profile.end], terrain, lift, PROFILE_COLOR);
+        for end in [profile.start, profile.end] {
+            self.profile_end(end, terrain);
+        }
+    }
+
+    /// Mark one end of a profile cut with a vertical tick.
+    pub fn profile_end(&mut self, point: DVec2, terrain: &TerrainData) {
+        let ground = drape(terrain, point);
+        self.push(ground, 0.0, PROFILE_COLOR);
+        self.push(ground, note_pin_height(terrain), PROFILE_COLOR);
+    }
+
+    /// Draw the antenna masts and the sight line between them, red beyond
+    /// the first obstruction.
+    pub fn sight_line(&mut self, los: &LineOfSight) {
+        let config = los.config;
+        self.mast(los.observer, config.observer_height as f64);
+        self.mast(los.target, config.target_height as f64);
+
+        let Some(obstruction) = los.obstruction else {
+            self.segment(los.observer, los.target, 0.0, SIGHT_CLEAR_COLOR);
+            return;
+        };
+        let span = (los.target.xz() - los.observer.xz()).length();
+        let t = (obstruction.xz() - los.observer.xz()).length() / span;
+        let blocked = los.observer.lerp(los.target, t);
+        self.segment(los.observer, blocked, 0.0, SIGHT_CLEAR_COLOR);
+        self.segment(blocked, los.target, 0.0, SIGHT_BLOCKED_COLOR);
+    }
+
+    /// Draw an antenna mast of `height` below the world-space point `top`.
+    pub fn mast(&mut self, top: DVec3, height: f64) {
+        self.segment(top - DVec3::Y * height, top, 0.0, SIGHT_CLEAR_COLOR);
+    }
+
+    /// Mark the world-space point `point` with a cross along each axis.
+    pub fn marker(&mut self, point: DVec3, terrain: &TerrainData) {
+        let half = terrain.cell_size * PICK_MARKER_SIZE / 2.0;
+        for axis in [DVec3::X, DVec3::Z] {
+            self.segment(
+                point - axis * half,
+                point + axis * half,
+                0.0,
+                PICK_MARKER_COLOR,
+            );
+        }
+        // Lifted after scaling, so it keeps its size at any height scale
+        self.push(point, -half, PICK_MARKER_COLOR);
+        self.push(point, half, PICK_MARKER_COLOR);
+    }
+
+    /// Draw an image alignment control point as a pin on the surface at
+    /// the world-space (x, z) `position`, with a cross at its foot.
+    pub fn control_point(&mut self, position: DVec2, terrain: &TerrainData) {
+        let ground = drape(terrain, position);
+        self.push(ground, 0.0, CONTROL_POINT_COLOR);
+        self.push(ground, note_pin_height(terrain), CONTROL_POINT_COLOR);
+        let half = terrain.cell_size * PICK_MARKER_SIZE / 2.0;
+        for axis in [DVec3::X, DVec3::Z] {
+            self.segment(
+                ground - axis * half,
+                ground + axis * half,
+                0.0,
+                CONTROL_POINT_COLOR,
+            );
+        }
+    }
+
+    /// Draw a point feature as a pin `height` tall standing on the surface
+    /// at the world-space (x, z) `position`, with a diamond at its top.
+    pub fn point(&mut self, position: DVec2, terrain: &TerrainData, height: f64, color: [f32; 3]) {
+        let ground = drape(terrain, position);
+        self.push(ground, 0.0, color);
+        self.push(ground, height, color);
+        // Diamond a quarter of the pin's height wide, lifted with its top
+        let half = height / 8.0;
+        let corners = [DVec3::X, DVec3::Z, DVec3::NEG_X, DVec3::NEG_Z];
+        for (i, &a) in corners.iter().enumerate() {
+            let b = corners[(i + 1) % corners.len()];
+            self.segment(ground + a * half, ground + b * half, height - half, color);
+        }
+    }
+
+    /// Mesh-space tops of the pins [`point`](Self::point) draws for the
+    /// points at `positions`, `heights` tall.
+    pub fn point_anchors(
+        &self,
+        positions: impl IntoIterator<Item = DVec2>,
+        heights: &[f64],
+        terrain: &TerrainData,
+    ) -> Vec<Vec3> {
+        positions
+            .into_iter()
+            .zip(heights)
+            .map(|(position, &height)| self.place(drape(terrain, position), height))
+            .collect()
+    }
+
+    /// Draw an error bar through the world-space point `center`, reaching
+    /// `half` above and below it, capped by ticks `cap` wide along x.
+    pub fn whisker(&mut self, center: DVec3, half: f64, cap: f64, color: [f32; 3]) {
+        let (low, high) = (center - DVec3::Y * half, center + DVec3::Y * half);
+        self.segment(low, high, 0.0, color);
+        for end in [low, high] {
+            let tick = DVec3::X * cap / 2.0;
+            self.segment(end - tick, end + tick, 0.0, color);
+        }
+    }
+
+    /// Add a line between two world-space points, raised by `lift` after
+    /// scaling their heights.
+    fn segment(&mut self, a: DVec3, b: DVec3, lift: f64, color: [f32; 3]) {
+        self.push(a, lift, color);
+        self.push(b, lift, color);
+    }
+
+    /// Add one end of a line.
+    fn push(&mut self, point: DVec3, lift: f64, color: [f32; 3]) {
+        self.indices.push(self.vertices.len() as u32);
+        self.vertices.push(Vertex {
+            position: self.place(point, lift).to_array(),
+            color,
+            normal: [0.0, 1.0, 0.0],
+        });
+    }
+
+    /// Mesh-space position of a world-space point, raised by `lift` after
+    /// scaling its height.
+    fn place(&self, point: DVec3, lift: f64) -> Vec3 {
+        let scaled = DVec3::new(point.x, point.y * self.height_scale + lift, point.z);
+        (scaled - self.origin).as_vec3()
+    }
+}
+
+/// Height of note pins over `terrain`.
+fn note_pin_height(terrain: &TerrainData) -> f64 {
+    terrain.cell_size * NOTE_PIN_HEIGHT
+}
+
+/// World-space point on the unscaled surface above (x, z), with the height
+/// clamped to the grid footprint.
+fn drape(terrain: &TerrainData, point: DVec2) -> DVec3 {
+    let max = terrain.origin
+        + DVec2::new(
+            terrain.width.saturating_sub(1) as f64,
+            terrain.height.saturating_sub(1) as f64,
+        ) * terrain.cell_size;
+    let clamped = point.clamp(terrain.origin, max);
+    let height = terrain.height_at(clamped.x, clamped.y).unwrap_or(0.0);
+    DVec3::new(point.x, height, point.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::hydrology::FlowGrid;
+    use crate::terrain::visibility::SightConfig;
+
+    #[test]
+    fn test_catchment_outline_drapes_surface() {
+        // A slope draining toward x = 0, so each row is one catchment
+        let points = vec![(0..3).map(|x| x as f64).collect::<Vec<_>>(); 2];
+        let terrain = TerrainData::new(points, None);
+        let flow = FlowGrid::compute(&terrain);
+        let catchment = Catchment::delineate(&terrain, &flow, 0);
+        assert_eq!(catchment.samples, vec![0, 1, 2]);
+
+        let mut overlay = Overlay::new(DVec3::new(1.0, 0.0, 0.0), 2.0);
+        overlay.catchment(&catchment, &terrain, 0.5);
+
+        // A 3x1 rectangle split per cell: 8 outline segments, plus the marker
+        assert_eq!(overlay.indices.len(), (8 + 1) * 2);
+        for vertex in &overlay.vertices[..16] {
+            let [x, y, _] = vertex.position;
+            // Doubled heights, clamped at the grid edge, lifted
+            let expected = (x + 1.0).clamp(0.0, 2.0) * 2.0 + 0.5;
+            assert!((y - expected).abs() < 1e-6);
+        }
+        let marker = &overlay.vertices[16..];
+        assert_eq!(marker[0].position, [-1.0, 0.0, 0.0]);
+        assert_eq!(marker[1].position, [-1.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn test_note_pins_carry_text() {
+        let terrain = TerrainData::new(vec![vec![1.0; 3]; 3], None);
+        let annotations = [
+            Annotation::Polyline(vec![DVec2::ZERO, DVec2::new(2.0, 0.0)]),
+            Annotation::Note {
+                position: DVec2::ONE,
+                text: "Spring".to_string(),
+            },
+        ];
+        let mut overlay = Overlay::new(DVec3::new(1.0, 0.0, 1.0), 2.0);
+        for annotation in &annotations {
+            overlay.annotation(annotation, &terrain, 0.0);
+        }
+
+        // Two line segments, then the pin from the doubled ground height
+        assert_eq!(overlay.indices.len(), 2 * 2 + 2);
+        assert_eq!(overlay.vertices[4].position, [0.0, 2.0, 0.0]);
+        let top = overlay.vertices[5].position;
+        assert_eq!(top, [0.0, 2.0 + NOTE_PIN_HEIGHT as f32, 0.0]);
+        assert_eq!(
+            overlay.note_anchors(&annotations, &terrain),
+            vec![(Vec3::from(top), "Spring")]
+        );
+    }
+
+    #[test]
+    fn test_sight_line_splits_at_obstruction() {
+        let row: Vec<f64> = (0..9).map(|x| if x == 4 { 5.0 } else { 0.0 }).collect();
+        let terrain = TerrainData::new(vec![row; 3], None);
+        let los = LineOfSight::compute(
+            &terrain,
+            DVec2::new(0.0, 1.0),
+            DVec2::new(8.0, 1.0),
+            &SightConfig::default(),
+        )
+        .unwrap();
+
+        let mut overlay = Overlay::new(DVec3::ZERO, 2.0);
+        overlay.sight_line(&los);
+        let positions: Vec<[f32; 3]> = overlay.vertices.iter().map(|v| v.position).collect();
+
+        // Masts from the ground to the doubled antenna heights
+        assert_eq!(&positions[..2], &[[0.0, 0.0, 1.0], [0.0, 4.0, 1.0]]);
+        // Clear up to the obstruction at x = 3.4, then blocked
+        assert_eq!(positions[4], [0.0, 4.0, 1.0]);
+        assert!((Vec3::from(positions[5]) - Vec3::new(3.4, 4.0, 1.0)).length() < 1e-5);
+        assert_eq!(overlay.vertices[6].color, SIGHT_BLOCKED_COLOR);
+        assert_eq!(positions.len(), 8);
+    }
+}
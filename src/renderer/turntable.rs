@@ -0,0 +1,304 @@
+//! Turntable rotation and animation export.
+//!
+//! [`AutoRotate`] spins the live view around its target at a steady rate,
+//! for showing a model off without touching the mouse. For export, the
+//! renderer draws a full turn of the camera around its target into an
+//! offscreen texture, one frame per step, and the frames are encoded here as
+//! a looping animated GIF or WebP for READMEs and issue reports.
+//!
+//! GIF frames are quantized to a 256-color palette each. WebP frames are
+//! stored losslessly, which keeps gradients smooth at a larger file size.
+
+use std::f32::consts::TAU;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+/// Turntable export settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TurntableConfig {
+    /// Number of frames in one full turn
+    pub frames: u32,
+    /// Frame width in pixels
+    pub width: u32,
+    /// Frame height in pixels
+    pub height: u32,
+    /// Length of one turn in seconds
+    pub duration: f32,
+}
+
+impl Default for TurntableConfig {
+    fn default() -> Self {
+        Self {
+            frames: 36,
+            width: 480,
+            height: 320,
+            duration: 4.0,
+        }
+    }
+}
+
+impl TurntableConfig {
+    /// Camera azimuth for `frame`, turning once from `start` over all frames.
+    pub fn azimuth(&self, start: f32, frame: u32) -> f32 {
+        start + TAU * frame as f32 / self.frames.max(1) as f32
+    }
+
+    /// Display time of each frame in milliseconds.
+    pub fn frame_delay_ms(&self) -> u32 {
+        (self.duration * 1000.0 / self.frames.max(1) as f32).round() as u32
+    }
+}
+
+/// Continuous turntable rotation of the live view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoRotate {
+    /// Whether the camera turns around its target
+    pub enabled: bool,
+    /// Turn rate in degrees per second; negative turns the other way
+    pub speed: f32,
+}
+
+impl Default for AutoRotate {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            speed: 10.0,
+        }
+    }
+}
+
+impl AutoRotate {
+    /// Azimuth change, in radians, over `dt` seconds.
+    pub fn angle(&self, dt: f32) -> f32 {
+        if self.enabled {
+            self.speed.to_radians() * dt
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Animated image formats for turntable export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurntableFormat {
+    Gif,
+    WebP,
+}
+
+impl TurntableFormat {
+    /// Pick a format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "gif" => Some(Self::Gif),
+            "webp" => Some(Self::WebP),
+            _ => None,
+        }
+    }
+
+    /// Encode `frames` of `width` x `height` RGBA texels as a looping
+    /// animation showing each frame for `delay_ms`.
+    pub fn encode(
+        self,
+        frames: &[Vec<[u8; 4]>],
+        (width, height): (u32, u32),
+        delay_ms: u32,
+    ) -> Result<Vec<u8>> {
+        if frames.is_empty() {
+            bail!("No frames to encode");
+        }
+        match self {
+            Self::Gif => encode_gif(frames, (width, height), delay_ms),
+            Self::WebP => encode_webp(frames, (width, height), delay_ms),
+        }
+    }
+}
+
+/// Encode frames as a looping GIF.
+fn encode_gif(
+    frames: &[Vec<[u8; 4]>],
+    (width, height): (u32, u32),
+    delay_ms: u32,
+) -> Result<Vec<u8>> {
+    let (Ok(w), Ok(h)) = (u16::try_from(width), u16::try_from(height)) else {
+        bail!("GIF frames are limited to 65535 pixels per side");
+    };
+    let mut out = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut out, w, h, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for pixels in frames {
+            let mut rgba: Vec<u8> = pixels.iter().flatten().copied().collect();
+            // Speed 10 is gif's default quantizer trade-off
+            let mut frame = gif::Frame::from_rgba_speed(w, h, &mut rgba, 10);
+            // GIF delays are in hundredths of a second
+            frame.delay = (delay_ms / 10).clamp(1, u16::MAX as u32) as u16;
+            encoder.write_frame(&frame)?;
+        }
+    }
+    Ok(out)
+}
+
+/// Encode frames as a looping animated WebP with lossless frames.
+///
+/// `image-webp` only writes still images, so each frame is encoded on its
+/// own and its `VP8L` chunk is wrapped in an `ANMF` frame chunk.
+fn encode_webp(
+    frames: &[Vec<[u8; 4]>],
+    (width, height): (u32, u32),
+    delay_ms: u32,
+) -> Result<Vec<u8>> {
+    if width > 1 << 24 || height > 1 << 24 {
+        bail!("WebP frames are limited to 16777216 pixels per side");
+    }
+    let mut body = Vec::new();
+    body.extend_from_slice(b"WEBP");
+
+    // Canvas with animation and alpha
+    let mut vp8x = vec![0x02 | 0x10, 0, 0, 0];
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+    write_chunk(&mut body, b"VP8X", &vp8x)?;
+
+    // Transparent background, loop forever
+    write_chunk(&mut body, b"ANIM", &[0, 0, 0, 0, 0, 0])?;
+
+    let delay = delay_ms.min((1 << 24) - 1);
+    for pixels in frames {
+        let rgba: Vec<u8> = pixels.iter().flatten().copied().collect();
+        let mut still = Vec::new();
+        image_webp::WebPEncoder::new(&mut still).encode(
+            &rgba,
+            width,
+            height,
+            image_webp::ColorType::Rgba8,
+        )?;
+
+        // Frame at the canvas origin, replacing the previous one
+        let mut anmf = vec![0; 6];
+        anmf.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&delay.to_le_bytes()[..3]);
+        anmf.push(0x02);
+        // The still is "RIFF", size, "WEBP", then the VP8L chunk
+        anmf.extend_from_slice(&still[12..]);
+        write_chunk(&mut body, b"ANMF", &anmf)?;
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 8);
+    write_chunk(&mut out, b"RIFF", &body)?;
+    Ok(out)
+}
+
+/// Write a RIFF chunk, padded to an even length.
+fn write_chunk(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    out.write_all(name)?;
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    out.write_all(data)?;
+    if data.len() % 2 == 1 {
+        out.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Frames of a pixel moving right across a small image.
+    fn frames(count: u32, (width, height): (u32, u32)) -> Vec<Vec<[u8; 4]>> {
+        (0..count)
+            .map(|i| {
+                (0..width * height)
+                    .map(|p| {
+                        if p % width == i % width {
+                            [255, 255, 255, 255]
+                        } else {
+                            [20, 40, 80, 255]
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_azimuth_covers_one_turn() {
+        let config = TurntableConfig {
+            frames: 4,
+            ..TurntableConfig::default()
+        };
+        assert_eq!(config.azimuth(1.0, 0), 1.0);
+        assert!((config.azimuth(1.0, 2) - (1.0 + TAU / 2.0)).abs() < 1e-6);
+        // The last frame stops short of the first, so the loop is seamless
+        assert!(config.azimuth(0.0, 3) < TAU);
+        assert_eq!(config.frame_delay_ms(), 1000);
+    }
+
+    #[test]
+    fn test_auto_rotate_rate() {
+        let mut spin = AutoRotate::default();
+        assert_eq!(spin.angle(1.0), 0.0);
+        spin.enabled = true;
+        spin.speed = 90.0;
+        assert!((spin.angle(2.0) - TAU / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            TurntableFormat::from_path(Path::new("spin.GIF")),
+            Some(TurntableFormat::Gif)
+        );
+        assert_eq!(
+            TurntableFormat::from_path(Path::new("spin.webp")),
+            Some(TurntableFormat::WebP)
+        );
+        assert_eq!(TurntableFormat::from_path(Path::new("spin.png")), None);
+    }
+
+    #[test]
+    fn test_gif_round_trip() {
+        let gif = TurntableFormat::Gif
+            .encode(&frames(3, (8, 4)), (8, 4), 50)
+            .unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(Cursor::new(gif)).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (8, 4));
+        let mut count = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 5);
+            // The moving pixel is white
+            assert_eq!(&frame.buffer[count * 4..count * 4 + 3], &[255, 255, 255]);
+            count += 1;
+        }
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_webp_round_trip() {
+        let input = frames(3, (8, 4));
+        let webp = TurntableFormat::WebP.encode(&input, (8, 4), 50).unwrap();
+
+        let mut decoder = image_webp::WebPDecoder::new(Cursor::new(webp)).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.dimensions(), (8, 4));
+        assert_eq!(decoder.num_frames(), 3);
+        let mut buffer = vec![0; 8 * 4 * 4];
+        for expected in &input {
+            assert_eq!(decoder.read_frame(&mut buffer).unwrap(), 50);
+            let expected: Vec<u8> = expected.iter().flatten().copied().collect();
+            assert_eq!(buffer, expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_without_frames_fails() {
+        assert!(TurntableFormat::Gif.encode(&[], (8, 4), 50).is_err());
+    }
+}
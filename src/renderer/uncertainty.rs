@@ -0,0 +1,111 @@
+//! Uncertainty map sampled by the solid shader.
+//!
+//! A σ channel is stored relative to its largest value, one texel per
+//! terrain sample, and looked up by each fragment's mesh-space (x, z)
+//! position to fade or hatch the surface (see `solid.wgsl`). Its placement
+//! follows the render origin, so it's passed with the uniforms each frame.
+
+use glam::{DVec2, DVec3, Vec2, Vec3Swizzles};
+
+use crate::terrain::grid::Channel;
+use crate::terrain::{uncertainty, TerrainData};
+
+/// Uncertainty texture with bilinear filtering.
+pub struct UncertaintyMap {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl UncertaintyMap {
+    /// A map with no uncertainty, bound until a σ channel is shown.
+    pub fn empty(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::create(device, queue, (1, 1), &[0])
+    }
+
+    /// Map of the σ channel `sigma`, a texel per terrain sample.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, sigma: &Channel) -> Self {
+        let size = (sigma.grid.width as u32, sigma.grid.height as u32);
+        Self::create(device, queue, size, &uncertainty::normalized(sigma))
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (width, height): (u32, u32),
+        texels: &[u8],
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Uncertainty Map"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            texels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: None,
+            },
+            texture.size(),
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Uncertainty Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler,
+        }
+    }
+
+    /// Texture view for the solid bind group.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Bilinear sampler for the solid bind group.
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+}
+
+/// Mesh-space (x, z) corner of a map over `terrain`'s samples and its
+/// texture coordinates per mesh-space unit, with the mesh placed relative
+/// to `render_origin`. Each texel is centered on its sample.
+pub fn placement(terrain: &TerrainData, render_origin: DVec3) -> (Vec2, Vec2) {
+    let corner = terrain.origin - terrain.cell_size / 2.0 - render_origin.xz();
+    let extent = DVec2::new(terrain.width as f64, terrain.height as f64) * terrain.cell_size;
+    (corner.as_vec2(), extent.recip().as_vec2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_texels_centered_on_samples() {
+        let mut terrain = TerrainData::new(vec![vec![0.0; 4]; 2], None);
+        terrain.origin = DVec2::new(100.0, 50.0);
+        terrain.cell_size = 2.0;
+        let (origin, scale) = placement(&terrain, DVec3::new(103.0, 7.0, 51.0));
+
+        // Mesh position of sample (1, 1), halfway into its texel
+        let sample = Vec2::new(-1.0, 1.0);
+        let uv = (sample - origin) * scale;
+        assert_eq!(uv, Vec2::new(1.5 / 4.0, 1.5 / 2.0));
+    }
+}
@@ -0,0 +1,251 @@
+//! Background jobs.
+//!
+//! Work too slow for a frame, like computing the terrain analysis, baking
+//! the irradiance overlay or writing exports, runs as a job on its own
+//! thread while the viewer keeps drawing. A job reports its progress and
+//! checks for cancellation through a shared [`Progress`]; the frame loop
+//! polls finished jobs and applies their results, and the UI lists the
+//! running ones with a progress bar and a Cancel button.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+
+use anyhow::Result;
+use thiserror::Error;
+
+/// Identifies a job for the lifetime of its [`Workers`].
+pub type JobId = u64;
+
+/// Progress of a job, shared between it and the frame loop.
+#[derive(Debug)]
+pub struct Progress {
+    /// Fraction done as `f32` bits, NaN until first reported
+    fraction: AtomicU32,
+    cancelled: AtomicBool,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            fraction: AtomicU32::new(f32::NAN.to_bits()),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Progress {
+    /// Report the fraction of the job done, between 0 and 1.
+    pub fn set(&self, fraction: f32) {
+        let fraction = fraction.clamp(0.0, 1.0);
+        self.fraction.store(fraction.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Fraction of the job done, or `None` if the job doesn't report it.
+    pub fn fraction(&self) -> Option<f32> {
+        Some(f32::from_bits(self.fraction.load(Ordering::Relaxed))).filter(|f| !f.is_nan())
+    }
+
+    /// Ask the job to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the job was asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Stop a job that was cancelled, with a [`Cancelled`] error.
+    pub fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(Cancelled.into()),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Error of a job that stopped because it was cancelled.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Cancelled")]
+pub struct Cancelled;
+
+/// A running job, as the UI lists it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobStatus {
+    pub id: JobId,
+    /// What the job does, e.g. "Writing mesh.stl"
+    pub label: String,
+    /// Fraction done, if the job reports it
+    pub fraction: Option<f32>,
+    /// Whether the job was cancelled and is winding down
+    pub cancelled: bool,
+}
+
+/// A job that finished, successfully or not.
+pub struct Finished<T> {
+    pub id: JobId,
+    pub label: String,
+    /// The job's output, or why it failed; cancelled jobs fail with
+    /// [`Cancelled`]
+    pub result: Result<T>,
+}
+
+struct Job<T> {
+    id: JobId,
+    label: String,
+    progress: Arc<Progress>,
+    receiver: Receiver<Result<T>>,
+}
+
+/// Jobs producing `T`, each running on its own thread.
+pub struct Workers<T> {
+    jobs: Vec<Job<T>>,
+    next_id: JobId,
+}
+
+impl<T> Default for Workers<T> {
+    fn default() -> Self {
+        Self {
+            jobs: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl<T: Send + 'static> Workers<T> {
+    /// Run `work` on a new thread, listed under `label`.
+    pub fn spawn(
+        &mut self,
+        label: impl Into<String>,
+        work: impl FnOnce(&Progress) -> Result<T> + Send + 'static,
+    ) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let progress = Arc::new(Progress::default());
+        let (sender, receiver) = mpsc::channel();
+        let shared = progress.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(work(&shared));
+        });
+        self.jobs.push(Job {
+            id,
+            label: label.into(),
+            progress,
+            receiver,
+        });
+        id
+    }
+
+    /// Ask job `id` to stop, if it's still running. Its result is dropped.
+    pub fn cancel(&self, id: JobId) {
+        if let Some(job) = self.jobs.iter().find(|job| job.id == id) {
+            job.progress.cancel();
+        }
+    }
+
+    /// Whether any job is running.
+    pub fn is_busy(&self) -> bool {
+        !self.jobs.is_empty()
+    }
+
+    /// The running jobs, oldest first.
+    pub fn status(&self) -> Vec<JobStatus> {
+        self.jobs
+            .iter()
+            .map(|job| JobStatus {
+                id: job.id,
+                label: job.label.clone(),
+                fraction: job.progress.fraction(),
+                cancelled: job.progress.is_cancelled(),
+            })
+            .collect()
+    }
+
+    /// Take the jobs that finished since the last poll, without waiting.
+    pub fn poll(&mut self) -> Vec<Finished<T>> {
+        self.take_finished(false, |_| true)
+    }
+
+    /// Wait for job `id` and take it, or `None` if it isn't running.
+    pub fn wait_for(&mut self, id: JobId) -> Option<Finished<T>> {
+        self.take_finished(true, |job| job == id).pop()
+    }
+
+    /// Take the finished jobs among those `pick` picks, waiting for them
+    /// if `block`.
+    fn take_finished(&mut self, block: bool, pick: impl Fn(JobId) -> bool) -> Vec<Finished<T>> {
+        let mut finished = Vec::new();
+        self.jobs.retain(|job| {
+            if !pick(job.id) {
+                return true;
+            }
+            let result = match block {
+                true => job.receiver.recv().map_err(|_| TryRecvError::Disconnected),
+                false => job.receiver.try_recv(),
+            };
+            let result = match result {
+                Ok(_) if job.progress.is_cancelled() => Err(Cancelled.into()),
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return true,
+                // The job panicked
+                Err(TryRecvError::Disconnected) => Err(anyhow::anyhow!("Job stopped unexpectedly")),
+            };
+            finished.push(Finished {
+                id: job.id,
+                label: job.label.clone(),
+                result,
+            });
+            false
+        });
+        finished
+    }
+}
+
+/// Whether `err` is a job's [`Cancelled`] error.
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.is::<Cancelled>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::sync_channel;
+
+    #[test]
+    fn test_jobs_report_progress_and_cancel() {
+        let mut workers = Workers::default();
+        let (go, wait) = sync_channel::<()>(0);
+        let (step_done, stepped) = sync_channel::<()>(0);
+        let slow = workers.spawn("Counting", move |progress: &Progress| {
+            for step in 1..=4 {
+                progress.check()?;
+                wait.recv()?;
+                progress.set(step as f32 / 4.0);
+                let _ = step_done.send(());
+            }
+            Ok(4)
+        });
+        let quick = workers.spawn("Answering", |_: &Progress| Ok(42));
+
+        let status = workers.status();
+        assert_eq!(status.len(), 2);
+        assert_eq!(status[0].label, "Counting");
+        assert_eq!(status[0].fraction, None);
+        assert_eq!(workers.wait_for(quick).unwrap().result.unwrap(), 42);
+
+        go.send(()).unwrap();
+        stepped.recv().unwrap();
+        let status = workers.status();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].fraction, Some(0.25));
+
+        workers.cancel(slow);
+        assert!(workers.status()[0].cancelled);
+        drop(go);
+        let finished = workers.wait_for(slow).unwrap();
+        assert!(workers.status().is_empty());
+        assert!(is_cancelled(&finished.result.unwrap_err()));
+        assert!(workers.wait_for(quick).is_none());
+    }
+}
@@ -0,0 +1,261 @@
+//! Scene summary for `lrle dump-scene`.
+//!
+//! Computes what the viewer would display for a terrain with default
+//! settings — grid and mesh sizes, bounds, the normalization applied to
+//! positions and colors, and the initial camera framing — so external tools
+//! and tests can check it without opening a window.
+
+use std::fmt::Write;
+
+use glam::{DVec3, Vec3};
+
+use lrle::renderer::camera::Camera;
+use lrle::renderer::Projection;
+use lrle::terrain::{MeshOptions, TerrainData, TerrainMesh};
+
+/// Aspect ratio of the viewer's default 800x600 window, which the initial
+/// camera frames the terrain for.
+const DEFAULT_ASPECT: f32 = 4.0 / 3.0;
+
+/// What the viewer displays for a terrain.
+#[derive(Debug, Clone)]
+pub struct SceneSummary {
+    /// Grid columns and rows
+    pub grid: (usize, usize),
+    /// World-space (x, z) position of the first sample
+    pub origin: (f64, f64),
+    /// Distance between adjacent samples
+    pub cell_size: f64,
+    /// Lowest and highest sample
+    pub height_range: (f64, f64),
+    /// Height multiplier applied to the mesh
+    pub height_scale: f32,
+    /// World-space point subtracted from positions to center the mesh
+    pub center: DVec3,
+    /// Mesh vertices, wireframe lines, triangles and culling chunks
+    pub mesh: MeshCounts,
+    /// Mesh bounding box corners, after centering
+    pub bounds: (Vec3, Vec3),
+    /// Mesh bounding sphere as `(center, radius)`
+    pub sphere: (Vec3, f32),
+    /// Initial camera, framing the mesh, with clip planes fitted to it
+    pub camera: Camera,
+}
+
+/// Element counts of a [`TerrainMesh`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshCounts {
+    pub vertices: usize,
+    pub lines: usize,
+    pub triangles: usize,
+    pub chunks: usize,
+}
+
+impl SceneSummary {
+    /// Build the mesh the viewer would for `terrain` and summarize it.
+    pub fn compute(terrain: &TerrainData, height_scale: f32) -> Self {
+        let mesh = TerrainMesh::build(
+            terrain,
+            &MeshOptions {
+                height_scale,
+                ..MeshOptions::default()
+            },
+        );
+        let bounds = mesh.bounds.unwrap_or((Vec3::ZERO, Vec3::ZERO));
+        let sphere = mesh.bounding_sphere();
+
+        let mut camera = Camera::new();
+        if mesh.bounds.is_some() {
+            camera.fit_to_bounds(bounds.0, bounds.1, DEFAULT_ASPECT);
+            if camera.auto_clip {
+                camera.fit_clip_planes(sphere.0, sphere.1);
+            }
+        }
+
+        Self {
+            grid: (terrain.width, terrain.height),
+            origin: (terrain.origin.x, terrain.origin.y),
+            cell_size: terrain.cell_size,
+            height_range: terrain.height_bounds(),
+            height_scale,
+            center: terrain.center(),
+            mesh: MeshCounts {
+                vertices: mesh.vertices.len(),
+                lines: mesh.indices.len() / 2,
+                triangles: mesh.triangle_indices.len() / 3,
+                chunks: mesh.chunks.len(),
+            },
+            bounds,
+            sphere,
+            camera,
+        }
+    }
+
+    /// Serialize as a JSON object.
+    pub fn to_json(&self) -> String {
+        let camera = &self.camera;
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"terrain\":{{\"width\":{},\"height\":{},\"origin\":[{},{}],\"cell_size\":{},\"height_range\":[{},{}]}}",
+            self.grid.0,
+            self.grid.1,
+            self.origin.0,
+            self.origin.1,
+            self.cell_size,
+            self.height_range.0,
+            self.height_range.1,
+        );
+        let _ = write!(
+            out,
+            ",\"normalization\":{{\"center\":{},\"height_scale\":{},\"color_range\":[{},{}]}}",
+            json_vec(self.center.to_array()),
+            self.height_scale,
+            self.height_range.0,
+            self.height_range.1,
+        );
+        let _ = write!(
+            out,
+            ",\"mesh\":{{\"vertices\":{},\"lines\":{},\"triangles\":{},\"chunks\":{}}}",
+            self.mesh.vertices, self.mesh.lines, self.mesh.triangles, self.mesh.chunks,
+        );
+        let _ = write!(
+            out,
+            ",\"bounds\":{{\"min\":{},\"max\":{},\"center\":{},\"radius\":{}}}",
+            json_vec(self.bounds.0.to_array()),
+            json_vec(self.bounds.1.to_array()),
+            json_vec(self.sphere.0.to_array()),
+            self.sphere.1,
+        );
+        let _ = write!(
+            out,
+            ",\"camera\":{{\"projection\":\"{}\",\"position\":{},\"target\":{},\"distance\":{},\"azimuth\":{},\"elevation\":{},\"fov\":{},\"near\":{},\"far\":{}}}}}",
+            projection_name(camera.projection),
+            json_vec(camera.position().to_array()),
+            json_vec(camera.target.to_array()),
+            camera.distance,
+            camera.azimuth.to_degrees(),
+            camera.elevation.to_degrees(),
+            camera.fov,
+            camera.near,
+            camera.far,
+        );
+        out.push('\n');
+        out
+    }
+
+    /// Human-readable summary, one item per line.
+    pub fn to_text(&self) -> String {
+        let camera = &self.camera;
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Grid:        {} x {}, cell size {}, origin ({}, {})",
+            self.grid.0, self.grid.1, self.cell_size, self.origin.0, self.origin.1
+        );
+        let _ = writeln!(
+            out,
+            "Heights:     {} to {}, scaled by {}",
+            self.height_range.0, self.height_range.1, self.height_scale
+        );
+        let _ = writeln!(out, "Center:      {}", self.center);
+        let _ = writeln!(
+            out,
+            "Mesh:        {} vertices, {} lines, {} triangles, {} chunks",
+            self.mesh.vertices, self.mesh.lines, self.mesh.triangles, self.mesh.chunks
+        );
+        let _ = writeln!(out, "Bounds:      {} to {}", self.bounds.0, self.bounds.1);
+        let _ = writeln!(
+            out,
+            "Sphere:      center {}, radius {}",
+            self.sphere.0, self.sphere.1
+        );
+        let _ = writeln!(
+            out,
+            "Camera:      {} at {} looking at {}",
+            projection_name(camera.projection),
+            camera.position(),
+            camera.target
+        );
+        let _ = writeln!(
+            out,
+            "             distance {}, azimuth {}°, elevation {}°, fov {}°, clip {} to {}",
+            camera.distance,
+            camera.azimuth.to_degrees(),
+            camera.elevation.to_degrees(),
+            camera.fov,
+            camera.near,
+            camera.far
+        );
+        out
+    }
+}
+
+fn projection_name(projection: Projection) -> &'static str {
+    match projection {
+        Projection::Perspective => "perspective",
+        Projection::Orthographic => "orthographic",
+    }
+}
+
+fn json_vec<T: std::fmt::Display>([x, y, z]: [T; 3]) -> String {
+    format!("[{},{},{}]", x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_of_small_grid() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0], vec![4.0, 5.0, 6.0]], None);
+        let scene = SceneSummary::compute(&terrain, 2.0);
+
+        assert_eq!(scene.grid, (3, 2));
+        assert_eq!(scene.height_range, (0.0, 6.0));
+        assert_eq!(scene.center, DVec3::new(1.0, 0.0, 0.5));
+        assert_eq!(
+            scene.mesh,
+            MeshCounts {
+                vertices: 6,
+                lines: 7,
+                triangles: 4,
+                chunks: 1,
+            }
+        );
+        // Centered footprint, heights scaled but not shifted
+        assert_eq!(scene.bounds.0, Vec3::new(-1.0, 0.0, -0.5));
+        assert_eq!(scene.bounds.1, Vec3::new(1.0, 12.0, 0.5));
+        // Framed on the mesh, with clip planes enclosing it
+        assert_eq!(scene.camera.target, Vec3::new(0.0, 6.0, 0.0));
+        assert!(scene.camera.distance < 50.0);
+        let distance = (scene.camera.position() - scene.sphere.0).length();
+        assert!(scene.camera.near <= distance - scene.sphere.1);
+        assert!(scene.camera.far >= distance + scene.sphere.1);
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0], vec![2.0, 3.0]], None);
+        let json = SceneSummary::compute(&terrain, 1.0).to_json();
+
+        assert!(json.starts_with("{\"terrain\":{\"width\":2,\"height\":2,"));
+        assert!(json.contains("\"height_range\":[0,3]"));
+        assert!(json.contains("\"mesh\":{\"vertices\":4,\"lines\":4,\"triangles\":2,\"chunks\":1}"));
+        assert!(json.contains("\"camera\":{\"projection\":\"perspective\""));
+        assert!(json.contains("\"azimuth\":45,"));
+        assert!(json.ends_with("}}\n"));
+        // Balanced braces and brackets
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+        assert_eq!(json.matches('[').count(), json.matches(']').count());
+    }
+
+    #[test]
+    fn test_empty_terrain() {
+        let scene = SceneSummary::compute(&TerrainData::new(vec![], None), 1.0);
+
+        assert_eq!(scene.mesh.vertices, 0);
+        assert_eq!(scene.bounds, (Vec3::ZERO, Vec3::ZERO));
+        assert!(!scene.to_json().contains("NaN"));
+    }
+}
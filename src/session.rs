@@ -0,0 +1,325 @@
+//! The last view of each terrain, restored on the next launch.
+//!
+//! On exit the viewer keeps the window size, camera, color scheme, render
+//! mode and height scale it showed a terrain with, and shows the terrain
+//! the same way when it's opened again. Every terrain's view lives in
+//! `sessions.toml` in `$XDG_STATE_HOME/lrle` (`~/.local/state/lrle` by
+//! default), under the terrain file's absolute path:
+//!
+//! ```toml
+//! ["/home/me/dems/alps.tif"]
+//! window = [1600, 900]
+//! color_scheme = "heatmap"
+//! render_mode = "both"
+//! height_scale = 2.0
+//!
+//! ["/home/me/dems/alps.tif".camera]
+//! target = [512.0, 80.0, 512.0]
+//! distance = 400.0
+//! azimuth = 45.0
+//! elevation = 30.0
+//! fov = 60.0
+//! ```
+//!
+//! The camera is stored like a bookmark's pose, in terrain coordinates and
+//! degrees. Settings given on the command line win over the saved ones,
+//! and `--fresh` starts from the defaults.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use glam::DVec3;
+use toml::{Table, Value};
+
+use crate::renderer::camera_path::Keyframe;
+use crate::renderer::RenderMode;
+use crate::terrain::ColorScheme;
+
+/// How a terrain was last shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Session {
+    /// Inner window size in physical pixels, unless it was fixed by
+    /// presentation mode
+    pub window: Option<(u32, u32)>,
+    /// Camera pose; its time is unused
+    pub camera: Option<Keyframe>,
+    pub color_scheme: ColorScheme,
+    pub render_mode: RenderMode,
+    pub height_scale: f32,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            window: None,
+            camera: None,
+            color_scheme: ColorScheme::default(),
+            render_mode: RenderMode::default(),
+            height_scale: 1.0,
+        }
+    }
+}
+
+impl Session {
+    /// `lrle/sessions.toml` in the user's state directory, if it has one.
+    pub fn default_file() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state"))
+            })?;
+        Some(dir.join("lrle").join("sessions.toml"))
+    }
+
+    /// Key of the terrain at `terrain` in the sessions file: its absolute
+    /// path, so it's found from any working directory.
+    fn key(terrain: &Path) -> String {
+        std::fs::canonicalize(terrain)
+            .unwrap_or_else(|_| terrain.to_path_buf())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Read the session of the terrain at `terrain` from `file`, or `None`
+    /// if the terrain or the file has none yet.
+    pub fn load(file: &Path, terrain: &Path) -> Result<Option<Self>> {
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let table: Table = text.parse()?;
+        let key = Self::key(terrain);
+        let Some(value) = table.get(&key) else {
+            return Ok(None);
+        };
+        let session = value
+            .as_table()
+            .ok_or_else(|| anyhow!("Expected a table"))
+            .and_then(Self::from_toml)
+            .with_context(|| format!("In [\"{key}\"]"))?;
+        Ok(Some(session))
+    }
+
+    /// Keep the session as the terrain at `terrain`'s in `file`, next to
+    /// the other terrains', creating the file and its directory. A file
+    /// that can't be read is started over.
+    pub fn save(&self, file: &Path, terrain: &Path) -> Result<()> {
+        let mut table: Table = std::fs::read_to_string(file)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .unwrap_or_default();
+        table.insert(Self::key(terrain), Value::Table(self.to_toml()));
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(file, table.to_string())?;
+        Ok(())
+    }
+
+    /// The session as a TOML table, as kept under its terrain's key.
+    pub fn to_toml(&self) -> Table {
+        let name = |value: Option<clap::builder::PossibleValue>| {
+            Value::String(value.map_or_else(String::new, |v| v.get_name().to_string()))
+        };
+        let mut table = Table::new();
+        if let Some((width, height)) = self.window {
+            table.insert(
+                "window".into(),
+                Value::Array(vec![(width as i64).into(), (height as i64).into()]),
+            );
+        }
+        table.insert(
+            "color_scheme".into(),
+            name(self.color_scheme.to_possible_value()),
+        );
+        table.insert(
+            "render_mode".into(),
+            name(self.render_mode.to_possible_value()),
+        );
+        table.insert("height_scale".into(), (self.height_scale as f64).into());
+        if let Some(pose) = self.camera {
+            let mut camera = Table::new();
+            camera.insert(
+                "target".into(),
+                Value::Array(pose.target.to_array().map(Value::from).to_vec()),
+            );
+            camera.insert("distance".into(), (pose.distance as f64).into());
+            camera.insert("azimuth".into(), (pose.azimuth.to_degrees() as f64).into());
+            camera.insert(
+                "elevation".into(),
+                (pose.elevation.to_degrees() as f64).into(),
+            );
+            camera.insert("fov".into(), (pose.fov as f64).into());
+            table.insert("camera".into(), Value::Table(camera));
+        }
+        table
+    }
+
+    /// Parse a session written by [`to_toml`](Self::to_toml) or by hand.
+    /// Settings left out keep their defaults.
+    pub fn from_toml(table: &Table) -> Result<Self> {
+        let mut session = Self::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "window" => {
+                    let sides: Vec<u32> = value
+                        .as_array()
+                        .map(|sides| {
+                            sides
+                                .iter()
+                                .filter_map(Value::as_integer)
+                                .filter_map(|side| u32::try_from(side).ok())
+                                .filter(|&side| side > 0)
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    let [width, height] = sides[..] else {
+                        bail!("Expected window to be [width, height] in pixels");
+                    };
+                    session.window = Some((width, height));
+                }
+                "color_scheme" => session.color_scheme = enum_value(key, value)?,
+                "render_mode" => session.render_mode = enum_value(key, value)?,
+                "height_scale" => session.height_scale = number(key, value)?,
+                "camera" => {
+                    let camera = value
+                        .as_table()
+                        .ok_or_else(|| anyhow!("Expected camera to be a table"))?;
+                    session.camera = Some(camera_from_toml(camera).context("In camera")?);
+                }
+                _ => bail!("Unknown setting {key}"),
+            }
+        }
+        Ok(session)
+    }
+}
+
+/// The camera pose in `camera`, with every field given.
+fn camera_from_toml(camera: &Table) -> Result<Keyframe> {
+    let field = |key: &str| {
+        camera
+            .get(key)
+            .ok_or_else(|| anyhow!("Missing {key}"))
+            .and_then(|value| number(key, value))
+    };
+    let target: Vec<f64> = camera
+        .get("target")
+        .and_then(Value::as_array)
+        .map(|xyz| xyz.iter().filter_map(as_f64).collect())
+        .unwrap_or_default();
+    let [x, y, z] = target[..] else {
+        bail!("Expected target to be an array of 3 numbers");
+    };
+    let distance = field("distance")?;
+    if distance <= 0.0 {
+        bail!("Expected distance to be positive");
+    }
+    Ok(Keyframe {
+        time: 0.0,
+        target: DVec3::new(x, y, z),
+        distance,
+        azimuth: field("azimuth")?.to_radians(),
+        elevation: field("elevation")?.to_radians(),
+        fov: field("fov")?,
+    })
+}
+
+/// `value` as a float, from a float or an integer.
+fn as_f64(value: &Value) -> Option<f64> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|n| n as f64))
+}
+
+/// The finite number `value` of setting `key`.
+fn number(key: &str, value: &Value) -> Result<f32> {
+    as_f64(value)
+        .filter(|n| n.is_finite())
+        .map(|n| n as f32)
+        .ok_or_else(|| anyhow!("Expected {key} to be a number"))
+}
+
+/// The variant of `T` named by `value` of setting `key`, as on the command
+/// line.
+fn enum_value<T: ValueEnum>(key: &str, value: &Value) -> Result<T> {
+    let name = value
+        .as_str()
+        .ok_or_else(|| anyhow!("Expected {key} to be a name"))?;
+    T::from_str(name, true).map_err(|_| anyhow!("Unknown {key} \"{name}\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_are_kept_per_terrain() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lrle").join("sessions.toml");
+        let (alps, fjord) = (dir.path().join("alps.tif"), dir.path().join("fjord.asc"));
+        std::fs::write(&alps, "").unwrap();
+        std::fs::write(&fjord, "").unwrap();
+        assert_eq!(Session::load(&file, &alps).unwrap(), None);
+
+        let session = Session {
+            window: Some((1600, 900)),
+            camera: Some(Keyframe {
+                time: 0.0,
+                target: DVec3::new(512.0, 80.5, -12.0),
+                distance: 400.0,
+                azimuth: 45f32.to_radians(),
+                elevation: 30f32.to_radians(),
+                fov: 60.0,
+            }),
+            color_scheme: ColorScheme::Heatmap,
+            render_mode: RenderMode::Both,
+            height_scale: 2.5,
+        };
+        session.save(&file, &alps).unwrap();
+        Session::default().save(&file, &fjord).unwrap();
+
+        let loaded = Session::load(&file, &alps).unwrap().unwrap();
+        assert_eq!(loaded.window, session.window);
+        assert_eq!(loaded.color_scheme, ColorScheme::Heatmap);
+        assert_eq!(loaded.render_mode, RenderMode::Both);
+        assert_eq!(loaded.height_scale, 2.5);
+        let (camera, saved) = (loaded.camera.unwrap(), session.camera.unwrap());
+        assert_eq!(camera.target, saved.target);
+        assert!((camera.azimuth - saved.azimuth).abs() < 1e-6);
+        assert_eq!(
+            Session::load(&file, &fjord).unwrap(),
+            Some(Session::default())
+        );
+
+        // Found from another working directory
+        let relative = dir.path().join(".").join("alps.tif");
+        assert_eq!(Session::load(&file, &relative).unwrap(), Some(loaded));
+    }
+
+    #[test]
+    fn test_hand_written_session_over_defaults() {
+        let parse = |text: &str| Session::from_toml(&text.parse().unwrap());
+        let session = parse("render_mode = \"Wireframe\"\nheight_scale = 3").unwrap();
+        assert_eq!(session.render_mode, RenderMode::Wireframe);
+        assert_eq!(session.height_scale, 3.0);
+        assert_eq!(session.color_scheme, ColorScheme::Terrain);
+        assert_eq!(session.camera, None);
+
+        let err = |text: &str| format!("{:#}", parse(text).unwrap_err());
+        assert_eq!(
+            err("color_scheme = \"sepia\""),
+            "Unknown color_scheme \"sepia\""
+        );
+        assert_eq!(
+            err("window = [0, 600]"),
+            "Expected window to be [width, height] in pixels"
+        );
+        assert_eq!(
+            err("[camera]\ntarget = [0, 0, 0]\ndistance = 10"),
+            "In camera: Missing azimuth"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+// Layer Blend Mode Composite Shader
+//
+// Combines one group of translucent layers, accumulated like the
+// order-independent transparency targets, with a copy of the scene
+// beneath it using the layer's blend mode. See renderer/blend.rs.
+
+// ============================================================================
+// Bindings
+// ============================================================================
+
+/// Sum of weighted premultiplied colors (rgb) and weighted alphas (a)
+@group(0) @binding(0)
+var accum_texture: texture_2d<f32>;
+
+/// Product of (1 - alpha) over the layer's fragments
+@group(0) @binding(1)
+var reveal_texture: texture_2d<f32>;
+
+/// Copy of the scene drawn so far
+@group(1) @binding(0)
+var scene_texture: texture_2d<f32>;
+
+struct BlendUniforms {
+    /// Blend mode: 0 normal, 1 additive, 2 difference, 3 min, 4 max
+    mode: u32,
+    _pad: vec3<u32>,
+}
+
+@group(1) @binding(1)
+var<uniform> blend: BlendUniforms;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Fullscreen triangle from the vertex index; no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+/// Layer color `layer` combined with the scene color `base` at full
+/// coverage.
+fn blend_colors(base: vec3<f32>, layer: vec3<f32>) -> vec3<f32> {
+    switch blend.mode {
+        case 1u: {
+            return base + layer;
+        }
+        case 2u: {
+            return abs(base - layer);
+        }
+        case 3u: {
+            return min(base, layer);
+        }
+        case 4u: {
+            return max(base, layer);
+        }
+        default: {
+            return layer;
+        }
+    }
+}
+
+/// Scene color with the layer blended in by its coverage.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(position.xy);
+    let reveal = textureLoad(reveal_texture, coord, 0).r;
+    if reveal >= 0.999 {
+        discard;
+    }
+    let accum = textureLoad(accum_texture, coord, 0);
+    let layer = accum.rgb / max(accum.a, 1e-5);
+    let base = textureLoad(scene_texture, coord, 0);
+    let color = mix(base.rgb, blend_colors(base.rgb, layer), 1.0 - reveal);
+    return vec4<f32>(color, base.a);
+}
@@ -0,0 +1,136 @@
+// Bloom Shader
+//
+// Redraws the finished scene with a glow around its bright pixels, in
+// stages at half resolution: `fs_bright` keeps the part of the scene's
+// linear color above the threshold, `fs_blur_horizontal` and
+// `fs_blur_vertical` spread it with a separable Gaussian, and
+// `fs_composite` adds it back onto the scene. See renderer/bloom.rs.
+
+// ============================================================================
+// Bindings
+// ============================================================================
+
+/// Copy of the finished scene
+@group(0) @binding(0)
+var scene_texture: texture_2d<f32>;
+
+struct BloomUniforms {
+    /// Linear luminance above which pixels glow
+    threshold: f32,
+    /// Strength of the added glow
+    intensity: f32,
+    /// Reach of the glow in pixels
+    radius: f32,
+    /// 1 when the scene holds sRGB-encoded values rather than linear ones
+    encoded: u32,
+}
+
+@group(0) @binding(1)
+var<uniform> bloom: BloomUniforms;
+
+/// Half-resolution glow read by the current stage
+@group(0) @binding(2)
+var glow_texture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var glow_sampler: sampler;
+
+/// Taps on each side of the pixel in a blur pass
+const TAPS: i32 = 12;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Fullscreen triangle from the vertex index; no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ============================================================================
+// Fragment Shaders
+// ============================================================================
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+/// Linear color of a scene pixel, clamped to the texture.
+fn scene_color(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(scene_texture));
+    let color = textureLoad(scene_texture, clamp(pixel, vec2<i32>(0), size - 1), 0).rgb;
+    if bloom.encoded == 1u {
+        return srgb_to_linear(color);
+    }
+    return color;
+}
+
+/// The part of a color above the threshold, keeping its hue.
+fn bright_part(color: vec3<f32>) -> vec3<f32> {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return color * max(luminance - bloom.threshold, 0.0) / max(luminance, 1e-4);
+}
+
+/// Bright part of the 2x2 scene pixels under a glow pixel.
+@fragment
+fn fs_bright(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy) * 2;
+    var glow = vec3<f32>(0.0);
+    for (var y = 0; y < 2; y++) {
+        for (var x = 0; x < 2; x++) {
+            glow += bright_part(scene_color(pixel + vec2<i32>(x, y)));
+        }
+    }
+    return vec4<f32>(glow * 0.25, 1.0);
+}
+
+/// Gaussian blur of the glow along `direction`, reaching the glow radius
+/// (given in full-resolution pixels) on each side.
+fn blur(position: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(glow_texture));
+    let uv = position / size;
+    let step = direction * bloom.radius * 0.5 / f32(TAPS) / size;
+
+    var glow = textureSampleLevel(glow_texture, glow_sampler, uv, 0.0).rgb;
+    var total = 1.0;
+    for (var tap = 1; tap <= TAPS; tap++) {
+        let t = f32(tap) / f32(TAPS);
+        let weight = exp(-4.0 * t * t);
+        let offset = step * f32(tap);
+        glow += textureSampleLevel(glow_texture, glow_sampler, uv + offset, 0.0).rgb * weight;
+        glow += textureSampleLevel(glow_texture, glow_sampler, uv - offset, 0.0).rgb * weight;
+        total += 2.0 * weight;
+    }
+    return vec4<f32>(glow / total, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return blur(position.xy, vec2<f32>(1.0, 0.0));
+}
+
+@fragment
+fn fs_blur_vertical(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return blur(position.xy, vec2<f32>(0.0, 1.0));
+}
+
+/// Scene color plus the blurred glow, upsampled.
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let center = vec2<i32>(position.xy);
+    let scene = textureLoad(scene_texture, center, 0);
+    let uv = position.xy / vec2<f32>(textureDimensions(scene_texture));
+    let glow = textureSampleLevel(glow_texture, glow_sampler, uv, 0.0).rgb;
+
+    var result = scene_color(center) + glow * bloom.intensity;
+    if bloom.encoded == 1u {
+        result = linear_to_srgb(min(result, vec3<f32>(1.0)));
+    }
+    return vec4<f32>(result, scene.a);
+}
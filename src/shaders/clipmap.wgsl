@@ -0,0 +1,219 @@
+// Geometry Clipmap Terrain Shader
+//
+// Renders the terrain as nested square grids centered on the camera, each
+// level twice as coarse as the previous one. Heights are sampled from a
+// height texture in the vertex shader, so the geometry is independent of
+// the terrain size. See renderer/clipmap.rs.
+//
+// Each level is one instance of the same grid. The part of a level covered
+// by the next finer level is discarded in the fragment shader, and vertices
+// near a level's outer edge are morphed onto the coarser grid so that
+// adjacent levels meet without cracks.
+
+// ============================================================================
+// Uniforms
+// ============================================================================
+
+/// Camera, lighting, and terrain placement uniforms.
+struct Uniforms {
+    /// Combined view * projection matrix for transforming world -> clip space
+    view_proj: mat4x4<f32>,
+    /// Light direction (normalized, pointing toward light source)
+    light_dir: vec3<f32>,
+    /// Ambient light strength (0.0 - 1.0)
+    ambient: f32,
+    /// Light color/intensity
+    light_color: vec3<f32>,
+    /// 1 / log2(far + 1) for logarithmic depth, 0.0 to use linear depth
+    log_depth_coef: f32,
+    /// World-space (x, z) of height sample [0][0]
+    grid_origin: vec2<f32>,
+    /// Distance between adjacent samples
+    cell_size: f32,
+    /// Height scale multiplier
+    height_scale: f32,
+    /// Camera position in height-map texels
+    camera_grid: vec2<f32>,
+    /// Largest valid texel coordinate (width - 1, height - 1)
+    grid_extent: vec2<f32>,
+    /// World-space y of a zero height-map sample (after scaling)
+    height_base: f32,
+    /// Range of height-map values, for color lookup
+    height_range: f32,
+    /// Earth curvature drop per squared distance (1 / 2R), 0.0 for flat
+    curvature_coef: f32,
+    /// Quads along one side of a level
+    grid_size: f32,
+    /// Horizontal (x, z) position the curvature drop is measured from
+    curvature_center: vec2<f32>,
+    /// Padding
+    _pad: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+/// Heights relative to the terrain minimum, one texel per sample.
+@group(0) @binding(2)
+var height_map: texture_2d<f32>;
+
+/// Color scheme lookup, indexed by normalized height.
+@group(0) @binding(3)
+var color_ramp: texture_2d<f32>;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Input vertex data from the vertex buffer.
+struct VertexInput {
+    /// Grid coordinate relative to the level center, in level quads
+    @location(0) grid: vec2<f32>,
+}
+
+/// Output from vertex shader / input to fragment shader.
+struct VertexOutput {
+    /// Clip-space position (required builtin)
+    @builtin(position) clip_position: vec4<f32>,
+    /// Position in height-map texels
+    @location(0) texel: vec2<f32>,
+    /// Surface normal
+    @location(1) normal: vec3<f32>,
+    /// Height-map value, for color lookup
+    @location(2) height: f32,
+    /// Clip-space w (view depth for perspective) for logarithmic depth
+    @location(3) clip_w: f32,
+    /// Texel-space region drawn by the next finer level
+    @location(4) @interpolate(flat) hole_min: vec2<f32>,
+    @location(5) @interpolate(flat) hole_max: vec2<f32>,
+    /// World-space position for fog
+    @location(6) world_position: vec3<f32>,
+}
+
+/// Bilinearly interpolated height-map value at a texel position.
+fn height_at(texel: vec2<f32>) -> f32 {
+    let p = clamp(texel, vec2<f32>(0.0), uniforms.grid_extent);
+    let i0 = vec2<i32>(floor(p));
+    let i1 = min(i0 + vec2<i32>(1), vec2<i32>(uniforms.grid_extent));
+    let f = p - floor(p);
+    let h00 = textureLoad(height_map, i0, 0).r;
+    let h10 = textureLoad(height_map, vec2<i32>(i1.x, i0.y), 0).r;
+    let h01 = textureLoad(height_map, vec2<i32>(i0.x, i1.y), 0).r;
+    let h11 = textureLoad(height_map, i1, 0).r;
+    return mix(mix(h00, h10, f.x), mix(h01, h11, f.x), f.y);
+}
+
+/// Texel-space center of a level, snapped to twice its quad size so the
+/// grid only moves in whole coarse quads.
+fn level_center(scale: f32) -> vec2<f32> {
+    let snap = scale * 2.0;
+    return floor(uniforms.camera_grid / snap) * snap;
+}
+
+/// Lower a position by earth curvature relative to the curvature center.
+fn curve(position: vec3<f32>) -> vec3<f32> {
+    let d = position.xz - uniforms.curvature_center;
+    return position - vec3<f32>(0.0, dot(d, d) * uniforms.curvature_coef, 0.0);
+}
+
+/// Vertex shader entry point. The instance index is the clipmap level.
+@vertex
+fn vs_main(in: VertexInput, @builtin(instance_index) level: u32) -> VertexOutput {
+    let scale = exp2(f32(level));
+    let half_size = uniforms.grid_size * 0.5;
+
+    // Morph odd vertices onto the coarser grid in the outer quarter
+    let band = half_size * 0.25;
+    let edge = max(abs(in.grid.x), abs(in.grid.y));
+    let morph = clamp((edge - (half_size - band)) / band, 0.0, 1.0);
+    let odd = in.grid - floor(in.grid * 0.5) * 2.0;
+    let texel = level_center(scale) + (in.grid - odd * morph) * scale;
+
+    let h = height_at(texel);
+    let world = vec3<f32>(
+        uniforms.grid_origin.x + texel.x * uniforms.cell_size,
+        h * uniforms.height_scale + uniforms.height_base,
+        uniforms.grid_origin.y + texel.y * uniforms.cell_size,
+    );
+
+    // Central differences at this level's spacing
+    let dx = height_at(texel + vec2<f32>(scale, 0.0)) - height_at(texel - vec2<f32>(scale, 0.0));
+    let dz = height_at(texel + vec2<f32>(0.0, scale)) - height_at(texel - vec2<f32>(0.0, scale));
+    let slope = uniforms.height_scale / (2.0 * scale * uniforms.cell_size);
+
+    var out: VertexOutput;
+    out.world_position = curve(world);
+    out.clip_position = uniforms.view_proj * vec4<f32>(out.world_position, 1.0);
+    out.texel = texel;
+    out.normal = normalize(vec3<f32>(-dx * slope, 1.0, -dz * slope));
+    out.height = h;
+    out.clip_w = out.clip_position.w;
+
+    if level == 0u {
+        // Empty region: min > max
+        out.hole_min = vec2<f32>(1.0);
+        out.hole_max = vec2<f32>(0.0);
+    } else {
+        let finer = scale * 0.5;
+        let center = level_center(finer);
+        out.hole_min = center - half_size * finer;
+        out.hole_max = center + half_size * finer;
+    }
+    return out;
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+/// Directional lighting of the height-ramp color, faded by fog.
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let normal = normalize(in.normal);
+    let n_dot_l = max(dot(normal, uniforms.light_dir), 0.0);
+    let diffuse = uniforms.light_color * n_dot_l;
+    let lighting = uniforms.ambient + diffuse * (1.0 - uniforms.ambient);
+
+    let ramp_size = f32(textureDimensions(color_ramp).x);
+    let t = clamp(in.height / max(uniforms.height_range, 1e-6), 0.0, 1.0);
+    let base_color = textureLoad(color_ramp, vec2<i32>(i32(t * (ramp_size - 1.0)), 0), 0).rgb;
+
+    let color = base_color * lighting * param_exposure();
+    return vec4<f32>(apply_fog(color, in.world_position), 1.0);
+}
+
+/// Discard fragments drawn by a finer level or outside the terrain.
+fn clip(in: VertexOutput) {
+    if all(in.texel > in.hole_min) && all(in.texel < in.hole_max) {
+        discard;
+    }
+    if any(in.texel < vec2<f32>(0.0)) || any(in.texel > uniforms.grid_extent) {
+        discard;
+    }
+}
+
+/// Fragment shader entry point.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip(in);
+    return shade(in);
+}
+
+/// Fragment output with an explicit depth value.
+struct LogDepthOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+/// Fragment entry point for logarithmic depth.
+@fragment
+fn fs_main_log_depth(in: VertexOutput) -> LogDepthOutput {
+    clip(in);
+    var out: LogDepthOutput;
+    out.color = shade(in);
+    if uniforms.log_depth_coef > 0.0 {
+        out.depth = log2(max(1e-6, 1.0 + in.clip_w)) * uniforms.log_depth_coef;
+    } else {
+        out.depth = in.clip_position.z;
+    }
+    return out;
+}
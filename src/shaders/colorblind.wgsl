@@ -0,0 +1,66 @@
+// Color Vision Deficiency Simulation Shader
+//
+// Redraws the finished scene as seen with a color vision deficiency, by
+// mapping each pixel's linear color through a 3x3 matrix. See
+// renderer/colorblind.rs.
+
+// ============================================================================
+// Bindings
+// ============================================================================
+
+/// Copy of the finished scene
+@group(0) @binding(0)
+var scene_texture: texture_2d<f32>;
+
+struct ColorblindUniforms {
+    /// Rows of the simulation matrix (xyz used)
+    rows: array<vec4<f32>, 3>,
+    /// 1 when the scene holds sRGB-encoded values rather than linear ones
+    encoded: u32,
+    _pad: vec3<u32>,
+}
+
+@group(0) @binding(1)
+var<uniform> colorblind: ColorblindUniforms;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Fullscreen triangle from the vertex index; no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+/// Scene color mapped through the simulation matrix.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let scene = textureLoad(scene_texture, vec2<i32>(position.xy), 0);
+    var color = scene.rgb;
+    if colorblind.encoded == 1u {
+        color = srgb_to_linear(color);
+    }
+    color = clamp(vec3<f32>(
+        dot(colorblind.rows[0].xyz, color),
+        dot(colorblind.rows[1].xyz, color),
+        dot(colorblind.rows[2].xyz, color),
+    ), vec3<f32>(0.0), vec3<f32>(1.0));
+    if colorblind.encoded == 1u {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, scene.a);
+}
@@ -0,0 +1,117 @@
+// Chunk Culling Compute Shader
+//
+// Builds the indirect draw list for the mesh chunks: one
+// DrawIndexedIndirectArgs per chunk for the triangles and one for the
+// wireframe lines, with the instance count set to 0 for chunks outside the
+// view frustum or hidden according to the last occlusion query results.
+// See renderer/indirect.rs.
+
+// ============================================================================
+// Bindings
+// ============================================================================
+
+struct CullUniforms {
+    /// Combined view * projection matrix, as used for drawing
+    view_proj: mat4x4<f32>,
+    /// Number of chunks
+    chunk_count: u32,
+    /// Earth curvature drop per squared distance, 0.0 for flat
+    curvature_coef: f32,
+    /// Horizontal (x, z) position the curvature drop is measured from
+    curvature_center: vec2<f32>,
+    /// Scale and offset of mesh heights, as applied when drawing
+    height_scale: f32,
+    height_offset: f32,
+}
+
+/// Bounds and index ranges of one chunk.
+struct Chunk {
+    min: vec3<f32>,
+    first_triangle: u32,
+    max: vec3<f32>,
+    triangle_count: u32,
+    first_line: u32,
+    line_count: u32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+/// Layout of wgpu's DrawIndexedIndirectArgs.
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> cull: CullUniforms;
+
+@group(0) @binding(1)
+var<storage, read> chunks: array<Chunk>;
+
+/// Nonzero for chunks that passed the last occlusion test
+@group(0) @binding(2)
+var<storage, read> visibility: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read_write> triangle_args: array<DrawArgs>;
+
+@group(0) @binding(4)
+var<storage, read_write> line_args: array<DrawArgs>;
+
+// ============================================================================
+// Culling
+// ============================================================================
+
+/// Whether any part of a box is inside the frustum, testing the corner
+/// furthest along each plane normal.
+fn in_frustum(min: vec3<f32>, max: vec3<f32>) -> bool {
+    let m = transpose(cull.view_proj);
+    // Clip-space planes: -w <= x, y <= w and 0 <= z <= w
+    var planes = array<vec4<f32>, 6>(
+        m[3] + m[0],
+        m[3] - m[0],
+        m[3] + m[1],
+        m[3] - m[1],
+        m[2],
+        m[3] - m[2],
+    );
+    for (var i = 0; i < 6; i++) {
+        let plane = planes[i];
+        let corner = select(min, max, plane.xyz > vec3<f32>(0.0));
+        if dot(plane.xyz, corner) + plane.w < 0.0 {
+            return false;
+        }
+    }
+    return true;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= cull.chunk_count {
+        return;
+    }
+    let chunk = chunks[i];
+
+    // Rescale the box heights like the vertices; a negative scale flips it
+    let low = chunk.min.y * cull.height_scale + cull.height_offset;
+    let high = chunk.max.y * cull.height_scale + cull.height_offset;
+    var box_min = vec3<f32>(chunk.min.x, min(low, high), chunk.min.z);
+    let box_max = vec3<f32>(chunk.max.x, max(low, high), chunk.max.z);
+
+    // Extend the box down by the largest curvature drop over it
+    let far_corner = max(
+        abs(chunk.min.xz - cull.curvature_center),
+        abs(chunk.max.xz - cull.curvature_center),
+    );
+    box_min.y -= dot(far_corner, far_corner) * cull.curvature_coef;
+
+    let visible = visibility[i] != 0u && in_frustum(box_min, box_max);
+    let instances = select(0u, 1u, visible);
+
+    triangle_args[i] = DrawArgs(chunk.triangle_count, instances, chunk.first_triangle, 0, 0u);
+    line_args[i] = DrawArgs(chunk.line_count, instances, chunk.first_line, 0, 0u);
+}
@@ -0,0 +1,35 @@
+// Multisampled Depth Resolve Shader
+//
+// Copies the first sample of the multisampled scene depth into the
+// single-sampled depth buffer, so the passes after the main one test
+// against the opaque scene. See renderer/msaa.rs.
+
+// ============================================================================
+// Bindings
+// ============================================================================
+
+/// Depth of the multisampled main pass, bound as a float texture since
+/// GL can't load from depth textures
+@group(0) @binding(0)
+var scene_depth: texture_multisampled_2d<f32>;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Fullscreen triangle from the vertex index; no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+/// Depth of the pixel's first sample.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @builtin(frag_depth) f32 {
+    return textureLoad(scene_depth, vec2<i32>(position.xy), 0).r;
+}
@@ -0,0 +1,109 @@
+// Ground Grid Shader
+//
+// Draws a square on the zero-height plane with antialiased grid lines,
+// every fifth one stronger, fading out toward the square's edges. Blended
+// over the opaque scene without writing depth. See renderer/ground_grid.rs.
+
+// ============================================================================
+// Uniforms
+// ============================================================================
+
+struct Uniforms {
+    /// Combined view * projection matrix for transforming world -> clip space
+    view_proj: mat4x4<f32>,
+    /// Horizontal (x, z) center of the grid square
+    center: vec2<f32>,
+    /// Half-width of the grid square
+    extent: f32,
+    /// Distance between minor lines
+    spacing: f32,
+    /// Height of the plane
+    y: f32,
+    /// 1 / log2(far + 1) for logarithmic depth, 0.0 to use linear depth
+    log_depth_coef: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+/// Line color, and the opacity of minor and major lines.
+const LINE_COLOR: vec3<f32> = vec3<f32>(0.85, 0.85, 0.9);
+const MINOR_ALPHA: f32 = 0.25;
+const MAJOR_ALPHA: f32 = 0.6;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    /// Horizontal (x, z) world position
+    @location(0) ground: vec2<f32>,
+    /// Clip-space w (view depth for perspective) for logarithmic depth
+    @location(1) clip_w: f32,
+}
+
+/// Two triangles covering the square, from the vertex index.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let ground = uniforms.center + corners[index] * uniforms.extent;
+
+    var out: VertexOutput;
+    out.clip_position = uniforms.view_proj * vec4<f32>(ground.x, uniforms.y, ground.y, 1.0);
+    out.ground = ground;
+    out.clip_w = out.clip_position.w;
+    return out;
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+/// Coverage of the nearest line of a grid with `spacing`, one pixel wide.
+fn line_coverage(ground: vec2<f32>, spacing: f32) -> f32 {
+    let coord = ground / spacing;
+    let pixels = abs(fract(coord - 0.5) - 0.5) / fwidth(coord);
+    return 1.0 - min(min(pixels.x, pixels.y), 1.0);
+}
+
+/// Line color with its opacity, faded toward the edges.
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let minor = line_coverage(in.ground, uniforms.spacing) * MINOR_ALPHA;
+    let major = line_coverage(in.ground, uniforms.spacing * 5.0) * MAJOR_ALPHA;
+    let edge = length(in.ground - uniforms.center) / uniforms.extent;
+    let alpha = max(minor, major) * (1.0 - smoothstep(0.5, 1.0, edge));
+    return vec4<f32>(LINE_COLOR, alpha);
+}
+
+/// Fragment shader entry point.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Fragment output with an explicit depth value.
+struct LogDepthOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+/// Fragment entry point for logarithmic depth, matching the terrain's.
+@fragment
+fn fs_main_log_depth(in: VertexOutput) -> LogDepthOutput {
+    var out: LogDepthOutput;
+    out.color = shade(in);
+    if uniforms.log_depth_coef > 0.0 {
+        out.depth = log2(max(1e-6, 1.0 + in.clip_w)) * uniforms.log_depth_coef;
+    } else {
+        out.depth = in.clip_position.z;
+    }
+    return out;
+}
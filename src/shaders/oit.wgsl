@@ -0,0 +1,47 @@
+// Weighted Blended OIT Composite Shader
+//
+// Resolves the accumulation and revealage targets written by the solid
+// shader's `fs_oit` entry points over the opaque scene. See
+// renderer/oit.rs.
+
+// ============================================================================
+// Bindings
+// ============================================================================
+
+/// Sum of weighted premultiplied colors (rgb) and weighted alphas (a)
+@group(0) @binding(0)
+var accum_texture: texture_2d<f32>;
+
+/// Product of (1 - alpha) over all layers: the fraction of the opaque scene
+/// left visible
+@group(0) @binding(1)
+var reveal_texture: texture_2d<f32>;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Fullscreen triangle from the vertex index; no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+/// Weighted average color of the translucent layers, with coverage as
+/// alpha for blending over the opaque scene.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(position.xy);
+    let reveal = textureLoad(reveal_texture, coord, 0).r;
+    if reveal >= 0.999 {
+        discard;
+    }
+    let accum = textureLoad(accum_texture, coord, 0);
+    let average = accum.rgb / max(accum.a, 1e-5);
+    return vec4<f32>(average, 1.0 - reveal);
+}
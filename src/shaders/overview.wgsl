@@ -0,0 +1,26 @@
+// Overview Inset Background Shader
+//
+// Fills the picture-in-picture viewport before the overview camera draws
+// the scene into it. See renderer/overview.rs.
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Fullscreen triangle from the vertex index; the viewport and scissor
+/// limit it to the inset.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+/// Inset background, a little darker than the main view's clear color.
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.05, 0.05, 0.08, 1.0);
+}
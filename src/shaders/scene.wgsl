@@ -0,0 +1,85 @@
+// Scene Uniforms Prelude
+//
+// Prepended to the terrain shaders and the sky shader. Declares the scene
+// uniforms shared across pipelines, the distance fog applied to surface
+// colors and the environment map lookups. See renderer/atmosphere.rs and
+// renderer/environment.rs.
+
+/// Camera and atmosphere settings, updated each frame from the CPU.
+struct SceneUniforms {
+    /// Clip space to world space, for view rays
+    inv_view_proj: mat4x4<f32>,
+    /// World-space camera position
+    camera_position: vec3<f32>,
+    /// Fog extinction per world unit, 0.0 without fog
+    fog_density: f32,
+    /// Direction the camera looks along
+    camera_forward: vec3<f32>,
+    /// Color distant surfaces fade to
+    fog_color: vec3<f32>,
+    /// Sky color straight up
+    sky_zenith: vec3<f32>,
+    /// Sky color at and below the horizon
+    sky_horizon: vec3<f32>,
+    /// Environment map brightness, 0.0 without one
+    env_intensity: f32,
+    /// Environment map turn about the vertical axis, in radians
+    env_rotation: f32,
+    /// Environment map shown as the background (1.0 = yes)
+    env_background: f32,
+    /// Power encoding linear environment colors for the target
+    env_gamma: f32,
+    /// Irradiance spherical harmonic coefficients (see
+    /// terrain/environment.rs)
+    env_irradiance: array<vec4<f32>, 9>,
+}
+
+@group(0) @binding(6)
+var<uniform> scene: SceneUniforms;
+
+/// Fade `color` toward the fog color by the distance of world-space
+/// `position` from the camera.
+fn apply_fog(color: vec3<f32>, position: vec3<f32>) -> vec3<f32> {
+    let fog = 1.0 - exp(-scene.fog_density * distance(position, scene.camera_position));
+    return mix(color, scene.fog_color, fog);
+}
+
+const ENV_PI: f32 = 3.14159265;
+
+/// World `direction` in the environment map's frame, turned back by its
+/// rotation.
+fn environment_direction(direction: vec3<f32>) -> vec3<f32> {
+    let c = cos(scene.env_rotation);
+    let s = sin(scene.env_rotation);
+    return vec3<f32>(c * direction.x + s * direction.z, direction.y, c * direction.z - s * direction.x);
+}
+
+/// Equirectangular panorama coordinates along world `direction`.
+fn environment_uv(direction: vec3<f32>) -> vec2<f32> {
+    let d = environment_direction(normalize(direction));
+    let u = 0.5 + atan2(d.x, -d.z) / (2.0 * ENV_PI);
+    let v = acos(clamp(d.y, -1.0, 1.0)) / ENV_PI;
+    return vec2<f32>(u, v);
+}
+
+/// Environment light reflected by a white diffuse surface facing world
+/// `normal`, from the irradiance coefficients.
+fn environment_irradiance(normal: vec3<f32>) -> vec3<f32> {
+    let n = environment_direction(normal);
+    var basis = array<f32, 9>(
+        0.282095,
+        0.488603 * n.y,
+        0.488603 * n.z,
+        0.488603 * n.x,
+        1.092548 * n.x * n.y,
+        1.092548 * n.y * n.z,
+        0.315392 * (3.0 * n.z * n.z - 1.0),
+        1.092548 * n.x * n.z,
+        0.546274 * (n.x * n.x - n.y * n.y),
+    );
+    var light = vec3<f32>(0.0);
+    for (var i = 0; i < 9; i++) {
+        light += scene.env_irradiance[i].rgb * basis[i];
+    }
+    return max(light, vec3<f32>(0.0)) * scene.env_intensity;
+}
@@ -0,0 +1,49 @@
+// Shadow Map Depth Shader
+//
+// Renders the terrain's depth as seen from the sun into the shadow map.
+// The solid shader compares fragment depths against it to find shadowed
+// areas. See renderer/shadow.rs.
+
+// ============================================================================
+// Uniforms
+// ============================================================================
+
+struct ShadowUniforms {
+    /// Orthographic view * projection matrix of the light
+    light_view_proj: mat4x4<f32>,
+    /// Earth curvature drop per squared distance (1 / 2R), 0.0 for flat
+    curvature_coef: f32,
+    /// Padding
+    _pad: f32,
+    /// Horizontal (x, z) position the curvature drop is measured from
+    curvature_center: vec2<f32>,
+    /// Scale and offset of mesh heights, matching the solid shader
+    height_scale: f32,
+    height_offset: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> shadow: ShadowUniforms;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+/// Rescale a mesh position's height to the current height scale.
+fn rescale(position: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(position.x, position.y * shadow.height_scale + shadow.height_offset, position.z);
+}
+
+/// Lower a position by earth curvature relative to the curvature center,
+/// matching the solid shader.
+fn curve(position: vec3<f32>) -> vec3<f32> {
+    let d = position.xz - shadow.curvature_center;
+    return position - vec3<f32>(0.0, dot(d, d) * shadow.curvature_coef, 0.0);
+}
+
+/// Vertex shader entry point. There is no fragment stage; only depth is
+/// written.
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return shadow.light_view_proj * vec4<f32>(curve(rescale(position)), 1.0);
+}
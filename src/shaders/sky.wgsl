@@ -0,0 +1,62 @@
+// Sky Gradient Shader
+//
+// Fills the background before the scene is drawn, blending from the horizon
+// color to the zenith color by the elevation of each pixel's view ray, or
+// looking the ray up in the environment map. Reads the scene uniforms
+// declared by the scene prelude. See renderer/atmosphere.rs and
+// renderer/environment.rs.
+
+/// Environment panorama (see renderer/environment.rs).
+@group(0) @binding(9)
+var environment_map: texture_2d<f32>;
+
+@group(0) @binding(10)
+var environment_sampler: sampler;
+
+// ============================================================================
+// Vertex Shader
+// ============================================================================
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    /// Normalized device coordinates of the pixel
+    @location(0) ndc: vec2<f32>,
+}
+
+/// Fullscreen triangle from the vertex index; no vertex buffer.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+// ============================================================================
+// Fragment Shader
+// ============================================================================
+
+/// World-space point at normalized device coordinates `ndc` and depth `z`.
+fn unproject(ndc: vec2<f32>, z: f32) -> vec3<f32> {
+    let world = scene.inv_view_proj * vec4<f32>(ndc, z, 1.0);
+    return world.xyz / world.w;
+}
+
+/// Gradient or environment color along the pixel's view ray.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Two points along the ray, whichever way the depth range runs
+    var ray = normalize(unproject(in.ndc, 0.75) - unproject(in.ndc, 0.25));
+    if dot(ray, scene.camera_forward) < 0.0 {
+        ray = -ray;
+    }
+    if scene.env_background > 0.5 {
+        // The top level: the seam has no derivatives to pick a mip from
+        let env = textureSampleLevel(environment_map, environment_sampler, environment_uv(ray), 0.0);
+        return vec4<f32>(pow(env.rgb * scene.env_intensity, vec3<f32>(scene.env_gamma)), 1.0);
+    }
+    let elevation = clamp(ray.y, 0.0, 1.0);
+    let color = mix(scene.sky_horizon, scene.sky_zenith, sqrt(elevation));
+    return vec4<f32>(color, 1.0);
+}
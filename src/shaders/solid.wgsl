@@ -2,7 +2,14 @@
 //
 // Renders terrain with directional lighting for a 3D shaded appearance.
 // Supports both the terrain vertex color and lighting calculations.
-// Optionally renders contour lines at regular height intervals.
+// Optionally renders contour lines at regular height intervals, and fades
+// distant surfaces into the fog. An environment map, when loaded, replaces
+// the flat ambient light and is reflected off the surface.
+//
+// Tweakable parameters are declared with `// @param` annotations and read
+// through generated `param_<name>()` accessors (see renderer/params.rs).
+
+// @param exposure 1.0 0.25 4.0 Exposure
 
 // ============================================================================
 // Uniforms
@@ -14,8 +21,8 @@ struct Uniforms {
     view_proj: mat4x4<f32>,
     /// Light direction (normalized, pointing toward light source)
     light_dir: vec3<f32>,
-    /// Padding for alignment
-    _pad0: f32,
+    /// Shadows enabled (1.0 = on, 0.0 = off)
+    shadows_enabled: f32,
     /// Light color/intensity
     light_color: vec3<f32>,
     /// Ambient light strength (0.0 - 1.0)
@@ -28,13 +35,87 @@ struct Uniforms {
     contour_width: f32,
     /// Contour enabled (1.0 = on, 0.0 = off)
     contour_enabled: f32,
-    /// Padding
-    _pad1: vec2<f32>,
+    /// 1 / log2(far + 1) for logarithmic depth, 0.0 to use linear depth
+    log_depth_coef: f32,
+    /// Earth curvature drop per squared distance (1 / 2R), 0.0 for flat
+    curvature_coef: f32,
+    /// Horizontal (x, z) position the curvature drop is measured from
+    curvature_center: vec2<f32>,
+    /// Surface opacity when drawn as a translucent layer (1.0 = opaque)
+    opacity: f32,
+    /// View distance over which translucent layers fade in OIT weight
+    oit_range: f32,
+    /// Orthographic view * projection matrix of the light, for shadows
+    light_view_proj: mat4x4<f32>,
+    /// Scale and offset of mesh heights, for height scale changes since
+    /// the mesh was built (see HeightTransform in renderer/mod.rs)
+    height_scale: f32,
+    height_offset: f32,
+    /// Uncertainty display (0.0 = off, 1.0 = transparency, 2.0 = hatching)
+    uncertainty_style: f32,
+    /// Opacity removed or fraction hatched at the largest uncertainty
+    uncertainty_strength: f32,
+    /// Mesh-space (x, z) corner of the uncertainty map
+    uncertainty_origin: vec2<f32>,
+    /// Uncertainty map coordinates per mesh-space unit
+    uncertainty_scale: vec2<f32>,
+    /// Mesh-space (x, z) corner of the draped image
+    drape_origin: vec2<f32>,
+    /// Draped image coordinates per mesh-space unit
+    drape_scale: vec2<f32>,
+    /// Alignment correction of the draped image coordinates, the columns
+    /// of a 2x2 matrix and a shift (see DrapeAlignment in terrain/drape.rs)
+    drape_correction: vec4<f32>,
+    drape_shift: vec2<f32>,
+    /// Share of the draped image in the base color (0.0 = none)
+    drape_blend: f32,
 }
 
 @group(0) @binding(0)
 var<uniform> uniforms: Uniforms;
 
+/// Terrain depth as seen from the light (see renderer/shadow.rs).
+@group(0) @binding(2)
+var shadow_map: texture_depth_2d;
+
+@group(0) @binding(3)
+var shadow_sampler: sampler_comparison;
+
+/// Uncertainty relative to its largest value, one texel per terrain
+/// sample (see renderer/uncertainty.rs).
+@group(0) @binding(4)
+var uncertainty_map: texture_2d<f32>;
+
+@group(0) @binding(5)
+var uncertainty_sampler: sampler;
+
+/// Image draped over the terrain's extent (see renderer/drape.rs).
+@group(0) @binding(7)
+var drape_map: texture_2d<f32>;
+
+@group(0) @binding(8)
+var drape_sampler: sampler;
+
+/// Environment panorama (see renderer/environment.rs).
+@group(0) @binding(9)
+var environment_map: texture_2d<f32>;
+
+@group(0) @binding(10)
+var environment_sampler: sampler;
+
+/// Share of the environment reflected by a surface seen head-on.
+const REFLECTANCE: f32 = 0.04;
+
+/// Mip levels above the smallest the reflections are looked up in,
+/// blurring them like a rough surface would.
+const REFLECTION_SHARPNESS: f32 = 5.0;
+
+/// Screen-space distance between hatching lines, in pixels.
+const HATCH_SPACING: f32 = 8.0;
+
+/// Hatching line color.
+const HATCH_COLOR: vec3<f32> = vec3<f32>(0.05, 0.05, 0.05);
+
 // ============================================================================
 // Vertex Shader
 // ============================================================================
@@ -59,38 +140,143 @@ struct VertexOutput {
     @location(1) normal: vec3<f32>,
     /// World-space Y position for contour calculation
     @location(2) world_y: f32,
+    /// Clip-space w (view depth for perspective) for logarithmic depth
+    @location(3) clip_w: f32,
+    /// Light clip-space position for shadow lookup
+    @location(4) shadow_pos: vec4<f32>,
+    /// Mesh-space (x, z) position for the uncertainty and drape lookups
+    @location(5) mesh_xz: vec2<f32>,
+    /// World-space position for fog
+    @location(6) world_position: vec3<f32>,
+}
+
+/// Rescale a mesh position's height to the current height scale.
+fn rescale(position: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(position.x, position.y * uniforms.height_scale + uniforms.height_offset, position.z);
+}
+
+/// Lower a position by earth curvature relative to the curvature center.
+fn curve(position: vec3<f32>) -> vec3<f32> {
+    let d = position.xz - uniforms.curvature_center;
+    return position - vec3<f32>(0.0, dot(d, d) * uniforms.curvature_coef, 0.0);
 }
 
 /// Vertex shader entry point.
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = uniforms.view_proj * vec4<f32>(in.position, 1.0);
+    let position = rescale(in.position);
+    let world = vec4<f32>(curve(position), 1.0);
+    out.clip_position = uniforms.view_proj * world;
     out.color = in.color;
-    out.normal = in.normal;
-    out.world_y = in.position.y;
+    // Stretching heights tilts the normals: scale their horizontal part
+    out.normal = vec3<f32>(
+        in.normal.x * uniforms.height_scale,
+        in.normal.y,
+        in.normal.z * uniforms.height_scale,
+    );
+    out.world_y = position.y;
+    out.clip_w = out.clip_position.w;
+    out.shadow_pos = uniforms.light_view_proj * world;
+    out.mesh_xz = in.position.xz;
+    out.world_position = world.xyz;
     return out;
 }
 
+// ============================================================================
+// User Color Function
+// ============================================================================
+
+// This block is replaced at runtime by the snippet passed with
+// `--color-shader`. The snippet must define:
+//   fn user_color(height: f32, normal: vec3f) -> vec3f
+// @user_color begin
+const USE_USER_COLOR: bool = false;
+
+fn user_color(height: f32, normal: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(0.0);
+}
+// @user_color end
+
 // ============================================================================
 // Fragment Shader
 // ============================================================================
 
-/// Fragment shader entry point with directional lighting and contour lines.
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+/// Fraction of the light reaching a fragment, filtered over 3x3 shadow
+/// map texels. Always 1.0 when shadows are off.
+fn shadow_factor(shadow_pos: vec4<f32>) -> f32 {
+    if uniforms.shadows_enabled < 0.5 {
+        return 1.0;
+    }
+    let ndc = shadow_pos.xyz / shadow_pos.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+/// Uncertainty at a fragment relative to the largest, times the strength.
+fn uncertainty(in: VertexOutput) -> f32 {
+    let uv = (in.mesh_xz - uniforms.uncertainty_origin) * uniforms.uncertainty_scale;
+    let relative = textureSampleLevel(uncertainty_map, uncertainty_sampler, uv, 0.0).r;
+    return relative * uniforms.uncertainty_strength;
+}
+
+/// Directional lighting, contour lines, hatching and fog for a fragment.
+fn shade(in: VertexOutput) -> vec4<f32> {
     // Normalize the interpolated normal
     let normal = normalize(in.normal);
 
-    // Calculate diffuse lighting (Lambert)
-    let n_dot_l = max(dot(normal, uniforms.light_dir), 0.0);
+    // Calculate diffuse lighting (Lambert), dimmed where shadowed
+    let n_dot_l = max(dot(normal, uniforms.light_dir), 0.0) * shadow_factor(in.shadow_pos);
 
-    // Combine ambient and diffuse
+    // Combine ambient and diffuse, the ambient light coming from the
+    // environment map when one lights the scene
     let diffuse = uniforms.light_color * n_dot_l;
-    let lighting = uniforms.ambient + diffuse * (1.0 - uniforms.ambient);
+    var ambient = vec3<f32>(uniforms.ambient);
+    if scene.env_intensity > 0.0 {
+        ambient = environment_irradiance(normal);
+    }
+    let lighting = ambient + diffuse * (1.0 - uniforms.ambient);
+
+    // Sampled before any branching, as mip selection needs derivatives
+    let correction = mat2x2<f32>(uniforms.drape_correction.xy, uniforms.drape_correction.zw);
+    let drape_uv = correction * ((in.mesh_xz - uniforms.drape_origin) * uniforms.drape_scale)
+        + uniforms.drape_shift;
+    let drape = textureSample(drape_map, drape_sampler, drape_uv).rgb;
+
+    // Base color from the height gradient, or the user color function,
+    // blended with the draped image
+    var base_color = in.color;
+    if USE_USER_COLOR {
+        base_color = user_color(in.world_y, normal);
+    }
+    base_color = mix(base_color, drape, uniforms.drape_blend);
 
-    // Apply lighting to vertex color
-    var final_color = in.color * lighting;
+    // Apply lighting and exposure to base color
+    var final_color = base_color * lighting * param_exposure();
+
+    // Reflect the environment, mostly at grazing angles (Schlick)
+    if scene.env_intensity > 0.0 {
+        let view = normalize(in.world_position - scene.camera_position);
+        let facing = max(dot(-view, normal), 0.0);
+        let fresnel = REFLECTANCE + (1.0 - REFLECTANCE) * pow(1.0 - facing, 5.0);
+        let level = max(f32(textureNumLevels(environment_map)) - 1.0 - REFLECTION_SHARPNESS, 0.0);
+        let uv = environment_uv(reflect(view, normal));
+        let reflected = textureSampleLevel(environment_map, environment_sampler, uv, level).rgb;
+        let encoded = pow(reflected * scene.env_intensity, vec3<f32>(scene.env_gamma));
+        final_color = mix(final_color, encoded, fresnel);
+    }
 
     // Apply contour lines if enabled
     if uniforms.contour_enabled > 0.5 {
@@ -109,5 +295,102 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         }
     }
 
-    return vec4<f32>(final_color, 1.0);
+    // Hatch uncertain areas with diagonal lines, wider where less certain
+    if uniforms.uncertainty_style > 1.5 {
+        let stripe = fract((in.clip_position.x + in.clip_position.y) / HATCH_SPACING);
+        if stripe < uncertainty(in) {
+            final_color = HATCH_COLOR;
+        }
+    }
+
+    return vec4<f32>(apply_fog(final_color, in.world_position), 1.0);
+}
+
+/// Fragment shader entry point.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Fragment output with an explicit depth value.
+struct LogDepthOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+/// Logarithmic depth = log2(1 + w) / log2(1 + far), which spreads
+/// precision evenly over orders of magnitude of view distance.
+fn log_depth(in: VertexOutput) -> f32 {
+    if uniforms.log_depth_coef > 0.0 {
+        return log2(max(1e-6, 1.0 + in.clip_w)) * uniforms.log_depth_coef;
+    }
+    return in.clip_position.z;
+}
+
+/// Fragment entry point for logarithmic depth.
+@fragment
+fn fs_main_log_depth(in: VertexOutput) -> LogDepthOutput {
+    var out: LogDepthOutput;
+    out.color = shade(in);
+    out.depth = log_depth(in);
+    return out;
+}
+
+// ============================================================================
+// Order-Independent Transparency
+// ============================================================================
+
+/// Weighted blended OIT targets (see renderer/oit.rs).
+struct OitOutput {
+    /// Premultiplied color and alpha scaled by the depth weight, summed
+    @location(0) accum: vec4<f32>,
+    /// Alpha, multiplied into the revealage target as (1 - alpha)
+    @location(1) reveal: f32,
+}
+
+/// Surface opacity at a fragment, faded where uncertain when uncertainty
+/// is shown as transparency.
+fn layer_alpha(in: VertexOutput) -> f32 {
+    if uniforms.uncertainty_style > 0.5 && uniforms.uncertainty_style < 1.5 {
+        return uniforms.opacity * (1.0 - uncertainty(in));
+    }
+    return uniforms.opacity;
+}
+
+/// Accumulate a translucent fragment at the surface opacity.
+///
+/// Nearer fragments get larger weights, so they dominate the blended
+/// color regardless of draw order (McGuire and Bavoil, 2013).
+fn oit(in: VertexOutput) -> OitOutput {
+    let alpha = layer_alpha(in);
+    let d = in.clip_w / max(uniforms.oit_range, 1e-6);
+    let weight = clamp(0.03 / (1e-5 + pow(d, 4.0)), 1e-2, 3e3);
+    var out: OitOutput;
+    out.accum = vec4<f32>(shade(in).rgb * alpha, alpha) * weight;
+    out.reveal = alpha;
+    return out;
+}
+
+/// Fragment entry point for translucent layers.
+@fragment
+fn fs_oit(in: VertexOutput) -> OitOutput {
+    return oit(in);
+}
+
+/// OIT output with an explicit depth value.
+struct OitLogDepthOutput {
+    @location(0) accum: vec4<f32>,
+    @location(1) reveal: f32,
+    @builtin(frag_depth) depth: f32,
+}
+
+/// Fragment entry point for translucent layers with logarithmic depth.
+@fragment
+fn fs_oit_log_depth(in: VertexOutput) -> OitLogDepthOutput {
+    let layer = oit(in);
+    var out: OitLogDepthOutput;
+    out.accum = layer.accum;
+    out.reveal = layer.reveal;
+    out.depth = log_depth(in);
+    return out;
 }
@@ -2,7 +2,7 @@
 //
 // Simple vertex/fragment shader for rendering terrain wireframes.
 // Receives position and color per vertex, applies view-projection transform,
-// and outputs the color unchanged.
+// and outputs the color, dimmed by the line brightness and faded by fog.
 
 // ============================================================================
 // Uniforms
@@ -13,6 +13,18 @@
 struct Uniforms {
     /// Combined view * projection matrix for transforming world -> clip space
     view_proj: mat4x4<f32>,
+    /// 1 / log2(far + 1) for logarithmic depth, 0.0 to use linear depth
+    log_depth_coef: f32,
+    /// Earth curvature drop per squared distance (1 / 2R), 0.0 for flat
+    curvature_coef: f32,
+    /// Horizontal (x, z) position the curvature drop is measured from
+    curvature_center: vec2<f32>,
+    /// Scale and offset of mesh heights, for height scale changes since
+    /// the mesh was built (see HeightTransform in renderer/mod.rs)
+    height_scale: f32,
+    height_offset: f32,
+    /// Factor dimming the line colors, 1.0 for overlays
+    line_brightness: f32,
 }
 
 @group(0) @binding(0)
@@ -36,6 +48,21 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     /// Interpolated color passed to fragment shader
     @location(0) color: vec3<f32>,
+    /// Clip-space w (view depth for perspective) for logarithmic depth
+    @location(1) clip_w: f32,
+    /// World-space position for fog
+    @location(2) world_position: vec3<f32>,
+}
+
+/// Rescale a mesh position's height to the current height scale.
+fn rescale(position: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(position.x, position.y * uniforms.height_scale + uniforms.height_offset, position.z);
+}
+
+/// Lower a position by earth curvature relative to the curvature center.
+fn curve(position: vec3<f32>) -> vec3<f32> {
+    let d = position.xz - uniforms.curvature_center;
+    return position - vec3<f32>(0.0, dot(d, d) * uniforms.curvature_coef, 0.0);
 }
 
 /// Vertex shader entry point.
@@ -45,8 +72,11 @@ struct VertexOutput {
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = uniforms.view_proj * vec4<f32>(in.position, 1.0);
-    out.color = in.color;
+    let world = curve(rescale(in.position));
+    out.clip_position = uniforms.view_proj * vec4<f32>(world, 1.0);
+    out.color = in.color * uniforms.line_brightness;
+    out.clip_w = out.clip_position.w;
+    out.world_position = world;
     return out;
 }
 
@@ -54,10 +84,35 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 // Fragment Shader
 // ============================================================================
 
+/// Outputs the interpolated vertex color with full opacity, faded by fog.
+fn shade(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(apply_fog(in.color, in.world_position), 1.0);
+}
+
 /// Fragment shader entry point.
-///
-/// Simply outputs the interpolated vertex color with full opacity.
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    return shade(in);
+}
+
+/// Fragment output with an explicit depth value.
+struct LogDepthOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+/// Fragment entry point for logarithmic depth.
+///
+/// Writes depth = log2(1 + w) / log2(1 + far), which spreads precision
+/// evenly over orders of magnitude of view distance.
+@fragment
+fn fs_main_log_depth(in: VertexOutput) -> LogDepthOutput {
+    var out: LogDepthOutput;
+    out.color = shade(in);
+    if uniforms.log_depth_coef > 0.0 {
+        out.depth = log2(max(1e-6, 1.0 + in.clip_w)) * uniforms.log_depth_coef;
+    } else {
+        out.depth = in.clip_position.z;
+    }
+    return out;
 }
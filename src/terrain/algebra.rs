@@ -0,0 +1,407 @@
+//! Map algebra over the terrain's layers.
+//!
+//! A statement such as `relief = (height - dem2020).abs() * 2` computes a
+//! new channel sample by sample from the heights (`height`, or
+//! `elevation`) and the channels named in it, like the raster calculators
+//! of GIS tools. Expressions support numbers, `+ - * / ^`, parentheses and
+//! the functions below, called as `abs(x)` or `x.abs()`:
+//!
+//! | Function | Result |
+//! |---|---|
+//! | `abs`, `sqrt`, `exp`, `ln`, `floor`, `ceil`, `round` | of one value |
+//! | `min(a, b)`, `max(a, b)` | the smaller or larger of two |
+//! | `clamp(x, lo, hi)` | `x` limited to `lo..=hi` |
+//!
+//! The result is stored as a channel of the name on the left, replacing a
+//! channel of that name, so it can be colored by or used in later
+//! statements.
+
+use anyhow::{anyhow, bail, Result};
+
+use super::grid::Grid;
+use super::TerrainData;
+
+/// A parsed `name = expression` statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    /// Channel the result is stored as
+    pub name: String,
+    expr: Expr,
+}
+
+/// Expression tree; layers are looked up by name when evaluated.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Layer(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+/// Functions expressions can call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "floor" => Self::Floor,
+            "ceil" => Self::Ceil,
+            "round" => Self::Round,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "clamp" => Self::Clamp,
+            _ => return None,
+        })
+    }
+
+    /// Number of arguments, the receiver of a method call included.
+    fn arity(self) -> usize {
+        match self {
+            Self::Min | Self::Max => 2,
+            Self::Clamp => 3,
+            _ => 1,
+        }
+    }
+
+    fn apply(self, args: &[f64]) -> f64 {
+        match self {
+            Self::Abs => args[0].abs(),
+            Self::Sqrt => args[0].sqrt(),
+            Self::Exp => args[0].exp(),
+            Self::Ln => args[0].ln(),
+            Self::Floor => args[0].floor(),
+            Self::Ceil => args[0].ceil(),
+            Self::Round => args[0].round(),
+            Self::Min => args[0].min(args[1]),
+            Self::Max => args[0].max(args[1]),
+            Self::Clamp => args[0].max(args[1]).min(args[2]),
+        }
+    }
+}
+
+impl Statement {
+    /// Parse `name = expression`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let (name, _) = text
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected NAME = EXPRESSION"))?;
+        // Positions in errors count from the start of the statement
+        let mut parser = Parser {
+            text,
+            pos: name.len() + 1,
+        };
+        let name = name.trim();
+        if !is_identifier(name) {
+            bail!("Invalid output name '{name}'");
+        }
+        let expr = parser.expr()?;
+        parser.skip_space();
+        if parser.pos < parser.text.len() {
+            bail!("Unexpected '{}'", &parser.text[parser.pos..]);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            expr,
+        })
+    }
+
+    /// Evaluate the expression at every sample of `terrain`.
+    pub fn evaluate(&self, terrain: &TerrainData) -> Result<Grid<f32>> {
+        let values = (0..terrain.width * terrain.height)
+            .map(|i| eval(&self.expr, terrain, i))
+            .collect::<Result<Vec<f64>>>()?;
+        Ok(Grid::new(
+            terrain.width,
+            values.into_iter().map(|v| v as f32).collect(),
+        ))
+    }
+
+    /// Evaluate the statement and store it as a channel of `terrain`,
+    /// returning the channel's index.
+    pub fn run(&self, terrain: &mut TerrainData) -> Result<usize> {
+        let grid = self.evaluate(terrain)?;
+        Ok(terrain.set_channel(&self.name, grid))
+    }
+}
+
+/// Value of `expr` at sample `i`.
+fn eval(expr: &Expr, terrain: &TerrainData, i: usize) -> Result<f64> {
+    Ok(match expr {
+        Expr::Number(value) => *value,
+        Expr::Layer(name) => layer(terrain, name, i)?,
+        Expr::Neg(inner) => -eval(inner, terrain, i)?,
+        Expr::Binary(op, a, b) => {
+            let (a, b) = (eval(a, terrain, i)?, eval(b, terrain, i)?);
+            match op {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                '/' => a / b,
+                _ => a.powf(b),
+            }
+        }
+        Expr::Call(function, args) => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, terrain, i))
+                .collect::<Result<Vec<f64>>>()?;
+            function.apply(&args)
+        }
+    })
+}
+
+/// Value of layer `name` at sample `i`.
+fn layer(terrain: &TerrainData, name: &str, i: usize) -> Result<f64> {
+    if name == "height" || name == "elevation" {
+        return Ok(terrain.heights()[i]);
+    }
+    match terrain
+        .channels()
+        .iter()
+        .find(|channel| channel.name == name)
+    {
+        Some(channel) => Ok(channel.grid.values()[i] as f64),
+        None => {
+            let names: Vec<&str> = ["height"]
+                .into_iter()
+                .chain(terrain.channels().iter().map(|c| c.name.as_str()))
+                .collect();
+            bail!("Unknown layer '{name}'; layers: {}", names.join(", "))
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Recursive descent parser over an expression.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume `c` if it comes next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        if self.text[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if !self.eat(c) {
+            bail!("Expected '{c}' at position {}", self.pos + 1);
+        }
+        Ok(())
+    }
+
+    /// Sums and differences.
+    fn expr(&mut self) -> Result<Expr> {
+        let mut expr = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.term()?));
+        }
+    }
+
+    /// Products and quotients.
+    fn term(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    /// Negation, binding looser than powers: `-2^2` is -4.
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.postfix()?;
+        if self.eat('^') {
+            // Right-associative
+            return Ok(Expr::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    /// A primary followed by method calls.
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        while self.eat('.') {
+            let name = self.identifier()?;
+            let mut args = vec![expr];
+            self.expect('(')?;
+            args.extend(self.arguments()?);
+            expr = call(&name, args)?;
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        self.skip_space();
+        if self.eat('(') {
+            let expr = self.expr()?;
+            self.expect(')')?;
+            return Ok(expr);
+        }
+        let rest = &self.text[self.pos..];
+        if rest.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let value = rest[..len]
+                .parse()
+                .map_err(|_| anyhow!("Invalid number '{}'", &rest[..len]))?;
+            self.pos += len;
+            return Ok(Expr::Number(value));
+        }
+        let name = self.identifier()?;
+        if self.eat('(') {
+            let args = self.arguments()?;
+            return call(&name, args);
+        }
+        Ok(Expr::Layer(name))
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if !is_identifier(&rest[..len]) {
+            match rest.chars().next() {
+                Some(c) => bail!("Unexpected '{c}' at position {}", self.pos + 1),
+                None => bail!("Unexpected end of expression"),
+            }
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    /// Comma-separated arguments up to the closing parenthesis, after the
+    /// opening one.
+    fn arguments(&mut self) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.eat(')') {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if self.eat(')') {
+                return Ok(args);
+            }
+            self.expect(',')?;
+        }
+    }
+}
+
+/// Call of function `name`, checking its argument count.
+fn call(name: &str, args: Vec<Expr>) -> Result<Expr> {
+    let function = Function::from_name(name).ok_or_else(|| anyhow!("Unknown function '{name}'"))?;
+    if args.len() != function.arity() {
+        bail!(
+            "{name} takes {} arguments, got {}",
+            function.arity(),
+            args.len()
+        );
+    }
+    Ok(Expr::Call(function, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(terrain: &mut TerrainData, text: &str) -> Vec<f32> {
+        let index = Statement::parse(text).unwrap().run(terrain).unwrap();
+        terrain.channels()[index].grid.values().to_vec()
+    }
+
+    #[test]
+    fn test_statements_derive_channels() {
+        let mut terrain = TerrainData::new(vec![vec![1.0, 4.0], vec![9.0, 16.0]], None);
+        let before = TerrainData::new(vec![vec![2.0, 2.0], vec![2.0, 20.0]], None);
+        terrain.add_channel("before", &before);
+
+        assert_eq!(
+            run(&mut terrain, "change = (height - before).abs()"),
+            [1.0, 2.0, 7.0, 4.0]
+        );
+        assert_eq!(run(&mut terrain, "x = 2 + 3 * 4 - -1"), [15.0; 4]);
+        assert_eq!(run(&mut terrain, "x = -2^2 + 2^3^0"), [-2.0; 4]);
+        assert_eq!(
+            run(&mut terrain, "root = sqrt(elevation).max(2)"),
+            [2.0, 2.0, 3.0, 4.0]
+        );
+        assert_eq!(
+            run(&mut terrain, "x = clamp(change, 2, 5) / 2"),
+            [1.0, 1.0, 2.5, 2.0]
+        );
+
+        // Reusing a name replaces its channel
+        assert_eq!(terrain.channels().len(), 4);
+        assert_eq!(terrain.channels()[1].name, "change");
+    }
+
+    #[test]
+    fn test_errors_name_the_problem() {
+        let terrain = TerrainData::new(vec![vec![1.0]], None);
+        let err = |text: &str| match Statement::parse(text) {
+            Ok(statement) => statement.evaluate(&terrain).unwrap_err().to_string(),
+            Err(err) => err.to_string(),
+        };
+        assert_eq!(err("height * 2"), "Expected NAME = EXPRESSION");
+        assert_eq!(err("2x = height"), "Invalid output name '2x'");
+        assert_eq!(err("x = (height"), "Expected ')' at position 12");
+        assert_eq!(err("x = height height"), "Unexpected 'height'");
+        assert_eq!(err("x = cos(height)"), "Unknown function 'cos'");
+        assert_eq!(err("x = height.min()"), "min takes 2 arguments, got 1");
+        assert_eq!(err("x = rain + 1"), "Unknown layer 'rain'; layers: height");
+    }
+}
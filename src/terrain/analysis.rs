@@ -0,0 +1,771 @@
+//! Terrain analysis.
+//!
+//! Ridgeline and valley-line extraction from height curvature, with
+//! GeoJSON export for use in GIS tools, curvature and terrain index rasters
+//! for the analysis color modes, the elevation and slope distributions
+//! shown in the Analysis panel, and the area under a water level.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use glam::DVec3;
+
+use super::TerrainData;
+
+/// Whether a line follows a crest or a trough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Ridge,
+    Valley,
+}
+
+impl LineKind {
+    /// Name used in exported feature properties.
+    pub fn name(self) -> &'static str {
+        match self {
+            LineKind::Ridge => "ridge",
+            LineKind::Valley => "valley",
+        }
+    }
+}
+
+/// A ridge or valley line through terrain grid vertices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub kind: LineKind,
+    /// Grid vertex indices (`z * width + x`) along the line
+    pub vertices: Vec<usize>,
+}
+
+impl Polyline {
+    /// World-space positions of the line's vertices.
+    pub fn points(&self, terrain: &TerrainData) -> Vec<DVec3> {
+        self.vertices
+            .iter()
+            .map(|&i| terrain.world_position(i % terrain.width, i / terrain.width))
+            .collect()
+    }
+}
+
+/// Grid steps checked for a crest or trough profile: along X, along Z,
+/// and both diagonals.
+const PROFILE_DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+/// Classify a vertex as ridge or valley.
+///
+/// A vertex is on a ridge if, along some direction, it is higher than both
+/// neighbors and the height profile bends down by more than
+/// `min_curvature`; valleys are the reverse.
+fn classify(terrain: &TerrainData, x: usize, z: usize, min_curvature: f64) -> Option<LineKind> {
+    let (w, h) = (terrain.width as isize, terrain.height as isize);
+    let (x, z) = (x as isize, z as isize);
+    let at = |x: isize, z: isize| terrain.at(x as usize, z as usize);
+    let center = at(x, z);
+
+    let mut kind = None;
+    for (dx, dz) in PROFILE_DIRECTIONS {
+        let (x0, z0, x1, z1) = (x - dx, z - dz, x + dx, z + dz);
+        if x0 < 0 || z0 < 0 || z0 >= h || x1 >= w || z1 < 0 || z1 >= h {
+            continue;
+        }
+        let (a, b) = (at(x0, z0), at(x1, z1));
+        let bend = a + b - 2.0 * center;
+        if center > a && center > b && bend < -min_curvature {
+            kind = Some(LineKind::Ridge);
+        } else if center < a && center < b && bend > min_curvature && kind.is_none() {
+            kind = Some(LineKind::Valley);
+        }
+    }
+    kind
+}
+
+/// Extract ridge and valley lines from the terrain's curvature.
+///
+/// Vertices whose height profile bends by more than `min_curvature` (in
+/// height units per squared cell) are linked to neighboring vertices of the
+/// same kind and chained into polylines. Lines with fewer than
+/// `min_length` vertices are dropped as noise.
+pub fn extract_lines(
+    terrain: &TerrainData,
+    min_curvature: f64,
+    min_length: usize,
+) -> Vec<Polyline> {
+    let (width, height) = (terrain.width, terrain.height);
+    if width < 3 || height < 3 {
+        return Vec::new();
+    }
+
+    let kinds: Vec<Option<LineKind>> = (0..height)
+        .flat_map(|z| (0..width).map(move |x| (x, z)))
+        .map(|(x, z)| classify(terrain, x, z, min_curvature))
+        .collect();
+
+    // Link same-kind neighbors; diagonals only where no axis path exists
+    let mut neighbors = vec![Vec::new(); width * height];
+    let mut link = |a: usize, b: usize| {
+        neighbors[a].push(b);
+        neighbors[b].push(a);
+    };
+    let same = |a: usize, b: usize| kinds[a].is_some() && kinds[a] == kinds[b];
+    for z in 0..height {
+        for x in 0..width {
+            let i = z * width + x;
+            if kinds[i].is_none() {
+                continue;
+            }
+            let right = x + 1 < width;
+            let down = z + 1 < height;
+            if right && same(i, i + 1) {
+                link(i, i + 1);
+            }
+            if down && same(i, i + width) {
+                link(i, i + width);
+            }
+            if right && down && same(i, i + width + 1) && !same(i, i + 1) && !same(i, i + width) {
+                link(i, i + width + 1);
+            }
+            if x > 0 && down && same(i, i + width - 1) && !same(i, i - 1) && !same(i, i + width) {
+                link(i, i + width - 1);
+            }
+        }
+    }
+
+    // Chain edges into polylines, starting from endpoints and junctions so
+    // lines break there, then from whatever is left (closed loops)
+    let mut used = HashSet::new();
+    let mut lines = Vec::new();
+    let starts = (0..neighbors.len())
+        .filter(|&i| neighbors[i].len() != 2)
+        .chain(0..neighbors.len());
+    for start in starts {
+        for &first in &neighbors[start] {
+            if used.contains(&edge(start, first)) {
+                continue;
+            }
+            let mut vertices = vec![start];
+            let (mut prev, mut current) = (start, first);
+            used.insert(edge(prev, current));
+            vertices.push(current);
+            while neighbors[current].len() == 2 {
+                let next = neighbors[current][if neighbors[current][0] == prev { 1 } else { 0 }];
+                if !used.insert(edge(current, next)) {
+                    break;
+                }
+                vertices.push(next);
+                (prev, current) = (current, next);
+            }
+            if vertices.len() >= min_length {
+                lines.push(Polyline {
+                    kind: kinds[start].unwrap_or(LineKind::Ridge),
+                    vertices,
+                });
+            }
+        }
+    }
+    lines
+}
+
+/// Minimum profile bend used by [`ridgelines`], as a fraction of the
+/// terrain's height range.
+const RIDGELINE_CURVATURE: f64 = 0.01;
+/// Shortest line kept by [`ridgelines`], in vertices.
+const RIDGELINE_MIN_LENGTH: usize = 4;
+
+/// Extract ridge and valley lines with thresholds relative to the terrain's
+/// height range.
+pub fn ridgelines(terrain: &TerrainData) -> Vec<Polyline> {
+    let (min_h, max_h) = terrain.height_bounds();
+    extract_lines(
+        terrain,
+        (max_h - min_h) * RIDGELINE_CURVATURE,
+        RIDGELINE_MIN_LENGTH,
+    )
+}
+
+/// Undirected edge key.
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Serialize lines as a GeoJSON FeatureCollection of LineStrings.
+///
+/// Coordinates are `[x, z, height]` in world units, i.e. easting, northing
+/// and elevation for georeferenced terrain. Each feature has a `kind`
+/// property of `"ridge"` or `"valley"`.
+pub fn to_geojson(lines: &[Polyline], terrain: &TerrainData) -> String {
+    let mut out = String::from("{\"type\":\"FeatureCollection\",\"features\":[");
+    for (n, line) in lines.iter().enumerate() {
+        if n > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"type\":\"Feature\",\"properties\":{\"kind\":\"");
+        out.push_str(line.kind.name());
+        out.push_str("\"},\"geometry\":{\"type\":\"LineString\",\"coordinates\":[");
+        for (i, p) in line.points(terrain).iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "[{},{},{}]", p.x, p.z, p.y);
+        }
+        out.push_str("]}}");
+    }
+    out.push_str("]}\n");
+    out
+}
+
+/// Points on the hypsometric curve computed by [`TerrainStats::compute`].
+const HYPSOMETRIC_STEPS: usize = 64;
+/// Width of a [`TerrainStats::slope_histogram`] bin, in degrees.
+pub const SLOPE_BIN_DEGREES: f64 = 5.0;
+
+/// Summary statistics of a terrain's heights.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HeightSummary {
+    /// Samples per row and number of rows
+    pub size: (usize, usize),
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+}
+
+impl HeightSummary {
+    /// Summarize the heights of `terrain`.
+    pub fn compute(terrain: &TerrainData) -> Self {
+        let mut heights = terrain.heights().to_vec();
+        let count = heights.len();
+        if count == 0 {
+            return Self::default();
+        }
+        let (min, max) = terrain.height_bounds();
+        let mean = heights.iter().sum::<f64>() / count as f64;
+        let (lower, &mut upper, _) = heights.select_nth_unstable_by(count / 2, f64::total_cmp);
+        // Even counts average the two middle samples
+        let median = match count % 2 {
+            0 => (lower.iter().copied().fold(f64::MIN, f64::max) + upper) / 2.0,
+            _ => upper,
+        };
+        Self {
+            size: (terrain.width, terrain.height),
+            min,
+            max,
+            mean,
+            median,
+        }
+    }
+
+    /// Number of grid cells between the samples.
+    pub fn cells(&self) -> usize {
+        self.size.0.saturating_sub(1) * self.size.1.saturating_sub(1)
+    }
+}
+
+/// Elevation and slope distributions of a terrain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainStats {
+    /// Height range, mean and median
+    pub heights: HeightSummary,
+    /// Hypsometric curve; see [`hypsometric_curve`]
+    pub hypsometric: Vec<(f64, f64)>,
+    /// Hypsometric integral (mean relative height), between 0 and 1
+    pub hypsometric_integral: f64,
+    /// Sample counts per [`SLOPE_BIN_DEGREES`] of slope, from 0° to 90°
+    pub slope_histogram: Vec<u32>,
+}
+
+impl TerrainStats {
+    /// Compute all distributions of `terrain`.
+    pub fn compute(terrain: &TerrainData) -> Self {
+        let bins = (90.0 / SLOPE_BIN_DEGREES).ceil() as usize;
+        Self {
+            heights: HeightSummary::compute(terrain),
+            hypsometric: hypsometric_curve(terrain, HYPSOMETRIC_STEPS),
+            hypsometric_integral: hypsometric_integral(terrain),
+            slope_histogram: slope_histogram(terrain, bins),
+        }
+    }
+}
+
+/// Heights relative to the terrain's height range, between 0 and 1.
+fn relative_heights(terrain: &TerrainData) -> impl Iterator<Item = f64> + '_ {
+    let (min_h, max_h) = terrain.height_bounds();
+    let range = (max_h - min_h).max(f64::EPSILON);
+    terrain.heights().iter().map(move |&h| (h - min_h) / range)
+}
+
+/// Hypsometric curve: for `steps + 1` relative heights from 1 (summit) down
+/// to 0 (lowest point), the fraction of the area at or above that height.
+///
+/// Returned as `(area fraction, relative height)` pairs, the usual axes of
+/// a hypsometric plot.
+pub fn hypsometric_curve(terrain: &TerrainData, steps: usize) -> Vec<(f64, f64)> {
+    let mut heights: Vec<f64> = relative_heights(terrain).collect();
+    if heights.is_empty() || steps == 0 {
+        return Vec::new();
+    }
+    heights.sort_unstable_by(f64::total_cmp);
+
+    let total = heights.len() as f64;
+    (0..=steps)
+        .map(|i| {
+            let height = 1.0 - i as f64 / steps as f64;
+            let below = heights.partition_point(|&h| h < height);
+            ((heights.len() - below) as f64 / total, height)
+        })
+        .collect()
+}
+
+/// Area under the hypsometric curve, equal to the mean relative height.
+pub fn hypsometric_integral(terrain: &TerrainData) -> f64 {
+    let count = terrain.width * terrain.height;
+    if count == 0 {
+        return 0.0;
+    }
+    relative_heights(terrain).sum::<f64>() / count as f64
+}
+
+/// Histogram of heights over `bins` equal ranges from the lowest sample to
+/// the highest, the span the color schemes map.
+pub fn height_histogram(terrain: &TerrainData, bins: usize) -> Vec<u32> {
+    let mut counts = vec![0; bins];
+    if bins == 0 {
+        return counts;
+    }
+    for t in relative_heights(terrain) {
+        let bin = ((t * bins as f64) as usize).min(bins - 1);
+        counts[bin] += 1;
+    }
+    counts
+}
+
+/// Fraction of the samples below `level`, and the area they cover, one
+/// cell each: the terrain a water plane at that height floods.
+pub fn flooded(terrain: &TerrainData, level: f64) -> (f64, f64) {
+    let heights = terrain.heights();
+    if heights.is_empty() {
+        return (0.0, 0.0);
+    }
+    let below = heights.iter().filter(|&&height| height < level).count();
+    (
+        below as f64 / heights.len() as f64,
+        below as f64 * terrain.cell_size * terrain.cell_size,
+    )
+}
+
+/// Histogram of slope angles over `bins` equal ranges from 0° to 90°.
+pub fn slope_histogram(terrain: &TerrainData, bins: usize) -> Vec<u32> {
+    let mut counts = vec![0; bins];
+    if bins == 0 || terrain.width < 2 || terrain.height < 2 {
+        return counts;
+    }
+
+    let bin_size = 90.0 / bins as f64;
+    for degrees in slope_angles(terrain) {
+        let bin = ((degrees / bin_size) as usize).min(bins - 1);
+        counts[bin] += 1;
+    }
+    counts
+}
+
+/// Slope angle of each sample in degrees, in row-major order.
+///
+/// Slopes come from central differences of height (one-sided at the
+/// edges) over the grid spacing; a single row or column is flat across.
+pub fn slope_angles(terrain: &TerrainData) -> Vec<f64> {
+    let (width, height) = (terrain.width, terrain.height);
+    let h = |x: usize, z: usize| terrain.at(x, z);
+    let gradient = |a: f64, b: f64, cells: usize| {
+        if cells == 0 {
+            0.0
+        } else {
+            (b - a) / (cells as f64 * terrain.cell_size)
+        }
+    };
+    let mut angles = Vec::with_capacity(width * height);
+    for z in 0..height {
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+            let (z0, z1) = (z.saturating_sub(1), (z + 1).min(height - 1));
+            let dx = gradient(h(x0, z), h(x1, z), x1 - x0);
+            let dz = gradient(h(x, z0), h(x, z1), z1 - z0);
+            angles.push(dx.hypot(dz).atan().to_degrees());
+        }
+    }
+    angles
+}
+
+/// Height derivatives at a sample: first along x and z, then second along
+/// x, z and mixed.
+struct Derivatives {
+    fx: f64,
+    fz: f64,
+    fxx: f64,
+    fzz: f64,
+    fxz: f64,
+}
+
+/// Derivatives from central differences over the 3x3 window around
+/// (`x`, `z`). Edge samples use the window of their nearest interior
+/// sample. Needs at least a 3x3 grid.
+fn derivatives(terrain: &TerrainData, x: usize, z: usize) -> Derivatives {
+    let x = x.clamp(1, terrain.width - 2);
+    let z = z.clamp(1, terrain.height - 2);
+    let h =
+        |dx: isize, dz: isize| terrain.at((x as isize + dx) as usize, (z as isize + dz) as usize);
+    let l = terrain.cell_size;
+    Derivatives {
+        fx: (h(1, 0) - h(-1, 0)) / (2.0 * l),
+        fz: (h(0, 1) - h(0, -1)) / (2.0 * l),
+        fxx: (h(1, 0) - 2.0 * h(0, 0) + h(-1, 0)) / (l * l),
+        fzz: (h(0, 1) - 2.0 * h(0, 0) + h(0, -1)) / (l * l),
+        fxz: (h(1, 1) - h(1, -1) - h(-1, 1) + h(-1, -1)) / (4.0 * l * l),
+    }
+}
+
+/// Evaluate `curvature` at every sample in row-major order, or zeros for
+/// grids too small to have an interior.
+fn curvature_raster(terrain: &TerrainData, curvature: fn(&Derivatives) -> f64) -> Vec<f64> {
+    let (width, height) = (terrain.width, terrain.height);
+    if width < 3 || height < 3 {
+        return vec![0.0; width * height];
+    }
+    (0..height)
+        .flat_map(|z| (0..width).map(move |x| (x, z)))
+        .map(|(x, z)| curvature(&derivatives(terrain, x, z)))
+        .collect()
+}
+
+/// Gradients flatter than this have no defined flow direction, and their
+/// curvature is reported as zero.
+const FLAT_GRADIENT: f64 = 1e-12;
+
+/// Plan (contour) curvature per sample, in row-major order: how contour
+/// lines bend, per unit of distance.
+///
+/// Positive where contours bulge downhill (ridges and spurs, where flow
+/// diverges) and negative where they bend uphill (channels and hollows,
+/// where flow converges).
+pub fn plan_curvature(terrain: &TerrainData) -> Vec<f64> {
+    curvature_raster(terrain, |d| {
+        let p = d.fx * d.fx + d.fz * d.fz;
+        if p < FLAT_GRADIENT {
+            return 0.0;
+        }
+        -(d.fxx * d.fz * d.fz - 2.0 * d.fxz * d.fx * d.fz + d.fzz * d.fx * d.fx) / p.powf(1.5)
+    })
+}
+
+/// Profile curvature per sample, in row-major order: how the slope bends
+/// along the direction of steepest descent, per unit of distance.
+///
+/// Positive where the slope steepens downhill (convex crests and brinks,
+/// where flow accelerates) and negative where it eases (concave footslopes
+/// and channel beds, where flow slows).
+pub fn profile_curvature(terrain: &TerrainData) -> Vec<f64> {
+    curvature_raster(terrain, |d| {
+        let p = d.fx * d.fx + d.fz * d.fz;
+        if p < FLAT_GRADIENT {
+            return 0.0;
+        }
+        -(d.fxx * d.fx * d.fx + 2.0 * d.fxz * d.fx * d.fz + d.fzz * d.fz * d.fz)
+            / (p * (1.0 + p).powf(1.5))
+    })
+}
+
+/// Evaluate `index` at every sample in row-major order, passing the
+/// sample's height and the heights of its neighbors within `radius`
+/// samples (a `2 * radius + 1` square window, clipped to the grid).
+fn window_raster(
+    terrain: &TerrainData,
+    radius: usize,
+    index: impl Fn(f64, &[f64]) -> f64,
+) -> Vec<f64> {
+    let (width, height) = (terrain.width, terrain.height);
+    let mut neighbors = Vec::new();
+    let mut values = Vec::with_capacity(width * height);
+    for z in 0..height {
+        let (z0, z1) = (z.saturating_sub(radius), (z + radius).min(height - 1));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius).min(width - 1));
+            neighbors.clear();
+            for nz in z0..=z1 {
+                for nx in x0..=x1 {
+                    if (nx, nz) != (x, z) {
+                        neighbors.push(terrain.at(nx, nz));
+                    }
+                }
+            }
+            values.push(index(terrain.at(x, z), &neighbors));
+        }
+    }
+    values
+}
+
+/// Terrain Ruggedness Index per sample, in row-major order: the root of
+/// the summed squared height differences to the neighbors within `radius`.
+///
+/// With `radius` 1 this is GDAL's `gdaldem TRI` with the default Riley
+/// algorithm, except that edge samples use their clipped window instead
+/// of being left as nodata.
+pub fn ruggedness_index(terrain: &TerrainData, radius: usize) -> Vec<f64> {
+    window_raster(terrain, radius, |center, neighbors| {
+        neighbors
+            .iter()
+            .map(|h| (h - center).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    })
+}
+
+/// Topographic Position Index per sample, in row-major order: height above
+/// the mean of the neighbors within `radius`.
+///
+/// Positive on crests and negative in hollows. With `radius` 1 this is
+/// GDAL's `gdaldem TPI`, except at the edges as for [`ruggedness_index`].
+pub fn topographic_position_index(terrain: &TerrainData, radius: usize) -> Vec<f64> {
+    window_raster(terrain, radius, |center, neighbors| {
+        if neighbors.is_empty() {
+            return 0.0;
+        }
+        center - neighbors.iter().sum::<f64>() / neighbors.len() as f64
+    })
+}
+
+/// Roughness per sample, in row-major order: the height range of the
+/// window within `radius`, the sample included.
+///
+/// With `radius` 1 this is GDAL's `gdaldem roughness`, except at the edges
+/// as for [`ruggedness_index`].
+pub fn roughness(terrain: &TerrainData, radius: usize) -> Vec<f64> {
+    window_raster(terrain, radius, |center, neighbors| {
+        let (min, max) = neighbors
+            .iter()
+            .fold((center, center), |(min, max), &h| (min.min(h), max.max(h)));
+        max - min
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ridge along x = 3, falling off toward x = 0 and x = 6.
+    fn ridge_terrain() -> TerrainData {
+        let profile = [0.0, 4.0, 7.0, 9.0, 7.0, 4.0, 0.0];
+        let points = (0..5).map(|_| profile.to_vec()).collect();
+        TerrainData::new(points, None)
+    }
+
+    #[test]
+    fn test_extract_ridge_line() {
+        let terrain = ridge_terrain();
+        let lines = extract_lines(&terrain, 0.5, 2);
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].kind, LineKind::Ridge);
+        let xs: Vec<usize> = lines[0].vertices.iter().map(|i| i % 7).collect();
+        assert_eq!(xs, vec![3; 5]);
+    }
+
+    #[test]
+    fn test_extract_valley_line() {
+        let mut terrain = ridge_terrain();
+        for h in terrain.heights_mut() {
+            *h = -*h;
+        }
+        let lines = extract_lines(&terrain, 0.5, 2);
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].kind, LineKind::Valley);
+    }
+
+    #[test]
+    fn test_flat_terrain_has_no_lines() {
+        let terrain = TerrainData::new(vec![vec![1.0; 6]; 6], None);
+        assert!(extract_lines(&terrain, 0.0, 2).is_empty());
+    }
+
+    #[test]
+    fn test_min_curvature_filters_gentle_ridges() {
+        let terrain = ridge_terrain();
+        // The crest bends by 4 units per squared cell
+        assert!(extract_lines(&terrain, 5.0, 2).is_empty());
+    }
+
+    #[test]
+    fn test_geojson_output() {
+        let terrain = ridge_terrain();
+        let lines = extract_lines(&terrain, 0.5, 2);
+        let json = to_geojson(&lines, &terrain);
+
+        assert!(json.starts_with("{\"type\":\"FeatureCollection\""));
+        assert!(json.contains("\"kind\":\"ridge\""));
+        assert!(json.contains("\"type\":\"LineString\""));
+        assert!(json.contains("[3,0,9]"));
+        assert!(json.contains("[3,4,9]"));
+    }
+
+    #[test]
+    fn test_hypsometric_curve_of_uniform_ramp() {
+        // Heights 0..=4 in equal amounts
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0, 3.0, 4.0]; 3], None);
+        let curve = hypsometric_curve(&terrain, 4);
+
+        assert_eq!(curve.len(), 5);
+        assert_eq!(curve[0], (0.2, 1.0));
+        assert_eq!(curve[2], (0.6, 0.5));
+        assert_eq!(curve[4], (1.0, 0.0));
+        assert!((hypsometric_integral(&terrain) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_height_histogram() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 1.0, 4.0]], None);
+        assert_eq!(height_histogram(&terrain, 4), [1, 2, 0, 1]);
+        assert_eq!(height_histogram(&terrain, 2), [3, 1]);
+
+        // A flat terrain sits at the bottom of the range
+        let flat = TerrainData::new(vec![vec![5.0; 3]; 2], None);
+        assert_eq!(height_histogram(&flat, 3), [6, 0, 0]);
+    }
+
+    #[test]
+    fn test_flooded_below_level() {
+        let mut terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0, 3.0]], None);
+        terrain.cell_size = 2.0;
+        assert_eq!(flooded(&terrain, 2.0), (0.5, 8.0));
+        assert_eq!(flooded(&terrain, -1.0), (0.0, 0.0));
+        assert_eq!(flooded(&terrain, 10.0), (1.0, 16.0));
+    }
+
+    #[test]
+    fn test_slope_histogram() {
+        // Rises one unit per cell along x: 45° everywhere
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0, 3.0]; 3], None);
+        let counts = slope_histogram(&terrain, 18);
+
+        assert_eq!(counts.iter().sum::<u32>(), 12);
+        assert_eq!(counts[9], 12);
+
+        let flat = TerrainData::new(vec![vec![5.0; 4]; 4], None);
+        assert_eq!(slope_histogram(&flat, 18)[0], 16);
+    }
+
+    #[test]
+    fn test_slope_angles_follow_cell_size() {
+        let mut terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]; 2], None);
+        assert!(slope_angles(&terrain)
+            .iter()
+            .all(|&a| (a - 45.0).abs() < 1e-9));
+
+        // Wider cells flatten the same rise
+        terrain.cell_size = 3.0_f64.sqrt();
+        assert!((slope_angles(&terrain)[4] - 30.0).abs() < 1e-9);
+        // A single row has no slope across it
+        let row = TerrainData::new(vec![vec![0.0, 0.0]], None);
+        assert_eq!(slope_angles(&row), vec![0.0, 0.0]);
+    }
+
+    /// Heights of `f(x, z)` on a 7x7 grid centered on the origin.
+    fn surface(f: fn(f64, f64) -> f64) -> TerrainData {
+        let points = (0..7)
+            .map(|z| (0..7).map(|x| f(x as f64 - 3.0, z as f64 - 3.0)).collect())
+            .collect();
+        TerrainData::new(points, None)
+    }
+
+    #[test]
+    fn test_plan_curvature_of_valley_and_spur() {
+        // Contours of a valley draining toward -z bend uphill along its axis
+        let valley = plan_curvature(&surface(|x, z| x * x + z));
+        assert!((valley[3 * 7 + 3] + 2.0).abs() < 1e-9);
+        let spur = plan_curvature(&surface(|x, z| -x * x + z));
+        assert!((spur[3 * 7 + 3] - 2.0).abs() < 1e-9);
+
+        // A uniform slope has straight contours
+        let plane = plan_curvature(&surface(|x, z| 2.0 * x - z));
+        assert!(plane.iter().all(|k| k.abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_profile_curvature_of_brink_and_footslope() {
+        // Slope 2 easing downhill toward -x: fx = 2 + x, fxx = 1
+        let footslope = profile_curvature(&surface(|x, _| 2.0 * x + x * x / 2.0));
+        let expected = -4.0 / (4.0 * 5f64.powf(1.5));
+        assert!((footslope[3 * 7 + 3] - expected).abs() < 1e-9);
+        let brink = profile_curvature(&surface(|x, _| 2.0 * x - x * x / 2.0));
+        assert!((brink[3 * 7 + 3] + expected).abs() < 1e-9);
+
+        // Flat ground and grids without an interior are zero
+        assert!(profile_curvature(&surface(|_, _| 5.0))
+            .iter()
+            .all(|&k| k == 0.0));
+        let thin = TerrainData::new(vec![vec![0.0, 1.0, 4.0]; 2], None);
+        assert_eq!(profile_curvature(&thin), vec![0.0; 6]);
+    }
+
+    #[test]
+    fn test_terrain_indices_match_gdal() {
+        // GDAL's 3x3 definitions, at the center sample
+        let terrain = TerrainData::new(
+            vec![
+                vec![1.0, 2.0, 3.0],
+                vec![4.0, 9.0, 6.0],
+                vec![7.0, 8.0, 5.0],
+            ],
+            None,
+        );
+        let center = 4;
+        // Riley: sqrt(8² + 7² + 6² + 5² + 4² + 3² + 2² + 1²)
+        assert!((ruggedness_index(&terrain, 1)[center] - 204f64.sqrt()).abs() < 1e-12);
+        assert_eq!(topographic_position_index(&terrain, 1)[center], 9.0 - 4.5);
+        assert_eq!(roughness(&terrain, 1)[center], 8.0);
+
+        // Corners use the clipped window: 1 against 2, 4 and 9
+        assert_eq!(topographic_position_index(&terrain, 1)[0], 1.0 - 5.0);
+        assert_eq!(roughness(&terrain, 1)[0], 8.0);
+    }
+
+    #[test]
+    fn test_terrain_index_radius() {
+        // A single spike on flat ground
+        let mut points = vec![vec![0.0; 7]; 7];
+        points[3][3] = 10.0;
+        let terrain = TerrainData::new(points, None);
+
+        // Two samples away the spike is only in the larger window
+        let (near, wide) = (roughness(&terrain, 1), roughness(&terrain, 2));
+        assert_eq!(near[3 * 7 + 1], 0.0);
+        assert_eq!(wide[3 * 7 + 1], 10.0);
+        assert_eq!(topographic_position_index(&terrain, 3)[3 * 7 + 3], 10.0);
+        assert!(ruggedness_index(&terrain, 1)
+            .iter()
+            .all(|&v| v == 0.0 || v == 10.0 || v == 800f64.sqrt()));
+    }
+
+    #[test]
+    fn test_terrain_stats() {
+        let terrain = ridge_terrain();
+        let stats = TerrainStats::compute(&terrain);
+
+        assert_eq!(stats.hypsometric.len(), HYPSOMETRIC_STEPS + 1);
+        assert_eq!(stats.slope_histogram.len(), 18);
+        assert_eq!(stats.slope_histogram.iter().sum::<u32>(), 35);
+        assert!(stats.hypsometric_integral > 0.0 && stats.hypsometric_integral < 1.0);
+    }
+
+    #[test]
+    fn test_height_summary() {
+        let terrain = TerrainData::new(vec![vec![4.0, 1.0, 3.0], vec![2.0, 10.0, 0.0]], None);
+        let summary = HeightSummary::compute(&terrain);
+        assert_eq!(summary.size, (3, 2));
+        assert_eq!(summary.cells(), 2);
+        assert_eq!((summary.min, summary.max), (0.0, 10.0));
+        assert_eq!(summary.mean, 20.0 / 6.0);
+        assert_eq!(summary.median, 2.5);
+
+        let odd = TerrainData::new(vec![vec![5.0, 1.0, 3.0]], None);
+        assert_eq!(HeightSummary::compute(&odd).median, 3.0);
+        assert_eq!(HeightSummary::compute(&odd).cells(), 0);
+    }
+}
@@ -0,0 +1,286 @@
+//! User annotations drawn over the terrain.
+//!
+//! Polylines and polygons are drawn by right-clicking or dragging over the
+//! surface, and notes pin a line of text to a point. Annotations are kept
+//! as world-space (x, z) points, draped over the surface when drawn, and
+//! written as a GeoJSON FeatureCollection with the surface heights, which
+//! reads back in. Each terrain keeps its annotations in such a file next
+//! to it (`alps.tif` in `alps.tif.annotations.geojson`), so they last
+//! across sessions.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use glam::DVec2;
+use serde_json::Value;
+
+use super::TerrainData;
+
+/// Smallest distance between points of a freehand stroke, in cells.
+pub const FREEHAND_SPACING: f64 = 1.0;
+
+/// A shape or note placed on the terrain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    /// Open line through the points
+    Polyline(Vec<DVec2>),
+    /// Closed ring through the points
+    Polygon(Vec<DVec2>),
+    /// Text pinned to a point
+    Note { position: DVec2, text: String },
+}
+
+impl Annotation {
+    /// Points of the drawn outline; polygons return to their first point.
+    pub fn outline(&self) -> Vec<DVec2> {
+        match self {
+            Annotation::Polyline(points) => points.clone(),
+            Annotation::Polygon(points) => {
+                let mut ring = points.clone();
+                ring.extend(points.first());
+                ring
+            }
+            Annotation::Note { position, .. } => vec![*position],
+        }
+    }
+
+    /// Add `point` to a shape being drawn. With `spacing`, as for freehand
+    /// strokes, points nearer than that to the last are skipped.
+    pub fn extend(&mut self, point: DVec2, spacing: Option<f64>) {
+        let (Annotation::Polyline(points) | Annotation::Polygon(points)) = self else {
+            return;
+        };
+        let near = |last: &DVec2| spacing.is_some_and(|s| last.distance(point) < s);
+        if !points.last().is_some_and(near) {
+            points.push(point);
+        }
+    }
+
+    /// Whether the shape has enough points to keep: two for a polyline,
+    /// three for a polygon.
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Annotation::Polyline(points) => points.len() >= 2,
+            Annotation::Polygon(points) => points.len() >= 3,
+            Annotation::Note { .. } => true,
+        }
+    }
+}
+
+/// File keeping the annotations of the terrain at `terrain`.
+pub fn file_for(terrain: &Path) -> PathBuf {
+    let mut name = terrain.as_os_str().to_owned();
+    name.push(".annotations.geojson");
+    PathBuf::from(name)
+}
+
+/// Read the annotations saved in `file`, or none if it doesn't exist yet.
+pub fn load(file: &Path) -> Result<Vec<Annotation>> {
+    match std::fs::read_to_string(file) {
+        Ok(text) => from_geojson(&text),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Write `annotations` on `terrain` to `file`, or remove the file when
+/// there are none left.
+pub fn save(file: &Path, annotations: &[Annotation], terrain: &TerrainData) -> Result<()> {
+    if annotations.is_empty() {
+        return match std::fs::remove_file(file) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        };
+    }
+    std::fs::write(file, to_geojson(annotations, terrain))?;
+    Ok(())
+}
+
+/// GeoJSON FeatureCollection of `annotations`, with the height of the
+/// surface at each point as its third coordinate. Notes are points with a
+/// `text` property.
+pub fn to_geojson(annotations: &[Annotation], terrain: &TerrainData) -> String {
+    let position = |point: &DVec2| {
+        let height = terrain.height_at(point.x, point.y).unwrap_or(0.0);
+        format!("[{},{},{}]", point.x, point.y, height)
+    };
+    let positions = |points: &[DVec2]| points.iter().map(position).collect::<Vec<_>>().join(",");
+
+    let mut out = String::from("{\"type\":\"FeatureCollection\",\"features\":[");
+    for (i, annotation) in annotations.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let (geometry, properties) = match annotation {
+            Annotation::Polyline(points) => (
+                format!(
+                    "{{\"type\":\"LineString\",\"coordinates\":[{}]}}",
+                    positions(points)
+                ),
+                String::new(),
+            ),
+            Annotation::Polygon(_) => (
+                format!(
+                    "{{\"type\":\"Polygon\",\"coordinates\":[[{}]]}}",
+                    positions(&annotation.outline())
+                ),
+                String::new(),
+            ),
+            Annotation::Note { position: at, text } => (
+                format!("{{\"type\":\"Point\",\"coordinates\":{}}}", position(at)),
+                format!("\"text\":{}", json_string(text)),
+            ),
+        };
+        let _ = write!(
+            out,
+            "{{\"type\":\"Feature\",\"geometry\":{geometry},\"properties\":{{{properties}}}}}"
+        );
+    }
+    out.push_str("]}\n");
+    out
+}
+
+/// Annotations from a FeatureCollection written by [`to_geojson`]; heights
+/// are dropped, since annotations follow the surface.
+pub fn from_geojson(text: &str) -> Result<Vec<Annotation>> {
+    let value: Value = serde_json::from_str(text).context("Invalid JSON")?;
+    let features = value
+        .get("features")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("Expected a FeatureCollection"))?;
+    features
+        .iter()
+        .enumerate()
+        .map(|(i, feature)| feature_annotation(feature).with_context(|| format!("Feature {i}")))
+        .collect()
+}
+
+/// Annotation of one GeoJSON feature.
+fn feature_annotation(feature: &Value) -> Result<Annotation> {
+    let geometry = &feature["geometry"];
+    let point = |position: &Value| match position.as_array().map(Vec::as_slice) {
+        Some([x, z, ..]) => Some(DVec2::new(x.as_f64()?, z.as_f64()?)),
+        _ => None,
+    };
+    let points = |positions: &Value| {
+        positions
+            .as_array()
+            .and_then(|positions| positions.iter().map(point).collect::<Option<Vec<_>>>())
+            .ok_or_else(|| anyhow!("Expected an array of positions"))
+    };
+    let coordinates = &geometry["coordinates"];
+    Ok(match geometry["type"].as_str() {
+        Some("LineString") => Annotation::Polyline(points(coordinates)?),
+        Some("Polygon") => {
+            let mut ring = points(&coordinates[0])?;
+            // Rings repeat their first point at the end
+            if ring.len() > 1 && ring.first() == ring.last() {
+                ring.pop();
+            }
+            Annotation::Polygon(ring)
+        }
+        Some("Point") => Annotation::Note {
+            position: point(coordinates).ok_or_else(|| anyhow!("Expected a position"))?,
+            text: feature["properties"]["text"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        },
+        other => bail!("Unsupported geometry {:?}", other.unwrap_or("")),
+    })
+}
+
+/// `text` as a quoted JSON string.
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freehand_points_spaced() {
+        let mut line = Annotation::Polyline(Vec::new());
+        for x in [0.0, 0.4, 1.0, 1.5, 2.2] {
+            line.extend(DVec2::new(x, 0.0), Some(1.0));
+        }
+        assert_eq!(
+            line,
+            Annotation::Polyline(vec![DVec2::ZERO, DVec2::X, DVec2::new(2.2, 0.0)])
+        );
+
+        // Clicked points are all kept
+        let mut polygon = Annotation::Polygon(vec![DVec2::ZERO]);
+        assert!(!polygon.is_complete());
+        polygon.extend(DVec2::ZERO, None);
+        polygon.extend(DVec2::X, None);
+        assert!(polygon.is_complete());
+        assert_eq!(polygon.outline().len(), 4);
+    }
+
+    #[test]
+    fn test_geojson_features() {
+        let terrain = TerrainData::new(vec![vec![0.0, 2.0]; 2], None);
+        let annotations = [
+            Annotation::Polygon(vec![DVec2::ZERO, DVec2::X, DVec2::ONE]),
+            Annotation::Note {
+                position: DVec2::new(0.5, 0.0),
+                text: "Say \"hi\"".to_string(),
+            },
+        ];
+        let json = to_geojson(&annotations, &terrain);
+
+        // Closed ring, draped over the surface
+        assert!(json.contains(
+            "{\"type\":\"Polygon\",\"coordinates\":[[[0,0,0],[1,0,2],[1,1,2],[0,0,0]]]}"
+        ));
+        assert!(json.contains(
+            "{\"type\":\"Point\",\"coordinates\":[0.5,0,1]},\"properties\":{\"text\":\"Say \\\"hi\\\"\"}"
+        ));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+
+        // Reads back without the heights
+        assert_eq!(from_geojson(&json).unwrap(), annotations);
+    }
+
+    #[test]
+    fn test_annotations_round_trip_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = file_for(&dir.path().join("alps.tif"));
+        assert!(file.ends_with("alps.tif.annotations.geojson"));
+
+        // No file yet: no annotations
+        assert!(load(&file).unwrap().is_empty());
+
+        let terrain = TerrainData::new(vec![vec![0.0, 2.0]; 2], None);
+        let annotations = [
+            Annotation::Polyline(vec![DVec2::ZERO, DVec2::ONE]),
+            Annotation::Note {
+                position: DVec2::X,
+                text: "Cairn".to_string(),
+            },
+        ];
+        save(&file, &annotations, &terrain).unwrap();
+        assert_eq!(load(&file).unwrap(), annotations);
+
+        // Clearing them all removes the file
+        save(&file, &[], &terrain).unwrap();
+        assert!(!file.exists());
+        save(&file, &[], &terrain).unwrap();
+    }
+}
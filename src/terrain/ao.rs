@@ -0,0 +1,125 @@
+//! Baked terrain ambient occlusion.
+//!
+//! Horizon-based: from each grid vertex, rays are marched outward over the
+//! height field in several directions to find how high the horizon rises.
+//! The higher the horizon, the less of the sky the vertex sees. The result
+//! is baked once into per-vertex factors that darken the mesh colors, so it
+//! shows in every render mode and in exported meshes.
+
+use std::f64::consts::TAU;
+
+use super::TerrainData;
+
+/// Horizon directions sampled around each vertex.
+const AO_DIRECTIONS: usize = 16;
+/// Farthest horizon sample, in cells.
+const AO_RADIUS: f64 = 32.0;
+/// Growth of the step between horizon samples; nearby terrain is sampled
+/// densely and distant terrain sparsely.
+const AO_STEP_GROWTH: f64 = 1.4;
+
+/// Height at fractional grid position (`x`, `z`), bilinearly interpolated,
+/// or `None` outside the grid.
+pub(super) fn sample(terrain: &TerrainData, x: f64, z: f64) -> Option<f64> {
+    let max_x = (terrain.width - 1) as f64;
+    let max_z = (terrain.height - 1) as f64;
+    if x < 0.0 || z < 0.0 || x > max_x || z > max_z {
+        return None;
+    }
+    let (x0, z0) = (x.floor() as usize, z.floor() as usize);
+    let (x1, z1) = (
+        (x0 + 1).min(terrain.width - 1),
+        (z0 + 1).min(terrain.height - 1),
+    );
+    let (tx, tz) = (x - x0 as f64, z - z0 as f64);
+    let h = |x: usize, z: usize| terrain.at(x, z);
+    let near = h(x0, z0) + (h(x1, z0) - h(x0, z0)) * tx;
+    let far = h(x0, z1) + (h(x1, z1) - h(x0, z1)) * tx;
+    Some(near + (far - near) * tz)
+}
+
+/// Ambient occlusion factor per grid vertex, in row-major order matching
+/// [`TerrainMesh::vertices`](super::TerrainMesh::vertices).
+///
+/// 1.0 means an unobstructed sky and lower values are more occluded.
+/// Heights are multiplied by `height_scale`, so exaggerated terrain is
+/// darker in its valleys.
+pub fn bake(terrain: &TerrainData, height_scale: f64) -> Vec<f32> {
+    let mut factors = Vec::with_capacity(terrain.width * terrain.height);
+    if terrain.width == 0 || terrain.height == 0 {
+        return factors;
+    }
+
+    let directions: Vec<(f64, f64)> = (0..AO_DIRECTIONS)
+        .map(|i| {
+            let angle = i as f64 / AO_DIRECTIONS as f64 * TAU;
+            (angle.cos(), angle.sin())
+        })
+        .collect();
+    let mut distances = Vec::new();
+    let mut distance = 1.0;
+    while distance <= AO_RADIUS {
+        distances.push(distance);
+        distance *= AO_STEP_GROWTH;
+    }
+
+    for z in 0..terrain.height {
+        for x in 0..terrain.width {
+            let base = terrain.at(x, z);
+            let mut occlusion = 0.0;
+            for &(dx, dz) in &directions {
+                // Steepest rise toward the horizon in this direction
+                let mut max_slope: f64 = 0.0;
+                for &d in &distances {
+                    let Some(h) = sample(terrain, x as f64 + dx * d, z as f64 + dz * d) else {
+                        break;
+                    };
+                    let slope = (h - base) * height_scale / (d * terrain.cell_size);
+                    max_slope = max_slope.max(slope);
+                }
+                // Sine of the horizon elevation angle
+                occlusion += max_slope / max_slope.hypot(1.0);
+            }
+            factors.push((1.0 - occlusion / AO_DIRECTIONS as f64) as f32);
+        }
+    }
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_terrain_is_unoccluded() {
+        let terrain = TerrainData::new(vec![vec![3.0; 8]; 8], None);
+        let ao = bake(&terrain, 1.0);
+        assert_eq!(ao.len(), 64);
+        assert!(ao.iter().all(|&f| (f - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_valley_is_darker_than_peak() {
+        // A V-shaped valley along the middle column
+        let points: Vec<Vec<f64>> = (0..9)
+            .map(|_| (0..9).map(|x| (x as f64 - 4.0).abs() * 2.0).collect())
+            .collect();
+        let terrain = TerrainData::new(points, None);
+        let ao = bake(&terrain, 1.0);
+
+        let valley = ao[4 * 9 + 4];
+        let ridge = ao[4 * 9];
+        assert!(valley < ridge, "valley {} ridge {}", valley, ridge);
+        assert!(ao.iter().all(|&f| (0.0..=1.0).contains(&f)));
+        // Exaggerating the heights deepens the occlusion
+        assert!(bake(&terrain, 3.0)[4 * 9 + 4] < valley);
+    }
+
+    #[test]
+    fn test_sample_interpolates() {
+        let terrain = TerrainData::new(vec![vec![0.0, 2.0], vec![4.0, 6.0]], None);
+        assert_eq!(sample(&terrain, 0.5, 0.5), Some(3.0));
+        assert_eq!(sample(&terrain, 1.0, 1.0), Some(6.0));
+        assert_eq!(sample(&terrain, 1.5, 0.0), None);
+    }
+}
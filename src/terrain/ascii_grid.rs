@@ -0,0 +1,256 @@
+//! ESRI ASCII grid (.asc) parser.
+//!
+//! GIS tools export rasters as a short header of keywords and values,
+//! followed by the heights as whitespace-separated numbers, the northern
+//! row first:
+//!
+//! ```text
+//! ncols 3
+//! nrows 2
+//! xllcorner 500000
+//! yllcorner 4100000
+//! cellsize 30
+//! NODATA_value -9999
+//! 12.5 13.0 -9999
+//! 11.0 12.0 12.5
+//! ```
+//!
+//! The lower-left position may be given as the corner of the grid
+//! (`xllcorner`) or the center of its lower-left cell (`xllcenter`), and
+//! GDAL writes `dx` and `dy` instead of `cellsize` for rectangular cells.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use glam::DVec2;
+
+use super::loader::{LoadError, LoadOptions};
+use super::raster::{Georef, Raster};
+use super::TerrainData;
+
+/// Load terrain heights from an ESRI ASCII grid, averaged down and with
+/// nodata filled as set by `options`.
+///
+/// # Errors
+///
+/// Returns [`LoadError`] if the file cannot be read, its header is
+/// incomplete or its `ncols` and `nrows` aren't positive whole numbers, or
+/// a value cannot be parsed.
+pub fn load_ascii_grid<P: AsRef<Path>>(
+    path: P,
+    options: &LoadOptions,
+) -> Result<TerrainData, LoadError> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .map_err(|_| LoadError::FileNotFound(path.display().to_string()))?;
+    parse_ascii_grid(&contents, options)
+}
+
+/// Parse the contents of an ESRI ASCII grid.
+fn parse_ascii_grid(contents: &str, options: &LoadOptions) -> Result<TerrainData, LoadError> {
+    let mut header = HashMap::new();
+    let mut lines = contents.lines().enumerate().peekable();
+    // Header lines start with a keyword; the first number starts the data
+    while let Some((index, line)) = lines.peek() {
+        let mut tokens = line.split_whitespace();
+        let Some(key) = tokens.next() else {
+            lines.next();
+            continue;
+        };
+        if key.parse::<f64>().is_ok() {
+            break;
+        }
+        let value = tokens
+            .next()
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| LoadError::ParseError {
+                line: index + 1,
+                message: format!("invalid header value for {key}"),
+            })?;
+        header.insert(key.to_ascii_lowercase(), (index + 1, value));
+        lines.next();
+    }
+
+    let data_line = lines.peek().map_or(1, |(index, _)| index + 1);
+    let entry = |key: &str| {
+        header
+            .get(key)
+            .copied()
+            .ok_or_else(|| LoadError::ParseError {
+                line: data_line,
+                message: format!("missing {key} in header"),
+            })
+    };
+    let field = |key: &str| entry(key).map(|(_, value)| value);
+    // Cell counts, which a plain cast would truncate or clamp to zero
+    let count = |key: &str| {
+        let (line, value) = entry(key)?;
+        if value < 1.0 || value.fract() != 0.0 {
+            return Err(LoadError::ParseError {
+                line,
+                message: format!("{key} must be a positive whole number, not {value}"),
+            });
+        }
+        Ok((line, value as usize))
+    };
+    let (_, width) = count("ncols")?;
+    let (rows_line, height) = count("nrows")?;
+    let cells = width
+        .checked_mul(height)
+        .ok_or_else(|| LoadError::ParseError {
+            line: rows_line,
+            message: format!("{width}x{height} cells are too many to load"),
+        })?;
+    let spacing = match header.get("cellsize") {
+        Some(&(_, size)) => DVec2::splat(size),
+        None => DVec2::new(field("dx")?, field("dy")?),
+    };
+    // Center of the lower-left cell
+    let lower_left = DVec2::new(
+        match header.get("xllcenter") {
+            Some(&(_, x)) => x,
+            None => field("xllcorner")? + spacing.x / 2.0,
+        },
+        match header.get("yllcenter") {
+            Some(&(_, y)) => y,
+            None => field("yllcorner")? + spacing.y / 2.0,
+        },
+    );
+
+    let nodata = header.get("nodata_value").map(|&(_, value)| value);
+    // Each value takes two bytes with its separator, so the text bounds
+    // the capacity whatever counts the header claims
+    let mut values = Vec::with_capacity(cells.min(contents.len() / 2 + 1));
+    for (index, line) in lines {
+        for token in line.split_whitespace() {
+            let value: f64 = token.parse().map_err(|_| LoadError::ParseError {
+                line: index + 1,
+                message: format!("invalid height '{token}'"),
+            })?;
+            values.push(if Some(value) == nodata {
+                f64::NAN
+            } else {
+                value
+            });
+        }
+    }
+    if values.len() != cells {
+        return Err(LoadError::ParseError {
+            line: data_line,
+            message: format!(
+                "expected {cells} values for {width}x{height} cells, found {}",
+                values.len()
+            ),
+        });
+    }
+
+    let georef = Georef {
+        origin: lower_left + DVec2::Y * (height - 1) as f64 * spacing.y,
+        spacing,
+    };
+    Raster {
+        width,
+        height,
+        values,
+    }
+    .into_terrain(georef, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::sun::to_ascii_grid;
+
+    const GRID: &str = "\
+ncols 3
+nrows 2
+xllcorner 500000
+yllcorner 4100000
+cellsize 30
+NODATA_value -9999
+12.5 13.0 -9999
+11.0 12.0 12.5
+";
+
+    #[test]
+    fn test_parse_places_cells() {
+        let options = LoadOptions {
+            nodata_fill: Some(0.0),
+            ..LoadOptions::default()
+        };
+        let terrain = parse_ascii_grid(GRID, &options).unwrap();
+
+        // The southern row comes first, the nodata cell takes the fill
+        assert_eq!(terrain.heights(), [11.0, 12.0, 12.5, 12.5, 13.0, 0.0]);
+        assert_eq!(terrain.cell_size, 30.0);
+        assert_eq!(terrain.origin, DVec2::new(500_015.0, 4_100_015.0));
+    }
+
+    #[test]
+    fn test_round_trips_exported_grids() {
+        let mut terrain = TerrainData::new(vec![vec![0.0, 1.0], vec![2.0, 3.0]], None);
+        terrain.origin = DVec2::new(-10.0, 20.0);
+        terrain.cell_size = 2.0;
+        let parsed = parse_ascii_grid(
+            &to_ascii_grid(&terrain, terrain.heights()),
+            &LoadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(parsed.heights(), terrain.heights());
+        assert_eq!(parsed.origin, terrain.origin);
+        assert_eq!(parsed.cell_size, terrain.cell_size);
+    }
+
+    #[test]
+    fn test_centered_rectangular_cells() {
+        let grid = "ncols 2\nnrows 1\nxllcenter 5\nyllcenter 7\ndx 2\ndy 4\n1 2\n";
+        let terrain = parse_ascii_grid(grid, &LoadOptions::default()).unwrap();
+
+        assert_eq!(terrain.origin, DVec2::new(5.0, 7.0));
+        assert_eq!(terrain.cell_size, 3.0);
+    }
+
+    #[test]
+    fn test_invalid_grids() {
+        let options = LoadOptions::default();
+        let missing = "ncols 2\nnrows 1\nxllcorner 0\nyllcorner 0\n1 2\n";
+        assert!(matches!(
+            parse_ascii_grid(missing, &options),
+            Err(LoadError::ParseError { line: 5, .. })
+        ));
+        let short = GRID.replace("11.0 12.0 12.5", "11.0 12.0");
+        assert!(matches!(
+            parse_ascii_grid(&short, &options),
+            Err(LoadError::ParseError { line: 7, .. })
+        ));
+        let bad = GRID.replace("13.0", "x");
+        assert!(matches!(
+            parse_ascii_grid(&bad, &options),
+            Err(LoadError::ParseError { line: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_cell_counts() {
+        let options = LoadOptions::default();
+        for (ncols, nrows, line) in [
+            ("2.5", "2", 1),
+            ("-3", "2", 1),
+            ("0", "2", 1),
+            // Counts the data can't hold fail on it, without reserving them
+            ("1e10", "2", 7),
+            ("1e10", "1e10", 2),
+        ] {
+            let grid = GRID
+                .replace("ncols 3", &format!("ncols {ncols}"))
+                .replace("nrows 2", &format!("nrows {nrows}"));
+            let result = parse_ascii_grid(&grid, &options);
+            assert!(
+                matches!(result, Err(LoadError::ParseError { line: l, .. }) if l == line),
+                "ncols {ncols}, nrows {nrows}"
+            );
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Breaklines constraining the adaptive triangulation.
+//!
+//! Surveys mark roads, ridge crests, walls and banks as polylines where
+//! the slope changes abruptly. The TIN would otherwise cut across them
+//! wherever its error allows, rounding the break off; instead the samples
+//! along every breakline are inserted first, so its edges follow the line
+//! and it stays as crisp as in the full grid.
+//!
+//! Breaklines are read from GeoJSON (LineStrings, Polygon rings and their
+//! Multi- forms) or ASCII DXF (LINE, POLYLINE and LWPOLYLINE entities),
+//! as world-space (x, z) points like the annotations.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use glam::DVec2;
+use serde_json::Value;
+
+use super::TerrainData;
+
+/// A polyline of world-space (x, z) points.
+pub type Breakline = Vec<DVec2>;
+
+/// Breaklines from the GeoJSON or DXF file at `path`, by its extension.
+pub fn load(path: &Path) -> Result<Vec<Breakline>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("geojson" | "json") => from_geojson(&text),
+        Some("dxf") => from_dxf(&text),
+        _ => bail!(
+            "Unknown breakline format for {}, expected .geojson or .dxf",
+            path.display()
+        ),
+    }
+    .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Breaklines of the line and polygon geometries of a GeoJSON
+/// FeatureCollection, Feature or bare geometry. Points are skipped.
+pub fn from_geojson(text: &str) -> Result<Vec<Breakline>> {
+    let value: Value = serde_json::from_str(text).context("Invalid JSON")?;
+    let mut lines = Vec::new();
+    match value["type"].as_str() {
+        Some("FeatureCollection") => {
+            let features = value["features"]
+                .as_array()
+                .ok_or_else(|| anyhow!("Expected features"))?;
+            for (i, feature) in features.iter().enumerate() {
+                geometry_lines(&feature["geometry"], &mut lines)
+                    .with_context(|| format!("Feature {i}"))?;
+            }
+        }
+        Some("Feature") => geometry_lines(&value["geometry"], &mut lines)?,
+        _ => geometry_lines(&value, &mut lines)?,
+    }
+    Ok(lines)
+}
+
+/// Add the polylines of GeoJSON `geometry` to `lines`.
+fn geometry_lines(geometry: &Value, lines: &mut Vec<Breakline>) -> Result<()> {
+    let point = |position: &Value| match position.as_array().map(Vec::as_slice) {
+        Some([x, z, ..]) => Some(DVec2::new(x.as_f64()?, z.as_f64()?)),
+        _ => None,
+    };
+    let points = |positions: &Value| {
+        positions
+            .as_array()
+            .and_then(|positions| positions.iter().map(point).collect::<Option<Vec<_>>>())
+            .ok_or_else(|| anyhow!("Expected an array of positions"))
+    };
+    let each = |value: &Value| value.as_array().cloned().unwrap_or_default();
+    let coordinates = &geometry["coordinates"];
+    match geometry["type"].as_str() {
+        Some("LineString") => lines.push(points(coordinates)?),
+        Some("MultiLineString" | "Polygon") => {
+            for line in each(coordinates) {
+                lines.push(points(&line)?);
+            }
+        }
+        Some("MultiPolygon") => {
+            for polygon in each(coordinates) {
+                for ring in each(&polygon) {
+                    lines.push(points(&ring)?);
+                }
+            }
+        }
+        Some("GeometryCollection") => {
+            for geometry in each(&geometry["geometries"]) {
+                geometry_lines(&geometry, lines)?;
+            }
+        }
+        Some("Point" | "MultiPoint") | None => {}
+        Some(other) => bail!("Unsupported geometry {other:?}"),
+    }
+    Ok(())
+}
+
+/// Breaklines of the LINE, POLYLINE and LWPOLYLINE entities of an ASCII
+/// DXF drawing, with DXF x and y as world x and z. Closed polylines
+/// return to their first point.
+pub fn from_dxf(text: &str) -> Result<Vec<Breakline>> {
+    let mut lines = text.lines();
+    let mut pairs = Vec::new();
+    while let Some(code) = lines.next() {
+        let value = lines
+            .next()
+            .ok_or_else(|| anyhow!("Group code {} without a value", code.trim()))?;
+        let code: i32 = code
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid group code {:?}", code.trim()))?;
+        pairs.push((code, value.trim()));
+    }
+
+    // Split into entities at each group 0
+    let mut entities: Vec<(&str, Vec<(i32, &str)>)> = Vec::new();
+    for (code, value) in pairs {
+        match (code, entities.last_mut()) {
+            (0, _) => entities.push((value, Vec::new())),
+            (_, Some((_, groups))) => groups.push((code, value)),
+            (_, None) => {}
+        }
+    }
+
+    let number = |value: &str| -> Result<f64> {
+        value
+            .parse()
+            .with_context(|| format!("Invalid number {value:?}"))
+    };
+    let closed = |groups: &[(i32, &str)]| {
+        groups
+            .iter()
+            .any(|&(code, value)| code == 70 && value.parse::<i32>().is_ok_and(|f| f & 1 != 0))
+    };
+    let close = |mut line: Breakline, closed: bool| {
+        if closed && line.len() > 2 {
+            line.push(line[0]);
+        }
+        line
+    };
+    // Pairs of x (`x_code`) and y (`x_code + 10`) in order
+    let points = |groups: &[(i32, &str)], x_code: i32| -> Result<Breakline> {
+        let mut points = Vec::new();
+        let mut x = None;
+        for &(code, value) in groups {
+            if code == x_code {
+                x = Some(number(value)?);
+            } else if code == x_code + 10 {
+                let x = x.take().ok_or_else(|| anyhow!("Y coordinate without X"))?;
+                points.push(DVec2::new(x, number(value)?));
+            }
+        }
+        Ok(points)
+    };
+
+    let mut breaklines = Vec::new();
+    let mut polyline: Option<(Breakline, bool)> = None;
+    for (kind, groups) in &entities {
+        match *kind {
+            "LINE" => {
+                let start = points(groups, 10)?;
+                let end = points(groups, 11)?;
+                breaklines.push(start.into_iter().chain(end).collect());
+            }
+            "LWPOLYLINE" => breaklines.push(close(points(groups, 10)?, closed(groups))),
+            "POLYLINE" => polyline = Some((Vec::new(), closed(groups))),
+            "VERTEX" => {
+                if let Some((line, _)) = &mut polyline {
+                    line.extend(points(groups, 10)?);
+                }
+            }
+            "SEQEND" => {
+                if let Some((line, closed)) = polyline.take() {
+                    breaklines.push(close(line, closed));
+                }
+            }
+            _ => {}
+        }
+    }
+    breaklines.retain(|line| line.len() >= 2);
+    Ok(breaklines)
+}
+
+/// Row-major indices of the grid samples along `breaklines`, each line a
+/// path of samples stepping one row or column at a time as close to it as
+/// the grid allows. Parts outside the grid are left out.
+pub fn samples(terrain: &TerrainData, breaklines: &[Breakline]) -> Vec<u32> {
+    let (width, height) = (terrain.width as i64, terrain.height as i64);
+    let mut samples = Vec::new();
+    let mut push = |(x, z): (i64, i64)| {
+        if (0..width).contains(&x) && (0..height).contains(&z) {
+            samples.push((z * width + x) as u32);
+        }
+    };
+    let grid = |p: DVec2| (p - terrain.origin) / terrain.cell_size;
+    for line in breaklines {
+        for segment in line.windows(2) {
+            let (from, to) = (grid(segment[0]), grid(segment[1]));
+            let direction = to - from;
+            let offset = |(x, z): (i64, i64)| {
+                let p = DVec2::new(x as f64, z as f64) - from;
+                (direction.perp_dot(p) / direction.length().max(f64::EPSILON)).abs()
+            };
+            let (mut at, end) = (
+                (from.x.round() as i64, from.y.round() as i64),
+                (to.x.round() as i64, to.y.round() as i64),
+            );
+            push(at);
+            // Each step toward the end, along whichever axis stays nearer
+            while at != end {
+                let along_x = (at.0 + (end.0 - at.0).signum(), at.1);
+                let along_z = (at.0, at.1 + (end.1 - at.1).signum());
+                at = if at.0 == end.0 {
+                    along_z
+                } else if at.1 == end.1 || offset(along_x) <= offset(along_z) {
+                    along_x
+                } else {
+                    along_z
+                };
+                push(at);
+            }
+        }
+    }
+    samples.sort_unstable();
+    samples.dedup();
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::contours::{self, Contour};
+
+    #[test]
+    fn test_geojson_lines_and_rings() {
+        let text = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","geometry":{"type":"LineString","coordinates":[[0,0,5],[2,1]]}},
+            {"type":"Feature","geometry":{"type":"Polygon","coordinates":[[[0,0],[1,0],[1,1],[0,0]]]}},
+            {"type":"Feature","geometry":{"type":"Point","coordinates":[3,3]}}
+        ]}"#;
+        let lines = from_geojson(text).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], vec![DVec2::ZERO, DVec2::new(2.0, 1.0)]);
+        assert_eq!(lines[1].len(), 4);
+        assert!(from_geojson("{\"type\":\"LineString\",\"coordinates\":[[0]]}").is_err());
+    }
+
+    #[test]
+    fn test_dxf_reads_contour_export() {
+        let contours = [Contour {
+            level: 10.0,
+            points: vec![
+                DVec2::new(1.0, 2.0),
+                DVec2::new(3.0, 4.0),
+                DVec2::new(5.0, 2.0),
+            ],
+            closed: true,
+        }];
+        let text = contours::to_dxf(&contours);
+        let lines = from_dxf(&text).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].len(), 4);
+        assert_eq!(lines[0][1], DVec2::new(3.0, 4.0));
+        assert_eq!(lines[0][3], lines[0][0]);
+
+        let line =
+            "0\nSECTION\n2\nENTITIES\n0\nLINE\n8\n0\n10\n0\n20\n0\n11\n4\n21\n2\n0\nENDSEC\n";
+        assert_eq!(
+            from_dxf(line).unwrap(),
+            vec![vec![DVec2::ZERO, DVec2::new(4.0, 2.0)]]
+        );
+    }
+
+    #[test]
+    fn test_samples_step_along_the_line() {
+        let terrain = TerrainData::new(vec![vec![0.0; 10]; 10], None);
+        let line = vec![
+            DVec2::new(1.0, 1.0),
+            DVec2::new(7.0, 4.0),
+            DVec2::new(20.0, 4.0),
+        ];
+        let samples = samples(&terrain, &[line]);
+        // From (1, 1) over 6 columns and 3 rows, then to the grid's edge
+        assert_eq!(samples.len(), 10 + 2);
+        assert!(samples.contains(&11) && samples.contains(&47) && samples.contains(&49));
+        for &s in &samples {
+            let (x, z) = ((s % 10) as f64, (s / 10) as f64);
+            let offset = if x <= 7.0 {
+                (x - 1.0) * 0.5 + 1.0 - z
+            } else {
+                4.0 - z
+            };
+            assert!(offset.abs() <= 1.0, "{s}");
+        }
+    }
+}
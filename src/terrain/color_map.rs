@@ -0,0 +1,227 @@
+//! Georeferenced images of the terrain's colors.
+//!
+//! Each sample's color, as the viewer shows it after the color scheme,
+//! normalization and overlays, becomes one pixel of a north-up image placed
+//! on the ground, so the exact visualization can be laid under other 2D
+//! layers in GIS software. GeoTIFFs carry their placement in pixel scale
+//! and tiepoint tags; PNGs get an ESRI world file (.pgw) next to them.
+//!
+//! Pixels are centered on the samples, so the image covers half a cell
+//! beyond the outermost samples. Grid rows run north, so rows are flipped
+//! to put the northernmost first. Geographic GeoTIFFs come out in the
+//! approximate meters they were projected to on load.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use glam::DVec2;
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::tags::Tag;
+
+use super::colors::rgb_to_hex;
+use super::units::Units;
+use super::TerrainData;
+
+/// `GTModelTypeGeoKey` value for projected coordinates.
+const MODEL_TYPE_PROJECTED: u16 = 1;
+
+/// `GTRasterTypeGeoKey` value for tiepoints at pixel corners.
+const RASTER_PIXEL_IS_AREA: u16 = 1;
+
+/// Image formats of color map exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMapFormat {
+    /// PNG with a world file (.pgw)
+    Png,
+    /// GeoTIFF (.tif, .tiff)
+    GeoTiff,
+}
+
+impl ColorMapFormat {
+    /// Pick a format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(Self::Png),
+            "tif" | "tiff" => Some(Self::GeoTiff),
+            _ => None,
+        }
+    }
+}
+
+/// North-up RGB image of a terrain's sample colors, placed on the ground.
+#[derive(Debug, Clone)]
+pub struct ColorMap {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major sRGB pixels, northernmost row first
+    pub pixels: Vec<[u8; 3]>,
+    /// Easting and northing of the top-left pixel's outer corner
+    pub corner: DVec2,
+    /// Ground size of a pixel, the terrain's cell size
+    pub pixel_size: f64,
+    /// Unit of the coordinates
+    pub units: Units,
+}
+
+impl ColorMap {
+    /// Image of `colors`, one sRGB color per sample of `terrain` in
+    /// row-major grid order, such as the vertex colors of a mesh built
+    /// without linear colors. Missing colors are black.
+    pub fn new(terrain: &TerrainData, colors: &[[f32; 3]]) -> Self {
+        let (width, height) = (terrain.width, terrain.height);
+        let pixels = (0..height)
+            .rev()
+            .flat_map(|z| (0..width).map(move |x| z * width + x))
+            .map(|i| {
+                let [_, r, g, b] =
+                    rgb_to_hex(colors.get(i).copied().unwrap_or_default()).to_be_bytes();
+                [r, g, b]
+            })
+            .collect();
+        let half = terrain.cell_size / 2.0;
+        let north = terrain.origin.y + height.saturating_sub(1) as f64 * terrain.cell_size;
+        Self {
+            width,
+            height,
+            pixels,
+            corner: DVec2::new(terrain.origin.x - half, north + half),
+            pixel_size: terrain.cell_size,
+            units: terrain.units,
+        }
+    }
+
+    /// Encode the image as a PNG file, placed by [`Self::world_file`].
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.as_flattened())?;
+        writer.finish()?;
+        Ok(out)
+    }
+
+    /// ESRI world file placing the image: pixel size, rotation, and the
+    /// center of the top-left pixel.
+    pub fn world_file(&self) -> String {
+        let size = self.pixel_size;
+        let center = self.corner + DVec2::new(size, -size) / 2.0;
+        format!("{size}\n0\n0\n{}\n{}\n{}\n", -size, center.x, center.y)
+    }
+
+    /// Encode the image as an RGB GeoTIFF, with its pixel size, the ground
+    /// position of its top-left corner and, when known, its linear units.
+    pub fn to_geotiff(&self) -> Result<Vec<u8>, tiff::TiffError> {
+        let mut keys = vec![
+            [1024, 0, 1, MODEL_TYPE_PROJECTED],
+            [1025, 0, 1, RASTER_PIXEL_IS_AREA],
+        ];
+        if let Some(code) = self.units.epsg() {
+            keys.push([3076, 0, 1, code]);
+        }
+        // A header of four shorts, then four per key: id, location, count, value
+        let mut directory = vec![1, 1, 0, keys.len() as u16];
+        directory.extend(keys.into_iter().flatten());
+
+        let mut out = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut out)?;
+        let mut image =
+            encoder.new_image::<colortype::RGB8>(self.width as u32, self.height as u32)?;
+        let tags = image.encoder();
+        tags.write_tag(
+            Tag::ModelPixelScaleTag,
+            &[self.pixel_size, self.pixel_size, 0.0][..],
+        )?;
+        tags.write_tag(
+            Tag::ModelTiepointTag,
+            &[0.0, 0.0, 0.0, self.corner.x, self.corner.y, 0.0][..],
+        )?;
+        tags.write_tag(Tag::GeoKeyDirectoryTag, &directory[..])?;
+        image.write_data(self.pixels.as_flattened())?;
+        Ok(out.into_inner())
+    }
+}
+
+/// Write `map` to `path` as a GeoTIFF or as a PNG with a world file
+/// beside it, by the extension of `path`.
+pub fn write(map: &ColorMap, path: &Path) -> Result<()> {
+    let format = ColorMapFormat::from_path(path).ok_or_else(|| {
+        anyhow!(
+            "Unknown color map format for {}: expected .png, .tif or .tiff",
+            path.display()
+        )
+    })?;
+    let write = |path: &Path, contents: &[u8]| {
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    };
+    match format {
+        ColorMapFormat::Png => {
+            write(path, &map.to_png()?)?;
+            write(&path.with_extension("pgw"), map.world_file().as_bytes())
+        }
+        ColorMapFormat::GeoTiff => write(path, &map.to_geotiff()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::decoder::{Decoder, DecodingResult};
+
+    /// A 3x2 grid of 10 m cells, with sample `[0][0]` at (100, 200).
+    fn terrain() -> TerrainData {
+        let mut terrain = TerrainData::new(vec![vec![0.0; 3]; 2], None);
+        terrain.origin = DVec2::new(100.0, 200.0);
+        terrain.cell_size = 10.0;
+        terrain.units = Units::Meters;
+        terrain
+    }
+
+    fn colors() -> Vec<[f32; 3]> {
+        (0..6).map(|i| [i as f32 / 5.0, 0.0, 1.0]).collect()
+    }
+
+    #[test]
+    fn test_rows_are_north_up() {
+        let map = ColorMap::new(&terrain(), &colors());
+        assert_eq!((map.width, map.height), (3, 2));
+        // The last grid row is the northernmost
+        assert_eq!(map.pixels[0], [153, 0, 255]);
+        assert_eq!(map.pixels[3], [0, 0, 255]);
+        assert_eq!(map.corner, DVec2::new(95.0, 215.0));
+    }
+
+    #[test]
+    fn test_world_file_centers_first_pixel() {
+        let map = ColorMap::new(&terrain(), &colors());
+        assert_eq!(map.world_file(), "10\n0\n0\n-10\n100\n210\n");
+    }
+
+    #[test]
+    fn test_geotiff_carries_placement() {
+        let map = ColorMap::new(&terrain(), &colors());
+        let mut decoder = Decoder::new(Cursor::new(map.to_geotiff().unwrap())).unwrap();
+        assert_eq!(decoder.dimensions().unwrap(), (3, 2));
+        let mut tag = |tag| decoder.get_tag_f64_vec(tag).unwrap();
+        assert_eq!(tag(Tag::ModelPixelScaleTag), [10.0, 10.0, 0.0]);
+        assert_eq!(tag(Tag::ModelTiepointTag)[3..5], [95.0, 215.0]);
+        let keys = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag).unwrap();
+        assert!(keys.chunks_exact(4).any(|key| key == [3076, 0, 1, 9001]));
+        match decoder.read_image().unwrap() {
+            DecodingResult::U8(data) => assert_eq!(data, map.pixels.as_flattened()),
+            _ => panic!("expected 8-bit samples"),
+        }
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        let format = |name: &str| ColorMapFormat::from_path(Path::new(name));
+        assert_eq!(format("map.PNG"), Some(ColorMapFormat::Png));
+        assert_eq!(format("map.tif"), Some(ColorMapFormat::GeoTiff));
+        assert_eq!(format("map.jpg"), None);
+    }
+}
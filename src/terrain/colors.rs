@@ -1,9 +1,17 @@
 //! Color schemes for terrain visualization.
 //!
-//! Provides multiple color mapping functions for height-based coloring.
+//! Provides multiple color mapping functions for height-based coloring,
+//! and a diverging palette for signed quantities such as curvature.
+//! [`ColorMode`] picks the quantity: height or one of the analysis layers.
+//! [`SlopeBands`] colors slope angles in discrete bands, as backcountry
+//! maps mark avalanche terrain.
+//!
+//! Scheme and gradient colors are defined in sRGB, like reference palettes
+//! and color pickers. Shading and the sRGB surfaces work in linear space, so
+//! colors go through [`srgb_to_linear`] before they reach the GPU.
 
 /// Available color schemes for terrain rendering.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
 pub enum ColorScheme {
     /// Natural terrain colors: blue (water) → green → brown → white (snow)
     #[default]
@@ -12,10 +20,86 @@ pub enum ColorScheme {
     Heatmap,
     /// Single color with intensity based on height
     Monochrome,
+    /// Glowing neon on black: near-black → violet → magenta → cyan → white
+    Neon,
     /// User-defined gradient with low, mid, high colors
     Custom,
 }
 
+/// Quantity mapped to vertex colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Height through the color scheme
+    #[default]
+    Height,
+    /// Plan curvature through the diverging palette
+    PlanCurvature,
+    /// Profile curvature through the diverging palette
+    ProfileCurvature,
+    /// Terrain Ruggedness Index through the color scheme
+    Ruggedness,
+    /// Topographic Position Index through the diverging palette
+    Position,
+    /// Roughness (height range of the window) through the color scheme
+    Roughness,
+    /// Slope angle through the slope bands, over neutral gray
+    SlopeBands,
+    /// Value of the terrain channel at this index through the color scheme
+    Channel(usize),
+}
+
+/// Color of slopes outside every [`SlopeBands`] band, light enough for the
+/// hillshade to read through.
+const SLOPE_BACKGROUND: [f32; 3] = [0.9, 0.9, 0.9];
+
+/// One slope-angle band of [`SlopeBands`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlopeBand {
+    /// Lowest slope in degrees, inclusive
+    pub min: f32,
+    /// Highest slope in degrees, exclusive
+    pub max: f32,
+    /// Color of slopes in the band
+    pub color: [f32; 3],
+}
+
+/// Slope-angle bands for hazard maps.
+///
+/// The defaults follow the usual avalanche terrain shading: yellow from
+/// 27°, where slab avalanches become possible, through orange and red over
+/// the most frequent 30-45° starting zones, to purple on slopes too steep
+/// to hold much snow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlopeBands {
+    pub bands: [SlopeBand; 4],
+}
+
+impl Default for SlopeBands {
+    fn default() -> Self {
+        let band = |min, max, color| SlopeBand { min, max, color };
+        Self {
+            bands: [
+                band(27.0, 30.0, [1.0, 0.9, 0.0]),
+                band(30.0, 35.0, [1.0, 0.55, 0.0]),
+                band(35.0, 45.0, [0.9, 0.1, 0.1]),
+                band(45.0, 90.0, [0.55, 0.2, 0.7]),
+            ],
+        }
+    }
+}
+
+impl SlopeBands {
+    /// Color of a slope of `degrees`: the first band containing it, or
+    /// light gray outside all of them.
+    pub fn color(&self, degrees: f64) -> [f32; 3] {
+        let degrees = degrees as f32;
+        self.bands
+            .iter()
+            .find(|band| (band.min..band.max).contains(&degrees))
+            .map_or(SLOPE_BACKGROUND, |band| band.color)
+    }
+}
+
 /// Custom gradient configuration with three color stops.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct GradientConfig {
@@ -62,6 +146,39 @@ fn lerp_color(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
     ]
 }
 
+/// Unpack a 0xRRGGBB color, as given in `.fdf` files, into sRGB channels.
+pub fn hex_to_rgb(color: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((color >> shift) & 0xFF) as f32 / 255.0)
+}
+
+/// Pack sRGB channels into a 0xRRGGBB color, the inverse of [`hex_to_rgb`].
+pub fn rgb_to_hex(rgb: [f32; 3]) -> u32 {
+    rgb.iter().fold(0, |color, &c| {
+        (color << 8) | (c.clamp(0.0, 1.0) * 255.0).round() as u32
+    })
+}
+
+/// sRGB to linear transfer function for one channel.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Linear to sRGB transfer function for one channel, as applied by an sRGB
+/// surface.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
 /// Convert normalized height (0.0-1.0) to RGB color based on scheme.
 pub fn height_to_color(t: f32, scheme: ColorScheme) -> [f32; 3] {
     let t = t.clamp(0.0, 1.0);
@@ -69,6 +186,7 @@ pub fn height_to_color(t: f32, scheme: ColorScheme) -> [f32; 3] {
         ColorScheme::Terrain => terrain_color(t),
         ColorScheme::Heatmap => heatmap_color(t),
         ColorScheme::Monochrome => monochrome_color(t),
+        ColorScheme::Neon => neon_color(t),
         ColorScheme::Custom => GradientConfig::default().interpolate(t),
     }
 }
@@ -78,6 +196,23 @@ pub fn height_to_color_custom(t: f32, gradient: &GradientConfig) -> [f32; 3] {
     gradient.interpolate(t.clamp(0.0, 1.0))
 }
 
+/// Diverging palette for signed values scaled to -1.0..1.0: blue for
+/// negative, light gray at zero, red for positive.
+///
+/// Moreland's cool-warm map, whose ends have equal lightness so neither
+/// sign stands out more than the other.
+pub fn diverging_color(t: f32) -> [f32; 3] {
+    const NEGATIVE: [f32; 3] = [0.23, 0.299, 0.754];
+    const ZERO: [f32; 3] = [0.865, 0.865, 0.865];
+    const POSITIVE: [f32; 3] = [0.706, 0.016, 0.15];
+    let t = t.clamp(-1.0, 1.0);
+    if t < 0.0 {
+        lerp_color(ZERO, NEGATIVE, -t)
+    } else {
+        lerp_color(ZERO, POSITIVE, t)
+    }
+}
+
 /// Natural terrain gradient: blue → cyan → green → brown → white
 fn terrain_color(t: f32) -> [f32; 3] {
     if t < 0.3 {
@@ -126,10 +261,58 @@ fn monochrome_color(t: f32) -> [f32; 3] {
     [v, v, v]
 }
 
+/// Neon glow: near-black → violet → magenta → cyan → white, dark over
+/// most of the range so only the heights light up
+fn neon_color(t: f32) -> [f32; 3] {
+    if t < 0.4 {
+        // Near-black to deep violet
+        let s = t / 0.4;
+        [0.02 + s * 0.28, 0.0, 0.05 + s * 0.45]
+    } else if t < 0.65 {
+        // Violet to magenta
+        let s = (t - 0.4) / 0.25;
+        [0.3 + s * 0.7, 0.0, 0.5 + s * 0.3]
+    } else if t < 0.9 {
+        // Magenta to cyan
+        let s = (t - 0.65) / 0.25;
+        [1.0 - s, s * 0.9, 0.8 + s * 0.2]
+    } else {
+        // Cyan to white
+        let s = (t - 0.9) / 0.1;
+        [s, 0.9 + s * 0.1, 1.0]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // ==================== Transfer Function Tests ====================
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for i in 0..=20 {
+            let c = i as f32 / 20.0;
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        for color in [0x000000, 0xFF0000, 0x12AB7F, 0xFFFFFF] {
+            assert_eq!(rgb_to_hex(hex_to_rgb(color)), color);
+        }
+        assert_eq!(rgb_to_hex([2.0, -1.0, 0.5]), 0xFF0080);
+    }
+
+    #[test]
+    fn test_srgb_to_linear_darkens_midtones() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert_eq!(srgb_to_linear(1.0), 1.0);
+        // sRGB mid-grey is ~21% linear
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+
     // ==================== Terrain Color Tests ====================
 
     #[test]
@@ -208,6 +391,50 @@ mod tests {
         assert!(diff_rb < 0.01, "Monochrome R and B should be equal");
     }
 
+    // ==================== Neon Color Tests ====================
+
+    #[test]
+    fn test_neon_is_dark_low_and_bright_high() {
+        let brightness = |t| height_to_color(t, ColorScheme::Neon).iter().sum::<f32>() / 3.0;
+        assert!(brightness(0.0) < 0.05, "Low neon should be near black");
+        assert!(brightness(1.0) > 0.99, "High neon should be white");
+        // Continuous across the band edges
+        for edge in [0.4, 0.65, 0.9] {
+            let (below, above) = (
+                height_to_color(edge - 1e-4, ColorScheme::Neon),
+                height_to_color(edge + 1e-4, ColorScheme::Neon),
+            );
+            for i in 0..3 {
+                assert!((below[i] - above[i]).abs() < 0.01, "Jump at {}", edge);
+            }
+        }
+    }
+
+    // ==================== Diverging Color Tests ====================
+
+    #[test]
+    fn test_diverging_ends_and_center() {
+        assert_eq!(diverging_color(0.0), [0.865; 3]);
+        let (negative, positive) = (diverging_color(-1.0), diverging_color(1.0));
+        assert!(negative[2] > negative[0], "Negative end should be blue");
+        assert!(positive[0] > positive[2], "Positive end should be red");
+        assert_eq!(diverging_color(-3.0), negative);
+        assert_eq!(diverging_color(3.0), positive);
+    }
+
+    // ==================== Slope Band Tests ====================
+
+    #[test]
+    fn test_slope_bands() {
+        let bands = SlopeBands::default();
+        assert_eq!(bands.color(10.0), SLOPE_BACKGROUND);
+        assert_eq!(bands.color(27.0), bands.bands[0].color);
+        // Band ends are exclusive
+        assert_eq!(bands.color(35.0), bands.bands[2].color);
+        assert_eq!(bands.color(44.9), bands.bands[2].color);
+        assert_eq!(bands.color(60.0), bands.bands[3].color);
+    }
+
     // ==================== Edge Cases ====================
 
     #[test]
@@ -0,0 +1,371 @@
+//! Contour line tracing and export.
+//!
+//! Traces iso-height polylines with marching squares and writes them as
+//! GeoJSON, DXF or SVG. Unlike the shader contour overlay, the result is
+//! real geometry that can be loaded into GIS and CAD tools.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use glam::DVec2;
+
+use super::TerrainData;
+
+/// A contour polyline at one elevation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    /// Elevation of the line
+    pub level: f64,
+    /// World-space (x, z) positions along the line
+    pub points: Vec<DVec2>,
+    /// Whether the line is a loop; the first point is not repeated
+    pub closed: bool,
+}
+
+/// Output file formats for contours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContourFormat {
+    GeoJson,
+    Dxf,
+    Svg,
+}
+
+impl ContourFormat {
+    /// Pick a format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "geojson" | "json" => Some(Self::GeoJson),
+            "dxf" => Some(Self::Dxf),
+            "svg" => Some(Self::Svg),
+            _ => None,
+        }
+    }
+
+    /// Serialize contours in this format.
+    pub fn write(self, contours: &[Contour]) -> String {
+        match self {
+            Self::GeoJson => to_geojson(contours),
+            Self::Dxf => to_dxf(contours),
+            Self::Svg => to_svg(contours),
+        }
+    }
+}
+
+/// Identifier of a grid edge: horizontal edges from vertex `i` to `i + 1`
+/// are `2 * i`, vertical edges from `i` to `i + width` are `2 * i + 1`.
+type EdgeId = usize;
+
+/// Trace contours every `interval` height units.
+///
+/// Levels are the multiples of `interval` within the terrain's height
+/// range. Returns no contours for a non-positive interval.
+pub fn trace(terrain: &TerrainData, interval: f64) -> Vec<Contour> {
+    if interval.is_nan() || interval <= 0.0 || terrain.width < 2 || terrain.height < 2 {
+        return Vec::new();
+    }
+
+    let (min_h, max_h) = terrain.height_bounds();
+    let mut contours = Vec::new();
+    // Multiply rather than accumulate, so levels stay exact multiples
+    let mut step = (min_h / interval).ceil();
+    while step * interval <= max_h {
+        // Adding zero turns -0.0 into 0.0
+        contours.extend(trace_level(terrain, step * interval + 0.0));
+        step += 1.0;
+    }
+    contours
+}
+
+/// Trace all contours at a single level.
+fn trace_level(terrain: &TerrainData, level: f64) -> Vec<Contour> {
+    let width = terrain.width;
+    let h = |x: usize, z: usize| terrain.at(x, z);
+
+    // Crossing position of each edge the level passes through
+    let mut crossings: HashMap<EdgeId, DVec2> = HashMap::new();
+    let mut crossing = |edge: EdgeId| {
+        *crossings.entry(edge).or_insert_with(|| {
+            let i = edge / 2;
+            let (x, z) = (i % width, i / width);
+            let (x1, z1) = if edge.is_multiple_of(2) {
+                (x + 1, z)
+            } else {
+                (x, z + 1)
+            };
+            let (h0, h1) = (h(x, z), h(x1, z1));
+            let t = if h0 == h1 {
+                0.5
+            } else {
+                (level - h0) / (h1 - h0)
+            };
+            let grid = DVec2::new(x as f64, z as f64).lerp(DVec2::new(x1 as f64, z1 as f64), t);
+            terrain.origin + grid * terrain.cell_size
+        })
+    };
+
+    // Marching squares, linking segments through the edges they share
+    let mut links: HashMap<EdgeId, Vec<EdgeId>> = HashMap::new();
+    for z in 0..terrain.height - 1 {
+        for x in 0..width - 1 {
+            let corners = [h(x, z), h(x + 1, z), h(x + 1, z + 1), h(x, z + 1)];
+            let above = corners.map(|c| c >= level);
+            let i = z * width + x;
+            // Cell edges in corner order: top, right, bottom, left
+            let edges = [2 * i, 2 * (i + 1) + 1, 2 * (i + width), 2 * i + 1];
+            let crossed: Vec<usize> = (0..4).filter(|&e| above[e] != above[(e + 1) % 4]).collect();
+
+            let segments: Vec<(usize, usize)> = match crossed.len() {
+                2 => vec![(crossed[0], crossed[1])],
+                4 => {
+                    // Saddle: the center decides which corners connect
+                    let center_above = corners.iter().sum::<f64>() / 4.0 >= level;
+                    if center_above == above[0] {
+                        // Corners 0 and 2 connect, cutting off 1 and 3
+                        vec![(0, 1), (2, 3)]
+                    } else {
+                        vec![(3, 0), (1, 2)]
+                    }
+                }
+                _ => Vec::new(),
+            };
+            for (a, b) in segments {
+                let (a, b) = (edges[a], edges[b]);
+                crossing(a);
+                crossing(b);
+                links.entry(a).or_default().push(b);
+                links.entry(b).or_default().push(a);
+            }
+        }
+    }
+
+    // Walk open lines from their ends first, then the remaining loops
+    let mut ends: Vec<EdgeId> = links
+        .iter()
+        .filter(|(_, next)| next.len() == 1)
+        .map(|(&edge, _)| edge)
+        .collect();
+    ends.sort_unstable();
+    let mut rest: Vec<EdgeId> = links.keys().copied().collect();
+    rest.sort_unstable();
+
+    let mut contours = Vec::new();
+    for start in ends.into_iter().chain(rest) {
+        let Some(first) = links.get(&start).and_then(|next| next.first().copied()) else {
+            continue;
+        };
+        let mut edges = vec![start];
+        let mut current = first;
+        unlink(&mut links, start, first);
+        while current != start {
+            edges.push(current);
+            let Some(next) = links.get(&current).and_then(|n| n.first().copied()) else {
+                break;
+            };
+            unlink(&mut links, current, next);
+            current = next;
+        }
+        // Lines through a vertex at exactly `level` cross it on several edges
+        let mut points: Vec<DVec2> = edges.iter().map(|e| crossings[e]).collect();
+        points.dedup();
+        contours.push(Contour {
+            level,
+            points,
+            closed: current == start,
+        });
+    }
+    contours
+}
+
+/// Remove the link between two edges.
+fn unlink(links: &mut HashMap<EdgeId, Vec<EdgeId>>, a: EdgeId, b: EdgeId) {
+    for (from, to) in [(a, b), (b, a)] {
+        if let Some(next) = links.get_mut(&from) {
+            if let Some(pos) = next.iter().position(|&e| e == to) {
+                next.swap_remove(pos);
+            }
+            if next.is_empty() {
+                links.remove(&from);
+            }
+        }
+    }
+}
+
+/// Points of a contour, repeating the first point for closed lines.
+fn path(contour: &Contour) -> impl Iterator<Item = DVec2> + '_ {
+    let close = contour.closed.then(|| contour.points[0]);
+    contour.points.iter().copied().chain(close)
+}
+
+/// GeoJSON FeatureCollection of LineStrings with `[x, z]` coordinates and
+/// an `elevation` property.
+pub fn to_geojson(contours: &[Contour]) -> String {
+    let mut out = String::from("{\"type\":\"FeatureCollection\",\"features\":[");
+    for (n, contour) in contours.iter().enumerate() {
+        if n > 0 {
+            out.push(',');
+        }
+        let _ = write!(
+            out,
+            "{{\"type\":\"Feature\",\"properties\":{{\"elevation\":{}}},\
+             \"geometry\":{{\"type\":\"LineString\",\"coordinates\":[",
+            contour.level
+        );
+        for (i, p) in path(contour).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "[{},{}]", p.x, p.y);
+        }
+        out.push_str("]}}");
+    }
+    out.push_str("]}\n");
+    out
+}
+
+/// ASCII DXF (R12) with one POLYLINE per contour on layer `CONTOUR`, at
+/// the contour's elevation.
+pub fn to_dxf(contours: &[Contour]) -> String {
+    let mut out = String::from("0\nSECTION\n2\nENTITIES\n");
+    for contour in contours {
+        let flags = if contour.closed { 1 } else { 0 };
+        let _ = write!(
+            out,
+            "0\nPOLYLINE\n8\nCONTOUR\n66\n1\n10\n0\n20\n0\n30\n{}\n70\n{}\n",
+            contour.level, flags
+        );
+        for p in &contour.points {
+            let _ = write!(
+                out,
+                "0\nVERTEX\n8\nCONTOUR\n10\n{}\n20\n{}\n30\n{}\n",
+                p.x, p.y, contour.level
+            );
+        }
+        out.push_str("0\nSEQEND\n");
+    }
+    out.push_str("0\nENDSEC\n0\nEOF\n");
+    out
+}
+
+/// SVG drawing in world (x, z) units, one `polyline` per contour with its
+/// elevation in a `data-elevation` attribute.
+pub fn to_svg(contours: &[Contour]) -> String {
+    let (mut min, mut max) = (DVec2::splat(f64::MAX), DVec2::splat(f64::MIN));
+    for p in contours.iter().flat_map(|c| &c.points) {
+        min = min.min(*p);
+        max = max.max(*p);
+    }
+    if contours.is_empty() {
+        (min, max) = (DVec2::ZERO, DVec2::ONE);
+    }
+    let size = (max - min).max(DVec2::splat(f64::EPSILON));
+    let stroke = size.max_element() / 1000.0;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+        min.x, min.y, size.x, size.y
+    );
+    let _ = writeln!(
+        out,
+        "<g fill=\"none\" stroke=\"black\" stroke-width=\"{}\">",
+        stroke
+    );
+    for contour in contours {
+        let _ = write!(
+            out,
+            "<polyline data-elevation=\"{}\" points=\"",
+            contour.level
+        );
+        for (i, p) in path(contour).enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            let _ = write!(out, "{},{}", p.x, p.y);
+        }
+        out.push_str("\"/>\n");
+    }
+    out.push_str("</g>\n</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A single peak of height 4 in the middle of a 5x5 grid.
+    fn peak() -> TerrainData {
+        let points = (0..5)
+            .map(|z: i32| {
+                (0..5)
+                    .map(|x: i32| (4 - 2 * ((x - 2).abs().max((z - 2).abs()))) as f64)
+                    .collect()
+            })
+            .collect();
+        TerrainData::new(points, None)
+    }
+
+    #[test]
+    fn test_peak_gives_closed_rings() {
+        let contours = trace(&peak(), 2.0);
+        let rings: Vec<&Contour> = contours.iter().filter(|c| c.level == 2.0).collect();
+
+        assert_eq!(rings.len(), 1);
+        assert!(rings[0].closed);
+        // The level-2 ring runs through the vertices one step from the peak
+        for p in &rings[0].points {
+            assert_eq!((p.x - 2.0).abs().max((p.y - 2.0).abs()), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_slope_gives_open_lines() {
+        // Height rises by 1 per column
+        let points = vec![(0..6).map(|x| x as f64).collect(); 4];
+        let terrain = TerrainData::new(points, None);
+        let contours = trace(&terrain, 2.5);
+
+        assert_eq!(contours.len(), 2);
+        for contour in &contours {
+            assert!(!contour.closed);
+            assert_eq!(contour.points.len(), 4);
+            assert!(contour.points.iter().all(|p| p.x == contour.level));
+        }
+    }
+
+    #[test]
+    fn test_invalid_interval() {
+        assert!(trace(&peak(), 0.0).is_empty());
+        assert!(trace(&peak(), f64::NAN).is_empty());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        let format = |p: &str| ContourFormat::from_path(Path::new(p));
+        assert_eq!(format("a.geojson"), Some(ContourFormat::GeoJson));
+        assert_eq!(format("a.DXF"), Some(ContourFormat::Dxf));
+        assert_eq!(format("a.svg"), Some(ContourFormat::Svg));
+        assert_eq!(format("a.txt"), None);
+        assert_eq!(format("a"), None);
+    }
+
+    #[test]
+    fn test_writers() {
+        let contours = trace(&peak(), 2.0);
+
+        let json = ContourFormat::GeoJson.write(&contours);
+        assert!(json.contains("\"elevation\":2"));
+        assert!(json.contains("\"type\":\"LineString\""));
+
+        let dxf = ContourFormat::Dxf.write(&contours);
+        assert!(dxf.starts_with("0\nSECTION\n2\nENTITIES\n"));
+        assert_eq!(dxf.matches("POLYLINE").count(), contours.len());
+        assert!(dxf.ends_with("0\nEOF\n"));
+
+        let svg = ContourFormat::Svg.write(&contours);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<polyline").count(), contours.len());
+    }
+}
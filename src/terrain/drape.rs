@@ -0,0 +1,337 @@
+//! Images draped over the terrain, such as satellite or orthophotos.
+//!
+//! An image covers the terrain's whole extent, its top row at z = 0 like a
+//! heightmap's, and is shown blended with the height colors by the solid
+//! shader (see `renderer/drape.rs`). PNGs of any color type and bit depth
+//! and JPEGs are read as 8-bit sRGB RGBA; images larger than the GPU
+//! allows are halved until they fit.
+//!
+//! An image that's slightly off is aligned with [`DrapeAlignment`]:
+//! control points pair features on the terrain with where they show in the
+//! image, and the affine correction solved from them is applied to the
+//! image coordinates the surface is draped with.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use glam::{DAffine2, DMat2, DVec2};
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+use super::TerrainData;
+
+/// An 8-bit sRGB RGBA image, row by row from the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrapeImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<[u8; 4]>,
+}
+
+impl DrapeImage {
+    /// Read the PNG or JPEG image at `path`, picked by the file's signature.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        if bytes.starts_with(b"\x89PNG") {
+            Self::from_png(&bytes)
+        } else {
+            Self::from_jpeg(&bytes)
+        }
+        .with_context(|| format!("Failed to decode {}", path.display()))
+    }
+
+    fn from_png(bytes: &[u8]) -> Result<Self> {
+        let mut decoder = png::Decoder::new(Cursor::new(bytes));
+        // Palettes and sub-byte depths become 8-bit, 16-bit is kept
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let size = reader
+            .output_buffer_size()
+            .ok_or_else(|| anyhow!("Image too large"))?;
+        let mut buf = vec![0; size];
+        let info = reader.next_frame(&mut buf)?;
+        let buf = &buf[..info.buffer_size()];
+
+        // The high byte of 16-bit samples
+        let samples: Vec<u8> = match info.bit_depth {
+            png::BitDepth::Sixteen => buf.iter().step_by(2).copied().collect(),
+            _ => buf.to_vec(),
+        };
+        let rgba = samples
+            .chunks_exact(info.color_type.samples())
+            .map(|pixel| match *pixel {
+                [gray] => [gray, gray, gray, 255],
+                [gray, alpha] => [gray, gray, gray, alpha],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, a, ..] => [r, g, b, a],
+                _ => [0, 0, 0, 255],
+            })
+            .collect();
+        Self::new(info.width, info.height, rgba)
+    }
+
+    fn from_jpeg(bytes: &[u8]) -> Result<Self> {
+        let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA);
+        let mut decoder = JpegDecoder::new_with_options(bytes, options);
+        let pixels = decoder.decode().map_err(|e| anyhow!("{e}"))?;
+        let info = decoder
+            .info()
+            .ok_or_else(|| anyhow!("Missing JPEG header"))?;
+        let rgba = pixels
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect();
+        Self::new(info.width as u32, info.height as u32, rgba)
+    }
+
+    fn new(width: u32, height: u32, rgba: Vec<[u8; 4]>) -> Result<Self> {
+        if width == 0 || height == 0 || rgba.len() != (width * height) as usize {
+            return Err(anyhow!("Empty or truncated image"));
+        }
+        Ok(Self {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    /// The image at half the size, each pixel averaging up to 2x2.
+    pub fn halved(&self) -> Self {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let at = |x: u32, y: u32| {
+            let (x, y) = (x.min(self.width - 1), y.min(self.height - 1));
+            self.rgba[(y * self.width + x) as usize]
+        };
+        let rgba = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let block = [
+                    at(2 * x, 2 * y),
+                    at(2 * x + 1, 2 * y),
+                    at(2 * x, 2 * y + 1),
+                    at(2 * x + 1, 2 * y + 1),
+                ];
+                std::array::from_fn(|c| {
+                    let sum: u32 = block.iter().map(|p| p[c] as u32).sum();
+                    ((sum + 2) / 4) as u8
+                })
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// The image halved until neither side exceeds `max_size`.
+    pub fn fit(self, max_size: u32) -> Self {
+        let mut image = self;
+        while image.width > max_size || image.height > max_size {
+            image = image.halved();
+        }
+        image
+    }
+
+    /// The image followed by its halvings down to a single pixel, as the
+    /// mip levels of a texture.
+    pub fn mip_chain(&self) -> Vec<Self> {
+        let mut chain = vec![self.clone()];
+        while let Some(last) = chain.last().filter(|i| i.width > 1 || i.height > 1) {
+            let next = last.halved();
+            chain.push(next);
+        }
+        chain
+    }
+}
+
+/// A feature matched between the terrain and the draped image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlPoint {
+    /// World-space (x, z) position of the feature on the terrain
+    pub terrain: DVec2,
+    /// Position of the feature in the image, from (0, 0) at its top left
+    /// corner to (1, 1) at its bottom right
+    pub image: DVec2,
+}
+
+/// Control points correcting the placement of a draped image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DrapeAlignment {
+    pub points: Vec<ControlPoint>,
+}
+
+impl DrapeAlignment {
+    /// Correction from the image position a terrain point is draped with
+    /// unaligned (see [`image_position`]) to the one showing its feature.
+    ///
+    /// One control point shifts the image; two also rotate and scale it
+    /// evenly; three or more give the affine transform with the least
+    /// squared error, or the even one again if they lie on a line.
+    pub fn solve(&self, terrain: &TerrainData) -> DAffine2 {
+        if self.points.is_empty() {
+            return DAffine2::IDENTITY;
+        }
+        let pairs: Vec<(DVec2, DVec2)> = self
+            .points
+            .iter()
+            .map(|point| (image_position(terrain, point.terrain), point.image))
+            .collect();
+        let count = pairs.len() as f64;
+        let from_mean = pairs.iter().map(|(from, _)| *from).sum::<DVec2>() / count;
+        let to_mean = pairs.iter().map(|(_, to)| *to).sum::<DVec2>() / count;
+        let centered = pairs
+            .iter()
+            .map(|(from, to)| (*from - from_mean, *to - to_mean));
+
+        let outer = |a: DVec2, b: DVec2| DMat2::from_cols(a * b.x, a * b.y);
+        let spread: DMat2 = centered.clone().map(|(from, _)| outer(from, from)).sum();
+        let cross: DMat2 = centered.clone().map(|(from, to)| outer(to, from)).sum();
+        let size = spread.x_axis.x + spread.y_axis.y;
+        let matrix = if pairs.len() >= 3 && spread.determinant() > 1e-9 * size * size {
+            cross * spread.inverse()
+        } else if size > 0.0 {
+            // Rotation and even scale: [a -b; b a]
+            let a = centered.clone().map(|(from, to)| from.dot(to)).sum::<f64>() / size;
+            let b = centered.map(|(from, to)| from.perp_dot(to)).sum::<f64>() / size;
+            DMat2::from_cols(DVec2::new(a, b), DVec2::new(-b, a))
+        } else {
+            DMat2::IDENTITY
+        };
+        DAffine2::from_mat2_translation(matrix, to_mean - matrix * from_mean)
+    }
+
+    /// Index of the control point whose terrain position is nearest
+    /// `point`, if within `radius`.
+    pub fn nearest(&self, point: DVec2, radius: f64) -> Option<usize> {
+        self.points
+            .iter()
+            .map(|control| control.terrain.distance(point))
+            .enumerate()
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+}
+
+/// Position in an image draped unaligned over `terrain`'s extent of the
+/// world-space (x, z) `point`, the image's corners on the outer edges of
+/// the corner cells.
+pub fn image_position(terrain: &TerrainData, point: DVec2) -> DVec2 {
+    let corner = terrain.origin - terrain.cell_size / 2.0;
+    let extent = DVec2::new(terrain.width as f64, terrain.height as f64) * terrain.cell_size;
+    (point - corner) / extent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_color_types_become_rgba() {
+        let encode = |color: png::ColorType, depth: png::BitDepth, data: &[u8]| {
+            let mut bytes = Vec::new();
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(color);
+            encoder.set_depth(depth);
+            encoder
+                .write_header()
+                .unwrap()
+                .write_image_data(data)
+                .unwrap();
+            bytes
+        };
+        let gray = encode(png::ColorType::Grayscale, png::BitDepth::Eight, &[10, 200]);
+        let image = DrapeImage::from_png(&gray).unwrap();
+        assert_eq!(image.rgba, vec![[10, 10, 10, 255], [200, 200, 200, 255]]);
+
+        let rgb16 = encode(
+            png::ColorType::Rgb,
+            png::BitDepth::Sixteen,
+            &[1, 0, 2, 0, 3, 0, 255, 255, 0, 0, 128, 0],
+        );
+        let image = DrapeImage::from_png(&rgb16).unwrap();
+        assert_eq!(image.rgba, vec![[1, 2, 3, 255], [255, 0, 128, 255]]);
+    }
+
+    #[test]
+    fn test_halving_fits_and_ends_in_one_pixel() {
+        let image = DrapeImage::new(5, 2, vec![[100, 0, 0, 255]; 10]).unwrap();
+        let half = image.halved();
+        assert_eq!((half.width, half.height), (2, 1));
+        assert_eq!(half.rgba[0], [100, 0, 0, 255]);
+
+        let fitted = image.clone().fit(2);
+        assert_eq!((fitted.width, fitted.height), (2, 1));
+
+        let chain = image.mip_chain();
+        let sizes: Vec<_> = chain.iter().map(|i| (i.width, i.height)).collect();
+        assert_eq!(sizes, vec![(5, 2), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn test_alignment_solves_correction() {
+        let mut terrain = TerrainData::new(vec![vec![0.0; 10]; 10], None);
+        terrain.origin = DVec2::new(100.0, 200.0);
+        terrain.cell_size = 2.0;
+        assert_eq!(
+            image_position(&terrain, DVec2::new(99.0, 209.0)),
+            DVec2::new(0.0, 0.5)
+        );
+        let matched = |correction: DAffine2, points: &[DVec2]| DrapeAlignment {
+            points: points
+                .iter()
+                .map(|&terrain_point| ControlPoint {
+                    terrain: terrain_point,
+                    image: correction.transform_point2(image_position(&terrain, terrain_point)),
+                })
+                .collect(),
+        };
+        let close = |a: DAffine2, b: DAffine2| a.abs_diff_eq(b, 1e-9);
+        let points = [
+            DVec2::new(102.0, 204.0),
+            DVec2::new(110.0, 204.0),
+            DVec2::new(104.0, 214.0),
+            DVec2::new(112.0, 216.0),
+        ];
+
+        assert_eq!(
+            DrapeAlignment::default().solve(&terrain),
+            DAffine2::IDENTITY
+        );
+        let shift = DAffine2::from_translation(DVec2::new(0.02, -0.01));
+        assert!(close(matched(shift, &points[..1]).solve(&terrain), shift));
+        let even = DAffine2::from_scale_angle_translation(
+            DVec2::splat(1.05),
+            0.03,
+            DVec2::new(-0.01, 0.02),
+        );
+        assert!(close(matched(even, &points[..2]).solve(&terrain), even));
+        let affine = DAffine2::from_cols_array(&[1.02, 0.01, -0.03, 0.97, 0.015, 0.005]);
+        assert!(close(matched(affine, &points[..3]).solve(&terrain), affine));
+        assert!(close(matched(affine, &points).solve(&terrain), affine));
+
+        // Points on a line can't fix the shear, so are solved evenly
+        let line = [points[0], points[1], DVec2::new(106.0, 204.0)];
+        assert!(close(matched(even, &line).solve(&terrain), even));
+        // Even two points in one place only shift
+        let same = [points[0], points[0]];
+        assert!(close(matched(shift, &same).solve(&terrain), shift));
+    }
+
+    #[test]
+    fn test_nearest_control_point() {
+        let at = |x, z| ControlPoint {
+            terrain: DVec2::new(x, z),
+            image: DVec2::ZERO,
+        };
+        let alignment = DrapeAlignment {
+            points: vec![at(0.0, 0.0), at(10.0, 0.0)],
+        };
+        assert_eq!(alignment.nearest(DVec2::new(8.0, 1.0), 3.0), Some(1));
+        assert_eq!(alignment.nearest(DVec2::new(5.0, 0.0), 3.0), None);
+    }
+}
@@ -0,0 +1,315 @@
+//! HDR environment maps lighting the scene.
+//!
+//! An environment map is a Radiance `.hdr` panorama in equirectangular
+//! projection: x runs once around the horizon, y from straight up to
+//! straight down. The renderer shows it behind the terrain, reflects it off
+//! the solid surface and lights the surface by its [`Irradiance`], the
+//! diffuse light arriving from the whole sky for each surface direction,
+//! kept as nine spherical harmonic coefficients (Ramamoorthi and Hanrahan,
+//! "An Efficient Representation for Irradiance Environment Maps").
+//!
+//! Directions are in world space, y up; the panorama's center column looks
+//! along -z.
+
+use std::f32::consts::PI;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use glam::Vec3;
+
+/// Irradiance spherical harmonic coefficients, in the order of
+/// [`sh_basis`], scaled so that evaluating them for a normal gives the
+/// light a white Lambertian surface facing that way reflects.
+pub type Irradiance = [[f32; 3]; 9];
+
+/// A linear RGB panorama, row by row from straight up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentMap {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<[f32; 3]>,
+}
+
+impl EnvironmentMap {
+    /// Read the Radiance `.hdr` panorama at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_hdr(&bytes).with_context(|| format!("Failed to decode {}", path.display()))
+    }
+
+    /// Decode Radiance RGBE pixels, flat or run-length encoded per
+    /// scanline, with the usual `-Y height +X width` orientation.
+    fn from_hdr(bytes: &[u8]) -> Result<Self> {
+        let mut lines = bytes.split(|&b| b == b'\n');
+        let magic = lines.next().unwrap_or_default();
+        if !magic.starts_with(b"#?") {
+            bail!("Not a Radiance HDR file");
+        }
+        let mut offset = magic.len() + 1;
+        // Header lines up to an empty one
+        for line in lines.by_ref() {
+            offset += line.len() + 1;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix(b"FORMAT=") {
+                if format != b"32-bit_rle_rgbe" {
+                    bail!("Unsupported format {}", String::from_utf8_lossy(format));
+                }
+            }
+        }
+        let resolution = lines.next().ok_or_else(|| anyhow!("Missing resolution"))?;
+        offset += resolution.len() + 1;
+        let resolution = String::from_utf8_lossy(resolution);
+        let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (width.parse::<u32>()?, height.parse::<u32>()?),
+            _ => bail!("Unsupported orientation {resolution}"),
+        };
+        if width == 0 || height == 0 {
+            bail!("Empty image");
+        }
+
+        let mut data = bytes.get(offset..).unwrap_or_default();
+        let mut rgb = Vec::with_capacity((width * height) as usize);
+        for _ in 0..height {
+            let (scanline, rest) = read_scanline(data, width as usize)?;
+            rgb.extend(scanline.iter().map(|&rgbe| rgbe_to_rgb(rgbe)));
+            data = rest;
+        }
+        Ok(Self { width, height, rgb })
+    }
+
+    /// The panorama at half the size, each pixel averaging up to 2x2.
+    pub fn halved(&self) -> Self {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let at = |x: u32, y: u32| {
+            let (x, y) = (x.min(self.width - 1), y.min(self.height - 1));
+            Vec3::from(self.rgb[(y * self.width + x) as usize])
+        };
+        let rgb = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let sum = at(2 * x, 2 * y)
+                    + at(2 * x + 1, 2 * y)
+                    + at(2 * x, 2 * y + 1)
+                    + at(2 * x + 1, 2 * y + 1);
+                (sum / 4.0).to_array()
+            })
+            .collect();
+        Self { width, height, rgb }
+    }
+
+    /// The panorama halved until neither side exceeds `max_size`.
+    pub fn fit(self, max_size: u32) -> Self {
+        let mut map = self;
+        while map.width > max_size || map.height > max_size {
+            map = map.halved();
+        }
+        map
+    }
+
+    /// The panorama followed by its halvings down to a single pixel, as
+    /// the mip levels of a texture.
+    pub fn mip_chain(&self) -> Vec<Self> {
+        let mut chain = vec![self.clone()];
+        while let Some(last) = chain.last().filter(|m| m.width > 1 || m.height > 1) {
+            let next = last.halved();
+            chain.push(next);
+        }
+        chain
+    }
+
+    /// World direction through the center of pixel (`x`, `y`).
+    fn direction(&self, x: u32, y: u32) -> Vec3 {
+        let theta = PI * (y as f32 + 0.5) / self.height as f32;
+        let phi = 2.0 * PI * ((x as f32 + 0.5) / self.width as f32 - 0.5);
+        Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    /// Diffuse irradiance of the panorama, integrated over each pixel's
+    /// solid angle.
+    pub fn irradiance(&self) -> Irradiance {
+        // Cosine lobe convolution per band, over pi for Lambert's BRDF
+        const BAND_SCALE: [f32; 9] = [
+            1.0,
+            2.0 / 3.0,
+            2.0 / 3.0,
+            2.0 / 3.0,
+            0.25,
+            0.25,
+            0.25,
+            0.25,
+            0.25,
+        ];
+        let pixel_angle = (2.0 * PI / self.width as f32) * (PI / self.height as f32);
+        let mut coefficients = [Vec3::ZERO; 9];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let direction = self.direction(x, y);
+                let weight = pixel_angle * (PI * (y as f32 + 0.5) / self.height as f32).sin();
+                let radiance = Vec3::from(self.rgb[(y * self.width + x) as usize]) * weight;
+                for (c, basis) in coefficients.iter_mut().zip(sh_basis(direction)) {
+                    *c += radiance * basis;
+                }
+            }
+        }
+        std::array::from_fn(|i| (coefficients[i] * BAND_SCALE[i]).to_array())
+    }
+}
+
+/// The first nine real spherical harmonics at unit `direction`.
+pub fn sh_basis(direction: Vec3) -> [f32; 9] {
+    let Vec3 { x, y, z } = direction;
+    [
+        0.282_095,
+        0.488_603 * y,
+        0.488_603 * z,
+        0.488_603 * x,
+        1.092_548 * x * y,
+        1.092_548 * y * z,
+        0.315_392 * (3.0 * z * z - 1.0),
+        1.092_548 * x * z,
+        0.546_274 * (x * x - y * y),
+    ]
+}
+
+/// Light reflected by a white Lambertian surface facing `normal` under
+/// `irradiance`.
+pub fn evaluate(irradiance: &Irradiance, normal: Vec3) -> Vec3 {
+    irradiance
+        .iter()
+        .zip(sh_basis(normal))
+        .map(|(c, basis)| Vec3::from(*c) * basis)
+        .sum()
+}
+
+/// Read one scanline of `width` RGBE pixels from `data`, returning them
+/// and the data after them.
+fn read_scanline(data: &[u8], width: usize) -> Result<(Vec<[u8; 4]>, &[u8])> {
+    let truncated = || anyhow!("Truncated pixel data");
+    // Run-length encoded scanlines start with 2, 2 and their width
+    let rle = (8..0x8000).contains(&width)
+        && data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && data[2] & 0x80 == 0;
+    if !rle {
+        let bytes = data.get(..width * 4).ok_or_else(truncated)?;
+        let pixels = bytes.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]);
+        return Ok((pixels.collect(), &data[width * 4..]));
+    }
+    if ((data[2] as usize) << 8 | data[3] as usize) != width {
+        bail!("Scanline width mismatch");
+    }
+
+    // Each channel in turn, as runs and literal spans
+    let mut pixels = vec![[0; 4]; width];
+    let mut at = 4;
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(at).ok_or_else(truncated)? as usize;
+            at += 1;
+            if count > 128 {
+                let count = count - 128;
+                let value = *data.get(at).ok_or_else(truncated)?;
+                at += 1;
+                for pixel in pixels.get_mut(x..x + count).ok_or_else(truncated)? {
+                    pixel[channel] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 {
+                    bail!("Empty run in scanline");
+                }
+                let values = data.get(at..at + count).ok_or_else(truncated)?;
+                at += count;
+                for (pixel, &value) in pixels
+                    .get_mut(x..x + count)
+                    .ok_or_else(truncated)?
+                    .iter_mut()
+                    .zip(values)
+                {
+                    pixel[channel] = value;
+                }
+                x += count;
+            }
+        }
+    }
+    Ok((pixels, &data[at..]))
+}
+
+/// Linear RGB of a shared-exponent RGBE pixel.
+fn rgbe_to_rgb([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+    let scale = 2f32.powi(e as i32 - 136);
+    [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hdr(width: usize, height: usize, scanline: &[u8]) -> Vec<u8> {
+        let mut bytes =
+            format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n").into_bytes();
+        for _ in 0..height {
+            bytes.extend_from_slice(scanline);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_decodes_flat_and_run_length_scanlines() {
+        // 128 * 2^(129 - 136) = 1.0
+        let flat = hdr(2, 1, &[128, 64, 0, 129, 128, 128, 128, 130]);
+        let map = EnvironmentMap::from_hdr(&flat).unwrap();
+        assert_eq!(map.rgb, vec![[1.0, 0.5, 0.0], [2.0, 2.0, 2.0]]);
+
+        // Red as a run, green as literals, blue and exponent as runs
+        let mut scanline = vec![2, 2, 0, 8, 128 + 8, 128, 8];
+        scanline.extend(0..8u8);
+        scanline.extend([128 + 8, 0, 128 + 8, 129]);
+        let map = EnvironmentMap::from_hdr(&hdr(8, 2, &scanline)).unwrap();
+        assert_eq!((map.width, map.height), (8, 2));
+        assert_eq!(map.rgb[3], [1.0, 3.0 / 128.0, 0.0]);
+        assert_eq!(map.rgb[8], [1.0, 0.0, 0.0]);
+
+        assert!(EnvironmentMap::from_hdr(&hdr(8, 2, &scanline[..10])).is_err());
+        assert!(EnvironmentMap::from_hdr(b"P6\n").is_err());
+    }
+
+    #[test]
+    fn test_irradiance_of_uniform_and_overhead_light() {
+        // A uniform white sky lights every direction the same
+        let sky = EnvironmentMap {
+            width: 64,
+            height: 32,
+            rgb: vec![[1.0; 3]; 64 * 32],
+        };
+        let irradiance = sky.irradiance();
+        for normal in [Vec3::Y, Vec3::X, Vec3::NEG_Y, Vec3::new(0.6, 0.0, -0.8)] {
+            let light = evaluate(&irradiance, normal);
+            assert!((light - Vec3::ONE).abs().max_element() < 0.01, "{light}");
+        }
+
+        // Light from the upper half only: brighter facing up than down
+        let mut upper = sky.clone();
+        upper.rgb[64 * 16..].fill([0.0; 3]);
+        let irradiance = upper.irradiance();
+        let (up, side, down) = (
+            evaluate(&irradiance, Vec3::Y).x,
+            evaluate(&irradiance, Vec3::Z).x,
+            evaluate(&irradiance, Vec3::NEG_Y).x,
+        );
+        assert!(up > 0.9 && (side - 0.5).abs() < 0.05 && down < 0.1);
+        assert_eq!(sky.mip_chain().len(), 7);
+    }
+}
@@ -0,0 +1,131 @@
+//! Mesh export in the format named by the output file.
+//!
+//! Dispatches a [`TerrainMesh`] to the [`gltf`](super::gltf),
+//! [`obj`](super::obj), [`stl`](super::stl) or [`usdz`](super::usdz) writer,
+//! for the command line and the viewer's "Export Mesh" button alike.
+
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use super::{gltf, obj, stl, usdz, TerrainMesh};
+
+/// Mesh file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// Binary glTF (.glb)
+    Gltf,
+    /// Wavefront OBJ, with an MTL material for textured exports
+    Obj,
+    /// Binary STL, geometry only, for 3D printing
+    Stl,
+    /// USDZ for AR Quick Look; textures aren't included
+    Usdz,
+}
+
+impl MeshFormat {
+    /// Pick a format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "glb" => Some(Self::Gltf),
+            "obj" => Some(Self::Obj),
+            "stl" => Some(Self::Stl),
+            "usdz" => Some(Self::Usdz),
+            _ => None,
+        }
+    }
+}
+
+/// Write `mesh` to `path` in the format its extension names.
+///
+/// A `texture` (texture coordinates per vertex and a PNG, such as a baked
+/// lightmap) is embedded in glTF files; for OBJ it's written next to the
+/// file with a material, sharing its name.
+pub fn write(mesh: &TerrainMesh, path: &Path, texture: Option<(&[[f32; 2]], &[u8])>) -> Result<()> {
+    let format = MeshFormat::from_path(path).ok_or_else(|| {
+        anyhow!(
+            "Unknown mesh format for {}: expected .glb, .obj, .stl or .usdz",
+            path.display()
+        )
+    })?;
+    write_as(format, mesh, path, texture)
+}
+
+/// Write `mesh` to `path` as `format`, whatever the extension. See
+/// [`write`].
+pub fn write_as(
+    format: MeshFormat,
+    mesh: &TerrainMesh,
+    path: &Path,
+    texture: Option<(&[[f32; 2]], &[u8])>,
+) -> Result<()> {
+    let data = match format {
+        MeshFormat::Gltf => {
+            let texture = texture.map(|(uvs, png)| gltf::Texture { uvs, png });
+            gltf::to_glb(mesh, texture.as_ref())
+        }
+        MeshFormat::Obj => match texture {
+            Some((uvs, png)) => {
+                // Material and texture go next to the OBJ, sharing its name
+                let mtl = path.with_extension("mtl");
+                let image = path.with_extension("png");
+                let file_name = |p: &Path| {
+                    p.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .with_context(|| format!("Invalid OBJ path {}", path.display()))
+                };
+                std::fs::write(&image, png)?;
+                std::fs::write(&mtl, obj::to_mtl(&file_name(&image)?))?;
+                obj::to_obj(mesh, Some(uvs), Some(&file_name(&mtl)?)).into_bytes()
+            }
+            None => obj::to_obj(mesh, None, None).into_bytes(),
+        },
+        MeshFormat::Stl => stl::to_stl(mesh),
+        MeshFormat::Usdz => usdz::to_usdz(mesh),
+    };
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::{MeshOptions, TerrainData};
+
+    #[test]
+    fn test_format_from_extension() {
+        let format = |p: &str| MeshFormat::from_path(Path::new(p));
+        assert_eq!(format("terrain.glb"), Some(MeshFormat::Gltf));
+        assert_eq!(format("terrain.OBJ"), Some(MeshFormat::Obj));
+        assert_eq!(format("terrain.usdz"), Some(MeshFormat::Usdz));
+        assert_eq!(format("terrain.stl"), Some(MeshFormat::Stl));
+        assert_eq!(format("terrain.ply"), None);
+        assert_eq!(format("terrain"), None);
+    }
+
+    #[test]
+    fn test_write_by_extension() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0], vec![2.0, 3.0]], None);
+        let mesh = TerrainMesh::build(&terrain, &MeshOptions::default());
+        let dir = tempfile::tempdir().unwrap();
+
+        let glb = dir.path().join("terrain.glb");
+        write(&mesh, &glb, None).unwrap();
+        assert!(std::fs::read(&glb).unwrap().starts_with(b"glTF"));
+
+        // Textured OBJ brings its material and image along
+        let obj = dir.path().join("terrain.obj");
+        write(&mesh, &obj, Some((&[[0.0; 2]; 4], b"png"))).unwrap();
+        let text = std::fs::read_to_string(&obj).unwrap();
+        assert!(text.contains("mtllib terrain.mtl"));
+        assert!(dir.path().join("terrain.mtl").exists());
+        assert_eq!(
+            std::fs::read(dir.path().join("terrain.png")).unwrap(),
+            b"png"
+        );
+
+        let err = write(&mesh, &dir.path().join("terrain.ply"), None).unwrap_err();
+        assert!(err.to_string().starts_with("Unknown mesh format"));
+    }
+}
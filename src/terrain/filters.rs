@@ -0,0 +1,139 @@
+//! Filters over the heights, applied before meshing.
+//!
+//! Sensor noise, such as the speckle of LiDAR-derived grids, makes a
+//! terrain look rough in lighting and contours. A bilateral filter averages
+//! each sample with its neighbors, weighted both by distance and by how
+//! close their heights are, so noise is smoothed away but samples across a
+//! cliff or a sharp ridge, far apart in height, barely mix and the edge
+//! stays crisp.
+
+use rayon::prelude::*;
+
+use super::TerrainData;
+
+/// Edge-preserving denoise configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DenoiseConfig {
+    /// Build the mesh from the filtered heights
+    pub enabled: bool,
+    /// Half-width of the filter window in samples
+    pub radius: u32,
+    /// Height difference at which neighbors count much less, in the
+    /// terrain's units: noise should be well below it, cliffs well above
+    pub range: f32,
+    /// Show the raw heights for a moment, to compare
+    pub show_raw: bool,
+}
+
+impl Default for DenoiseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 2,
+            range: 1.0,
+            show_raw: false,
+        }
+    }
+}
+
+impl DenoiseConfig {
+    /// Whether the mesh is built from filtered heights.
+    pub fn active(&self) -> bool {
+        self.enabled && !self.show_raw && self.radius > 0 && self.range > 0.0
+    }
+}
+
+/// Copy of `terrain` with its heights smoothed by a bilateral filter over
+/// a window of `radius` samples, with a Gaussian falloff in height
+/// difference of standard deviation `range`. Missing samples stay missing
+/// and are left out of their neighbors' averages.
+pub fn bilateral(terrain: &TerrainData, radius: usize, range: f64) -> TerrainData {
+    let (width, height) = (terrain.width, terrain.height);
+    let heights = terrain.heights();
+    // Spatial falloff reaching about 2 standard deviations at the window edge
+    let spatial = -1.0 / (2.0 * (radius as f64 / 2.0).max(0.5).powi(2));
+    let tonal = -1.0 / (2.0 * range * range);
+    let reach = radius as isize;
+
+    let mut filtered = terrain.clone();
+    filtered
+        .heights_mut()
+        .par_chunks_mut(width.max(1))
+        .enumerate()
+        .for_each(|(z, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                let center = heights[z * width + x];
+                if !center.is_finite() {
+                    continue;
+                }
+                let (mut sum, mut weights) = (0.0, 0.0);
+                for dz in -reach..=reach {
+                    let Some(sz) = z.checked_add_signed(dz).filter(|&sz| sz < height) else {
+                        continue;
+                    };
+                    for dx in -reach..=reach {
+                        let Some(sx) = x.checked_add_signed(dx).filter(|&sx| sx < width) else {
+                            continue;
+                        };
+                        let h = heights[sz * width + sx];
+                        if !h.is_finite() {
+                            continue;
+                        }
+                        let distance = (dx * dx + dz * dz) as f64;
+                        let weight = (distance * spatial + (h - center).powi(2) * tonal).exp();
+                        sum += h * weight;
+                        weights += weight;
+                    }
+                }
+                *out = sum / weights;
+            }
+        });
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A step of `cliff` between columns 3 and 4 of an 8x8 grid, with
+    /// checkerboard noise of `noise`.
+    fn noisy_step(cliff: f64, noise: f64) -> TerrainData {
+        let rows = (0..8)
+            .map(|z| {
+                (0..8)
+                    .map(|x| {
+                        let base = if x < 4 { 0.0 } else { cliff };
+                        base + if (x + z) % 2 == 0 { noise } else { -noise }
+                    })
+                    .collect()
+            })
+            .collect();
+        TerrainData::new(rows, None)
+    }
+
+    #[test]
+    fn test_noise_is_smoothed() {
+        let terrain = noisy_step(0.0, 0.1);
+        let filtered = bilateral(&terrain, 2, 1.0);
+        let rough = |t: &TerrainData| (t.at(3, 3) - t.at(4, 3)).abs();
+        assert!(rough(&filtered) < rough(&terrain) / 4.0);
+    }
+
+    #[test]
+    fn test_cliff_is_kept() {
+        let terrain = noisy_step(50.0, 0.1);
+        let filtered = bilateral(&terrain, 2, 1.0);
+        // Each side smooths toward its own level, not the other's
+        assert!(filtered.at(3, 3).abs() < 0.1);
+        assert!((filtered.at(4, 3) - 50.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_missing_samples_stay_missing() {
+        let mut terrain = noisy_step(0.0, 0.1);
+        terrain.heights_mut()[9] = f64::NAN;
+        let filtered = bilateral(&terrain, 1, 1.0);
+        assert!(filtered.heights()[9].is_nan());
+        assert!(filtered.heights()[10].is_finite());
+    }
+}
@@ -0,0 +1,431 @@
+//! ESRI float grids (.flt), memory-mapped and sampled lazily.
+//!
+//! A float grid is raw 32-bit floats, row-major with the northern row
+//! first, described by a `.hdr` file next to it with the ASCII grid's
+//! keywords and the byte order:
+//!
+//! ```text
+//! ncols 40000
+//! nrows 40000
+//! xllcorner 500000
+//! yllcorner 4100000
+//! cellsize 1
+//! NODATA_value -9999
+//! byteorder LSBFIRST
+//! ```
+//!
+//! [`MappedGrid`] maps the samples into memory instead of reading them, so
+//! opening a multi-gigabyte grid is as quick as a small one and only the
+//! pages of the samples actually read are loaded: the overview shown picks
+//! one sample per block, and the renderer keeps the grid mapped to read
+//! windows near the camera at full resolution with [`MappedGrid::region`]
+//! (see [`detail`](crate::renderer::detail)). Float grids are the only raw
+//! binary grids lrle reads, and the only format opened this way.
+//!
+//! The cost of mapping is that the file must stay as it is while it's
+//! open: if another process truncates it, the next read of a page past its
+//! new end raises SIGBUS and lrle exits without an error message. Every
+//! other format is read into memory and can't fail this way.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use glam::DVec2;
+use memmap2::Mmap;
+
+use super::loader::{LoadError, LoadOptions};
+use super::raster::{Georef, Raster};
+use super::TerrainData;
+
+/// Bytes per sample.
+const SAMPLE_SIZE: usize = 4;
+
+/// A float grid mapped into memory, read one sample at a time.
+pub struct MappedGrid {
+    pub width: usize,
+    pub height: usize,
+    /// Placement of the first sample
+    pub georef: Georef,
+    nodata: Option<f32>,
+    big_endian: bool,
+    map: Mmap,
+}
+
+impl MappedGrid {
+    /// Map the float grid at `path`, with its header read from the `.hdr`
+    /// file of the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LoadError`] if either file cannot be opened, the header is
+    /// incomplete or its `ncols` and `nrows` aren't positive whole numbers,
+    /// or the data doesn't hold exactly `ncols` x `nrows` samples.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let header_path = path.with_extension("hdr");
+        let header = fs::read_to_string(&header_path).map_err(io_error(&header_path))?;
+        let file = File::open(path).map_err(io_error(path))?;
+        // SAFETY: the map is read-only, but nothing stops another process
+        // truncating the file while it's mapped, and reading a page past
+        // its new end then raises SIGBUS and kills lrle. The other loaders
+        // read their files into memory and can't fail this way; this one
+        // maps on purpose, and the renderer keeps the map for the session.
+        let map = unsafe { Mmap::map(&file) }.map_err(io_error(path))?;
+        Self::from_map(&header, map)
+    }
+
+    /// Describe the samples in `map` with the `.hdr` contents `header`.
+    fn from_map(header: &str, map: Mmap) -> Result<Self, LoadError> {
+        let mut fields = HashMap::new();
+        for (index, line) in header.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            let (Some(key), Some(value)) = (tokens.next(), tokens.next()) else {
+                continue;
+            };
+            fields.insert(key.to_ascii_lowercase(), (index + 1, value));
+        }
+        let lines = header.lines().count();
+        let entry = |key: &str| {
+            fields
+                .get(key)
+                .copied()
+                .ok_or_else(|| LoadError::ParseError {
+                    line: lines,
+                    message: format!("missing {key} in header"),
+                })
+        };
+        let field = |key: &str| {
+            let (line, value) = entry(key)?;
+            value.parse::<f64>().map_err(|_| LoadError::ParseError {
+                line,
+                message: format!("invalid header value for {key}"),
+            })
+        };
+        let optional = |key: &str| fields.contains_key(key).then(|| field(key)).transpose();
+        // Sample counts, which a cast from the float would truncate or clamp
+        let count = |key: &str| {
+            let (line, value) = entry(key)?;
+            match value.parse::<usize>() {
+                Ok(count) if count > 0 => Ok((line, count)),
+                _ => Err(LoadError::ParseError {
+                    line,
+                    message: format!("{key} must be a positive whole number, not '{value}'"),
+                }),
+            }
+        };
+
+        let (_, width) = count("ncols")?;
+        let (rows_line, height) = count("nrows")?;
+        let spacing = match optional("cellsize")? {
+            Some(size) => DVec2::splat(size),
+            None => DVec2::new(field("dx")?, field("dy")?),
+        };
+        let lower_left = DVec2::new(
+            match optional("xllcenter")? {
+                Some(x) => x,
+                None => field("xllcorner")? + spacing.x / 2.0,
+            },
+            match optional("yllcenter")? {
+                Some(y) => y,
+                None => field("yllcorner")? + spacing.y / 2.0,
+            },
+        );
+        let big_endian = match fields.get("byteorder") {
+            Some(&(_, order)) if order.eq_ignore_ascii_case("msbfirst") => true,
+            Some(&(_, order)) if order.eq_ignore_ascii_case("lsbfirst") => false,
+            Some(&(line, order)) => {
+                return Err(LoadError::ParseError {
+                    line,
+                    message: format!("unknown byte order '{order}'"),
+                })
+            }
+            None => false,
+        };
+        let expected = width
+            .checked_mul(height)
+            .and_then(|samples| samples.checked_mul(SAMPLE_SIZE))
+            .ok_or_else(|| LoadError::ParseError {
+                line: rows_line,
+                message: format!("{width}x{height} samples are too many to map"),
+            })?;
+        if map.len() != expected {
+            return Err(LoadError::SizeMismatch {
+                actual: map.len(),
+                expected,
+            });
+        }
+
+        Ok(Self {
+            width,
+            height,
+            georef: Georef {
+                origin: lower_left + DVec2::Y * (height - 1) as f64 * spacing.y,
+                spacing,
+            },
+            nodata: optional("nodata_value")?.map(|v| v as f32),
+            big_endian,
+            map,
+        })
+    }
+
+    /// Sample at column `x` of row `y`, counted from the north, or NaN
+    /// for nodata.
+    pub fn sample(&self, x: usize, y: usize) -> f64 {
+        let offset = (y * self.width + x) * SAMPLE_SIZE;
+        let bytes = self.map[offset..offset + SAMPLE_SIZE].try_into().unwrap();
+        let value = match self.big_endian {
+            true => f32::from_be_bytes(bytes),
+            false => f32::from_le_bytes(bytes),
+        };
+        if Some(value) == self.nodata {
+            f64::NAN
+        } else {
+            value as f64
+        }
+    }
+
+    /// `width` x `height` samples at full resolution, starting at column
+    /// `x` of row `y`.
+    pub fn region(&self, x: usize, y: usize, width: usize, height: usize) -> Raster {
+        self.region_every(x, y, width, height, 1)
+    }
+
+    /// `width` x `height` samples `step` apart, starting at column `x` of
+    /// row `y`.
+    pub fn region_every(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        step: usize,
+    ) -> Raster {
+        let values = (0..height)
+            .flat_map(|row| (0..width).map(move |column| (x + column * step, y + row * step)))
+            .map(|(x, y)| self.sample(x, y))
+            .collect();
+        Raster {
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// Samples of the grid per sample of its overview along each side, for
+    /// neither of the overview's sides to be over `max_size`.
+    pub fn overview_step(&self, max_size: usize) -> usize {
+        self.width.max(self.height).div_ceil(max_size.max(1)).max(1)
+    }
+
+    /// The grid with neither side over `max_size`, taking the middle
+    /// sample of each block instead of averaging, so only the rows picked
+    /// are read, and the placement of that overview. Overview sample
+    /// (x, y) is grid sample `(offset + x * step, offset + y * step)`, with
+    /// [`overview_step`](Self::overview_step) and `offset` half a block.
+    pub fn overview(&self, max_size: usize) -> (Raster, Georef) {
+        let factor = self.overview_step(max_size);
+        if factor == 1 {
+            return (self.region(0, 0, self.width, self.height), self.georef);
+        }
+        log::info!(
+            "Sampling {}x{} float grid down by {factor}",
+            self.width,
+            self.height
+        );
+        let offset = (factor - 1) / 2;
+        let picks = |len: usize| (offset..len).step_by(factor);
+        let values: Vec<f64> = picks(self.height)
+            .flat_map(|y| picks(self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.sample(x, y))
+            .collect();
+        let width = picks(self.width).len();
+        let raster = Raster {
+            width,
+            height: values.len() / width,
+            values,
+        };
+        let georef = Georef {
+            origin: self.georef.origin
+                + DVec2::new(self.georef.spacing.x, -self.georef.spacing.y) * offset as f64,
+            spacing: self.georef.spacing * factor as f64,
+        };
+        (raster, georef)
+    }
+}
+
+/// The error for failing to open or map `path`: not found if it's
+/// missing, or the I/O error otherwise.
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> LoadError + '_ {
+    move |err| match err.kind() {
+        io::ErrorKind::NotFound => LoadError::FileNotFound(path.display().to_string()),
+        _ => LoadError::Io {
+            path: path.display().to_string(),
+            source: err,
+        },
+    }
+}
+
+/// Load terrain heights from an ESRI float grid, sampled down and with
+/// nodata filled as set by `options`.
+///
+/// # Errors
+///
+/// Returns [`LoadError`] if the grid cannot be mapped, as for
+/// [`MappedGrid::open`].
+pub fn load_float_grid<P: AsRef<Path>>(
+    path: P,
+    options: &LoadOptions,
+) -> Result<TerrainData, LoadError> {
+    let (raster, georef) = MappedGrid::open(path)?.overview(options.max_size);
+    raster.into_terrain(georef, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+
+    const HEADER: &str = "\
+ncols 3
+nrows 2
+xllcorner 500000
+yllcorner 4100000
+cellsize 30
+NODATA_value -9999
+byteorder LSBFIRST
+";
+
+    /// Write `header` and the little-endian `values` as a float grid.
+    fn write_grid(dir: &Path, header: &str, values: &[f32]) -> std::path::PathBuf {
+        let path = dir.join("grid.flt");
+        fs::write(path.with_extension("hdr"), header).unwrap();
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_places_cells() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_grid(dir.path(), HEADER, &[12.5, 13.0, -9999.0, 11.0, 12.0, 12.5]);
+        let options = LoadOptions {
+            nodata_fill: Some(0.0),
+            ..LoadOptions::default()
+        };
+        let terrain = load_float_grid(&path, &options).unwrap();
+
+        // Same placement as the equivalent ESRI ASCII grid
+        assert_eq!(terrain.heights(), [11.0, 12.0, 12.5, 12.5, 13.0, 0.0]);
+        assert_eq!(terrain.cell_size, 30.0);
+        assert_eq!(terrain.origin, DVec2::new(500_015.0, 4_100_015.0));
+    }
+
+    #[test]
+    fn test_big_endian_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grid.flt");
+        fs::write(
+            path.with_extension("hdr"),
+            HEADER.replace("LSBFIRST", "MSBFIRST"),
+        )
+        .unwrap();
+        let bytes: Vec<u8> = (0..6).flat_map(|v| (v as f32).to_be_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+
+        let grid = MappedGrid::open(&path).unwrap();
+        assert_eq!(grid.sample(2, 0), 2.0);
+        assert_eq!(grid.region(1, 1, 2, 1).values, [4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_invalid_grids() {
+        let dir = tempfile::tempdir().unwrap();
+        let short = write_grid(dir.path(), HEADER, &[1.0; 5]);
+        assert!(matches!(
+            MappedGrid::open(&short),
+            Err(LoadError::SizeMismatch {
+                actual: 20,
+                expected: 24
+            })
+        ));
+        assert!(matches!(
+            MappedGrid::open(dir.path().join("none.flt")),
+            Err(LoadError::FileNotFound(_))
+        ));
+        // A directory in place of the samples fails to map, not to be found
+        fs::create_dir(dir.path().join("folder.flt")).unwrap();
+        fs::write(dir.path().join("folder.hdr"), HEADER).unwrap();
+        assert!(matches!(
+            MappedGrid::open(dir.path().join("folder.flt")),
+            Err(LoadError::Io { .. })
+        ));
+        let missing = write_grid(dir.path(), "ncols 3\nnrows 2\n", &[1.0; 6]);
+        assert!(matches!(
+            MappedGrid::open(&missing),
+            Err(LoadError::ParseError { line: 2, .. })
+        ));
+        let order = write_grid(dir.path(), &HEADER.replace("LSB", "XSB"), &[1.0; 6]);
+        assert!(matches!(
+            MappedGrid::open(&order),
+            Err(LoadError::ParseError { line: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_sample_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        for (ncols, nrows, line) in [
+            ("2.5", "2", 1),
+            ("-3", "2", 1),
+            ("0", "2", 1),
+            ("3", "1e3", 2),
+            ("3", &usize::MAX.to_string(), 2),
+        ] {
+            let header = HEADER
+                .replace("ncols 3", &format!("ncols {ncols}"))
+                .replace("nrows 2", &format!("nrows {nrows}"));
+            let path = write_grid(dir.path(), &header, &[1.0; 6]);
+            let result = MappedGrid::open(&path);
+            assert!(
+                matches!(result, Err(LoadError::ParseError { line: l, .. }) if l == line),
+                "ncols {ncols}, nrows {nrows}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_large_grid_opens_without_reading_it() {
+        // 64 MB of samples, left sparse on disk, of which the overview and
+        // window read a few pages
+        const SIDE: usize = 4096;
+        let dir = tempfile::tempdir().unwrap();
+        let header = format!(
+            "ncols {SIDE}\nnrows {SIDE}\nxllcorner 0\nyllcorner 0\ncellsize 1\nbyteorder LSBFIRST\n"
+        );
+        let path = write_grid(dir.path(), &header, &[]);
+        let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len((SIDE * SIDE * SAMPLE_SIZE) as u64).unwrap();
+        // One peak, on a sample the overview picks
+        let (x, y) = (2079, 1055);
+        file.seek(SeekFrom::Start(((y * SIDE + x) * SAMPLE_SIZE) as u64))
+            .unwrap();
+        file.write_all(&100f32.to_le_bytes()).unwrap();
+        drop(file);
+
+        let grid = MappedGrid::open(&path).unwrap();
+        let (overview, georef) = grid.overview(64);
+        assert_eq!((overview.width, overview.height), (64, 64));
+        assert_eq!(grid.overview_step(64), 64);
+        assert_eq!(georef.spacing, DVec2::splat(64.0));
+        // Overview sample (32, 16) is grid sample (31 + 32 * 64, ...)
+        assert_eq!(overview.values[16 * 64 + 32], 100.0);
+        assert_eq!(overview.values.iter().sum::<f64>(), 100.0);
+        // Full-resolution windows read around it
+        let region = grid.region(x - 1, y, 3, 1);
+        assert_eq!(region.values, [0.0, 100.0, 0.0]);
+        let sparse = grid.region_every(x - 4, y - 8, 3, 3, 4);
+        assert_eq!(sparse.values[7], 100.0);
+    }
+}
@@ -0,0 +1,354 @@
+//! Single-band GeoTIFF elevation models.
+//!
+//! DEMs from national surveys, SRTM or GDAL come as GeoTIFFs: one band of
+//! heights, integer or floating point, with tags placing the raster on the
+//! ground. The georeferencing sets the grid's origin and cell size, so
+//! samples keep their real spacing instead of one unit apart.
+//!
+//! Rasters are stored north-up, first row northernmost, while grid rows run
+//! toward +Z (north); rows are flipped on load so east stays +X. Rasters in
+//! geographic coordinates (degrees) are projected to approximate meters
+//! around their center latitude, so their cells share the heights' units.
+//!
+//! Samples equal to the GDAL nodata value, or NaN, are filled from their
+//! valid neighbors, and rasters larger than the size limit are averaged down
+//! in square blocks.
+//!
+//! The terrain's units come from the vertical or, failing that, the linear
+//! units GeoKey; geographic rasters are in meters once projected.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use glam::DVec2;
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+use super::loader::{LoadError, LoadOptions};
+use super::raster::{Georef, Raster};
+use super::units::Units;
+use super::TerrainData;
+
+/// Meters per degree of latitude, and of longitude at the equator.
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// `GTModelTypeGeoKey`: projected, geographic or geocentric coordinates.
+const MODEL_TYPE_KEY: u16 = 1024;
+
+/// `GTRasterTypeGeoKey`: whether tiepoints refer to pixel corners or centers.
+const RASTER_TYPE_KEY: u16 = 1025;
+
+/// `ProjLinearUnitsGeoKey`: EPSG unit of projected coordinates.
+const LINEAR_UNITS_KEY: u16 = 3076;
+
+/// `VerticalUnitsGeoKey`: EPSG unit of heights.
+const VERTICAL_UNITS_KEY: u16 = 4099;
+
+/// `ModelTypeGeographic`: coordinates are longitude and latitude.
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+
+/// `RasterPixelIsPoint`: tiepoints refer to pixel centers.
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+/// Load terrain heights from a single-band GeoTIFF, averaged down and with
+/// nodata filled as set by `options`.
+///
+/// # Errors
+///
+/// Returns [`LoadError`] if the file cannot be read or decoded, has more
+/// than one band, or holds no valid samples.
+pub fn load_geotiff<P: AsRef<Path>>(
+    path: P,
+    options: &LoadOptions,
+) -> Result<TerrainData, LoadError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|_| LoadError::FileNotFound(path.display().to_string()))?;
+    let mut decoder = Decoder::new(BufReader::new(file))
+        .map_err(image_error)?
+        .with_limits(Limits::unlimited());
+
+    if !matches!(
+        decoder.colortype().map_err(image_error)?,
+        ColorType::Gray(_)
+    ) {
+        return Err(LoadError::Image(
+            "expected a single-band GeoTIFF".to_string(),
+        ));
+    }
+    let (width, height) = decoder.dimensions().map_err(image_error)?;
+    let (width, height) = (width as usize, height as usize);
+    if width == 0 || height == 0 {
+        return Err(LoadError::EmptyFile);
+    }
+
+    let (georef, units) = read_georef(&mut decoder)?;
+    let nodata = match decoder.find_tag(Tag::GdalNodata).map_err(image_error)? {
+        Some(value) => value
+            .into_string()
+            .map_err(image_error)?
+            .trim()
+            .parse()
+            .ok(),
+        None => None,
+    };
+    let values: Vec<f64> = to_f64(decoder.read_image().map_err(image_error)?)
+        .into_iter()
+        .map(|v| if Some(v) == nodata { f64::NAN } else { v })
+        .collect();
+    if values.len() < width * height {
+        return Err(LoadError::Image("truncated raster".to_string()));
+    }
+
+    let raster = Raster {
+        width,
+        height,
+        values,
+    };
+    let mut terrain = raster.into_terrain(georef, options)?;
+    terrain.units = units;
+    Ok(terrain)
+}
+
+/// Read the raster's placement and units from its GeoTIFF tags, falling
+/// back to unit cells at the origin when it has none.
+fn read_georef<R: std::io::Read + std::io::Seek>(
+    decoder: &mut Decoder<R>,
+) -> Result<(Georef, Units), LoadError> {
+    let mut tag = |tag| -> Result<Option<Vec<f64>>, LoadError> {
+        decoder
+            .find_tag(tag)
+            .map_err(image_error)?
+            .map(|value| value.into_f64_vec().map_err(image_error))
+            .transpose()
+    };
+    let scale = tag(Tag::ModelPixelScaleTag)?;
+    let tiepoint = tag(Tag::ModelTiepointTag)?;
+    let transform = tag(Tag::ModelTransformationTag)?;
+    let keys = match decoder
+        .find_tag(Tag::GeoKeyDirectoryTag)
+        .map_err(image_error)?
+    {
+        Some(value) => value.into_u16_vec().map_err(image_error)?,
+        None => Vec::new(),
+    };
+
+    // Model coordinates of pixel corner (0, 0) and the pixel size
+    let (corner, size) = match (scale, tiepoint, transform) {
+        (Some(scale), Some(tie), _) if scale.len() >= 2 && tie.len() >= 6 => (
+            DVec2::new(tie[3] - tie[0] * scale[0], tie[4] + tie[1] * scale[1]),
+            DVec2::new(scale[0], scale[1]),
+        ),
+        (_, _, Some(m)) if m.len() >= 16 => {
+            if m[1] != 0.0 || m[4] != 0.0 {
+                return Err(LoadError::Image(
+                    "rotated rasters are not supported".to_string(),
+                ));
+            }
+            (DVec2::new(m[3], m[7]), DVec2::new(m[0], -m[5]))
+        }
+        _ => {
+            log::warn!("GeoTIFF has no georeferencing, using unit cells");
+            (DVec2::ZERO, DVec2::ONE)
+        }
+    };
+
+    let center = if geo_key(&keys, RASTER_TYPE_KEY) == Some(RASTER_PIXEL_IS_POINT) {
+        corner
+    } else {
+        corner + DVec2::new(size.x, -size.y) * 0.5
+    };
+    let mut georef = Georef {
+        origin: center,
+        spacing: size,
+    };
+    let geographic = geo_key(&keys, MODEL_TYPE_KEY) == Some(MODEL_TYPE_GEOGRAPHIC);
+    if geographic {
+        // Equirectangular projection around the raster's latitude
+        let east = METERS_PER_DEGREE * center.y.to_radians().cos();
+        let meters = DVec2::new(east, METERS_PER_DEGREE);
+        georef.origin *= meters;
+        georef.spacing *= meters;
+    }
+    let (dx, dy) = (georef.spacing.x, georef.spacing.y);
+    if (dx - dy).abs() > 1e-3 * dx.max(dy) {
+        log::warn!("GeoTIFF cells are {dx} x {dy}, using their mean as the cell size");
+    }
+    let units = geo_key(&keys, VERTICAL_UNITS_KEY)
+        .or_else(|| geo_key(&keys, LINEAR_UNITS_KEY))
+        .and_then(Units::from_epsg);
+    let units = match units {
+        Some(units) => units,
+        None if geographic => Units::Meters,
+        None => Units::Unitless,
+    };
+    Ok((georef, units))
+}
+
+/// Value of a short-valued key in a GeoKey directory.
+fn geo_key(keys: &[u16], key: u16) -> Option<u16> {
+    // A header of four shorts, then four per key: id, location, count, value
+    keys.get(4..)?
+        .chunks_exact(4)
+        .find(|entry| entry[0] == key && entry[1] == 0)
+        .map(|entry| entry[3])
+}
+
+fn to_f64(result: DecodingResult) -> Vec<f64> {
+    match result {
+        DecodingResult::U8(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U16(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::U64(v) => v.into_iter().map(|v| v as f64).collect(),
+        DecodingResult::F16(v) => v.into_iter().map(|v| v.to_f64()).collect(),
+        DecodingResult::F32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::F64(v) => v,
+        DecodingResult::I8(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I16(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I32(v) => v.into_iter().map(f64::from).collect(),
+        DecodingResult::I64(v) => v.into_iter().map(|v| v as f64).collect(),
+    }
+}
+
+fn image_error(err: impl std::fmt::Display) -> LoadError {
+    LoadError::Image(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    /// Write a 32-bit float GeoTIFF with the given pixel scale, tiepoint,
+    /// GeoKeys and nodata value.
+    fn write_geotiff(
+        (width, height): (u32, u32),
+        data: &[f32],
+        scale: [f64; 3],
+        tiepoint: [f64; 6],
+        keys: &[u16],
+        nodata: Option<&str>,
+    ) -> tempfile::NamedTempFile {
+        let file = tempfile::Builder::new().suffix(".tif").tempfile().unwrap();
+        let mut encoder = TiffEncoder::new(file.reopen().unwrap()).unwrap();
+        let mut image = encoder
+            .new_image::<colortype::Gray32Float>(width, height)
+            .unwrap();
+        let tags = image.encoder();
+        tags.write_tag(Tag::ModelPixelScaleTag, &scale[..]).unwrap();
+        tags.write_tag(Tag::ModelTiepointTag, &tiepoint[..])
+            .unwrap();
+        if !keys.is_empty() {
+            tags.write_tag(Tag::GeoKeyDirectoryTag, keys).unwrap();
+        }
+        if let Some(nodata) = nodata {
+            tags.write_tag(Tag::GdalNodata, nodata).unwrap();
+        }
+        image.write_data(data).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_projected_raster_keeps_spacing() {
+        // 3x2 raster of 30 m pixels, top-left corner at (500000, 4100000)
+        let file = write_geotiff(
+            (3, 2),
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            [30.0, 30.0, 0.0],
+            [0.0, 0.0, 0.0, 500_000.0, 4_100_000.0, 0.0],
+            &[],
+            None,
+        );
+        let terrain = load_geotiff(file.path(), &LoadOptions::default()).unwrap();
+
+        assert_eq!(terrain.cell_size, 30.0);
+        assert_eq!(terrain.units, Units::Unitless);
+        // The southern row comes first, at its pixel centers
+        assert_eq!(terrain.heights(), [4.0, 5.0, 6.0, 1.0, 2.0, 3.0]);
+        assert_eq!(terrain.origin, DVec2::new(500_015.0, 4_100_000.0 - 45.0));
+    }
+
+    #[test]
+    fn test_geographic_raster_in_meters() {
+        // Pixel-is-point tiepoint on the equator, 1/1200 degree pixels
+        let keys = [1, 1, 0, 2, 1024, 0, 1, 2, 1025, 0, 1, 2];
+        let step = 1.0 / 1200.0;
+        let file = write_geotiff(
+            (2, 2),
+            &[0.0; 4],
+            [step, step, 0.0],
+            [0.0, 0.0, 0.0, 10.0, 0.0, 0.0],
+            &keys,
+            None,
+        );
+        let terrain = load_geotiff(file.path(), &LoadOptions::default()).unwrap();
+
+        assert!((terrain.cell_size - METERS_PER_DEGREE * step).abs() < 1e-9);
+        assert_eq!(terrain.units, Units::Meters);
+        assert!((terrain.origin.x - METERS_PER_DEGREE * 10.0).abs() < 1e-6);
+        assert!((terrain.origin.y + METERS_PER_DEGREE * step).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_units_from_geokeys() {
+        // The linear units, unless the vertical units say otherwise
+        let load = |keys: &[u16]| {
+            let file = write_geotiff((1, 1), &[0.0], [1.0; 3], [0.0; 6], keys, None);
+            load_geotiff(file.path(), &LoadOptions::default())
+                .unwrap()
+                .units
+        };
+        assert_eq!(load(&[1, 1, 0, 1, 3076, 0, 1, 9002]), Units::Feet);
+        assert_eq!(
+            load(&[1, 1, 0, 2, 3076, 0, 1, 9002, 4099, 0, 1, 9001]),
+            Units::Meters
+        );
+    }
+
+    #[test]
+    fn test_nodata_filled_from_neighbors() {
+        let file = write_geotiff(
+            (3, 1),
+            &[2.0, -9999.0, 4.0],
+            [1.0, 1.0, 0.0],
+            [0.0; 6],
+            &[],
+            Some("-9999"),
+        );
+        let terrain = load_geotiff(file.path(), &LoadOptions::default()).unwrap();
+        assert_eq!(terrain.heights(), [2.0, 3.0, 4.0]);
+
+        let empty = write_geotiff(
+            (2, 1),
+            &[f32::NAN, f32::NAN],
+            [1.0, 1.0, 0.0],
+            [0.0; 6],
+            &[],
+            None,
+        );
+        assert!(matches!(
+            load_geotiff(empty.path(), &LoadOptions::default()),
+            Err(LoadError::Image(_))
+        ));
+    }
+
+    #[test]
+    fn test_large_raster_averaged_down() {
+        // 5x4 raster limited to 2 samples: 3x3 blocks, partial at the edges
+        let data: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let file = write_geotiff((5, 4), &data, [10.0, 10.0, 0.0], [0.0; 6], &[], None);
+        let options = LoadOptions {
+            max_size: 2,
+            ..LoadOptions::default()
+        };
+        let terrain = load_geotiff(file.path(), &options).unwrap();
+
+        assert_eq!((terrain.width, terrain.height), (2, 2));
+        assert_eq!(terrain.cell_size, 30.0);
+        // Raster rows 0-2 average to the north row, row 3 alone to the south
+        assert_eq!(terrain.heights(), [16.0, 18.5, 6.0, 8.5]);
+        // First block centered 1 pixel in from the first pixel's center
+        assert_eq!(terrain.origin, DVec2::new(15.0, -15.0 - 30.0));
+    }
+}
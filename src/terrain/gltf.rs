@@ -0,0 +1,256 @@
+//! Binary glTF (.glb) mesh export.
+//!
+//! Writes the solid surface of a [`TerrainMesh`] as a single triangle
+//! primitive with positions, normals and either vertex colors (`COLOR_0`)
+//! or a texture such as a baked lightmap, so baked effects like ambient
+//! occlusion carry over to other tools.
+
+use std::fmt::Write;
+
+use glam::Vec3;
+
+use super::{TerrainMesh, Vertex};
+
+/// glTF component type for 32-bit floats.
+const FLOAT: u32 = 5126;
+/// glTF component type for 32-bit unsigned integers.
+const UNSIGNED_INT: u32 = 5125;
+/// glTF buffer view target for vertex attributes.
+const ARRAY_BUFFER: u32 = 34962;
+/// glTF buffer view target for indices.
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+/// glTF sampler filter for linear interpolation.
+const LINEAR: u32 = 9729;
+/// glTF sampler wrap mode clamping to the texture edge.
+const CLAMP_TO_EDGE: u32 = 33071;
+
+/// Texture mapped onto an exported mesh, such as a baked lightmap.
+pub struct Texture<'a> {
+    /// Texture coordinates per mesh vertex
+    pub uvs: &'a [[f32; 2]],
+    /// PNG-encoded image
+    pub png: &'a [u8],
+}
+
+/// Binary chunk under construction, with the JSON for its buffer views.
+#[derive(Default)]
+struct Buffer {
+    bin: Vec<u8>,
+    views: Vec<String>,
+}
+
+impl Buffer {
+    /// Append `data` as a new buffer view and return its index.
+    fn push(&mut self, data: &[u8], target: Option<u32>) -> usize {
+        let mut view = format!(
+            "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{}",
+            self.bin.len(),
+            data.len()
+        );
+        if let Some(target) = target {
+            let _ = write!(view, ",\"target\":{}", target);
+        }
+        view.push('}');
+        self.bin.extend_from_slice(data);
+        // Keep every view 4-byte aligned
+        self.bin.resize(self.bin.len().next_multiple_of(4), 0);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+}
+
+/// Serialize the mesh's triangles as a binary glTF 2.0 file.
+///
+/// With a `texture`, the mesh gets an unlit material showing it in place
+/// of the vertex colors, so baked lighting displays as-is in other engines.
+pub fn to_glb(mesh: &TerrainMesh, texture: Option<&Texture>) -> Vec<u8> {
+    let count = mesh.vertices.len();
+    let (min, max) = mesh.vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), v| {
+            let p = Vec3::from_array(v.position);
+            (min.min(p), max.max(p))
+        },
+    );
+
+    let mut buffer = Buffer::default();
+    let attribute = |buffer: &mut Buffer, get: fn(&Vertex) -> &[f32; 3]| {
+        let data: Vec<f32> = mesh.vertices.iter().flat_map(get).copied().collect();
+        buffer.push(bytemuck::cast_slice(&data), Some(ARRAY_BUFFER))
+    };
+    let mut accessors = Vec::new();
+    let mut accessor = |view: usize, component: u32, count: usize, kind: &str, extra: &str| {
+        accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"{}\"{}}}",
+            view, component, count, kind, extra
+        ));
+        accessors.len() - 1
+    };
+
+    let view = attribute(&mut buffer, |v| &v.position);
+    let bounds = format!(
+        ",\"min\":[{},{},{}],\"max\":[{},{},{}]",
+        min.x, min.y, min.z, max.x, max.y, max.z
+    );
+    let positions = accessor(view, FLOAT, count, "VEC3", &bounds);
+    let view = attribute(&mut buffer, |v| &v.normal);
+    let normals = accessor(view, FLOAT, count, "VEC3", "");
+    let view = buffer.push(
+        bytemuck::cast_slice(&mesh.triangle_indices),
+        Some(ELEMENT_ARRAY_BUFFER),
+    );
+    let indices = accessor(
+        view,
+        UNSIGNED_INT,
+        mesh.triangle_indices.len(),
+        "SCALAR",
+        "",
+    );
+
+    let mut primitive = format!(
+        "{{\"attributes\":{{\"POSITION\":{},\"NORMAL\":{}",
+        positions, normals
+    );
+    let mut materials = String::new();
+    match texture {
+        // The texture replaces the vertex colors, which glTF would
+        // otherwise multiply in again
+        Some(texture) => {
+            let view = buffer.push(bytemuck::cast_slice(texture.uvs), Some(ARRAY_BUFFER));
+            let uvs = accessor(view, FLOAT, texture.uvs.len(), "VEC2", "");
+            let image = buffer.push(texture.png, None);
+            let _ = write!(
+                primitive,
+                ",\"TEXCOORD_0\":{}}},\"indices\":{},\"material\":0",
+                uvs, indices
+            );
+            let _ = write!(
+                materials,
+                "\"extensionsUsed\":[\"KHR_materials_unlit\"],\
+                 \"materials\":[{{\"pbrMetallicRoughness\":{{\"baseColorTexture\":{{\"index\":0}},\
+                 \"metallicFactor\":0}},\"extensions\":{{\"KHR_materials_unlit\":{{}}}}}}],\
+                 \"textures\":[{{\"source\":0,\"sampler\":0}}],\
+                 \"samplers\":[{{\"magFilter\":{},\"minFilter\":{},\"wrapS\":{},\"wrapT\":{}}}],\
+                 \"images\":[{{\"bufferView\":{},\"mimeType\":\"image/png\"}}],",
+                LINEAR, LINEAR, CLAMP_TO_EDGE, CLAMP_TO_EDGE, image
+            );
+        }
+        None => {
+            let view = attribute(&mut buffer, |v| &v.color);
+            let colors = accessor(view, FLOAT, count, "VEC3", "");
+            let _ = write!(
+                primitive,
+                ",\"COLOR_0\":{}}},\"indices\":{}",
+                colors, indices
+            );
+        }
+    }
+    primitive.push_str(",\"mode\":4}");
+
+    let mut json = String::from("{\"asset\":{\"version\":\"2.0\",\"generator\":\"lrle\"},");
+    json.push_str("\"scene\":0,\"scenes\":[{\"nodes\":[0]}],\"nodes\":[{\"mesh\":0}],");
+    let _ = write!(
+        json,
+        "\"meshes\":[{{\"name\":\"terrain\",\"primitives\":[{}]}}],{}",
+        primitive, materials
+    );
+    let _ = write!(
+        json,
+        "\"buffers\":[{{\"byteLength\":{}}}],\"bufferViews\":[{}],\"accessors\":[{}]}}",
+        buffer.bin.len(),
+        buffer.views.join(","),
+        accessors.join(",")
+    );
+
+    // Chunks are 4-byte aligned: JSON padded with spaces, binary with zeros
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let bin = buffer.bin;
+
+    let total = 12 + 8 + json.len() + 8 + bin.len();
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(b"glTF");
+    out.extend_from_slice(&2u32.to_le_bytes());
+    out.extend_from_slice(&(total as u32).to_le_bytes());
+    out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"JSON");
+    out.extend_from_slice(&json);
+    out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"BIN\0");
+    out.extend_from_slice(&bin);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::TerrainData;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_glb_layout() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]; 3], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let glb = to_glb(&mesh, None);
+
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(read_u32(&glb, 4), 2);
+        assert_eq!(read_u32(&glb, 8) as usize, glb.len());
+
+        let json_len = read_u32(&glb, 12) as usize;
+        assert!(json_len.is_multiple_of(4));
+        assert_eq!(&glb[16..20], b"JSON");
+        let json = std::str::from_utf8(&glb[20..20 + json_len]).unwrap();
+        assert!(json.contains("\"COLOR_0\":3"));
+        assert!(json.contains(&format!("\"count\":{}", mesh.triangle_indices.len())));
+
+        let bin_start = 20 + json_len;
+        assert_eq!(&glb[bin_start + 4..bin_start + 8], b"BIN\0");
+        let bin_len = read_u32(&glb, bin_start) as usize;
+        assert_eq!(bin_len, 9 * 36 + mesh.triangle_indices.len() * 4);
+        assert_eq!(bin_start + 8 + bin_len, glb.len());
+    }
+
+    #[test]
+    fn test_glb_colors_follow_vertices() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0]; 2], None);
+        let mut mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        mesh.apply_occlusion(&[0.5; 4]);
+        let glb = to_glb(&mesh, None);
+
+        // Colors follow positions, normals and indices in the binary chunk
+        let json_len = read_u32(&glb, 12) as usize;
+        let colors = 20 + json_len + 8 + 2 * 4 * 12 + mesh.triangle_indices.len() * 4;
+        let first: &[u8] = bytemuck::cast_slice(&mesh.vertices[0].color);
+        assert_eq!(&glb[colors..colors + 12], first);
+    }
+
+    #[test]
+    fn test_glb_with_texture() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0]; 2], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let uvs = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+        let png = [1u8, 2, 3, 4, 5];
+        let glb = to_glb(
+            &mesh,
+            Some(&Texture {
+                uvs: &uvs,
+                png: &png,
+            }),
+        );
+
+        let json_len = read_u32(&glb, 12) as usize;
+        let json = std::str::from_utf8(&glb[20..20 + json_len]).unwrap();
+        assert!(json.contains("\"TEXCOORD_0\":3"));
+        assert!(json.contains("\"material\":0"));
+        assert!(!json.contains("COLOR_0"));
+        assert!(json.contains("\"mimeType\":\"image/png\""));
+        // Every view is padded to 4 bytes, including the odd-sized image
+        let bin_len = read_u32(&glb, 20 + json_len) as usize;
+        assert_eq!(bin_len, 2 * 48 + 6 * 4 + 32 + 8);
+        assert_eq!(20 + json_len + 8 + bin_len, glb.len());
+    }
+}
@@ -0,0 +1,114 @@
+//! Named value grids over the terrain samples.
+//!
+//! Elevation shapes the terrain, but any scalar field measured on the same
+//! ground can color it: temperature, rainfall, land cover classes. A
+//! [`Channel`] holds one such field as a [`Grid`] with one value per
+//! terrain sample, resampled from its own grid by world position so it
+//! needn't share the terrain's resolution or extent.
+
+use std::ops::Range;
+
+use super::{ao, TerrainData};
+
+/// Row-major values, `width` per row, indexed like [`TerrainData::at`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T> {
+    /// Number of columns (X dimension)
+    pub width: usize,
+    /// Number of rows (Z dimension)
+    pub height: usize,
+    values: Vec<T>,
+}
+
+impl<T: Copy> Grid<T> {
+    /// Grid of `values` in row-major order, `width` per row.
+    pub fn new(width: usize, values: Vec<T>) -> Self {
+        Self {
+            width,
+            height: values.len().checked_div(width).unwrap_or(0),
+            values,
+        }
+    }
+
+    /// Value of sample `(x, z)`.
+    pub fn at(&self, x: usize, z: usize) -> T {
+        self.values[z * self.width + x]
+    }
+
+    /// All values in row-major order.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Values in `columns` and `rows`, which must lie within the grid, as a
+    /// grid of their own.
+    pub fn crop(&self, columns: Range<usize>, rows: Range<usize>) -> Self {
+        Self::new(columns.len(), crop(&self.values, self.width, columns, rows))
+    }
+}
+
+/// Values in `columns` and `rows` of row-major `values`, `width` per row.
+pub fn crop<T: Copy>(
+    values: &[T],
+    width: usize,
+    columns: Range<usize>,
+    rows: Range<usize>,
+) -> Vec<T> {
+    rows.flat_map(|z| &values[z * width..][columns.clone()])
+        .copied()
+        .collect()
+}
+
+/// Named scalar field over the terrain samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    /// Name shown in the UI, e.g. "temperature"
+    pub name: String,
+    /// One value per terrain sample
+    pub grid: Grid<f32>,
+}
+
+/// Heights of `source` at each sample's world position in `onto`,
+/// bilinearly interpolated. Samples beyond `source`'s footprint take its
+/// nearest edge value; an empty `source` gives zeros.
+pub fn resample(source: &TerrainData, onto: &TerrainData) -> Vec<f64> {
+    (0..onto.height)
+        .flat_map(|z| {
+            (0..onto.width).map(move |x| {
+                if source.width == 0 || source.height == 0 {
+                    return 0.0;
+                }
+                let world = onto.world_position(x, z);
+                let gx = (world.x - source.origin.x) / source.cell_size;
+                let gz = (world.z - source.origin.y) / source.cell_size;
+                let gx = gx.clamp(0.0, (source.width - 1) as f64);
+                let gz = gz.clamp(0.0, (source.height - 1) as f64);
+                ao::sample(source, gx, gz).unwrap_or(0.0)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::DVec2;
+
+    #[test]
+    fn test_channel_resampled_by_world_position() {
+        let mut terrain = TerrainData::new(vec![vec![0.0; 3]; 2], None);
+        terrain.origin = DVec2::new(10.0, 0.0);
+        // Coarser field starting one sample in, e.g. temperatures
+        let mut field = TerrainData::new(vec![vec![5.0, 9.0]; 2], None);
+        field.origin = DVec2::new(11.0, 0.0);
+        field.cell_size = 2.0;
+        terrain.add_channel("temperature", &field);
+
+        let channel = &terrain.channels()[0];
+        assert_eq!(channel.name, "temperature");
+        assert_eq!((channel.grid.width, channel.grid.height), (3, 2));
+        // Clamped before the field, then halfway between its samples
+        assert_eq!(channel.grid.at(0, 1), 5.0);
+        assert_eq!(channel.grid.values()[..3], [5.0, 5.0, 7.0]);
+    }
+}
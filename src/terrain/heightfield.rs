@@ -0,0 +1,304 @@
+//! Ray and height queries against a terrain's surface.
+//!
+//! [`Heightfield`] views a [`TerrainData`] grid with its heights multiplied
+//! by a height scale, as the viewer draws it, and answers the queries that
+//! picking, camera collision and line of sight need, for library users as
+//! well:
+//!
+//! - [`sample_height`](Heightfield::sample_height) - the surface height at
+//!   a world-space (x, z), bilinearly interpolated between the samples
+//! - [`raycast`](Heightfield::raycast) - the first point where a ray meets
+//!   the surface
+//!
+//! A ray walks the grid cell by cell (a DDA traversal), skipping cells it
+//! passes above, and within each cell solves for where it meets the
+//! bilinear patch between the four corner samples. That's exact for the
+//! interpolated surface and costs one step per cell crossed, so rays
+//! across large terrains stay cheap.
+
+use glam::DVec3;
+
+use super::TerrainData;
+
+/// A terrain surface with its heights scaled.
+#[derive(Debug, Clone, Copy)]
+pub struct Heightfield<'a> {
+    terrain: &'a TerrainData,
+    height_scale: f64,
+}
+
+/// Where a ray meets the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// World-space point hit, with the scaled surface height
+    pub position: DVec3,
+    /// Distance from the ray's origin along its direction
+    pub distance: f64,
+    /// Unit normal of the scaled surface at the point
+    pub normal: DVec3,
+    /// Grid cell (column, row) hit, named by its lower corner sample
+    pub cell: (usize, usize),
+}
+
+impl<'a> Heightfield<'a> {
+    /// The surface of `terrain` with heights multiplied by `height_scale`.
+    pub fn new(terrain: &'a TerrainData, height_scale: f64) -> Self {
+        Self {
+            terrain,
+            height_scale,
+        }
+    }
+
+    /// The terrain this surface is drawn from.
+    pub fn terrain(&self) -> &'a TerrainData {
+        self.terrain
+    }
+
+    /// Scaled height at world-space (`x`, `z`), bilinearly interpolated, or
+    /// `None` outside the grid footprint.
+    pub fn sample_height(&self, x: f64, z: f64) -> Option<f64> {
+        Some(self.terrain.height_at(x, z)? * self.height_scale)
+    }
+
+    /// First point where the ray from `origin` along `direction` meets the
+    /// surface, or `None` if it misses, or starts below the surface or
+    /// enters the grid's side below it.
+    pub fn raycast(&self, origin: DVec3, direction: DVec3) -> Option<RayHit> {
+        self.raycast_within(origin, direction, f64::INFINITY)
+    }
+
+    /// Like [`raycast`](Self::raycast), but only hits within
+    /// `max_distance` of `origin`, e.g. to test a segment between two
+    /// points.
+    pub fn raycast_within(
+        &self,
+        origin: DVec3,
+        direction: DVec3,
+        max_distance: f64,
+    ) -> Option<RayHit> {
+        let terrain = self.terrain;
+        let (width, height) = (terrain.width, terrain.height);
+        if width < 2 || height < 2 || direction == DVec3::ZERO {
+            return None;
+        }
+        let direction = direction.normalize();
+        let cell_size = terrain.cell_size;
+        let (low, high) = terrain.height_bounds();
+        let (low, high) = (low * self.height_scale, high * self.height_scale);
+        let min = DVec3::new(terrain.origin.x, low.min(high), terrain.origin.y);
+        let max = DVec3::new(
+            terrain.origin.x + (width - 1) as f64 * cell_size,
+            low.max(high),
+            terrain.origin.y + (height - 1) as f64 * cell_size,
+        );
+
+        // Slab test against the box around the surface
+        let (mut t0, mut t1) = (0.0_f64, max_distance);
+        for axis in 0..3 {
+            let (o, d) = (origin[axis], direction[axis]);
+            if d.abs() < f64::EPSILON {
+                if o < min[axis] || o > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let (a, b) = ((min[axis] - o) / d, (max[axis] - o) / d);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        if t0 > t1 {
+            return None;
+        }
+        let entry = (origin + direction * t0).clamp(min, max);
+        if entry.y < self.sample_height(entry.x, entry.z)? {
+            return None;
+        }
+
+        // Grid coordinates along the ray, and the cell the ray enters
+        let grid = |t: f64| {
+            let p = origin + direction * t;
+            (
+                (p.x - terrain.origin.x) / cell_size,
+                (p.z - terrain.origin.y) / cell_size,
+            )
+        };
+        let (gx, gz) = grid(t0);
+        let mut cell = (
+            (gx.floor().max(0.0) as usize).min(width - 2),
+            (gz.floor().max(0.0) as usize).min(height - 2),
+        );
+        // Distance to the next cell boundary along each axis, and between
+        // boundaries
+        let axis = |o: f64, d: f64, start: f64, index: usize| {
+            if d.abs() < f64::EPSILON {
+                return (f64::INFINITY, f64::INFINITY);
+            }
+            let boundary = if d > 0.0 { index + 1 } else { index };
+            let t = (start + boundary as f64 * cell_size - o) / d;
+            (t, cell_size / d.abs())
+        };
+        let (mut next_x, step_x) = axis(origin.x, direction.x, terrain.origin.x, cell.0);
+        let (mut next_z, step_z) = axis(origin.z, direction.z, terrain.origin.y, cell.1);
+
+        let mut t_enter = t0;
+        loop {
+            let t_exit = next_x.min(next_z).min(t1);
+            if let Some(hit) = self.hit_in_cell(origin, direction, cell, t_enter, t_exit) {
+                return Some(hit);
+            }
+            if t_exit >= t1 {
+                return None;
+            }
+            if next_x < next_z {
+                cell.0 = cell.0.checked_add_signed(direction.x.signum() as isize)?;
+                next_x += step_x;
+            } else {
+                cell.1 = cell.1.checked_add_signed(direction.z.signum() as isize)?;
+                next_z += step_z;
+            }
+            if cell.0 > width - 2 || cell.1 > height - 2 {
+                return None;
+            }
+            t_enter = t_exit;
+        }
+    }
+
+    /// Where the ray meets the bilinear patch of `cell` between distances
+    /// `t_enter` and `t_exit`, if it does.
+    fn hit_in_cell(
+        &self,
+        origin: DVec3,
+        direction: DVec3,
+        (cx, cz): (usize, usize),
+        t_enter: f64,
+        t_exit: f64,
+    ) -> Option<RayHit> {
+        let terrain = self.terrain;
+        let h = |x: usize, z: usize| terrain.at(x, z) * self.height_scale;
+        let (h00, h10, h01, h11) = (h(cx, cz), h(cx + 1, cz), h(cx, cz + 1), h(cx + 1, cz + 1));
+        // The ray is lowest at one end of its span, so a span above every
+        // corner passes over the patch
+        let (y_enter, y_exit) = (
+            origin.y + direction.y * t_enter,
+            origin.y + direction.y * t_exit,
+        );
+        if y_enter.min(y_exit) > h00.max(h10).max(h01).max(h11) {
+            return None;
+        }
+
+        // Patch coordinates at `t_enter` and their rate along the ray
+        let cell_size = terrain.cell_size;
+        let start = origin + direction * t_enter;
+        let u0 = (start.x - terrain.origin.x) / cell_size - cx as f64;
+        let v0 = (start.z - terrain.origin.y) / cell_size - cz as f64;
+        let (du, dv) = (direction.x / cell_size, direction.z / cell_size);
+        // h(u, v) = h00 + a u + b v + k u v
+        let (a, b, k) = (h10 - h00, h01 - h00, h00 - h10 - h01 + h11);
+        // Height of the ray above the patch, quadratic in the distance past
+        // `t_enter`: qa s² + qb s + qc
+        let qa = -k * du * dv;
+        let qb = direction.y - (a * du + b * dv + k * (u0 * dv + v0 * du));
+        let qc = y_enter - (h00 + a * u0 + b * v0 + k * u0 * v0);
+
+        let span = t_exit - t_enter;
+        let tolerance = 1e-9 * cell_size;
+        let s = first_root(qa, qb, qc, span + tolerance)?.clamp(0.0, span);
+
+        let distance = t_enter + s;
+        let (u, v) = ((u0 + du * s).clamp(0.0, 1.0), (v0 + dv * s).clamp(0.0, 1.0));
+        let point = origin + direction * distance;
+        let surface = h00 + a * u + b * v + k * u * v;
+        let slope_x = (a + k * v) / cell_size;
+        let slope_z = (b + k * u) / cell_size;
+        Some(RayHit {
+            position: DVec3::new(point.x, surface, point.z),
+            distance,
+            normal: DVec3::new(-slope_x, 1.0, -slope_z).normalize(),
+            cell: (cx, cz),
+        })
+    }
+}
+
+/// Smallest `s` in `0..=limit` where `qa s² + qb s + qc` reaches zero from
+/// above, or `None` if it stays above zero. Starting at zero and rising,
+/// the ray grazes the surface rather than meeting it.
+fn first_root(qa: f64, qb: f64, qc: f64, limit: f64) -> Option<f64> {
+    if qc < 0.0 || (qc == 0.0 && (qb < 0.0 || (qb == 0.0 && qa < 0.0))) {
+        return Some(0.0);
+    }
+    let in_range = |s: f64| s > 0.0 && s <= limit;
+    if qa.abs() < 1e-12 {
+        let s = -qc / qb;
+        return (qb < 0.0 && in_range(s)).then_some(s);
+    }
+    let discriminant = qb * qb - 4.0 * qa * qc;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // The stable form of the quadratic formula, avoiding cancellation
+    let q = -0.5 * (qb + qb.signum() * discriminant.sqrt());
+    let (r1, r2) = (q / qa, if q == 0.0 { f64::INFINITY } else { qc / q });
+    [r1.min(r2), r1.max(r2)].into_iter().find(|&s| in_range(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::DVec2;
+
+    /// A 9 by 9 grid of heights `f(x, z)`, two units a cell, off the origin.
+    fn grid(f: impl Fn(f64, f64) -> f64) -> TerrainData {
+        let rows = (0..9)
+            .map(|z| (0..9).map(|x| f(x as f64, z as f64)).collect())
+            .collect();
+        let mut terrain = TerrainData::new(rows, None);
+        terrain.origin = DVec2::new(-4.0, 10.0);
+        terrain.cell_size = 2.0;
+        terrain
+    }
+
+    #[test]
+    fn test_raycast_lands_on_the_bilinear_surface() {
+        // A saddle, curved within every cell
+        let terrain = grid(|x, z| (x - 4.0) * (z - 4.0) * 0.5);
+        let field = Heightfield::new(&terrain, 3.0);
+        let origin = DVec3::new(-20.0, 60.0, 0.0);
+        for target in [DVec3::new(3.3, 0.0, 21.7), DVec3::new(9.1, 0.0, 24.0)] {
+            let direction = target - origin;
+            let hit = field.raycast(origin, direction).unwrap();
+            let expected = field.sample_height(hit.position.x, hit.position.z).unwrap();
+            assert!((hit.position.y - expected).abs() < 1e-6);
+            let along = origin + direction.normalize() * hit.distance;
+            assert!((along - hit.position).length() < 1e-6);
+            assert!(hit.normal.is_normalized() && hit.normal.y > 0.0);
+            // Nothing was passed through on the way
+            for i in 0..1000 {
+                let p = origin + direction.normalize() * hit.distance * (i as f64 / 1000.0);
+                if let Some(ground) = field.sample_height(p.x, p.z) {
+                    assert!(p.y >= ground - 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_raycast_within_stops_short() {
+        let terrain = grid(|x, _| if x == 6.0 { 10.0 } else { 0.0 });
+        let field = Heightfield::new(&terrain, 1.0);
+        let origin = DVec3::new(-4.0, 2.0, 14.0);
+        // The wall's slope rises through 2 a fifth of the way up from x = 6
+        let hit = field.raycast(origin, DVec3::X).unwrap();
+        assert!((hit.position - DVec3::new(6.4, 2.0, 14.0)).length() < 1e-9);
+        assert_eq!(hit.cell, (5, 2));
+        assert_eq!(field.raycast_within(origin, DVec3::X, 10.0), None);
+
+        // Straight down, along a grid line and from below
+        let hit = field.raycast(DVec3::new(0.0, 5.0, 12.0), DVec3::NEG_Y);
+        assert_eq!(
+            hit.map(|hit| hit.position),
+            Some(DVec3::new(0.0, 0.0, 12.0))
+        );
+        assert_eq!(field.raycast(DVec3::new(8.0, 1.0, 14.0), DVec3::X), None);
+        assert_eq!(field.sample_height(-5.0, 12.0), None);
+    }
+}
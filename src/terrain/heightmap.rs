@@ -0,0 +1,180 @@
+//! Grayscale heightmap images.
+//!
+//! Heightmaps from terrain generators such as World Machine or Gaea are
+//! images whose brightness is the height: one sample per pixel, with the
+//! top row at z = 0. PNGs may be 8 or 16 bits per channel; JPEGs are always
+//! 8 bits and lossy, so they show compression steps on gentle slopes.
+//!
+//! Heights are measured in 8-bit gray levels, 0 to 255, whatever the bit
+//! depth: a 16-bit image covers the same range in finer steps. Color images
+//! are converted to their luminance, and alpha is ignored.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+use super::loader::LoadError;
+use super::TerrainData;
+
+/// Load terrain heights from a PNG or JPEG heightmap, picked by the file's
+/// signature.
+///
+/// # Errors
+///
+/// Returns [`LoadError`] if the file cannot be read or decoded.
+pub fn load_heightmap_image<P: AsRef<Path>>(path: P) -> Result<TerrainData, LoadError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|_| LoadError::FileNotFound(path.display().to_string()))?;
+    if bytes.starts_with(b"\x89PNG") {
+        parse_png(&bytes)
+    } else {
+        parse_jpeg(&bytes)
+    }
+}
+
+/// Decode a PNG heightmap.
+fn parse_png(bytes: &[u8]) -> Result<TerrainData, LoadError> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    // Palettes and sub-byte depths become 8-bit; 16-bit stays 16-bit
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(image_error)?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| LoadError::Image("image too large".to_string()))?;
+    let mut buf = vec![0; size];
+    let info = reader.next_frame(&mut buf).map_err(image_error)?;
+    let buf = &buf[..info.buffer_size()];
+
+    let channels = info.color_type.samples();
+    let levels: Vec<f64> = match info.bit_depth {
+        png::BitDepth::Sixteen => buf
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / 257.0)
+            .collect(),
+        _ => buf.iter().map(|&b| b as f64).collect(),
+    };
+    let heights = levels.chunks_exact(channels).map(|pixel| match pixel {
+        [gray] | [gray, _] => *gray,
+        [r, g, b, ..] => luminance(*r, *g, *b),
+        _ => 0.0,
+    });
+    grid(heights, info.width as usize, info.height as usize)
+}
+
+/// Decode a JPEG heightmap.
+fn parse_jpeg(bytes: &[u8]) -> Result<TerrainData, LoadError> {
+    let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::Luma);
+    let mut decoder = JpegDecoder::new_with_options(bytes, options);
+    let pixels = decoder.decode().map_err(image_error)?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| LoadError::Image("missing JPEG header".to_string()))?;
+    grid(
+        pixels.into_iter().map(f64::from),
+        info.width as usize,
+        info.height as usize,
+    )
+}
+
+/// Rec. 709 luminance of a color.
+fn luminance(r: f64, g: f64, b: f64) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Arrange row-major heights into a grid.
+fn grid(
+    heights: impl Iterator<Item = f64>,
+    width: usize,
+    height: usize,
+) -> Result<TerrainData, LoadError> {
+    let heights: Vec<f64> = heights.collect();
+    if width == 0 || height == 0 {
+        return Err(LoadError::EmptyFile);
+    }
+    Ok(TerrainData::from_heights(width, heights, None))
+}
+
+fn image_error(err: impl std::fmt::Display) -> LoadError {
+    LoadError::Image(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_png(
+        (width, height): (u32, u32),
+        color: png::ColorType,
+        depth: png::BitDepth,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn test_gray8_png() {
+        let png = encode_png(
+            (3, 2),
+            png::ColorType::Grayscale,
+            png::BitDepth::Eight,
+            &[0, 10, 20, 30, 40, 255],
+        );
+        let terrain = parse_png(&png).unwrap();
+
+        assert_eq!((terrain.width, terrain.height), (3, 2));
+        assert_eq!(terrain.heights(), [0.0, 10.0, 20.0, 30.0, 40.0, 255.0]);
+    }
+
+    #[test]
+    fn test_gray16_png_keeps_precision() {
+        // Big-endian 16-bit samples: white, one step above black, and mid
+        let png = encode_png(
+            (3, 1),
+            png::ColorType::Grayscale,
+            png::BitDepth::Sixteen,
+            &[0xFF, 0xFF, 0x00, 0x01, 0x80, 0x80],
+        );
+        let terrain = parse_png(&png).unwrap();
+
+        assert_eq!(terrain.at(0, 0), 255.0);
+        assert_eq!(terrain.at(1, 0), 1.0 / 257.0);
+        assert_eq!(terrain.at(2, 0), 128.0);
+    }
+
+    #[test]
+    fn test_color_png_uses_luminance() {
+        let png = encode_png(
+            (2, 1),
+            png::ColorType::Rgba,
+            png::BitDepth::Eight,
+            &[255, 0, 0, 0, 200, 200, 200, 255],
+        );
+        let terrain = parse_png(&png).unwrap();
+
+        assert!((terrain.at(0, 0) - 0.2126 * 255.0).abs() < 1e-9);
+        assert!((terrain.at(1, 0) - 200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_invalid_images() {
+        assert!(matches!(
+            parse_png(b"\x89PNG\r\n"),
+            Err(LoadError::Image(_))
+        ));
+        assert!(matches!(
+            parse_jpeg(b"\xFF\xD8\xFF"),
+            Err(LoadError::Image(_))
+        ));
+    }
+}
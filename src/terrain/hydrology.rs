@@ -0,0 +1,427 @@
+//! Surface hydrology.
+//!
+//! D8 flow routing: every sample drains to the lowest of its eight
+//! neighbors, by drop per distance. Flow accumulation counts the samples
+//! draining through each sample, and a catchment is everything upstream of
+//! a pour point, outlined as polygons for display and GeoJSON export.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use glam::DVec2;
+
+use super::TerrainData;
+
+/// Neighbor offsets searched for the flow direction.
+const D8: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Flow directions and accumulation of a terrain.
+#[derive(Debug, Clone)]
+pub struct FlowGrid {
+    /// Sample each sample drains to, in row-major order; `None` for pits,
+    /// flats and edge samples with no lower neighbor
+    pub directions: Vec<Option<usize>>,
+    /// Number of samples draining through each sample, itself included
+    pub accumulation: Vec<u32>,
+}
+
+impl FlowGrid {
+    /// Route flow over `terrain`.
+    pub fn compute(terrain: &TerrainData) -> Self {
+        let directions = flow_directions(terrain);
+        let accumulation = flow_accumulation(terrain, &directions);
+        Self {
+            directions,
+            accumulation,
+        }
+    }
+
+    /// The sample with the most accumulated flow within `radius` samples of
+    /// `index`, so a pour point clicked next to a channel lands in it.
+    pub fn snap(&self, terrain: &TerrainData, index: usize, radius: usize) -> usize {
+        let (x, z) = (index % terrain.width, index / terrain.width);
+        let (x0, x1) = (
+            x.saturating_sub(radius),
+            (x + radius).min(terrain.width - 1),
+        );
+        let (z0, z1) = (
+            z.saturating_sub(radius),
+            (z + radius).min(terrain.height - 1),
+        );
+        let mut best = index;
+        for nz in z0..=z1 {
+            for nx in x0..=x1 {
+                let i = nz * terrain.width + nx;
+                if self.accumulation[i] > self.accumulation[best] {
+                    best = i;
+                }
+            }
+        }
+        best
+    }
+}
+
+/// D8 flow direction of every sample, in row-major order.
+pub fn flow_directions(terrain: &TerrainData) -> Vec<Option<usize>> {
+    let (width, height) = (terrain.width, terrain.height);
+    let mut directions = Vec::with_capacity(width * height);
+    for z in 0..height {
+        for x in 0..width {
+            let h = terrain.at(x, z);
+            let mut best = None;
+            let mut steepest = 0.0;
+            for (dx, dz) in D8 {
+                let (nx, nz) = (x as isize + dx, z as isize + dz);
+                if nx < 0 || nz < 0 || nx >= width as isize || nz >= height as isize {
+                    continue;
+                }
+                let (nx, nz) = (nx as usize, nz as usize);
+                let distance = if dx != 0 && dz != 0 {
+                    std::f64::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let slope = (h - terrain.at(nx, nz)) / distance;
+                if slope > steepest {
+                    steepest = slope;
+                    best = Some(nz * width + nx);
+                }
+            }
+            directions.push(best);
+        }
+    }
+    directions
+}
+
+/// Samples draining through each sample, itself included.
+///
+/// Flow only runs downhill, so visiting samples from the highest down
+/// passes each one's full count on before its receiver is visited.
+pub fn flow_accumulation(terrain: &TerrainData, directions: &[Option<usize>]) -> Vec<u32> {
+    let heights = terrain.heights();
+    let mut order: Vec<usize> = (0..heights.len()).collect();
+    order.sort_unstable_by(|&a, &b| heights[b].total_cmp(&heights[a]));
+
+    let mut accumulation = vec![1; heights.len()];
+    for i in order {
+        if let Some(next) = directions[i] {
+            accumulation[next] += accumulation[i];
+        }
+    }
+    accumulation
+}
+
+/// The area draining to a pour point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catchment {
+    /// Pour point sample
+    pub outlet: usize,
+    /// Samples draining to the outlet, the outlet included, ascending
+    pub samples: Vec<usize>,
+    /// Area in squared world units, each sample covering one cell
+    pub area: f64,
+    /// Outline as polygons of world-space (x, z) rings: an exterior ring
+    /// counterclockwise, then any holes clockwise. Rings are closed, their
+    /// last point repeating the first.
+    pub polygons: Vec<Vec<Vec<DVec2>>>,
+}
+
+impl Catchment {
+    /// Delineate the catchment of `outlet` from `flow`.
+    pub fn delineate(terrain: &TerrainData, flow: &FlowGrid, outlet: usize) -> Self {
+        let mut upstream: Vec<Vec<usize>> = vec![Vec::new(); flow.directions.len()];
+        for (i, direction) in flow.directions.iter().enumerate() {
+            if let Some(next) = direction {
+                upstream[*next].push(i);
+            }
+        }
+
+        let mut inside = vec![false; flow.directions.len()];
+        inside[outlet] = true;
+        let mut stack = vec![outlet];
+        while let Some(i) = stack.pop() {
+            for &up in &upstream[i] {
+                if !inside[up] {
+                    inside[up] = true;
+                    stack.push(up);
+                }
+            }
+        }
+
+        let samples: Vec<usize> = (0..inside.len()).filter(|&i| inside[i]).collect();
+        Self {
+            outlet,
+            area: samples.len() as f64 * terrain.cell_size * terrain.cell_size,
+            polygons: outline(terrain, &inside),
+            samples,
+        }
+    }
+
+    /// Serialize as a GeoJSON Feature with a MultiPolygon of the outline and
+    /// the area and pour point as properties.
+    pub fn to_geojson(&self, terrain: &TerrainData) -> String {
+        let outlet =
+            terrain.world_position(self.outlet % terrain.width, self.outlet / terrain.width);
+        let mut out = String::new();
+        out.push_str(
+            "{\"type\":\"Feature\",\"geometry\":{\"type\":\"MultiPolygon\",\"coordinates\":[",
+        );
+        for (p, polygon) in self.polygons.iter().enumerate() {
+            out.push_str(if p > 0 { ",[" } else { "[" });
+            for (r, ring) in polygon.iter().enumerate() {
+                out.push_str(if r > 0 { ",[" } else { "[" });
+                for (i, point) in ring.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    let _ = write!(out, "[{},{}]", point.x, point.y);
+                }
+                out.push(']');
+            }
+            out.push(']');
+        }
+        let _ = writeln!(
+            out,
+            "]}},\"properties\":{{\"area\":{},\"samples\":{},\"outlet\":[{},{},{}]}}}}",
+            self.area,
+            self.samples.len(),
+            outlet.x,
+            outlet.z,
+            outlet.y
+        );
+        out
+    }
+}
+
+/// Outline of the cells of the samples in `inside` as polygons.
+///
+/// Each sample owns the cell-sized square centered on it. Boundary edges
+/// run with the inside on their left, so exterior rings come out
+/// counterclockwise and holes clockwise. Where two cells only touch at a
+/// corner, the sharpest left turn is taken, keeping rings simple.
+fn outline(terrain: &TerrainData, inside: &[bool]) -> Vec<Vec<Vec<DVec2>>> {
+    let (width, height) = (terrain.width as isize, terrain.height as isize);
+    let is_inside = |x: isize, z: isize| {
+        x >= 0 && z >= 0 && x < width && z < height && inside[(z * width + x) as usize]
+    };
+
+    // Directed edges between cell corners; corner (i, j) is the lower-left
+    // corner of cell (i, j)
+    let mut edges: HashMap<(isize, isize), Vec<(isize, isize)>> = HashMap::new();
+    for z in 0..height {
+        for x in 0..width {
+            if !is_inside(x, z) {
+                continue;
+            }
+            let sides = [
+                ((0, -1), (x, z), (x + 1, z)),
+                ((1, 0), (x + 1, z), (x + 1, z + 1)),
+                ((0, 1), (x + 1, z + 1), (x, z + 1)),
+                ((-1, 0), (x, z + 1), (x, z)),
+            ];
+            for ((dx, dz), from, to) in sides {
+                if !is_inside(x + dx, z + dz) {
+                    edges.entry(from).or_default().push(to);
+                }
+            }
+        }
+    }
+
+    let mut rings = Vec::new();
+    let mut starts: Vec<(isize, isize)> = edges.keys().copied().collect();
+    starts.sort_unstable();
+    for start in starts {
+        while let Some(first) = edges.get_mut(&start).and_then(Vec::pop) {
+            let mut ring = vec![start];
+            let (mut from, mut at) = (start, first);
+            while at != start {
+                ring.push(at);
+                let outgoing = edges.get_mut(&at).expect("boundary edges form rings");
+                let heading = (at.0 - from.0, at.1 - from.1);
+                // Leftmost turn: left, then straight, then right
+                let left = (-heading.1, heading.0);
+                let right = (heading.1, -heading.0);
+                let pick = [left, heading, right]
+                    .iter()
+                    .find_map(|d| {
+                        outgoing
+                            .iter()
+                            .position(|&to| to == (at.0 + d.0, at.1 + d.1))
+                    })
+                    .expect("boundary edges form rings");
+                from = at;
+                at = outgoing.swap_remove(pick);
+            }
+            ring.push(start);
+            rings.push(simplify(ring));
+        }
+    }
+
+    // Holes run clockwise and belong to the exterior ring around them
+    let (exteriors, holes): (Vec<_>, Vec<_>) = rings.into_iter().partition(|r| signed_area(r) > 0);
+    let mut polygons: Vec<Vec<Vec<(isize, isize)>>> =
+        exteriors.into_iter().map(|ring| vec![ring]).collect();
+    for hole in holes {
+        let corner = hole[0];
+        if let Some(polygon) = polygons.iter_mut().find(|p| contains(&p[0], corner)) {
+            polygon.push(hole);
+        }
+    }
+
+    let to_world = |(i, j): (isize, isize)| {
+        DVec2::new(
+            terrain.origin.x + (i as f64 - 0.5) * terrain.cell_size,
+            terrain.origin.y + (j as f64 - 0.5) * terrain.cell_size,
+        )
+    };
+    polygons
+        .into_iter()
+        .map(|polygon| {
+            polygon
+                .into_iter()
+                .map(|ring| ring.into_iter().map(to_world).collect())
+                .collect()
+        })
+        .collect()
+}
+
+/// Drop corners in the middle of straight runs of a closed ring.
+fn simplify(ring: Vec<(isize, isize)>) -> Vec<(isize, isize)> {
+    // The ring repeats its start at the end
+    let n = ring.len() - 1;
+    let turns = |i: usize| {
+        let (prev, at, next) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+        (at.0 - prev.0) * (next.1 - at.1) != (at.1 - prev.1) * (next.0 - at.0)
+    };
+    let mut simple: Vec<_> = (0..n).filter(|&i| turns(i)).map(|i| ring[i]).collect();
+    simple.push(simple[0]);
+    simple
+}
+
+/// Twice the signed area of a closed ring, positive when counterclockwise.
+fn signed_area(ring: &[(isize, isize)]) -> isize {
+    ring.windows(2)
+        .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+        .sum()
+}
+
+/// Whether corner `point` lies inside the closed `ring`, by the even-odd
+/// rule on a ray through the cell centers to its right.
+fn contains(ring: &[(isize, isize)], point: (isize, isize)) -> bool {
+    // Offset by half a cell so the ray never passes through a corner
+    let (px, pz) = (point.0 as f64 + 0.5, point.1 as f64 + 0.5);
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let ((x0, z0), (x1, z1)) = (w[0], w[1]);
+        if (z0 as f64 > pz) != (z1 as f64 > pz) {
+            let x = x0 as f64 + (pz - z0 as f64) / (z1 - z0) as f64 * (x1 - x0) as f64;
+            if x > px {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A V-shaped valley draining along x = 2 toward z = 0.
+    fn valley() -> TerrainData {
+        let points = (0..4)
+            .map(|z| {
+                (0..5)
+                    .map(|x: i32| z as f64 + (x - 2).abs() as f64 * 2.0)
+                    .collect()
+            })
+            .collect();
+        TerrainData::new(points, None)
+    }
+
+    #[test]
+    fn test_flow_follows_valley() {
+        let terrain = valley();
+        let flow = FlowGrid::compute(&terrain);
+
+        // Flanks drain toward the axis, diagonally where that is steeper,
+        // and the axis drains downstream
+        assert_eq!(flow.directions[2 * 5], Some(5 + 1));
+        assert_eq!(flow.directions[2 * 5 + 2], Some(5 + 2));
+        assert_eq!(flow.directions[2], None);
+        // Everything ends at the lowest sample
+        assert_eq!(flow.accumulation[2], 20);
+        assert_eq!(flow.accumulation[3 * 5], 1);
+        // A click beside the axis snaps onto it
+        assert_eq!(flow.snap(&terrain, 5 + 3, 1), 2);
+    }
+
+    #[test]
+    fn test_catchment_of_outlet() {
+        let terrain = valley();
+        let flow = FlowGrid::compute(&terrain);
+
+        let whole = Catchment::delineate(&terrain, &flow, 2);
+        assert_eq!(whole.samples.len(), 20);
+        assert_eq!(whole.area, 20.0);
+        // One rectangle around the grid, counterclockwise
+        assert_eq!(
+            whole.polygons,
+            vec![vec![vec![
+                DVec2::new(-0.5, -0.5),
+                DVec2::new(4.5, -0.5),
+                DVec2::new(4.5, 3.5),
+                DVec2::new(-0.5, 3.5),
+                DVec2::new(-0.5, -0.5),
+            ]]]
+        );
+
+        // The top of a flank drains nothing else
+        let single = Catchment::delineate(&terrain, &flow, 3 * 5);
+        assert_eq!(single.samples, vec![3 * 5]);
+        assert_eq!(single.polygons[0][0].len(), 5);
+    }
+
+    #[test]
+    fn test_outline_with_hole_and_corner_touch() {
+        let terrain = TerrainData::new(vec![vec![0.0; 5]; 5], None);
+        // A ring of cells around (1..4, 1..4) missing its center, plus a
+        // cell touching its corner diagonally
+        let mut inside = vec![false; 25];
+        for z in 1..4 {
+            for x in 1..4 {
+                inside[z * 5 + x] = (x, z) != (2, 2);
+            }
+        }
+        inside[4 * 5 + 4] = true;
+        let polygons = outline(&terrain, &inside);
+
+        assert_eq!(polygons.len(), 2);
+        let square = polygons.iter().find(|p| p.len() == 2).unwrap();
+        assert_eq!(square[0].len(), 5);
+        // The hole runs clockwise around the missing center cell
+        assert_eq!(square[1][0], DVec2::new(1.5, 1.5));
+        assert_eq!(square[1][1], DVec2::new(1.5, 2.5));
+    }
+
+    #[test]
+    fn test_geojson_feature() {
+        let terrain = valley();
+        let flow = FlowGrid::compute(&terrain);
+        let json = Catchment::delineate(&terrain, &flow, 3 * 5).to_geojson(&terrain);
+
+        assert!(json.starts_with("{\"type\":\"Feature\",\"geometry\":{\"type\":\"MultiPolygon\""));
+        assert!(json.contains("[[[[-0.5,2.5],[0.5,2.5],[0.5,3.5],[-0.5,3.5],[-0.5,2.5]]]]"));
+        assert!(json.contains("\"properties\":{\"area\":1,\"samples\":1,\"outlet\":[0,3,7]}"));
+        assert_eq!(json.matches('[').count(), json.matches(']').count());
+    }
+}
@@ -0,0 +1,199 @@
+//! Texture-space lightmap baking.
+//!
+//! Bakes the solid shader's look (mesh colors, including baked ambient
+//! occlusion, lit by Lambert hillshade and cast sun shadows) into a single
+//! texture stretched over the grid, so exported meshes look the same in
+//! other engines without a lighting shader.
+
+use glam::{DVec3, Vec3};
+
+use super::colors::linear_to_srgb;
+use super::{sun, TerrainData, TerrainMesh};
+
+/// Texels per grid cell along each axis.
+const TEXELS_PER_CELL: usize = 4;
+/// Largest texture dimension; dense grids get fewer texels per cell.
+const MAX_TEXTURE_SIZE: usize = 8192;
+
+/// Light used for baking, matching the viewer's lighting settings.
+#[derive(Debug, Clone, Copy)]
+pub struct LightmapOptions {
+    /// Direction toward the light (need not be normalized)
+    pub direction: DVec3,
+    /// Ambient light strength (0.0 - 1.0)
+    pub ambient: f64,
+    /// Height multiplier the mesh was built with, for casting shadows
+    pub height_scale: f64,
+}
+
+/// Baked RGB texture covering the grid, in sRGB.
+///
+/// Texel row 0 lies along grid row 0 and texel column 0 along grid column
+/// 0; see [`grid_uvs`].
+#[derive(Debug, Clone)]
+pub struct Lightmap {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major RGB texels
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Lightmap {
+    /// Encode the lightmap as a PNG file.
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.as_flattened())?;
+        writer.finish()?;
+        Ok(out)
+    }
+}
+
+/// Texture coordinates of each mesh vertex, spanning the grid from the
+/// first to the last vertex in both directions.
+pub fn grid_uvs(terrain: &TerrainData) -> Vec<[f32; 2]> {
+    let du = 1.0 / terrain.width.saturating_sub(1).max(1) as f32;
+    let dv = 1.0 / terrain.height.saturating_sub(1).max(1) as f32;
+    (0..terrain.height)
+        .flat_map(|z| (0..terrain.width).map(move |x| [x as f32 * du, z as f32 * dv]))
+        .collect()
+}
+
+/// Linear to 8-bit sRGB, as applied by the viewer's sRGB surface.
+fn to_srgb(linear: f32) -> u8 {
+    (linear_to_srgb(linear) * 255.0).round() as u8
+}
+
+/// Bake lighting for `mesh`, which must be built from `terrain` on a flat
+/// grid (vertices in row-major grid order).
+pub fn bake(terrain: &TerrainData, mesh: &TerrainMesh, options: &LightmapOptions) -> Lightmap {
+    let (width, height) = (terrain.width, terrain.height);
+    if width < 2 || height < 2 || mesh.vertices.len() != width * height {
+        return Lightmap {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        };
+    }
+
+    let cells = (width - 1).max(height - 1);
+    let texels_per_cell = TEXELS_PER_CELL.min(MAX_TEXTURE_SIZE / cells).max(1);
+    let tex_width = (width - 1) * texels_per_cell;
+    let tex_height = (height - 1) * texels_per_cell;
+
+    let direction = options.direction.normalize_or(DVec3::Y);
+    let light = direction.as_vec3();
+    // Scaling heights by s casts the same shadows as dividing the light's
+    // rise by s over unscaled heights
+    let scale = options.height_scale.max(f64::EPSILON);
+    let lit = sun::sunlit(
+        terrain,
+        DVec3::new(direction.x, direction.y / scale, direction.z),
+    );
+    let ambient = options.ambient as f32;
+
+    let mut pixels = Vec::with_capacity(tex_width * tex_height);
+    for tz in 0..tex_height {
+        // Texel centers in grid coordinates
+        let gz = (tz as f32 + 0.5) / texels_per_cell as f32;
+        let z0 = (gz.floor() as usize).min(height - 2);
+        let fz = gz - z0 as f32;
+        for tx in 0..tex_width {
+            let gx = (tx as f32 + 0.5) / texels_per_cell as f32;
+            let x0 = (gx.floor() as usize).min(width - 2);
+            let fx = gx - x0 as f32;
+
+            let corners = [
+                (z0, x0, (1.0 - fx) * (1.0 - fz)),
+                (z0, x0 + 1, fx * (1.0 - fz)),
+                (z0 + 1, x0, (1.0 - fx) * fz),
+                (z0 + 1, x0 + 1, fx * fz),
+            ];
+            let (mut color, mut normal, mut shadow) = (Vec3::ZERO, Vec3::ZERO, 0.0);
+            for (z, x, weight) in corners {
+                let vertex = &mesh.vertices[z * width + x];
+                color += Vec3::from_array(vertex.color) * weight;
+                normal += Vec3::from_array(vertex.normal) * weight;
+                if lit[z * width + x] {
+                    shadow += weight;
+                }
+            }
+
+            // Same as the solid shader's Lambert term
+            let n_dot_l = normal.normalize_or_zero().dot(light).max(0.0) * shadow;
+            let lighting = ambient + n_dot_l * (1.0 - ambient);
+            let c = color * lighting;
+            pixels.push([to_srgb(c.x), to_srgb(c.y), to_srgb(c.z)]);
+        }
+    }
+
+    Lightmap {
+        width: tex_width,
+        height: tex_height,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: LightmapOptions = LightmapOptions {
+        direction: DVec3::new(0.0, 1.0, -1.0),
+        ambient: 0.3,
+        height_scale: 1.0,
+    };
+
+    #[test]
+    fn test_lightmap_size_and_uvs() {
+        let terrain = TerrainData::new(vec![vec![0.0; 5]; 3], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let map = bake(&terrain, &mesh, &OPTIONS);
+        assert_eq!((map.width, map.height), (16, 8));
+        assert_eq!(map.pixels.len(), 16 * 8);
+
+        let uvs = grid_uvs(&terrain);
+        assert_eq!(uvs.len(), mesh.vertices.len());
+        assert_eq!(uvs[0], [0.0, 0.0]);
+        assert_eq!(uvs[14], [1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_wall_shadow_is_baked() {
+        // Light from the south (-Z); the rows north of the wall are shaded
+        let mut points = vec![vec![0.0; 6]; 12];
+        points[4] = vec![50.0; 6];
+        let terrain = TerrainData::new(points, None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let map = bake(&terrain, &mesh, &OPTIONS);
+
+        let brightness = |x: usize, z: usize| {
+            let [r, g, b] = map.pixels[z * map.width + x];
+            r as u32 + g as u32 + b as u32
+        };
+        // Same height color, darker in the shadow
+        assert!(brightness(10, 9 * TEXELS_PER_CELL) < brightness(10, 2));
+    }
+
+    #[test]
+    fn test_png_signature() {
+        let map = Lightmap {
+            width: 2,
+            height: 1,
+            pixels: vec![[255, 0, 0], [0, 0, 255]],
+        };
+        let png = map.to_png().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_srgb_encoding() {
+        assert_eq!(to_srgb(0.0), 0);
+        assert_eq!(to_srgb(1.0), 255);
+        // Linear mid-grey is brighter in sRGB
+        assert_eq!(to_srgb(0.216), 128);
+    }
+}
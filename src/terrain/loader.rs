@@ -1,4 +1,4 @@
-//! .fdf file format parser.
+//! .fdf file format parser and writer, and picking a loader by file format.
 //!
 //! The .fdf format is a simple text-based terrain format:
 //! - Each line represents a row of height values
@@ -19,21 +19,39 @@
 //! 0,0xFF0000 1,0x00FF00
 //! 2,0x0000FF 3,0xFFFFFF
 //! ```
+//!
+//! Files are streamed a batch of lines at a time, each batch parsed in
+//! parallel, so huge files needn't fit in memory as text and can report
+//! their progress.
 
-use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 
+use rayon::prelude::*;
 use thiserror::Error;
 
+use super::ascii_grid::load_ascii_grid;
+use super::float_grid::load_float_grid;
+use super::geotiff::load_geotiff;
+use super::heightmap::load_heightmap_image;
+use super::raster;
 use super::TerrainData;
 
-/// Errors that can occur when loading .fdf files.
+/// Errors that can occur when loading terrain files.
 #[derive(Error, Debug)]
 pub enum LoadError {
     /// File could not be opened or read.
     #[error("Cannot open file: {0}")]
     FileNotFound(String),
 
+    /// File exists but could not be read or mapped.
+    #[error("Cannot read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
     /// A value could not be parsed.
     #[error("Parse error at line {line}: {message}")]
     ParseError { line: usize, message: String },
@@ -49,6 +67,132 @@ pub enum LoadError {
     /// File contains no data.
     #[error("File is empty")]
     EmptyFile,
+
+    /// Binary samples don't fill the size their header gives.
+    #[error("Data holds {actual} bytes, expected {expected} for the size in its header")]
+    SizeMismatch { actual: usize, expected: usize },
+
+    /// A heightmap image or raster could not be decoded.
+    #[error("Cannot decode image: {0}")]
+    Image(String),
+
+    /// The file is binary, but in none of the image or raster formats.
+    #[error("Unknown format: not a text grid, heightmap image, GeoTIFF or ASCII grid")]
+    UnknownFormat,
+
+    /// Loading was stopped through its progress callback.
+    #[error("Loading stopped")]
+    Stopped,
+}
+
+impl LoadError {
+    /// Line of the file the error is on, counting from 1, if known.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Self::ParseError { line, .. } => Some(*line),
+            Self::InconsistentRow { row, .. } => Some(*row),
+            _ => None,
+        }
+    }
+}
+
+/// Terrain file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TerrainFormat {
+    /// Text grid of heights, see [`load_fdf`]
+    Fdf,
+    /// Grayscale PNG or JPEG heightmap, see [`load_heightmap_image`]
+    Image,
+    /// Single-band GeoTIFF elevation model, see [`load_geotiff`]
+    #[value(name = "geotiff")]
+    GeoTiff,
+    /// ESRI ASCII grid, see [`load_ascii_grid`]
+    #[value(name = "asc")]
+    AsciiGrid,
+    /// ESRI float grid with a `.hdr` header, see [`load_float_grid`]
+    #[value(name = "flt")]
+    FloatGrid,
+}
+
+impl TerrainFormat {
+    /// Pick a format from a file extension, defaulting to .fdf.
+    pub fn from_path(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match ext.as_deref() {
+            Some("png" | "jpg" | "jpeg") => Self::Image,
+            Some("tif" | "tiff") => Self::GeoTiff,
+            Some("asc") => Self::AsciiGrid,
+            Some("flt") => Self::FloatGrid,
+            _ => Self::Fdf,
+        }
+    }
+}
+
+/// How to load a terrain file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadOptions {
+    /// File format, or `None` to pick it from the file's extension
+    pub format: Option<TerrainFormat>,
+    /// Longest side of a GeoTIFF, ASCII or float grid, in samples, before
+    /// it's averaged down (sampled down for float grids)
+    pub max_size: usize,
+    /// Height given to nodata samples of GeoTIFFs and ASCII or float grids, or
+    /// `None` to fill them from their valid neighbors
+    pub nodata_fill: Option<f64>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            max_size: raster::DEFAULT_MAX_SIZE,
+            nodata_fill: None,
+        }
+    }
+}
+
+/// Bytes of .fdf text read before parsing them as a batch
+const BATCH_BYTES: usize = 4 << 20;
+
+/// Load terrain data as set by `options`.
+///
+/// # Errors
+///
+/// Returns [`LoadError`] if the file cannot be read or parsed.
+pub fn load_terrain<P: AsRef<Path>>(
+    path: P,
+    options: &LoadOptions,
+) -> Result<TerrainData, LoadError> {
+    load_terrain_with_progress(path, options, |_| true)
+}
+
+/// Load terrain data as set by `options`. Only .fdf files report their
+/// progress: `on_progress` is told the fraction of the file read so far,
+/// and stops the load by returning false.
+///
+/// # Errors
+///
+/// Returns [`LoadError`] if the file cannot be read or parsed, or
+/// [`LoadError::Stopped`] if `on_progress` stopped it.
+pub fn load_terrain_with_progress<P: AsRef<Path>>(
+    path: P,
+    options: &LoadOptions,
+    on_progress: impl FnMut(f32) -> bool,
+) -> Result<TerrainData, LoadError> {
+    let path = path.as_ref();
+    match options
+        .format
+        .unwrap_or_else(|| TerrainFormat::from_path(path))
+    {
+        TerrainFormat::Fdf => load_fdf_with_progress(path, on_progress),
+        TerrainFormat::Image => load_heightmap_image(path),
+        TerrainFormat::GeoTiff => load_geotiff(path, options),
+        TerrainFormat::AsciiGrid => load_ascii_grid(path, options),
+        TerrainFormat::FloatGrid => load_float_grid(path, options),
+    }
 }
 
 /// Parse a single value which can be "height" or "height,0xRRGGBB".
@@ -61,7 +205,7 @@ pub enum LoadError {
 /// # Returns
 ///
 /// A tuple of (height, optional_color)
-fn parse_value(s: &str, line: usize) -> Result<(f32, Option<u32>), LoadError> {
+fn parse_value(s: &str, line: usize) -> Result<(f64, Option<u32>), LoadError> {
     let s = s.trim();
     if s.is_empty() {
         return Err(LoadError::ParseError {
@@ -72,7 +216,7 @@ fn parse_value(s: &str, line: usize) -> Result<(f32, Option<u32>), LoadError> {
 
     if let Some((height_str, color_str)) = s.split_once(',') {
         // Parse height
-        let height: f32 = height_str
+        let height: f64 = height_str
             .trim()
             .parse()
             .map_err(|_| LoadError::ParseError {
@@ -93,7 +237,7 @@ fn parse_value(s: &str, line: usize) -> Result<(f32, Option<u32>), LoadError> {
         Ok((height, Some(color)))
     } else {
         // Height only, no color
-        let height: f32 = s.parse().map_err(|_| LoadError::ParseError {
+        let height: f64 = s.parse().map_err(|_| LoadError::ParseError {
             line,
             message: format!("expected number, got '{}'", s),
         })?;
@@ -111,11 +255,25 @@ fn parse_value(s: &str, line: usize) -> Result<(f32, Option<u32>), LoadError> {
 ///
 /// Returns [`LoadError`] if the file cannot be read or parsed.
 pub fn load_fdf<P: AsRef<Path>>(path: P) -> Result<TerrainData, LoadError> {
-    let path = path.as_ref();
-    let content = fs::read_to_string(path)
-        .map_err(|_| LoadError::FileNotFound(path.display().to_string()))?;
+    load_fdf_with_progress(path, |_| true)
+}
 
-    parse_fdf_content(&content)
+/// Load terrain data from a .fdf file. `on_progress` is told the fraction
+/// of the file read after each batch of lines, and stops the load by
+/// returning false.
+///
+/// # Errors
+///
+/// Returns [`LoadError`] if the file cannot be read or parsed, or
+/// [`LoadError::Stopped`] if `on_progress` stopped it.
+pub fn load_fdf_with_progress<P: AsRef<Path>>(
+    path: P,
+    on_progress: impl FnMut(f32) -> bool,
+) -> Result<TerrainData, LoadError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|_| LoadError::FileNotFound(path.display().to_string()))?;
+    let len = file.metadata().map_or(0, |metadata| metadata.len());
+    parse_fdf(BufReader::new(file), len, on_progress)
 }
 
 /// Parse .fdf content from a string.
@@ -130,59 +288,137 @@ pub fn load_fdf<P: AsRef<Path>>(path: P) -> Result<TerrainData, LoadError> {
 ///
 /// Returns [`LoadError`] if the content cannot be parsed.
 pub fn parse_fdf_content(content: &str) -> Result<TerrainData, LoadError> {
-    let mut points: Vec<Vec<f32>> = Vec::new();
-    let mut colors: Vec<Vec<u32>> = Vec::new();
+    parse_fdf(content.as_bytes(), content.len() as u64, |_| true)
+}
+
+/// Parse the .fdf text of `reader`, `len` bytes long, a batch of lines at
+/// a time, telling `on_progress` the fraction read after each batch.
+fn parse_fdf(
+    mut reader: impl BufRead,
+    len: u64,
+    mut on_progress: impl FnMut(f32) -> bool,
+) -> Result<TerrainData, LoadError> {
+    let mut points: Vec<f64> = Vec::new();
+    let mut colors: Vec<u32> = Vec::new();
     let mut has_any_color = false;
     let mut expected_width: Option<usize> = None;
-
-    for (line_idx, line) in content.lines().enumerate() {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
+    let mut lines_read = 0;
+    let mut bytes_read = 0;
+
+    loop {
+        // Read a batch of lines, then parse them all at once
+        let first_line = lines_read + 1;
+        let mut batch = Vec::new();
+        let mut batch_bytes = 0;
+        while batch_bytes < BATCH_BYTES {
+            let mut line = String::new();
+            // Text that isn't UTF-8 is taken for a binary file
+            let read = reader
+                .read_line(&mut line)
+                .map_err(|err| match err.kind() {
+                    std::io::ErrorKind::InvalidData => LoadError::UnknownFormat,
+                    _ => LoadError::ParseError {
+                        line: lines_read + 1,
+                        message: err.to_string(),
+                    },
+                })?;
+            if read == 0 {
+                break;
+            }
+            lines_read += 1;
+            batch_bytes += read;
+            batch.push(line);
         }
+        if batch.is_empty() {
+            break;
+        }
+        bytes_read += batch_bytes;
+        let rows: Vec<_> = batch
+            .par_iter()
+            .enumerate()
+            .map(|(i, line)| parse_row(line, first_line + i))
+            .collect();
+
+        // Rows are checked in file order, so the first error is reported
+        for (i, row) in rows.into_iter().enumerate() {
+            let row = row?;
+            if row.is_empty() {
+                continue;
+            }
 
-        let mut row_heights: Vec<f32> = Vec::new();
-        let mut row_colors: Vec<u32> = Vec::new();
-
-        for value in line.split_whitespace() {
-            let (height, color) = parse_value(value, line_idx + 1)?;
-            row_heights.push(height);
-            // Default to white if no color specified
-            row_colors.push(color.unwrap_or(0xFFFFFF));
-            if color.is_some() {
-                has_any_color = true;
+            // Validate row width consistency
+            if let Some(expected) = expected_width {
+                if row.len() != expected {
+                    return Err(LoadError::InconsistentRow {
+                        row: first_line + i,
+                        actual: row.len(),
+                        expected,
+                    });
+                }
+            } else {
+                expected_width = Some(row.len());
             }
-        }
 
-        // Validate row width consistency
-        if let Some(expected) = expected_width {
-            if row_heights.len() != expected {
-                return Err(LoadError::InconsistentRow {
-                    row: line_idx + 1,
-                    actual: row_heights.len(),
-                    expected,
-                });
+            for (height, color) in row {
+                points.push(height);
+                // Default to white if no color specified
+                colors.push(color.unwrap_or(0xFFFFFF));
+                if color.is_some() {
+                    has_any_color = true;
+                }
             }
-        } else {
-            expected_width = Some(row_heights.len());
         }
-
-        points.push(row_heights);
-        colors.push(row_colors);
+        if !on_progress(bytes_read as f32 / len.max(1) as f32) {
+            return Err(LoadError::Stopped);
+        }
     }
 
-    if points.is_empty() {
+    let Some(width) = expected_width else {
         return Err(LoadError::EmptyFile);
-    }
+    };
 
     // Only include colors if at least one was specified
     let colors = if has_any_color { Some(colors) } else { None };
-    Ok(TerrainData::new(points, colors))
+    Ok(TerrainData::from_heights(width, points, colors))
+}
+
+/// Parse the values of one line, numbered `line` from 1; empty for a blank
+/// line.
+fn parse_row(line: &str, line_number: usize) -> Result<Vec<(f64, Option<u32>)>, LoadError> {
+    line.split_whitespace()
+        .map(|value| parse_value(value, line_number))
+        .collect()
+}
+
+/// Write `terrain` as .fdf content, one line per row, with each value's
+/// color suffix when the terrain has colors.
+///
+/// Heights are written in full, so [`parse_fdf_content`] reads the same
+/// values back; the grid's origin and cell size aren't kept.
+pub fn to_fdf(terrain: &TerrainData) -> String {
+    let mut out = String::new();
+    for (z, row) in terrain.rows().enumerate() {
+        for (x, height) in row.iter().enumerate() {
+            if x > 0 {
+                out.push(' ');
+            }
+            match &terrain.colors {
+                Some(colors) => {
+                    let color = colors[z * terrain.width + x];
+                    out.push_str(&format!("{height},0x{color:06X}"));
+                }
+                None => out.push_str(&height.to_string()),
+            }
+        }
+        out.push('\n');
+    }
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     #[test]
     fn test_parse_simple_fdf() {
@@ -191,8 +427,8 @@ mod tests {
 
         assert_eq!(terrain.width, 3);
         assert_eq!(terrain.height, 2);
-        assert_eq!(terrain.points[0], vec![0.0, 1.0, 2.0]);
-        assert_eq!(terrain.points[1], vec![3.0, 4.0, 5.0]);
+        assert_eq!(terrain.row(0), [0.0, 1.0, 2.0]);
+        assert_eq!(terrain.row(1), [3.0, 4.0, 5.0]);
         assert!(terrain.colors.is_none());
     }
 
@@ -203,8 +439,28 @@ mod tests {
 
         assert!(terrain.colors.is_some());
         let colors = terrain.colors.unwrap();
-        assert_eq!(colors[0][0], 0xFF0000);
-        assert_eq!(colors[0][1], 0x00FF00);
+        assert_eq!(colors[0], 0xFF0000);
+        assert_eq!(colors[1], 0x00FF00);
+    }
+
+    #[test]
+    fn test_write_round_trips() {
+        let content = "0,0xFF0000 1.5,0x00FF00\n-2,0x0000FF 3,0xFFFFFF";
+        let terrain = parse_fdf_content(content).unwrap();
+        assert_eq!(
+            to_fdf(&terrain),
+            "0,0xFF0000 1.5,0x00FF00\n-2,0x0000FF 3,0xFFFFFF\n"
+        );
+
+        let mut terrain = parse_fdf_content("0.1 1\n2 3").unwrap();
+        assert_eq!(to_fdf(&terrain), "0.1 1\n2 3\n");
+        terrain.paint((0, 0), 0.0, 0x123456);
+        let read = parse_fdf_content(&to_fdf(&terrain)).unwrap();
+        assert_eq!(read.heights(), terrain.heights());
+        assert_eq!(
+            read.colors,
+            Some(vec![0x123456, 0xFFFFFF, 0xFFFFFF, 0xFFFFFF])
+        );
     }
 
     #[test]
@@ -213,6 +469,15 @@ mod tests {
         let result = parse_fdf_content(content);
 
         assert!(matches!(result, Err(LoadError::InconsistentRow { .. })));
+        assert_eq!(result.unwrap_err().line(), Some(2));
+    }
+
+    #[test]
+    fn test_binary_file_is_unknown_format() {
+        let bytes = b"PK\x03\x04\xff\xfe\x00\x00\n";
+        let result = parse_fdf(&bytes[..], bytes.len() as u64, |_| true);
+        assert!(matches!(result, Err(LoadError::UnknownFormat)));
+        assert_eq!(parse_fdf_content("1 2\n3 x").unwrap_err().line(), Some(2));
     }
 
     #[test]
@@ -228,8 +493,8 @@ mod tests {
         let content = "-5 0 5\n-10 0 10";
         let terrain = parse_fdf_content(content).unwrap();
 
-        assert_eq!(terrain.points[0][0], -5.0);
-        assert_eq!(terrain.points[1][0], -10.0);
+        assert_eq!(terrain.at(0, 0), -5.0);
+        assert_eq!(terrain.at(0, 1), -10.0);
     }
 
     #[test]
@@ -240,11 +505,93 @@ mod tests {
         assert_eq!(terrain.height, 2);
     }
 
+    #[test]
+    fn test_load_streams_in_batches_with_progress() {
+        // Enough rows for several batches, with a bad row in the last one
+        let row = "1.25 2.5 3.75 5\n";
+        let rows = 2 * BATCH_BYTES / row.len() + 10;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.fdf");
+        fs::write(&path, row.repeat(rows)).unwrap();
+
+        let mut fractions = Vec::new();
+        let terrain = load_fdf_with_progress(&path, |f| {
+            fractions.push(f);
+            true
+        })
+        .unwrap();
+        assert_eq!((terrain.width, terrain.height), (4, rows));
+        assert_eq!(terrain.row(rows - 1), [1.25, 2.5, 3.75, 5.0]);
+        assert!(fractions.len() >= 3);
+        assert!(fractions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(fractions.last(), Some(&1.0));
+        assert!(matches!(
+            load_fdf_with_progress(&path, |_| false),
+            Err(LoadError::Stopped)
+        ));
+
+        fs::write(&path, row.repeat(rows) + "1 2\n").unwrap();
+        assert!(matches!(
+            load_fdf(&path),
+            Err(LoadError::InconsistentRow { row, .. }) if row == rows + 1
+        ));
+    }
+
     #[test]
     fn test_parse_float_heights() {
         let content = "0.5 1.5 2.5";
         let terrain = parse_fdf_content(content).unwrap();
 
-        assert_eq!(terrain.points[0], vec![0.5, 1.5, 2.5]);
+        assert_eq!(terrain.row(0), [0.5, 1.5, 2.5]);
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            TerrainFormat::from_path(Path::new("a.fdf")),
+            TerrainFormat::Fdf
+        );
+        assert_eq!(
+            TerrainFormat::from_path(Path::new("a.PNG")),
+            TerrainFormat::Image
+        );
+        assert_eq!(
+            TerrainFormat::from_path(Path::new("a.jpeg")),
+            TerrainFormat::Image
+        );
+        assert_eq!(
+            TerrainFormat::from_path(Path::new("dem.tif")),
+            TerrainFormat::GeoTiff
+        );
+        assert_eq!(
+            TerrainFormat::from_path(Path::new("dem.asc")),
+            TerrainFormat::AsciiGrid
+        );
+        assert_eq!(
+            TerrainFormat::from_path(Path::new("terrain")),
+            TerrainFormat::Fdf
+        );
+    }
+
+    #[test]
+    fn test_load_terrain_by_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heights.png");
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[7, 9]).unwrap();
+        writer.finish().unwrap();
+        fs::write(&path, &png).unwrap();
+
+        let terrain = load_terrain(&path, &LoadOptions::default()).unwrap();
+        assert_eq!(terrain.heights(), [7.0, 9.0]);
+        // Forcing the text format fails on the binary file
+        let options = LoadOptions {
+            format: Some(TerrainFormat::Fdf),
+            ..LoadOptions::default()
+        };
+        assert!(load_terrain(&path, &options).is_err());
     }
 }
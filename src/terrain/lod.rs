@@ -0,0 +1,208 @@
+//! Levels of detail for mesh chunks.
+//!
+//! Each chunk can carry coarser index sets over the same vertices, level
+//! `n` keeping every `2^n`-th row and column of the grid, so distant chunks
+//! draw a fraction of the triangles and lines without more vertex memory.
+//! The renderer picks a level per chunk from its distance to the camera
+//! each frame.
+//!
+//! A chunk's outline always keeps every sample: cells along it are drawn
+//! as fans from their center to each sample of the outline, so chunks at
+//! different levels meet without cracks.
+
+use std::ops::Range;
+
+/// Coarser levels built per chunk when levels of detail are on; the
+/// coarsest keeps every 16th sample.
+pub const LOD_LEVELS: usize = 4;
+
+/// Level of detail settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodConfig {
+    /// Whether distant chunks are drawn coarser
+    pub enabled: bool,
+    /// Distance, in chunk sizes, up to which chunks are drawn in full; each
+    /// doubling of it drops one level
+    pub distance: f32,
+}
+
+impl Default for LodConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            distance: 2.0,
+        }
+    }
+}
+
+impl LodConfig {
+    /// Level for a chunk `size` across whose nearest point is `distance`
+    /// away, from 0 (full detail) up to `levels`.
+    pub fn level(&self, distance: f32, size: f32, levels: usize) -> usize {
+        let full = self.distance * size;
+        if distance.is_nan() || distance <= full {
+            return 0;
+        }
+        ((distance / full).log2() as usize + 1).min(levels)
+    }
+}
+
+/// Index ranges of a chunk at one coarser level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodRanges {
+    /// Range of the triangle indices
+    pub triangles: Range<u32>,
+    /// Range of the line indices
+    pub lines: Range<u32>,
+}
+
+/// Number of coarser levels, up to `max`, a tile of `cells_x` x `cells_z`
+/// cells can be drawn at: each level's step must divide both sides.
+pub fn tile_levels(cells_x: usize, cells_z: usize, max: usize) -> usize {
+    (1..=max)
+        .take_while(|level| {
+            cells_x.is_multiple_of(1 << level) && cells_z.is_multiple_of(1 << level)
+        })
+        .count()
+}
+
+/// Line and triangle indices of the tile of cells from (`x0`, `z0`) to
+/// (`x1`, `z1`) in a grid of `width` x `height` samples, keeping every
+/// `step`-th row and column inside it. Like full-detail tiles, the tile
+/// draws the lines of its first row and column, and of its last only at
+/// the end of the grid.
+pub fn tile_indices(
+    (x0, z0): (usize, usize),
+    (x1, z1): (usize, usize),
+    (width, height): (usize, usize),
+    step: usize,
+) -> (Vec<u32>, Vec<u32>) {
+    let index = |x: usize, z: usize| (z * width + x) as u32;
+    let mut lines = Vec::new();
+    let mut triangles = Vec::new();
+
+    // Rows, then columns, in full along the outline
+    let last_z = if z1 == height - 1 { z1 } else { z1 - 1 };
+    for z in (z0..=last_z).step_by(step) {
+        let stride = if z == z0 || z == z1 { 1 } else { step };
+        for x in (x0..x1).step_by(stride) {
+            lines.extend([index(x, z), index(x + stride, z)]);
+        }
+    }
+    let last_x = if x1 == width - 1 { x1 } else { x1 - 1 };
+    for x in (x0..=last_x).step_by(step) {
+        let stride = if x == x0 || x == x1 { 1 } else { step };
+        for z in (z0..z1).step_by(stride) {
+            lines.extend([index(x, z), index(x, z + stride)]);
+        }
+    }
+
+    let mut outline = Vec::new();
+    for z in (z0..z1).step_by(step) {
+        for x in (x0..x1).step_by(step) {
+            let (right, bottom) = (x + step, z + step);
+            if x != x0 && z != z0 && right != x1 && bottom != z1 {
+                // Same winding as the full-detail cells
+                triangles.extend([index(x, z), index(x, bottom), index(right, z)]);
+                triangles.extend([index(right, z), index(x, bottom), index(right, bottom)]);
+                continue;
+            }
+
+            // Around the cell: down the left side, along the bottom, up the
+            // right side and back along the top, every sample on the outline
+            outline.clear();
+            let stride = |on_outline: bool| if on_outline { 1 } else { step };
+            let left = stride(x == x0);
+            outline.extend((z..bottom).step_by(left).map(|z| index(x, z)));
+            let below = stride(bottom == z1);
+            outline.extend((x..right).step_by(below).map(|x| index(x, bottom)));
+            let right_side = stride(right == x1);
+            outline.extend(
+                (z + 1..=bottom)
+                    .rev()
+                    .step_by(right_side)
+                    .map(|z| index(right, z)),
+            );
+            let above = stride(z == z0);
+            outline.extend((x + 1..=right).rev().step_by(above).map(|x| index(x, z)));
+
+            let center = index(x + step / 2, z + step / 2);
+            for (i, &a) in outline.iter().enumerate() {
+                let b = outline[(i + 1) % outline.len()];
+                triangles.extend([center, a, b]);
+            }
+        }
+    }
+
+    (lines, triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_level_by_distance() {
+        let lod = LodConfig::default();
+        assert_eq!(lod.level(100.0, 64.0, LOD_LEVELS), 0);
+        assert_eq!(lod.level(128.0, 64.0, LOD_LEVELS), 0);
+        assert_eq!(lod.level(200.0, 64.0, LOD_LEVELS), 1);
+        assert_eq!(lod.level(300.0, 64.0, LOD_LEVELS), 2);
+        assert_eq!(lod.level(1e6, 64.0, LOD_LEVELS), LOD_LEVELS);
+        assert_eq!(lod.level(1e6, 64.0, 1), 1);
+
+        assert_eq!(tile_levels(64, 64, LOD_LEVELS), 4);
+        assert_eq!(tile_levels(64, 12, LOD_LEVELS), 2);
+        assert_eq!(tile_levels(63, 64, LOD_LEVELS), 0);
+    }
+
+    /// Twice the signed area of a triangle in the (x, z) plane.
+    fn winding(a: u32, b: u32, c: u32, width: usize) -> i64 {
+        let p = |i: u32| ((i as usize % width) as i64, (i as usize / width) as i64);
+        let ((ax, az), (bx, bz), (cx, cz)) = (p(a), p(b), p(c));
+        (bx - ax) * (cz - az) - (bz - az) * (cx - ax)
+    }
+
+    #[test]
+    fn test_coarse_tile_covers_cells_without_cracks() {
+        let (width, height) = (9, 9);
+        let (lines, triangles) = tile_indices((0, 0), (8, 8), (width, height), 4);
+
+        // The triangles tile the 8x8 cells once, all wound the same way
+        let mut area = 0;
+        for tri in triangles.chunks(3) {
+            let w = winding(tri[0], tri[1], tri[2], width);
+            assert!(w < 0, "{tri:?}");
+            area -= w;
+        }
+        assert_eq!(area, 2 * 64);
+
+        // Every edge is shared by two triangles except along the outline,
+        // which is split at every sample
+        let mut edges: HashMap<(u32, u32), i32> = HashMap::new();
+        for tri in triangles.chunks(3) {
+            for (a, b) in [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])] {
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        let coord = |i: u32| (i as usize % width, i as usize / width);
+        for (&(a, b), &count) in &edges {
+            let ((ax, az), (bx, bz)) = (coord(a), coord(b));
+            let on_outline =
+                (ax == bx && (ax == 0 || ax == 8)) || (az == bz && (az == 0 || az == 8));
+            if on_outline {
+                assert_eq!(count, 1);
+                assert_eq!(ax.abs_diff(bx) + az.abs_diff(bz), 1);
+            } else {
+                assert_eq!(count, 2, "{:?} {:?}", coord(a), coord(b));
+            }
+        }
+
+        // Lines: the first row and column in full, the middle ones coarse
+        // and the last only at the end of the grid
+        assert_eq!(lines.len() / 2, (8 + 2 + 8) * 2);
+        let (inner, _) = tile_indices((0, 0), (8, 8), (20, 20), 4);
+        assert_eq!(inner.len() / 2, (8 + 2) * 2);
+    }
+}
@@ -3,10 +3,20 @@
 //! Converts [`TerrainData`] into GPU-ready vertex and index buffers
 //! for wireframe rendering.
 
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use bytemuck::{Pod, Zeroable};
-use glam::Vec3;
+use glam::{DVec3, Vec3};
+use rayon::prelude::*;
 
-use super::colors::{height_to_color, height_to_color_custom, ColorScheme, GradientConfig};
+use super::analysis;
+use super::colors::{
+    diverging_color, height_to_color, height_to_color_custom, hex_to_rgb, rgb_to_hex,
+    srgb_to_linear, ColorMode, ColorScheme, GradientConfig, SlopeBands,
+};
+use super::lod::{self, LodRanges};
+use super::scan;
 use super::TerrainData;
 
 /// GPU vertex data with position and color.
@@ -60,7 +70,6 @@ impl Vertex {
 
 /// Shading mode for normal calculation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-#[allow(dead_code)] // Flat variant reserved for future UI toggle
 pub enum ShadingMode {
     /// Flat shading - normals from height gradient
     Flat,
@@ -69,22 +78,152 @@ pub enum ShadingMode {
     Smooth,
 }
 
+/// How [`TerrainMesh::indices`] list the wireframe's segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineTopology {
+    /// Index pairs, one per segment (LineList)
+    #[default]
+    List,
+    /// Runs of connected segments, each ended by [`PRIMITIVE_RESTART`]
+    /// (LineStrip), about half as many indices for grid lines
+    Strip,
+}
+
+/// Index ending a line strip. Uploaded as 16-bit indices it becomes
+/// `0xFFFF`, still the restart value.
+pub const PRIMITIVE_RESTART: u32 = u32::MAX;
+
 /// Generated mesh ready for GPU upload.
 ///
 /// Contains vertices and indices for wireframe line rendering.
 /// The mesh is centered at the origin for orbital camera rotation.
+#[derive(Default)]
 pub struct TerrainMesh {
     /// Vertex data (position + color per vertex)
     pub vertices: Vec<Vertex>,
-    /// Index pairs for line segments (LineList topology)
+    /// Line segments, as index pairs or strips per `line_topology`
     pub indices: Vec<u32>,
+    /// How `indices` and `lod_indices` list the segments
+    pub line_topology: LineTopology,
     /// Triangle indices for solid rendering (TriangleList)
     pub triangle_indices: Vec<u32>,
+    /// Square tiles of the grid, each owning a contiguous range of both
+    /// index buffers, for culling parts of the terrain
+    pub chunks: Vec<MeshChunk>,
+    /// Line indices of the chunks' coarser levels of detail
+    pub lod_indices: Vec<u32>,
+    /// Triangle indices of the chunks' coarser levels of detail
+    pub lod_triangle_indices: Vec<u32>,
+    /// Axis-aligned bounding box of the vertices as `(min, max)`, or `None`
+    /// for an empty mesh
+    pub bounds: Option<(Vec3, Vec3)>,
+}
+
+/// Cells per side of a [`MeshChunk`].
+pub const CHUNK_SIZE: usize = 64;
+
+/// A tile of up to [`CHUNK_SIZE`] x [`CHUNK_SIZE`] grid cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshChunk {
+    /// Range of `TerrainMesh::triangle_indices`
+    pub triangles: Range<u32>,
+    /// Range of `TerrainMesh::indices`
+    pub lines: Range<u32>,
+    /// Minimum corner of the bounding box
+    pub min: Vec3,
+    /// Maximum corner of the bounding box
+    pub max: Vec3,
+    /// Coarser levels of detail, each halving the resolution of the one
+    /// before. Their ranges index `indices` and `triangle_indices` followed
+    /// by `lod_indices` and `lod_triangle_indices`, as uploaded together.
+    pub lods: Vec<LodRanges>,
+}
+
+impl MeshChunk {
+    /// Triangle index range at `level` of detail, the coarsest there is if
+    /// the chunk has fewer levels.
+    pub fn triangles_at(&self, level: usize) -> Range<u32> {
+        match level.min(self.lods.len()) {
+            0 => self.triangles.clone(),
+            level => self.lods[level - 1].triangles.clone(),
+        }
+    }
+
+    /// Line index range at `level` of detail, as for
+    /// [`triangles_at`](Self::triangles_at).
+    pub fn lines_at(&self, level: usize) -> Range<u32> {
+        match level.min(self.lods.len()) {
+            0 => self.lines.clone(),
+            level => self.lods[level - 1].lines.clone(),
+        }
+    }
+}
+
+/// Mean earth radius in meters, used for curved-earth and globe rendering.
+pub const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Options controlling mesh generation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshOptions {
+    /// Multiplier for height values (Y axis)
+    pub height_scale: f32,
+    /// Flat or smooth shading for normals
+    pub shading_mode: ShadingMode,
+    /// Quantity mapped to vertex colors
+    pub color_mode: ColorMode,
+    /// Window half-width in samples for the terrain index color modes;
+    /// 1 is the 3x3 window of GDAL's DEM tools
+    pub index_radius: usize,
+    /// Color gradient scheme for height coloring
+    pub color_scheme: ColorScheme,
+    /// Gradient used when `color_scheme` is Custom
+    pub gradient: GradientConfig,
+    /// Slope-angle bands used by the slope bands color mode
+    pub slope_bands: SlopeBands,
+    /// World-space point mapped to the mesh origin; `None` centers the grid
+    pub origin: Option<DVec3>,
+    /// Map the grid onto a sphere of [`EARTH_RADIUS`], treating world x/z
+    /// as longitude/latitude in degrees and heights as meters
+    pub globe: bool,
+    /// Fraction of wireframe lines to keep, in (0, 1]. Below 1 only every
+    /// Nth grid line is kept, plus the outline and lines along ridges and
+    /// valleys, so dense grids stay readable.
+    pub edge_density: f32,
+    /// Convert the (sRGB) scheme colors to linear, as lighting and the sRGB
+    /// surface expect. Off uploads them unconverted, which looks washed out.
+    pub linear_colors: bool,
+    /// Use the per-point colors of the terrain file, when it has them,
+    /// instead of `color_mode`
+    pub file_colors: bool,
+    /// Coarser levels of detail to build per chunk; see [`lod`]
+    pub lod_levels: usize,
+    /// Wireframe lines as segment pairs or strips
+    pub line_topology: LineTopology,
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        Self {
+            height_scale: 1.0,
+            shading_mode: ShadingMode::Smooth,
+            color_mode: ColorMode::Height,
+            index_radius: 1,
+            color_scheme: ColorScheme::Terrain,
+            gradient: GradientConfig::default(),
+            slope_bands: SlopeBands::default(),
+            origin: None,
+            globe: false,
+            edge_density: 1.0,
+            linear_colors: true,
+            file_colors: true,
+            lod_levels: 0,
+            line_topology: LineTopology::List,
+        }
+    }
 }
 
 impl TerrainMesh {
     /// Generate mesh with default settings (smooth shading, terrain colors).
-    #[allow(dead_code)] // Used in tests and as public convenience API
     pub fn from_terrain(terrain: &TerrainData, height_scale: f32) -> Self {
         Self::from_terrain_with_options(
             terrain,
@@ -101,7 +240,15 @@ impl TerrainMesh {
         shading_mode: ShadingMode,
         color_scheme: ColorScheme,
     ) -> Self {
-        Self::from_terrain_full(terrain, height_scale, shading_mode, color_scheme, None)
+        Self::build(
+            terrain,
+            &MeshOptions {
+                height_scale,
+                shading_mode,
+                color_scheme,
+                ..MeshOptions::default()
+            },
+        )
     }
 
     /// Generate mesh from terrain data with custom gradient.
@@ -111,150 +258,553 @@ impl TerrainMesh {
         shading_mode: ShadingMode,
         gradient: &GradientConfig,
     ) -> Self {
-        Self::from_terrain_full(
+        Self::build(
             terrain,
-            height_scale,
-            shading_mode,
-            ColorScheme::Custom,
-            Some(gradient),
+            &MeshOptions {
+                height_scale,
+                shading_mode,
+                color_scheme: ColorScheme::Custom,
+                gradient: *gradient,
+                ..MeshOptions::default()
+            },
         )
     }
 
     /// Generate mesh from terrain data with all options.
     ///
-    /// # Arguments
-    ///
-    /// * `terrain` - Source terrain height data
-    /// * `height_scale` - Multiplier for height values (Y axis)
-    /// * `shading_mode` - Flat or smooth shading for normals
-    /// * `color_scheme` - Color gradient scheme for height coloring
-    /// * `gradient` - Optional custom gradient (used when color_scheme is Custom)
+    /// Positions are computed in f64 world space and rebased to
+    /// `options.origin` (the terrain center by default) before converting
+    /// to f32, so large georeferenced coordinates don't lose precision.
     ///
     /// # Returns
     ///
     /// A mesh with:
-    /// - Vertices positioned in 3D space, centered at origin
-    /// - Height-based gradient coloring using the specified scheme
+    /// - Vertices positioned in 3D space relative to the origin
+    /// - Height-based gradient coloring using the specified scheme, or the
+    ///   file's own colors, in linear space unless `options.linear_colors`
+    ///   is off
     /// - Surface normals for lighting
     /// - Index pairs for horizontal and vertical wireframe lines
     /// - Triangle indices for solid rendering
-    fn from_terrain_full(
-        terrain: &TerrainData,
-        height_scale: f32,
-        shading_mode: ShadingMode,
-        color_scheme: ColorScheme,
-        gradient: Option<&GradientConfig>,
-    ) -> Self {
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
+    pub fn build(terrain: &TerrainData, options: &MeshOptions) -> Self {
+        Self::build_with_progress(terrain, options, |_| true).unwrap_or_default()
+    }
 
+    /// Build the mesh like [`build`](Self::build), reporting the fraction
+    /// of its chunks built to `on_progress`, which may be called from any
+    /// thread and stops the build by returning false.
+    ///
+    /// Returns `None` if `on_progress` stopped the build.
+    pub fn build_with_progress(
+        terrain: &TerrainData,
+        options: &MeshOptions,
+        on_progress: impl Fn(f32) -> bool + Sync,
+    ) -> Option<Self> {
         if terrain.width == 0 || terrain.height == 0 {
-            return Self {
-                vertices,
-                indices,
-                triangle_indices: Vec::new(),
-            };
+            return Some(Self::default());
         }
 
-        let (min_h, max_h) = terrain.height_bounds();
-        let height_range = if (max_h - min_h).abs() < f32::EPSILON {
-            1.0
-        } else {
-            max_h - min_h
+        // Center the mesh at origin for orbital camera
+        let center = terrain.center();
+        let offset = options.origin.unwrap_or(center) - center;
+        let height_scale = options.height_scale as f64;
+        let globe = options.globe.then(|| GlobeFrame::new(center));
+
+        // first, generate positions and colors, one row per task
+        let (width, height) = (terrain.width, terrain.height);
+        let mut positions = vec![Vec3::ZERO; width * height];
+        positions
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(z, row)| {
+                for (x, position) in row.iter_mut().enumerate() {
+                    let world = terrain.world_position(x, z);
+                    let scaled = DVec3::new(world.x, world.y * height_scale, world.z);
+                    let local = match &globe {
+                        Some(frame) => frame.to_local(scaled),
+                        None => scaled - center,
+                    };
+                    *position = (local - offset).as_vec3();
+                }
+            });
+        let bounds = positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &p| (min.min(p), max.max(p)),
+        );
+        let colors = vertex_colors(terrain, options);
+
+        let normals = match options.shading_mode {
+            ShadingMode::Smooth => calculate_smooth_normals(terrain, &positions),
+            ShadingMode::Flat => calculate_flat_normals(terrain, &positions),
         };
 
-        // Center the mesh at origin for orbital camera
-        let offset_x = (terrain.width - 1) as f32 / 2.0;
-        let offset_z = (terrain.height - 1) as f32 / 2.0;
+        let vertices = positions
+            .par_iter()
+            .zip(&colors)
+            .zip(&normals)
+            .map(|((position, &color), normal)| Vertex {
+                position: position.to_array(),
+                color,
+                normal: normal.to_array(),
+            })
+            .collect();
 
-        // first, generate positions and colors
-        let mut positions = Vec::with_capacity(terrain.width * terrain.height);
-        let mut colors = Vec::with_capacity(terrain.width * terrain.height);
+        // Generate indices tile by tile so each chunk is a contiguous range
+        let decimation = Decimation::new(terrain, options.edge_density);
+        // Tiles start on cells, not samples, so a grid whose last sample
+        // lands on a tile boundary doesn't get an empty extra tile
+        let (cells_x, cells_z) = ((width - 1).max(1), (height - 1).max(1));
+        let tiles: Vec<(usize, usize)> = (0..cells_z)
+            .step_by(CHUNK_SIZE)
+            .flat_map(|z0| (0..cells_x).step_by(CHUNK_SIZE).map(move |x0| (x0, z0)))
+            .collect();
+        // The vertices count as one more chunk
+        let steps = tiles.len() + 1;
+        let built = AtomicUsize::new(1);
+        if !on_progress(1.0 / steps as f32) {
+            return None;
+        }
+        let tiles: Vec<Tile> = tiles
+            .into_par_iter()
+            .map(|(x0, z0)| {
+                let tile = Tile::build(x0, z0, width, height, &positions, &decimation, options);
+                let done = built.fetch_add(1, Ordering::Relaxed) + 1;
+                on_progress(done as f32 / steps as f32).then_some(tile)
+            })
+            .collect::<Option<_>>()?;
 
-        for z in 0..terrain.height {
-            for x in 0..terrain.width {
-                let h = terrain.points[z][x];
-                let y = h * height_scale;
+        // Concatenate the tiles in order into preallocated buffers
+        let line_count = tiles.iter().map(|tile| tile.lines.len()).sum();
+        let triangle_count = tiles.iter().map(|tile| tile.triangles.len()).sum();
+        let mut indices = Vec::with_capacity(line_count);
+        let mut triangle_indices = Vec::with_capacity(triangle_count);
+        let mut chunks = Vec::with_capacity(tiles.len());
+        let mut lod_indices = Vec::new();
+        let mut lod_triangle_indices = Vec::new();
+        for tile in tiles {
+            let line_start = indices.len() as u32;
+            let triangle_start = triangle_indices.len() as u32;
+            indices.extend_from_slice(&tile.lines);
+            triangle_indices.extend_from_slice(&tile.triangles);
+            // Coarser levels come after all full-detail indices
+            let lods = tile
+                .lods
+                .iter()
+                .map(|(lines, triangles)| {
+                    let line_start = (line_count + lod_indices.len()) as u32;
+                    let triangle_start = (triangle_count + lod_triangle_indices.len()) as u32;
+                    lod_indices.extend_from_slice(lines);
+                    lod_triangle_indices.extend_from_slice(triangles);
+                    LodRanges {
+                        triangles: triangle_start
+                            ..(triangle_count + lod_triangle_indices.len()) as u32,
+                        lines: line_start..(line_count + lod_indices.len()) as u32,
+                    }
+                })
+                .collect();
+            chunks.push(MeshChunk {
+                triangles: triangle_start..triangle_indices.len() as u32,
+                lines: line_start..indices.len() as u32,
+                min: tile.min,
+                max: tile.max,
+                lods,
+            });
+        }
 
-                positions.push(Vec3::new(x as f32 - offset_x, y, z as f32 - offset_z));
+        Some(Self {
+            vertices,
+            indices,
+            line_topology: options.line_topology,
+            triangle_indices,
+            chunks,
+            lod_indices,
+            lod_triangle_indices,
+            bounds: Some(bounds),
+        })
+    }
 
-                let t = (h - min_h) / height_range;
-                let color = if let Some(grad) = gradient {
-                    height_to_color_custom(t, grad)
-                } else {
-                    height_to_color(t, color_scheme)
-                };
-                colors.push(color);
+    /// Index format fitting every vertex, 16-bit when the vertices are
+    /// few enough to leave `0xFFFF` for [`PRIMITIVE_RESTART`].
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        if self.vertices.len() <= u16::MAX as usize {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+}
+
+/// `lines`, index pairs, as strips: each segment starting where the one
+/// before ended continues its strip, any other starts a new one. The last
+/// strip is ended too, so the strips of chunks can be drawn together.
+pub fn line_strips(lines: &[u32]) -> Vec<u32> {
+    let mut strips = Vec::with_capacity(lines.len());
+    for segment in lines.chunks_exact(2) {
+        let (a, b) = (segment[0], segment[1]);
+        match strips.last() {
+            Some(&end) if end == a => strips.push(b),
+            Some(&end) if end == b => strips.push(a),
+            Some(_) => strips.extend([PRIMITIVE_RESTART, a, b]),
+            None => strips.extend([a, b]),
+        }
+    }
+    if !strips.is_empty() {
+        strips.push(PRIMITIVE_RESTART);
+    }
+    strips
+}
+
+/// Indices and bounds of one chunk, built independently of the others.
+struct Tile {
+    lines: Vec<u32>,
+    triangles: Vec<u32>,
+    min: Vec3,
+    max: Vec3,
+    /// Lines and triangles of each coarser level
+    lods: Vec<(Vec<u32>, Vec<u32>)>,
+}
+
+impl Tile {
+    /// Build the chunk of cells starting at (`x0`, `z0`), with up to
+    /// `lod_levels` coarser levels.
+    fn build(
+        x0: usize,
+        z0: usize,
+        width: usize,
+        height: usize,
+        positions: &[Vec3],
+        decimation: &Decimation,
+        options: &MeshOptions,
+    ) -> Self {
+        let z1 = (z0 + CHUNK_SIZE).min(height - 1);
+        let x1 = (x0 + CHUNK_SIZE).min(width - 1);
+        // The last row and column of vertices belong to the last chunk
+        let z_end = if z1 == height - 1 { height } else { z1 };
+        let x_end = if x1 == width - 1 { width } else { x1 };
+        let mut lines = Vec::new();
+        let mut triangles = Vec::with_capacity((z1 - z0) * (x1 - x0) * 6);
+
+        // Wireframe lines along X, then along Z, each line in order so
+        // strips can follow it
+        for z in z0..z_end {
+            for x in x0..x1 {
+                let i = z * width + x;
+                if decimation.keep(z, height - 1, i, i + 1) {
+                    lines.push(i as u32);
+                    lines.push(i as u32 + 1);
+                }
+            }
+        }
+        for x in x0..x_end {
+            for z in z0..z1 {
+                let i = z * width + x;
+                if decimation.keep(x, width - 1, i, i + width) {
+                    lines.push(i as u32);
+                    lines.push((i + width) as u32);
+                }
             }
         }
 
-        let normals = match shading_mode {
-            ShadingMode::Smooth => calculate_smooth_normals(terrain, &positions),
-            ShadingMode::Flat => calculate_flat_normals(terrain, &positions),
-        };
+        for z in z0..z1 {
+            for x in x0..x1 {
+                let top_left = (z * width + x) as u32;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + width as u32;
+                let bottom_right = bottom_left + 1;
 
-        for i in 0..positions.len() {
-            vertices.push(Vertex {
-                position: positions[i].to_array(),
-                color: colors[i],
-                normal: normals[i].to_array(),
-            });
+                triangles.push(top_left);
+                triangles.push(bottom_left);
+                triangles.push(top_right);
+
+                triangles.push(top_right);
+                triangles.push(bottom_left);
+                triangles.push(bottom_right);
+            }
         }
 
-        // Generate indices for wireframe (LineList topology)
-        // Horizontal lines (along X axis)
-        for z in 0..terrain.height {
-            for x in 0..terrain.width - 1 {
-                let i = (z * terrain.width + x) as u32;
-                indices.push(i);
-                indices.push(i + 1);
+        let (mut min, mut max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+        for z in z0..=z1 {
+            for p in &positions[z * width + x0..=z * width + x1] {
+                min = min.min(*p);
+                max = max.max(*p);
             }
         }
 
-        // Vertical lines (along Z axis)
-        for z in 0..terrain.height - 1 {
-            for x in 0..terrain.width {
-                let i = (z * terrain.width + x) as u32;
-                indices.push(i);
-                indices.push(i + terrain.width as u32);
+        let levels = lod::tile_levels(x1 - x0, z1 - z0, options.lod_levels);
+        let mut lods: Vec<(Vec<u32>, Vec<u32>)> = (1..=levels)
+            .map(|level| lod::tile_indices((x0, z0), (x1, z1), (width, height), 1 << level))
+            .collect();
+        if options.line_topology == LineTopology::Strip {
+            lines = line_strips(&lines);
+            for (lines, _) in &mut lods {
+                *lines = line_strips(lines);
             }
         }
 
-        let mut triangle_indices = Vec::new();
-        for z in 0..terrain.height - 1 {
-            for x in 0..terrain.width - 1 {
-                let top_left = (z * terrain.width + x) as u32;
-                let top_right = top_left + 1;
-                let bottom_left = top_left + terrain.width as u32;
-                let bottom_right = bottom_left + 1;
+        Self {
+            lines,
+            triangles,
+            min,
+            max,
+            lods,
+        }
+    }
+}
+
+impl TerrainMesh {
+    /// Bounding sphere of the mesh as `(center, radius)`.
+    ///
+    /// Uses the center and half-diagonal of the axis-aligned bounding box.
+    /// Returns a zero-radius sphere at the origin for an empty mesh.
+    pub fn bounding_sphere(&self) -> (Vec3, f32) {
+        match self.bounds {
+            Some((min, max)) => ((min + max) / 2.0, (max - min).length() / 2.0),
+            None => (Vec3::ZERO, 0.0),
+        }
+    }
 
-                triangle_indices.push(top_left);
-                triangle_indices.push(bottom_left);
-                triangle_indices.push(top_right);
+    /// Recolor vertices by the heights of `terrain`, a grid of the same size
+    /// as the mesh's, e.g. heights relative to a reference surface.
+    pub fn recolor(&mut self, terrain: &TerrainData, options: &MeshOptions) {
+        for (vertex, color) in self
+            .vertices
+            .iter_mut()
+            .zip(vertex_colors(terrain, options))
+        {
+            vertex.color = color;
+        }
+    }
 
-                triangle_indices.push(top_right);
-                triangle_indices.push(bottom_left);
-                triangle_indices.push(bottom_right);
+    /// Replace vertex colors with `colors`, in row-major vertex order, e.g.
+    /// an analysis overlay. Extra or missing colors are ignored.
+    pub fn paint(&mut self, colors: &[[f32; 3]]) {
+        for (vertex, &color) in self.vertices.iter_mut().zip(colors) {
+            vertex.color = color;
+        }
+    }
+
+    /// Darken vertex colors by per-vertex ambient occlusion factors from
+    /// [`ao::bake`](super::ao::bake). Extra or missing factors are ignored.
+    pub fn apply_occlusion(&mut self, factors: &[f32]) {
+        for (vertex, &factor) in self.vertices.iter_mut().zip(factors) {
+            for channel in &mut vertex.color {
+                *channel *= factor;
             }
         }
+    }
+}
+
+/// Share of samples whose value fits in the diverging palette; the rest
+/// saturate, so a few sharp pits and peaks don't wash out the map.
+const SIGNED_PERCENTILE: f64 = 0.98;
 
+/// Color of each sample for `options.color_mode`, or from the file when
+/// `options.file_colors` is set and it has colors, in row-major vertex
+/// order.
+fn vertex_colors(terrain: &TerrainData, options: &MeshOptions) -> Vec<[f32; 3]> {
+    let colors = match terrain.colors.as_ref().filter(|_| options.file_colors) {
+        Some(colors) => colors.iter().map(|&c| hex_to_rgb(c)).collect(),
+        None => mode_colors(terrain, options),
+    };
+    if options.linear_colors {
+        colors
+            .into_iter()
+            .map(|color| color.map(srgb_to_linear))
+            .collect()
+    } else {
+        colors
+    }
+}
+
+/// Color of each sample for `options.color_mode` as 0xRRGGBB, ignoring
+/// the file's colors, e.g. to start painting from what's on screen.
+pub fn mode_hex_colors(terrain: &TerrainData, options: &MeshOptions) -> Vec<u32> {
+    mode_colors(terrain, options)
+        .into_iter()
+        .map(rgb_to_hex)
+        .collect()
+}
+
+/// Color of each sample for `options.color_mode`, in sRGB.
+fn mode_colors(terrain: &TerrainData, options: &MeshOptions) -> Vec<[f32; 3]> {
+    let radius = options.index_radius;
+    match options.color_mode {
+        ColorMode::Height => {
+            // The terrain caches its height range
+            let (min, max) = terrain.height_bounds();
+            ramp_colors(scan::normalize(terrain.heights(), min, max), options)
+        }
+        ColorMode::PlanCurvature => signed_colors(&analysis::plan_curvature(terrain)),
+        ColorMode::ProfileCurvature => signed_colors(&analysis::profile_curvature(terrain)),
+        ColorMode::Ruggedness => {
+            scheme_colors(&analysis::ruggedness_index(terrain, radius), options)
+        }
+        ColorMode::Position => {
+            signed_colors(&analysis::topographic_position_index(terrain, radius))
+        }
+        ColorMode::Roughness => scheme_colors(&analysis::roughness(terrain, radius), options),
+        ColorMode::SlopeBands => analysis::slope_angles(terrain)
+            .into_iter()
+            .map(|degrees| options.slope_bands.color(degrees))
+            .collect(),
+        ColorMode::Channel(index) => match terrain.channels().get(index) {
+            Some(channel) => {
+                let values: Vec<f64> = channel.grid.values().iter().map(|&v| v as f64).collect();
+                scheme_colors(&values, options)
+            }
+            // Relief grids and other terrains without the channel
+            None => mode_colors(
+                terrain,
+                &MeshOptions {
+                    color_mode: ColorMode::Height,
+                    ..*options
+                },
+            ),
+        },
+    }
+}
+
+/// Scheme color of each value by its position within the values' range.
+fn scheme_colors(values: &[f64], options: &MeshOptions) -> Vec<[f32; 3]> {
+    let (min, max) = scan::min_max(values).unwrap_or((0.0, 0.0));
+    ramp_colors(scan::normalize(values, min, max), options)
+}
+
+/// Scheme color of each position along the ramp, from 0 to 1.
+fn ramp_colors(positions: impl IntoIterator<Item = f32>, options: &MeshOptions) -> Vec<[f32; 3]> {
+    positions
+        .into_iter()
+        .map(|t| match options.color_scheme {
+            ColorScheme::Custom => height_to_color_custom(t, &options.gradient),
+            scheme => height_to_color(t, scheme),
+        })
+        .collect()
+}
+
+/// Diverging palette color of each signed value, scaled so
+/// [`SIGNED_PERCENTILE`] of the magnitudes fit.
+fn signed_colors(values: &[f64]) -> Vec<[f32; 3]> {
+    let mut magnitudes: Vec<f64> = values.iter().map(|v| v.abs()).collect();
+    magnitudes.sort_unstable_by(f64::total_cmp);
+    let index = ((magnitudes.len().saturating_sub(1)) as f64 * SIGNED_PERCENTILE) as usize;
+    let scale = match magnitudes.get(index) {
+        Some(&m) if m > 0.0 => m,
+        _ => 1.0,
+    };
+    values
+        .iter()
+        .map(|v| diverging_color((v / scale) as f32))
+        .collect()
+}
+
+/// Local tangent frame at the center of a geographic grid.
+///
+/// Converts (longitude, height, latitude) to earth-centered coordinates on a
+/// sphere, then into east/up/north axes at the grid center so the globe
+/// patch sits at the origin with +Y up, like the flat mesh.
+struct GlobeFrame {
+    center: DVec3,
+    east: DVec3,
+    up: DVec3,
+    north: DVec3,
+}
+
+impl GlobeFrame {
+    fn new(center: DVec3) -> Self {
+        let (lon, lat) = (center.x.to_radians(), center.z.to_radians());
+        let up = DVec3::new(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin());
+        let east = DVec3::new(-lon.sin(), lon.cos(), 0.0);
         Self {
-            vertices,
-            indices,
-            triangle_indices,
+            center: up * EARTH_RADIUS,
+            east,
+            up,
+            north: up.cross(east),
         }
     }
+
+    /// Map `(longitude, height, latitude)` into the tangent frame.
+    fn to_local(&self, p: DVec3) -> DVec3 {
+        let (lon, lat) = (p.x.to_radians(), p.z.to_radians());
+        let r = EARTH_RADIUS + p.y;
+        let ecef = DVec3::new(
+            r * lat.cos() * lon.cos(),
+            r * lat.cos() * lon.sin(),
+            r * lat.sin(),
+        ) - self.center;
+        DVec3::new(ecef.dot(self.east), ecef.dot(self.up), ecef.dot(self.north))
+    }
+}
+
+/// Wireframe line selection for [`MeshOptions::edge_density`].
+struct Decimation {
+    /// Keep every `stride`-th grid line
+    stride: usize,
+    /// Vertices on ridges or valleys; empty when keeping every line
+    ridge: Vec<bool>,
+}
+
+impl Decimation {
+    fn new(terrain: &TerrainData, density: f32) -> Self {
+        let density = density.clamp(f32::EPSILON, 1.0);
+        if density >= 1.0 {
+            return Self {
+                stride: 1,
+                ridge: Vec::new(),
+            };
+        }
+
+        // Curvature as the magnitude of the discrete Laplacian of height
+        let (width, height) = (terrain.width, terrain.height);
+        let h = |x: usize, z: usize| terrain.at(x, z);
+        let mut curvature = Vec::with_capacity(width * height);
+        for z in 0..height {
+            for x in 0..width {
+                let neighbors = h(x.saturating_sub(1), z)
+                    + h((x + 1).min(width - 1), z)
+                    + h(x, z.saturating_sub(1))
+                    + h(x, (z + 1).min(height - 1));
+                curvature.push((neighbors - 4.0 * h(x, z)).abs());
+            }
+        }
+
+        // Keep the most curved `density` fraction of vertices
+        let mut sorted = curvature.clone();
+        let cut = ((1.0 - density as f64) * sorted.len() as f64) as usize;
+        let cut = cut.min(sorted.len() - 1);
+        let (_, &mut threshold, _) = sorted.select_nth_unstable_by(cut, f64::total_cmp);
+        let ridge = curvature
+            .iter()
+            .map(|&c| c > 0.0 && c >= threshold)
+            .collect();
+
+        Self {
+            stride: (1.0 / density).round() as usize,
+            ridge,
+        }
+    }
+
+    /// Whether to keep the line from vertex `a` to `b`, which lies on grid
+    /// line `line` of `0..=last` (rows for lines along X, columns for lines
+    /// along Z). The first and last grid lines form the outline.
+    fn keep(&self, line: usize, last: usize, a: usize, b: usize) -> bool {
+        self.ridge.is_empty()
+            || line.is_multiple_of(self.stride)
+            || line == last
+            || (self.ridge[a] && self.ridge[b])
+    }
 }
 
 /// Calculate smooth normals by averaging face normals at each vertex
 fn calculate_smooth_normals(terrain: &TerrainData, positions: &[Vec3]) -> Vec<Vec3> {
     let width = terrain.width;
     let height = terrain.height;
-    let mut normals = vec![Vec3::ZERO; positions.len()];
 
-    for z in 0..height - 1 {
-        for x in 0..width - 1 {
-            let idx = z * width + x;
+    // Normals of each cell's two triangles, one row of cells per task
+    let cells_x = width.saturating_sub(1);
+    let faces: Vec<(Vec3, Vec3)> = (0..height.saturating_sub(1) * cells_x)
+        .into_par_iter()
+        .map(|cell| {
+            let idx = cell / cells_x * width + cell % cells_x;
             let tl = positions[idx];
             let tr = positions[idx + 1];
             let bl = positions[idx + width];
@@ -264,21 +814,42 @@ fn calculate_smooth_normals(terrain: &TerrainData, positions: &[Vec3]) -> Vec<Ve
             let n1 = (bl - tl).cross(tr - tl).normalize_or_zero();
             // second triangle normal
             let n2 = (bl - tr).cross(br - tr).normalize_or_zero();
+            (n1, n2)
+        })
+        .collect();
 
-            // add to all vertices of each triange
-            normals[idx] += n1;
-            normals[idx + width] += n1 + n2;
-            normals[idx + 1] += n1 + n2;
-            normals[idx + width + 1] += n2;
-        }
-    }
+    // Vertex (x, z) sums the triangles it belongs to: the first of cell
+    // (x, z), both of cells (x, z - 1) and (x - 1, z), and the second of
+    // cell (x - 1, z - 1)
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    normals
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(z, row)| {
+            let face = |x: usize, z: usize| faces[z * cells_x + x];
+            for (x, n) in row.iter_mut().enumerate() {
+                let (right, below) = (x < cells_x, z + 1 < height);
+                if right && below {
+                    *n += face(x, z).0;
+                }
+                if right && z > 0 {
+                    let (n1, n2) = face(x, z - 1);
+                    *n += n1 + n2;
+                }
+                if x > 0 && below {
+                    let (n1, n2) = face(x - 1, z);
+                    *n += n1 + n2;
+                }
+                if x > 0 && z > 0 {
+                    *n += face(x - 1, z - 1).1;
+                }
 
-    for n in &mut normals {
-        *n = n.normalize_or_zero();
-        if n.y < 0.0 {
-            *n = -*n;
-        }
-    }
+                *n = n.normalize_or_zero();
+                if n.y < 0.0 {
+                    *n = -*n;
+                }
+            }
+        });
 
     normals
 }
@@ -289,29 +860,32 @@ fn calculate_flat_normals(terrain: &TerrainData, positions: &[Vec3]) -> Vec<Vec3
     let height = terrain.height;
     let mut normals = vec![Vec3::Y; positions.len()];
 
-    for z in 0..height {
-        for x in 0..width {
-            let idx = z * width + x;
+    normals
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(z, row)| {
+            for (x, normal) in row.iter_mut().enumerate() {
+                let idx = z * width + x;
 
-            let dx = if x == 0 {
-                positions[idx + 1].y - positions[idx].y
-            } else if x == width - 1 {
-                positions[idx].y - positions[idx - 1].y
-            } else {
-                (positions[idx + 1].y - positions[idx - 1].y) / 2.0
-            };
+                let dx = if x == 0 {
+                    positions[idx + 1].y - positions[idx].y
+                } else if x == width - 1 {
+                    positions[idx].y - positions[idx - 1].y
+                } else {
+                    (positions[idx + 1].y - positions[idx - 1].y) / 2.0
+                };
 
-            let dz = if z == 0 {
-                positions[idx + width].y - positions[idx].y
-            } else if z == height - 1 {
-                positions[idx].y - positions[idx - width].y
-            } else {
-                (positions[idx + width].y - positions[idx - width].y) / 2.0
-            };
+                let dz = if z == 0 {
+                    positions[idx + width].y - positions[idx].y
+                } else if z == height - 1 {
+                    positions[idx].y - positions[idx - width].y
+                } else {
+                    (positions[idx + width].y - positions[idx - width].y) / 2.0
+                };
 
-            normals[idx] = Vec3::new(-dx, 1.0, -dz).normalize_or_zero();
-        }
-    }
+                *normal = Vec3::new(-dx, 1.0, -dz).normalize_or_zero();
+            }
+        });
 
     normals
 }
@@ -367,6 +941,454 @@ mod tests {
         assert_eq!(mesh2.vertices[0].position[1], 20.0);
     }
 
+    #[test]
+    fn test_rebased_utm_coordinates_keep_precision() {
+        let mut terrain =
+            TerrainData::new(vec![vec![1500.25, 1500.5], vec![1500.75, 1501.0]], None);
+        terrain.origin = glam::DVec2::new(500_000.0, 4_000_000.0);
+        terrain.cell_size = 0.1;
+
+        let origin = terrain.world_position(0, 0);
+        let mesh = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                origin: Some(origin),
+                ..MeshOptions::default()
+            },
+        );
+
+        let p: Vec<Vec3> = mesh
+            .vertices
+            .iter()
+            .map(|v| Vec3::from_array(v.position))
+            .collect();
+        assert_eq!(p[0], Vec3::ZERO);
+        assert!((p[1].x - 0.1).abs() < 1e-6);
+        assert!((p[2].z - 0.1).abs() < 1e-6);
+        assert!((p[3].y - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_globe_center_is_origin_and_edges_drop() {
+        // 1 degree x 1 degree patch at 45N
+        let mut terrain = TerrainData::new(vec![vec![0.0; 3]; 3], None);
+        terrain.origin = glam::DVec2::new(9.0, 44.0);
+        terrain.cell_size = 0.5;
+
+        let mesh = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                globe: true,
+                ..MeshOptions::default()
+            },
+        );
+        let p: Vec<Vec3> = mesh
+            .vertices
+            .iter()
+            .map(|v| Vec3::from_array(v.position))
+            .collect();
+
+        assert!(p[4].length() < 1e-3);
+        // North edge is ~55 km away and below the tangent plane by ~d^2 / 2R
+        assert!((p[7].z - 55_597.0).abs() < 100.0);
+        assert!(p[7].y < -200.0 && p[7].y > -300.0);
+        // East is +X, like the flat mesh
+        assert!(p[5].x > 0.0);
+        assert!(mesh.vertices[4].normal[1] > 0.99);
+    }
+
+    #[test]
+    fn test_chunk_levels_of_detail() {
+        // Two full chunks and a 5-cell one, which can't be halved
+        let points = vec![vec![0.0; CHUNK_SIZE * 2 + 6]; CHUNK_SIZE + 1];
+        let terrain = TerrainData::new(points, None);
+        let full = TerrainMesh::from_terrain(&terrain, 1.0);
+        let mesh = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                lod_levels: lod::LOD_LEVELS,
+                ..MeshOptions::default()
+            },
+        );
+        assert!(full.lod_triangle_indices.is_empty());
+        assert_eq!(mesh.triangle_indices, full.triangle_indices);
+        assert_eq!(mesh.indices, full.indices);
+
+        let levels: Vec<usize> = mesh.chunks.iter().map(|chunk| chunk.lods.len()).collect();
+        assert_eq!(levels, [lod::LOD_LEVELS, lod::LOD_LEVELS, 0]);
+        let triangles = [&mesh.triangle_indices[..], &mesh.lod_triangle_indices].concat();
+        let mut counts = Vec::new();
+        for level in 0..=lod::LOD_LEVELS + 1 {
+            let range = mesh.chunks[0].triangles_at(level);
+            counts.push(range.len());
+            assert!(range.end as usize <= triangles.len());
+            assert!(triangles[range.start as usize..range.end as usize]
+                .iter()
+                .all(|&i| (i as usize) < mesh.vertices.len()));
+        }
+        // Fewer triangles at each level, the coarsest repeating past the end
+        assert!(counts.windows(2).take(lod::LOD_LEVELS).all(|c| c[1] < c[0]));
+        assert_eq!(counts[lod::LOD_LEVELS], counts[lod::LOD_LEVELS + 1]);
+        assert_eq!(mesh.chunks[2].triangles_at(3), mesh.chunks[2].triangles);
+    }
+
+    #[test]
+    fn test_chunks_partition_indices() {
+        let points = vec![vec![0.0; CHUNK_SIZE * 2 + 5]; CHUNK_SIZE + 3];
+        let terrain = TerrainData::new(points, None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let (w, h) = (terrain.width, terrain.height);
+
+        // 3 x 2 tiles
+        assert_eq!(mesh.chunks.len(), 6);
+
+        // Ranges are contiguous and cover both index buffers exactly
+        let mut triangles = 0;
+        let mut lines = 0;
+        for chunk in &mesh.chunks {
+            assert_eq!(chunk.triangles.start, triangles);
+            assert_eq!(chunk.lines.start, lines);
+            triangles = chunk.triangles.end;
+            lines = chunk.lines.end;
+        }
+        assert_eq!(triangles as usize, (w - 1) * (h - 1) * 6);
+        assert_eq!(lines as usize, ((w - 1) * h + w * (h - 1)) * 2);
+        assert_eq!(triangles as usize, mesh.triangle_indices.len());
+        assert_eq!(lines as usize, mesh.indices.len());
+
+        // Every edge appears once
+        let mut edges: Vec<(u32, u32)> = mesh.indices.chunks(2).map(|e| (e[0], e[1])).collect();
+        edges.sort();
+        edges.dedup();
+        assert_eq!(edges.len(), mesh.indices.len() / 2);
+    }
+
+    #[test]
+    fn test_line_strips_cover_the_same_edges() {
+        let points = vec![vec![0.0; CHUNK_SIZE + 5]; 9];
+        let terrain = TerrainData::new(points, None);
+        let list = TerrainMesh::build(&terrain, &MeshOptions::default());
+        let strips = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                line_topology: LineTopology::Strip,
+                ..MeshOptions::default()
+            },
+        );
+        assert_eq!(strips.line_topology, LineTopology::Strip);
+        assert!(strips.indices.len() * 3 < list.indices.len() * 2);
+
+        let sorted = |mut edges: Vec<(u32, u32)>| {
+            edges.sort();
+            edges
+        };
+        let listed = list.indices.chunks(2).map(|e| (e[0], e[1])).collect();
+        let stripped = strips
+            .indices
+            .split(|&i| i == PRIMITIVE_RESTART)
+            .flat_map(|strip| strip.windows(2).map(|e| (e[0].min(e[1]), e[0].max(e[1]))))
+            .collect();
+        assert_eq!(sorted(listed), sorted(stripped));
+        // Each chunk's strips are its own
+        for chunk in &strips.chunks {
+            assert_ne!(
+                strips.indices[chunk.lines.start as usize],
+                PRIMITIVE_RESTART
+            );
+        }
+    }
+
+    #[test]
+    fn test_index_format_fits_vertices() {
+        let small = TerrainData::new(vec![vec![0.0; 255]; 257], None);
+        let mesh = TerrainMesh::from_terrain(&small, 1.0);
+        assert_eq!(mesh.index_format(), wgpu::IndexFormat::Uint16);
+        let large = TerrainData::new(vec![vec![0.0; 256]; 256], None);
+        let mesh = TerrainMesh::from_terrain(&large, 1.0);
+        assert_eq!(mesh.index_format(), wgpu::IndexFormat::Uint32);
+    }
+
+    #[test]
+    fn test_build_matches_across_thread_counts() {
+        let points: Vec<Vec<f64>> = (0..CHUNK_SIZE + 9)
+            .map(|z| {
+                (0..CHUNK_SIZE * 2 + 3)
+                    .map(|x| ((x * z) % 17) as f64)
+                    .collect()
+            })
+            .collect();
+        let terrain = TerrainData::new(points, None);
+        let options = MeshOptions {
+            edge_density: 0.5,
+            ..MeshOptions::default()
+        };
+        let parallel = TerrainMesh::build(&terrain, &options);
+        let serial = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| TerrainMesh::build(&terrain, &options));
+
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&parallel.vertices),
+            bytemuck::cast_slice::<_, u8>(&serial.vertices)
+        );
+        assert_eq!(parallel.indices, serial.indices);
+        assert_eq!(parallel.triangle_indices, serial.triangle_indices);
+        assert_eq!(parallel.chunks, serial.chunks);
+    }
+
+    #[test]
+    fn test_build_reports_chunks_built() {
+        let terrain = TerrainData::new(vec![vec![0.0; CHUNK_SIZE * 2 + 1]; 3], None);
+        let options = MeshOptions::default();
+        let reported = std::sync::Mutex::new(Vec::new());
+        let mesh = TerrainMesh::build_with_progress(&terrain, &options, |done| {
+            reported.lock().unwrap().push(done);
+            true
+        })
+        .unwrap();
+        assert_eq!(mesh.chunks, TerrainMesh::build(&terrain, &options).chunks);
+        let mut reported = reported.into_inner().unwrap();
+        reported.sort_by(f32::total_cmp);
+        // The vertices, then each of the two chunks
+        assert_eq!(reported, [1.0 / 3.0, 2.0 / 3.0, 1.0]);
+
+        // Stopped, nothing is built
+        assert!(TerrainMesh::build_with_progress(&terrain, &options, |_| false).is_none());
+    }
+
+    #[test]
+    fn test_grid_ending_on_chunk_boundary() {
+        // The last sample row lies on a tile boundary: one row of tiles,
+        // and its lines aren't emitted twice
+        let terrain = TerrainData::new(vec![vec![0.0; 3]; CHUNK_SIZE + 1], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+
+        assert_eq!(mesh.chunks.len(), 1);
+        assert_eq!(
+            mesh.indices.len(),
+            (2 * (CHUNK_SIZE + 1) + 3 * CHUNK_SIZE) * 2
+        );
+    }
+
+    #[test]
+    fn test_chunk_bounds_contain_triangles() {
+        let points: Vec<Vec<f64>> = (0..70)
+            .map(|z| (0..70).map(|x| ((x * z) % 13) as f64).collect())
+            .collect();
+        let mesh = TerrainMesh::from_terrain(&TerrainData::new(points, None), 1.0);
+
+        for chunk in &mesh.chunks {
+            for &i in
+                &mesh.triangle_indices[chunk.triangles.start as usize..chunk.triangles.end as usize]
+            {
+                let p = Vec3::from_array(mesh.vertices[i as usize].position);
+                assert!(p.cmpge(chunk.min).all() && p.cmple(chunk.max).all());
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_density_keeps_outline_and_ridges() {
+        // Flat grid with a single ridge along x = 5
+        let (w, h) = (12, 12);
+        let points = (0..h)
+            .map(|_| (0..w).map(|x| if x == 5 { 10.0 } else { 0.0 }).collect())
+            .collect();
+        let terrain = TerrainData::new(points, None);
+        let full = TerrainMesh::from_terrain(&terrain, 1.0);
+        let sparse = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                edge_density: 0.25,
+                ..MeshOptions::default()
+            },
+        );
+        assert!(sparse.indices.len() < full.indices.len() / 2);
+
+        let has_line =
+            |a: usize, b: usize| sparse.indices.chunks(2).any(|l| l == [a as u32, b as u32]);
+        // Every 4th row, the outline, and the ridge survive
+        assert!(has_line(4 * w + 1, 4 * w + 2));
+        assert!(has_line((h - 1) * w + 1, (h - 1) * w + 2));
+        assert!(has_line(w - 1, 2 * w - 1));
+        assert!(has_line(w + 5, 2 * w + 5));
+        // An ordinary interior line is dropped
+        assert!(!has_line(w + 1, w + 2));
+    }
+
+    #[test]
+    fn test_colors_are_linear() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]], None);
+        let options = MeshOptions {
+            color_scheme: ColorScheme::Monochrome,
+            ..MeshOptions::default()
+        };
+        let linear = TerrainMesh::build(&terrain, &options);
+        let srgb = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                linear_colors: false,
+                ..options
+            },
+        );
+
+        // Monochrome mid-height is 0.55 in sRGB, ~0.26 linear
+        assert!((srgb.vertices[1].color[0] - 0.55).abs() < 1e-5);
+        assert!((linear.vertices[1].color[0] - srgb_to_linear(0.55)).abs() < 1e-5);
+        assert!(linear.vertices[1].color[0] < 0.3);
+        assert_eq!(linear.vertices[2].color, [1.0; 3]);
+    }
+
+    #[test]
+    fn test_file_colors() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0]], Some(vec![vec![0xFF0000, 0x0080FF]]));
+        let options = MeshOptions {
+            linear_colors: false,
+            ..MeshOptions::default()
+        };
+        let mesh = TerrainMesh::build(&terrain, &options);
+        assert_eq!(mesh.vertices[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(mesh.vertices[1].color, [0.0, 128.0 / 255.0, 1.0]);
+
+        // Height coloring overrides them
+        let height = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                file_colors: false,
+                ..options
+            },
+        );
+        let plain = TerrainMesh::build(
+            &TerrainData::from_heights(2, terrain.heights().to_vec(), None),
+            &options,
+        );
+        assert_eq!(height.vertices[1].color, plain.vertices[1].color);
+    }
+
+    #[test]
+    fn test_channel_color_mode() {
+        let mut terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]], None);
+        let field = TerrainData::new(vec![vec![30.0, 20.0, 10.0]], None);
+        terrain.add_channel("temperature", &field);
+        let height = TerrainMesh::build(&terrain, &MeshOptions::default());
+        let channel = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                color_mode: ColorMode::Channel(0),
+                ..MeshOptions::default()
+            },
+        );
+
+        // The channel runs against the heights
+        assert_eq!(channel.vertices[0].color, height.vertices[2].color);
+        assert_eq!(channel.vertices[2].color, height.vertices[0].color);
+
+        // A missing channel falls back to height
+        let missing = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                color_mode: ColorMode::Channel(1),
+                ..MeshOptions::default()
+            },
+        );
+        assert_eq!(missing.vertices[0].color, height.vertices[0].color);
+    }
+
+    #[test]
+    fn test_curvature_color_mode() {
+        // A ridge along x = 2 on a slope
+        let points = (0..5)
+            .map(|z| {
+                (0..5)
+                    .map(|x: i32| z as f64 - (x - 2).abs() as f64 * 2.0)
+                    .collect()
+            })
+            .collect();
+        let terrain = TerrainData::new(points, None);
+        let mesh = TerrainMesh::build(
+            &terrain,
+            &MeshOptions {
+                color_mode: ColorMode::PlanCurvature,
+                linear_colors: false,
+                ..MeshOptions::default()
+            },
+        );
+
+        // Red along the ridge, neutral on the straight flanks
+        assert_eq!(mesh.vertices[2 * 5 + 2].color, diverging_color(1.0));
+        assert_eq!(mesh.vertices[2 * 5 + 4].color, diverging_color(0.0));
+    }
+
+    #[test]
+    fn test_slope_bands_color_mode() {
+        // A 37° slope beside a flat terrace
+        let row = vec![0.0, 0.0, 0.0, 0.75, 1.5, 2.25];
+        let terrain = TerrainData::new(vec![row; 3], None);
+        let options = MeshOptions {
+            color_mode: ColorMode::SlopeBands,
+            linear_colors: false,
+            height_scale: 4.0,
+            ..MeshOptions::default()
+        };
+        let mesh = TerrainMesh::build(&terrain, &options);
+
+        // Bands follow the true slope, not the exaggerated one
+        let bands = options.slope_bands;
+        assert_eq!(mesh.vertices[5].color, bands.color(37.0));
+        assert_eq!(mesh.vertices[5].color, bands.bands[2].color);
+        assert_eq!(mesh.vertices[0].color, bands.color(0.0));
+    }
+
+    #[test]
+    fn test_recolor_keeps_shape() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]], None);
+        let options = MeshOptions {
+            color_scheme: ColorScheme::Monochrome,
+            ..MeshOptions::default()
+        };
+        let mut mesh = TerrainMesh::build(&terrain, &options);
+        let positions: Vec<_> = mesh.vertices.iter().map(|v| v.position).collect();
+        // Reversed heights swap the lowest and highest colors
+        mesh.recolor(&TerrainData::new(vec![vec![2.0, 1.0, 0.0]], None), &options);
+
+        assert_eq!(mesh.vertices[0].color, [1.0; 3]);
+        assert_eq!(mesh.vertices[2].color, [srgb_to_linear(0.1); 3]);
+        assert!(mesh
+            .vertices
+            .iter()
+            .zip(positions)
+            .all(|(v, p)| v.position == p));
+    }
+
+    #[test]
+    fn test_bounding_sphere() {
+        let terrain = TerrainData::new(vec![vec![0.0, 0.0, 0.0], vec![0.0, 0.0, 4.0]], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let (center, radius) = mesh.bounding_sphere();
+
+        // Extent: x in [-1, 1], y in [0, 4], z in [-0.5, 0.5]
+        assert_eq!(
+            mesh.bounds,
+            Some((Vec3::new(-1.0, 0.0, -0.5), Vec3::new(1.0, 4.0, 0.5)))
+        );
+        assert!((center - Vec3::new(0.0, 2.0, 0.0)).length() < 1e-5);
+        assert!((radius - (4.0f32 + 16.0 + 1.0).sqrt() / 2.0).abs() < 1e-5);
+
+        for v in &mesh.vertices {
+            assert!((Vec3::from_array(v.position) - center).length() <= radius + 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_bounding_sphere_empty() {
+        let terrain = TerrainData::new(vec![], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        assert_eq!(mesh.bounds, None);
+        assert_eq!(mesh.bounding_sphere(), (Vec3::ZERO, 0.0));
+    }
+
     #[test]
     fn test_height_to_color_bounds() {
         // Test terrain color gradient at key points
@@ -381,4 +1403,118 @@ mod tests {
         // High should be whitish
         assert!(high[0] > 0.9 && high[1] > 0.9 && high[2] > 0.9);
     }
+
+    // ==================== Property Tests ====================
+
+    use proptest::prelude::*;
+
+    /// Grids of 1..=80 samples per side, so some span several chunks, with
+    /// heights in [-1000, 1000] and a random footprint.
+    fn terrain() -> impl Strategy<Value = TerrainData> {
+        (1..=80usize, 1..=80usize)
+            .prop_flat_map(|(w, h)| {
+                (
+                    prop::collection::vec(prop::collection::vec(-1000.0..1000.0f64, w), h),
+                    -1e4..1e4f64,
+                    -1e4..1e4f64,
+                    0.1..10.0f64,
+                )
+            })
+            .prop_map(|(points, x, z, cell_size)| {
+                let mut terrain = TerrainData::new(points, None);
+                terrain.origin = glam::DVec2::new(x, z);
+                terrain.cell_size = cell_size;
+                terrain
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_index_counts(terrain in terrain()) {
+            let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+            let (w, h) = (terrain.width, terrain.height);
+
+            prop_assert_eq!(mesh.vertices.len(), w * h);
+            prop_assert_eq!(mesh.triangle_indices.len(), (w - 1) * (h - 1) * 6);
+            prop_assert_eq!(mesh.indices.len(), ((w - 1) * h + w * (h - 1)) * 2);
+            let tiles = |n: usize| (n - 1).max(1).div_ceil(CHUNK_SIZE);
+            prop_assert_eq!(mesh.chunks.len(), tiles(w) * tiles(h));
+        }
+
+        #[test]
+        fn prop_indices_in_range(terrain in terrain()) {
+            let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+            let count = mesh.vertices.len() as u32;
+
+            prop_assert!(mesh.indices.iter().all(|&i| i < count));
+            prop_assert!(mesh.triangle_indices.iter().all(|&i| i < count));
+
+            // Lines join grid neighbours; triangles have three distinct corners
+            for line in mesh.indices.chunks(2) {
+                let step = line[1] - line[0];
+                prop_assert!(step == 1 || step == terrain.width as u32);
+            }
+            for tri in mesh.triangle_indices.chunks(3) {
+                prop_assert!(tri[0] != tri[1] && tri[1] != tri[2] && tri[0] != tri[2]);
+            }
+        }
+
+        #[test]
+        fn prop_triangles_face_up(terrain in terrain()) {
+            let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+            let p = |i: u32| Vec3::from_array(mesh.vertices[i as usize].position);
+
+            // Same winding everywhere: counter-clockwise seen from above
+            for tri in mesh.triangle_indices.chunks(3) {
+                let normal = (p(tri[1]) - p(tri[0])).cross(p(tri[2]) - p(tri[0]));
+                prop_assert!(normal.y > 0.0);
+            }
+        }
+
+        #[test]
+        fn prop_mesh_is_centered(terrain in terrain(), height_scale in 0.1..10.0f32) {
+            let mesh = TerrainMesh::from_terrain(&terrain, height_scale);
+            let (min, max) = mesh.vertices.iter().fold(
+                (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                |(min, max), v| {
+                    let p = Vec3::from_array(v.position);
+                    (min.min(p), max.max(p))
+                },
+            );
+
+            // The footprint is centered on the origin; heights are only scaled
+            let tolerance = 1e-3 * terrain.cell_size as f32;
+            prop_assert!((min.x + max.x).abs() < tolerance);
+            prop_assert!((min.z + max.z).abs() < tolerance);
+            let (min_h, max_h) = terrain.height_bounds();
+            prop_assert!((min.y - min_h as f32 * height_scale).abs() < 1e-2);
+            prop_assert!((max.y - max_h as f32 * height_scale).abs() < 1e-2);
+        }
+
+        #[test]
+        fn prop_color_is_monotonic_in_height(terrain in terrain()) {
+            let mesh = TerrainMesh::from_terrain_with_options(
+                &terrain,
+                1.0,
+                ShadingMode::Smooth,
+                ColorScheme::Monochrome,
+            );
+            let mut by_height: Vec<(f32, f32)> = mesh
+                .vertices
+                .iter()
+                .map(|v| (v.position[1], v.color[0]))
+                .collect();
+            by_height.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            // Brightness never decreases with height and spans the gradient
+            for pair in by_height.windows(2) {
+                prop_assert!(pair[1].1 >= pair[0].1 - 1e-6);
+            }
+            let (min_h, max_h) = terrain.height_bounds();
+            if max_h > min_h {
+                prop_assert!((by_height[0].1 - srgb_to_linear(0.1)).abs() < 1e-5);
+                prop_assert!((by_height[by_height.len() - 1].1 - 1.0).abs() < 1e-5);
+            }
+        }
+    }
 }
@@ -2,89 +2,370 @@
 //!
 //! This module provides:
 //! - [`TerrainData`] - Raw height map data structure
-//! - [`load_fdf`] - Parser for .fdf terrain files
+//! - [`loader::load_fdf`] - Parser for .fdf terrain files
+//! - [`load_terrain`] - Loader picking the file format by extension
 //! - [`TerrainMesh`] - GPU-ready mesh generation
+//! - [`algebra`] - Map algebra deriving channels from layers
+//! - [`analysis`] - Ridgeline extraction and export
+//! - [`annotations`] - User polylines, polygons and notes
+//! - [`ao`] - Baked ambient occlusion
+//! - [`ascii_grid`] - ESRI ASCII grid (.asc) parser
+//! - [`breaklines`] - Breaklines constraining the adaptive triangulation
+//! - [`color_map`] - Georeferenced images of the terrain's colors
+//! - [`contours`] - Contour polyline tracing and export
+//! - [`drape`] - Images draped over the terrain, e.g. orthophotos
+//! - [`environment`] - HDR environment maps lighting the scene
+//! - [`export`] - Mesh export in the format named by the file
+//! - [`filters`] - Edge-preserving denoising of the heights
+//! - [`geotiff`] - Single-band GeoTIFF elevation models
+//! - [`gltf`] - Binary glTF mesh export
+//! - [`grid`] - Named value grids over the terrain samples
+//! - [`heightmap`] - Grayscale PNG and JPEG heightmaps
+//! - [`hydrology`] - Flow routing and catchment delineation
+//! - [`lightmap`] - Texture-space lighting bakes
+//! - [`lod`] - Coarser levels of detail for mesh chunks
+//! - [`obj`] - Wavefront OBJ mesh export
+//! - [`picking`] - Terrain points picked under the cursor
+//! - [`points`] - Point features such as wells, with attributes
+//! - [`processing`] - Smoothing and downsampling of the whole grid
+//! - [`raster`] - Georeferenced rasters shared by the GIS loaders
+//! - [`relief`] - Heights relative to a reference surface
+//! - [`scan`] - Vectorized min/max and normalization scans
+//! - [`selection`] - Selected regions with their statistics
+//! - [`simplify`] - Mesh simplification to a triangle budget
+//! - [`solar`] - Clear-sky solar irradiation over a day
+//! - [`stitch`] - Averaging the shared borders of adjacent tiles
+//! - [`sun`] - Sun position and shadow-hour studies
+//! - [`tiles`] - Web map tiles of hillshade and color relief
+//! - [`tin`] - Adaptive triangulated irregular networks
+//! - [`uncertainty`] - Showing per-sample height uncertainty
+//! - [`units`] - Units of heights and distances, and their conversion
+//! - [`usdz`] - USDZ mesh export for AR viewers
+//! - [`vectors`] - Vector field overlays as arrows or streamlines
+//! - [`visibility`] - Line of sight between two points
 
+pub mod algebra;
+pub mod analysis;
+pub mod annotations;
+pub mod ao;
+pub mod ascii_grid;
+pub mod breaklines;
+pub mod color_map;
 pub mod colors;
+pub mod contours;
+pub mod drape;
+pub mod environment;
+pub mod export;
+pub mod filters;
+pub mod float_grid;
+pub mod geotiff;
+pub mod gltf;
+pub mod grid;
+pub mod heightfield;
+pub mod heightmap;
+pub mod hydrology;
+pub mod lightmap;
 pub mod loader;
+pub mod lod;
 pub mod mesh;
+pub mod obj;
+pub mod picking;
+pub mod points;
+pub mod processing;
+pub mod profile;
+pub mod raster;
+pub mod relief;
+pub mod scan;
+pub mod selection;
+pub mod simplify;
+pub mod solar;
+pub mod stitch;
+pub mod stl;
+pub mod sun;
+pub mod tiles;
+pub mod tin;
+pub mod uncertainty;
+pub mod units;
+pub mod usdz;
+pub mod vectors;
+pub mod visibility;
 
-pub use colors::{ColorScheme, GradientConfig};
-pub use loader::load_fdf;
-pub use mesh::{TerrainMesh, Vertex};
+pub use colors::{ColorMode, ColorScheme, GradientConfig, SlopeBands};
+pub use heightfield::{Heightfield, RayHit};
+pub use loader::{load_terrain, LoadOptions, TerrainFormat};
+pub use mesh::{MeshOptions, TerrainMesh, Vertex, EARTH_RADIUS};
+
+use std::ops::Range;
+use std::slice::ChunksExact;
+use std::sync::OnceLock;
+
+use glam::{DVec2, DVec3};
+
+use grid::{Channel, Grid};
+use units::Units;
 
 /// Raw terrain height data parsed from a .fdf file.
 ///
-/// Stores a 2D grid of height values with optional per-vertex colors.
+/// Stores a 2D grid of height values with optional per-vertex colors, in
+/// one contiguous row-major buffer so scans stay cache-friendly and grids
+/// can be handed to the GPU or a file mapping without regrouping.
 /// The coordinate system uses:
 /// - X axis: columns (width)
 /// - Z axis: rows (height/depth)
 /// - Y axis: height values
+///
+/// Heights and the grid origin are stored in f64 so georeferenced data
+/// (e.g. UTM coordinates in the millions) keeps full precision. The mesh
+/// is rebased to a nearby origin before converting to f32 for the GPU.
 #[derive(Debug, Clone)]
 pub struct TerrainData {
     /// Number of columns (X dimension)
     pub width: usize,
     /// Number of rows (Z dimension)
     pub height: usize,
-    /// 2D grid of height values, indexed as `points[z][x]`
-    pub points: Vec<Vec<f32>>,
-    /// Optional per-vertex colors as RGB values (0xRRGGBB).
-    /// Parsed from FDF files but not yet used in mesh generation.
-    #[allow(dead_code)] // Reserved for future per-vertex color support
-    pub colors: Option<Vec<Vec<u32>>>,
+    /// Height values in row-major order, `width` per row; see [`Self::at`]
+    points: Vec<f64>,
+    /// World-space (x, z) position of sample `[0][0]`
+    pub origin: DVec2,
+    /// Distance between adjacent samples in world units
+    pub cell_size: f64,
+    /// Unit of the heights and the cell size
+    pub units: Units,
+    /// Optional per-vertex colors as RGB values (0xRRGGBB), parsed from
+    /// FDF files or painted in the viewer, in the same row-major order as
+    /// the heights.
+    pub colors: Option<Vec<u32>>,
+    /// Height bounds, computed on first use
+    bounds: OnceLock<(f64, f64)>,
+    /// Other values measured over the samples; elevation is the heights
+    channels: Vec<Channel>,
 }
 
 impl TerrainData {
-    /// Create new terrain data from a 2D grid of heights.
+    /// Create new terrain data from rows of heights.
     ///
     /// # Arguments
     ///
-    /// * `points` - 2D vector of height values, indexed as `[row][column]`
-    /// * `colors` - Optional 2D vector of RGB colors (0xRRGGBB format)
+    /// * `rows` - Rows of height values of equal length, indexed as
+    ///   `[row][column]`
+    /// * `colors` - Optional rows of RGB colors (0xRRGGBB format)
     ///
     /// # Example
     ///
     /// ```
     /// use lrle::terrain::TerrainData;
     ///
-    /// let points = vec![
+    /// let rows = vec![
     ///     vec![0.0, 1.0, 2.0],
     ///     vec![1.0, 2.0, 3.0],
     /// ];
-    /// let terrain = TerrainData::new(points, None);
+    /// let terrain = TerrainData::new(rows, None);
     /// assert_eq!(terrain.width, 3);
     /// assert_eq!(terrain.height, 2);
     /// ```
-    pub fn new(points: Vec<Vec<f32>>, colors: Option<Vec<Vec<u32>>>) -> Self {
-        let height = points.len();
-        let width = points.first().map(|r| r.len()).unwrap_or(0);
+    pub fn new(rows: Vec<Vec<f64>>, colors: Option<Vec<Vec<u32>>>) -> Self {
+        let width = rows.first().map(|r| r.len()).unwrap_or(0);
+        Self::from_heights(width, rows.concat(), colors.map(|c| c.concat()))
+    }
+
+    /// Create new terrain data from heights in row-major order, `width` per
+    /// row, with optional colors in the same order.
+    pub fn from_heights(width: usize, points: Vec<f64>, colors: Option<Vec<u32>>) -> Self {
+        let height = points.len().checked_div(width).unwrap_or(0);
         Self {
             width,
             height,
             points,
+            origin: DVec2::ZERO,
+            cell_size: 1.0,
+            units: Units::default(),
             colors,
+            bounds: OnceLock::new(),
+            channels: Vec::new(),
         }
     }
 
-    /// Returns the minimum and maximum height values in the terrain.
-    ///
-    /// Returns `(0.0, 0.0)` for empty terrain.
-    pub fn height_bounds(&self) -> (f32, f32) {
-        let mut min = f32::MAX;
-        let mut max = f32::MIN;
-
-        for row in &self.points {
-            for &h in row {
-                min = min.min(h);
-                max = max.max(h);
+    /// Unscaled height of sample `(x, z)`.
+    #[inline]
+    pub fn at(&self, x: usize, z: usize) -> f64 {
+        self.points[z * self.width + x]
+    }
+
+    /// All heights in row-major order.
+    pub fn heights(&self) -> &[f64] {
+        &self.points
+    }
+
+    /// Heights for editing in place. Clears the cached height bounds.
+    pub fn heights_mut(&mut self) -> &mut [f64] {
+        self.bounds = OnceLock::new();
+        &mut self.points
+    }
+
+    /// Heights of row `z`.
+    pub fn row(&self, z: usize) -> &[f64] {
+        &self.points[z * self.width..(z + 1) * self.width]
+    }
+
+    /// Rows of heights from `z = 0`.
+    pub fn rows(&self) -> ChunksExact<'_, f64> {
+        self.points.chunks_exact(self.width.max(1))
+    }
+
+    /// Named value grids besides elevation, in the order they were added.
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    /// Add `source`'s heights as a channel named `name`, sampled at this
+    /// terrain's sample positions (see [`grid::resample`]).
+    pub fn add_channel(&mut self, name: impl Into<String>, source: &TerrainData) {
+        let values = grid::resample(source, self)
+            .into_iter()
+            .map(|v| v as f32)
+            .collect();
+        self.channels.push(Channel {
+            name: name.into(),
+            grid: Grid::new(self.width, values),
+        });
+    }
+
+    /// Store `grid` as the channel named `name`, replacing a channel of that
+    /// name or adding one, and return its index.
+    pub fn set_channel(&mut self, name: &str, grid: Grid<f32>) -> usize {
+        match self
+            .channels
+            .iter()
+            .position(|channel| channel.name == name)
+        {
+            Some(index) => {
+                self.channels[index].grid = grid;
+                index
+            }
+            None => {
+                self.channels.push(Channel {
+                    name: name.to_string(),
+                    grid,
+                });
+                self.channels.len() - 1
             }
         }
+    }
+
+    /// World-space position of sample `(x, z)` with unscaled height.
+    pub fn world_position(&self, x: usize, z: usize) -> DVec3 {
+        DVec3::new(
+            self.origin.x + x as f64 * self.cell_size,
+            self.at(x, z),
+            self.origin.y + z as f64 * self.cell_size,
+        )
+    }
+
+    /// World-space center of the grid footprint, at height zero.
+    pub fn center(&self) -> DVec3 {
+        let half_width = self.width.saturating_sub(1) as f64 / 2.0;
+        let half_height = self.height.saturating_sub(1) as f64 / 2.0;
+        DVec3::new(
+            self.origin.x + half_width * self.cell_size,
+            0.0,
+            self.origin.y + half_height * self.cell_size,
+        )
+    }
+
+    /// Unscaled height at world-space (`x`, `z`), bilinearly interpolated,
+    /// or `None` outside the grid footprint.
+    pub fn height_at(&self, x: f64, z: f64) -> Option<f64> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        ao::sample(
+            self,
+            (x - self.origin.x) / self.cell_size,
+            (z - self.origin.y) / self.cell_size,
+        )
+    }
 
-        if min > max {
-            (0.0, 0.0)
-        } else {
-            (min, max)
+    /// The samples in `columns` and `rows`, clipped to the grid, as a
+    /// terrain of their own in the same place, with their colors and
+    /// channels.
+    pub fn crop(&self, columns: Range<usize>, rows: Range<usize>) -> Self {
+        let clip = |range: Range<usize>, len: usize| range.start.min(len)..range.end.min(len);
+        let (columns, rows) = (clip(columns, self.width), clip(rows, self.height));
+        let heights = grid::crop(&self.points, self.width, columns.clone(), rows.clone());
+        let colors = self
+            .colors
+            .as_deref()
+            .map(|colors| grid::crop(colors, self.width, columns.clone(), rows.clone()));
+        let mut cropped = Self::from_heights(columns.len(), heights, colors);
+        cropped.origin =
+            self.origin + DVec2::new(columns.start as f64, rows.start as f64) * self.cell_size;
+        cropped.cell_size = self.cell_size;
+        cropped.units = self.units;
+        cropped.channels = self
+            .channels
+            .iter()
+            .map(|channel| Channel {
+                name: channel.name.clone(),
+                grid: channel.grid.crop(columns.clone(), rows.clone()),
+            })
+            .collect();
+        cropped
+    }
+
+    /// Row-major index of the sample nearest world-space (`x`, `z`), or
+    /// `None` outside the grid footprint.
+    pub fn nearest_sample(&self, x: f64, z: f64) -> Option<usize> {
+        let gx = ((x - self.origin.x) / self.cell_size).round();
+        let gz = ((z - self.origin.y) / self.cell_size).round();
+        let inside = gx >= 0.0 && gz >= 0.0 && gx < self.width as f64 && gz < self.height as f64;
+        inside.then(|| gz as usize * self.width + gx as usize)
+    }
+
+    /// Set the color of every sample within `radius` cells of sample
+    /// `(x, z)` to `color` (0xRRGGBB), returning whether any changed. A
+    /// terrain without colors gets white ones first, as in .fdf files.
+    pub fn paint(&mut self, (x, z): (usize, usize), radius: f64, color: u32) -> bool {
+        let len = self.points.len();
+        let colors = self.colors.get_or_insert_with(|| vec![0xFFFFFF; len]);
+        let reach = radius.max(0.0).floor() as usize;
+        let mut changed = false;
+        for sz in z.saturating_sub(reach)..(z + reach + 1).min(self.height) {
+            for sx in x.saturating_sub(reach)..(x + reach + 1).min(self.width) {
+                let (dx, dz) = (sx.abs_diff(x) as f64, sz.abs_diff(z) as f64);
+                if dx * dx + dz * dz > radius * radius {
+                    continue;
+                }
+                let sample = &mut colors[sz * self.width + sx];
+                changed |= *sample != color;
+                *sample = color;
+            }
         }
+        changed
+    }
+
+    /// First point where a world-space ray meets the surface, with heights
+    /// multiplied by `height_scale`, as found by [`Heightfield::raycast`].
+    /// The returned point has the unscaled surface height.
+    pub fn raycast(&self, origin: DVec3, direction: DVec3, height_scale: f64) -> Option<DVec3> {
+        let hit = Heightfield::new(self, height_scale).raycast(origin, direction)?;
+        let (x, z) = (hit.position.x, hit.position.z);
+        Some(DVec3::new(x, self.height_at(x, z)?, z))
+    }
+
+    /// Returns the minimum and maximum height values in the terrain.
+    ///
+    /// Returns `(0.0, 0.0)` for empty terrain. The scan runs once and is
+    /// cached until the heights are edited through [`Self::heights_mut`].
+    pub fn height_bounds(&self) -> (f64, f64) {
+        *self
+            .bounds
+            .get_or_init(|| scan::min_max(&self.points).unwrap_or((0.0, 0.0)))
+    }
+
+    /// Take `bounds` as the height range, e.g. for a window of a larger
+    /// terrain to be colored like the whole, until the heights are edited.
+    pub fn set_height_bounds(&mut self, bounds: (f64, f64)) {
+        self.bounds = OnceLock::from(bounds);
     }
 }
 
@@ -119,6 +400,113 @@ mod tests {
         assert_eq!(max, 10.0);
     }
 
+    #[test]
+    fn test_height_bounds_follow_edits() {
+        let mut terrain = TerrainData::new(vec![vec![1.0, 2.0]], None);
+        assert_eq!(terrain.height_bounds(), (1.0, 2.0));
+
+        terrain.heights_mut()[1] = 7.0;
+        assert_eq!(terrain.height_bounds(), (1.0, 7.0));
+    }
+
+    #[test]
+    fn test_world_position_and_center() {
+        let mut terrain = TerrainData::new(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]], None);
+        terrain.origin = DVec2::new(500_000.0, 4_000_000.0);
+        terrain.cell_size = 30.0;
+
+        assert_eq!(
+            terrain.world_position(2, 1),
+            DVec3::new(500_060.0, 6.0, 4_000_030.0)
+        );
+        assert_eq!(terrain.center(), DVec3::new(500_030.0, 0.0, 4_000_015.0));
+    }
+
+    #[test]
+    fn test_crop_keeps_place_colors_and_channels() {
+        let rows = (0..4)
+            .map(|z| (0..5).map(|x| (x + 10 * z) as f64).collect())
+            .collect();
+        let colors = (0..4)
+            .map(|z| (0..5).map(|x| x + 10 * z).collect())
+            .collect();
+        let mut terrain = TerrainData::new(rows, Some(colors));
+        terrain.origin = DVec2::new(100.0, 200.0);
+        terrain.cell_size = 2.0;
+        let channel = terrain.clone();
+        terrain.add_channel("copy", &channel);
+
+        // Rows run past the grid and are clipped
+        let cropped = terrain.crop(1..3, 2..9);
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert_eq!(cropped.heights(), [21.0, 22.0, 31.0, 32.0]);
+        assert_eq!(cropped.colors.as_deref(), Some(&[21, 22, 31, 32][..]));
+        assert_eq!(
+            cropped.channels()[0].grid.values(),
+            [21.0, 22.0, 31.0, 32.0]
+        );
+        assert_eq!(cropped.world_position(0, 0), terrain.world_position(1, 2));
+        assert_eq!(cropped.height_bounds(), (21.0, 32.0));
+
+        assert_eq!(terrain.crop(7..9, 0..4).width, 0);
+    }
+
+    #[test]
+    fn test_height_at_and_nearest_sample() {
+        let mut terrain = TerrainData::new(vec![vec![0.0, 2.0], vec![4.0, 6.0]], None);
+        terrain.origin = DVec2::new(10.0, 20.0);
+        terrain.cell_size = 2.0;
+
+        assert_eq!(terrain.height_at(11.0, 21.0), Some(3.0));
+        assert_eq!(terrain.height_at(9.0, 21.0), None);
+        assert_eq!(terrain.nearest_sample(11.5, 20.4), Some(1));
+        assert_eq!(terrain.nearest_sample(12.9, 22.9), Some(3));
+        assert_eq!(terrain.nearest_sample(13.1, 20.0), None);
+    }
+
+    #[test]
+    fn test_paint_colors_disc() {
+        let mut terrain = TerrainData::new(vec![vec![0.0; 5]; 5], None);
+        assert!(terrain.paint((2, 2), 1.0, 0xFF0000));
+        let colors = terrain.colors.as_ref().unwrap();
+        let painted: Vec<usize> = (0..25).filter(|&i| colors[i] == 0xFF0000).collect();
+        assert_eq!(painted, [7, 11, 12, 13, 17]);
+        assert_eq!(colors[0], 0xFFFFFF);
+
+        // Clipped at the edges; repainting the same color changes nothing
+        assert!(terrain.paint((0, 0), 1.5, 0x00FF00));
+        assert!(!terrain.paint((0, 0), 1.5, 0x00FF00));
+        let colors = terrain.colors.as_ref().unwrap();
+        assert_eq!([colors[0], colors[1], colors[5], colors[6]], [0x00FF00; 4]);
+    }
+
+    #[test]
+    fn test_raycast_hits_surface() {
+        // A slope rising along x
+        let points = vec![(0..5).map(|x| x as f64).collect::<Vec<_>>(); 5];
+        let terrain = TerrainData::new(points, None);
+
+        // Straight down onto the slope, with heights doubled
+        let hit = terrain
+            .raycast(DVec3::new(1.5, 50.0, 2.0), DVec3::NEG_Y, 2.0)
+            .unwrap();
+        assert!((hit - DVec3::new(1.5, 1.5, 2.0)).length() < 1e-6);
+
+        // Level from beyond the low edge, meeting the slope at its own height
+        let hit = terrain
+            .raycast(DVec3::new(-10.0, 2.0, 2.0), DVec3::X, 1.0)
+            .unwrap();
+        assert!((hit.x - 2.0).abs() < 1e-6);
+
+        // Rays missing the footprint or starting underground
+        assert!(terrain
+            .raycast(DVec3::new(10.0, 50.0, 2.0), DVec3::NEG_Y, 1.0)
+            .is_none());
+        assert!(terrain
+            .raycast(DVec3::new(3.0, 1.0, 2.0), DVec3::Y, 1.0)
+            .is_none());
+    }
+
     #[test]
     fn test_height_bounds_empty() {
         let terrain = TerrainData::new(vec![], None);
@@ -0,0 +1,119 @@
+//! Wavefront OBJ mesh export.
+//!
+//! Writes the solid surface of a [`TerrainMesh`] with normals and per-vertex
+//! colors (the common `v x y z r g b` extension), plus texture coordinates
+//! and a companion MTL material when a texture such as a baked lightmap is
+//! exported alongside it. Vertex colors are written in sRGB, as OBJ
+//! readers display them unconverted.
+
+use std::fmt::Write;
+
+use super::colors::linear_to_srgb;
+use super::TerrainMesh;
+
+/// Name of the material referenced by textured exports.
+const MATERIAL: &str = "terrain";
+
+/// Serialize the mesh's triangles as OBJ text.
+///
+/// With `uvs`, faces reference texture coordinates and use the material
+/// from `mtllib`, which should name a file written with [`to_mtl`].
+pub fn to_obj(mesh: &TerrainMesh, uvs: Option<&[[f32; 2]]>, mtllib: Option<&str>) -> String {
+    let mut out = String::from("# lrle terrain\n");
+    if let Some(mtllib) = mtllib {
+        let _ = writeln!(out, "mtllib {}", mtllib);
+    }
+    for v in &mesh.vertices {
+        let [x, y, z] = v.position;
+        let [r, g, b] = v.color.map(linear_to_srgb);
+        let _ = writeln!(out, "v {} {} {} {} {} {}", x, y, z, r, g, b);
+    }
+    for v in &mesh.vertices {
+        let [x, y, z] = v.normal;
+        let _ = writeln!(out, "vn {} {} {}", x, y, z);
+    }
+    if let Some(uvs) = uvs {
+        // OBJ texture space has v pointing up
+        for [u, v] in uvs {
+            let _ = writeln!(out, "vt {} {}", u, 1.0 - v);
+        }
+    }
+    if mtllib.is_some() {
+        let _ = writeln!(out, "usemtl {}", MATERIAL);
+    }
+
+    for triangle in mesh.triangle_indices.chunks_exact(3) {
+        out.push('f');
+        // OBJ indices are 1-based
+        for &i in triangle {
+            let i = i + 1;
+            match uvs {
+                Some(_) => {
+                    let _ = write!(out, " {}/{}/{}", i, i, i);
+                }
+                None => {
+                    let _ = write!(out, " {}//{}", i, i);
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Material file for a textured [`to_obj`] export showing `texture`
+/// without lighting.
+pub fn to_mtl(texture: &str) -> String {
+    format!(
+        "newmtl {}\nKa 1 1 1\nKd 1 1 1\nillum 0\nmap_Kd {}\n",
+        MATERIAL, texture
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::TerrainData;
+
+    #[test]
+    fn test_obj_counts() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]; 3], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let obj = to_obj(&mesh, None, None);
+
+        let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+        assert_eq!(count("v "), 9);
+        assert_eq!(count("vn "), 9);
+        assert_eq!(count("vt "), 0);
+        assert_eq!(count("f "), mesh.triangle_indices.len() / 3);
+        assert!(obj.contains("\nf 1//1 4//4 2//2\n"));
+        assert!(!obj.contains("usemtl"));
+
+        // Colors are back in sRGB: mid-height terrain green is (0.2, 0.8, 0.4)
+        let color: Vec<f32> = obj
+            .lines()
+            .find(|l| l.starts_with("v 0 1 0 "))
+            .unwrap()
+            .split(' ')
+            .skip(4)
+            .map(|c| c.parse().unwrap())
+            .collect();
+        for (c, expected) in color.iter().zip([0.2, 0.8, 0.4]) {
+            assert!((c - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_textured_obj() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0]; 2], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let uvs = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]];
+        let obj = to_obj(&mesh, Some(&uvs), Some("terrain.mtl"));
+
+        assert!(obj.contains("mtllib terrain.mtl\n"));
+        assert!(obj.contains("usemtl terrain\n"));
+        assert!(obj.contains("vt 0 1\n"));
+        assert!(obj.contains("\nf 1/1/1 3/3/3 2/2/2\n"));
+        assert!(to_mtl("terrain.png").ends_with("map_Kd terrain.png\n"));
+    }
+}
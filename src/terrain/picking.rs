@@ -0,0 +1,73 @@
+//! Terrain points picked under the cursor.
+//!
+//! A view ray is intersected with the height grid (see
+//! [`TerrainData::raycast`]) and the hit described in grid terms: where it
+//! falls between the samples and which sample it's nearest, with that
+//! sample's height as stored in the terrain file.
+
+use glam::{DVec2, DVec3, Vec3Swizzles};
+
+use super::TerrainData;
+
+/// A point on the terrain surface and the sample nearest to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    /// World-space point hit, with the unscaled surface height
+    pub position: DVec3,
+    /// Fractional grid coordinates (column, row) of the point
+    pub grid: DVec2,
+    /// Nearest sample (column, row)
+    pub sample: (usize, usize),
+    /// Stored height of the nearest sample
+    pub height: f64,
+}
+
+impl Pick {
+    /// First point where the ray from `origin` along `direction` meets the
+    /// surface with heights multiplied by `height_scale`.
+    pub fn cast(
+        terrain: &TerrainData,
+        origin: DVec3,
+        direction: DVec3,
+        height_scale: f64,
+    ) -> Option<Self> {
+        Self::at(terrain, terrain.raycast(origin, direction, height_scale)?)
+    }
+
+    /// Describe the world-space surface point `position`, or `None` outside
+    /// the grid footprint.
+    pub fn at(terrain: &TerrainData, position: DVec3) -> Option<Self> {
+        let index = terrain.nearest_sample(position.x, position.z)?;
+        let sample = (index % terrain.width, index / terrain.width);
+        Some(Self {
+            position,
+            grid: (position.xz() - terrain.origin) / terrain.cell_size,
+            sample,
+            height: terrain.at(sample.0, sample.1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_nearest_sample() {
+        let mut terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0], vec![3.0, 4.0, 5.0]], None);
+        terrain.origin = DVec2::new(100.0, 50.0);
+        terrain.cell_size = 2.0;
+
+        // Straight down onto a point between the samples
+        let origin = DVec3::new(103.2, 100.0, 51.2);
+        let pick = Pick::cast(&terrain, origin, DVec3::NEG_Y, 1.0).unwrap();
+        assert!((pick.grid - DVec2::new(1.6, 0.6)).length() < 1e-6);
+        assert_eq!(pick.sample, (2, 1));
+        assert_eq!(pick.height, 5.0);
+        // The hit follows the surface rather than the sample
+        assert!((pick.position.y - 3.4).abs() < 1e-6);
+
+        // Rays missing the grid pick nothing
+        assert_eq!(Pick::cast(&terrain, origin, DVec3::Y, 1.0), None);
+    }
+}
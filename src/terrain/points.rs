@@ -0,0 +1,396 @@
+//! Point features such as wells or weather stations.
+//!
+//! Points are read from CSV, one per row with a header naming the columns,
+//! or from the Point and MultiPoint features of GeoJSON, with the other
+//! columns or the feature properties as attributes. Like the annotations
+//! they're world-space (x, z) positions, drawn as pins on the surface
+//! whose color and size can follow an attribute.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use glam::DVec2;
+use serde_json::Value;
+
+use super::colors::{height_to_color, ColorScheme};
+
+/// CSV columns taken as the x and z coordinates, in order of preference.
+const COORDINATE_COLUMNS: [(&str, &str); 5] = [
+    ("x", "z"),
+    ("x", "y"),
+    ("easting", "northing"),
+    ("lon", "lat"),
+    ("longitude", "latitude"),
+];
+
+/// Color of points when no attribute colors them.
+const POINT_COLOR: [f32; 3] = [0.2, 0.6, 1.0];
+
+/// Colors of the values of a text attribute, repeating past the last.
+const CATEGORY_COLORS: [[f32; 3]; 8] = [
+    [0.12, 0.47, 0.71],
+    [1.0, 0.5, 0.05],
+    [0.17, 0.63, 0.17],
+    [0.84, 0.15, 0.16],
+    [0.58, 0.4, 0.74],
+    [0.55, 0.34, 0.29],
+    [0.89, 0.47, 0.76],
+    [0.74, 0.74, 0.13],
+];
+
+/// Smallest and largest marker size relative to the configured size, for
+/// the lowest and highest value of the attribute sizing them.
+const SIZE_RANGE: (f64, f64) = (0.5, 2.0);
+
+/// How point features are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointConfig {
+    /// Whether the markers are drawn
+    pub enabled: bool,
+    /// Marker height, in cells
+    pub size: f32,
+    /// Attribute coloring the markers, by index into
+    /// [`PointSet::attributes`]
+    pub color_by: Option<usize>,
+    /// Numeric attribute scaling the markers
+    pub size_by: Option<usize>,
+}
+
+impl Default for PointConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: 6.0,
+            color_by: None,
+            size_by: None,
+        }
+    }
+}
+
+/// A point with its attribute values, in the order of
+/// [`PointSet::attributes`]; empty where the file has none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointFeature {
+    pub position: DVec2,
+    pub values: Vec<String>,
+}
+
+/// Points of one file, sharing its attribute names.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PointSet {
+    /// Attribute names, in file order
+    pub attributes: Vec<String>,
+    pub points: Vec<PointFeature>,
+}
+
+impl PointSet {
+    /// Values of attribute `index` as numbers, if all that are given parse.
+    fn numbers(&self, index: usize) -> Option<Vec<Option<f64>>> {
+        self.points
+            .iter()
+            .map(|point| match point.values[index].trim() {
+                "" => Some(None),
+                value => value.parse().ok().map(Some),
+            })
+            .collect()
+    }
+
+    /// Whether attribute `index` holds numbers, so it can size markers.
+    pub fn is_numeric(&self, index: usize) -> bool {
+        self.numbers(index)
+            .is_some_and(|numbers| numbers.iter().any(Option::is_some))
+    }
+
+    /// Marker color of each point: along the heatmap scale for numeric
+    /// attributes, one color per distinct value for text.
+    pub fn colors(&self, config: &PointConfig) -> Vec<[f32; 3]> {
+        let Some(index) = config.color_by.filter(|&i| i < self.attributes.len()) else {
+            return vec![POINT_COLOR; self.points.len()];
+        };
+        if let Some(numbers) = self.numbers(index) {
+            let scale = Scale::of(&numbers);
+            return numbers
+                .iter()
+                .map(|n| {
+                    n.map_or(POINT_COLOR, |n| {
+                        height_to_color(scale.apply(n) as f32, ColorScheme::Heatmap)
+                    })
+                })
+                .collect();
+        }
+        let mut categories: Vec<&str> = Vec::new();
+        self.points
+            .iter()
+            .map(|point| {
+                let value = point.values[index].as_str();
+                let category = match categories.iter().position(|&c| c == value) {
+                    Some(category) => category,
+                    None => {
+                        categories.push(value);
+                        categories.len() - 1
+                    }
+                };
+                CATEGORY_COLORS[category % CATEGORY_COLORS.len()]
+            })
+            .collect()
+    }
+
+    /// Marker height of each point in cells, scaled between half and twice
+    /// the configured size by a numeric attribute.
+    pub fn sizes(&self, config: &PointConfig) -> Vec<f64> {
+        let size = config.size as f64;
+        let numbers = config
+            .size_by
+            .filter(|&i| i < self.attributes.len())
+            .and_then(|index| self.numbers(index));
+        let Some(numbers) = numbers else {
+            return vec![size; self.points.len()];
+        };
+        let scale = Scale::of(&numbers);
+        let (min, max) = SIZE_RANGE;
+        numbers
+            .iter()
+            .map(|n| n.map_or(size, |n| size * (min + (max - min) * scale.apply(n))))
+            .collect()
+    }
+}
+
+/// Linear map of a value range onto 0.0-1.0.
+struct Scale {
+    min: f64,
+    span: f64,
+}
+
+impl Scale {
+    fn of(numbers: &[Option<f64>]) -> Self {
+        let (min, max) = numbers
+            .iter()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &n| {
+                (min.min(n), max.max(n))
+            });
+        Self {
+            min,
+            span: max - min,
+        }
+    }
+
+    fn apply(&self, n: f64) -> f64 {
+        if self.span > 0.0 {
+            (n - self.min) / self.span
+        } else {
+            0.5
+        }
+    }
+}
+
+/// Points from the CSV or GeoJSON file at `path`, by its extension.
+pub fn load(path: &Path) -> Result<PointSet> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("csv") => from_csv(&text),
+        Some("geojson" | "json") => from_geojson(&text),
+        _ => bail!(
+            "Unknown point format for {}, expected .csv or .geojson",
+            path.display()
+        ),
+    }
+    .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Points of a CSV table with a header row. The coordinates are the first
+/// of the column pairs x and z, x and y, easting and northing, lon and lat
+/// or longitude and latitude found, by case-insensitive name; the other
+/// columns are attributes.
+pub fn from_csv(text: &str) -> Result<PointSet> {
+    let mut rows = text.lines().filter(|line| !line.trim().is_empty());
+    let header = split_csv(rows.next().ok_or_else(|| anyhow!("Empty file"))?);
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column.trim().eq_ignore_ascii_case(name))
+    };
+    let (x, z) = COORDINATE_COLUMNS
+        .iter()
+        .find_map(|&(x, z)| Some((column(x)?, column(z)?)))
+        .ok_or_else(|| anyhow!("No coordinate columns, expected x and z or lon and lat"))?;
+
+    let attributes: Vec<usize> = (0..header.len()).filter(|&i| i != x && i != z).collect();
+    let mut points = Vec::new();
+    for (row, line) in rows.enumerate() {
+        let fields = split_csv(line);
+        let coordinate = |i: usize| -> Result<f64> {
+            let field = fields.get(i).map_or("", |f| f.trim());
+            field
+                .parse()
+                .with_context(|| format!("Row {}: invalid coordinate {field:?}", row + 1))
+        };
+        points.push(PointFeature {
+            position: DVec2::new(coordinate(x)?, coordinate(z)?),
+            values: attributes
+                .iter()
+                .map(|&i| fields.get(i).map_or("", |f| f.trim()).to_string())
+                .collect(),
+        });
+    }
+    Ok(PointSet {
+        attributes: attributes
+            .iter()
+            .map(|&i| header[i].trim().to_string())
+            .collect(),
+        points,
+    })
+}
+
+/// Fields of a CSV line, unquoting fields in double quotes.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Points of the Point and MultiPoint features of a GeoJSON
+/// FeatureCollection, with their properties as attributes. Other
+/// geometries are skipped.
+pub fn from_geojson(text: &str) -> Result<PointSet> {
+    let value: Value = serde_json::from_str(text).context("Invalid JSON")?;
+    let features = match value["type"].as_str() {
+        Some("FeatureCollection") => value["features"]
+            .as_array()
+            .cloned()
+            .ok_or_else(|| anyhow!("Expected features"))?,
+        Some("Feature") => vec![value],
+        _ => bail!("Expected a Feature or FeatureCollection"),
+    };
+
+    let point = |position: &Value| match position.as_array().map(Vec::as_slice) {
+        Some([x, z, ..]) => Some(DVec2::new(x.as_f64()?, z.as_f64()?)),
+        _ => None,
+    };
+    let mut found = Vec::new();
+    for (i, feature) in features.iter().enumerate() {
+        let geometry = &feature["geometry"];
+        let coordinates = &geometry["coordinates"];
+        let positions = match geometry["type"].as_str() {
+            Some("Point") => vec![point(coordinates)],
+            Some("MultiPoint") => coordinates.as_array().map_or(Vec::new(), |positions| {
+                positions.iter().map(point).collect()
+            }),
+            _ => continue,
+        };
+        let positions = positions
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("Feature {i}: expected positions"))?;
+        let properties = feature["properties"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        found.extend(positions.into_iter().map(|p| (p, properties.clone())));
+    }
+
+    // Attributes in order of first appearance, each feature's by name
+    let mut attributes: Vec<String> = Vec::new();
+    for (_, properties) in &found {
+        for key in properties.keys() {
+            if !attributes.contains(key) {
+                attributes.push(key.clone());
+            }
+        }
+    }
+    let points = found
+        .into_iter()
+        .map(|(position, properties)| PointFeature {
+            position,
+            values: attributes
+                .iter()
+                .map(|key| match properties.get(key) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(PointSet { attributes, points })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_columns_and_quotes() {
+        let text = "name,Lat,depth,Lon\n\"Well, north\",20,12.5,10\nsouth,5,,3\n";
+        let set = from_csv(text).unwrap();
+        assert_eq!(set.attributes, vec!["name", "depth"]);
+        assert_eq!(set.points.len(), 2);
+        assert_eq!(set.points[0].position, DVec2::new(10.0, 20.0));
+        assert_eq!(set.points[0].values, vec!["Well, north", "12.5"]);
+        assert_eq!(set.points[1].values, vec!["south", ""]);
+        assert!(set.is_numeric(1) && !set.is_numeric(0));
+
+        assert!(from_csv("name,depth\na,1\n").is_err());
+        assert!(from_csv("x,z\n1,a\n").is_err());
+    }
+
+    #[test]
+    fn test_geojson_points_with_properties() {
+        let text = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","geometry":{"type":"Point","coordinates":[1,2]},
+             "properties":{"id":"A","flow":3}},
+            {"type":"Feature","geometry":{"type":"MultiPoint","coordinates":[[3,4],[5,6]]},
+             "properties":{"flow":1.5,"status":null}},
+            {"type":"Feature","geometry":{"type":"LineString","coordinates":[[0,0],[1,1]]}}
+        ]}"#;
+        let set = from_geojson(text).unwrap();
+        assert_eq!(set.attributes, vec!["flow", "id", "status"]);
+        assert_eq!(set.points.len(), 3);
+        assert_eq!(set.points[0].values, vec!["3", "A", ""]);
+        assert_eq!(set.points[2].position, DVec2::new(5.0, 6.0));
+        assert_eq!(set.points[2].values, vec!["1.5", "", ""]);
+    }
+
+    #[test]
+    fn test_attributes_drive_color_and_size() {
+        let set = from_csv("x,z,kind,depth\n0,0,a,10\n1,0,b,20\n2,0,a,30\n").unwrap();
+        let config = PointConfig {
+            color_by: Some(0),
+            size_by: Some(1),
+            ..PointConfig::default()
+        };
+        let colors = set.colors(&config);
+        assert_eq!(colors[0], colors[2]);
+        assert_ne!(colors[0], colors[1]);
+
+        let sizes = set.sizes(&config);
+        let size = config.size as f64;
+        assert_eq!(sizes, vec![size * 0.5, size * 1.25, size * 2.0]);
+
+        let numeric = PointConfig {
+            color_by: Some(1),
+            ..config
+        };
+        let colors = set.colors(&numeric);
+        assert_eq!(colors[0], height_to_color(0.0, ColorScheme::Heatmap));
+        assert_eq!(colors[2], height_to_color(1.0, ColorScheme::Heatmap));
+    }
+}
@@ -0,0 +1,216 @@
+//! Smoothing and downsampling that replace the terrain's grid.
+//!
+//! LiDAR-derived grids are often too noisy and too dense to view raw.
+//! Unlike the denoise filter, which only changes the heights the mesh is
+//! built from, these produce a new [`TerrainData`] that analysis, tools
+//! and exports all work on, with the original kept by the viewer to go
+//! back to.
+
+use rayon::prelude::*;
+
+use super::grid::{Channel, Grid};
+use super::TerrainData;
+
+/// Downsampling factors offered in the UI.
+pub const DOWNSAMPLE_FACTORS: [usize; 2] = [2, 4];
+
+/// An operation replacing the terrain's grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Processing {
+    /// Gaussian blur of the heights with this standard deviation, in
+    /// samples
+    Smooth(f64),
+    /// Keep every this many samples along each side
+    Downsample(usize),
+}
+
+impl Processing {
+    /// Apply the operation to `terrain`.
+    pub fn apply(self, terrain: &TerrainData) -> TerrainData {
+        match self {
+            Self::Smooth(sigma) => gaussian(terrain, sigma),
+            Self::Downsample(factor) => downsample(terrain, factor),
+        }
+    }
+}
+
+/// Copy of `terrain` with its heights blurred by a Gaussian of standard
+/// deviation `sigma` samples, cut off at 3σ. Missing samples stay missing
+/// and are left out of their neighbors' averages; colors and channels are
+/// kept as they are.
+pub fn gaussian(terrain: &TerrainData, sigma: f64) -> TerrainData {
+    let mut smoothed = terrain.clone();
+    if sigma <= 0.0 || terrain.width == 0 {
+        return smoothed;
+    }
+    let reach = (3.0 * sigma).ceil() as isize;
+    let kernel: Vec<f64> = (-reach..=reach)
+        .map(|d| (-((d * d) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let (width, height) = (terrain.width, terrain.height);
+    let heights = terrain.heights();
+
+    // Separable: along the rows, then along the columns
+    let mut across = vec![f64::NAN; heights.len()];
+    across
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(z, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                if heights[z * width + x].is_finite() {
+                    *out = convolve(&kernel, reach, x, width, |sx| heights[z * width + sx]);
+                }
+            }
+        });
+    smoothed
+        .heights_mut()
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(z, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                if out.is_finite() {
+                    *out = convolve(&kernel, reach, z, height, |sz| across[sz * width + x]);
+                }
+            }
+        });
+    smoothed
+}
+
+/// Weighted mean of the finite values `value(i)` around `at`, within
+/// `0..len`, weighted by `kernel` centered on `at`.
+fn convolve(
+    kernel: &[f64],
+    reach: isize,
+    at: usize,
+    len: usize,
+    value: impl Fn(usize) -> f64,
+) -> f64 {
+    let (mut sum, mut weights) = (0.0, 0.0);
+    for (d, &weight) in (-reach..=reach).zip(kernel) {
+        let Some(i) = at.checked_add_signed(d).filter(|&i| i < len) else {
+            continue;
+        };
+        let v = value(i);
+        if v.is_finite() {
+            sum += v * weight;
+            weights += weight;
+        }
+    }
+    sum / weights
+}
+
+/// `terrain` with every `factor`th sample along each side, the first and
+/// the ones `factor` apart, each averaging the valid heights within half
+/// a step of it so the coarser grid doesn't alias the noise. Colors and
+/// channels take the kept sample's values. The grid stays in place on the
+/// ground, with cells `factor` times as large.
+pub fn downsample(terrain: &TerrainData, factor: usize) -> TerrainData {
+    if factor < 2 || terrain.width == 0 {
+        return terrain.clone();
+    }
+    let (width, height) = (terrain.width, terrain.height);
+    let columns = (width - 1) / factor + 1;
+    let rows = (height - 1) / factor + 1;
+    let reach = factor / 2;
+    let window =
+        |center: usize, len: usize| center.saturating_sub(reach)..(center + reach + 1).min(len);
+
+    let heights = (0..rows * columns)
+        .into_par_iter()
+        .map(|i| {
+            let (x, z) = (i % columns * factor, i / columns * factor);
+            if !terrain.at(x, z).is_finite() {
+                return terrain.at(x, z);
+            }
+            let (mut sum, mut count) = (0.0, 0);
+            for sz in window(z, height) {
+                for &h in &terrain.row(sz)[window(x, width)] {
+                    if h.is_finite() {
+                        sum += h;
+                        count += 1;
+                    }
+                }
+            }
+            sum / count as f64
+        })
+        .collect();
+    let kept = |values: &[u32]| -> Vec<u32> {
+        (0..rows * columns)
+            .map(|i| values[i / columns * factor * width + i % columns * factor])
+            .collect()
+    };
+    let mut downsampled =
+        TerrainData::from_heights(columns, heights, terrain.colors.as_deref().map(kept));
+    downsampled.origin = terrain.origin;
+    downsampled.cell_size = terrain.cell_size * factor as f64;
+    downsampled.units = terrain.units;
+    for Channel { name, grid } in terrain.channels() {
+        let values = (0..rows * columns)
+            .map(|i| grid.at(i % columns * factor, i / columns * factor))
+            .collect();
+        downsampled.set_channel(name, Grid::new(columns, values));
+    }
+    downsampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::DVec2;
+
+    /// `width` x `height` grid with checkerboard noise of `noise` around 10.
+    fn noisy(width: usize, height: usize, noise: f64) -> TerrainData {
+        let rows = (0..height)
+            .map(|z| {
+                (0..width)
+                    .map(|x| 10.0 + if (x + z) % 2 == 0 { noise } else { -noise })
+                    .collect()
+            })
+            .collect();
+        TerrainData::new(rows, None)
+    }
+
+    #[test]
+    fn test_gaussian_smooths_noise_and_keeps_holes() {
+        let mut terrain = noisy(9, 9, 1.0);
+        terrain.heights_mut()[40] = f64::NAN;
+        let smoothed = gaussian(&terrain, 1.5);
+        assert!(smoothed.heights()[40].is_nan());
+        for h in smoothed.heights().iter().filter(|h| h.is_finite()) {
+            assert!((h - 10.0).abs() < 0.3, "{h}");
+        }
+        assert_eq!(
+            gaussian(&terrain, 0.0).heights()[..40],
+            terrain.heights()[..40]
+        );
+    }
+
+    #[test]
+    fn test_downsample_keeps_place_and_extent() {
+        let mut terrain = noisy(9, 5, 1.0);
+        terrain.origin = DVec2::new(100.0, 200.0);
+        terrain.colors = Some((0..45).collect());
+        let channel = terrain.clone();
+        terrain.add_channel("copy", &channel);
+
+        let downsampled = downsample(&terrain, 4);
+        assert_eq!((downsampled.width, downsampled.height), (3, 2));
+        assert_eq!(downsampled.cell_size, 4.0);
+        // Corners land on the same ground
+        assert_eq!(
+            downsampled.world_position(2, 1).x,
+            terrain.world_position(8, 4).x
+        );
+        assert_eq!(
+            downsampled.world_position(2, 1).z,
+            terrain.world_position(8, 4).z
+        );
+        assert_eq!(
+            downsampled.colors.as_deref(),
+            Some(&[0, 4, 8, 36, 40, 44][..])
+        );
+        assert_eq!(downsampled.channels()[0].grid.at(1, 0), 11.0);
+        // Averaging the neighborhood cancels most of the noise
+        assert!((downsampled.at(1, 0) - 10.0).abs() < 0.5);
+    }
+}
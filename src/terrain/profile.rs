@@ -0,0 +1,131 @@
+//! Elevation profiles along a straight cut across the terrain.
+//!
+//! The cut is sampled every half cell with the same interpolation as the
+//! line-of-sight check, so the profile follows the surface the mesh shows.
+//! Heights are stored unscaled, whatever the viewer's height exaggeration.
+
+use glam::DVec2;
+
+use super::TerrainData;
+
+/// Heights sampled along a straight line between two points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// World-space (x, z) start of the cut
+    pub start: DVec2,
+    /// World-space (x, z) end of the cut
+    pub end: DVec2,
+    /// Distance from the start and unscaled height of each sample
+    pub samples: Vec<(f64, f64)>,
+}
+
+impl Profile {
+    /// Sample the terrain along the cut from world-space (x, z) point
+    /// `from` to `to`, every half cell and at both ends.
+    ///
+    /// Returns `None` if either point lies outside the grid.
+    pub fn compute(terrain: &TerrainData, from: DVec2, to: DVec2) -> Option<Self> {
+        terrain.height_at(from.x, from.y)?;
+        terrain.height_at(to.x, to.y)?;
+
+        let length = (to - from).length();
+        let steps = ((length / (terrain.cell_size * 0.5)).ceil() as usize).max(1);
+        let samples = (0..=steps)
+            .filter_map(|i| {
+                let t = i as f64 / steps as f64;
+                let point = from.lerp(to, t);
+                let height = terrain.height_at(point.x, point.y)?;
+                Some((length * t, height))
+            })
+            .collect();
+
+        Some(Self {
+            start: from,
+            end: to,
+            samples,
+        })
+    }
+
+    /// Horizontal length of the cut.
+    pub fn length(&self) -> f64 {
+        (self.end - self.start).length()
+    }
+
+    /// Lowest and highest sampled height.
+    pub fn height_range(&self) -> (f64, f64) {
+        self.samples.iter().fold(
+            (f64::INFINITY, f64::NEG_INFINITY),
+            |(low, high), &(_, h)| (low.min(h), high.max(h)),
+        )
+    }
+
+    /// Total height gained and lost walking from the start to the end.
+    pub fn climb(&self) -> (f64, f64) {
+        self.samples
+            .windows(2)
+            .fold((0.0, 0.0), |(ascent, descent), pair| {
+                let rise = pair[1].1 - pair[0].1;
+                (ascent + rise.max(0.0), descent + (-rise).max(0.0))
+            })
+    }
+
+    /// Samples with distance and height both rescaled to 0..=1, for
+    /// plotting. A flat profile lies along the bottom.
+    pub fn normalized(&self) -> Vec<(f64, f64)> {
+        let length = self.length().max(f64::EPSILON);
+        let (low, high) = self.height_range();
+        let range = (high - low).max(f64::EPSILON);
+        self.samples
+            .iter()
+            .map(|&(distance, height)| (distance / length, (height - low) / range))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ridge along x = 2 rising to 4 over flat ground at 0.
+    fn ridge() -> TerrainData {
+        let row = vec![0.0, 2.0, 4.0, 2.0, 0.0];
+        TerrainData::new(vec![row; 3], None)
+    }
+
+    #[test]
+    fn test_profile_across_ridge() {
+        let terrain = ridge();
+        let profile =
+            Profile::compute(&terrain, DVec2::new(0.0, 1.0), DVec2::new(4.0, 1.0)).unwrap();
+
+        // Every half cell, both ends included
+        assert_eq!(profile.samples.len(), 9);
+        assert_eq!(profile.samples[0], (0.0, 0.0));
+        assert_eq!(profile.samples[3], (1.5, 3.0));
+        assert_eq!(profile.samples[8], (4.0, 0.0));
+        assert_eq!(profile.length(), 4.0);
+        assert_eq!(profile.height_range(), (0.0, 4.0));
+        assert_eq!(profile.climb(), (4.0, 4.0));
+
+        let normalized = profile.normalized();
+        assert_eq!(normalized[4], (0.5, 1.0));
+        assert_eq!(normalized[8], (1.0, 0.0));
+    }
+
+    #[test]
+    fn test_profile_outside_grid() {
+        let terrain = ridge();
+        assert!(Profile::compute(&terrain, DVec2::new(0.0, 1.0), DVec2::new(9.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_single_point_profile() {
+        let terrain = ridge();
+        let point = DVec2::new(2.0, 1.0);
+        let profile = Profile::compute(&terrain, point, point).unwrap();
+
+        assert_eq!(profile.samples, vec![(0.0, 4.0), (0.0, 4.0)]);
+        assert_eq!(profile.climb(), (0.0, 0.0));
+        assert_eq!(profile.normalized()[0], (0.0, 0.0));
+    }
+}
@@ -0,0 +1,230 @@
+//! Georeferenced rasters shared by the GIS loaders.
+//!
+//! GeoTIFFs and ESRI ASCII grids both store heights north-up, first row
+//! northernmost, with some samples marked as nodata. Loaders decode into a
+//! [`Raster`] with NaN for nodata and a [`Georef`] placing it on the
+//! ground, then [`Raster::into_terrain`] averages huge rasters down, fills
+//! the nodata and flips the rows to run toward +Z (north).
+
+use std::collections::VecDeque;
+
+use glam::DVec2;
+
+use super::loader::{LoadError, LoadOptions};
+use super::TerrainData;
+
+/// Default limit on the longer side of a loaded raster, in samples.
+pub const DEFAULT_MAX_SIZE: usize = 4096;
+
+/// Row-major raster samples, first row northernmost, NaN for nodata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f64>,
+}
+
+impl Raster {
+    /// Average down until neither side exceeds `options.max_size`, fill
+    /// nodata samples, and place the grid with `georef`.
+    ///
+    /// Nodata samples take `options.nodata_fill`, or the mean of their
+    /// valid neighbors, growing inward from the valid data, when `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LoadError::Image`] if the raster holds no valid samples.
+    pub fn into_terrain(
+        self,
+        georef: Georef,
+        options: &LoadOptions,
+    ) -> Result<TerrainData, LoadError> {
+        let factor = self
+            .width
+            .max(self.height)
+            .div_ceil(options.max_size.max(1));
+        let (mut raster, georef) = if factor > 1 {
+            log::info!(
+                "Averaging {}x{} raster down by {factor}",
+                self.width,
+                self.height
+            );
+            (self.downsample(factor), georef.downsample(factor))
+        } else {
+            (self, georef)
+        };
+        match options.nodata_fill {
+            Some(fill) => {
+                for value in raster.values.iter_mut().filter(|v| v.is_nan()) {
+                    *value = fill;
+                }
+            }
+            None => raster.fill_nodata()?,
+        }
+        Ok(raster.place(&georef))
+    }
+
+    /// Average `factor` x `factor` blocks of valid samples; blocks at the
+    /// right and bottom edges may be partial.
+    fn downsample(&self, factor: usize) -> Self {
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut values = Vec::with_capacity(width * height);
+        for by in 0..height {
+            for bx in 0..width {
+                let (mut sum, mut count) = (0.0, 0);
+                for y in by * factor..((by + 1) * factor).min(self.height) {
+                    let row = &self.values[y * self.width..(y + 1) * self.width];
+                    for &v in &row[bx * factor..((bx + 1) * factor).min(self.width)] {
+                        if !v.is_nan() {
+                            sum += v;
+                            count += 1;
+                        }
+                    }
+                }
+                values.push(if count > 0 {
+                    sum / count as f64
+                } else {
+                    f64::NAN
+                });
+            }
+        }
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// Replace nodata samples with the mean of their valid neighbors,
+    /// growing inward from the valid data.
+    fn fill_nodata(&mut self) -> Result<(), LoadError> {
+        let (width, height) = (self.width, self.height);
+        let mut queue: VecDeque<usize> = (0..self.values.len())
+            .filter(|&i| !self.values[i].is_nan())
+            .collect();
+        if queue.is_empty() {
+            return Err(LoadError::Image("raster has no valid samples".to_string()));
+        }
+        if queue.len() == self.values.len() {
+            return Ok(());
+        }
+
+        let neighbors = move |i: usize| {
+            let (x, y) = ((i % width) as isize, (i / width) as isize);
+            (-1..=1)
+                .flat_map(move |dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+                .filter(move |&(nx, ny)| {
+                    (nx, ny) != (x, y)
+                        && (0..width as isize).contains(&nx)
+                        && (0..height as isize).contains(&ny)
+                })
+                .map(move |(nx, ny)| ny as usize * width + nx as usize)
+        };
+        let mut queued: Vec<bool> = self.values.iter().map(|v| !v.is_nan()).collect();
+        let mut filled = 0;
+        while let Some(i) = queue.pop_front() {
+            if self.values[i].is_nan() {
+                let (sum, count) = neighbors(i)
+                    .map(|n| self.values[n])
+                    .filter(|v| !v.is_nan())
+                    .fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+                self.values[i] = sum / count as f64;
+                filled += 1;
+            }
+            for n in neighbors(i) {
+                if !queued[n] {
+                    queued[n] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+        log::info!("Filled {filled} nodata samples");
+        Ok(())
+    }
+
+    /// Flip the rows to run south to north and place them with `georef`.
+    fn place(self, georef: &Georef) -> TerrainData {
+        let points = self
+            .values
+            .chunks_exact(self.width)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        let mut terrain = TerrainData::from_heights(self.width, points, None);
+        terrain.origin = DVec2::new(
+            georef.origin.x,
+            georef.origin.y - (self.height - 1) as f64 * georef.spacing.y,
+        );
+        terrain.cell_size = (georef.spacing.x + georef.spacing.y) / 2.0;
+        terrain
+    }
+}
+
+/// Ground position of a raster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Georef {
+    /// Easting and northing of the first sample's center
+    pub origin: DVec2,
+    /// Distance between sample centers east and south
+    pub spacing: DVec2,
+}
+
+impl Georef {
+    /// Placement after averaging `factor` x `factor` blocks: the first
+    /// block's center and its size.
+    fn downsample(&self, factor: usize) -> Self {
+        let offset = (factor - 1) as f64 / 2.0;
+        Self {
+            origin: self.origin + DVec2::new(self.spacing.x, -self.spacing.y) * offset,
+            spacing: self.spacing * factor as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cells() -> Georef {
+        Georef {
+            origin: DVec2::ZERO,
+            spacing: DVec2::ONE,
+        }
+    }
+
+    #[test]
+    fn test_nodata_filled_from_neighbors_or_value() {
+        let raster = Raster {
+            width: 3,
+            height: 1,
+            values: vec![2.0, f64::NAN, 4.0],
+        };
+        let filled = raster
+            .clone()
+            .into_terrain(unit_cells(), &LoadOptions::default())
+            .unwrap();
+        assert_eq!(filled.heights(), [2.0, 3.0, 4.0]);
+
+        let options = LoadOptions {
+            nodata_fill: Some(-1.0),
+            ..LoadOptions::default()
+        };
+        let filled = raster.into_terrain(unit_cells(), &options).unwrap();
+        assert_eq!(filled.heights(), [2.0, -1.0, 4.0]);
+    }
+
+    #[test]
+    fn test_all_nodata_is_an_error() {
+        let raster = Raster {
+            width: 2,
+            height: 1,
+            values: vec![f64::NAN; 2],
+        };
+        assert!(matches!(
+            raster.into_terrain(unit_cells(), &LoadOptions::default()),
+            Err(LoadError::Image(_))
+        ));
+    }
+}
@@ -0,0 +1,201 @@
+//! Heights relative to a reference surface.
+//!
+//! Subtracting a reference from the terrain leaves its local relief:
+//! terraces, channels and mounds that absolute elevation coloring hides
+//! under the overall slope of a valley or a mountainside. The reference is
+//! a plane fitted to the whole grid, a smoothed copy of the terrain, or a
+//! second grid such as an earlier survey.
+
+use super::{grid, TerrainData};
+
+/// Surface subtracted from the terrain heights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReferenceSurface {
+    /// Least-squares plane through all samples
+    #[default]
+    Plane,
+    /// Local mean over a square window
+    Smoothed,
+    /// A second loaded grid
+    Grid,
+}
+
+/// Relative relief configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReliefConfig {
+    /// Color by height above the reference instead of absolute height
+    pub enabled: bool,
+    /// Surface the heights are measured from
+    pub reference: ReferenceSurface,
+    /// Half-width of the smoothing window in samples
+    pub smoothing_radius: u32,
+    /// Also subtract the reference from positions, flattening the terrain
+    /// onto it
+    pub flatten: bool,
+}
+
+impl Default for ReliefConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reference: ReferenceSurface::Plane,
+            smoothing_radius: 8,
+            flatten: false,
+        }
+    }
+}
+
+/// Heights of `terrain` above the reference surface in `config`, as a grid
+/// with the same size and footprint.
+///
+/// `grid` is the reference for [`ReferenceSurface::Grid`], sampled at each
+/// terrain sample's world position; returns `None` if it's missing.
+pub fn relative_terrain(
+    terrain: &TerrainData,
+    config: &ReliefConfig,
+    grid: Option<&TerrainData>,
+) -> Option<TerrainData> {
+    let reference = match config.reference {
+        ReferenceSurface::Plane => fit_plane(terrain),
+        ReferenceSurface::Smoothed => smoothed(terrain, config.smoothing_radius as usize),
+        ReferenceSurface::Grid => grid::resample(grid?, terrain),
+    };
+    let mut relative = terrain.clone();
+    for (h, r) in relative.heights_mut().iter_mut().zip(reference) {
+        *h -= r;
+    }
+    Some(relative)
+}
+
+/// Least-squares plane through the samples, evaluated at each sample.
+///
+/// On a full rectangular grid the centered x and z coordinates are
+/// uncorrelated, so the slopes along each axis fit independently.
+fn fit_plane(terrain: &TerrainData) -> Vec<f64> {
+    let (width, height) = (terrain.width, terrain.height);
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let mean_x = (width - 1) as f64 / 2.0;
+    let mean_z = (height - 1) as f64 / 2.0;
+
+    let (mut sum_h, mut sum_xh, mut sum_zh, mut sum_xx, mut sum_zz) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (z, row) in terrain.rows().enumerate() {
+        let dz = z as f64 - mean_z;
+        for (x, &h) in row.iter().enumerate() {
+            let dx = x as f64 - mean_x;
+            sum_h += h;
+            sum_xh += dx * h;
+            sum_zh += dz * h;
+            sum_xx += dx * dx;
+            sum_zz += dz * dz;
+        }
+    }
+    let mean_h = sum_h / (width * height) as f64;
+    let slope_x = if sum_xx > 0.0 { sum_xh / sum_xx } else { 0.0 };
+    let slope_z = if sum_zz > 0.0 { sum_zh / sum_zz } else { 0.0 };
+
+    (0..height)
+        .flat_map(|z| {
+            (0..width).map(move |x| {
+                mean_h + slope_x * (x as f64 - mean_x) + slope_z * (z as f64 - mean_z)
+            })
+        })
+        .collect()
+}
+
+/// Mean height in the square window of `radius` samples around each
+/// sample, clipped to the grid.
+fn smoothed(terrain: &TerrainData, radius: usize) -> Vec<f64> {
+    let (width, height) = (terrain.width, terrain.height);
+    // Summed-area table with a zero row and column in front
+    let mut sums = vec![vec![0.0; width + 1]; height + 1];
+    for z in 0..height {
+        for x in 0..width {
+            sums[z + 1][x + 1] = terrain.at(x, z) + sums[z][x + 1] + sums[z + 1][x] - sums[z][x];
+        }
+    }
+
+    let sums = &sums;
+    (0..height)
+        .flat_map(|z| {
+            let (z0, z1) = (z.saturating_sub(radius), (z + radius + 1).min(height));
+            (0..width).map(move |x| {
+                let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+                let sum = sums[z1][x1] - sums[z0][x1] - sums[z1][x0] + sums[z0][x0];
+                sum / ((z1 - z0) * (x1 - x0)) as f64
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::DVec2;
+
+    fn config(reference: ReferenceSurface) -> ReliefConfig {
+        ReliefConfig {
+            enabled: true,
+            reference,
+            ..ReliefConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_plane_removes_tilt() {
+        // A tilted plane with a bump in the middle
+        let mut points: Vec<Vec<f64>> = (0..5)
+            .map(|z| (0..5).map(|x| 2.0 * x as f64 - z as f64 + 10.0).collect())
+            .collect();
+        points[2][2] += 3.0;
+        let terrain = TerrainData::new(points, None);
+        let relative = relative_terrain(&terrain, &config(ReferenceSurface::Plane), None).unwrap();
+
+        // The bump stands out by the same amount everywhere on the tilt
+        let bump = relative.at(2, 2);
+        let flat = relative.at(0, 0);
+        assert!((bump - flat - 3.0).abs() < 1e-9);
+        assert!(relative
+            .heights()
+            .iter()
+            .all(|&h| h == bump || (h - flat).abs() < 1e-9));
+        // Residuals of a least-squares fit sum to zero
+        let sum: f64 = relative.heights().iter().sum();
+        assert!(sum.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_smoothed_keeps_local_relief() {
+        let mut points = vec![vec![5.0; 7]; 7];
+        points[3][3] = 14.0;
+        let terrain = TerrainData::new(points, None);
+        let config = ReliefConfig {
+            smoothing_radius: 1,
+            ..config(ReferenceSurface::Smoothed)
+        };
+        let relative = relative_terrain(&terrain, &config, None).unwrap();
+
+        // The peak is 8 above its 3x3 neighborhood mean of 6
+        assert_eq!(relative.at(3, 3), 8.0);
+        // Samples out of reach of the peak are at their reference
+        assert_eq!(relative.at(0, 0), 0.0);
+        assert_eq!(relative.at(2, 3), -1.0);
+    }
+
+    #[test]
+    fn test_grid_reference_by_world_position() {
+        let mut terrain = TerrainData::new(vec![vec![10.0, 20.0, 30.0]; 2], None);
+        terrain.origin = DVec2::new(100.0, 0.0);
+        // Half-resolution reference covering only the first two samples
+        let mut reference = TerrainData::new(vec![vec![4.0, 6.0], vec![4.0, 6.0]], None);
+        reference.origin = DVec2::new(100.0, 0.0);
+        reference.cell_size = 2.0;
+        let relative =
+            relative_terrain(&terrain, &config(ReferenceSurface::Grid), Some(&reference)).unwrap();
+
+        assert_eq!(relative.row(0), [6.0, 15.0, 24.0]);
+        assert_eq!(relative.origin, terrain.origin);
+        assert!(relative_terrain(&terrain, &config(ReferenceSurface::Grid), None).is_none());
+    }
+}
@@ -0,0 +1,87 @@
+//! Vectorized scans over height values.
+//!
+//! Finding the height range and normalizing heights into it run over every
+//! sample, often several times per mesh rebuild. The loops here work on
+//! fixed-width lanes of [`LANES`] values with branch-free updates, which
+//! the compiler turns into SIMD instructions on stable Rust, and finish the
+//! leftover tail one value at a time.
+
+/// Values processed side by side; 8 f64s fill one AVX-512 register or
+/// several SSE/NEON ones.
+const LANES: usize = 8;
+
+/// Lowest and highest of `values`, ignoring NaN, or `None` if there are no
+/// other values.
+pub fn min_max(values: &[f64]) -> Option<(f64, f64)> {
+    let mut min = [f64::INFINITY; LANES];
+    let mut max = [f64::NEG_INFINITY; LANES];
+    let chunks = values.chunks_exact(LANES);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        for lane in 0..LANES {
+            // Comparisons with NaN are false, so NaN never replaces a bound
+            let v = chunk[lane];
+            min[lane] = if v < min[lane] { v } else { min[lane] };
+            max[lane] = if v > max[lane] { v } else { max[lane] };
+        }
+    }
+    let (mut low, mut high) = (f64::INFINITY, f64::NEG_INFINITY);
+    for (&v_min, &v_max) in min.iter().zip(&max).chain(tail.iter().zip(tail)) {
+        low = if v_min < low { v_min } else { low };
+        high = if v_max > high { v_max } else { high };
+    }
+    (low <= high).then_some((low, high))
+}
+
+/// Position of each value within `min..=max`, 0 at `min` and 1 at `max`.
+/// A flat range maps everything to 0.
+pub fn normalize(values: &[f64], min: f64, max: f64) -> Vec<f32> {
+    let span = max - min;
+    let scale = if span.abs() < f64::EPSILON {
+        1.0
+    } else {
+        1.0 / span
+    };
+    let mut out = vec![0.0; values.len()];
+    let mut chunks = out.chunks_exact_mut(LANES);
+    let mut inputs = values.chunks_exact(LANES);
+    for (out, chunk) in (&mut chunks).zip(&mut inputs) {
+        for lane in 0..LANES {
+            out[lane] = ((chunk[lane] - min) * scale) as f32;
+        }
+    }
+    for (out, &v) in chunks.into_remainder().iter_mut().zip(inputs.remainder()) {
+        *out = ((v - min) * scale) as f32;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_max_over_lanes_and_tail() {
+        // 19 values: two full lanes and a tail of three
+        let mut values: Vec<f64> = (0..19).map(|i| (i * 7 % 19) as f64).collect();
+        values[4] = f64::NAN;
+        assert_eq!(min_max(&values), Some((0.0, 18.0)));
+        values[18] = -5.0;
+        assert_eq!(min_max(&values), Some((-5.0, 18.0)));
+
+        assert_eq!(min_max(&[]), None);
+        assert_eq!(min_max(&[f64::NAN; 9]), None);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let values: Vec<f64> = (0..11).map(|i| 10.0 + i as f64).collect();
+        let t = normalize(&values, 10.0, 20.0);
+        assert_eq!(t.len(), 11);
+        assert_eq!(t[0], 0.0);
+        assert_eq!(t[5], 0.5);
+        assert_eq!(t[10], 1.0);
+
+        assert_eq!(normalize(&[3.0; 3], 3.0, 3.0), vec![0.0; 3]);
+    }
+}
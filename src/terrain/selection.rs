@@ -0,0 +1,229 @@
+//! Regions of samples selected on the terrain.
+//!
+//! A selection is a closed world-space outline, dragged as a rectangle or
+//! drawn freehand as a lasso, and the samples inside it. Its height
+//! statistics are computed whenever it changes, and its cells can be
+//! copied as CSV for quick checks in a spreadsheet.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use glam::{DVec2, DVec3, Vec3Swizzles};
+
+use super::TerrainData;
+
+/// Samples inside a closed outline on the terrain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    /// World-space (x, z) outline; the last point repeats the first
+    pub outline: Vec<DVec2>,
+    /// Grid column and row of each selected sample, in row-major order
+    pub cells: Vec<(usize, usize)>,
+    /// World-space position of each selected sample, with unscaled height
+    pub positions: Vec<DVec3>,
+    /// Height statistics of the selected samples; `None` when there are none
+    pub stats: Option<SelectionStats>,
+}
+
+/// Height statistics of a selection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectionStats {
+    /// Lowest height
+    pub min: f64,
+    /// Highest height
+    pub max: f64,
+    /// Mean height
+    pub mean: f64,
+    /// Population standard deviation of the heights
+    pub std_dev: f64,
+    /// Footprint of the samples, a cell each, in squared world units
+    pub area: f64,
+}
+
+impl Selection {
+    /// Select the samples in the axis-aligned rectangle with world-space
+    /// (x, z) corners `a` and `b`, edges included.
+    pub fn rectangle(terrain: &TerrainData, a: DVec2, b: DVec2) -> Self {
+        let outline = vec![a, DVec2::new(b.x, a.y), b, DVec2::new(a.x, b.y), a];
+        let (min, max) = (a.min(b), a.max(b));
+        Self::select(terrain, outline, |p| {
+            p.cmpge(min).all() && p.cmple(max).all()
+        })
+    }
+
+    /// Select the samples inside the ring through `points`, which is closed
+    /// if it isn't already, by the even-odd rule.
+    pub fn polygon(terrain: &TerrainData, points: &[DVec2]) -> Self {
+        let mut outline = points.to_vec();
+        if outline.first() != outline.last() {
+            outline.extend(points.first());
+        }
+        let ring = outline.clone();
+        Self::select(terrain, outline, |p| contains(&ring, p))
+    }
+
+    /// Samples within the outline's bounds for which `inside` holds.
+    fn select(terrain: &TerrainData, outline: Vec<DVec2>, inside: impl Fn(DVec2) -> bool) -> Self {
+        let mut cells = Vec::new();
+        let mut positions = Vec::new();
+        if let Some((min, max)) = bounds(terrain, &outline) {
+            for z in min.1..=max.1 {
+                for x in min.0..=max.0 {
+                    let position = terrain.world_position(x, z);
+                    if inside(position.xz()) {
+                        cells.push((x, z));
+                        positions.push(position);
+                    }
+                }
+            }
+        }
+        let stats = SelectionStats::compute(&positions, terrain.cell_size);
+        Self {
+            outline,
+            cells,
+            positions,
+            stats,
+        }
+    }
+
+    /// Grid columns and rows spanned by the selected samples, or `None`
+    /// when there are none.
+    pub fn extent(&self) -> Option<(Range<usize>, Range<usize>)> {
+        let (&first, rest) = self.cells.split_first()?;
+        let (min, max) = rest.iter().fold((first, first), |(lo, hi), &(x, z)| {
+            ((lo.0.min(x), lo.1.min(z)), (hi.0.max(x), hi.1.max(z)))
+        });
+        Some((min.0..max.0 + 1, min.1..max.1 + 1))
+    }
+
+    /// Selected cells as CSV: a header, then the grid column and row,
+    /// world-space x and z, and height of each sample.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("column,row,x,z,height\n");
+        for (&(column, row), p) in self.cells.iter().zip(&self.positions) {
+            let _ = writeln!(out, "{column},{row},{},{},{}", p.x, p.z, p.y);
+        }
+        out
+    }
+}
+
+impl SelectionStats {
+    /// Statistics of the heights at `positions`, each covering a cell of
+    /// `cell_size`, or `None` without positions.
+    fn compute(positions: &[DVec3], cell_size: f64) -> Option<Self> {
+        if positions.is_empty() {
+            return None;
+        }
+        let count = positions.len() as f64;
+        let (mut min, mut max, mut sum) = (f64::MAX, f64::MIN, 0.0);
+        for p in positions {
+            min = min.min(p.y);
+            max = max.max(p.y);
+            sum += p.y;
+        }
+        let mean = sum / count;
+        let variance = positions.iter().map(|p| (p.y - mean).powi(2)).sum::<f64>() / count;
+        Some(Self {
+            min,
+            max,
+            mean,
+            std_dev: variance.sqrt(),
+            area: count * cell_size * cell_size,
+        })
+    }
+}
+
+/// Grid columns and rows of the samples within the bounding box of
+/// `outline`, as inclusive `(min, max)` corners, or `None` if the box
+/// misses the grid.
+fn bounds(terrain: &TerrainData, outline: &[DVec2]) -> Option<((usize, usize), (usize, usize))> {
+    if outline.is_empty() || terrain.width == 0 || terrain.height == 0 {
+        return None;
+    }
+    let (min, max) = outline
+        .iter()
+        .fold((DVec2::MAX, DVec2::MIN), |(lo, hi), &p| {
+            (lo.min(p), hi.max(p))
+        });
+    let min = ((min - terrain.origin) / terrain.cell_size).ceil();
+    let max = ((max - terrain.origin) / terrain.cell_size).floor();
+    let last = DVec2::new(terrain.width as f64 - 1.0, terrain.height as f64 - 1.0);
+    if max.x < 0.0 || max.y < 0.0 || min.x > last.x || min.y > last.y {
+        return None;
+    }
+    let (min, max) = (min.max(DVec2::ZERO), max.min(last));
+    min.cmple(max).all().then_some((
+        (min.x as usize, min.y as usize),
+        (max.x as usize, max.y as usize),
+    ))
+}
+
+/// Whether `point` lies inside the closed `ring`, by the even-odd rule.
+fn contains(ring: &[DVec2], point: DVec2) -> bool {
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if x > point.x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5x5 grid whose height is the column plus ten times the row.
+    fn ramp() -> TerrainData {
+        let rows = (0..5)
+            .map(|z| (0..5).map(|x| (x + 10 * z) as f64).collect())
+            .collect();
+        TerrainData::new(rows, None)
+    }
+
+    #[test]
+    fn test_rectangle_selects_inclusive_cells() {
+        let mut terrain = ramp();
+        terrain.cell_size = 2.0;
+
+        // Corners in either order, clipped to the grid
+        let selection = Selection::rectangle(&terrain, DVec2::new(2.0, 4.5), DVec2::new(-3.0, 2.0));
+        assert_eq!(selection.cells, [(0, 1), (1, 1), (0, 2), (1, 2)]);
+        assert_eq!(*selection.outline.first().unwrap(), selection.outline[4]);
+        assert_eq!(selection.extent(), Some((0..2, 1..3)));
+
+        let stats = selection.stats.unwrap();
+        assert_eq!((stats.min, stats.max, stats.mean), (10.0, 21.0, 15.5));
+        assert!((stats.std_dev - 25.25f64.sqrt()).abs() < 1e-12);
+        assert_eq!(stats.area, 16.0);
+
+        assert_eq!(
+            selection.to_csv().lines().take(3).collect::<Vec<_>>(),
+            ["column,row,x,z,height", "0,1,0,2,10", "1,1,2,2,11"]
+        );
+
+        let outside = Selection::rectangle(&terrain, DVec2::new(20.0, 0.0), DVec2::new(30.0, 8.0));
+        assert!(outside.cells.is_empty());
+        assert!(outside.stats.is_none());
+        assert_eq!(outside.extent(), None);
+    }
+
+    #[test]
+    fn test_lasso_selects_inside_ring() {
+        let terrain = ramp();
+        // A triangle over the lower left half, left open
+        let points = [
+            DVec2::new(-0.5, -0.5),
+            DVec2::new(4.6, -0.5),
+            DVec2::new(-0.5, 4.6),
+        ];
+        let selection = Selection::polygon(&terrain, &points);
+        assert_eq!(selection.outline.len(), 4);
+        assert_eq!(selection.cells.len(), 5 + 4 + 3 + 2 + 1);
+        assert!(selection.cells.iter().all(|&(x, z)| x + z <= 4));
+    }
+}
@@ -0,0 +1,441 @@
+//! Mesh simplification to a triangle budget.
+//!
+//! Grid meshes spend as many triangles on a flat field as on a cliff, far
+//! more than game engines and web viewers want. Simplification repeatedly
+//! collapses the edge whose removal changes the surface least, measured by
+//! quadric error metrics (Garland and Heckbert): each vertex accumulates
+//! the planes of the triangles around it, and the cost of moving it is its
+//! squared distance to them. Flat areas collapse first while ridges,
+//! valleys and breaks of slope hold their vertices.
+//!
+//! Collapses move a vertex onto a neighbor rather than to a new position,
+//! so every vertex kept is one of the input's, with its color, normal and
+//! texture coordinates. The outline is held in place by extra planes
+//! standing on the boundary edges, and collapses that would fold a triangle
+//! over or tear the surface are skipped.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use glam::{DVec3, Vec3};
+
+use super::mesh::{TerrainMesh, Vertex};
+
+/// Weight of the planes holding boundary edges in place, relative to the
+/// surface's own.
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+/// Symmetric 4x4 matrix summing squared distances to planes, stored as its
+/// upper triangle.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Squared distance to the plane through `point` with unit `normal`,
+    /// times `weight`.
+    fn plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+        let [a, b, c] = normal.to_array();
+        let d = -normal.dot(point);
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|q| q * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (q, o) in self.0.iter_mut().zip(other.0) {
+            *q += o;
+        }
+    }
+
+    /// Weighted sum of squared distances from `p` to the planes.
+    fn error(&self, p: DVec3) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        aa * x * x
+            + 2.0 * ab * x * y
+            + 2.0 * ac * x * z
+            + 2.0 * ad * x
+            + bb * y * y
+            + 2.0 * bc * y * z
+            + 2.0 * bd * y
+            + cc * z * z
+            + 2.0 * cd * z
+            + dd
+    }
+}
+
+/// Candidate collapse of vertex `from` onto `to`, valid while neither
+/// vertex has changed since it was queued.
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    stamps: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Cheapest first in a max-heap.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// A simplified mesh and where its vertices came from.
+pub struct Simplified {
+    /// Triangle mesh, without wireframe lines, chunks or levels of detail
+    pub mesh: TerrainMesh,
+    /// Index in the input mesh of each vertex, for carrying per-vertex data
+    /// such as texture coordinates over
+    pub sources: Vec<u32>,
+}
+
+/// Working state of a simplification.
+struct Simplifier {
+    positions: Vec<DVec3>,
+    quadrics: Vec<Quadric>,
+    /// Triangles around each vertex; may list removed ones
+    around: Vec<Vec<u32>>,
+    triangles: Vec<[u32; 3]>,
+    live: Vec<bool>,
+    live_count: usize,
+    /// Whether each vertex lies on the outline
+    boundary: Vec<bool>,
+    removed: Vec<bool>,
+    stamps: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+    fn new(mesh: &TerrainMesh) -> Self {
+        let positions: Vec<DVec3> = mesh
+            .vertices
+            .iter()
+            .map(|v| Vec3::from(v.position).as_dvec3())
+            .collect();
+        let triangles: Vec<[u32; 3]> = mesh
+            .triangle_indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        let n = positions.len();
+        let mut around = vec![Vec::new(); n];
+        let mut quadrics = vec![Quadric::default(); n];
+        for (i, &[a, b, c]) in triangles.iter().enumerate() {
+            let [pa, pb, pc] = [a, b, c].map(|v| positions[v as usize]);
+            let cross = (pb - pa).cross(pc - pa);
+            // Weighted by area, so small triangles don't outvote large ones
+            let plane = Quadric::plane(cross.normalize_or_zero(), pa, cross.length() / 2.0);
+            for v in [a, b, c] {
+                around[v as usize].push(i as u32);
+                quadrics[v as usize].add(&plane);
+            }
+        }
+
+        // Edges of one triangle only are on the outline; walls standing on
+        // them keep it from moving
+        let mut edges: Vec<(u32, u32, u32)> = triangles
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &[a, b, c])| {
+                [(a, b), (b, c), (c, a)].map(|(u, v)| (u.min(v), u.max(v), i as u32))
+            })
+            .collect();
+        edges.sort_unstable();
+        let mut boundary = vec![false; n];
+        for (i, edge) in edges.iter().enumerate() {
+            let shared = (i > 0 && edges[i - 1].0 == edge.0 && edges[i - 1].1 == edge.1)
+                || edges
+                    .get(i + 1)
+                    .is_some_and(|next| next.0 == edge.0 && next.1 == edge.1);
+            if shared {
+                continue;
+            }
+            let (u, v, t) = *edge;
+            let (pu, pv) = (positions[u as usize], positions[v as usize]);
+            let [a, b, c] = triangles[t as usize].map(|v| positions[v as usize]);
+            let face = (b - a).cross(c - a).normalize_or_zero();
+            let wall = (pv - pu).cross(face).normalize_or_zero();
+            let plane = Quadric::plane(wall, pu, BOUNDARY_WEIGHT * pu.distance_squared(pv));
+            for w in [u, v] {
+                quadrics[w as usize].add(&plane);
+                boundary[w as usize] = true;
+            }
+        }
+
+        let mut simplifier = Self {
+            positions,
+            quadrics,
+            around,
+            live: vec![true; triangles.len()],
+            live_count: triangles.len(),
+            triangles,
+            boundary,
+            removed: vec![false; n],
+            stamps: vec![0; n],
+            heap: BinaryHeap::new(),
+        };
+        edges.dedup_by_key(|&mut (u, v, _)| (u, v));
+        for (u, v, _) in edges {
+            simplifier.queue(u, v);
+        }
+        simplifier
+    }
+
+    /// Live triangles around `v`.
+    fn triangles_around(&self, v: u32) -> impl Iterator<Item = u32> + '_ {
+        self.around[v as usize]
+            .iter()
+            .copied()
+            .filter(|&t| self.live[t as usize])
+    }
+
+    /// Vertices sharing a live triangle with `v`.
+    fn neighbors(&self, v: u32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = self
+            .triangles_around(v)
+            .flat_map(|t| self.triangles[t as usize])
+            .filter(|&w| w != v)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    /// Queue the cheaper direction of collapsing edge `u`-`v`.
+    fn queue(&mut self, u: u32, v: u32) {
+        let mut quadric = self.quadrics[u as usize];
+        quadric.add(&self.quadrics[v as usize]);
+        let (from, to) = match (self.boundary[u as usize], self.boundary[v as usize]) {
+            // Only along the outline may a boundary vertex move
+            (true, false) => (v, u),
+            (false, true) => (u, v),
+            _ if quadric.error(self.positions[u as usize])
+                < quadric.error(self.positions[v as usize]) =>
+            {
+                (v, u)
+            }
+            _ => (u, v),
+        };
+        self.heap.push(Collapse {
+            cost: quadric.error(self.positions[to as usize]),
+            from,
+            to,
+            stamps: (self.stamps[from as usize], self.stamps[to as usize]),
+        });
+    }
+
+    /// Whether moving `from` onto `to` keeps the surface a manifold without
+    /// folded triangles or a moved outline.
+    fn can_collapse(&self, from: u32, to: u32) -> bool {
+        let shared = self
+            .triangles_around(from)
+            .filter(|&t| self.triangles[t as usize].contains(&to))
+            .count();
+        // Interior vertices move across interior edges, and outline ones
+        // along the outline
+        if shared == 0 || (self.boundary[from as usize] && shared != 1) {
+            return false;
+        }
+        // Two vertices sharing more neighbors than triangles would pinch
+        // the surface
+        let theirs = self.neighbors(to);
+        let common = self
+            .neighbors(from)
+            .iter()
+            .filter(|w| theirs.binary_search(w).is_ok())
+            .count();
+        if common != shared {
+            return false;
+        }
+
+        let target = self.positions[to as usize];
+        self.triangles_around(from)
+            .map(|t| self.triangles[t as usize])
+            .filter(|triangle| !triangle.contains(&to))
+            .all(|triangle| {
+                let [a, b, c] = triangle.map(|v| self.positions[v as usize]);
+                let [na, nb, nc] = triangle.map(|v| match v == from {
+                    true => target,
+                    false => self.positions[v as usize],
+                });
+                let before = (b - a).cross(c - a);
+                let after = (nb - na).cross(nc - na);
+                after.length_squared() > 0.0 && before.dot(after) > 0.0
+            })
+    }
+
+    /// Move `from` onto `to`, removing the triangles between them.
+    fn collapse(&mut self, from: u32, to: u32) {
+        let around = std::mem::take(&mut self.around[from as usize]);
+        for &t in &around {
+            if !self.live[t as usize] {
+                continue;
+            }
+            let triangle = &mut self.triangles[t as usize];
+            if triangle.contains(&to) {
+                self.live[t as usize] = false;
+                self.live_count -= 1;
+            } else {
+                for v in triangle.iter_mut().filter(|v| **v == from) {
+                    *v = to;
+                }
+                self.around[to as usize].push(t);
+            }
+        }
+        let moved = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&moved);
+        self.removed[from as usize] = true;
+        self.stamps[to as usize] += 1;
+
+        let live: Vec<u32> = self.triangles_around(to).collect();
+        self.around[to as usize] = live;
+        for w in self.neighbors(to) {
+            self.queue(to, w);
+        }
+    }
+
+    /// Collapse the cheapest edges until at most `max_triangles` remain or
+    /// none can go.
+    fn run(&mut self, max_triangles: usize) {
+        while self.live_count > max_triangles {
+            let Some(candidate) = self.heap.pop() else {
+                break;
+            };
+            let Collapse { from, to, .. } = candidate;
+            let stale = self.removed[from as usize]
+                || self.removed[to as usize]
+                || candidate.stamps != (self.stamps[from as usize], self.stamps[to as usize]);
+            if !stale && self.can_collapse(from, to) {
+                self.collapse(from, to);
+            }
+        }
+    }
+}
+
+/// Simplify `mesh` to at most `max_triangles` triangles, or as close as it
+/// gets without tearing it. Vertices are kept in their input order.
+pub fn simplify(mesh: &TerrainMesh, max_triangles: usize) -> Simplified {
+    let mut simplifier = Simplifier::new(mesh);
+    simplifier.run(max_triangles);
+
+    let live = simplifier
+        .triangles
+        .iter()
+        .zip(&simplifier.live)
+        .filter_map(|(triangle, &live)| live.then_some(triangle));
+    let mut used = vec![false; mesh.vertices.len()];
+    for &v in live.clone().flatten() {
+        used[v as usize] = true;
+    }
+    // Compact the vertices left, in order
+    let mut index = vec![0; mesh.vertices.len()];
+    let mut sources = Vec::new();
+    let mut vertices: Vec<Vertex> = Vec::new();
+    for v in (0..mesh.vertices.len()).filter(|&v| used[v]) {
+        index[v] = vertices.len() as u32;
+        sources.push(v as u32);
+        vertices.push(mesh.vertices[v]);
+    }
+    let triangle_indices = live.flatten().map(|&v| index[v as usize]).collect();
+    let bounds = vertices.iter().map(|v| Vec3::from(v.position)).fold(
+        None,
+        |bounds: Option<(Vec3, Vec3)>, p| match bounds {
+            Some((min, max)) => Some((min.min(p), max.max(p))),
+            None => Some((p, p)),
+        },
+    );
+
+    Simplified {
+        mesh: TerrainMesh {
+            vertices,
+            triangle_indices,
+            bounds,
+            ..TerrainMesh::default()
+        },
+        sources,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::{MeshOptions, TerrainData};
+
+    /// Mesh of a `size` x `size` grid with heights from `height(x, z)`.
+    fn grid(size: usize, height: impl Fn(usize, usize) -> f64) -> TerrainMesh {
+        let rows = (0..size)
+            .map(|z| (0..size).map(|x| height(x, z)).collect())
+            .collect();
+        TerrainMesh::build(&TerrainData::new(rows, None), &MeshOptions::default())
+    }
+
+    #[test]
+    fn test_flat_grid_collapses_to_its_corners() {
+        let mesh = grid(17, |_, _| 0.0);
+        let simplified = simplify(&mesh, 2);
+        assert_eq!(simplified.mesh.triangle_indices.len(), 6);
+        assert_eq!(simplified.mesh.vertices.len(), 4);
+        // The outline stays where it was
+        assert_eq!(simplified.mesh.bounds, mesh.bounds);
+    }
+
+    #[test]
+    fn test_ridge_is_kept() {
+        // A tent with its ridge along x = 8
+        let mesh = grid(17, |x, _| 8.0 - (x as f64 - 8.0).abs());
+        let simplified = simplify(&mesh, 8);
+        assert!(simplified.mesh.triangle_indices.len() / 3 <= 8);
+        assert_eq!(simplified.mesh.bounds, mesh.bounds);
+        // Every vertex left lies on the tent, on its ridge or outline
+        for v in &simplified.mesh.vertices {
+            let [x, y, _] = v.position;
+            assert!((y - (8.0 - x.abs())).abs() < 1e-4, "{:?}", v.position);
+        }
+    }
+
+    #[test]
+    fn test_sources_point_at_input_vertices() {
+        let mesh = grid(9, |x, z| ((x * 7 + z * 3) % 5) as f64);
+        let simplified = simplify(&mesh, 40);
+        assert!(simplified.mesh.triangle_indices.len() / 3 <= 40);
+        for (vertex, &source) in simplified.mesh.vertices.iter().zip(&simplified.sources) {
+            assert_eq!(vertex.position, mesh.vertices[source as usize].position);
+        }
+        assert!(simplified.sources.is_sorted());
+    }
+
+    #[test]
+    fn test_budget_above_count_keeps_mesh() {
+        let mesh = grid(5, |x, z| (x * z) as f64);
+        let simplified = simplify(&mesh, 1_000);
+        assert_eq!(simplified.mesh.triangle_indices, mesh.triangle_indices);
+        assert_eq!(simplified.mesh.vertices.len(), mesh.vertices.len());
+    }
+}
@@ -0,0 +1,171 @@
+//! Clear-sky solar irradiation over a day.
+//!
+//! Sums the sunlight each grid vertex receives from sunrise to sunset under
+//! a cloudless sky, for quick solar siting on roofs and slopes. Each step of
+//! the day adds the beam falling on the surface, by its slope and aspect and
+//! only where the sun isn't hidden by the terrain, plus the diffuse light of
+//! the visible sky dome.
+//!
+//! The atmosphere follows Meinel's air-mass attenuation, and the diffuse
+//! light is a fixed fraction of the beam spread evenly over the sky, so
+//! values are indicative rather than a substitute for measured weather
+//! data. Heights and the cell size are taken as meters.
+
+use glam::DVec3;
+
+use super::colors::{height_to_color, ColorScheme};
+use super::scan;
+use super::sun::{sun_direction, sunlit};
+use super::TerrainData;
+
+/// Sunlight above the atmosphere, in W/m².
+pub const SOLAR_CONSTANT: f64 = 1361.0;
+
+/// Diffuse sky light on a horizontal surface as a fraction of the direct
+/// beam's intensity.
+const DIFFUSE_FRACTION: f64 = 0.1;
+
+/// Direct beam intensity at ground level, in W/m² normal to the beam, for
+/// a sun `elevation` radians above the horizon.
+pub fn beam_irradiance(elevation: f64) -> f64 {
+    if elevation <= 0.0 {
+        return 0.0;
+    }
+    let air_mass = 1.0 / elevation.sin();
+    SOLAR_CONSTANT * 0.7_f64.powf(air_mass.powf(0.678))
+}
+
+/// Clear-sky energy reaching each grid vertex over the day, in kWh/m²,
+/// sampling the sun every `step_hours`.
+///
+/// Returned in row-major vertex order like the heights. `progress` is told
+/// the fraction of the day done before each step, and stops the
+/// computation, returning `None`, by returning false.
+pub fn daily_irradiation(
+    terrain: &TerrainData,
+    latitude: f64,
+    day_of_year: f64,
+    step_hours: f64,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Option<Vec<f64>> {
+    let mut energy = vec![0.0; terrain.width * terrain.height];
+    if step_hours <= 0.0 || terrain.width == 0 || terrain.height == 0 {
+        return Some(energy);
+    }
+
+    let normals = surface_normals(terrain);
+    let steps = (24.0 / step_hours).round() as usize;
+    for i in 0..steps {
+        if !progress(i as f64 / steps as f64) {
+            return None;
+        }
+        // Sample the middle of each step
+        let hour = (i as f64 + 0.5) * step_hours;
+        let direction = sun_direction(latitude, day_of_year, hour);
+        if direction.y <= 0.0 {
+            continue;
+        }
+        let beam = beam_irradiance(direction.y.asin());
+        let diffuse = beam * DIFFUSE_FRACTION;
+        let lit = sunlit(terrain, direction);
+        for ((value, lit), normal) in energy.iter_mut().zip(lit).zip(&normals) {
+            // Tilted surfaces see part of the sky dome
+            let mut watts = diffuse * (1.0 + normal.y) / 2.0;
+            if lit {
+                watts += beam * normal.dot(direction).max(0.0);
+            }
+            *value += watts * step_hours / 1000.0;
+        }
+    }
+    Some(energy)
+}
+
+/// Lowest and highest value of a grid, the ends of its color legend.
+pub fn range(values: &[f64]) -> (f64, f64) {
+    scan::min_max(values).unwrap_or((0.0, 0.0))
+}
+
+/// Heatmap color (sRGB) of each value by its position within `range`.
+pub fn colors(values: &[f64], (min, max): (f64, f64)) -> Vec<[f32; 3]> {
+    scan::normalize(values, min, max)
+        .into_iter()
+        .map(|t| height_to_color(t, ColorScheme::Heatmap))
+        .collect()
+}
+
+/// Upward unit normal at each grid vertex, from central differences of
+/// height (one-sided at the edges) over the grid spacing.
+fn surface_normals(terrain: &TerrainData) -> Vec<DVec3> {
+    let (width, height) = (terrain.width, terrain.height);
+    let h = |x: usize, z: usize| terrain.at(x, z);
+    (0..height)
+        .flat_map(|z| {
+            (0..width).map(move |x| {
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(height - 1));
+                let dx = if x1 > x0 {
+                    (h(x1, z) - h(x0, z)) / ((x1 - x0) as f64 * terrain.cell_size)
+                } else {
+                    0.0
+                };
+                let dz = if z1 > z0 {
+                    (h(x, z1) - h(x, z0)) / ((z1 - z0) as f64 * terrain.cell_size)
+                } else {
+                    0.0
+                };
+                DVec3::new(-dx, 1.0, -dz).normalize()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beam_weakens_toward_horizon() {
+        let overhead = beam_irradiance(90_f64.to_radians());
+        assert!((overhead - SOLAR_CONSTANT * 0.7).abs() < 1e-9);
+        assert!(beam_irradiance(10_f64.to_radians()) < overhead / 2.0);
+        assert_eq!(beam_irradiance(-0.1), 0.0);
+    }
+
+    #[test]
+    fn test_flat_ground_at_equinox() {
+        let terrain = TerrainData::new(vec![vec![0.0; 3]; 3], None);
+        let energy = daily_irradiation(&terrain, 0.0, 81.0, 0.25, |_| true).unwrap();
+
+        // Clear equatorial days bring roughly 7 kWh/m²
+        assert!((6.0..9.0).contains(&energy[4]), "{}", energy[4]);
+        assert!(energy.iter().all(|&e| e == energy[4]));
+        // Winter days in the north are much darker
+        let winter = daily_irradiation(&terrain, 50.0, 355.0, 0.25, |_| true).unwrap();
+        assert!(winter[4] < energy[4] / 3.0);
+    }
+
+    #[test]
+    fn test_south_slopes_collect_more_in_the_north() {
+        // A ridge along x: rows rise to the middle, so the south half faces
+        // south (toward lower rows) and the north half north
+        let points = (0..7)
+            .map(|z: i32| vec![3.0 - (z - 3).abs() as f64; 5])
+            .collect();
+        let terrain = TerrainData::new(points, None);
+        let energy = daily_irradiation(&terrain, 50.0, 81.0, 0.25, |_| true).unwrap();
+
+        let flat = TerrainData::new(vec![vec![0.0; 5]; 7], None);
+        let flat = daily_irradiation(&flat, 50.0, 81.0, 0.25, |_| true).unwrap();
+        assert!(energy[5 + 2] > flat[5 + 2]);
+        assert!(energy[5 * 5 + 2] < flat[5 * 5 + 2]);
+
+        // The sunniest slope is hot, the darkest cold
+        let (min, max) = range(&energy);
+        let colors = colors(&energy, (min, max));
+        assert_eq!(colors[5 + 2], height_to_color(1.0, ColorScheme::Heatmap));
+        assert_eq!(
+            colors[5 * 5 + 2],
+            height_to_color(0.0, ColorScheme::Heatmap)
+        );
+    }
+}
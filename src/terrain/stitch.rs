@@ -0,0 +1,138 @@
+//! Stitching the seams between adjacent tiles.
+//!
+//! Mosaicked tiles usually repeat their neighbors' edge samples, each tile
+//! carrying its own copy of the shared border. When the copies disagree,
+//! the two meshes meet at different heights and the background shows
+//! through the crack. Stitching finds border samples that land on the same
+//! ground position in several tiles and sets them all to their average, as
+//! long as they differ by at most a tolerance: larger differences are real,
+//! such as tiles from different surveys, and are left alone.
+//!
+//! Tiles that only abut, with no samples in common, have no shared border
+//! to stitch.
+
+use std::collections::HashMap;
+
+use super::TerrainData;
+
+/// Distance, in cells of the finest tile, within which border samples of
+/// different tiles count as the same position.
+const SNAP: f64 = 0.01;
+
+/// Seam stitching configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StitchConfig {
+    /// Average the shared borders of the loaded terrains
+    pub enabled: bool,
+    /// Largest height difference between copies of a border sample that is
+    /// averaged away, in the terrain's units
+    pub tolerance: f32,
+}
+
+impl Default for StitchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tolerance: 1.0,
+        }
+    }
+}
+
+/// Indices of the samples on the edges of a `width` x `height` grid.
+fn border(width: usize, height: usize) -> impl Iterator<Item = usize> {
+    (0..width * height).filter(move |i| {
+        let (x, z) = (i % width, i / width);
+        x == 0 || z == 0 || x + 1 == width || z + 1 == height
+    })
+}
+
+/// Set the border samples `tiles` share to their average where the copies
+/// differ by at most `tolerance`. Returns the number of samples changed.
+pub fn stitch(tiles: &mut [TerrainData], tolerance: f64) -> usize {
+    let Some(snap) = tiles
+        .iter()
+        .map(|tile| tile.cell_size)
+        .filter(|&size| size > 0.0)
+        .min_by(f64::total_cmp)
+        .map(|size| size * SNAP)
+    else {
+        return 0;
+    };
+
+    // Border samples of every tile, by snapped ground position
+    let mut seams: HashMap<(i64, i64), Vec<(usize, usize)>> = HashMap::new();
+    for (t, tile) in tiles.iter().enumerate() {
+        for i in border(tile.width, tile.height) {
+            let p = tile.world_position(i % tile.width, i / tile.width);
+            let key = ((p.x / snap).round() as i64, (p.z / snap).round() as i64);
+            seams.entry(key).or_default().push((t, i));
+        }
+    }
+
+    let mut changed = 0;
+    for samples in seams.values().filter(|samples| samples.len() > 1) {
+        let heights: Vec<f64> = samples
+            .iter()
+            .map(|&(t, i)| tiles[t].heights()[i])
+            .collect();
+        let (min, max) = heights
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &h| {
+                (min.min(h), max.max(h))
+            });
+        // Missing samples and real differences stay as they are
+        if !(min.is_finite() && max.is_finite()) || max - min > tolerance || max == min {
+            continue;
+        }
+        let average = heights.iter().sum::<f64>() / heights.len() as f64;
+        for (&(t, i), &h) in samples.iter().zip(&heights) {
+            if h != average {
+                tiles[t].heights_mut()[i] = average;
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::DVec2;
+
+    /// A 3x3 tile of `height` with sample `[0][0]` at (`x`, 0), 1 unit cells.
+    fn tile(x: f64, height: f64) -> TerrainData {
+        let mut tile = TerrainData::new(vec![vec![height; 3]; 3], None);
+        tile.origin = DVec2::new(x, 0.0);
+        tile
+    }
+
+    #[test]
+    fn test_shared_border_is_averaged() {
+        // The east column of the first tile is the west column of the second
+        let mut tiles = [tile(0.0, 10.0), tile(2.0, 10.5)];
+        assert_eq!(stitch(&mut tiles, 1.0), 6);
+        for z in 0..3 {
+            assert_eq!(tiles[0].at(2, z), 10.25);
+            assert_eq!(tiles[1].at(0, z), 10.25);
+        }
+        // Only the seam moves
+        assert_eq!(tiles[0].at(1, 1), 10.0);
+        assert_eq!(tiles[1].at(2, 1), 10.5);
+        assert_eq!(tiles[0].height_bounds(), (10.0, 10.25));
+    }
+
+    #[test]
+    fn test_differences_beyond_tolerance_are_kept() {
+        let mut tiles = [tile(0.0, 10.0), tile(2.0, 15.0)];
+        assert_eq!(stitch(&mut tiles, 1.0), 0);
+        assert_eq!(tiles[0].at(2, 0), 10.0);
+        assert_eq!(tiles[1].at(0, 0), 15.0);
+    }
+
+    #[test]
+    fn test_abutting_tiles_share_nothing() {
+        let mut tiles = [tile(0.0, 10.0), tile(3.0, 10.5)];
+        assert_eq!(stitch(&mut tiles, 1.0), 0);
+    }
+}
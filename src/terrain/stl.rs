@@ -0,0 +1,84 @@
+//! Binary STL mesh export, for 3D printing and CAD.
+//!
+//! Writes the solid surface of a [`TerrainMesh`] as bare triangles, each
+//! with its facet normal. STL has no colors or textures, so only the
+//! geometry is kept. Slicers and CAD tools treat +Z as up, so the mesh is
+//! turned from lrle's +Y up, keeping the triangles' winding.
+
+use glam::Vec3;
+
+use super::TerrainMesh;
+
+/// Header text, padded to the 80 bytes STL reserves for it. It mustn't
+/// start with "solid", which readers take for ASCII STL.
+const HEADER: &[u8] = b"lrle terrain";
+
+/// Bytes of the header before the triangle count.
+const HEADER_SIZE: usize = 80;
+
+/// Bytes per triangle: normal, three corners, and an attribute count.
+const TRIANGLE_SIZE: usize = 50;
+
+/// Serialize the mesh's triangles as binary STL.
+pub fn to_stl(mesh: &TerrainMesh) -> Vec<u8> {
+    let triangles = mesh.triangle_indices.len() / 3;
+    let mut out = Vec::with_capacity(HEADER_SIZE + 4 + triangles * TRIANGLE_SIZE);
+    out.extend_from_slice(HEADER);
+    out.resize(HEADER_SIZE, 0);
+    out.extend_from_slice(&(triangles as u32).to_le_bytes());
+
+    // (x, y, z) with y up is (x, -z, y) with z up
+    let corner = |i: u32| {
+        let [x, y, z] = mesh.vertices[i as usize].position;
+        Vec3::new(x, -z, y)
+    };
+    for triangle in mesh.triangle_indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| corner(triangle[k]));
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        for v in [normal, a, b, c] {
+            for component in v.to_array() {
+                out.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+        // Attribute byte count, unused
+        out.extend_from_slice(&0u16.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::TerrainData;
+
+    /// Little-endian f32 at byte `offset`.
+    fn f32_at(data: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_stl_layout() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]; 3], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let stl = to_stl(&mesh);
+
+        let triangles = mesh.triangle_indices.len() / 3;
+        assert_eq!(stl.len(), HEADER_SIZE + 4 + triangles * TRIANGLE_SIZE);
+        assert!(!stl.starts_with(b"solid"));
+        let count = u32::from_le_bytes(stl[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+        assert_eq!(count as usize, triangles);
+
+        // First corner of the first triangle, turned to z up
+        let first = HEADER_SIZE + 4;
+        let [x, y, z] = mesh.vertices[mesh.triangle_indices[0] as usize].position;
+        let corner: Vec<f32> = (0..3).map(|k| f32_at(&stl, first + 12 + k * 4)).collect();
+        assert_eq!(corner, [x, -z, y]);
+        // The surface faces up, and every facet's normal is a unit vector
+        for t in 0..triangles {
+            let offset = first + t * TRIANGLE_SIZE;
+            let normal = Vec3::from_array([0, 1, 2].map(|k| f32_at(&stl, offset + k * 4)));
+            assert!((normal.length() - 1.0).abs() < 1e-5);
+            assert!(normal.z > 0.0);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Sun position and shadow studies.
+//!
+//! Computes the sun direction for a latitude, day of year and local solar
+//! time, and accumulates the hours each grid vertex spends in terrain
+//! shadow over a day. World axes follow the globe convention: +X is east,
+//! +Y is up and +Z (increasing row index) is north.
+
+use std::f64::consts::TAU;
+use std::fmt::Write;
+
+use glam::DVec3;
+
+use super::TerrainData;
+
+/// Solar declination in radians for a day of the year (1 = January 1st),
+/// using Cooper's approximation.
+pub fn declination(day_of_year: f64) -> f64 {
+    23.44_f64.to_radians() * (TAU * (284.0 + day_of_year) / 365.0).sin()
+}
+
+/// Unit vector pointing toward the sun.
+///
+/// `latitude` is in degrees (north positive) and `solar_hour` is local
+/// solar time, with the sun highest at 12.0. The y component is the sine
+/// of the sun's elevation, so it is negative at night.
+pub fn sun_direction(latitude: f64, day_of_year: f64, solar_hour: f64) -> DVec3 {
+    let lat = latitude.to_radians();
+    let dec = declination(day_of_year);
+    let hour_angle = (solar_hour - 12.0) / 24.0 * TAU;
+
+    let east = -dec.cos() * hour_angle.sin();
+    let north = dec.sin() * lat.cos() - dec.cos() * lat.sin() * hour_angle.cos();
+    let up = lat.sin() * dec.sin() + lat.cos() * dec.cos() * hour_angle.cos();
+    DVec3::new(east, up, north)
+}
+
+/// Whether the sample at grid position (`x`, `z`) sees the sun in
+/// `direction`, marching the height field one cell at a time until the ray
+/// rises above the highest point.
+fn is_lit(terrain: &TerrainData, x: usize, z: usize, max_h: f64, direction: DVec3) -> bool {
+    let horizontal = direction.x.hypot(direction.z);
+    if horizontal < f64::EPSILON {
+        return true;
+    }
+    // Per step: one cell horizontally, and the matching rise in height
+    let step_x = direction.x / horizontal;
+    let step_z = direction.z / horizontal;
+    let rise = direction.y / horizontal * terrain.cell_size;
+
+    let (mut px, mut pz) = (x as f64, z as f64);
+    let mut height = terrain.at(x, z);
+    loop {
+        px += step_x;
+        pz += step_z;
+        height += rise;
+        if height > max_h {
+            return true;
+        }
+        let (ix, iz) = (px.round(), pz.round());
+        if ix < 0.0 || iz < 0.0 || ix >= terrain.width as f64 || iz >= terrain.height as f64 {
+            return true;
+        }
+        if terrain.at(ix as usize, iz as usize) > height {
+            return false;
+        }
+    }
+}
+
+/// Whether each grid vertex sees a light in `direction`, in row-major
+/// vertex order like the heights. A light below the horizon lights nothing.
+pub fn sunlit(terrain: &TerrainData, direction: DVec3) -> Vec<bool> {
+    if direction.y <= 0.0 {
+        return vec![false; terrain.width * terrain.height];
+    }
+    let (_, max_h) = terrain.height_bounds();
+    (0..terrain.height)
+        .flat_map(|z| (0..terrain.width).map(move |x| (x, z)))
+        .map(|(x, z)| is_lit(terrain, x, z, max_h, direction))
+        .collect()
+}
+
+/// Hours each grid vertex spends in terrain shadow while the sun is up,
+/// sampling the day every `step_hours`.
+///
+/// Returned in row-major vertex order like the heights. Heights and the
+/// cell size are taken as the same unit, so the result is physical only
+/// for unexaggerated data.
+///
+/// `progress` is told the fraction of the day done before each step, and
+/// stops the computation, returning `None`, by returning false.
+pub fn shadow_hours(
+    terrain: &TerrainData,
+    latitude: f64,
+    day_of_year: f64,
+    step_hours: f64,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Option<Vec<f64>> {
+    let mut hours = vec![0.0; terrain.width * terrain.height];
+    if step_hours <= 0.0 || terrain.width == 0 || terrain.height == 0 {
+        return Some(hours);
+    }
+
+    let steps = (24.0 / step_hours).round() as usize;
+    for i in 0..steps {
+        if !progress(i as f64 / steps as f64) {
+            return None;
+        }
+        // Sample the middle of each step
+        let hour = (i as f64 + 0.5) * step_hours;
+        let direction = sun_direction(latitude, day_of_year, hour);
+        if direction.y <= 0.0 {
+            continue;
+        }
+        let lit = sunlit(terrain, direction);
+        for (value, lit) in hours.iter_mut().zip(lit) {
+            if !lit {
+                *value += step_hours;
+            }
+        }
+    }
+    Some(hours)
+}
+
+/// Day of the year (1-365) for a month (1-12) and day of month, ignoring
+/// leap years.
+pub fn day_of_year(month: u32, day: u32) -> u32 {
+    const MONTH_START: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    MONTH_START[(month.clamp(1, 12) - 1) as usize] + day.max(1)
+}
+
+/// Serialize values of each grid vertex, in row-major order, as an ESRI
+/// ASCII raster, readable by most GIS tools. The first grid row is written
+/// last, since the format lists rows from north to south.
+pub fn to_ascii_grid(terrain: &TerrainData, values: &[f64]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "ncols {}", terrain.width);
+    let _ = writeln!(out, "nrows {}", terrain.height);
+    // Cell-centered on the samples
+    let half = terrain.cell_size / 2.0;
+    let _ = writeln!(out, "xllcorner {}", terrain.origin.x - half);
+    let _ = writeln!(out, "yllcorner {}", terrain.origin.y - half);
+    let _ = writeln!(out, "cellsize {}", terrain.cell_size);
+    let _ = writeln!(out, "NODATA_value -9999");
+    for row in values.chunks_exact(terrain.width.max(1)).rev() {
+        let line: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        out.push_str(&line.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    /// Length of daylight in hours.
+    fn day_length(latitude: f64, day_of_year: f64) -> f64 {
+        let cos_h = -latitude.to_radians().tan() * declination(day_of_year).tan();
+        2.0 * cos_h.clamp(-1.0, 1.0).acos() / PI * 12.0
+    }
+
+    #[test]
+    fn test_noon_sun_faces_equator() {
+        // Northern mid-latitude at the equinox: sun due south, 45° up
+        let dir = sun_direction(45.0, 81.0, 12.0);
+        assert!((dir.length() - 1.0).abs() < 1e-12);
+        assert!(dir.x.abs() < 1e-9);
+        assert!(dir.z < 0.0);
+        assert!((dir.y.asin().to_degrees() - 45.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_sun_rises_in_the_east() {
+        let morning = sun_direction(0.0, 81.0, 8.0);
+        let evening = sun_direction(0.0, 81.0, 16.0);
+        assert!(morning.x > 0.0 && morning.y > 0.0);
+        assert!(evening.x < 0.0 && evening.y > 0.0);
+        assert!(sun_direction(0.0, 81.0, 0.0).y < 0.0);
+    }
+
+    #[test]
+    fn test_summer_days_are_longer() {
+        let summer = day_of_year(6, 21) as f64;
+        let winter = day_of_year(12, 21) as f64;
+        assert!(day_length(50.0, summer) > 16.0);
+        assert!(day_length(50.0, winter) < 8.5);
+        assert_eq!(day_of_year(1, 1), 1);
+        assert_eq!(day_of_year(12, 31), 365);
+    }
+
+    #[test]
+    fn test_wall_casts_shadow() {
+        // A tall wall along row 5; at the equinox at 45°N the sun is south
+        // (toward lower rows), so the rows north of the wall are shaded
+        let mut points = vec![vec![0.0; 6]; 10];
+        points[5] = vec![100.0; 6];
+        let terrain = TerrainData::new(points, None);
+        let hours = shadow_hours(&terrain, 45.0, 81.0, 0.5, |_| true).unwrap();
+
+        let at = |x: usize, z: usize| hours[z * 6 + x];
+        assert!(at(3, 7) > at(3, 2));
+        assert!(at(3, 7) > 4.0);
+        assert_eq!(at(3, 5), 0.0);
+    }
+
+    #[test]
+    fn test_flat_terrain_is_never_shaded() {
+        let terrain = TerrainData::new(vec![vec![1.0; 4]; 4], None);
+        let hours = shadow_hours(&terrain, 30.0, 172.0, 1.0, |_| true).unwrap();
+        assert!(hours.iter().all(|&h| h == 0.0));
+    }
+
+    #[test]
+    fn test_ascii_grid() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0], vec![2.0, 3.0]], None);
+        let grid = to_ascii_grid(&terrain, terrain.heights());
+
+        assert!(grid.starts_with("ncols 2\nnrows 2\n"));
+        assert!(grid.contains("cellsize 1\n"));
+        // North (last) row first
+        assert!(grid.ends_with("2 3\n0 1\n"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Web map tiles of hillshade and color relief.
+//!
+//! [`render_tile`] draws one 256 x 256 tile of the standard XYZ scheme used
+//! by web maps: zoom level `z` splits the Web Mercator square into
+//! 2^z x 2^z tiles, `x` counting east from the antimeridian and `y` south
+//! from the top. The terrain's world coordinates are taken as Web Mercator
+//! meters (EPSG:3857), easting and northing, so reproject a dataset first,
+//! e.g. with `gdalwarp -t_srs EPSG:3857`.
+//!
+//! Each pixel is computed from the terrain alone, with colors normalized
+//! over the whole terrain's height range, so any tile can be rendered on
+//! its own, in any order or in parallel, and always comes out the same and
+//! matching its neighbors. Pixels off the terrain are transparent.
+
+use glam::{DVec2, DVec3};
+use rayon::prelude::*;
+
+use super::colors::{height_to_color, ColorScheme};
+use super::TerrainData;
+
+/// Width and height of a tile, in pixels.
+pub const TILE_SIZE: u32 = 256;
+
+/// Half the side of the Web Mercator square, in meters.
+const MERCATOR_EXTENT: f64 = 20_037_508.342_789_244;
+
+/// What a tile shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileLayer {
+    /// Gray shading by the light on each slope
+    #[default]
+    Hillshade,
+    /// Heights through the color scheme
+    ColorRelief,
+    /// Color relief darkened by the hillshade
+    ShadedRelief,
+}
+
+/// How tiles are drawn. The light defaults to GDAL's `gdaldem hillshade`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileStyle {
+    pub layer: TileLayer,
+    /// Colors of the relief layers
+    pub color_scheme: ColorScheme,
+    /// Direction the light comes from, in degrees clockwise from north
+    pub azimuth: f64,
+    /// Height of the light above the horizon, in degrees
+    pub altitude: f64,
+    /// Vertical exaggeration of the shading
+    pub z_factor: f64,
+}
+
+impl Default for TileStyle {
+    fn default() -> Self {
+        Self {
+            layer: TileLayer::default(),
+            color_scheme: ColorScheme::default(),
+            azimuth: 315.0,
+            altitude: 45.0,
+            z_factor: 1.0,
+        }
+    }
+}
+
+impl TileStyle {
+    /// Unit vector toward the light, in (east, north, up).
+    fn light(&self) -> DVec3 {
+        let (azimuth, altitude) = (self.azimuth.to_radians(), self.altitude.to_radians());
+        DVec3::new(
+            azimuth.sin() * altitude.cos(),
+            azimuth.cos() * altitude.cos(),
+            altitude.sin(),
+        )
+    }
+}
+
+/// An 8-bit sRGB RGBA image, row by row from the top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl RgbaImage {
+    /// Encode the image as a PNG file.
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.as_flattened())?;
+        writer.finish()?;
+        Ok(out)
+    }
+}
+
+/// Render tile (`x`, `y`) of zoom level `z` of `dataset` in `style`.
+/// Tiles outside the zoom level's grid are blank.
+pub fn render_tile(dataset: &TerrainData, z: u32, x: u32, y: u32, style: &TileStyle) -> RgbaImage {
+    let tiles = 2f64.powi(z.min(32) as i32);
+    let tile_size = 2.0 * MERCATOR_EXTENT / tiles;
+    let pixel_size = tile_size / TILE_SIZE as f64;
+    let corner = DVec2::new(
+        -MERCATOR_EXTENT + x as f64 * tile_size,
+        MERCATOR_EXTENT - y as f64 * tile_size,
+    );
+    let inside =
+        (x as f64) < tiles && (y as f64) < tiles && dataset.width > 0 && dataset.height > 0;
+
+    let (low, high) = dataset.height_bounds();
+    let range = (high - low).max(f64::EPSILON);
+    let light = style.light();
+    // Slopes are measured over a pixel, or a cell when zoomed in further
+    let step = pixel_size.max(dataset.cell_size);
+    let mut pixels = vec![[0; 4]; (TILE_SIZE * TILE_SIZE) as usize];
+    if inside {
+        pixels
+            .par_chunks_mut(TILE_SIZE as usize)
+            .enumerate()
+            .for_each(|(row, pixels)| {
+                for (column, pixel) in pixels.iter_mut().enumerate() {
+                    let position =
+                        corner + DVec2::new(column as f64 + 0.5, -(row as f64 + 0.5)) * pixel_size;
+                    let Some(height) = dataset.height_at(position.x, position.y) else {
+                        continue;
+                    };
+                    let shade = || {
+                        let slope = gradient(dataset, position, step) * style.z_factor;
+                        let normal = DVec3::new(-slope.x, -slope.y, 1.0).normalize();
+                        normal.dot(light).max(0.0)
+                    };
+                    let color =
+                        || height_to_color(((height - low) / range) as f32, style.color_scheme);
+                    let rgb = match style.layer {
+                        TileLayer::Hillshade => [shade() as f32; 3],
+                        TileLayer::ColorRelief => color(),
+                        TileLayer::ShadedRelief => {
+                            let shade = shade() as f32;
+                            color().map(|c| c * shade)
+                        }
+                    };
+                    let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                    *pixel = [r, g, b, 255];
+                }
+            });
+    }
+    RgbaImage {
+        width: TILE_SIZE,
+        height: TILE_SIZE,
+        pixels,
+    }
+}
+
+/// Height change per unit east and north at `position`, over `step` to
+/// either side, or less at the edges of the terrain.
+fn gradient(terrain: &TerrainData, position: DVec2, step: f64) -> DVec2 {
+    let min = terrain.origin;
+    let max = min
+        + DVec2::new(
+            terrain.width.saturating_sub(1) as f64,
+            terrain.height.saturating_sub(1) as f64,
+        ) * terrain.cell_size;
+    let at = |p: DVec2| terrain.height_at(p.x, p.y).unwrap_or_default();
+    let slope = |axis: DVec2| {
+        let before = (position - axis * step).clamp(min, max);
+        let after = (position + axis * step).clamp(min, max);
+        let run = (after - before).dot(axis);
+        if run > 0.0 {
+            (at(after) - at(before)) / run
+        } else {
+            0.0
+        }
+    };
+    DVec2::new(slope(DVec2::X), slope(DVec2::Y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 100 km square centered on the Mercator origin, rising 1 m per
+    /// 10 m to the east.
+    fn ramp() -> TerrainData {
+        let rows = vec![(0..101).map(|x| x as f64 * 100.0).collect(); 101];
+        let mut terrain = TerrainData::new(rows, None);
+        terrain.cell_size = 1000.0;
+        terrain.origin = DVec2::splat(-50_000.0);
+        terrain
+    }
+
+    #[test]
+    fn test_tiles_cover_the_terrain_and_repeat_exactly() {
+        let terrain = ramp();
+        let style = TileStyle::default();
+
+        // A quarter of the northern hemisphere: one opaque pixel in the
+        // corner at the origin
+        let quarter = render_tile(&terrain, 2, 2, 1, &style);
+        assert_eq!(quarter.pixels.len(), 256 * 256);
+        let opaque: Vec<_> = (0..256 * 256)
+            .filter(|&i| quarter.pixels[i][3] == 255)
+            .collect();
+        assert_eq!(opaque, vec![255 * 256]);
+
+        // Zoom 10 tile northeast of the origin, wholly on the terrain
+        let tile = render_tile(&terrain, 10, 512, 511, &style);
+        assert!(tile.pixels.iter().all(|p| p[3] == 255));
+        assert_eq!(render_tile(&terrain, 10, 512, 511, &style), tile);
+        assert!(render_tile(&terrain, 10, 1024, 0, &style)
+            .pixels
+            .iter()
+            .all(|p| *p == [0; 4]));
+        assert!(tile.to_png().unwrap().starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn test_hillshade_faces_the_light_and_relief_follows_height() {
+        let terrain = ramp();
+        // Slopes facing west are lit by a western light, not an eastern one
+        let shade = |azimuth: f64| {
+            let style = TileStyle {
+                azimuth,
+                ..Default::default()
+            };
+            render_tile(&terrain, 10, 512, 511, &style).pixels[0][0]
+        };
+        assert!(shade(270.0) > shade(90.0));
+        assert_eq!(shade(270.0), shade(-90.0));
+
+        let relief = TileStyle {
+            layer: TileLayer::ColorRelief,
+            color_scheme: ColorScheme::Heatmap,
+            ..Default::default()
+        };
+        let west = render_tile(&terrain, 10, 511, 511, &relief).pixels[0];
+        let east = render_tile(&terrain, 10, 512, 511, &relief).pixels[255];
+        assert_ne!(west, east);
+        let shaded = render_tile(
+            &terrain,
+            10,
+            512,
+            511,
+            &TileStyle {
+                layer: TileLayer::ShadedRelief,
+                ..relief
+            },
+        );
+        let plain = render_tile(&terrain, 10, 512, 511, &relief);
+        assert!(shaded.pixels[0][0] <= plain.pixels[0][0]);
+    }
+}
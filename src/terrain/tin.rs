@@ -0,0 +1,544 @@
+//! Adaptive triangulation of the heightfield (TIN).
+//!
+//! A grid mesh spends two triangles on every cell, as many on a flat field
+//! as on a cliff. Greedy insertion (Garland and Heckbert, "Fast Polygonal
+//! Approximation of Terrains and Height Fields") starts from the grid's
+//! four corners and repeatedly inserts the sample farthest above or below
+//! the surface triangulated so far, keeping the triangulation Delaunay by
+//! flipping edges, until every sample lies within a tolerance of it. Flat
+//! areas end up with a few large triangles while ridges and breaks of
+//! slope get as many as they need.
+//!
+//! The triangulation works in grid coordinates, so orientation and
+//! in-circle tests are exact integer arithmetic, and its vertices are
+//! those of the grid mesh, with their colors and normals.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use glam::Vec3;
+
+use super::mesh::{self, LineTopology, MeshChunk, TerrainMesh};
+
+/// Default vertical tolerance, in terrain height units.
+pub const DEFAULT_TOLERANCE: f32 = 1.0;
+
+/// No neighbor across an edge: it lies on the outline.
+const NONE: u32 = u32::MAX;
+
+/// Sample of a triangle farthest from it, valid while the triangle's
+/// stamp is unchanged.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    error: f32,
+    triangle: u32,
+    sample: u32,
+    stamp: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    /// Largest error first in a max-heap.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.error.total_cmp(&other.error)
+    }
+}
+
+/// Working state of a triangulation.
+struct Triangulation<'a> {
+    width: usize,
+    /// Height of each grid sample
+    heights: &'a [f32],
+    tolerance: f32,
+    /// Vertices of each triangle as sample indices, counterclockwise in
+    /// grid coordinates
+    triangles: Vec<[u32; 3]>,
+    /// Neighbor across edge `e`, from vertex `e` to vertex `e + 1`
+    neighbors: Vec<[u32; 3]>,
+    stamps: Vec<u32>,
+    heap: BinaryHeap<Candidate>,
+    /// Triangles changed by the insertion underway
+    changed: Vec<u32>,
+}
+
+impl<'a> Triangulation<'a> {
+    fn new(width: usize, heights: &'a [f32], tolerance: f32) -> Self {
+        let height = heights.len() / width;
+        let (a, b) = (0, width as u32 - 1);
+        let (c, d) = ((width * (height - 1)) as u32, (width * height) as u32 - 1);
+        let mut triangulation = Self {
+            width,
+            heights,
+            tolerance,
+            triangles: vec![[a, b, d], [a, d, c]],
+            neighbors: vec![[NONE, NONE, 1], [0, NONE, NONE]],
+            stamps: vec![0, 0],
+            heap: BinaryHeap::new(),
+            changed: Vec::new(),
+        };
+        triangulation.scan(0);
+        triangulation.scan(1);
+        triangulation
+    }
+
+    /// Grid coordinates of sample `i`.
+    fn coords(&self, i: u32) -> (i64, i64) {
+        let i = i as usize;
+        ((i % self.width) as i64, (i / self.width) as i64)
+    }
+
+    /// Twice the signed area of `a`, `b`, `c`; positive counterclockwise.
+    fn orient(&self, a: u32, b: u32, c: u32) -> i64 {
+        let ((ax, az), (bx, bz), (cx, cz)) = (self.coords(a), self.coords(b), self.coords(c));
+        (bx - ax) * (cz - az) - (bz - az) * (cx - ax)
+    }
+
+    /// Whether `d` lies strictly inside the circumcircle of the
+    /// counterclockwise triangle `a`, `b`, `c`.
+    fn in_circle(&self, [a, b, c]: [u32; 3], d: u32) -> bool {
+        let (dx, dz) = self.coords(d);
+        let row = |p: u32| {
+            let (x, z) = self.coords(p);
+            let (x, z) = ((x - dx) as i128, (z - dz) as i128);
+            (x, z, x * x + z * z)
+        };
+        let ((ax, az, aw), (bx, bz, bw), (cx, cz, cw)) = (row(a), row(b), row(c));
+        ax * (bz * cw - bw * cz) - az * (bx * cw - bw * cx) + aw * (bx * cz - bz * cx) > 0
+    }
+
+    /// Find the sample of triangle `t` farthest from its plane, and queue
+    /// it if beyond the tolerance.
+    fn scan(&mut self, t: u32) {
+        let [a, b, c] = self.triangles[t as usize];
+        let area = self.orient(a, b, c) as f32;
+        let [(ax, az), (bx, bz), (cx, cz)] = [a, b, c].map(|v| self.coords(v));
+        let [ha, hb, hc] = [a, b, c].map(|v| self.heights[v as usize]);
+        let mut worst: Option<(f32, u32)> = None;
+        for z in az.min(bz).min(cz)..=az.max(bz).max(cz) {
+            for x in ax.min(bx).min(cx)..=ax.max(bx).max(cx) {
+                let s = (z as usize * self.width + x as usize) as u32;
+                if s == a || s == b || s == c {
+                    continue;
+                }
+                let (wa, wb, wc) = (
+                    self.orient(b, c, s),
+                    self.orient(c, a, s),
+                    self.orient(a, b, s),
+                );
+                if wa < 0 || wb < 0 || wc < 0 {
+                    continue;
+                }
+                let surface = (wa as f32 * ha + wb as f32 * hb + wc as f32 * hc) / area;
+                let error = (self.heights[s as usize] - surface).abs();
+                if error > worst.map_or(self.tolerance, |(worst, _)| worst) {
+                    worst = Some((error, s));
+                }
+            }
+        }
+        if let Some((error, sample)) = worst {
+            self.heap.push(Candidate {
+                error,
+                triangle: t,
+                sample,
+                stamp: self.stamps[t as usize],
+            });
+        }
+    }
+
+    /// Insert samples until all lie within the tolerance.
+    fn run(&mut self) {
+        while let Some(candidate) = self.heap.pop() {
+            if candidate.stamp != self.stamps[candidate.triangle as usize] {
+                continue;
+            }
+            self.insert(candidate.triangle, candidate.sample);
+            self.rescan();
+        }
+    }
+
+    /// Insert `samples` whatever their error, e.g. along breaklines.
+    fn keep(&mut self, samples: &[u32]) {
+        let mut t = 0;
+        for &p in samples {
+            t = self.locate(t, p);
+            if !self.triangles[t as usize].contains(&p) {
+                self.insert(t, p);
+                self.rescan();
+            }
+        }
+    }
+
+    /// Triangle holding sample `p`, inside or on its outline, walking
+    /// toward it from triangle `t`.
+    fn locate(&self, mut t: u32, p: u32) -> u32 {
+        loop {
+            let [a, b, c] = self.triangles[t as usize];
+            let beyond = [(a, b), (b, c), (c, a)]
+                .iter()
+                .position(|&(from, to)| self.orient(from, to, p) < 0);
+            match beyond {
+                Some(e) => t = self.neighbors[t as usize][e],
+                None => return t,
+            }
+        }
+    }
+
+    /// Give the triangles the last insertion changed new stamps, and find
+    /// their farthest samples again.
+    fn rescan(&mut self) {
+        let mut changed = std::mem::take(&mut self.changed);
+        changed.sort_unstable();
+        changed.dedup();
+        for &t in &changed {
+            self.stamps[t as usize] += 1;
+            self.scan(t);
+        }
+        changed.clear();
+        self.changed = changed;
+    }
+
+    /// Insert sample `p`, which lies in triangle `t` or on its edges.
+    fn insert(&mut self, t: u32, p: u32) {
+        let [a, b, c] = self.triangles[t as usize];
+        let edge = [(a, b), (b, c), (c, a)]
+            .iter()
+            .position(|&(from, to)| self.orient(from, to, p) == 0);
+        match edge {
+            Some(e) => self.split_edge(t, e, p),
+            None => self.split_triangle(t, p),
+        }
+    }
+
+    /// Add triangle `vertices` with `neighbors`, returning its index.
+    fn push(&mut self, vertices: [u32; 3], neighbors: [u32; 3]) -> u32 {
+        self.triangles.push(vertices);
+        self.neighbors.push(neighbors);
+        self.stamps.push(0);
+        self.triangles.len() as u32 - 1
+    }
+
+    /// Point `t`'s neighbor across the edge they share at `to` instead.
+    fn relink(&mut self, t: u32, from: u32, to: u32) {
+        if t == NONE {
+            return;
+        }
+        for neighbor in &mut self.neighbors[t as usize] {
+            if *neighbor == from {
+                *neighbor = to;
+            }
+        }
+    }
+
+    /// Split triangle `t` into three around `p` inside it.
+    fn split_triangle(&mut self, t: u32, p: u32) {
+        let [a, b, c] = self.triangles[t as usize];
+        let [n_ab, n_bc, n_ca] = self.neighbors[t as usize];
+        let count = self.triangles.len() as u32;
+        let (t1, t2) = (count, count + 1);
+        self.triangles[t as usize] = [a, b, p];
+        self.neighbors[t as usize] = [n_ab, t1, t2];
+        self.push([b, c, p], [n_bc, t2, t]);
+        self.push([c, a, p], [n_ca, t, t1]);
+        self.relink(n_bc, t, t1);
+        self.relink(n_ca, t, t2);
+        for triangle in [t, t1, t2] {
+            self.legalize(triangle);
+        }
+    }
+
+    /// Split edge `e` of triangle `t` at `p` on it, along with the
+    /// neighbor across it, if any.
+    fn split_edge(&mut self, t: u32, e: usize, p: u32) {
+        let rotate = |v: [u32; 3]| [v[e], v[(e + 1) % 3], v[(e + 2) % 3]];
+        let [a, b, c] = rotate(self.triangles[t as usize]);
+        let [u, n_bc, n_ca] = rotate(self.neighbors[t as usize]);
+        let t1 = self.triangles.len() as u32;
+        let u1 = if u == NONE { NONE } else { t1 + 1 };
+
+        self.triangles[t as usize] = [c, a, p];
+        self.neighbors[t as usize] = [n_ca, u, t1];
+        self.push([b, c, p], [n_bc, t, u1]);
+        self.relink(n_bc, t, t1);
+        if u != NONE {
+            // The neighbor runs the edge the other way, from b to a
+            let f = self.neighbors[u as usize]
+                .iter()
+                .position(|&n| n == t)
+                .expect("neighbors link both ways");
+            let rotate = |v: [u32; 3]| [v[f], v[(f + 1) % 3], v[(f + 2) % 3]];
+            let [_, _, d] = rotate(self.triangles[u as usize]);
+            let [_, n_ad, n_db] = rotate(self.neighbors[u as usize]);
+            self.triangles[u as usize] = [a, d, p];
+            self.neighbors[u as usize] = [n_ad, u1, t];
+            self.push([d, b, p], [n_db, t1, u]);
+            self.relink(n_db, u, u1);
+        }
+        for triangle in [t, t1, u, u1] {
+            if triangle != NONE {
+                self.legalize(triangle);
+            }
+        }
+    }
+
+    /// Restore the Delaunay property across edge 0 of triangle `t`, the
+    /// one facing its newly inserted vertex 2, flipping it and the edges
+    /// beyond as needed.
+    fn legalize(&mut self, t: u32) {
+        self.changed.push(t);
+        let u = self.neighbors[t as usize][0];
+        if u == NONE {
+            return;
+        }
+        let [a, b, p] = self.triangles[t as usize];
+        let f = self.neighbors[u as usize]
+            .iter()
+            .position(|&n| n == t)
+            .expect("neighbors link both ways");
+        let rotate = |v: [u32; 3]| [v[f], v[(f + 1) % 3], v[(f + 2) % 3]];
+        let [_, _, q] = rotate(self.triangles[u as usize]);
+        if !self.in_circle([a, b, p], q) {
+            return;
+        }
+
+        // Flip edge a-b of the quad a, q, b, p to q-p
+        let [_, n_bp, n_pa] = self.neighbors[t as usize];
+        let [_, n_aq, n_qb] = rotate(self.neighbors[u as usize]);
+        self.triangles[t as usize] = [a, q, p];
+        self.neighbors[t as usize] = [n_aq, u, n_pa];
+        self.triangles[u as usize] = [q, b, p];
+        self.neighbors[u as usize] = [n_qb, n_bp, t];
+        self.relink(n_aq, u, t);
+        self.relink(n_bp, t, u);
+        self.legalize(t);
+        self.legalize(u);
+    }
+}
+
+/// Triangulate grid mesh `mesh`, `width` vertices wide, adaptively, until
+/// every vertex lies within `tolerance` of the surface vertically, in the
+/// mesh's height units. The `kept` samples, such as those along
+/// [`breaklines`](super::breaklines), are inserted first: neighboring
+/// ones in a row or column always end up joined by an edge, since no
+/// other sample can lie in the circle through them.
+///
+/// All of the grid's vertices are kept, used or not, so anything indexing
+/// them by sample still lines up; [`compact`] drops the unused ones. The
+/// triangles and their edges make one chunk without levels of detail.
+pub fn triangulate(mesh: &TerrainMesh, width: usize, tolerance: f32, kept: &[u32]) -> TerrainMesh {
+    let height = mesh.vertices.len().checked_div(width).unwrap_or(0);
+    if width < 2 || height < 2 {
+        return TerrainMesh {
+            vertices: mesh.vertices.clone(),
+            indices: mesh.indices.clone(),
+            line_topology: mesh.line_topology,
+            triangle_indices: mesh.triangle_indices.clone(),
+            chunks: mesh.chunks.clone(),
+            lod_indices: mesh.lod_indices.clone(),
+            lod_triangle_indices: mesh.lod_triangle_indices.clone(),
+            bounds: mesh.bounds,
+        };
+    }
+    let heights: Vec<f32> = mesh.vertices.iter().map(|v| v.position[1]).collect();
+    let mut triangulation = Triangulation::new(width, &heights, tolerance.max(0.0));
+    triangulation.keep(kept);
+    triangulation.run();
+
+    // Wound like the grid mesh's triangles, clockwise in grid coordinates
+    let triangle_indices: Vec<u32> = triangulation
+        .triangles
+        .iter()
+        .flat_map(|&[a, b, c]| [a, c, b])
+        .collect();
+    // Each edge once, from the triangle on its lower-numbered side
+    let indices: Vec<u32> = triangulation
+        .triangles
+        .iter()
+        .zip(&triangulation.neighbors)
+        .enumerate()
+        .flat_map(|(t, (&[a, b, c], neighbors))| {
+            [(a, b), (b, c), (c, a)]
+                .into_iter()
+                .zip(*neighbors)
+                .filter(move |&(_, n)| n == NONE || n > t as u32)
+                .flat_map(|((from, to), _)| [from, to])
+        })
+        .collect();
+    let indices = match mesh.line_topology {
+        LineTopology::List => indices,
+        LineTopology::Strip => mesh::line_strips(&indices),
+    };
+
+    let (min, max) = mesh.bounds.unwrap_or((Vec3::ZERO, Vec3::ZERO));
+    TerrainMesh {
+        vertices: mesh.vertices.clone(),
+        chunks: vec![MeshChunk {
+            triangles: 0..triangle_indices.len() as u32,
+            lines: 0..indices.len() as u32,
+            min,
+            max,
+            lods: Vec::new(),
+        }],
+        indices,
+        line_topology: mesh.line_topology,
+        triangle_indices,
+        lod_indices: Vec::new(),
+        lod_triangle_indices: Vec::new(),
+        bounds: mesh.bounds,
+    }
+}
+
+/// `mesh` without the vertices no triangle uses, e.g. for exporting a
+/// triangulation, with the index in `mesh` of each vertex kept.
+pub fn compact(mesh: &TerrainMesh) -> (TerrainMesh, Vec<u32>) {
+    let mut remap = vec![NONE; mesh.vertices.len()];
+    let mut sources = Vec::new();
+    let triangle_indices = mesh
+        .triangle_indices
+        .iter()
+        .map(|&i| {
+            if remap[i as usize] == NONE {
+                remap[i as usize] = sources.len() as u32;
+                sources.push(i);
+            }
+            remap[i as usize]
+        })
+        .collect();
+    let compacted = TerrainMesh {
+        vertices: sources.iter().map(|&i| mesh.vertices[i as usize]).collect(),
+        triangle_indices,
+        bounds: mesh.bounds,
+        ..TerrainMesh::default()
+    };
+    (compacted, sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::{breaklines, MeshOptions, TerrainData};
+    use glam::DVec2;
+    use std::collections::HashSet;
+
+    fn grid_mesh(terrain: &TerrainData) -> TerrainMesh {
+        TerrainMesh::build(terrain, &MeshOptions::default())
+    }
+
+    /// Height of the triangulated surface at sample `(x, z)`.
+    fn surface_at(mesh: &TerrainMesh, width: usize, (x, z): (usize, usize)) -> Option<f32> {
+        let coords = |i: u32| ((i as usize % width) as f32, (i as usize / width) as f32);
+        let (px, pz) = (x as f32, z as f32);
+        mesh.triangle_indices.chunks_exact(3).find_map(|t| {
+            let [(ax, az), (bx, bz), (cx, cz)] = [t[0], t[1], t[2]].map(coords);
+            let area = (bx - ax) * (cz - az) - (bz - az) * (cx - ax);
+            let wa = ((cx - bx) * (pz - bz) - (cz - bz) * (px - bx)) / area;
+            let wb = ((ax - cx) * (pz - cz) - (az - cz) * (px - cx)) / area;
+            let wc = 1.0 - wa - wb;
+            (wa >= -1e-6 && wb >= -1e-6 && wc >= -1e-6).then(|| {
+                let h = |i: u32| mesh.vertices[i as usize].position[1];
+                wa * h(t[0]) + wb * h(t[1]) + wc * h(t[2])
+            })
+        })
+    }
+
+    #[test]
+    fn test_flat_grid_needs_two_triangles() {
+        let terrain = TerrainData::new(vec![vec![3.0; 20]; 12], None);
+        let tin = triangulate(&grid_mesh(&terrain), 20, 0.1, &[]);
+        assert_eq!(tin.triangle_indices.len(), 6);
+        // Four outline edges and the diagonal
+        assert_eq!(tin.indices.len(), 10);
+        assert_eq!(tin.chunks.len(), 1);
+    }
+
+    #[test]
+    fn test_all_samples_within_tolerance() {
+        let (width, height) = (33, 25);
+        let rows = (0..height)
+            .map(|z| {
+                (0..width)
+                    .map(|x| {
+                        let (x, z) = (x as f64, z as f64);
+                        // A ridge on gentle noise over a plain
+                        (12.0 - (x - 16.0).abs()).max(0.0) + ((x * 0.7).sin() * (z * 1.3).cos())
+                    })
+                    .collect()
+            })
+            .collect();
+        let terrain = TerrainData::new(rows, None);
+        let mesh = grid_mesh(&terrain);
+        let tin = triangulate(&mesh, width, 0.5, &[]);
+        assert!(tin.triangle_indices.len() < mesh.triangle_indices.len());
+
+        for z in 0..height {
+            for x in 0..width {
+                let expected = mesh.vertices[z * width + x].position[1];
+                let surface = surface_at(&tin, width, (x, z)).expect("triangles cover the grid");
+                assert!((surface - expected).abs() <= 0.5 + 1e-4, "{x}, {z}");
+            }
+        }
+        // Wound like the grid mesh, so the same faces are culled
+        let normal = |t: &[u32]| {
+            let p = |i: u32| Vec3::from(tin.vertices[i as usize].position);
+            (p(t[1]) - p(t[0])).cross(p(t[2]) - p(t[0]))
+        };
+        assert!(tin
+            .triangle_indices
+            .chunks_exact(3)
+            .all(|t| normal(t).y > 0.0));
+        assert!(normal(&mesh.triangle_indices[..3]).y > 0.0);
+    }
+
+    #[test]
+    fn test_breakline_samples_are_joined() {
+        // A wall along a diagonal the flat ground wouldn't otherwise need
+        let terrain = TerrainData::new(vec![vec![0.0; 16]; 16], None);
+        let line = vec![DVec2::new(2.0, 3.0), DVec2::new(13.0, 9.0)];
+        let kept = breaklines::samples(&terrain, &[line]);
+        let tin = triangulate(&grid_mesh(&terrain), 16, 0.1, &kept);
+
+        let edges: HashSet<(u32, u32)> = tin
+            .triangle_indices
+            .chunks_exact(3)
+            .flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        let joined = |a: u32, b: u32| edges.contains(&(a.min(b), a.max(b)));
+        for &s in &kept {
+            let right = s + 1;
+            let below = s + 16;
+            if kept.contains(&right) && right % 16 != 0 {
+                assert!(joined(s, right), "{s}");
+            }
+            if kept.contains(&below) {
+                assert!(joined(s, below), "{s}");
+            }
+        }
+        assert!(kept.iter().all(|s| tin.triangle_indices.contains(s)));
+    }
+
+    #[test]
+    fn test_compact_keeps_used_vertices() {
+        let terrain = TerrainData::new(vec![vec![0.0; 5]; 5], None);
+        let tin = triangulate(&grid_mesh(&terrain), 5, 0.1, &[]);
+        let (compacted, sources) = compact(&tin);
+        assert_eq!(sources.len(), 4);
+        assert_eq!(compacted.vertices.len(), 4);
+        assert_eq!(compacted.triangle_indices.len(), 6);
+        for (&i, &source) in compacted.triangle_indices.iter().zip(&tin.triangle_indices) {
+            assert_eq!(sources[i as usize], source);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Per-sample uncertainty of the terrain heights.
+//!
+//! Surveys and interpolated grids often come with a standard deviation (σ)
+//! per sample. Loaded as a [`Channel`], it's shown over the surface rather
+//! than as its colors: uncertain areas fade out or are hatched, or whiskers
+//! mark the range of heights within a few σ of the surface.
+
+use glam::DVec3;
+
+use super::grid::Channel;
+use super::TerrainData;
+
+/// Whiskers drawn along the longer side of the grid.
+const WHISKERS_ACROSS: usize = 48;
+
+/// How uncertainty is shown on the surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UncertaintyStyle {
+    /// Fade the surface out where σ is large
+    #[default]
+    Transparency,
+    /// Hatch the surface, densest where σ is large
+    Hatching,
+    /// Vertical error bars spanning the heights within a few σ
+    Whiskers,
+}
+
+impl UncertaintyStyle {
+    /// All styles, in UI order.
+    pub const ALL: [UncertaintyStyle; 3] = [
+        UncertaintyStyle::Transparency,
+        UncertaintyStyle::Hatching,
+        UncertaintyStyle::Whiskers,
+    ];
+
+    /// Display name.
+    pub fn label(self) -> &'static str {
+        match self {
+            UncertaintyStyle::Transparency => "Transparency",
+            UncertaintyStyle::Hatching => "Hatching",
+            UncertaintyStyle::Whiskers => "Whiskers",
+        }
+    }
+
+    /// Style number understood by `solid.wgsl`; whiskers aren't drawn by
+    /// the surface shader.
+    pub fn shader_index(self) -> u32 {
+        match self {
+            UncertaintyStyle::Transparency => 1,
+            UncertaintyStyle::Hatching => 2,
+            UncertaintyStyle::Whiskers => 0,
+        }
+    }
+}
+
+/// Uncertainty display configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UncertaintyConfig {
+    /// Channel holding σ, or `None` to show no uncertainty
+    pub channel: Option<usize>,
+    /// How σ is shown
+    pub style: UncertaintyStyle,
+    /// Opacity removed or fraction hatched at the largest σ (0.0 - 1.0)
+    pub strength: f32,
+    /// Whisker half-length in multiples of σ
+    pub sigmas: f32,
+}
+
+impl Default for UncertaintyConfig {
+    fn default() -> Self {
+        Self {
+            channel: None,
+            style: UncertaintyStyle::default(),
+            strength: 0.8,
+            sigmas: 2.0,
+        }
+    }
+}
+
+/// σ of each sample relative to the largest, for sampling in the shader.
+/// All zero when no sample is uncertain.
+pub fn normalized(sigma: &Channel) -> Vec<u8> {
+    let max = sigma
+        .grid
+        .values()
+        .iter()
+        .fold(0.0f32, |max, &s| max.max(s.abs()));
+    sigma
+        .grid
+        .values()
+        .iter()
+        .map(|&s| {
+            let relative = if max > 0.0 { s.abs() / max } else { 0.0 };
+            (relative * 255.0).round() as u8
+        })
+        .collect()
+}
+
+/// Samples between whiskers along each axis.
+pub fn whisker_spacing(terrain: &TerrainData) -> usize {
+    (terrain.width.max(terrain.height) / WHISKERS_ACROSS).max(1)
+}
+
+/// Whiskers over an even spread of samples: each sample's world position
+/// on the unscaled surface and the half-length of its whisker, `sigmas`
+/// times its σ. Samples with no uncertainty get none.
+pub fn whiskers(terrain: &TerrainData, sigma: &Channel, sigmas: f32) -> Vec<(DVec3, f64)> {
+    let spacing = whisker_spacing(terrain);
+    let offset = spacing / 2;
+    (offset..terrain.height)
+        .step_by(spacing)
+        .flat_map(|z| {
+            (offset..terrain.width)
+                .step_by(spacing)
+                .map(move |x| (x, z))
+        })
+        .filter_map(|(x, z)| {
+            let half = sigma.grid.values()[z * terrain.width + x].abs() as f64 * sigmas as f64;
+            (half > 0.0).then(|| (terrain.world_position(x, z), half))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whiskers_span_sigmas() {
+        let mut terrain = TerrainData::new(vec![vec![1.0; 4]; 2], None);
+        let sigma = TerrainData::new(vec![vec![0.0, 0.5, 0.0, 2.0]; 2], None);
+        terrain.add_channel("sigma", &sigma);
+        let channel = &terrain.channels()[0];
+
+        // Small grids get a whisker per sample, none where σ is zero
+        let whiskers = whiskers(&terrain, channel, 2.0);
+        assert_eq!(whiskers.len(), 4);
+        assert_eq!(whiskers[0], (DVec3::new(1.0, 1.0, 0.0), 1.0));
+        assert_eq!(whiskers[1], (DVec3::new(3.0, 1.0, 0.0), 4.0));
+
+        assert_eq!(normalized(channel)[..4], [0, 64, 0, 255]);
+    }
+}
@@ -0,0 +1,162 @@
+//! Units of terrain heights and distances.
+//!
+//! A terrain's heights and cell size share one unit, read from GeoTIFF
+//! GeoKeys or given with `--units`, or unknown. [`UnitFormat`] shows
+//! measurements in it, or converted to another unit picked in the viewer,
+//! with the unit's symbol. Unitless terrains are shown as stored, since
+//! there is nothing to convert from.
+
+/// Unit of a terrain's heights and distances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Units {
+    /// Unknown or arbitrary units, shown without a symbol
+    #[default]
+    Unitless,
+    /// Meters
+    Meters,
+    /// International feet
+    Feet,
+}
+
+/// Meters per international foot.
+const METERS_PER_FOOT: f64 = 0.3048;
+
+impl Units {
+    /// Units that measurements can be shown in.
+    pub const ALL: [Units; 2] = [Units::Meters, Units::Feet];
+
+    /// Name shown in the UI.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Unitless => "Unitless",
+            Self::Meters => "Meters",
+            Self::Feet => "Feet",
+        }
+    }
+
+    /// Symbol following values, empty when unitless.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Unitless => "",
+            Self::Meters => "m",
+            Self::Feet => "ft",
+        }
+    }
+
+    /// Meters in one unit, or `None` when unitless.
+    fn meters(self) -> Option<f64> {
+        match self {
+            Self::Unitless => None,
+            Self::Meters => Some(1.0),
+            Self::Feet => Some(METERS_PER_FOOT),
+        }
+    }
+
+    /// Units of an EPSG linear unit code, as used by GeoTIFF GeoKeys:
+    /// 9001 for meters, 9002 and 9003 (US survey) for feet.
+    pub fn from_epsg(code: u16) -> Option<Self> {
+        match code {
+            9001 => Some(Self::Meters),
+            9002 | 9003 => Some(Self::Feet),
+            _ => None,
+        }
+    }
+
+    /// EPSG linear unit code of the units, or `None` when unitless; see
+    /// [`Self::from_epsg`].
+    pub fn epsg(self) -> Option<u16> {
+        match self {
+            Self::Unitless => None,
+            Self::Meters => Some(9001),
+            Self::Feet => Some(9002),
+        }
+    }
+}
+
+/// Formats lengths and areas measured in a terrain's units for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UnitFormat {
+    /// Units the terrain is measured in
+    pub source: Units,
+    /// Units to show measurements in
+    pub display: Units,
+}
+
+impl UnitFormat {
+    /// Units measurements are shown in: the display units, unless the
+    /// terrain's are unknown.
+    pub fn shown(self) -> Units {
+        match (self.source.meters(), self.display.meters()) {
+            (Some(_), Some(_)) => self.display,
+            _ => self.source,
+        }
+    }
+
+    /// `length` converted to the shown units.
+    pub fn value(self, length: f64) -> f64 {
+        match (self.source.meters(), self.shown().meters()) {
+            (Some(from), Some(to)) => length * from / to,
+            _ => length,
+        }
+    }
+
+    /// `length` in the shown units, with their symbol.
+    pub fn length(self, length: f64) -> String {
+        match self.shown().symbol() {
+            "" => format!("{:.4}", self.value(length)),
+            symbol => format!("{:.4} {symbol}", self.value(length)),
+        }
+    }
+
+    /// `area` in the shown units squared, with their symbol.
+    pub fn area(self, area: f64) -> String {
+        let factor = self.value(1.0);
+        let symbol = match self.shown().symbol() {
+            "" => "units",
+            symbol => symbol,
+        };
+        format!("{:.4} {symbol}²", area * factor * factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_converts_known_units() {
+        let feet = UnitFormat {
+            source: Units::Meters,
+            display: Units::Feet,
+        };
+        assert_eq!(feet.shown(), Units::Feet);
+        assert_eq!(feet.length(0.3048), "1.0000 ft");
+        assert_eq!(feet.area(3.048 * 3.048), "100.0000 ft²");
+
+        let meters = UnitFormat {
+            source: Units::Feet,
+            display: Units::Meters,
+        };
+        assert_eq!(meters.length(10.0), "3.0480 m");
+
+        // Nothing to convert from or to
+        let unitless = UnitFormat {
+            source: Units::Unitless,
+            display: Units::Feet,
+        };
+        assert_eq!(unitless.length(2.5), "2.5000");
+        assert_eq!(unitless.area(4.0), "4.0000 units²");
+        let stored = UnitFormat {
+            source: Units::Meters,
+            display: Units::Unitless,
+        };
+        assert_eq!(stored.length(2.0), "2.0000 m");
+
+        assert_eq!(Units::from_epsg(9002), Some(Units::Feet));
+        assert_eq!(Units::from_epsg(9101), None);
+        assert_eq!(
+            Units::Feet.epsg().and_then(Units::from_epsg),
+            Some(Units::Feet)
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! USDZ mesh export for AR Quick Look.
+//!
+//! Writes the solid surface of a [`TerrainMesh`] as a USD mesh with its
+//! baked vertex colors, packaged the way USDZ requires: an uncompressed zip
+//! whose file data starts on 64-byte boundaries. The layer is ASCII USD
+//! (`.usda`), which Quick Look reads like the binary form.
+//!
+//! The stage is scaled so the terrain's largest side is [`TABLETOP_SIZE`]
+//! meters, since grid units rarely match a useful size in AR.
+
+use std::fmt::Write;
+
+use glam::Vec3;
+
+use super::TerrainMesh;
+
+/// Largest side of the exported terrain in meters.
+pub const TABLETOP_SIZE: f32 = 1.0;
+
+/// Name of the USD layer inside the archive.
+const LAYER_NAME: &str = "terrain.usda";
+
+/// Alignment of file data in a USDZ archive.
+const USDZ_ALIGNMENT: usize = 64;
+
+/// Serialize the mesh's triangles as a USDZ archive.
+pub fn to_usdz(mesh: &TerrainMesh) -> Vec<u8> {
+    to_zip(&[(LAYER_NAME, to_usda(mesh).as_bytes())])
+}
+
+/// Serialize the mesh's triangles as an ASCII USD layer.
+///
+/// Vertex colors become the `displayColor` primvar, read into the diffuse
+/// color of a `UsdPreviewSurface` material so AR viewers shade them.
+pub fn to_usda(mesh: &TerrainMesh) -> String {
+    let (min, max) = mesh.vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), v| {
+            let p = Vec3::from_array(v.position);
+            (min.min(p), max.max(p))
+        },
+    );
+    let extent = (max - min).max_element();
+    let meters_per_unit = if extent > 0.0 {
+        TABLETOP_SIZE / extent
+    } else {
+        1.0
+    };
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "#usda 1.0\n(\n    defaultPrim = \"Terrain\"\n    metersPerUnit = {}\n    upAxis = \"Y\"\n)\n\n",
+        meters_per_unit
+    );
+    out.push_str("def Xform \"Terrain\"\n{\n");
+
+    out.push_str("    def Mesh \"Surface\"\n    {\n");
+    let triangles = mesh.triangle_indices.len() / 3;
+    out.push_str("        int[] faceVertexCounts = [");
+    push_list(&mut out, (0..triangles).map(|_| "3".to_string()));
+    out.push_str("]\n        int[] faceVertexIndices = [");
+    push_list(&mut out, mesh.triangle_indices.iter().map(u32::to_string));
+    out.push_str("]\n        point3f[] points = [");
+    push_list(&mut out, mesh.vertices.iter().map(|v| tuple(v.position)));
+    out.push_str("]\n        normal3f[] normals = [");
+    push_list(&mut out, mesh.vertices.iter().map(|v| tuple(v.normal)));
+    out.push_str("] (\n            interpolation = \"vertex\"\n        )\n");
+    out.push_str("        color3f[] primvars:displayColor = [");
+    push_list(&mut out, mesh.vertices.iter().map(|v| tuple(v.color)));
+    out.push_str("] (\n            interpolation = \"vertex\"\n        )\n");
+    if !mesh.vertices.is_empty() {
+        let _ = writeln!(
+            out,
+            "        float3[] extent = [{}, {}]",
+            tuple(min.to_array()),
+            tuple(max.to_array())
+        );
+    }
+    out.push_str("        uniform token subdivisionScheme = \"none\"\n");
+    out.push_str("        rel material:binding = </Terrain/Material>\n    }\n\n");
+
+    out.push_str(concat!(
+        "    def Material \"Material\"\n    {\n",
+        "        token outputs:surface.connect = </Terrain/Material/Surface.outputs:surface>\n\n",
+        "        def Shader \"Surface\"\n        {\n",
+        "            uniform token info:id = \"UsdPreviewSurface\"\n",
+        "            color3f inputs:diffuseColor.connect = </Terrain/Material/Color.outputs:result>\n",
+        "            float inputs:roughness = 0.9\n",
+        "            float inputs:metallic = 0\n",
+        "            token outputs:surface\n        }\n\n",
+        "        def Shader \"Color\"\n        {\n",
+        "            uniform token info:id = \"UsdPrimvarReader_float3\"\n",
+        "            string inputs:varname = \"displayColor\"\n",
+        "            float3 outputs:result\n        }\n    }\n}\n",
+    ));
+    out
+}
+
+/// Append comma-separated items.
+fn push_list(out: &mut String, items: impl Iterator<Item = String>) {
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(&item);
+    }
+}
+
+fn tuple([x, y, z]: [f32; 3]) -> String {
+    format!("({}, {}, {})", x, y, z)
+}
+
+/// Pack files into an uncompressed zip with 64-byte aligned file data.
+///
+/// Alignment comes from padding each local header's extra field, as USDZ
+/// readers map the files in place.
+fn to_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for &(name, data) in files {
+        let offset = out.len() as u32;
+        let crc = crc32fast::hash(data);
+        let header_len = out.len() + 30 + name.len();
+        // Extra field of padding, with its own 4-byte header when present
+        let mut padding = header_len.next_multiple_of(USDZ_ALIGNMENT) - header_len;
+        if padding > 0 && padding < 4 {
+            padding += USDZ_ALIGNMENT;
+        }
+
+        push_u32(&mut out, 0x0403_4b50);
+        push_file_fields(&mut out, crc, data.len(), name.len());
+        push_u16(&mut out, padding as u16);
+        out.extend_from_slice(name.as_bytes());
+        if padding > 0 {
+            push_u16(&mut out, 0x1986);
+            push_u16(&mut out, (padding - 4) as u16);
+            out.resize(out.len() + padding - 4, 0);
+        }
+        out.extend_from_slice(data);
+
+        push_u32(&mut central, 0x0201_4b50);
+        push_u16(&mut central, 20); // made by
+        push_file_fields(&mut central, crc, data.len(), name.len());
+        push_u16(&mut central, 0); // extra field length
+        push_u16(&mut central, 0); // comment length
+        push_u16(&mut central, 0); // disk
+        push_u16(&mut central, 0); // internal attributes
+        push_u32(&mut central, 0); // external attributes
+        push_u32(&mut central, offset);
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    push_u32(&mut out, 0x0605_4b50);
+    push_u16(&mut out, 0); // disk
+    push_u16(&mut out, 0); // disk with central directory
+    push_u16(&mut out, files.len() as u16);
+    push_u16(&mut out, files.len() as u16);
+    push_u32(&mut out, central.len() as u32);
+    push_u32(&mut out, central_offset);
+    push_u16(&mut out, 0); // comment length
+    out
+}
+
+/// Header fields shared by local and central entries, from the version
+/// needed up to the file name length.
+fn push_file_fields(out: &mut Vec<u8>, crc: u32, size: usize, name_len: usize) {
+    push_u16(out, 20); // version needed
+    push_u16(out, 0); // flags
+    push_u16(out, 0); // stored
+    push_u16(out, 0); // time
+    push_u16(out, 0x21); // date: 1980-01-01
+    push_u32(out, crc);
+    push_u32(out, size as u32);
+    push_u32(out, size as u32);
+    push_u16(out, name_len as u16);
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::TerrainData;
+
+    fn u16_at(data: &[u8], offset: usize) -> usize {
+        u16::from_le_bytes([data[offset], data[offset + 1]]) as usize
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> usize {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn test_usda_mesh() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0, 2.0]; 3], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let usda = to_usda(&mesh);
+
+        assert!(usda.starts_with("#usda 1.0\n"));
+        // 2 units across, scaled to a meter
+        assert!(usda.contains("metersPerUnit = 0.5\n"));
+        let counts = usda
+            .lines()
+            .find(|l| l.contains("faceVertexCounts"))
+            .unwrap();
+        assert_eq!(counts.matches('3').count(), mesh.triangle_indices.len() / 3);
+        assert!(usda.contains("faceVertexIndices = [0, 3, 1, 1, 3, 4,"));
+        assert!(usda.contains("point3f[] points = [(-1, 0, -1), (0, 1, -1),"));
+        assert!(usda.contains("float3[] extent = [(-1, 0, -1), (1, 2, 1)]"));
+        assert!(usda.contains("primvars:displayColor"));
+        assert!(usda.contains("rel material:binding = </Terrain/Material>"));
+        // Balanced braces
+        assert_eq!(usda.matches('{').count(), usda.matches('}').count());
+    }
+
+    #[test]
+    fn test_usdz_archive_layout() {
+        let terrain = TerrainData::new(vec![vec![0.0, 1.0]; 2], None);
+        let mesh = TerrainMesh::from_terrain(&terrain, 1.0);
+        let usdz = to_usdz(&mesh);
+        let usda = to_usda(&mesh);
+
+        // Local header, stored, then the layer on a 64-byte boundary
+        assert_eq!(u32_at(&usdz, 0), 0x0403_4b50);
+        assert_eq!(u16_at(&usdz, 8), 0);
+        assert_eq!(u32_at(&usdz, 14), crc32fast::hash(usda.as_bytes()) as usize);
+        let name_len = u16_at(&usdz, 26);
+        let start = 30 + name_len + u16_at(&usdz, 28);
+        assert_eq!(&usdz[30..30 + name_len], LAYER_NAME.as_bytes());
+        assert_eq!(start % USDZ_ALIGNMENT, 0);
+        assert_eq!(&usdz[start..start + usda.len()], usda.as_bytes());
+
+        // End of central directory points back at one entry
+        let eocd = usdz.len() - 22;
+        assert_eq!(u32_at(&usdz, eocd), 0x0605_4b50);
+        assert_eq!(u16_at(&usdz, eocd + 10), 1);
+        let central = u32_at(&usdz, eocd + 16);
+        assert_eq!(u32_at(&usdz, central), 0x0201_4b50);
+        assert_eq!(u32_at(&usdz, central + 42), 0);
+    }
+
+    #[test]
+    fn test_zip_pads_short_alignment_gaps() {
+        // A name leaving 1-3 bytes to the boundary needs a whole extra block
+        let name = "a".repeat(USDZ_ALIGNMENT - 30 - 2);
+        let zip = to_zip(&[(&name, b"data")]);
+        let extra = u16_at(&zip, 28);
+
+        assert_eq!(extra, 2 + USDZ_ALIGNMENT);
+        assert_eq!((30 + name.len() + extra) % USDZ_ALIGNMENT, 0);
+        assert_eq!(u16_at(&zip, 30 + name.len()), 0x1986);
+    }
+}
@@ -0,0 +1,240 @@
+//! Vector fields over the terrain, such as wind or surface flow.
+//!
+//! A field is given as two grids, its east (+X) and north (+Z) components,
+//! resampled onto the terrain samples like a [`Channel`](super::grid::Channel).
+//! It's shown as lines draped over the surface: an arrow per sample block,
+//! or streamlines traced through the field from seeds spread the same way.
+
+use glam::{DVec2, Vec3Swizzles};
+
+use super::{grid, TerrainData};
+
+/// Fraction of an arrow's length taken by each stroke of its head.
+const HEAD_LENGTH: f64 = 0.3;
+/// Angle between the shaft and each stroke of an arrow head, in radians.
+const HEAD_ANGLE: f64 = 0.45;
+/// Streamline integration step, in cells.
+const STREAMLINE_STEP: f64 = 0.5;
+/// Magnitude, relative to the field's largest, below which streamlines
+/// stop, as the direction becomes meaningless.
+const STREAMLINE_MIN_SPEED: f64 = 1e-3;
+
+/// How a vector field is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VectorStyle {
+    /// One arrow per block of samples, its length following the magnitude
+    #[default]
+    Arrows,
+    /// Lines following the field from evenly spread seeds
+    Streamlines,
+}
+
+/// Vector field overlay configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VectorConfig {
+    /// Whether the overlay is drawn
+    pub enabled: bool,
+    /// Arrows or streamlines
+    pub style: VectorStyle,
+    /// Samples between arrows or streamline seeds along each axis
+    pub spacing: u32,
+    /// Length of the longest arrow, or of every streamline, in multiples
+    /// of the spacing
+    pub scale: f32,
+}
+
+impl Default for VectorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            style: VectorStyle::default(),
+            spacing: 8,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Two-component field with one vector per terrain sample.
+#[derive(Debug, Clone)]
+pub struct VectorField {
+    east: TerrainData,
+    north: TerrainData,
+}
+
+impl VectorField {
+    /// Field with components from the heights of `east` and `north`,
+    /// sampled at `terrain`'s sample positions (see [`grid::resample`]).
+    pub fn resampled(east: &TerrainData, north: &TerrainData, terrain: &TerrainData) -> Self {
+        let onto = |source| {
+            let mut component =
+                TerrainData::from_heights(terrain.width, grid::resample(source, terrain), None);
+            component.origin = terrain.origin;
+            component.cell_size = terrain.cell_size;
+            component
+        };
+        Self {
+            east: onto(east),
+            north: onto(north),
+        }
+    }
+
+    /// Vector at world-space (`x`, `z`), bilinearly interpolated, or `None`
+    /// outside the grid footprint.
+    pub fn at(&self, point: DVec2) -> Option<DVec2> {
+        Some(DVec2::new(
+            self.east.height_at(point.x, point.y)?,
+            self.north.height_at(point.x, point.y)?,
+        ))
+    }
+
+    /// Largest magnitude over the samples.
+    pub fn max_magnitude(&self) -> f64 {
+        self.east
+            .heights()
+            .iter()
+            .zip(self.north.heights())
+            .map(|(&e, &n)| e.hypot(n))
+            .fold(0.0, f64::max)
+    }
+
+    /// World-space (x, z) polylines drawing the field as set by `config`.
+    pub fn lines(&self, config: &VectorConfig) -> Vec<Vec<DVec2>> {
+        match config.style {
+            VectorStyle::Arrows => self.arrows(config),
+            VectorStyle::Streamlines => self.streamlines(config),
+        }
+    }
+
+    /// Centers of the sample blocks `config.spacing` samples wide.
+    fn seeds(&self, config: &VectorConfig) -> impl Iterator<Item = DVec2> + '_ {
+        let spacing = config.spacing.max(1) as usize;
+        let offset = spacing / 2;
+        let grid = &self.east;
+        (offset..grid.height).step_by(spacing).flat_map(move |z| {
+            (offset..grid.width)
+                .step_by(spacing)
+                .map(move |x| grid.world_position(x, z).xz())
+        })
+    }
+
+    /// A shaft and a two-stroke head per seed, scaled so the strongest
+    /// vector spans `config.scale` spacings.
+    fn arrows(&self, config: &VectorConfig) -> Vec<Vec<DVec2>> {
+        let max = self.max_magnitude();
+        if max <= 0.0 {
+            return Vec::new();
+        }
+        let length = self.spacing_length(config) / max;
+        let mut lines = Vec::new();
+        for seed in self.seeds(config) {
+            let Some(vector) = self.at(seed).filter(|v| *v != DVec2::ZERO) else {
+                continue;
+            };
+            let shaft = vector * length;
+            let tail = seed - shaft / 2.0;
+            let tip = seed + shaft / 2.0;
+            let back = -shaft * HEAD_LENGTH;
+            let left = tip + DVec2::from_angle(HEAD_ANGLE).rotate(back);
+            let right = tip + DVec2::from_angle(-HEAD_ANGLE).rotate(back);
+            lines.push(vec![tail, tip]);
+            lines.push(vec![left, tip, right]);
+        }
+        lines
+    }
+
+    /// Lines traced forward from each seed with second-order Runge-Kutta
+    /// steps along the field direction, ending after `config.scale`
+    /// spacings, at the grid edge, or where the field vanishes.
+    fn streamlines(&self, config: &VectorConfig) -> Vec<Vec<DVec2>> {
+        let max = self.max_magnitude();
+        if max <= 0.0 {
+            return Vec::new();
+        }
+        let step = STREAMLINE_STEP * self.east.cell_size;
+        let steps = (self.spacing_length(config) / step).ceil() as usize;
+        let direction = |point: DVec2| {
+            self.at(point)
+                .filter(|v| v.length() > max * STREAMLINE_MIN_SPEED)
+                .map(|v| v.normalize())
+        };
+        self.seeds(config)
+            .filter_map(|seed| {
+                let mut line = vec![seed];
+                let mut point = seed;
+                for _ in 0..steps {
+                    let Some(next) = direction(point)
+                        .and_then(|d| direction(point + d * step / 2.0))
+                        .map(|d| point + d * step)
+                        .filter(|&next| self.at(next).is_some())
+                    else {
+                        break;
+                    };
+                    line.push(next);
+                    point = next;
+                }
+                (line.len() > 1).then_some(line)
+            })
+            .collect()
+    }
+
+    /// World length of `config.scale` spacings.
+    fn spacing_length(&self, config: &VectorConfig) -> f64 {
+        config.spacing.max(1) as f64 * self.east.cell_size * config.scale as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uniform field over a 9x9 grid.
+    fn uniform(east: f64, north: f64) -> VectorField {
+        VectorField::resampled(
+            &TerrainData::new(vec![vec![east; 9]; 9], None),
+            &TerrainData::new(vec![vec![north; 9]; 9], None),
+            &TerrainData::new(vec![vec![0.0; 9]; 9], None),
+        )
+    }
+
+    #[test]
+    fn test_arrows_follow_field() {
+        let field = uniform(2.0, 0.0);
+        let config = VectorConfig {
+            spacing: 4,
+            ..VectorConfig::default()
+        };
+        let lines = field.lines(&config);
+
+        // Seeds at 2 and 6 on each axis, a shaft and a head each
+        assert_eq!(lines.len(), 4 * 2);
+        // The strongest vector spans one spacing, centered on its seed
+        assert_eq!(lines[0], vec![DVec2::new(0.0, 2.0), DVec2::new(4.0, 2.0)]);
+        let head = &lines[1];
+        assert_eq!(head[1], DVec2::new(4.0, 2.0));
+        assert!(head[0].x < 4.0 && head[0].y < 2.0);
+        assert!(head[2].x < 4.0 && head[2].y > 2.0);
+    }
+
+    #[test]
+    fn test_streamlines_stop_at_edge() {
+        let field = uniform(0.0, 1.0);
+        let config = VectorConfig {
+            style: VectorStyle::Streamlines,
+            spacing: 4,
+            scale: 10.0,
+            ..VectorConfig::default()
+        };
+        let lines = field.lines(&config);
+
+        assert_eq!(lines.len(), 4);
+        for line in &lines {
+            // Straight north from the seed to the last row, in half cells
+            assert!(line.iter().all(|p| p.x == line[0].x));
+            assert_eq!(line.last().unwrap().y, 8.0);
+            assert_eq!(line.len() as f64, (8.0 - line[0].y) * 2.0 + 1.0);
+        }
+
+        // A still field draws nothing
+        assert!(uniform(0.0, 0.0).lines(&config).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Line of sight between two points over the terrain.
+//!
+//! A sight line runs straight from an observer's antenna to a target's,
+//! each standing some height above the surface, and is blocked where it
+//! first meets the terrain, found with [`Heightfield::raycast_within`].
+//! Grazing the surface doesn't block it. The surface is taken as flat, without earth
+//! curvature or refraction, so long radio paths come out slightly
+//! optimistic.
+//!
+//! Scaling heights scales the sight line with the terrain, so
+//! intervisibility doesn't depend on the viewer's height exaggeration.
+
+use glam::{DVec2, DVec3};
+
+use super::{Heightfield, TerrainData};
+
+/// Antenna heights of a line-of-sight check.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SightConfig {
+    /// Height of the observer's antenna above the surface
+    pub observer_height: f32,
+    /// Height of the target's antenna above the surface
+    pub target_height: f32,
+}
+
+impl Default for SightConfig {
+    fn default() -> Self {
+        Self {
+            observer_height: 2.0,
+            target_height: 2.0,
+        }
+    }
+}
+
+/// Result of a line-of-sight check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineOfSight {
+    /// World-space observer antenna, with unscaled height
+    pub observer: DVec3,
+    /// World-space target antenna, with unscaled height
+    pub target: DVec3,
+    /// Antenna heights the check was made with
+    pub config: SightConfig,
+    /// Where the sight line first meets the terrain, walking from the
+    /// observer
+    pub obstruction: Option<DVec3>,
+    /// Lowest observer antenna height that clears the terrain, with the
+    /// target's antenna unchanged
+    pub required_height: f64,
+}
+
+impl LineOfSight {
+    /// Check the sight line from world-space (x, z) point `from` to `to`.
+    ///
+    /// Returns `None` if either point lies outside the grid.
+    pub fn compute(
+        terrain: &TerrainData,
+        from: DVec2,
+        to: DVec2,
+        config: &SightConfig,
+    ) -> Option<Self> {
+        let ground = terrain.height_at(from.x, from.y)?;
+        let observer = DVec3::new(from.x, ground + config.observer_height as f64, from.y);
+        let target = DVec3::new(
+            to.x,
+            terrain.height_at(to.x, to.y)? + config.target_height as f64,
+            to.y,
+        );
+
+        let surface = Heightfield::new(terrain, 1.0);
+        let obstruction = first_obstruction(&surface, observer, target);
+
+        // Samples every half cell give a lower bound on the height needed
+        let steps = ((to - from).length() / (terrain.cell_size * 0.5)).ceil() as usize;
+        // Observer elevation needed to see over every sample so far
+        let mut clearing = ground;
+        for i in 1..steps {
+            let t = i as f64 / steps as f64;
+            let point = from.lerp(to, t);
+            let Some(height) = terrain.height_at(point.x, point.y) else {
+                continue;
+            };
+            // The line through the target and this sample, extended back
+            clearing = clearing.max((height - target.y * t) / (1.0 - t));
+        }
+        // Then the lowest that clears the surface between the samples too,
+        // by doubling the margin until it clears and bisecting back down
+        let blocked = |y: f64| {
+            let observer = DVec3::new(from.x, y, from.y);
+            first_obstruction(&surface, observer, target).is_some()
+        };
+        if blocked(clearing) {
+            let scale = terrain.cell_size.max(1e-9);
+            let (mut low, mut margin) = (clearing, scale);
+            while blocked(clearing + margin) {
+                low = clearing + margin;
+                margin *= 2.0;
+            }
+            let mut high = clearing + margin;
+            for _ in 0..100 {
+                let middle = (low + high) * 0.5;
+                if middle <= low || middle >= high {
+                    break;
+                }
+                if blocked(middle) {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            clearing = high;
+        }
+
+        Some(Self {
+            observer,
+            target,
+            config: *config,
+            obstruction,
+            required_height: clearing - ground,
+        })
+    }
+
+    /// Whether the observer and target see each other.
+    pub fn is_visible(&self) -> bool {
+        self.obstruction.is_none()
+    }
+}
+
+/// Where the sight line from `observer` first meets the surface before
+/// reaching `target`.
+fn first_obstruction(surface: &Heightfield, observer: DVec3, target: DVec3) -> Option<DVec3> {
+    // Stopping just short of the target, which may stand on the surface
+    let reach = observer.distance(target) * (1.0 - 1e-9);
+    surface
+        .raycast_within(observer, target - observer, reach)
+        .map(|hit| hit.position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flat ground at 0 with a wall of `wall` across x = 4.
+    fn walled(wall: f64) -> TerrainData {
+        let row: Vec<f64> = (0..9).map(|x| if x == 4 { wall } else { 0.0 }).collect();
+        TerrainData::new(vec![row; 3], None)
+    }
+
+    #[test]
+    fn test_wall_blocks_low_antennas() {
+        let terrain = walled(5.0);
+        let los = LineOfSight::compute(
+            &terrain,
+            DVec2::new(0.0, 1.0),
+            DVec2::new(8.0, 1.0),
+            &SightConfig::default(),
+        )
+        .unwrap();
+
+        assert!(!los.is_visible());
+        assert_eq!(los.observer, DVec3::new(0.0, 2.0, 1.0));
+        // The wall's slope rises through 2 at x = 3.4
+        let obstruction = los.obstruction.unwrap();
+        assert!((obstruction - DVec3::new(3.4, 2.0, 1.0)).length() < 1e-9);
+        // Clearing the wall's top halfway to a target at 2 takes 8
+        assert!((los.required_height - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tall_antenna_sees_over_wall() {
+        let terrain = walled(5.0);
+        let config = SightConfig {
+            observer_height: 8.5,
+            target_height: 2.0,
+        };
+        let los = LineOfSight::compute(
+            &terrain,
+            DVec2::new(0.0, 1.0),
+            DVec2::new(8.0, 1.0),
+            &config,
+        )
+        .unwrap();
+
+        assert!(los.is_visible());
+        assert!((los.required_height - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_open_ground_needs_no_antenna() {
+        let terrain = walled(0.0);
+        let config = SightConfig {
+            observer_height: 0.0,
+            target_height: 0.0,
+        };
+        let los = LineOfSight::compute(
+            &terrain,
+            DVec2::new(1.0, 0.0),
+            DVec2::new(7.0, 2.0),
+            &config,
+        )
+        .unwrap();
+
+        assert!(los.is_visible());
+        assert_eq!(los.required_height, 0.0);
+        assert!(
+            LineOfSight::compute(&terrain, DVec2::ZERO, DVec2::new(9.0, 0.0), &config).is_none()
+        );
+    }
+}
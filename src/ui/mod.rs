@@ -1,24 +1,252 @@
 //! User interface using egui.
 //!
-//! Provides camera info panel, render mode selection, and lighting controls.
+//! Provides camera info panel, render mode selection, and lighting controls,
+//! plus an overlay for shader compilation errors, a camera inspector
+//! window for debugging, a timeline bar for the animated views and the
+//! draped image's alignment window.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use egui::Context;
+use glam::{DVec2, Mat4, Vec2, Vec3};
 
-use crate::renderer::camera::Camera;
-use crate::renderer::{ContourConfig, LightingConfig, RenderMode};
+use crate::input::{key_from_name, Action, InputConfig};
+use crate::renderer::bookmarks::Bookmark;
+use crate::renderer::camera::{Camera, CameraMode};
+use crate::renderer::load_failure::LoadFailure;
+use crate::renderer::pacing::Spread;
+use crate::renderer::recovery::Edits;
+use crate::renderer::workers::{JobId, JobStatus};
 use crate::renderer::Projection;
-use crate::terrain::{ColorScheme, GradientConfig};
+use crate::renderer::{
+    BlendMode, BloomConfig, ColorVision, CompareConfig, DepthMode, EarthModel, LightingConfig,
+    OverviewView, PresentMode, RenderMode, RenderSettings, RenderStrategy, TimelineConfig, Tool,
+};
+use crate::terrain::analysis::{TerrainStats, SLOPE_BIN_DEGREES};
+use crate::terrain::annotations::Annotation;
+use crate::terrain::colors::{height_to_color, height_to_color_custom};
+use crate::terrain::drape::{DrapeAlignment, DrapeImage};
+use crate::terrain::grid::Channel;
+use crate::terrain::hydrology::Catchment;
+use crate::terrain::picking::Pick;
+use crate::terrain::points::PointSet;
+use crate::terrain::processing::{Processing, DOWNSAMPLE_FACTORS};
+use crate::terrain::profile::Profile;
+use crate::terrain::relief::ReferenceSurface;
+use crate::terrain::selection::Selection;
+use crate::terrain::uncertainty::UncertaintyStyle;
+use crate::terrain::units::{UnitFormat, Units};
+use crate::terrain::vectors::VectorStyle;
+use crate::terrain::visibility::LineOfSight;
+use crate::terrain::{ColorMode, ColorScheme, GradientConfig, SlopeBands};
+use crate::update::{Release, CURRENT_VERSION};
+
+/// Read-only per-frame state displayed by the UI.
+pub struct FrameInfo<'a> {
+    /// Frames per second
+    pub fps: f32,
+    /// Time from input events to presenting the frame that shows them
+    pub input_latency: Option<Spread>,
+    /// Time spent waiting for the surface to hand out a texture
+    pub acquire_wait: Option<Spread>,
+    /// Viewport width/height aspect ratio
+    pub aspect: f32,
+    /// Last shader compilation error
+    pub shader_error: Option<&'a str>,
+    /// Error of the last raster calculator statement
+    pub algebra_error: Option<&'a str>,
+    /// Corners of the captured debug frustum
+    pub captured_frustum: Option<&'a [Vec3; 8]>,
+    /// Mesh chunks drawn and total, when occlusion culling
+    pub chunks: Option<(usize, usize)>,
+    /// Elevation and slope distributions, once computed
+    pub terrain_stats: Option<&'a TerrainStats>,
+    /// Whether the distributions are being computed
+    pub computing_analysis: bool,
+    /// Analysis, bakes and exports running in the background
+    pub jobs: &'a [JobStatus],
+    /// Sample counts in equal ranges from the lowest height to the highest
+    pub height_histogram: &'a [u32],
+    /// Whether a reference grid is loaded for relative relief
+    pub has_reference: bool,
+    /// Name the main terrain is listed under among the layers
+    pub terrain_name: &'a str,
+    /// Samples the last seam stitch changed
+    pub stitched_samples: usize,
+    /// Columns and rows of the main terrain's grid
+    pub grid_size: (usize, usize),
+    /// Samples per grid cell of the full-resolution window drawn near the
+    /// camera, while one is
+    pub detail: Option<usize>,
+    /// Breaklines constraining the TIN strategy
+    pub breaklines: usize,
+    /// Loaded point features
+    pub points: Option<&'a PointSet>,
+    /// Screen positions of the point markers in front of the camera, in
+    /// physical pixels, with the points' indices
+    pub point_markers: Vec<(Vec2, usize)>,
+    /// Columns and rows of the grid as loaded, while the terrain is
+    /// cropped, smoothed or downsampled
+    pub original_size: Option<(usize, usize)>,
+    /// Whether the terrain file gives per-point colors
+    pub has_file_colors: bool,
+    /// Multisample counts the adapter supports
+    pub msaa_counts: &'a [u32],
+    /// Present modes the window surface supports; none offscreen
+    pub present_modes: &'a [PresentMode],
+    /// Whether the surface can be copied for passes over the finished
+    /// scene, such as the color vision simulation
+    pub can_copy_scene: bool,
+    /// Value grids besides elevation that the terrain can be colored by
+    pub channels: &'a [Channel],
+    /// Lowest and highest height of the terrain
+    pub height_range: Option<(f64, f64)>,
+    /// Units measurements are shown in
+    pub units: UnitFormat,
+    /// Whether a vector field is loaded
+    pub has_vector_field: bool,
+    /// Whether an image is draped over the terrain
+    pub has_drape: bool,
+    /// The draped image shrunk for the alignment window
+    pub drape_preview: Option<&'a DrapeImage>,
+    /// Control points aligning the draped image
+    pub drape_alignment: &'a DrapeAlignment,
+    /// Whether an environment map is loaded
+    pub has_environment: bool,
+    /// Whether a snapshot was taken for the swipe comparison
+    pub has_compare_snapshot: bool,
+    /// Number of keyframes in the camera path
+    pub camera_keyframes: usize,
+    /// Named camera views of the terrain
+    pub bookmarks: &'a [Bookmark],
+    /// Timeline time reached by the camera path recording, while recording
+    pub recording: Option<f32>,
+    /// Last catchment delineated with the catchment tool
+    pub catchment: Option<&'a Catchment>,
+    /// Observer of a line-of-sight check waiting for its target
+    pub sight_start: Option<DVec2>,
+    /// Last line-of-sight check
+    pub line_of_sight: Option<&'a LineOfSight>,
+    /// Start of a profile cut waiting for its end
+    pub profile_start: Option<DVec2>,
+    /// Heights along the last profile cut
+    pub profile: Option<&'a Profile>,
+    /// Last point picked with the inspect tool
+    pub inspected: Option<&'a Pick>,
+    /// Region selected with the selection tools
+    pub selection: Option<&'a Selection>,
+    /// Shapes and notes drawn with the annotation tools
+    pub annotations: &'a [Annotation],
+    /// Shape being drawn with the annotation tools
+    pub sketch: Option<&'a Annotation>,
+    /// Screen positions of note pins in front of the camera, in physical
+    /// pixels, with the note text
+    pub notes: Vec<(Vec2, &'a str)>,
+    /// Lowest and highest daily irradiation in kWh/m², while the irradiance
+    /// overlay is shown
+    pub irradiance_range: Option<(f64, f64)>,
+    /// Newer release found by the update check
+    pub update: Option<&'a Release>,
+    /// Share of the terrain below the water plane and its area
+    pub flooded: Option<(f64, f64)>,
+    /// File the input settings are saved to
+    pub config_file: Option<&'a Path>,
+    /// Edits left by a crashed session, waiting to be restored or discarded
+    pub recovered: Option<&'a Edits>,
+    /// Terrain file that failed to load, until loaded again or dismissed
+    pub load_failure: Option<&'a LoadFailure>,
+    /// Share of the pipeline variants built ahead of use, while building
+    pub warmup: Option<f32>,
+}
+
+/// Triangle budget offered when simplification is first turned on.
+const DEFAULT_MESH_TRIANGLES: usize = 100_000;
 
 /// UI state and rendering.
 pub struct Ui {
     /// Whether the side panel is visible
     pub panel_visible: bool,
+    /// Whether the camera inspector window is visible
+    pub inspector_visible: bool,
+    /// Whether the terrain analysis window is visible
+    pub analysis_visible: bool,
+    /// Whether the timeline bar is visible
+    pub timeline_visible: bool,
+    /// Output path for shadow-hours exports
+    pub shadow_hours_path: String,
+    /// Output path for turntable exports
+    pub turntable_path: String,
+    /// Output path for catchment exports
+    pub catchment_path: String,
+    /// Text of notes placed with the note tool
+    pub note_text: String,
+    /// Output path for annotation exports
+    pub annotations_path: String,
+    /// Output path for terrains saved with painted colors
+    pub fdf_path: String,
+    /// File camera paths are exported to and imported from
+    pub camera_path_file: String,
+    /// Name new bookmarks are saved under
+    pub bookmark_name: String,
+    /// Output path for mesh exports
+    pub mesh_path: String,
+    /// Simplify mesh exports to at most this many triangles
+    pub mesh_triangles: Option<usize>,
+    /// Output path for color map exports
+    pub color_map_path: String,
+    /// Terrain file to open as a layer
+    pub terrain_path: String,
+    /// Path of the breaklines file to load
+    pub breaklines_path: String,
+    /// Path of the point features file to load
+    pub points_path: String,
+    /// First and last grid column to crop to, clamped to the grid
+    pub crop_columns: [usize; 2],
+    /// First and last grid row to crop to, clamped to the grid
+    pub crop_rows: [usize; 2],
+    /// Standard deviation of the smoothing blur, in samples
+    pub smooth_sigma: f64,
+    /// Map algebra statement of the raster calculator
+    pub algebra: String,
+    /// Action waiting for a key press to be bound to it
+    pub rebinding: Option<Action>,
+    /// UI scale picked on the slider while it's dragged, applied on
+    /// release so the slider doesn't move under the cursor
+    pub ui_scale_drag: Option<f32>,
+    /// Draped image shown in the alignment window, uploaded on first use
+    /// and cleared when another image is draped
+    pub drape_texture: Option<egui::TextureHandle>,
 }
 
 impl Ui {
     pub fn new() -> Self {
         Self {
             panel_visible: true,
+            inspector_visible: false,
+            analysis_visible: false,
+            timeline_visible: false,
+            shadow_hours_path: "shadow_hours.asc".to_string(),
+            turntable_path: "turntable.gif".to_string(),
+            catchment_path: "catchment.geojson".to_string(),
+            note_text: String::new(),
+            annotations_path: "annotations.geojson".to_string(),
+            fdf_path: "painted.fdf".to_string(),
+            camera_path_file: "camera_path.json".to_string(),
+            bookmark_name: String::new(),
+            mesh_path: "terrain.glb".to_string(),
+            mesh_triangles: None,
+            color_map_path: "colors.tif".to_string(),
+            terrain_path: String::new(),
+            breaklines_path: String::new(),
+            points_path: String::new(),
+            crop_columns: [0, usize::MAX],
+            crop_rows: [0, usize::MAX],
+            smooth_sigma: 1.0,
+            algebra: String::new(),
+            rebinding: None,
+            ui_scale_drag: None,
+            drape_texture: None,
         }
     }
 
@@ -27,15 +255,132 @@ impl Ui {
         &mut self,
         ctx: &Context,
         camera: &mut Camera,
-        render_mode: &mut RenderMode,
-        color_scheme: &mut ColorScheme,
-        gradient: &mut GradientConfig,
-        lighting: &mut LightingConfig,
-        contour: &mut ContourConfig,
-        height_scale: &mut f32,
-        fps: f32,
+        settings: &mut RenderSettings,
+        input: &mut InputConfig,
+        info: &FrameInfo,
     ) -> UiResponse {
         let mut response = UiResponse::default();
+        let RenderSettings {
+            render_mode,
+            strategy,
+            tin_tolerance,
+            occlusion_culling,
+            indirect_draw,
+            depth_mode,
+            msaa,
+            earth_model,
+            color_mode,
+            index_radius,
+            color_scheme,
+            gradient,
+            slope_bands,
+            gamma_correct,
+            color_vision,
+            bloom,
+            background,
+            sky,
+            fog,
+            environment,
+            ground_grid,
+            axes_gizmo,
+            line_brightness,
+            file_colors,
+            lighting,
+            shadows,
+            sun,
+            texture_blend,
+            surface_opacity,
+            surface_blend,
+            water,
+            overview,
+            motion,
+            compare,
+            timeline,
+            turntable,
+            auto_rotate,
+            contour,
+            height_scale,
+            edge_density,
+            lod,
+            ridgelines,
+            vectors,
+            points,
+            uncertainty,
+            ambient_occlusion,
+            relief,
+            denoise,
+            tool,
+            sight,
+            brush,
+            units,
+            shader_params,
+            ui_scale,
+            pacing,
+            layers,
+            stitch,
+        } = settings;
+
+        if let Some(error) = info.shader_error {
+            shader_error_overlay(ctx, error);
+        }
+
+        note_labels(ctx, &info.notes);
+
+        if let Some(set) = info.points {
+            point_tooltip(ctx, set, &info.point_markers);
+        }
+
+        if let Some(edits) = info.recovered {
+            recovery_prompt(ctx, edits, &mut response);
+        }
+
+        if let Some(failure) = info.load_failure {
+            load_failure_modal(ctx, failure, &mut response);
+        }
+
+        if let Some(fraction) = info.warmup {
+            warmup_splash(ctx, fraction);
+        }
+
+        if let Some(release) = info.update {
+            update_notice(ctx, release, &mut response);
+        }
+
+        if !info.jobs.is_empty() {
+            jobs_window(ctx, info.jobs, &mut response);
+        }
+
+        if compare.enabled && info.has_compare_snapshot {
+            swipe_divider(ctx, compare);
+        }
+
+        if self.inspector_visible {
+            camera_inspector(
+                ctx,
+                &mut self.inspector_visible,
+                camera,
+                info,
+                &mut response,
+            );
+        }
+
+        if self.analysis_visible {
+            analysis_window(ctx, &mut self.analysis_visible, info, &mut response);
+        }
+
+        if let Some(profile) = info.profile {
+            profile_window(ctx, profile, info.units, &mut response);
+        }
+
+        if let Some(preview) = info.drape_preview.filter(|_| *tool == Tool::AlignImage) {
+            let texture = self.drape_texture.get_or_insert_with(|| {
+                let size = [preview.width as usize, preview.height as usize];
+                let image =
+                    egui::ColorImage::from_rgba_unmultiplied(size, preview.rgba.as_flattened());
+                ctx.load_texture("drape_preview", image, egui::TextureOptions::LINEAR)
+            });
+            align_window(ctx, texture, info.drape_alignment, &mut response);
+        }
 
         // Toggle panel with Tab key
         if ctx.input(|i| i.key_pressed(egui::Key::Tab)) {
@@ -50,9 +395,192 @@ impl Ui {
                     ui.separator();
 
                     // Performance
-                    ui.label(format!("FPS: {:.1}", fps));
+                    ui.label(format!("FPS: {:.1}", info.fps));
+                    if let Some(latency) = info.input_latency {
+                        ui.label(format!(
+                            "Input to Present: {:.1} ms (max {:.1})",
+                            latency.mean.as_secs_f32() * 1000.0,
+                            latency.max.as_secs_f32() * 1000.0
+                        ))
+                        .on_hover_text(
+                            "From an input event to presenting the first frame drawn after \
+                             it, over the last 120 frames with input. The display adds its \
+                             own delay on top",
+                        );
+                    }
+                    if let Some(wait) = info.acquire_wait {
+                        ui.label(format!(
+                            "Acquire Wait: {:.1} ms (max {:.1})",
+                            wait.mean.as_secs_f32() * 1000.0,
+                            wait.max.as_secs_f32() * 1000.0
+                        ))
+                        .on_hover_text(
+                            "Time each frame waits for the window's next image, over the last \
+                             120 frames. Long waits mean the present mode or compositor is \
+                             holding frames back",
+                        );
+                    }
+                    if let Some((drawn, total)) = info.chunks {
+                        ui.label(format!("Chunks: {}/{}", drawn, total));
+                    }
                     ui.separator();
 
+                    // Other terrains drawn next to the main one
+                    ui.collapsing("Terrains", |ui| {
+                        ui.label(format!("Main: {}", info.terrain_name));
+                        for (i, layer) in layers.iter_mut().enumerate() {
+                            ui.push_id(i, |ui| {
+                                ui.horizontal(|ui| {
+                                    let name = layer.name.clone();
+                                    ui.checkbox(&mut layer.visible, name);
+                                    if ui
+                                        .small_button("Make Main")
+                                        .on_hover_text("Analyze, edit and export this terrain")
+                                        .clicked()
+                                    {
+                                        response.make_main_terrain = Some(i);
+                                    }
+                                    if ui.small_button("Remove").clicked() {
+                                        response.remove_terrain = Some(i);
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Offset:");
+                                    for (value, axis) in layer.offset.iter_mut().zip(["X", "Y", "Z"])
+                                    {
+                                        ui.add(egui::DragValue::new(value).prefix(format!("{axis} ")));
+                                    }
+                                })
+                                .response
+                                .on_hover_text("East, up and north, in terrain units");
+                                ui.horizontal(|ui| {
+                                    ui.label("Scale:");
+                                    ui.add(
+                                        egui::DragValue::new(&mut layer.scale)
+                                            .speed(0.01)
+                                            .range(0.01..=100.0),
+                                    );
+                                });
+                            });
+                        }
+                        if !layers.is_empty() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut stitch.enabled, "Stitch Seams").on_hover_text(
+                                    "Average border samples the terrains share, closing cracks \
+                                     between tiles",
+                                );
+                                ui.add_enabled(
+                                    stitch.enabled,
+                                    egui::DragValue::new(&mut stitch.tolerance)
+                                        .speed(0.1)
+                                        .range(0.0..=f32::MAX)
+                                        .prefix("Tolerance "),
+                                )
+                                .on_hover_text(
+                                    "Largest height difference averaged away, in terrain units",
+                                );
+                            });
+                            if stitch.enabled {
+                                ui.label(format!("Stitched {} samples", info.stitched_samples));
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("File:");
+                            ui.text_edit_singleline(&mut self.terrain_path);
+                        });
+                        let path = self.terrain_path.trim();
+                        if ui
+                            .add_enabled(!path.is_empty(), egui::Button::new("Open Terrain"))
+                            .on_hover_text("Load another terrain to overlay or set beside this one")
+                            .clicked()
+                        {
+                            response.open_terrain = Some(PathBuf::from(path));
+                        }
+                    });
+
+                    // Crop to a window of the grid
+                    ui.collapsing("Crop", |ui| {
+                        let (width, height) = info.grid_size;
+                        let range = |ui: &mut egui::Ui, label, [first, last]: &mut [usize; 2], len: usize| {
+                            let indices = 0..=len.saturating_sub(1);
+                            ui.horizontal(|ui| {
+                                ui.label(label);
+                                ui.add(egui::DragValue::new(first).range(indices.clone()));
+                                ui.label("to");
+                                ui.add(egui::DragValue::new(last).range(indices));
+                            })
+                            .response
+                        };
+                        range(ui, "Columns:", &mut self.crop_columns, width)
+                            .on_hover_text("Column 0 is the westernmost");
+                        range(ui, "Rows:", &mut self.crop_rows, height)
+                            .on_hover_text("Row 0 is the southernmost");
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Crop")
+                                .on_hover_text("Keep only these columns and rows, and frame them")
+                                .clicked()
+                            {
+                                let ([c0, c1], [r0, r1]) = (self.crop_columns, self.crop_rows);
+                                response.crop = Some((c0..c1.saturating_add(1), r0..r1.saturating_add(1)));
+                            }
+                            if let Some((width, height)) = info.original_size {
+                                if ui
+                                    .button("Restore Full Grid")
+                                    .on_hover_text(format!("Back to all {width}x{height} samples"))
+                                    .clicked()
+                                {
+                                    response.restore_original = true;
+                                }
+                            }
+                        });
+                    });
+
+                    // Smoothing and downsampling of the whole grid
+                    ui.collapsing("Processing", |ui| {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Smooth")
+                                .on_hover_text("Gaussian blur of the heights, for analysis and exports too")
+                                .clicked()
+                            {
+                                response.process = Some(Processing::Smooth(self.smooth_sigma));
+                            }
+                            ui.add(
+                                egui::Slider::new(&mut self.smooth_sigma, 0.5..=8.0)
+                                    .text("σ")
+                                    .suffix(" samples"),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Downsample:");
+                            for factor in DOWNSAMPLE_FACTORS {
+                                if ui
+                                    .button(format!("{factor}x"))
+                                    .on_hover_text(format!("Keep every {factor}th sample along each side"))
+                                    .clicked()
+                                {
+                                    response.process = Some(Processing::Downsample(factor));
+                                }
+                            }
+                        });
+                        let (width, height) = info.grid_size;
+                        ui.label(format!("Grid: {width}x{height} samples"));
+                        if let Some(subdivisions) = info.detail {
+                            ui.label(format!("Near the camera: {subdivisions}x finer"))
+                                .on_hover_text("Read from the mapped grid at full resolution");
+                        }
+                        if let Some((width, height)) = info.original_size {
+                            if ui
+                                .button("Restore Original")
+                                .on_hover_text(format!("Undo crops and processing, back to all {width}x{height} samples"))
+                                .clicked()
+                            {
+                                response.restore_original = true;
+                            }
+                        }
+                    });
+
                     // Rendering section
                     ui.collapsing("Rendering", |ui| {
                         ui.horizontal(|ui| {
@@ -74,6 +602,328 @@ impl Ui {
                                 });
                         });
 
+                        ui.horizontal(|ui| {
+                            ui.label("Geometry:");
+                            egui::ComboBox::from_id_salt("strategy")
+                                .selected_text(match strategy {
+                                    RenderStrategy::Mesh => "Mesh",
+                                    RenderStrategy::Clipmap => "Clipmap",
+                                    RenderStrategy::Tin => "TIN",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(strategy, RenderStrategy::Mesh, "Mesh");
+                                    ui.selectable_value(
+                                        strategy,
+                                        RenderStrategy::Clipmap,
+                                        "Clipmap",
+                                    )
+                                    .on_hover_text(
+                                        "Camera-centered levels of detail for huge terrains",
+                                    );
+                                    ui.selectable_value(strategy, RenderStrategy::Tin, "TIN")
+                                        .on_hover_text(
+                                            "Few triangles on flat ground, more on ridges; exports too",
+                                        );
+                                });
+                        });
+
+                        if *strategy == RenderStrategy::Tin {
+                            ui.horizontal(|ui| {
+                                ui.label("Tolerance:");
+                                ui.add(
+                                    egui::DragValue::new(tin_tolerance)
+                                        .speed(0.05)
+                                        .range(0.0..=f32::MAX),
+                                )
+                                .on_hover_text("Largest height error allowed, in terrain units");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Breaklines:");
+                                ui.text_edit_singleline(&mut self.breaklines_path);
+                            });
+                            ui.horizontal(|ui| {
+                                let path = self.breaklines_path.trim();
+                                if ui
+                                    .add_enabled(!path.is_empty(), egui::Button::new("Load"))
+                                    .on_hover_text(
+                                        "Keep edges along roads, ridges and walls from a GeoJSON or DXF file",
+                                    )
+                                    .clicked()
+                                {
+                                    response.load_breaklines = Some(PathBuf::from(path));
+                                }
+                                if info.breaklines > 0 {
+                                    ui.label(format!("{} lines", info.breaklines));
+                                    if ui.button("Clear").clicked() {
+                                        response.clear_breaklines = true;
+                                    }
+                                }
+                            });
+                        }
+
+                        if matches!(render_mode, RenderMode::Wireframe | RenderMode::Both) {
+                            ui.horizontal(|ui| {
+                                ui.label("Edge density:");
+                                ui.add(egui::Slider::new(edge_density, 0.05..=1.0))
+                                    .on_hover_text(
+                                        "Keep every Nth grid line plus ridges and valleys",
+                                    );
+                            });
+                        }
+
+                        ui.checkbox(ridgelines, "Ridgelines")
+                            .on_hover_text("Overlay ridge (orange) and valley (blue) lines");
+
+                        ui.checkbox(ambient_occlusion, "Ambient Occlusion")
+                            .on_hover_text("Darken valleys and hollows by baked sky visibility");
+
+                        if *strategy == RenderStrategy::Mesh {
+                            ui.checkbox(&mut lod.enabled, "Level of Detail")
+                                .on_hover_text("Draw distant chunks with fewer triangles and lines");
+                            if lod.enabled {
+                                ui.horizontal(|ui| {
+                                    ui.label("Full detail:");
+                                    ui.add(
+                                        egui::Slider::new(&mut lod.distance, 0.5..=16.0)
+                                            .logarithmic(true)
+                                            .suffix(" chunks"),
+                                    )
+                                    .on_hover_text("Distance drawn in full, in chunk sizes");
+                                });
+                            }
+                        }
+
+                        ui.checkbox(occlusion_culling, "Occlusion Culling")
+                            .on_hover_text("Skip terrain chunks hidden behind other terrain");
+                        ui.checkbox(indirect_draw, "GPU Draw List").on_hover_text(
+                            "Cull chunks on the GPU and draw them with indirect multi-draws",
+                        );
+
+                        ui.horizontal(|ui| {
+                            ui.label("Depth:");
+                            egui::ComboBox::from_id_salt("depth_mode")
+                                .selected_text(match depth_mode {
+                                    DepthMode::Standard => "Standard",
+                                    DepthMode::Reversed => "Reversed-Z",
+                                    DepthMode::Logarithmic => "Logarithmic",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        depth_mode,
+                                        DepthMode::Standard,
+                                        "Standard",
+                                    );
+                                    ui.selectable_value(
+                                        depth_mode,
+                                        DepthMode::Reversed,
+                                        "Reversed-Z",
+                                    );
+                                    ui.selectable_value(
+                                        depth_mode,
+                                        DepthMode::Logarithmic,
+                                        "Logarithmic",
+                                    );
+                                });
+                        });
+
+                        if info.msaa_counts.len() > 1 {
+                            ui.horizontal(|ui| {
+                                ui.label("Antialiasing:");
+                                let label = |count: u32| match count {
+                                    1 => "Off".to_string(),
+                                    _ => format!("{count}x MSAA"),
+                                };
+                                egui::ComboBox::from_id_salt("msaa")
+                                    .selected_text(label(*msaa))
+                                    .show_ui(ui, |ui| {
+                                        for &count in info.msaa_counts {
+                                            ui.selectable_value(msaa, count, label(count));
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text("Smooth the edges of the surface and lines");
+                            });
+                        }
+
+                        if !info.present_modes.is_empty() {
+                            ui.horizontal(|ui| {
+                                ui.label("Present:");
+                                egui::ComboBox::from_id_salt("present_mode")
+                                    .selected_text(pacing.present_mode.label())
+                                    .show_ui(ui, |ui| {
+                                        for &mode in info.present_modes {
+                                            ui.selectable_value(
+                                                &mut pacing.present_mode,
+                                                mode,
+                                                mode.label(),
+                                            );
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text("VSync waits for the display; the others don't");
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            let mut capped = pacing.max_fps.is_some();
+                            if ui.checkbox(&mut capped, "Max FPS").changed() {
+                                pacing.max_fps = capped.then_some(60);
+                            }
+                            if let Some(fps) = &mut pacing.max_fps {
+                                ui.add(egui::DragValue::new(fps).range(1..=480));
+                            }
+                        });
+                        ui.checkbox(&mut pacing.power_saving, "Power Saving")
+                            .on_hover_text("Only draw after input or while something moves");
+
+                        ui.horizontal(|ui| {
+                            ui.label("Earth:");
+                            egui::ComboBox::from_id_salt("earth_model")
+                                .selected_text(match earth_model {
+                                    EarthModel::Flat => "Flat",
+                                    EarthModel::Curved => "Curved",
+                                    EarthModel::Globe => "Globe",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(earth_model, EarthModel::Flat, "Flat");
+                                    ui.selectable_value(earth_model, EarthModel::Curved, "Curved")
+                                        .on_hover_text("Drop distant terrain by earth curvature");
+                                    ui.selectable_value(earth_model, EarthModel::Globe, "Globe")
+                                        .on_hover_text(
+                                            "Map a longitude/latitude grid onto a sphere",
+                                        );
+                                });
+                        });
+
+                        if info.has_file_colors {
+                            ui.checkbox(file_colors, "File Colors")
+                                .on_hover_text("Use the colors given in the terrain file");
+                        }
+
+                        if info.has_drape && matches!(render_mode, RenderMode::Solid | RenderMode::Both) {
+                            ui.horizontal(|ui| {
+                                ui.label("Texture:");
+                                ui.add(egui::Slider::new(texture_blend, 0.0..=1.0))
+                                    .on_hover_text("Blend from the height colors to the draped image");
+                            });
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label("Color By:");
+                            egui::ComboBox::from_id_salt("color_mode")
+                                .selected_text(match color_mode {
+                                    ColorMode::Height => "Height",
+                                    ColorMode::PlanCurvature => "Plan Curvature",
+                                    ColorMode::ProfileCurvature => "Profile Curvature",
+                                    ColorMode::Ruggedness => "Ruggedness (TRI)",
+                                    ColorMode::Position => "Position (TPI)",
+                                    ColorMode::Roughness => "Roughness",
+                                    ColorMode::SlopeBands => "Slope Bands",
+                                    ColorMode::Channel(index) => info
+                                        .channels
+                                        .get(*index)
+                                        .map_or("Channel", |channel| channel.name.as_str()),
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(color_mode, ColorMode::Height, "Height");
+                                    ui.selectable_value(
+                                        color_mode,
+                                        ColorMode::PlanCurvature,
+                                        "Plan Curvature",
+                                    )
+                                    .on_hover_text(
+                                        "Contour bending: red spurs and ridges, blue channels",
+                                    );
+                                    ui.selectable_value(
+                                        color_mode,
+                                        ColorMode::ProfileCurvature,
+                                        "Profile Curvature",
+                                    )
+                                    .on_hover_text(
+                                        "Slope bending: red convex brinks, blue concave footslopes",
+                                    );
+                                    ui.selectable_value(
+                                        color_mode,
+                                        ColorMode::Ruggedness,
+                                        "Ruggedness (TRI)",
+                                    )
+                                    .on_hover_text("Height differences to the neighbors");
+                                    ui.selectable_value(
+                                        color_mode,
+                                        ColorMode::Position,
+                                        "Position (TPI)",
+                                    )
+                                    .on_hover_text(
+                                        "Height above the neighbors' mean: red crests, blue hollows",
+                                    );
+                                    ui.selectable_value(
+                                        color_mode,
+                                        ColorMode::Roughness,
+                                        "Roughness",
+                                    )
+                                    .on_hover_text("Height range around each sample");
+                                    ui.selectable_value(
+                                        color_mode,
+                                        ColorMode::SlopeBands,
+                                        "Slope Bands",
+                                    )
+                                    .on_hover_text("Slope angle bands, e.g. avalanche terrain");
+                                    for (index, channel) in info.channels.iter().enumerate() {
+                                        ui.selectable_value(
+                                            color_mode,
+                                            ColorMode::Channel(index),
+                                            &channel.name,
+                                        )
+                                        .on_hover_text("Values loaded with --channel or computed");
+                                    }
+                                });
+                        });
+                        if ui
+                            .button("Avalanche Preset")
+                            .on_hover_text("Standard slope-angle bands over hillshade")
+                            .clicked()
+                        {
+                            *render_mode = RenderMode::Solid;
+                            *color_mode = ColorMode::SlopeBands;
+                            *slope_bands = SlopeBands::default();
+                            sun.irradiance = false;
+                        }
+                        if *color_mode == ColorMode::SlopeBands {
+                            for band in &mut slope_bands.bands {
+                                ui.horizontal(|ui| {
+                                    color_edit(ui, &mut band.color);
+                                    ui.add(
+                                        egui::DragValue::new(&mut band.min)
+                                            .speed(0.5)
+                                            .suffix("°")
+                                            .range(0.0..=90.0),
+                                    );
+                                    ui.label("to");
+                                    ui.add(
+                                        egui::DragValue::new(&mut band.max)
+                                            .speed(0.5)
+                                            .suffix("°")
+                                            .range(0.0..=90.0),
+                                    );
+                                });
+                            }
+                        }
+                        if matches!(
+                            color_mode,
+                            ColorMode::Ruggedness | ColorMode::Position | ColorMode::Roughness
+                        ) {
+                            ui.horizontal(|ui| {
+                                ui.label("Window:");
+                                ui.add(egui::Slider::new(index_radius, 1..=8).show_value(true))
+                                    .on_hover_text(
+                                        "Half-width in samples; 1 is GDAL's 3x3 window",
+                                    );
+                            });
+                        }
+                        if *color_mode != ColorMode::Height && *strategy == RenderStrategy::Clipmap
+                        {
+                            ui.label("Analysis colors apply to the Mesh strategy only");
+                        }
+
                         ui.horizontal(|ui| {
                             ui.label("Colors:");
                             egui::ComboBox::from_id_salt("color_scheme")
@@ -81,6 +931,7 @@ impl Ui {
                                     ColorScheme::Terrain => "Terrain",
                                     ColorScheme::Heatmap => "Heatmap",
                                     ColorScheme::Monochrome => "Monochrome",
+                                    ColorScheme::Neon => "Neon",
                                     ColorScheme::Custom => "Custom",
                                 })
                                 .show_ui(ui, |ui| {
@@ -99,6 +950,11 @@ impl Ui {
                                         ColorScheme::Monochrome,
                                         "Monochrome",
                                     );
+                                    ui.selectable_value(
+                                        color_scheme,
+                                        ColorScheme::Neon,
+                                        "Neon",
+                                    );
                                     ui.selectable_value(
                                         color_scheme,
                                         ColorScheme::Custom,
@@ -106,6 +962,9 @@ impl Ui {
                                     );
                                 });
                         });
+                        ui.collapsing("Compare Schemes", |ui| {
+                            scheme_previews(ui, color_scheme, gradient, info);
+                        });
 
                         // Custom gradient editor (shown when Custom is selected)
                         if *color_scheme == ColorScheme::Custom {
@@ -123,34 +982,655 @@ impl Ui {
                             });
                         }
 
+                        ui.checkbox(gamma_correct, "Gamma-Correct Colors")
+                            .on_hover_text("Convert sRGB colors to linear before shading");
+
+                        ui.add_enabled_ui(info.can_copy_scene, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Vision:");
+                                egui::ComboBox::from_id_salt("color_vision")
+                                    .selected_text(color_vision.label())
+                                    .show_ui(ui, |ui| {
+                                        for option in ColorVision::ALL {
+                                            ui.selectable_value(
+                                                color_vision,
+                                                option,
+                                                option.label(),
+                                            );
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text(
+                                        "Show the scene as seen with a color vision deficiency",
+                                    )
+                                    .on_disabled_hover_text(
+                                        "The surface can't be copied on this device",
+                                    );
+                            });
+                        });
+
+                        ui.checkbox(&mut sky.enabled, "Sky")
+                            .on_hover_text("Gradient from the horizon up behind the terrain");
+                        if sky.enabled {
+                            ui.horizontal(|ui| {
+                                ui.label("Zenith:");
+                                color_edit(ui, &mut sky.zenith);
+                                ui.label("Horizon:");
+                                color_edit(ui, &mut sky.horizon);
+                            });
+                        } else {
+                            ui.horizontal(|ui| {
+                                ui.label("Background:");
+                                color_edit(ui, background);
+                            });
+                        }
+                        ui.checkbox(&mut fog.enabled, "Fog")
+                            .on_hover_text("Fade distant surfaces into the fog color");
+                        if fog.enabled {
+                            ui.horizontal(|ui| {
+                                ui.label("Density:");
+                                ui.add(
+                                    egui::Slider::new(&mut fog.density, 0.05..=10.0)
+                                        .logarithmic(true),
+                                )
+                                .on_hover_text("Fog thickness per scene radius of distance");
+                                color_edit(ui, &mut fog.color);
+                            });
+                            if sky.enabled && ui.button("Match Horizon").clicked() {
+                                fog.color = sky.horizon;
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Lines:");
+                            ui.add(egui::Slider::new(line_brightness, 0.0..=1.0))
+                                .on_hover_text("Brightness of the wireframe lines");
+                        });
+                        ui.add_enabled_ui(info.can_copy_scene, |ui| {
+                            ui.checkbox(&mut bloom.enabled, "Bloom")
+                                .on_hover_text("Glow around bright parts of the image")
+                                .on_disabled_hover_text(
+                                    "The surface can't be copied on this device",
+                                );
+                        });
+                        if bloom.enabled && info.can_copy_scene {
+                            ui.horizontal(|ui| {
+                                ui.label("Threshold:");
+                                ui.add(egui::Slider::new(&mut bloom.threshold, 0.0..=1.0))
+                                    .on_hover_text("Brightness above which pixels glow");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Intensity:");
+                                ui.add(egui::Slider::new(&mut bloom.intensity, 0.0..=4.0));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Radius:");
+                                ui.add(
+                                    egui::Slider::new(&mut bloom.radius, 1.0..=48.0)
+                                        .suffix(" px"),
+                                );
+                            });
+                        }
+                        if ui
+                            .button("Night Mode")
+                            .on_hover_text("Glowing neon colors and dim lines on black")
+                            .clicked()
+                        {
+                            *render_mode = RenderMode::Both;
+                            *color_mode = ColorMode::Height;
+                            *color_scheme = ColorScheme::Neon;
+                            *file_colors = false;
+                            lighting.ambient = 0.8;
+                            *background = [0.0, 0.0, 0.03];
+                            sky.enabled = false;
+                            fog.enabled = false;
+                            *line_brightness = 0.35;
+                            *bloom = BloomConfig {
+                                enabled: true,
+                                ..BloomConfig::default()
+                            };
+                        }
+
                         ui.horizontal(|ui| {
                             ui.label("Height:");
                             ui.add(
                                 egui::Slider::new(height_scale, 0.1..=10.0)
                                     .logarithmic(true)
                                     .show_value(true),
-                            );
+                            )
+                            .on_hover_text("Vertical exaggeration, applied without rebuilding the mesh");
                         });
+
+                        if info.units.source != Units::Unitless {
+                            ui.horizontal(|ui| {
+                                ui.label("Units:");
+                                egui::ComboBox::from_id_salt("units")
+                                    .selected_text(units.label())
+                                    .show_ui(ui, |ui| {
+                                        for option in Units::ALL {
+                                            ui.selectable_value(units, option, option.label());
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text(format!(
+                                        "Show measurements converted from the terrain's {}",
+                                        info.units.source.label().to_lowercase()
+                                    ));
+                            });
+                        }
                     });
 
                     ui.separator();
 
-                    // Lighting section (only shown for solid/both modes)
-                    if matches!(render_mode, RenderMode::Solid | RenderMode::Both) {
-                        ui.collapsing("Lighting", |ui| {
-                            // Light direction as azimuth/elevation
-                            let mut light_azimuth = lighting
-                                .direction
-                                .z
-                                .atan2(lighting.direction.x)
-                                .to_degrees();
-                            let mut light_elevation = lighting.direction.y.asin().to_degrees();
-
+                    // Edge-preserving denoise section
+                    ui.collapsing("Denoise", |ui| {
+                        ui.checkbox(&mut denoise.enabled, "Smooth Noise")
+                            .on_hover_text("Bilateral filter: smooths noise, keeps cliffs and ridges");
+                        if denoise.enabled {
                             ui.horizontal(|ui| {
-                                ui.label("Azimuth:");
-                                if ui
-                                    .add(
-                                        egui::DragValue::new(&mut light_azimuth)
+                                ui.label("Radius:");
+                                ui.add(egui::Slider::new(&mut denoise.radius, 1..=8).suffix(" samples"));
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Edge Height:");
+                                ui.add(
+                                    egui::DragValue::new(&mut denoise.range)
+                                        .speed(0.05)
+                                        .range(0.01..=f32::MAX),
+                                )
+                                .on_hover_text(
+                                    "Height differences well above this are kept as edges, in terrain units",
+                                );
+                            });
+                            denoise.show_raw = ui
+                                .button("Hold to Show Raw")
+                                .on_hover_text("Compare with the unfiltered heights")
+                                .is_pointer_button_down_on();
+                            if *strategy == RenderStrategy::Clipmap {
+                                ui.label("Denoising applies to the Mesh strategy only");
+                            }
+                        } else {
+                            denoise.show_raw = false;
+                        }
+                    });
+
+                    // Relative relief section
+                    ui.collapsing("Relief", |ui| {
+                        ui.checkbox(&mut relief.enabled, "Relative to Reference")
+                            .on_hover_text("Color by height above a reference surface");
+
+                        if relief.enabled {
+                            ui.horizontal(|ui| {
+                                ui.label("Reference:");
+                                egui::ComboBox::from_id_salt("relief_reference")
+                                    .selected_text(match relief.reference {
+                                        ReferenceSurface::Plane => "Fitted Plane",
+                                        ReferenceSurface::Smoothed => "Smoothed",
+                                        ReferenceSurface::Grid => "Loaded Grid",
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(
+                                            &mut relief.reference,
+                                            ReferenceSurface::Plane,
+                                            "Fitted Plane",
+                                        );
+                                        ui.selectable_value(
+                                            &mut relief.reference,
+                                            ReferenceSurface::Smoothed,
+                                            "Smoothed",
+                                        );
+                                        ui.add_enabled_ui(info.has_reference, |ui| {
+                                            ui.selectable_value(
+                                                &mut relief.reference,
+                                                ReferenceSurface::Grid,
+                                                "Loaded Grid",
+                                            )
+                                            .on_disabled_hover_text("Load a grid with --reference");
+                                        });
+                                    });
+                            });
+
+                            if relief.reference == ReferenceSurface::Smoothed {
+                                ui.horizontal(|ui| {
+                                    ui.label("Radius:");
+                                    ui.add(
+                                        egui::Slider::new(&mut relief.smoothing_radius, 1..=64)
+                                            .logarithmic(true)
+                                            .show_value(true),
+                                    )
+                                    .on_hover_text("Smoothing window half-width in samples");
+                                });
+                            }
+
+                            ui.checkbox(&mut relief.flatten, "Flatten")
+                                .on_hover_text("Also subtract the reference from the shape");
+
+                            if *strategy == RenderStrategy::Clipmap {
+                                ui.label("Relief applies to the Mesh strategy only");
+                            }
+                        }
+                    });
+
+                    // Vector field section
+                    if info.has_vector_field {
+                        ui.collapsing("Vectors", |ui| {
+                            ui.checkbox(&mut vectors.enabled, "Show Vectors");
+
+                            if vectors.enabled {
+                                ui.horizontal(|ui| {
+                                    ui.label("Style:");
+                                    ui.radio_value(&mut vectors.style, VectorStyle::Arrows, "Arrows");
+                                    ui.radio_value(
+                                        &mut vectors.style,
+                                        VectorStyle::Streamlines,
+                                        "Streamlines",
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Spacing:");
+                                    ui.add(egui::Slider::new(&mut vectors.spacing, 2..=64).logarithmic(true))
+                                        .on_hover_text("Samples between arrows or streamline seeds");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Scale:");
+                                    ui.add(
+                                        egui::Slider::new(&mut vectors.scale, 0.25..=8.0)
+                                            .logarithmic(true),
+                                    )
+                                    .on_hover_text("Length of the longest arrow or of each streamline, in spacings");
+                                });
+                            }
+                        });
+                    }
+
+                    // Point features section
+                    ui.collapsing("Points", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("File:");
+                            ui.text_edit_singleline(&mut self.points_path);
+                        });
+                        let path = self.points_path.trim();
+                        if ui
+                            .add_enabled(!path.is_empty(), egui::Button::new("Load"))
+                            .on_hover_text("Wells, stations or other points from a CSV or GeoJSON file")
+                            .clicked()
+                        {
+                            response.load_points = Some(PathBuf::from(path));
+                        }
+                        let Some(set) = info.points else {
+                            return;
+                        };
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut points.enabled, format!("Show {} points", set.points.len()));
+                            if ui.button("Clear").clicked() {
+                                response.clear_points = true;
+                            }
+                        });
+                        if !points.enabled {
+                            return;
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Size:");
+                            ui.add(egui::Slider::new(&mut points.size, 1.0..=50.0).logarithmic(true))
+                                .on_hover_text("Marker height, in cells");
+                        });
+                        let name = |index: Option<usize>| {
+                            index.map_or("None", |i| set.attributes[i].as_str())
+                        };
+                        ui.horizontal(|ui| {
+                            ui.label("Color by:");
+                            egui::ComboBox::from_id_salt("point_color_by")
+                                .selected_text(name(points.color_by))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut points.color_by, None, "None");
+                                    for (i, attribute) in set.attributes.iter().enumerate() {
+                                        ui.selectable_value(&mut points.color_by, Some(i), attribute);
+                                    }
+                                });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Size by:");
+                            egui::ComboBox::from_id_salt("point_size_by")
+                                .selected_text(name(points.size_by))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut points.size_by, None, "None");
+                                    for (i, attribute) in set.attributes.iter().enumerate() {
+                                        if set.is_numeric(i) {
+                                            ui.selectable_value(&mut points.size_by, Some(i), attribute);
+                                        }
+                                    }
+                                });
+                        });
+                    });
+
+                    // Raster calculator section
+                    ui.collapsing("Raster Calculator", |ui| {
+                        let edit = ui
+                            .add(
+                                egui::TextEdit::singleline(&mut self.algebra)
+                                    .hint_text("change = (height - before).abs()"),
+                            )
+                            .on_hover_text(
+                                "NAME = EXPRESSION over height and the channels, with + - * / ^, \
+                                 abs sqrt exp ln floor ceil round min max clamp",
+                            );
+                        let entered =
+                            edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if ui.button("Evaluate").clicked() || entered {
+                            response.run_algebra = Some(self.algebra.clone());
+                        }
+                        if let Some(error) = info.algebra_error {
+                            ui.colored_label(egui::Color32::LIGHT_RED, error);
+                        }
+                    });
+
+                    // Uncertainty section
+                    if !info.channels.is_empty() {
+                        ui.collapsing("Uncertainty", |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("σ Channel:");
+                                egui::ComboBox::from_id_salt("uncertainty_channel")
+                                    .selected_text(
+                                        uncertainty
+                                            .channel
+                                            .and_then(|index| info.channels.get(index))
+                                            .map_or("None", |channel| channel.name.as_str()),
+                                    )
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut uncertainty.channel, None, "None");
+                                        for (index, channel) in info.channels.iter().enumerate() {
+                                            ui.selectable_value(
+                                                &mut uncertainty.channel,
+                                                Some(index),
+                                                &channel.name,
+                                            );
+                                        }
+                                    });
+                            });
+
+                            if uncertainty.channel.is_some() {
+                                ui.horizontal(|ui| {
+                                    ui.label("Style:");
+                                    egui::ComboBox::from_id_salt("uncertainty_style")
+                                        .selected_text(uncertainty.style.label())
+                                        .show_ui(ui, |ui| {
+                                            for style in UncertaintyStyle::ALL {
+                                                ui.selectable_value(
+                                                    &mut uncertainty.style,
+                                                    style,
+                                                    style.label(),
+                                                );
+                                            }
+                                        });
+                                });
+                                if uncertainty.style == UncertaintyStyle::Whiskers {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Whisker σ:");
+                                        ui.add(egui::Slider::new(&mut uncertainty.sigmas, 0.5..=4.0))
+                                            .on_hover_text("Heights spanned above and below the surface, in σ");
+                                    });
+                                } else {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Strength:");
+                                        ui.add(egui::Slider::new(&mut uncertainty.strength, 0.0..=1.0))
+                                            .on_hover_text("Opacity removed or fraction hatched at the largest σ");
+                                    });
+                                }
+                            }
+                        });
+                    }
+
+                    // Tools section
+                    ui.collapsing("Tools", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Right-Click:");
+                            egui::ComboBox::from_id_salt("tool")
+                                .selected_text(match tool {
+                                    Tool::None => "Nothing",
+                                    Tool::Catchment => "Catchment",
+                                    Tool::LineOfSight => "Line of Sight",
+                                    Tool::Profile => "Profile",
+                                    Tool::Polyline => "Polyline",
+                                    Tool::Polygon => "Polygon",
+                                    Tool::Note => "Note",
+                                    Tool::Inspect => "Inspect",
+                                    Tool::Paint => "Paint",
+                                    Tool::SelectRectangle => "Select Rectangle",
+                                    Tool::SelectLasso => "Select Lasso",
+                                    Tool::AlignImage => "Align Image",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(tool, Tool::None, "Nothing");
+                                    ui.selectable_value(tool, Tool::Catchment, "Catchment")
+                                        .on_hover_text("Area draining to the clicked point");
+                                    ui.selectable_value(tool, Tool::LineOfSight, "Line of Sight")
+                                        .on_hover_text("Click an observer, then a target");
+                                    ui.selectable_value(tool, Tool::Profile, "Profile")
+                                        .on_hover_text("Click both ends of a cut to plot its heights");
+                                    ui.selectable_value(tool, Tool::Polyline, "Polyline")
+                                        .on_hover_text("Click or drag points of an open line");
+                                    ui.selectable_value(tool, Tool::Polygon, "Polygon")
+                                        .on_hover_text("Click or drag points of a closed shape");
+                                    ui.selectable_value(tool, Tool::Note, "Note")
+                                        .on_hover_text("Pin the note text to the clicked point");
+                                    ui.selectable_value(tool, Tool::Inspect, "Inspect")
+                                        .on_hover_text("Grid position and height of the clicked point");
+                                    ui.selectable_value(tool, Tool::Paint, "Paint")
+                                        .on_hover_text("Click or drag to color the terrain's points");
+                                    ui.selectable_value(tool, Tool::SelectRectangle, "Select Rectangle")
+                                        .on_hover_text("Drag between corners to select samples");
+                                    ui.selectable_value(tool, Tool::SelectLasso, "Select Lasso")
+                                        .on_hover_text("Drag around the samples to select");
+                                    if info.has_drape {
+                                        ui.selectable_value(tool, Tool::AlignImage, "Align Image")
+                                            .on_hover_text("Match terrain features with the draped image");
+                                    }
+                                });
+                        });
+
+                        if *tool == Tool::Paint {
+                            ui.horizontal(|ui| {
+                                ui.label("Color:");
+                                ui.color_edit_button_rgb(&mut brush.color);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Radius:");
+                                ui.add(egui::Slider::new(&mut brush.radius, 0.0..=50.0))
+                                    .on_hover_text("Brush radius in cells");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Output:");
+                                ui.text_edit_singleline(&mut self.fdf_path);
+                            });
+                            if ui
+                                .button("Save .fdf")
+                                .on_hover_text("Heights with the painted colors")
+                                .clicked()
+                            {
+                                response.save_fdf = Some(PathBuf::from(&self.fdf_path));
+                            }
+                        }
+
+                        if *tool == Tool::Note {
+                            ui.horizontal(|ui| {
+                                ui.label("Text:");
+                                ui.text_edit_singleline(&mut self.note_text);
+                            });
+                        }
+
+                        if let Some(sketch) = info.sketch {
+                            let points = sketch.outline().len();
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Drawing: {points} points"));
+                                if ui.button("Finish").clicked() {
+                                    response.finish_annotation = true;
+                                }
+                            });
+                        }
+
+                        if !info.annotations.is_empty() {
+                            ui.separator();
+                            ui.label(format!("Annotations: {}", info.annotations.len()));
+                            ui.horizontal(|ui| {
+                                ui.label("Output:");
+                                ui.text_edit_singleline(&mut self.annotations_path);
+                            });
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .button("Export Annotations")
+                                    .on_hover_text("Shapes and notes as GeoJSON")
+                                    .clicked()
+                                {
+                                    response.export_annotations =
+                                        Some(PathBuf::from(&self.annotations_path));
+                                }
+                                if ui.button("Clear Annotations").clicked() {
+                                    response.clear_annotations = true;
+                                }
+                            });
+                        }
+
+                        if *tool == Tool::LineOfSight {
+                            ui.horizontal(|ui| {
+                                ui.label("Observer:");
+                                ui.add(
+                                    egui::DragValue::new(&mut sight.observer_height)
+                                        .speed(0.1)
+                                        .range(0.0..=f32::MAX),
+                                )
+                                .on_hover_text("Antenna height above the surface");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Target:");
+                                ui.add(
+                                    egui::DragValue::new(&mut sight.target_height)
+                                        .speed(0.1)
+                                        .range(0.0..=f32::MAX),
+                                )
+                                .on_hover_text("Antenna height above the surface");
+                            });
+                            if info.sight_start.is_some() {
+                                ui.label("Right-click the target");
+                            }
+                        }
+
+                        if *tool == Tool::Profile && info.profile_start.is_some() {
+                            ui.label("Right-click the end of the cut");
+                        }
+
+                        if let Some(los) = info.line_of_sight {
+                            ui.separator();
+                            let length = |l| info.units.length(l);
+                            let distance = (los.target - los.observer).length();
+                            ui.label(format!("Distance: {}", length(distance)));
+                            if los.is_visible() {
+                                ui.label("Intervisible");
+                            } else if let Some(obstruction) = los.obstruction {
+                                let blocked = (obstruction - los.observer).length();
+                                ui.label(format!("Blocked at {}", length(blocked)));
+                            }
+                            ui.label(format!("Required Observer: {}", length(los.required_height)))
+                                .on_hover_text("Lowest observer antenna clearing the terrain");
+                        }
+
+                        if let Some(pick) = info.inspected {
+                            ui.separator();
+                            let (column, row) = pick.sample;
+                            ui.label(format!("Sample: ({column}, {row})")).on_hover_text(
+                                format!("Grid position ({:.2}, {:.2})", pick.grid.x, pick.grid.y),
+                            );
+                            ui.label(format!("Height: {}", info.units.length(pick.height)))
+                                .on_hover_text("Stored height of the nearest sample");
+                            let p = pick.position;
+                            let value = |v| info.units.value(v);
+                            ui.label(format!(
+                                "World: ({:.4}, {:.4}, {:.4})",
+                                value(p.x),
+                                value(p.y),
+                                value(p.z)
+                            ))
+                            .on_hover_text(format!(
+                                "Picked point on the unscaled surface{}",
+                                unit_suffix(info.units)
+                            ));
+                        }
+
+                        if let Some(selection) = info.selection {
+                            ui.separator();
+                            ui.label(format!("Selection: {} samples", selection.cells.len()));
+                            if let Some(stats) = &selection.stats {
+                                let length = |l| info.units.length(l);
+                                ui.label(format!("Area: {}", info.units.area(stats.area)));
+                                ui.label(format!(
+                                    "Height: {} to {}",
+                                    length(stats.min),
+                                    length(stats.max)
+                                ));
+                                ui.label(format!("Mean: {}", length(stats.mean)));
+                                ui.label(format!("Std Dev: {}", length(stats.std_dev)));
+                                if ui
+                                    .button("Copy as CSV")
+                                    .on_hover_text("Column, row, x, z and height of each sample")
+                                    .clicked()
+                                {
+                                    ui.ctx().copy_text(selection.to_csv());
+                                }
+                                if ui
+                                    .button("Crop to Selection")
+                                    .on_hover_text("Keep only the rows and columns the selection spans")
+                                    .clicked()
+                                {
+                                    response.crop = selection.extent();
+                                }
+                            }
+                        }
+
+                        if let Some(catchment) = info.catchment {
+                            ui.separator();
+                            ui.label(format!("Catchment: {}", info.units.area(catchment.area)));
+                            ui.label(format!("Samples: {}", catchment.samples.len()));
+                            ui.horizontal(|ui| {
+                                ui.label("Output:");
+                                ui.text_edit_singleline(&mut self.catchment_path);
+                            });
+                            if ui
+                                .button("Export Catchment")
+                                .on_hover_text("Outline and area as GeoJSON")
+                                .clicked()
+                            {
+                                response.export_catchment =
+                                    Some(PathBuf::from(&self.catchment_path));
+                            }
+                        }
+
+                        let has_results = info.catchment.is_some()
+                            || info.sight_start.is_some()
+                            || info.line_of_sight.is_some()
+                            || info.profile_start.is_some()
+                            || info.profile.is_some()
+                            || info.inspected.is_some();
+                        if has_results && ui.button("Clear").clicked() {
+                            response.clear_tools = true;
+                        }
+                    });
+
+                    ui.separator();
+
+                    // Lighting section (only shown for solid/both modes)
+                    if matches!(render_mode, RenderMode::Solid | RenderMode::Both) {
+                        ui.collapsing("Lighting", |ui| {
+                            // Light direction as azimuth/elevation
+                            let mut light_azimuth = lighting
+                                .direction
+                                .z
+                                .atan2(lighting.direction.x)
+                                .to_degrees();
+                            let mut light_elevation = lighting.direction.y.asin().to_degrees();
+
+                            ui.horizontal(|ui| {
+                                ui.label("Azimuth:");
+                                if ui
+                                    .add(
+                                        egui::DragValue::new(&mut light_azimuth)
                                             .speed(1.0)
                                             .suffix("°"),
                                     )
@@ -191,11 +1671,90 @@ impl Ui {
                                 );
                             });
 
+                            ui.checkbox(shadows, "Shadows");
+
+                            if info.has_environment {
+                                ui.checkbox(&mut environment.enabled, "Environment")
+                                    .on_hover_text(
+                                        "Ambient light and reflections from the environment map",
+                                    );
+                            }
+                            if info.has_environment && environment.enabled {
+                                ui.horizontal(|ui| {
+                                    ui.label("Rotation:");
+                                    ui.add(
+                                        egui::Slider::new(&mut environment.rotation, -180.0..=180.0)
+                                            .suffix("°"),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Intensity:");
+                                    ui.add(
+                                        egui::Slider::new(&mut environment.intensity, 0.05..=8.0)
+                                            .logarithmic(true),
+                                    );
+                                });
+                                ui.checkbox(&mut environment.background, "Show as Background")
+                                    .on_hover_text("Show the environment behind the terrain");
+                            }
+
                             if ui.button("Reset Lighting").clicked() {
                                 *lighting = LightingConfig::default();
                             }
                         });
 
+                        // Sun path section
+                        ui.collapsing("Sun Path", |ui| {
+                            ui.checkbox(&mut sun.enabled, "Follow Sun");
+
+                            ui.horizontal(|ui| {
+                                ui.label("Latitude:");
+                                ui.add(
+                                    egui::DragValue::new(&mut sun.latitude)
+                                        .speed(0.5)
+                                        .suffix("°")
+                                        .range(-90.0..=90.0),
+                                );
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("Day:");
+                                ui.add(
+                                    egui::DragValue::new(&mut sun.day_of_year)
+                                        .speed(1.0)
+                                        .range(1..=365),
+                                );
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("Hour:");
+                                ui.add_enabled(
+                                    !timeline.sun,
+                                    egui::Slider::new(&mut sun.hour, 0.0..=24.0).show_value(true),
+                                )
+                                .on_disabled_hover_text("Set by the timeline");
+                            });
+
+                            ui.checkbox(&mut sun.irradiance, "Irradiance")
+                                .on_hover_text("Color by the day's clear-sky solar energy");
+                            if let Some(range) = info.irradiance_range {
+                                irradiance_legend(ui, range);
+                            }
+                            if sun.irradiance && *strategy == RenderStrategy::Clipmap {
+                                ui.label("Irradiance applies to the Mesh strategy only");
+                            }
+
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                ui.label("Output:");
+                                ui.text_edit_singleline(&mut self.shadow_hours_path);
+                            });
+                            if ui.button("Export Shadow Hours").clicked() {
+                                response.export_shadow_hours =
+                                    Some(PathBuf::from(&self.shadow_hours_path));
+                            }
+                        });
+
                         // Contour section
                         ui.collapsing("Contours", |ui| {
                             ui.checkbox(&mut contour.enabled, "Show Contours");
@@ -220,35 +1779,115 @@ impl Ui {
                             }
                         });
 
+                        // Transparency section
+                        ui.collapsing("Transparency", |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Surface:");
+                                ui.add(
+                                    egui::Slider::new(surface_opacity, 0.05..=1.0).show_value(true),
+                                )
+                                .on_hover_text("Terrain opacity; below 1.0 shows hidden folds");
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("Surface blend:");
+                                blend_mode_combo(ui, "surface_blend", surface_blend);
+                            });
+
+                            ui.checkbox(&mut water.enabled, "Water Plane");
+
+                            if water.enabled {
+                                ui.horizontal(|ui| {
+                                    ui.label("Level:");
+                                    // Over the terrain's heights, where
+                                    // flooding changes anything
+                                    match info.height_range {
+                                        Some((low, high)) if low < high => ui.add(
+                                            egui::Slider::new(
+                                                &mut water.level,
+                                                low as f32..=high as f32,
+                                            )
+                                            .clamping(egui::SliderClamping::Never),
+                                        ),
+                                        _ => ui.add(
+                                            egui::DragValue::new(&mut water.level).speed(1.0),
+                                        ),
+                                    }
+                                    .on_hover_text("Water surface height, as stored in the file");
+                                });
+                                if let Some((share, area)) = info.flooded {
+                                    ui.label(format!(
+                                        "Flooded: {:.1}% ({})",
+                                        share * 100.0,
+                                        info.units.area(area)
+                                    ));
+                                }
+
+                                ui.horizontal(|ui| {
+                                    ui.label("Opacity:");
+                                    ui.add(
+                                        egui::Slider::new(&mut water.opacity, 0.0..=1.0)
+                                            .show_value(true),
+                                    );
+                                });
+
+                                ui.horizontal(|ui| {
+                                    ui.label("Blend:");
+                                    blend_mode_combo(ui, "water_blend", &mut water.blend);
+                                });
+                            }
+                        });
+
+                        ui.separator();
+                    }
+
+                    // Shader parameters, generated from the shaders' @param annotations
+                    if !shader_params.is_empty() {
+                        ui.collapsing("Shader Parameters", |ui| {
+                            for param in shader_params.iter_mut() {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("{}:", param.label));
+                                    ui.add(
+                                        egui::Slider::new(&mut param.value, param.min..=param.max)
+                                            .show_value(true),
+                                    );
+                                });
+                            }
+
+                            if ui.button("Reset Parameters").clicked() {
+                                shader_params.reset();
+                            }
+                        });
+
                         ui.separator();
                     }
 
                     // Camera section
                     ui.collapsing("Camera", |ui| {
-                       ui.horizontal(|ui| {
-                           ui.label("Projection:");
-                           egui::ComboBox::from_id_salt("projection")
-                               .selected_text(match camera.projection {
-                                   Projection::Perspective => "Perspective",
-                                   Projection::Orthographic => "Orthographic",
-                               })
-                               .show_ui(ui, |ui| {
-                                   ui.selectable_value(
-                                       &mut camera.projection,
-                                       Projection::Perspective,
-                                       "Perspective",
-                                   );
-                                   ui.selectable_value(
-                                       &mut camera.projection,
-                                       Projection::Orthographic,
-                                       "Orthographic",
-                                   );
-                               });
-                       });
-
-                       if ui.button("Isometric View").clicked() {
-                           camera.set_isometric();
-                       }
+                        ui.horizontal(|ui| {
+                            ui.label("Projection:");
+                            egui::ComboBox::from_id_salt("projection")
+                                .selected_text(match camera.projection {
+                                    Projection::Perspective => "Perspective",
+                                    Projection::Orthographic => "Orthographic",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut camera.projection,
+                                        Projection::Perspective,
+                                        "Perspective",
+                                    );
+                                    ui.selectable_value(
+                                        &mut camera.projection,
+                                        Projection::Orthographic,
+                                        "Orthographic",
+                                    );
+                                });
+                        });
+
+                        if ui.button("Isometric View").clicked() {
+                            camera.set_isometric();
+                        }
 
                         ui.horizontal(|ui| {
                             ui.label("Distance:");
@@ -286,6 +1925,24 @@ impl Ui {
                             }
                         });
 
+                        ui.checkbox(&mut camera.auto_clip, "Auto Near/Far");
+                        if !camera.auto_clip {
+                            ui.horizontal(|ui| {
+                                ui.label("Near:");
+                                ui.add(
+                                    egui::DragValue::new(&mut camera.near)
+                                        .speed(0.01)
+                                        .range(0.001..=camera.far),
+                                );
+                                ui.label("Far:");
+                                ui.add(
+                                    egui::DragValue::new(&mut camera.far)
+                                        .speed(10.0)
+                                        .range(camera.near..=1.0e7),
+                                );
+                            });
+                        }
+
                         ui.horizontal(|ui| {
                             ui.label("FOV:");
                             ui.add(
@@ -296,32 +1953,1249 @@ impl Ui {
                             );
                         });
 
-                        if ui.button("Reset Camera").clicked() {
-                            response.reset_camera = true;
+                        ui.horizontal(|ui| {
+                            ui.label("Mode:");
+                            ui.selectable_value(&mut camera.mode, CameraMode::Orbit, "Orbit");
+                            ui.selectable_value(&mut camera.mode, CameraMode::Fly, "Fly")
+                                .on_hover_text("Drag to look around, WASD to move, Q/E down/up");
+                        });
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Reset Camera").clicked() {
+                                response.reset_camera = true;
+                            }
+                            if ui
+                                .button("Frame Terrain")
+                                .on_hover_text("Fit the whole terrain in view (F)")
+                                .clicked()
+                            {
+                                response.frame_scene = true;
+                            }
+                        });
+
+                        ui.checkbox(&mut motion.enabled, "Smooth Motion")
+                            .on_hover_text("Ease camera moves instead of jumping");
+                        if motion.enabled {
+                            ui.horizontal(|ui| {
+                                ui.label("Response:");
+                                ui.add(
+                                    egui::Slider::new(&mut motion.response, 0.02..=1.0)
+                                        .logarithmic(true)
+                                        .suffix(" s"),
+                                );
+                            });
+                        }
+
+                        ui.checkbox(&mut self.inspector_visible, "Show Inspector");
+
+                        ui.checkbox(ground_grid, "Ground Grid")
+                            .on_hover_text("Reference grid at height zero");
+                        ui.checkbox(axes_gizmo, "Axes Gizmo")
+                            .on_hover_text("World axes in the corner, turning with the camera");
+                        ui.checkbox(&mut overview.enabled, "Overview Inset")
+                            .on_hover_text("Fixed overview camera with a marker for this one");
+                        if overview.enabled {
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(
+                                    &mut overview.view,
+                                    OverviewView::TopDown,
+                                    "Top-Down",
+                                );
+                                ui.selectable_value(
+                                    &mut overview.view,
+                                    OverviewView::Isometric,
+                                    "Isometric",
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Size:");
+                                ui.add(
+                                    egui::Slider::new(&mut overview.size, 0.1..=0.5)
+                                        .show_value(true),
+                                );
+                            });
+                        }
+                    });
+
+                    // A/B swipe comparison
+                    ui.collapsing("Compare", |ui| {
+                        if ui
+                            .button("Take Snapshot")
+                            .on_hover_text(
+                                "Keep the render mode, lighting, contours and height scale",
+                            )
+                            .clicked()
+                        {
+                            response.take_compare_snapshot = true;
+                        }
+                        ui.add_enabled_ui(info.has_compare_snapshot, |ui| {
+                            ui.checkbox(&mut compare.enabled, "Swipe")
+                                .on_hover_text("Snapshot left of the divider, current settings right");
+                            ui.horizontal(|ui| {
+                                ui.label("Divider:");
+                                ui.add(egui::Slider::new(&mut compare.divider, 0.0..=1.0));
+                            });
+                        });
+                    });
+
+                    // Camera path keyframes
+                    ui.collapsing("Bookmarks", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Name:");
+                            ui.text_edit_singleline(&mut self.bookmark_name);
+                        });
+                        if ui
+                            .button("Save View")
+                            .on_hover_text("Current camera, replacing a bookmark of the same name")
+                            .clicked()
+                        {
+                            let name = self.bookmark_name.trim();
+                            let name = if name.is_empty() {
+                                format!("View {}", info.bookmarks.len() + 1)
+                            } else {
+                                name.to_string()
+                            };
+                            response.save_bookmark = Some(name);
+                        }
+                        for (i, view) in info.bookmarks.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                let label = if i < 9 {
+                                    format!("{}: {}", i + 1, view.name)
+                                } else {
+                                    view.name.clone()
+                                };
+                                if ui.button(label).on_hover_text("Go to this view").clicked() {
+                                    response.show_bookmark = Some(i);
+                                }
+                                let lens = view.lens.map_or(String::new(), |lens| {
+                                    format!(
+                                        ", {:.2}:1 from {:.2} to {:.2}",
+                                        lens.aspect, lens.near, lens.far
+                                    )
+                                });
+                                if ui
+                                    .small_button("Frustum")
+                                    .on_hover_text(format!(
+                                        "Show what this view sees as a line box ({:.0}° field of view{lens})",
+                                        view.pose.fov
+                                    ))
+                                    .clicked()
+                                {
+                                    response.show_bookmark_frustum = Some(i);
+                                }
+                                if ui.small_button("Delete").clicked() {
+                                    response.delete_bookmark = Some(i);
+                                }
+                            });
+                        }
+                    });
+
+                    ui.collapsing("Camera Path", |ui| {
+                        ui.label(format!("Keyframes: {}", info.camera_keyframes));
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Add Keyframe")
+                                .on_hover_text("Current camera at the timeline's time")
+                                .clicked()
+                            {
+                                response.add_camera_keyframe = true;
+                            }
+                            if ui.button("Clear").clicked() {
+                                response.clear_camera_path = true;
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let label = if info.recording.is_some() { "Stop" } else { "Record" };
+                            if ui
+                                .button(label)
+                                .on_hover_text(
+                                    "Keyframes of the live camera every half second, from the timeline's time",
+                                )
+                                .clicked()
+                            {
+                                response.toggle_recording = true;
+                            }
+                            if let Some(time) = info.recording {
+                                ui.label(format!("Recording: {time:.1} s"));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("File:");
+                            ui.text_edit_singleline(&mut self.camera_path_file);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Export").on_hover_text("Keyframes as JSON").clicked() {
+                                response.export_camera_path =
+                                    Some(PathBuf::from(&self.camera_path_file));
+                            }
+                            if ui.button("Import").clicked() {
+                                response.import_camera_path =
+                                    Some(PathBuf::from(&self.camera_path_file));
+                            }
+                        });
+                    });
+
+                    // Turntable rotation and export
+                    ui.collapsing("Turntable", |ui| {
+                        ui.checkbox(&mut auto_rotate.enabled, "Auto-Rotate")
+                            .on_hover_text("Turn the view around its target (T)");
+                        ui.horizontal(|ui| {
+                            ui.label("Speed:");
+                            ui.add(
+                                egui::Slider::new(&mut auto_rotate.speed, -90.0..=90.0)
+                                    .suffix(" °/s"),
+                            );
+                        });
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.label("Frames:");
+                            ui.add(egui::DragValue::new(&mut turntable.frames).range(2..=360));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Size:");
+                            ui.add(
+                                egui::DragValue::new(&mut turntable.width)
+                                    .range(16..=2048)
+                                    .suffix(" px"),
+                            );
+                            ui.label("x");
+                            ui.add(
+                                egui::DragValue::new(&mut turntable.height)
+                                    .range(16..=2048)
+                                    .suffix(" px"),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Duration:");
+                            ui.add(
+                                egui::Slider::new(&mut turntable.duration, 1.0..=20.0)
+                                    .suffix(" s")
+                                    .show_value(true),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Output:");
+                            ui.text_edit_singleline(&mut self.turntable_path);
+                        });
+                        if ui
+                            .button("Export Turntable")
+                            .on_hover_text("Animated .gif or .webp of one full turn")
+                            .clicked()
+                        {
+                            response.export_turntable = Some(PathBuf::from(&self.turntable_path));
+                        }
+                    });
+
+                    // Mesh export
+                    ui.collapsing("Mesh Export", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Output:");
+                            ui.text_edit_singleline(&mut self.mesh_path);
+                        });
+                        ui.horizontal(|ui| {
+                            let mut simplify = self.mesh_triangles.is_some();
+                            ui.checkbox(&mut simplify, "Simplify to")
+                                .on_hover_text(
+                                    "Fewer triangles where the surface is flat, keeping ridges and the outline",
+                                );
+                            let mut triangles = self.mesh_triangles.unwrap_or(DEFAULT_MESH_TRIANGLES);
+                            ui.add_enabled(
+                                simplify,
+                                egui::DragValue::new(&mut triangles)
+                                    .speed(1000.0)
+                                    .range(2..=usize::MAX)
+                                    .suffix(" triangles"),
+                            );
+                            self.mesh_triangles = simplify.then_some(triangles);
+                        });
+                        if ui
+                            .button("Export Mesh")
+                            .on_hover_text("Surface as .glb, .obj or .usdz with its colors, or .stl")
+                            .clicked()
+                        {
+                            response.export_mesh =
+                                Some((PathBuf::from(&self.mesh_path), self.mesh_triangles));
+                        }
+                    });
+
+                    // Color map export
+                    ui.collapsing("Color Map Export", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Output:");
+                            ui.text_edit_singleline(&mut self.color_map_path);
+                        });
+                        if ui
+                            .button("Export Color Map")
+                            .on_hover_text(
+                                "Each sample's color as a georeferenced .tif, or a .png with a world file",
+                            )
+                            .clicked()
+                        {
+                            response.export_color_map = Some(PathBuf::from(&self.color_map_path));
                         }
                     });
 
+                    if ui
+                        .button("Save Screenshot")
+                        .on_hover_text("PNG of the view without the panel (F12)")
+                        .clicked()
+                    {
+                        response.save_screenshot = true;
+                    }
+
                     ui.separator();
 
+                    ui.checkbox(&mut self.analysis_visible, "Show Analysis");
+                    ui.checkbox(&mut self.timeline_visible, "Show Timeline");
+
+                    ui.horizontal(|ui| {
+                        ui.label("UI Scale:");
+                        let mut scale = self.ui_scale_drag.unwrap_or(*ui_scale);
+                        let slider = ui.add(
+                            egui::Slider::new(&mut scale, 0.75..=2.0)
+                                .step_by(0.05)
+                                .custom_formatter(|scale, _| format!("{:.0}%", scale * 100.0))
+                                .custom_parser(|text| {
+                                    let percent = text.trim().trim_end_matches('%');
+                                    percent.trim().parse::<f64>().ok().map(|p| p / 100.0)
+                                }),
+                        );
+                        if slider.dragged() {
+                            self.ui_scale_drag = Some(scale);
+                        } else {
+                            self.ui_scale_drag = None;
+                            *ui_scale = scale;
+                        }
+                    });
+
+                    ui.separator();
+
+                    ui.collapsing("Input Settings", |ui| {
+                        input_settings(ui, input, &mut self.rebinding, info, &mut response);
+                    });
+
                     // Help section
                     ui.collapsing("Controls", |ui| {
                         ui.label("Left Drag: Rotate");
                         ui.label("Scroll: Zoom");
                         ui.label("Shift+Drag: Pan");
                         ui.label("Middle Drag: Pan");
-                        ui.label("P: Toggle Projection");
-                        ui.label("I: Isometric View");
-                        ui.label("R: Reset Camera");
+                        ui.label("Right Click: Use Tool");
+                        ui.label("Right Drag: Draw Freehand");
+                        for action in Action::ALL {
+                            ui.label(format!("{}: {}", input.keys.label(action), action.label()));
+                        }
+                        ui.label("1-9: Bookmark or Keyframe");
                         ui.label("Tab: Toggle Panel");
                         ui.label("ESC: Quit");
                     });
                 });
         }
 
+        if self.timeline_visible {
+            timeline_bar(ctx, timeline, camera);
+        }
+
+        // In the corner left free by the panels
+        if *axes_gizmo {
+            axes_gizmo_overlay(ctx, camera);
+        }
+
+        // Ranges of the old grid mean nothing on the new one
+        if response.crop.is_some() || response.process.is_some() || response.restore_original {
+            self.crop_columns = [0, usize::MAX];
+            self.crop_rows = [0, usize::MAX];
+        }
+
         response
     }
 }
 
+/// Bar along the bottom of the window with the shared animation clock:
+/// play/pause, scrubbing, speed, looping and the tracks it drives.
+fn timeline_bar(ctx: &Context, timeline: &mut TimelineConfig, camera: &mut Camera) {
+    egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            let label = if timeline.playing { "Pause" } else { "Play" };
+            if ui.button(label).clicked() {
+                // Played to the end, start over
+                if !timeline.playing && timeline.time >= timeline.duration {
+                    timeline.time = 0.0;
+                }
+                timeline.playing = !timeline.playing;
+            }
+
+            // Scrubbing moves the tracks as playing would
+            let time = timeline.time;
+            let duration = timeline.duration;
+            ui.add(
+                egui::Slider::new(&mut timeline.time, 0.0..=duration)
+                    .suffix(" s")
+                    .fixed_decimals(1),
+            );
+            if timeline.orbit {
+                camera.azimuth += timeline.orbit_angle(timeline.time - time);
+            }
+
+            ui.label("Speed:");
+            ui.add(
+                egui::DragValue::new(&mut timeline.speed)
+                    .speed(0.05)
+                    .suffix("x")
+                    .range(0.0..=10.0),
+            );
+            ui.label("Length:");
+            ui.add(
+                egui::DragValue::new(&mut timeline.duration)
+                    .speed(1.0)
+                    .suffix(" s")
+                    .range(1.0..=600.0),
+            )
+            .on_hover_text("One day of the sun path, one turn of the orbit");
+            timeline.time = timeline.time.min(timeline.duration);
+            ui.checkbox(&mut timeline.looping, "Loop");
+
+            ui.separator();
+            ui.checkbox(&mut timeline.sun, "Sun Path")
+                .on_hover_text("Time of day, with the sun study on");
+            ui.checkbox(&mut timeline.orbit, "Camera Orbit");
+            ui.checkbox(&mut timeline.path, "Camera Path")
+                .on_hover_text("Follow the camera path's keyframes");
+        });
+    });
+}
+
+/// Vertical line at the swipe divider, with a handle dragging it across
+/// the window.
+fn swipe_divider(ctx: &Context, compare: &mut CompareConfig) {
+    const HANDLE_WIDTH: f32 = 12.0;
+    let window = ctx.viewport_rect();
+    let x = window.left() + window.width() * compare.divider;
+    egui::Area::new(egui::Id::new("swipe_divider"))
+        .fixed_pos(egui::pos2(x - HANDLE_WIDTH / 2.0, window.top()))
+        .show(ctx, |ui| {
+            let size = egui::vec2(HANDLE_WIDTH, window.height());
+            let (rect, handle) = ui.allocate_exact_size(size, egui::Sense::drag());
+            if handle.dragged() {
+                let x = x + handle.drag_delta().x;
+                compare.divider = ((x - window.left()) / window.width()).clamp(0.0, 1.0);
+            }
+            let color = if handle.hovered() || handle.dragged() {
+                egui::Color32::WHITE
+            } else {
+                egui::Color32::from_white_alpha(180)
+            };
+            let painter = ui.painter();
+            painter.vline(
+                rect.center().x,
+                rect.y_range(),
+                egui::Stroke::new(2.0, color),
+            );
+            painter.circle_filled(rect.center(), HANDLE_WIDTH / 2.0, color);
+            handle.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+        });
+}
+
+/// Colors of the X, Y and Z axes in the gizmo.
+const AXIS_COLORS: [egui::Color32; 3] = [
+    egui::Color32::from_rgb(230, 80, 80),
+    egui::Color32::from_rgb(100, 200, 90),
+    egui::Color32::from_rgb(80, 130, 240),
+];
+
+/// World X, Y and Z axes as seen by the camera, in the bottom-left corner
+/// of the area left by the panels, behind them.
+fn axes_gizmo_overlay(ctx: &Context, camera: &Camera) {
+    const RADIUS: f32 = 30.0;
+    const MARGIN: f32 = 14.0;
+    let area = ctx.available_rect();
+    let center = egui::pos2(
+        area.left() + MARGIN + RADIUS,
+        area.bottom() - MARGIN - RADIUS,
+    );
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    painter.circle_filled(center, RADIUS + 9.0, egui::Color32::from_black_alpha(90));
+
+    // View-space directions, farthest first so nearer axes are drawn over them
+    let view = camera.build_view_matrix();
+    let mut axes = [Vec3::X, Vec3::Y, Vec3::Z]
+        .map(|axis| view.transform_vector3(axis))
+        .into_iter()
+        .zip(AXIS_COLORS.into_iter().zip(["X", "Y", "Z"]))
+        .collect::<Vec<_>>();
+    axes.sort_by(|(a, _), (b, _)| a.z.total_cmp(&b.z));
+    for (direction, (color, label)) in axes {
+        let end = center + egui::vec2(direction.x, -direction.y) * RADIUS;
+        painter.line_segment([center, end], egui::Stroke::new(2.0, color));
+        painter.circle_filled(end, 7.0, color);
+        painter.text(
+            end,
+            egui::Align2::CENTER_CENTER,
+            label,
+            egui::FontId::proportional(10.0),
+            egui::Color32::BLACK,
+        );
+    }
+}
+
+/// Note text above each note's pin, behind the panels.
+fn note_labels(ctx: &Context, notes: &[(Vec2, &str)]) {
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let pixels_per_point = ctx.pixels_per_point();
+    for (pin, text) in notes {
+        let anchor = egui::pos2(pin.x, pin.y) / pixels_per_point - egui::vec2(0.0, 2.0);
+        let galley = painter.layout_no_wrap(
+            text.to_string(),
+            egui::FontId::proportional(14.0),
+            egui::Color32::WHITE,
+        );
+        let rect = egui::Align2::CENTER_BOTTOM.anchor_size(anchor, galley.size());
+        painter.rect_filled(rect.expand(3.0), 3.0, egui::Color32::from_black_alpha(160));
+        painter.galley(rect.min, galley, egui::Color32::WHITE);
+    }
+}
+
+/// Attributes of the point marker nearest the pointer, if it's over one.
+fn point_tooltip(ctx: &Context, set: &PointSet, markers: &[(Vec2, usize)]) {
+    const HOVER_RADIUS: f32 = 10.0;
+    if ctx.is_pointer_over_area() {
+        return;
+    }
+    let Some(pointer) = ctx.pointer_hover_pos() else {
+        return;
+    };
+    let pixels_per_point = ctx.pixels_per_point();
+    let nearest = markers
+        .iter()
+        .map(|(top, i)| (egui::pos2(top.x, top.y) / pixels_per_point, *i))
+        .map(|(top, i)| (top.distance(pointer), top, i))
+        .filter(|(distance, ..)| *distance <= HOVER_RADIUS)
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+    let Some((_, top, index)) = nearest else {
+        return;
+    };
+    let point = &set.points[index];
+    egui::Area::new(egui::Id::new("point_tooltip"))
+        .order(egui::Order::Tooltip)
+        .fixed_pos(top + egui::vec2(12.0, 0.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                egui::Grid::new("point_attributes").show(ui, |ui| {
+                    for (name, value) in set.attributes.iter().zip(&point.values) {
+                        ui.label(name);
+                        ui.label(value);
+                        ui.end_row();
+                    }
+                    ui.label("Position");
+                    ui.label(format!("{:.1}, {:.1}", point.position.x, point.position.y));
+                    ui.end_row();
+                });
+            });
+        });
+}
+
+/// Overlay showing the last shader compilation error.
+fn shader_error_overlay(ctx: &Context, error: &str) {
+    egui::Window::new("Shader Error")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(
+                egui::RichText::new(error)
+                    .monospace()
+                    .color(egui::Color32::LIGHT_RED),
+            );
+            ui.label("Fix the shader and save to reload.");
+        });
+}
+
+/// Centered progress of the pipelines built ahead of use at startup.
+fn warmup_splash(ctx: &Context, fraction: f32) {
+    egui::Window::new("Compiling Shaders")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .title_bar(false)
+        .show(ctx, |ui| {
+            ui.label("Compiling Shaders");
+            ui.add(egui::ProgressBar::new(fraction).show_percentage());
+        });
+}
+
+/// Mouse sensitivities, inverted axes and key bindings, saved to the
+/// config file.
+fn input_settings(
+    ui: &mut egui::Ui,
+    input: &mut InputConfig,
+    rebinding: &mut Option<Action>,
+    info: &FrameInfo,
+    response: &mut UiResponse,
+) {
+    let mut changed = false;
+    let sensitivities = [
+        ("Rotate:", &mut input.rotate_sensitivity, 0.0005..=0.05),
+        ("Pan:", &mut input.pan_sensitivity, 0.01..=1.0),
+        ("Zoom:", &mut input.zoom_sensitivity, 0.01..=0.5),
+    ];
+    for (label, value, range) in sensitivities {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            changed |= ui
+                .add(egui::Slider::new(value, range).logarithmic(true))
+                .changed();
+        });
+    }
+    changed |= ui.checkbox(&mut input.invert_x, "Invert X").changed();
+    changed |= ui.checkbox(&mut input.invert_y, "Invert Y").changed();
+    changed |= ui.checkbox(&mut input.invert_zoom, "Invert Zoom").changed();
+
+    // The next key pressed is bound to the action picked
+    if let Some(action) = *rebinding {
+        let pressed = ui.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    physical_key,
+                    pressed: true,
+                    ..
+                } => Some(physical_key.unwrap_or(*key)),
+                _ => None,
+            })
+        });
+        match pressed {
+            Some(egui::Key::Escape) => *rebinding = None,
+            Some(key) => {
+                if let Some(code) = key_from_name(key.name()) {
+                    input.keys.set(action, vec![code]);
+                    changed = true;
+                }
+                *rebinding = None;
+            }
+            None => {}
+        }
+    }
+    egui::Grid::new("key_bindings").show(ui, |ui| {
+        for action in Action::ALL {
+            ui.label(action.label());
+            let text = match *rebinding == Some(action) {
+                true => "Press a key...".to_string(),
+                false => input.keys.label(action),
+            };
+            if ui
+                .button(text)
+                .on_hover_text("Click, then press the key to bind (ESC cancels)")
+                .clicked()
+            {
+                *rebinding = Some(action);
+            }
+            ui.end_row();
+        }
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("Defaults").clicked() {
+            *input = InputConfig::default();
+            *rebinding = None;
+            changed = true;
+        }
+        let save = ui
+            .add_enabled(info.config_file.is_some(), egui::Button::new("Save"))
+            .on_disabled_hover_text("No config file");
+        if let Some(file) = info.config_file {
+            if save.on_hover_text(file.display().to_string()).clicked() {
+                response.save_config = true;
+            }
+        }
+    });
+    response.input_changed |= changed;
+}
+
+/// Offer to restore the edits a crashed session left.
+fn recovery_prompt(ctx: &Context, edits: &Edits, response: &mut UiResponse) {
+    egui::Window::new("Recover Edits")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("The last session didn't close cleanly and left unsaved edits:");
+            ui.label(edits.summary());
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Restore")
+                    .on_hover_text("Add them to the current session")
+                    .clicked()
+                {
+                    response.restore_edits = true;
+                }
+                if ui.button("Discard").clicked() {
+                    response.discard_edits = true;
+                }
+            });
+        });
+}
+
+/// Modal showing why a terrain file failed to load and the offending
+/// line, offering to load it again once fixed.
+fn load_failure_modal(ctx: &Context, failure: &LoadFailure, response: &mut UiResponse) {
+    let name = failure.path.file_name().unwrap_or(failure.path.as_os_str());
+    let modal = egui::Modal::new(egui::Id::new("load_failure")).show(ctx, |ui| {
+        ui.heading(format!("Failed to load {}", name.to_string_lossy()));
+        ui.weak(failure.path.display().to_string());
+        ui.label(egui::RichText::new(&failure.message).color(egui::Color32::LIGHT_RED));
+        if let Some((number, text)) = &failure.line {
+            egui::Grid::new("load_failure_line").show(ui, |ui| {
+                ui.label(format!("Line {number}:"));
+                ui.label(egui::RichText::new(text).monospace());
+                ui.end_row();
+            });
+        }
+        ui.label("Fix the file, then retry.");
+        ui.horizontal(|ui| {
+            if ui
+                .button("Retry")
+                .on_hover_text("Load the file again")
+                .clicked()
+            {
+                response.retry_load = true;
+            }
+            if ui.button("Dismiss").clicked() {
+                response.dismiss_load_failure = true;
+            }
+        });
+    });
+    // ESC or a click outside closes it too
+    if modal.should_close() && !response.retry_load {
+        response.dismiss_load_failure = true;
+    }
+}
+
+/// Corner list of the background jobs, with their progress.
+fn jobs_window(ctx: &Context, jobs: &[JobStatus], response: &mut UiResponse) {
+    egui::Window::new("Jobs")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            for job in jobs {
+                ui.horizontal(|ui| {
+                    let bar = match job.fraction {
+                        Some(fraction) => egui::ProgressBar::new(fraction).show_percentage(),
+                        None => egui::ProgressBar::new(0.0).animate(true),
+                    };
+                    ui.add(bar.desired_width(120.0));
+                    ui.label(&job.label);
+                    if job.cancelled {
+                        ui.weak("Cancelling");
+                    } else if ui.small_button("Cancel").clicked() {
+                        response.cancel_job = Some(job.id);
+                    }
+                });
+            }
+        });
+}
+
+/// Corner notice of a newer release, collapsed to its title until opened.
+fn update_notice(ctx: &Context, release: &Release, response: &mut UiResponse) {
+    egui::Window::new("Update Available")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .default_open(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "lrle {} is available (running {}).",
+                release.version, CURRENT_VERSION
+            ));
+            if !release.notes.trim().is_empty() {
+                ui.collapsing("Release notes", |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| ui.label(release.notes.trim()));
+                });
+            }
+            ui.horizontal(|ui| {
+                if !release.url.is_empty() {
+                    ui.hyperlink_to("Release page", &release.url);
+                }
+                if ui.button("Dismiss").clicked() {
+                    response.dismiss_update = true;
+                }
+            });
+        });
+}
+
+/// Developer window showing camera matrices and frustum geometry.
+fn camera_inspector(
+    ctx: &Context,
+    open: &mut bool,
+    camera: &Camera,
+    info: &FrameInfo,
+    response: &mut UiResponse,
+) {
+    egui::Window::new("Camera Inspector")
+        .open(open)
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            egui::Grid::new("camera_params").show(ui, |ui| {
+                ui.label("Position:");
+                ui.monospace(format_vec3(camera.position()));
+                ui.end_row();
+                ui.label("Target:");
+                ui.monospace(format_vec3(camera.target));
+                ui.end_row();
+                ui.label("Near / Far:");
+                ui.monospace(format!("{:.3} / {:.1}", camera.near, camera.far));
+                ui.end_row();
+                ui.label("Aspect:");
+                ui.monospace(format!("{:.3}", info.aspect));
+                ui.end_row();
+            });
+
+            ui.separator();
+            ui.label("View matrix:");
+            matrix_grid(ui, "view_matrix", &camera.build_view_matrix());
+            ui.label("Projection matrix:");
+            matrix_grid(
+                ui,
+                "projection_matrix",
+                &camera.build_projection_matrix(info.aspect),
+            );
+
+            ui.separator();
+            ui.label("Frustum corners:");
+            frustum_grid(ui, "frustum", &camera.frustum_corners(info.aspect));
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Capture Frustum").clicked() {
+                    response.capture_frustum = true;
+                }
+                if info.captured_frustum.is_some() && ui.button("Clear").clicked() {
+                    response.clear_frustum = true;
+                }
+            });
+            if let Some(corners) = info.captured_frustum {
+                ui.label("Captured frustum:");
+                frustum_grid(ui, "captured_frustum", corners);
+            }
+        });
+}
+
+/// Window with the hypsometric curve and slope histogram of the terrain.
+fn analysis_window(ctx: &Context, open: &mut bool, info: &FrameInfo, response: &mut UiResponse) {
+    egui::Window::new("Analysis")
+        .open(open)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            let Some(stats) = info.terrain_stats else {
+                ui.label("Height statistics and elevation and slope distributions of the terrain.");
+                if info.computing_analysis {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Computing");
+                    });
+                } else if ui.button("Compute").clicked() {
+                    response.compute_analysis = true;
+                }
+                return;
+            };
+
+            ui.strong("Heights");
+            let summary = &stats.heights;
+            egui::Grid::new("height_summary").show(ui, |ui| {
+                let (width, height) = summary.size;
+                ui.label("Grid:");
+                ui.label(format!("{width} x {height} samples"));
+                ui.end_row();
+                ui.label("Cells:");
+                ui.label(summary.cells().to_string());
+                ui.end_row();
+                for (name, value) in [
+                    ("Min:", summary.min),
+                    ("Max:", summary.max),
+                    ("Mean:", summary.mean),
+                    ("Median:", summary.median),
+                ] {
+                    ui.label(name);
+                    ui.label(info.units.length(value));
+                    ui.end_row();
+                }
+            });
+            let histogram = info.height_histogram;
+            let bin = (summary.max - summary.min) / histogram.len().max(1) as f64;
+            bar_plot(ui, histogram, |i, count| {
+                let from = summary.min + i as f64 * bin;
+                format!(
+                    "{} to {}: {} samples",
+                    info.units.length(from),
+                    info.units.length(from + bin),
+                    count
+                )
+            });
+            ui.label(format!("{} equal height ranges", histogram.len()));
+
+            ui.separator();
+
+            ui.strong("Hypsometric Curve");
+            let (low, high) = info.height_range.unwrap_or((0.0, 1.0));
+            line_plot(ui, &stats.hypsometric, |area, height| {
+                let height = low + height * (high - low);
+                format!(
+                    "{:.0}% of area above {}",
+                    area * 100.0,
+                    info.units.length(height)
+                )
+            });
+            ui.label("Area above (x) vs relative height (y)");
+            ui.label(format!(
+                "Hypsometric integral: {:.3}",
+                stats.hypsometric_integral
+            ));
+
+            ui.separator();
+
+            ui.strong("Slope Distribution");
+            bar_plot(ui, &stats.slope_histogram, |i, count| {
+                let from = i as f64 * SLOPE_BIN_DEGREES;
+                format!(
+                    "{}° to {}°: {} samples",
+                    from,
+                    from + SLOPE_BIN_DEGREES,
+                    count
+                )
+            });
+            ui.label(format!("0° to 90° in {}° bins", SLOPE_BIN_DEGREES));
+        });
+}
+
+/// The draped image in `texture` with the control points' image ends,
+/// dragged onto the features they mark on the terrain.
+fn align_window(
+    ctx: &Context,
+    texture: &egui::TextureHandle,
+    alignment: &DrapeAlignment,
+    response: &mut UiResponse,
+) {
+    const MARKER_RADIUS: f32 = 6.0;
+    egui::Window::new("Align Image").show(ctx, |ui| {
+        ui.label("Right-click a feature on the terrain, then drag its marker onto it here");
+        let size = texture.size_vec2();
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        painter.image(texture.id(), rect, uv, egui::Color32::WHITE);
+
+        let color = egui::Color32::from_rgb(77, 153, 255);
+        for (i, control) in alignment.points.iter().enumerate() {
+            let center =
+                rect.min + egui::vec2(control.image.x as f32, control.image.y as f32) * size;
+            let marker =
+                egui::Rect::from_center_size(center, egui::Vec2::splat(3.0 * MARKER_RADIUS));
+            let drag = ui.interact(
+                marker,
+                ui.id().with(("control_point", i)),
+                egui::Sense::drag(),
+            );
+            if let Some(pos) = drag.interact_pointer_pos().filter(|_| drag.dragged()) {
+                let image = (pos - rect.min) / size;
+                let image = DVec2::new(image.x as f64, image.y as f64);
+                response.move_control_point = Some((i, image.clamp(DVec2::ZERO, DVec2::ONE)));
+            }
+            painter.circle_stroke(center, MARKER_RADIUS, egui::Stroke::new(2.0, color));
+            painter.text(
+                center + egui::vec2(MARKER_RADIUS + 2.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+                (i + 1).to_string(),
+                egui::FontId::proportional(12.0),
+                color,
+            );
+        }
+
+        ui.label(match alignment.points.len() {
+            0 => "No control points",
+            1 => "Correction: shift",
+            2 => "Correction: shift, rotation and scale",
+            _ => "Correction: affine",
+        });
+        egui::Grid::new("control_points").show(ui, |ui| {
+            for (i, control) in alignment.points.iter().enumerate() {
+                ui.label((i + 1).to_string());
+                ui.label(format!(
+                    "{:.1}, {:.1}",
+                    control.terrain.x, control.terrain.y
+                ));
+                if ui.small_button("Remove").clicked() {
+                    response.remove_control_point = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if !alignment.points.is_empty() && ui.button("Clear Points").clicked() {
+            response.clear_control_points = true;
+        }
+    });
+}
+
+/// Plot of the heights along a profile cut, with its length and climb.
+/// Closing the window clears the cut.
+fn profile_window(ctx: &Context, profile: &Profile, units: UnitFormat, response: &mut UiResponse) {
+    let mut open = true;
+    egui::Window::new("Profile")
+        .open(&mut open)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            let length = |l| units.length(l);
+            let (low, high) = profile.height_range();
+            let distance = profile.length();
+            line_plot(ui, &profile.normalized(), |x, y| {
+                format!(
+                    "{} at {}",
+                    length(low + y * (high - low)),
+                    length(x * distance)
+                )
+            });
+            ui.label("Distance along the cut (x) vs height (y)");
+
+            let (ascent, descent) = profile.climb();
+            egui::Grid::new("profile_summary").show(ui, |ui| {
+                for (name, value) in [
+                    ("Length:", distance),
+                    ("Lowest:", low),
+                    ("Highest:", high),
+                    ("Ascent:", ascent),
+                    ("Descent:", descent),
+                ] {
+                    ui.label(name);
+                    ui.label(length(value));
+                    ui.end_row();
+                }
+            });
+        });
+    if !open {
+        response.clear_profile = true;
+    }
+}
+
+/// Height of the plots in the analysis and profile windows.
+const PLOT_HEIGHT: f32 = 140.0;
+
+/// Plot points with x and y in 0..=1 as a line, hovered points described
+/// by `label`.
+fn line_plot(ui: &mut egui::Ui, points: &[(f64, f64)], label: impl Fn(f64, f64) -> String) {
+    let size = egui::vec2(ui.available_width(), PLOT_HEIGHT);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+    let to_screen = |(x, y): (f64, f64)| {
+        egui::pos2(
+            rect.left() + x as f32 * rect.width(),
+            rect.bottom() - y as f32 * rect.height(),
+        )
+    };
+    let line: Vec<egui::Pos2> = points.iter().copied().map(to_screen).collect();
+    painter.add(egui::Shape::line(
+        line,
+        egui::Stroke::new(2.0, visuals.selection.bg_fill),
+    ));
+
+    if let Some(pos) = response.hover_pos() {
+        let x = ((pos.x - rect.left()) / rect.width()) as f64;
+        if let Some(&(x, y)) = points
+            .iter()
+            .min_by(|a, b| (a.0 - x).abs().total_cmp(&(b.0 - x).abs()))
+        {
+            response.on_hover_text(label(x, y));
+        }
+    }
+}
+
+/// Plot counts as vertical bars scaled to the largest count, the hovered
+/// bar's index and count described by `label`.
+fn bar_plot(ui: &mut egui::Ui, counts: &[u32], label: impl Fn(usize, u32) -> String) {
+    let size = egui::vec2(ui.available_width(), PLOT_HEIGHT);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+    let max = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let bar_width = rect.width() / counts.len().max(1) as f32;
+    for (i, &count) in counts.iter().enumerate() {
+        let left = rect.left() + i as f32 * bar_width;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(
+                left + 1.0,
+                rect.bottom() - count as f32 / max * rect.height(),
+            ),
+            egui::pos2(left + bar_width - 1.0, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, visuals.selection.bg_fill);
+    }
+
+    if let Some(pos) = response.hover_pos() {
+        let i = ((pos.x - rect.left()) / bar_width) as usize;
+        if let Some(&count) = counts.get(i) {
+            response.on_hover_text(label(i, count));
+        }
+    }
+}
+
+/// Height of the irradiance legend's color bar.
+const LEGEND_HEIGHT: f32 = 12.0;
+
+/// Heatmap color bar from the lowest to the highest daily irradiation.
+fn irradiance_legend(ui: &mut egui::Ui, (min, max): (f64, f64)) {
+    let size = egui::vec2(ui.available_width(), LEGEND_HEIGHT);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+
+    const STEPS: usize = 32;
+    let step_width = rect.width() / STEPS as f32;
+    for i in 0..STEPS {
+        let [r, g, b] = height_to_color((i as f32 + 0.5) / STEPS as f32, ColorScheme::Heatmap)
+            .map(|c| (c * 255.0) as u8);
+        let left = rect.left() + i as f32 * step_width;
+        let step = egui::Rect::from_min_max(
+            egui::pos2(left, rect.top()),
+            egui::pos2(left + step_width + 0.5, rect.bottom()),
+        );
+        painter.rect_filled(step, 0.0, egui::Color32::from_rgb(r, g, b));
+    }
+
+    if let Some(pos) = response.hover_pos() {
+        let t = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64;
+        response.on_hover_text(format!("{:.2} kWh/m²", min + t * (max - min)));
+    }
+    ui.horizontal(|ui| {
+        ui.label(format!("{:.2}", min));
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(format!("{:.2} kWh/m² per day", max));
+        });
+    });
+}
+
+/// Height of each strip in the color scheme comparison.
+const PREVIEW_HEIGHT: f32 = 20.0;
+
+/// One strip per color scheme with the terrain's height histogram drawn in
+/// its colors, so it shows which colors most of the terrain would get.
+/// Hovering shows the heights of a column; clicking a strip picks its
+/// scheme.
+fn scheme_previews(
+    ui: &mut egui::Ui,
+    scheme: &mut ColorScheme,
+    gradient: &GradientConfig,
+    info: &FrameInfo,
+) {
+    let histogram = info.height_histogram;
+    const SCHEMES: [(ColorScheme, &str); 5] = [
+        (ColorScheme::Terrain, "Terrain"),
+        (ColorScheme::Heatmap, "Heatmap"),
+        (ColorScheme::Monochrome, "Monochrome"),
+        (ColorScheme::Neon, "Neon"),
+        (ColorScheme::Custom, "Custom"),
+    ];
+    // Without a terrain, the plain gradients
+    let bins = histogram.len().max(1);
+    let max = histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+
+    for (candidate, name) in SCHEMES {
+        ui.small(name);
+        let size = egui::vec2(ui.available_width(), PREVIEW_HEIGHT);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let bin_width = rect.width() / bins as f32;
+        for i in 0..bins {
+            let t = (i as f32 + 0.5) / bins as f32;
+            let rgb = match candidate {
+                ColorScheme::Custom => height_to_color_custom(t, gradient),
+                scheme => height_to_color(t, scheme),
+            };
+            let [r, g, b] = rgb.map(|c| (c * 255.0) as u8);
+            let color = egui::Color32::from_rgb(r, g, b);
+            let share = histogram.get(i).map_or(1.0, |&count| count as f32 / max);
+
+            // The whole palette faintly, the samples in each range in full
+            let left = rect.left() + i as f32 * bin_width;
+            let right = left + bin_width + 0.5;
+            let column = egui::Rect::from_min_max(
+                egui::pos2(left, rect.top()),
+                egui::pos2(right, rect.bottom()),
+            );
+            painter.rect_filled(column, 0.0, color.gamma_multiply(0.25));
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(left, rect.bottom() - share * rect.height()),
+                egui::pos2(right, rect.bottom()),
+            );
+            painter.rect_filled(bar, 0.0, color);
+        }
+
+        if *scheme == candidate {
+            painter.rect_stroke(
+                rect,
+                2.0,
+                ui.visuals().selection.stroke,
+                egui::StrokeKind::Outside,
+            );
+        }
+        if let (Some(pos), Some((low, high))) = (response.hover_pos(), info.height_range) {
+            let i = (((pos.x - rect.left()) / bin_width) as usize).min(bins - 1);
+            let step = (high - low) / bins as f64;
+            let from = low + i as f64 * step;
+            response.show_tooltip_text(format!(
+                "{} to {}: {} samples",
+                info.units.length(from),
+                info.units.length(from + step),
+                histogram.get(i).copied().unwrap_or(0)
+            ));
+        }
+        if response.clicked() {
+            *scheme = candidate;
+        }
+    }
+}
+
+/// ", in" the units values are shown in, or nothing when unitless.
+fn unit_suffix(units: UnitFormat) -> String {
+    match units.shown().symbol() {
+        "" => String::new(),
+        symbol => format!(", in {symbol}"),
+    }
+}
+
+/// Display a matrix in row-major order.
+fn matrix_grid(ui: &mut egui::Ui, id: &str, matrix: &Mat4) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for row in 0..4 {
+            for value in matrix.row(row).to_array() {
+                ui.monospace(format!("{:>9.3}", value));
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// Display the near and far plane corners of a frustum.
+fn frustum_grid(ui: &mut egui::Ui, id: &str, corners: &[Vec3; 8]) {
+    const NAMES: [&str; 4] = ["bottom-left", "bottom-right", "top-right", "top-left"];
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for (i, corner) in corners.iter().enumerate() {
+            let plane = if i < 4 { "near" } else { "far" };
+            ui.label(format!("{} {}", plane, NAMES[i % 4]));
+            ui.monospace(format_vec3(*corner));
+            ui.end_row();
+        }
+    });
+}
+
+fn format_vec3(v: Vec3) -> String {
+    format!("({:.2}, {:.2}, {:.2})", v.x, v.y, v.z)
+}
+
 fn update_light_direction(lighting: &mut LightingConfig, azimuth_deg: f32, elevation_deg: f32) {
     let azimuth = azimuth_deg.to_radians();
     let elevation = elevation_deg.to_radians();
@@ -347,6 +3221,17 @@ fn color_edit(ui: &mut egui::Ui, color: &mut [f32; 3]) {
     }
 }
 
+/// Drop-down selecting a translucent layer's blend mode
+fn blend_mode_combo(ui: &mut egui::Ui, id: &str, mode: &mut BlendMode) {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(mode.label())
+        .show_ui(ui, |ui| {
+            for option in BlendMode::ALL {
+                ui.selectable_value(mode, option, option.label());
+            }
+        });
+}
+
 impl Default for Ui {
     fn default() -> Self {
         Self::new()
@@ -357,4 +3242,101 @@ impl Default for Ui {
 #[derive(Default)]
 pub struct UiResponse {
     pub reset_camera: bool,
+    /// Fit the whole terrain in view
+    pub frame_scene: bool,
+    /// Hide the new release notice
+    pub dismiss_update: bool,
+    /// Input settings were edited
+    pub input_changed: bool,
+    /// Write the input settings to their file
+    pub save_config: bool,
+    /// Restore the edits of a crashed session
+    pub restore_edits: bool,
+    /// Drop the edits of a crashed session
+    pub discard_edits: bool,
+    /// Load the file that failed to load again
+    pub retry_load: bool,
+    /// Close the load failure without loading again
+    pub dismiss_load_failure: bool,
+    /// Freeze the current camera frustum for display
+    pub capture_frustum: bool,
+    /// Remove the captured frustum
+    pub clear_frustum: bool,
+    /// Stop this background job
+    pub cancel_job: Option<JobId>,
+    /// Compute the terrain's elevation and slope distributions
+    pub compute_analysis: bool,
+    /// Write the sun study's shadow hours to this path
+    pub export_shadow_hours: Option<PathBuf>,
+    /// Render a turntable animation to this path
+    pub export_turntable: Option<PathBuf>,
+    /// Save a screenshot of the view
+    pub save_screenshot: bool,
+    /// Write the terrain mesh to this path, in the format of its extension,
+    /// simplified to at most a number of triangles
+    pub export_mesh: Option<(PathBuf, Option<usize>)>,
+    /// Write the terrain's colors as a georeferenced image to this path
+    pub export_color_map: Option<PathBuf>,
+    /// Load the terrain at this path as a layer
+    pub open_terrain: Option<PathBuf>,
+    /// Load breaklines for the TIN strategy from this path
+    pub load_breaklines: Option<PathBuf>,
+    /// Drop the loaded breaklines
+    pub clear_breaklines: bool,
+    /// Load point features from this path
+    pub load_points: Option<PathBuf>,
+    /// Remove the loaded point features
+    pub clear_points: bool,
+    /// Swap this layer with the main terrain
+    pub make_main_terrain: Option<usize>,
+    /// Remove this layer
+    pub remove_terrain: Option<usize>,
+    /// Crop the terrain to these grid columns and rows
+    pub crop: Option<(Range<usize>, Range<usize>)>,
+    /// Smooth or downsample the terrain's grid
+    pub process: Option<Processing>,
+    /// Go back to the terrain as loaded, undoing crops and processing
+    pub restore_original: bool,
+    /// Keep the current settings for the swipe comparison
+    pub take_compare_snapshot: bool,
+    /// Add the current camera to the camera path
+    pub add_camera_keyframe: bool,
+    /// Remove all camera path keyframes
+    pub clear_camera_path: bool,
+    /// Start or stop recording the live camera into the camera path
+    pub toggle_recording: bool,
+    /// Keep the current camera as a bookmark of this name
+    pub save_bookmark: Option<String>,
+    /// Move the camera to this bookmark
+    pub show_bookmark: Option<usize>,
+    /// Show the frustum of this bookmark
+    pub show_bookmark_frustum: Option<usize>,
+    /// Remove this bookmark
+    pub delete_bookmark: Option<usize>,
+    /// Write the camera path as JSON to this path
+    pub export_camera_path: Option<PathBuf>,
+    /// Replace the camera path with one read from this JSON file
+    pub import_camera_path: Option<PathBuf>,
+    /// Remove the results of all tools
+    pub clear_tools: bool,
+    /// Remove the profile cut
+    pub clear_profile: bool,
+    /// Write the last catchment as GeoJSON to this path
+    pub export_catchment: Option<PathBuf>,
+    /// Add the shape being drawn to the annotations
+    pub finish_annotation: bool,
+    /// Remove all annotations
+    pub clear_annotations: bool,
+    /// Write the annotations as GeoJSON to this path
+    pub export_annotations: Option<PathBuf>,
+    /// Write the terrain with its painted colors as .fdf to this path
+    pub save_fdf: Option<PathBuf>,
+    /// Evaluate this map algebra statement into a channel
+    pub run_algebra: Option<String>,
+    /// Move the image end of this control point to this image position
+    pub move_control_point: Option<(usize, DVec2)>,
+    /// Remove this image alignment control point
+    pub remove_control_point: Option<usize>,
+    /// Remove all image alignment control points
+    pub clear_control_points: bool,
 }
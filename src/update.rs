@@ -0,0 +1,263 @@
+//! Opt-in check for newer releases.
+//!
+//! With `--check-updates`, a background thread asks GitHub's releases API
+//! for the project's releases once at startup, and the viewer shows a small
+//! notice with the release notes when one is newer than the running build.
+//! The stable channel only considers full releases; the prerelease channel
+//! also offers release candidates and betas. Nothing is sent besides the
+//! request itself, and a failed check is only logged.
+
+use std::cmp::Ordering;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+
+/// Releases of the project, newest first.
+const RELEASES_URL: &str = "https://api.github.com/repos/yuann3/lrle/releases";
+
+/// How long to wait for GitHub before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Version of the running build.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Releases an update check offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum UpdateChannel {
+    /// Full releases only
+    #[default]
+    Stable,
+    /// Full releases and prereleases
+    Prerelease,
+}
+
+/// A release newer than the running build.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    /// Version, without a leading `v`
+    pub version: String,
+    /// Release notes, as Markdown
+    pub notes: String,
+    /// Release page
+    pub url: String,
+}
+
+/// Semantic version; a prerelease orders before its release, and
+/// prerelease tags compare the semver way (see [`Ord`] below).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Version {
+    numbers: [u64; 3],
+    release: bool,
+    pre: String,
+}
+
+impl Version {
+    /// Parse `1.2.3`, `v1.2` or `1.2.3-rc.1`, ignoring build metadata.
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().trim_start_matches('v');
+        let text = text.split_once('+').map_or(text, |(version, _)| version);
+        let (core, pre) = text.split_once('-').unwrap_or((text, ""));
+        let mut numbers = [0; 3];
+        let mut parts = core.split('.');
+        for number in &mut numbers {
+            if let Some(part) = parts.next() {
+                *number = part.parse().ok()?;
+            }
+        }
+        if parts.next().is_some() || core.is_empty() {
+            return None;
+        }
+        Some(Self {
+            numbers,
+            release: pre.is_empty(),
+            pre: pre.to_string(),
+        })
+    }
+}
+
+impl Ord for Version {
+    /// Compare the numbers, then a prerelease before its release, then the
+    /// prerelease tags identifier by identifier: numeric ones as numbers and
+    /// before alphanumeric ones, which compare as text, and a tag that runs
+    /// out first before the longer one, so `rc.2 < rc.10 < rc.10.1`.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numbers
+            .cmp(&other.numbers)
+            .then(self.release.cmp(&other.release))
+            .then_with(|| compare_prerelease(&self.pre, &other.pre))
+            // Tags equal as numbers, like `01` and `1`, still order
+            .then_with(|| self.pre.cmp(&other.pre))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compare prerelease tags by their dot-separated identifiers.
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        let order = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b),
+            },
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+/// The newest release in a GitHub releases API response that is newer than
+/// `current` and offered on `channel`. Drafts and unparseable tags are
+/// skipped.
+fn newest_release(json: &str, current: &str, channel: UpdateChannel) -> Result<Option<Release>> {
+    let current = Version::parse(current).ok_or_else(|| anyhow!("Invalid version '{current}'"))?;
+    let releases: Value = serde_json::from_str(json)?;
+    let releases = releases
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected an array of releases"))?;
+    let newest = releases
+        .iter()
+        .filter(|release| release["draft"] != true)
+        .filter(|release| channel == UpdateChannel::Prerelease || release["prerelease"] != true)
+        .filter_map(|release| {
+            let tag = release["tag_name"].as_str()?;
+            Some((Version::parse(tag)?, release))
+        })
+        .filter(|(version, _)| *version > current)
+        .max_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(newest.map(|(_, release)| Release {
+        version: release["tag_name"]
+            .as_str()
+            .unwrap_or_default()
+            .trim_start_matches('v')
+            .to_string(),
+        notes: release["body"].as_str().unwrap_or_default().to_string(),
+        url: release["html_url"].as_str().unwrap_or_default().to_string(),
+    }))
+}
+
+/// Ask GitHub for a release newer than the running build.
+fn fetch(channel: UpdateChannel) -> Result<Option<Release>> {
+    let json = attohttpc::get(RELEASES_URL)
+        .header("User-Agent", concat!("lrle/", env!("CARGO_PKG_VERSION")))
+        .header("Accept", "application/vnd.github+json")
+        .timeout(TIMEOUT)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .context("Failed to fetch releases")?;
+    newest_release(&json, CURRENT_VERSION, channel)
+}
+
+/// An update check running in the background.
+pub struct UpdateCheck {
+    receiver: Receiver<Release>,
+    /// Newer release, once found
+    available: Option<Release>,
+}
+
+impl UpdateCheck {
+    /// Start checking for a release newer than the running build.
+    pub fn spawn(channel: UpdateChannel) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || match fetch(channel) {
+            Ok(Some(release)) => {
+                log::info!("lrle {} is available", release.version);
+                let _ = sender.send(release);
+            }
+            Ok(None) => log::info!("lrle {CURRENT_VERSION} is up to date"),
+            Err(err) => log::warn!("Failed to check for updates: {:#}", err),
+        });
+        Self {
+            receiver,
+            available: None,
+        }
+    }
+
+    /// Take the check's result, if it has finished.
+    pub fn poll(&mut self) {
+        if let Ok(release) = self.receiver.try_recv() {
+            self.available = Some(release);
+        }
+    }
+
+    /// Newer release found by the check, once polled.
+    pub fn available(&self) -> Option<&Release> {
+        self.available.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_order_prereleases_first() {
+        let v = |text| Version::parse(text).unwrap();
+        assert!(v("v1.2.0") > v("1.1.9"));
+        assert!(v("0.10.0") > v("0.9.3"));
+        assert!(v("1.2.0-rc.1") < v("1.2.0"));
+        assert!(v("1.2.0-rc.1") > v("1.1.0"));
+        assert_eq!(v("1.2"), v("1.2.0+build.5"));
+        assert_eq!(Version::parse("latest"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+    }
+
+    #[test]
+    fn test_prerelease_identifiers_compare_as_numbers() {
+        let v = |text| Version::parse(text).unwrap();
+        assert!(v("1.2.0-rc.10") > v("1.2.0-rc.2"));
+        assert!(v("1.2.0-rc.10.1") > v("1.2.0-rc.10"));
+        assert!(v("1.2.0-beta.11") < v("1.2.0-rc.1"));
+        assert!(v("1.2.0-alpha") < v("1.2.0-alpha.1"));
+        assert!(v("1.2.0-rc.1") < v("1.2.0-rc.x"));
+        assert!(v("1.2.0-rc.10") < v("1.2.0"));
+    }
+
+    #[test]
+    fn test_newest_release_follows_channel() {
+        let json = r#"[
+            {"tag_name": "v0.4.0-beta.1", "prerelease": true, "draft": false,
+             "body": "Beta", "html_url": "https://example.com/0.4.0-beta.1"},
+            {"tag_name": "v0.5.0", "prerelease": false, "draft": true,
+             "body": "Draft", "html_url": "https://example.com/0.5.0"},
+            {"tag_name": "v0.3.1", "prerelease": false, "draft": false,
+             "body": "Fixes", "html_url": "https://example.com/0.3.1"},
+            {"tag_name": "v0.2.0", "prerelease": false, "draft": false,
+             "body": "Old", "html_url": "https://example.com/0.2.0"}
+        ]"#;
+
+        let stable = newest_release(json, "0.3.0", UpdateChannel::Stable).unwrap();
+        assert_eq!(
+            stable,
+            Some(Release {
+                version: "0.3.1".to_string(),
+                notes: "Fixes".to_string(),
+                url: "https://example.com/0.3.1".to_string(),
+            })
+        );
+        let pre = newest_release(json, "0.3.0", UpdateChannel::Prerelease).unwrap();
+        assert_eq!(pre.unwrap().version, "0.4.0-beta.1");
+
+        // Up to date
+        assert_eq!(
+            newest_release(json, "0.3.1", UpdateChannel::Stable).unwrap(),
+            None
+        );
+        assert!(newest_release("{}", "0.3.0", UpdateChannel::Stable).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! Terrain rendering for embedding in another wgpu application.
+//!
+//! A [`TerrainView`] draws a terrain with the viewer's renderer on the
+//! host's device, into a texture the host provides, e.g. one it then
+//! samples in its own render pass:
+//!
+//! ```no_run
+//! # fn frame(
+//! #     adapter: &wgpu::Adapter,
+//! #     device: wgpu::Device,
+//! #     queue: wgpu::Queue,
+//! #     target: &wgpu::Texture,
+//! # ) -> anyhow::Result<()> {
+//! use lrle::terrain::TerrainData;
+//! use lrle::TerrainView;
+//!
+//! let terrain = TerrainData::new(vec![vec![0.0, 1.0], vec![1.0, 2.0]], None);
+//! let mut view = TerrainView::new(adapter, device, queue, target.format())?;
+//! view.set_terrain(&terrain);
+//! view.camera_mut().azimuth = 45f32.to_radians();
+//! view.render_to(target)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The view has no window and no UI; the host drives the camera and
+//! settings. Everything else the viewer can do is reached through
+//! [`renderer_mut`](TerrainView::renderer_mut).
+
+use crate::renderer::camera::Camera;
+use crate::renderer::{RenderSettings, Renderer};
+use crate::terrain::TerrainData;
+
+/// A terrain drawn into host-provided textures.
+pub struct TerrainView {
+    renderer: Renderer,
+}
+
+impl TerrainView {
+    /// Create a view rendering `format` frames with `device`, which came
+    /// from `adapter`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shaders fail to build.
+    pub fn new(
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        format: wgpu::TextureFormat,
+    ) -> anyhow::Result<Self> {
+        let renderer = Renderer::with_device(adapter, device, queue, format, 1, 1)?;
+        Ok(Self { renderer })
+    }
+
+    /// Show `terrain`, framed by the camera.
+    pub fn set_terrain(&mut self, terrain: &TerrainData) {
+        self.renderer
+            .upload_terrain(terrain, self.renderer.settings.height_scale);
+        self.renderer.frame_scene();
+    }
+
+    /// The camera looking at the terrain.
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.renderer.camera
+    }
+
+    /// Render mode, colors, lighting and the other display settings.
+    pub fn settings_mut(&mut self) -> &mut RenderSettings {
+        &mut self.renderer.settings
+    }
+
+    /// Draw a frame into `texture`, which must come from the view's
+    /// device, have its format and be a render attachment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `texture` has another format.
+    pub fn render_to(&mut self, texture: &wgpu::Texture) -> anyhow::Result<()> {
+        self.renderer.render_to(texture)
+    }
+
+    /// The renderer behind the view.
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    /// The renderer behind the view, for anything not wrapped here.
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A view on a fresh device, or `None` without a GPU adapter.
+    fn view(format: wgpu::TextureFormat) -> Option<(TerrainView, wgpu::Device)> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&Default::default())).ok()?;
+        let view = TerrainView::new(&adapter, device.clone(), queue, format).ok()?;
+        Some((view, device))
+    }
+
+    fn target(device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 48,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    #[test]
+    fn test_renders_into_host_texture() {
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let Some((mut view, device)) = view(format) else {
+            return;
+        };
+        let terrain = TerrainData::new(vec![vec![0.0, 5.0, 0.0]; 3], None);
+        view.set_terrain(&terrain);
+
+        let texture = target(&device, format);
+        view.render_to(&texture).unwrap();
+        assert_eq!(view.renderer().size.width, 64);
+        let pixels = view.renderer().read_pixels().unwrap();
+        assert_eq!(pixels.len(), 64 * 48);
+
+        let other = target(&device, wgpu::TextureFormat::Bgra8UnormSrgb);
+        assert!(view.render_to(&other).is_err());
+    }
+}